@@ -42,19 +42,30 @@ impl RDataParser for RData {
             RecordType::ANY => panic!("parsing ANY doesn't make sense"), // valid panic, never should happen
             RecordType::AXFR => panic!("parsing AXFR doesn't make sense"), // valid panic, never should happen
             RecordType::CAA => caa::parse(tokens).map(RData::CAA)?,
+            RecordType::CERT => RData::CERT(cert::parse(tokens)?),
             RecordType::CNAME => RData::CNAME(name::parse(tokens, origin)?),
+            RecordType::CSYNC => RData::CSYNC(csync::parse(tokens)?),
+            RecordType::DHCID => RData::DHCID(dhcid::parse(tokens)?),
+            RecordType::HINFO => RData::HINFO(hinfo::parse(tokens)?),
+            RecordType::HTTPS => RData::HTTPS(svcb::parse(tokens, origin)?),
             RecordType::IXFR => panic!("parsing IXFR doesn't make sense"), // valid panic, never should happen
+            RecordType::LOC => RData::LOC(loc::parse(tokens)?),
             RecordType::MX => RData::MX(mx::parse(tokens, origin)?),
+            RecordType::NAPTR => RData::NAPTR(naptr::parse(tokens, origin)?),
             RecordType::NULL => RData::NULL(null::parse(tokens)?),
             RecordType::NS => RData::NS(name::parse(tokens, origin)?),
             RecordType::OPENPGPKEY => RData::OPENPGPKEY(openpgpkey::parse(tokens)?),
             RecordType::OPT => panic!("parsing OPT doesn't make sense"), // valid panic, never should happen
             RecordType::PTR => RData::PTR(name::parse(tokens, origin)?),
+            RecordType::RP => RData::RP(rp::parse(tokens, origin)?),
             RecordType::SOA => RData::SOA(soa::parse(tokens, origin)?),
             RecordType::SRV => RData::SRV(srv::parse(tokens, origin)?),
             RecordType::SSHFP => RData::SSHFP(sshfp::parse(tokens)?),
+            RecordType::SVCB => RData::SVCB(svcb::parse(tokens, origin)?),
             RecordType::TLSA => RData::TLSA(tlsa::parse(tokens)?),
             RecordType::TXT => RData::TXT(txt::parse(tokens)?),
+            RecordType::URI => RData::URI(uri::parse(tokens)?),
+            RecordType::ZONEMD => RData::ZONEMD(zonemd::parse(tokens)?),
             RecordType::DNSSEC(DNSSECRecordType::SIG) => panic!("parsing SIG doesn't make sense"), // valid panic, never should happen
             RecordType::DNSSEC(DNSSECRecordType::DNSKEY) => {
                 panic!("DNSKEY should be dynamically generated")
@@ -0,0 +1,69 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! certificate record
+
+use std::str::FromStr;
+
+use error::*;
+use rr::rdata::cert::CertType;
+use rr::rdata::CERT;
+
+/// Parse the RData from a set of Tokens
+///
+/// [RFC 4398, Storing Certificates in the DNS, March 2006](https://tools.ietf.org/html/rfc4398#section-2.2)
+///
+/// ```text
+/// 2.2.  Text Representation of CERT RRs
+///
+///    The RDATA portion of a CERT RR has the type field as an unsigned
+///    decimal integer or as a mnemonic symbol as listed in Section 2.1,
+///    above.
+///
+///    The key tag field is represented as an unsigned decimal integer.
+///
+///    The algorithm field is represented as an unsigned decimal integer or
+///    a mnemonic symbol as listed in [12].
+///
+///    The certificate/CRL portion is represented in base 64 [16] and may be
+///    divided into any number of white-space-separated substrings, any of
+///    which may be empty.
+/// ```
+///
+/// Only decimal integers are supported for the algorithm.
+pub fn parse<'i, I: Iterator<Item = &'i str>>(mut tokens: I) -> ParseResult<CERT> {
+    let mut next = |field: &str| {
+        tokens
+            .next()
+            .ok_or_else(|| ParseError::from(ParseErrorKind::MissingToken(field.to_string())))
+    };
+
+    let cert_type = CertType::from_str(next("type")?)?;
+    let key_tag: u16 = next("key tag")?.parse()?;
+    let algorithm: u8 = next("algorithm")?.parse()?;
+
+    let encoded: String = tokens.collect();
+    let cert_data = ::data_encoding::BASE64.decode(encoded.as_bytes())?;
+
+    Ok(CERT::new(cert_type, key_tag, algorithm, cert_data))
+}
+
+#[test]
+fn test_parsing() {
+    let cert = parse(vec!["PGP", "12345", "8", "mQEN", "BA=="].into_iter()).unwrap();
+    assert_eq!(cert.cert_type(), CertType::PGP);
+    assert_eq!(cert.key_tag(), 12345);
+    assert_eq!(cert.algorithm(), 8);
+    assert_eq!(cert.cert_data(), &[0x99, 0x01, 0x0d, 0x04]);
+
+    let cert = parse(vec!["1", "0", "0", "mQENBA=="].into_iter()).unwrap();
+    assert_eq!(cert.cert_type(), CertType::PKIX);
+
+    assert!(parse(vec!["X509", "0", "0", "mQENBA=="].into_iter()).is_err());
+    assert!(parse(vec!["PGP", "0", "RSASHA256", "mQENBA=="].into_iter()).is_err());
+    assert!(parse(vec!["PGP", "0"].into_iter()).is_err());
+}
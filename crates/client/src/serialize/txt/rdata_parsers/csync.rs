@@ -0,0 +1,81 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! child-to-parent synchronization record
+
+use std::str::FromStr;
+
+use error::*;
+use rr::rdata::CSYNC;
+use rr::RecordType;
+
+/// Parse the RData from a set of Tokens
+///
+/// [RFC 7477, Child-to-Parent Synchronization in DNS, March 2015](https://tools.ietf.org/html/rfc7477#section-2.1.2)
+///
+/// ```text
+/// 2.1.2.  The CSYNC Presentation Format
+///
+///    The CSYNC presentation format is as follows:
+///
+///       The SOA Serial field is represented as an unsigned decimal integer.
+///
+///       The Flags field is represented as an unsigned decimal integer.
+///
+///       The Type Bit Map field is represented as a sequence of RR type
+///       mnemonics.  When the mnemonic is not known, the TYPE
+///       representation as described in [RFC3597], Section 5, MUST be
+///       used.
+///
+/// example.com. 3600 IN CSYNC 66 3 A NS AAAA
+/// ```
+pub fn parse<'i, I: Iterator<Item = &'i str>>(mut tokens: I) -> ParseResult<CSYNC> {
+    let soa_serial: u32 = tokens
+        .next()
+        .ok_or_else(|| ParseError::from(ParseErrorKind::MissingToken("SOA Serial".to_string())))?
+        .parse()?;
+    let flags: u16 = tokens
+        .next()
+        .ok_or_else(|| ParseError::from(ParseErrorKind::MissingToken("Flags".to_string())))?
+        .parse()?;
+
+    let type_bit_maps = tokens
+        .map(|rr_type| {
+            if rr_type.starts_with("TYPE") {
+                Ok(RecordType::from(u16::from_str(&rr_type[4..])?))
+            } else {
+                RecordType::from_str(rr_type).map_err(Into::into)
+            }
+        })
+        .collect::<ParseResult<Vec<_>>>()?;
+
+    Ok(CSYNC::new(
+        soa_serial,
+        flags & 0b01 == 0b01,
+        flags & 0b10 == 0b10,
+        type_bit_maps,
+    ))
+}
+
+#[test]
+fn test_parsing() {
+    let csync = parse(vec!["66", "3", "A", "NS", "TYPE65280"].into_iter()).unwrap();
+    assert_eq!(csync.soa_serial(), 66);
+    assert!(csync.immediate());
+    assert!(csync.soa_minimum());
+    assert_eq!(
+        csync.type_bit_maps(),
+        &[RecordType::A, RecordType::NS, RecordType::Unknown(65280)]
+    );
+
+    let csync = parse(vec!["66", "0"].into_iter()).unwrap();
+    assert!(!csync.immediate());
+    assert!(csync.type_bit_maps().is_empty());
+
+    assert!(parse(vec!["66", "3", "BOGUS"].into_iter()).is_err());
+    assert!(parse(vec!["66"].into_iter()).is_err());
+}
@@ -0,0 +1,44 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! DHCP identifier record
+
+use error::*;
+use rr::rdata::DHCID;
+
+/// Parse the RData from a set of Tokens
+///
+/// [RFC 4701, A DNS Resource Record (RR) for Encoding DHCP Information, October 2006](https://tools.ietf.org/html/rfc4701#section-3.2)
+///
+/// ```text
+/// 3.2.  DHCID Presentation Format
+///
+///    In DNS master files, the RDATA is represented as a single block in
+///    base-64 encoding identical to that used for representing binary data
+///    in [3], Section 3.  The data may be divided up into any number of
+///    white-space-separated substrings, each of which is a valid base-64
+///    encoding, that are concatenated to form the complete RDATA.
+/// ```
+pub fn parse<'i, I: Iterator<Item = &'i str>>(tokens: I) -> ParseResult<DHCID> {
+    let encoded: String = tokens.collect();
+    let digest = ::data_encoding::BASE64.decode(encoded.as_bytes())?;
+
+    if digest.is_empty() {
+        return Err(ParseErrorKind::Message("DHCID digest field is missing").into());
+    }
+    Ok(DHCID::new(digest))
+}
+
+#[test]
+fn test_parsing() {
+    let dhcid =
+        parse(vec!["AAIBY2/AuCccgoJbsaxcQc9TUapptP69", "lOjxfNuVAA2kjEA="].into_iter()).unwrap();
+    assert_eq!(dhcid.digest().len(), 35);
+
+    assert!(parse(::std::iter::empty()).is_err());
+    assert!(parse(vec!["äöü"].into_iter()).is_err());
+}
@@ -0,0 +1,45 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! host information record
+
+use error::*;
+use rr::rdata::HINFO;
+
+/// Parse the RData from a set of Tokens
+///
+/// [RFC 1035, DOMAIN NAMES - IMPLEMENTATION AND SPECIFICATION, November 1987](https://tools.ietf.org/html/rfc1035#section-5.1)
+///
+/// ```text
+/// <owner> <ttl> <class> HINFO <cpu> <os>
+///
+/// A HINFO record has two <character-string>s, which are quoted if they contain spaces.
+/// ```
+pub fn parse<'i, I: Iterator<Item = &'i str>>(mut tokens: I) -> ParseResult<HINFO> {
+    let cpu = tokens
+        .next()
+        .ok_or_else(|| ParseError::from(ParseErrorKind::MissingToken("cpu".to_string())))?;
+    let os = tokens
+        .next()
+        .ok_or_else(|| ParseError::from(ParseErrorKind::MissingToken("os".to_string())))?;
+    if tokens.next().is_some() {
+        return Err(ParseErrorKind::Message("too many fields for HINFO").into());
+    }
+
+    Ok(HINFO::new(cpu.to_string(), os.to_string()))
+}
+
+#[test]
+fn test_parsing() {
+    // the lexer removes the quotes of the strings
+    let hinfo = parse(vec!["VAX-11/780", "UNIX"].into_iter()).unwrap();
+    assert_eq!(hinfo.cpu(), b"VAX-11/780");
+    assert_eq!(hinfo.os(), b"UNIX");
+
+    assert!(parse(vec!["VAX-11/780"].into_iter()).is_err());
+    assert!(parse(vec!["VAX-11/780", "UNIX", "BSD"].into_iter()).is_err());
+}
@@ -0,0 +1,234 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! location record
+
+use std::iter::Peekable;
+
+use error::*;
+use rr::rdata::loc::{self, LOC_EQUATOR, LOC_REFERENCE_ALTITUDE};
+use rr::rdata::LOC;
+
+/// Parse the RData from a set of Tokens
+///
+/// [RFC 1876, Location Information in the DNS, January 1996](https://tools.ietf.org/html/rfc1876#section-3)
+///
+/// ```text
+/// The LOC record is expressed in a master file in the following format:
+///
+/// <owner> <TTL> <class> LOC ( d1 [m1 [s1]] {"N"|"S"} d2 [m2 [s2]]
+///                             {"E"|"W"} alt["m"] [siz["m"] [hp["m"]
+///                             [vp["m"]]]] )
+///
+/// where:
+///
+///    d1:     [0 .. 90]            (degrees latitude)
+///    d2:     [0 .. 180]           (degrees longitude)
+///    m1, m2: [0 .. 59]            (minutes latitude/longitude)
+///    s1, s2: [0 .. 59.999]        (seconds latitude/longitude)
+///    alt:    [-100000.00 .. 42849672.95] BY .01 (altitude in meters)
+///    siz, hp, vp: [0 .. 90000000.00] (size/precision in meters)
+///
+/// If omitted, minutes and seconds default to zero, size defaults to 1m,
+/// horizontal precision defaults to 10000m, and vertical precision
+/// defaults to 10m.
+/// ```
+pub fn parse<'i, I: Iterator<Item = &'i str>>(tokens: I) -> ParseResult<LOC> {
+    let mut tokens = tokens.peekable();
+
+    let latitude = parse_angle(&mut tokens, 90, "N", "S")?;
+    let longitude = parse_angle(&mut tokens, 180, "E", "W")?;
+
+    let altitude = tokens
+        .next()
+        .ok_or_else(|| ParseError::from(ParseErrorKind::MissingToken("altitude".to_string())))
+        .and_then(parse_meters)?
+        + i64::from(LOC_REFERENCE_ALTITUDE);
+    if altitude < 0 || altitude > i64::from(u32::max_value()) {
+        return Err(
+            ParseErrorKind::Msg(format!("altitude of LOC out of range: {}cm", altitude)).into(),
+        );
+    }
+
+    let mut precision = |default_cm: u64| -> ParseResult<u8> {
+        let cm = match tokens.next() {
+            Some(token) => parse_meters(token)?,
+            None => return Ok(loc::precision_from_cm(default_cm)?),
+        };
+        if cm < 0 {
+            return Err(
+                ParseErrorKind::Msg(format!("precision of LOC is negative: {}cm", cm)).into(),
+            );
+        }
+        Ok(loc::precision_from_cm(cm as u64)?)
+    };
+    let size = precision(100)?;
+    let horiz_pre = precision(1_000_000)?;
+    let vert_pre = precision(1000)?;
+
+    if tokens.next().is_some() {
+        return Err(ParseErrorKind::Message("too many fields for LOC").into());
+    }
+
+    Ok(LOC::new(
+        size,
+        horiz_pre,
+        vert_pre,
+        latitude,
+        longitude,
+        altitude as u32,
+    ))
+}
+
+/// Parses `d [m [s]] {positive|negative}` as thousandths of a second of arc from the equator or prime meridian
+fn parse_angle<'i, I: Iterator<Item = &'i str>>(
+    tokens: &mut Peekable<I>,
+    max_degrees: i64,
+    positive: &str,
+    negative: &str,
+) -> ParseResult<u32> {
+    let mut next = |field: &str| {
+        tokens
+            .next()
+            .ok_or_else(|| ParseError::from(ParseErrorKind::MissingToken(field.to_string())))
+    };
+
+    let degrees = parse_decimal(next("degrees")?, 0)?;
+    let mut minutes = 0;
+    let mut seconds = 0;
+    let mut token = next("hemisphere")?;
+    if token != positive && token != negative {
+        minutes = parse_decimal(token, 0)?;
+        token = next("hemisphere")?;
+        if token != positive && token != negative {
+            seconds = parse_decimal(token, 3)?;
+            token = next("hemisphere")?;
+        }
+    }
+
+    if degrees < 0
+        || degrees > max_degrees
+        || minutes < 0
+        || minutes > 59
+        || seconds < 0
+        || seconds > 59_999
+    {
+        return Err(ParseErrorKind::Msg(format!(
+            "angle of LOC out of range: {} {} {}",
+            degrees, minutes, seconds
+        ))
+        .into());
+    }
+
+    let thousandths = (degrees * 60 + minutes) * 60_000 + seconds;
+    if thousandths > max_degrees * 3_600_000 {
+        return Err(
+            ParseErrorKind::Msg(format!("angle of LOC out of range: {}", thousandths)).into(),
+        );
+    }
+
+    let angle = if token == positive {
+        i64::from(LOC_EQUATOR) + thousandths
+    } else if token == negative {
+        i64::from(LOC_EQUATOR) - thousandths
+    } else {
+        return Err(ParseErrorKind::Msg(format!(
+            "expected {} or {}: {}",
+            positive, negative, token
+        ))
+        .into());
+    };
+
+    Ok(angle as u32)
+}
+
+/// Parses meters with an optional `m` suffix as centimeters
+fn parse_meters(meters: &str) -> ParseResult<i64> {
+    let meters = if meters.ends_with('m') {
+        &meters[..meters.len() - 1]
+    } else {
+        meters
+    };
+
+    parse_decimal(meters, 2)
+}
+
+/// Parses the decimal with at most `decimals` digits after the point, as an integer scaled by 10^decimals
+fn parse_decimal(value: &str, decimals: usize) -> ParseResult<i64> {
+    let (negative, digits) = if value.starts_with('-') {
+        (true, &value[1..])
+    } else {
+        (false, value)
+    };
+
+    let (integer, fraction) = match digits.find('.') {
+        Some(idx) => (&digits[..idx], &digits[idx + 1..]),
+        None => (digits, ""),
+    };
+
+    if integer.is_empty()
+        || integer.len() > 9
+        || fraction.len() > decimals
+        || !integer
+            .chars()
+            .chain(fraction.chars())
+            .all(|c| c.is_ascii_digit())
+    {
+        return Err(ParseErrorKind::Msg(format!("invalid decimal in LOC: {}", value)).into());
+    }
+
+    let mut scaled: i64 = integer.parse()?;
+    for i in 0..decimals {
+        let digit = fraction.as_bytes().get(i).map_or(0, |d| d - b'0');
+        scaled = scaled * 10 + i64::from(digit);
+    }
+
+    Ok(if negative { -scaled } else { scaled })
+}
+
+#[test]
+fn test_parsing() {
+    // RFC 1876 section 4
+    let loc = parse("42 21 54 N 71 06 18 W -24m 30m".split_whitespace()).unwrap();
+    assert_eq!(loc.latitude(), LOC_EQUATOR + 152_514_000);
+    assert_eq!(loc.longitude(), LOC_EQUATOR - 255_978_000);
+    assert_eq!(loc.altitude(), LOC_REFERENCE_ALTITUDE - 2400);
+    assert_eq!(loc.size(), 0x33);
+    assert_eq!(loc.horiz_pre(), 0x16);
+    assert_eq!(loc.vert_pre(), 0x13);
+
+    let loc = parse("42 21 43.952 N 71 5 6.344 W -24m 1m 200m".split_whitespace()).unwrap();
+    assert_eq!(loc.latitude(), LOC_EQUATOR + 152_503_952);
+    assert_eq!(loc.horiz_pre(), 0x24);
+
+    let loc = parse("52 N 0.5 W 0.01m 0 0 0".split_whitespace());
+    assert!(loc.is_err());
+
+    let loc = parse("52 N 1 W 10.5 0 0 0".split_whitespace()).unwrap();
+    assert_eq!(loc.longitude(), LOC_EQUATOR - 3_600_000);
+    assert_eq!(loc.altitude(), LOC_REFERENCE_ALTITUDE + 1050);
+    assert_eq!(loc.size(), 0);
+
+    let display = "42 21 54.000 N 71 6 18.000 W -24.00m 30m 10000m 10m";
+    assert_eq!(
+        parse(display.split_whitespace()).unwrap().to_string(),
+        display
+    );
+}
+
+#[test]
+fn test_parsing_invalid() {
+    assert!(parse("91 N 0 E 0m".split_whitespace()).is_err());
+    assert!(parse("90 1 N 0 E 0m".split_whitespace()).is_err());
+    assert!(parse("42 60 N 0 E 0m".split_whitespace()).is_err());
+    assert!(parse("42 N 181 E 0m".split_whitespace()).is_err());
+    assert!(parse("42 X 71 E 0m".split_whitespace()).is_err());
+    assert!(parse("42 N 71 E".split_whitespace()).is_err());
+    assert!(parse("42 N 71 E -100000.01m".split_whitespace()).is_err());
+    assert!(parse("42 N 71 E 0m 1.001m".split_whitespace()).is_err());
+    assert!(parse("42 N 71 E 0m 1m 1m 1m 1m".split_whitespace()).is_err());
+}
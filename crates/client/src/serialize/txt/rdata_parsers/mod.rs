@@ -22,12 +22,22 @@
 pub mod a;
 pub mod aaaa;
 pub mod caa;
+pub mod cert;
+pub mod csync;
+pub mod dhcid;
+pub mod hinfo;
+pub mod loc;
 pub mod mx;
 pub mod name;
+pub mod naptr;
 pub mod null;
 pub mod openpgpkey;
+pub mod rp;
 pub mod soa;
 pub mod srv;
 pub mod sshfp;
+pub mod svcb;
 pub mod tlsa;
 pub mod txt;
+pub mod uri;
+pub mod zonemd;
@@ -0,0 +1,106 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! naming authority pointer, DDDS rule, record
+
+use error::*;
+use rr::domain::Name;
+use rr::rdata::NAPTR;
+
+/// Parse the RData from a set of Tokens
+///
+/// [RFC 3403, DDDS Part Three: The DNS Database, October 2002](https://tools.ietf.org/html/rfc3403#section-4.1)
+///
+/// ```text
+/// Domain TTL Class Type Order Preference Flags Service Regexp Replacement
+///
+/// IN NAPTR 100  10  ""  ""  "!^cid:.+@([^\.]+\.)(.*)$!\2!i"    .
+/// ```
+pub fn parse<'i, I: Iterator<Item = &'i str>>(
+    mut tokens: I,
+    origin: Option<&Name>,
+) -> ParseResult<NAPTR> {
+    let mut next = |field: &str| {
+        tokens
+            .next()
+            .ok_or_else(|| ParseError::from(ParseErrorKind::MissingToken(field.to_string())))
+    };
+
+    let order: u16 = next("order")?.parse()?;
+    let preference: u16 = next("preference")?.parse()?;
+
+    let flags = next("flags")?;
+    if !flags.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(
+            ParseErrorKind::Msg(format!("flags of NAPTR are not alphanumeric: {}", flags)).into(),
+        );
+    }
+    let flags = flags.as_bytes().to_vec().into_boxed_slice();
+    let services = next("services")?.as_bytes().to_vec().into_boxed_slice();
+    let regexp = next("regexp")?.as_bytes().to_vec().into_boxed_slice();
+    let replacement = Name::parse(next("replacement")?, origin)?;
+
+    Ok(NAPTR::new(
+        order,
+        preference,
+        flags,
+        services,
+        regexp,
+        replacement,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn test_parsing() {
+        // the lexer removes the quotes of the strings
+        let tokens = vec![
+            "100",
+            "10",
+            "u",
+            "E2U+sip",
+            "!^.*$!sip:info@example.com!",
+            ".",
+        ];
+        let naptr = parse(tokens.into_iter(), None).unwrap();
+
+        assert_eq!(naptr.order(), 100);
+        assert_eq!(naptr.preference(), 10);
+        assert_eq!(naptr.flags(), b"u");
+        assert_eq!(naptr.services(), b"E2U+sip");
+        assert_eq!(naptr.regexp(), b"!^.*$!sip:info@example.com!");
+        assert!(naptr.replacement().is_root());
+
+        let origin = Name::from_str("example.com.").unwrap();
+        let tokens = vec!["100", "50", "s", "SIP+D2U", "", "_sip._udp"];
+        let naptr = parse(tokens.into_iter(), Some(&origin)).unwrap();
+        assert_eq!(
+            *naptr.replacement(),
+            Name::from_str("_sip._udp.example.com.").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parsing_invalid() {
+        assert!(parse(vec!["100", "10", "u", "E2U+sip", ""].into_iter(), None).is_err());
+        assert!(parse(
+            vec!["100", "10", "u+", "E2U+sip", "", "."].into_iter(),
+            None
+        )
+        .is_err());
+        assert!(parse(
+            vec!["65536", "10", "u", "E2U+sip", "", "."].into_iter(),
+            None
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,50 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! responsible person record
+
+use error::*;
+use rr::domain::Name;
+use rr::rdata::RP;
+
+/// Parse the RData from a set of Tokens
+///
+/// [RFC 1183, New DNS RR Definitions, October 1990](https://tools.ietf.org/html/rfc1183#section-2.2)
+///
+/// ```text
+/// <owner> <ttl> <class> RP <mbox-dname> <txt-dname>
+/// ```
+pub fn parse<'i, I: Iterator<Item = &'i str>>(
+    mut tokens: I,
+    origin: Option<&Name>,
+) -> ParseResult<RP> {
+    let mbox = tokens
+        .next()
+        .ok_or_else(|| ParseError::from(ParseErrorKind::MissingToken("mbox".to_string())))
+        .and_then(|s| Name::parse(s, origin).map_err(ParseError::from))?;
+    let txt = tokens
+        .next()
+        .ok_or_else(|| ParseError::from(ParseErrorKind::MissingToken("txt".to_string())))
+        .and_then(|s| Name::parse(s, origin).map_err(ParseError::from))?;
+
+    Ok(RP::new(mbox, txt))
+}
+
+#[test]
+fn test_parsing() {
+    use std::str::FromStr;
+
+    let origin = Name::from_str("umd.edu.").unwrap();
+    let rp = parse(vec!["louie.trantor", "."].into_iter(), Some(&origin)).unwrap();
+    assert_eq!(
+        *rp.mbox(),
+        Name::from_str("louie.trantor.umd.edu.").unwrap()
+    );
+    assert!(rp.txt().is_root());
+
+    assert!(parse(vec!["louie.trantor.umd.edu."].into_iter(), None).is_err());
+}
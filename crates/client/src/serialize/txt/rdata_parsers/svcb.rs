@@ -0,0 +1,285 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! SVCB and HTTPS records for service binding
+
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+use data_encoding::BASE64;
+
+use error::*;
+use rr::domain::Name;
+use rr::rdata::svcb::{SvcParamKey, SvcParamValue};
+use rr::rdata::SVCB;
+
+/// Parse the RData from a set of Tokens
+///
+/// [RFC 9460, SVCB and HTTPS RRs](https://tools.ietf.org/html/rfc9460#section-2.1)
+///
+/// ```text
+/// 2.1.  Zone-file presentation format
+///
+///    The presentation format <RDATA> of the record is:
+///
+///    SvcPriority TargetName SvcParams
+///
+///    The SvcParams consist of a whitespace-delimited list of
+///    SvcParamKey=SvcParamValue pairs, each of which is presented as a
+///    single contiguous token.
+/// ```
+///
+/// Values in quotes must not contain whitespace.
+pub fn parse<'i, I: Iterator<Item = &'i str>>(
+    mut tokens: I,
+    origin: Option<&Name>,
+) -> ParseResult<SVCB> {
+    let svc_priority: u16 = tokens
+        .next()
+        .ok_or_else(|| ParseError::from(ParseErrorKind::MissingToken("SvcPriority".to_string())))
+        .and_then(|s| u16::from_str(s).map_err(Into::into))?;
+
+    let target_name: Name = tokens
+        .next()
+        .ok_or_else(|| ParseError::from(ParseErrorKind::MissingToken("TargetName".to_string())))
+        .and_then(|s| Name::parse(s, origin).map_err(ParseError::from))?;
+
+    let mut svc_params: Vec<(SvcParamKey, SvcParamValue)> = Vec::new();
+    for token in tokens {
+        let (key, value) = match token.find('=') {
+            Some(idx) => (&token[..idx], Some(unquote(&token[idx + 1..]))),
+            None => (token, None),
+        };
+
+        let key = parse_key(key)?;
+        if svc_params.iter().any(|&(k, _)| k == key) {
+            return Err(ParseErrorKind::Msg(format!("duplicate SvcParamKey: {}", key)).into());
+        }
+
+        let value = parse_value(key, value)?;
+        svc_params.push((key, value));
+    }
+
+    // RFC 9460 section 8, the keys listed in mandatory must be in the record
+    for &(_, ref value) in &svc_params {
+        if let SvcParamValue::Mandatory(ref keys) = *value {
+            for (i, key) in keys.iter().enumerate() {
+                if *key == SvcParamKey::Mandatory
+                    || keys[..i].contains(key)
+                    || !svc_params.iter().any(|&(k, _)| k == *key)
+                {
+                    return Err(ParseErrorKind::Msg(format!(
+                        "invalid mandatory SvcParamKey: {}",
+                        key
+                    ))
+                    .into());
+                }
+            }
+        }
+    }
+
+    Ok(SVCB::new(svc_priority, target_name, svc_params))
+}
+
+fn unquote(value: &str) -> &str {
+    if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        &value[1..value.len() - 1]
+    } else {
+        value
+    }
+}
+
+fn parse_key(key: &str) -> ParseResult<SvcParamKey> {
+    let key = match key {
+        "mandatory" => SvcParamKey::Mandatory,
+        "alpn" => SvcParamKey::Alpn,
+        "no-default-alpn" => SvcParamKey::NoDefaultAlpn,
+        "port" => SvcParamKey::Port,
+        "ipv4hint" => SvcParamKey::Ipv4Hint,
+        "ech" => SvcParamKey::Ech,
+        "ipv6hint" => SvcParamKey::Ipv6Hint,
+        key if key.starts_with("key") => {
+            let key = u16::from_str(&key[3..])?;
+            if key == u16::max_value() {
+                return Err(ParseErrorKind::Message("SvcParamKey 65535 is invalid").into());
+            }
+            SvcParamKey::from(key)
+        }
+        key => return Err(ParseErrorKind::Msg(format!("unknown SvcParamKey: {}", key)).into()),
+    };
+
+    Ok(key)
+}
+
+fn parse_value(key: SvcParamKey, value: Option<&str>) -> ParseResult<SvcParamValue> {
+    let value = match (key, value) {
+        (SvcParamKey::NoDefaultAlpn, None) => return Ok(SvcParamValue::NoDefaultAlpn),
+        (SvcParamKey::NoDefaultAlpn, Some(_)) => {
+            return Err(ParseErrorKind::Message("no-default-alpn has no value").into())
+        }
+        (SvcParamKey::Key(_), None) => return Ok(SvcParamValue::Unknown(vec![])),
+        (_, Some(value)) if !value.is_empty() => value,
+        (key, _) => {
+            return Err(ParseErrorKind::Msg(format!("SvcParam {} has no value", key)).into())
+        }
+    };
+
+    let value = match key {
+        SvcParamKey::Mandatory => SvcParamValue::Mandatory(
+            value
+                .split(',')
+                .map(parse_key)
+                .collect::<ParseResult<Vec<_>>>()?,
+        ),
+        SvcParamKey::Alpn => SvcParamValue::Alpn(split_escaped(value)?),
+        SvcParamKey::NoDefaultAlpn => SvcParamValue::NoDefaultAlpn,
+        SvcParamKey::Port => SvcParamValue::Port(u16::from_str(value)?),
+        SvcParamKey::Ipv4Hint => SvcParamValue::Ipv4Hint(
+            value
+                .split(',')
+                .map(|a| Ipv4Addr::from_str(a).map_err(Into::into))
+                .collect::<ParseResult<Vec<_>>>()?,
+        ),
+        SvcParamKey::Ech => SvcParamValue::Ech(BASE64.decode(value.as_bytes())?),
+        SvcParamKey::Ipv6Hint => SvcParamValue::Ipv6Hint(
+            value
+                .split(',')
+                .map(|a| Ipv6Addr::from_str(a).map_err(Into::into))
+                .collect::<ParseResult<Vec<_>>>()?,
+        ),
+        SvcParamKey::Key(_) => SvcParamValue::Unknown(unescape(value)?),
+    };
+
+    Ok(value)
+}
+
+/// Splits the comma separated value list, `\,` and `\\` are an escaped comma and backslash, RFC 9460 appendix A.1
+fn split_escaped(value: &str) -> ParseResult<Vec<String>> {
+    let mut items = vec![String::new()];
+    let mut chars = value.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => match chars.next() {
+                Some(ch) => items.last_mut().expect("items is never empty").push(ch),
+                None => {
+                    return Err(ParseErrorKind::Message("incomplete escape in value list").into())
+                }
+            },
+            ',' => items.push(String::new()),
+            ch => items.last_mut().expect("items is never empty").push(ch),
+        }
+    }
+
+    if items.iter().any(String::is_empty) {
+        return Err(ParseErrorKind::Message("empty item in value list").into());
+    }
+    Ok(items)
+}
+
+/// Unescapes `\DDD` and `\X` in the value, RFC 1035 section 5.1
+fn unescape(value: &str) -> ParseResult<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            let mut buf = [0; 4];
+            bytes.extend_from_slice(ch.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+
+        match chars.next() {
+            Some(d) if d.is_ascii_digit() => {
+                let digits: String = Some(d).into_iter().chain(chars.by_ref().take(2)).collect();
+                bytes.push(u8::from_str(&digits)?);
+            }
+            Some(ch) if ch.is_ascii() => bytes.push(ch as u8),
+            _ => return Err(ParseErrorKind::Message("invalid escape in value").into()),
+        }
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(rdata: &str) -> ParseResult<SVCB> {
+        parse(
+            rdata.split_whitespace(),
+            Some(&Name::from_str("example.com.").unwrap()),
+        )
+    }
+
+    #[test]
+    fn test_parsing() {
+        // RFC 9460 appendix D
+        let svcb = parse_str("0 foo.example.com.").unwrap();
+        assert!(svcb.is_alias_mode());
+        assert_eq!(
+            *svcb.target_name(),
+            Name::from_str("foo.example.com.").unwrap()
+        );
+
+        let svcb = parse_str("1 foo ipv6hint=2001:db8::1,2001:db8::53:1").unwrap();
+        assert_eq!(
+            *svcb.target_name(),
+            Name::from_str("foo.example.com.").unwrap()
+        );
+        assert_eq!(svcb.ipv6_hint().len(), 2);
+
+        let svcb = parse_str(
+            "16 foo.example.org. alpn=h2,h3-19 mandatory=ipv4hint,alpn ipv4hint=192.0.2.1",
+        )
+        .unwrap();
+        assert_eq!(svcb.alpn(), &["h2".to_string(), "h3-19".to_string()]);
+        assert_eq!(svcb.ipv4_hint(), &[Ipv4Addr::new(192, 0, 2, 1)]);
+        assert_eq!(
+            svcb.svc_param(SvcParamKey::Mandatory),
+            Some(&SvcParamValue::Mandatory(vec![
+                SvcParamKey::Ipv4Hint,
+                SvcParamKey::Alpn
+            ]))
+        );
+
+        let svcb =
+            parse_str(r#"1 . key667="hello\210qoo" port=53 no-default-alpn ech=AQID"#).unwrap();
+        assert_eq!(
+            svcb.svc_param(SvcParamKey::Key(667)),
+            Some(&SvcParamValue::Unknown(b"hello\xd2qoo".to_vec()))
+        );
+        assert_eq!(svcb.port(), Some(53));
+        assert_eq!(
+            svcb.svc_param(SvcParamKey::Ech),
+            Some(&SvcParamValue::Ech(vec![1, 2, 3]))
+        );
+
+        let svcb = parse_str(r#"1 . alpn="f\\oo\,bar,h2""#).unwrap();
+        assert_eq!(svcb.alpn(), &[r"f\oo,bar".to_string(), "h2".to_string()]);
+    }
+
+    #[test]
+    fn test_parsing_display() {
+        let rdata =
+            "1 svc.example.net. alpn=h2,h3\\,x no-default-alpn port=8443 ipv4hint=192.0.2.1";
+        assert_eq!(parse_str(rdata).unwrap().to_string(), rdata);
+    }
+
+    #[test]
+    fn test_parsing_invalid() {
+        // RFC 9460 appendix D.3
+        assert!(parse_str("1 foo.example.com. key123=abc key123=def").is_err());
+        assert!(parse_str("1 foo.example.com. mandatory").is_err());
+        assert!(parse_str("1 foo.example.com. alpn").is_err());
+        assert!(parse_str("1 foo.example.com. port").is_err());
+        assert!(parse_str("1 foo.example.com. mandatory=mandatory").is_err());
+        assert!(parse_str("1 foo.example.com. mandatory=key123,key123 key123=abc").is_err());
+        assert!(parse_str("1 foo.example.com. mandatory=port").is_err());
+        assert!(parse_str("1 foo.example.com. no-default-alpn=abc").is_err());
+        assert!(parse_str("1 foo.example.com. key65535=abc").is_err());
+    }
+}
@@ -0,0 +1,55 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! uniform resource identifier record
+
+use error::*;
+use rr::rdata::URI;
+
+/// Parse the RData from a set of Tokens
+///
+/// [RFC 7553, The URI DNS Resource Record, June 2015](https://tools.ietf.org/html/rfc7553#section-4.4)
+///
+/// ```text
+/// _ftp._tcp    IN URI 10 1 "ftp://ftp1.example.com/public"
+///
+///    This field holds the URI of the target, enclosed in double-quote
+///    characters ('"'), where the URI is as specified in RFC 3986
+///    [RFC3986].
+/// ```
+pub fn parse<'i, I: Iterator<Item = &'i str>>(mut tokens: I) -> ParseResult<URI> {
+    let mut next = |field: &str| {
+        tokens
+            .next()
+            .ok_or_else(|| ParseError::from(ParseErrorKind::MissingToken(field.to_string())))
+    };
+
+    let priority: u16 = next("priority")?.parse()?;
+    let weight: u16 = next("weight")?.parse()?;
+    let target = next("target")?;
+    if target.is_empty() {
+        return Err(ParseErrorKind::Message("target of URI is empty").into());
+    }
+
+    Ok(URI::new(
+        priority,
+        weight,
+        target.as_bytes().to_vec().into_boxed_slice(),
+    ))
+}
+
+#[test]
+fn test_parsing() {
+    // the lexer removes the quotes of the strings
+    let uri = parse(vec!["10", "1", "ftp://ftp1.example.com/public"].into_iter()).unwrap();
+    assert_eq!(uri.priority(), 10);
+    assert_eq!(uri.weight(), 1);
+    assert_eq!(uri.target(), b"ftp://ftp1.example.com/public");
+
+    assert!(parse(vec!["10", "1", ""].into_iter()).is_err());
+    assert!(parse(vec!["10", "1"].into_iter()).is_err());
+}
@@ -0,0 +1,93 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! message digest for DNS zones record
+
+use data_encoding::HEXLOWER_PERMISSIVE;
+
+use error::*;
+use rr::rdata::zonemd::{ZonemdHashAlgorithm, ZonemdScheme};
+use rr::rdata::ZONEMD;
+
+/// The minimum length of a digest, RFC 8976 section 2.2.4
+const MIN_DIGEST_LEN: usize = 12;
+
+/// Parse the RData from a set of Tokens
+///
+/// [RFC 8976, Message Digest for DNS Zones, February 2021](https://tools.ietf.org/html/rfc8976#section-2.3)
+///
+/// ```text
+/// 2.3.  ZONEMD Presentation Format
+///
+///    All fields of the ZONEMD RDATA are presented on a single line
+///    using the parentheses of a multi-line record to
+///    span lines if needed.
+///
+/// example.      86400  IN  ZONEMD  2018031900 1 1 (
+///                                  c68090d90a7aed71
+///                                  6bc459f9340e3d7c
+///                                  1370d4d24b7e2fc3
+///                                  a1ddc0b9a87153b9
+///                                  a9713b3c9ae5cc27
+///                                  777f98b8e730044c )
+/// ```
+pub fn parse<'i, I: Iterator<Item = &'i str>>(mut tokens: I) -> ParseResult<ZONEMD> {
+    let mut next = |field: &str| {
+        tokens
+            .next()
+            .ok_or_else(|| ParseError::from(ParseErrorKind::MissingToken(field.to_string())))
+    };
+
+    let serial: u32 = next("Serial")?.parse()?;
+    let scheme: u8 = next("Scheme")?.parse()?;
+    let hash_algorithm: u8 = next("Hash Algorithm")?.parse()?;
+
+    let encoded: String = tokens.collect();
+    let digest = HEXLOWER_PERMISSIVE.decode(encoded.as_bytes())?;
+    if digest.len() < MIN_DIGEST_LEN {
+        return Err(ParseErrorKind::Msg(format!(
+            "digest of ZONEMD is too short: {} octets",
+            digest.len()
+        ))
+        .into());
+    }
+
+    Ok(ZONEMD::new(
+        serial,
+        ZonemdScheme::from(scheme),
+        ZonemdHashAlgorithm::from(hash_algorithm),
+        digest,
+    ))
+}
+
+#[test]
+fn test_parsing() {
+    let zonemd = parse(
+        vec![
+            "2018031900",
+            "1",
+            "1",
+            "c68090d90a7aed71",
+            "6bc459f9340e3d7c",
+            "1370d4d24b7e2fc3",
+            "a1ddc0b9a87153b9",
+            "a9713b3c9ae5cc27",
+            "777F98B8E730044C",
+        ]
+        .into_iter(),
+    )
+    .unwrap();
+    assert_eq!(zonemd.serial(), 2018031900);
+    assert_eq!(zonemd.scheme(), ZonemdScheme::Simple);
+    assert_eq!(zonemd.hash_algorithm(), ZonemdHashAlgorithm::SHA384);
+    assert_eq!(zonemd.digest().len(), 48);
+    assert_eq!(zonemd.digest()[..2], [0xc6, 0x80]);
+
+    assert!(parse(vec!["2018031900", "1", "1", "c68090d9"].into_iter()).is_err());
+    assert!(parse(vec!["2018031900", "1", "1", "xyz"].into_iter()).is_err());
+    assert!(parse(vec!["2018031900", "1"].into_iter()).is_err());
+}
@@ -0,0 +1,137 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! DANE, an OpenSSL verify callback for the TLSA records of a service
+//!
+//! The TLSA records must have been validated with DNSSEC, e.g. with `AsyncResolver::dane_lookup` of the resolver.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use openssl::error::ErrorStack;
+use openssl::stack::Stack;
+use openssl::x509::store::X509StoreBuilder;
+use openssl::x509::{X509StoreContext, X509StoreContextRef, X509};
+
+use trust_dns_proto::rr::dane;
+use trust_dns_proto::rr::rdata::tlsa::TLSA;
+
+/// Returns a verify callback which verifies the certificates of the server with its TLSA records, see
+///  `trust_dns_proto::rr::dane::verify_chain`
+///
+/// The callback keeps the result of the verification of the chain by OpenSSL, it must only be used for a single
+///  connection, i.e. with `SslRef::set_verify_callback`. If none of the records is usable, DANE does not apply and the
+///  result of OpenSSL is returned as is.
+///
+/// # Arguments
+///
+/// * `tlsas` - the validated TLSA records of the server
+/// * `host` - the name of the server, certificates of trust anchors from the records (DANE-TA) must be issued for it
+pub fn dane_verify_callback(
+    tlsas: Vec<TLSA>,
+    host: String,
+) -> impl Fn(bool, &mut X509StoreContextRef) -> bool + Send + Sync + 'static {
+    let usable = tlsas.iter().any(dane::is_usable);
+    let pkix_valid = AtomicBool::new(true);
+
+    move |preverify_ok, ctx| {
+        if !preverify_ok {
+            pkix_valid.store(false, Ordering::Relaxed);
+        }
+
+        if !usable {
+            return preverify_ok;
+        }
+
+        // the end entity is verified last, once the whole chain has been seen
+        if ctx.error_depth() != 0 {
+            return true;
+        }
+
+        let chain = match ctx.chain() {
+            Some(chain) => chain
+                .iter()
+                .map(|cert| cert.to_owned())
+                .collect::<Vec<X509>>(),
+            None => return false,
+        };
+        let chain_der = match chain
+            .iter()
+            .map(|cert| cert.to_der())
+            .collect::<Result<Vec<_>, _>>()
+        {
+            Ok(chain_der) => chain_der,
+            Err(_) => return false,
+        };
+        let chain_der = chain_der.iter().map(Vec::as_slice).collect::<Vec<_>>();
+
+        // the chain is the path built and verified by OpenSSL, any of its certificates is on the PKIX path
+        dane::verify_chain(
+            &tlsas,
+            &chain_der,
+            |_| pkix_valid.load(Ordering::Relaxed),
+            |index| chains_to(&chain, index).unwrap_or(false) && is_issued_for(&chain[0], &host),
+        )
+        .is_ok()
+    }
+}
+
+/// Verifies the end entity certificate with the certificate at the index as the only trust anchor
+fn chains_to(chain: &[X509], index: usize) -> Result<bool, ErrorStack> {
+    let mut store = X509StoreBuilder::new()?;
+    store.add_cert(chain[index].clone())?;
+    let store = store.build();
+
+    let mut intermediates = Stack::new()?;
+    for cert in &chain[1..index] {
+        intermediates.push(cert.clone())?;
+    }
+
+    let mut context = X509StoreContext::new()?;
+    context.init(&store, &chain[0], &intermediates, |c| c.verify_cert())
+}
+
+/// Returns true if a DNS name of the subject alternative names of the certificate is the host, or a wildcard for it
+fn is_issued_for(cert: &X509, host: &str) -> bool {
+    let host = host.trim_end_matches('.');
+    let names = match cert.subject_alt_names() {
+        Some(names) => names,
+        None => return false,
+    };
+
+    names.iter().filter_map(|name| name.dnsname()).any(|name| {
+        if name.eq_ignore_ascii_case(host) {
+            return true;
+        }
+
+        match (name.starts_with("*."), host.find('.')) {
+            (true, Some(dot)) => name[1..].eq_ignore_ascii_case(&host[dot..]),
+            _ => false,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CA: &[u8] = include_bytes!("../../../tests/test-data/ca.der");
+    const CERT: &[u8] = include_bytes!("../../../tests/test-data/cert.der");
+
+    #[test]
+    fn test_is_issued_for() {
+        let cert = X509::from_der(CERT).unwrap();
+        assert!(is_issued_for(&cert, "ns.example.com."));
+        assert!(is_issued_for(&cert, "NS.example.com"));
+        assert!(!is_issued_for(&cert, "www.example.com"));
+
+        // the CA has no subject alternative names
+        assert!(!is_issued_for(
+            &X509::from_der(CA).unwrap(),
+            "root.example.com"
+        ));
+    }
+}
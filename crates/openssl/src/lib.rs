@@ -22,6 +22,8 @@ extern crate tokio_openssl;
 extern crate tokio_tcp;
 extern crate trust_dns_proto;
 
+#[cfg(feature = "dane")]
+pub mod dane;
 mod tls_client_stream;
 pub mod tls_server;
 mod tls_stream;
@@ -12,7 +12,6 @@
 //! Trust-DNS Protocol library
 
 extern crate byteorder;
-#[cfg(feature = "dnssec")]
 extern crate data_encoding;
 #[macro_use]
 extern crate enum_as_inner;
@@ -17,7 +17,7 @@
 //! Extended DNS options
 
 use error::*;
-use rr::rdata::opt::{self, EdnsCode, EdnsOption};
+use rr::rdata::opt::{self, ClientSubnet, EdnsCode, EdnsOption};
 use rr::rdata::OPT;
 use rr::{DNSClass, Name, RData, Record, RecordType};
 
@@ -113,6 +113,19 @@ impl Edns {
     pub fn set_option(&mut self, option: EdnsOption) {
         self.options.insert(option);
     }
+
+    /// Remove the EDNS option with the code
+    pub fn remove_option(&mut self, code: EdnsCode) -> Option<EdnsOption> {
+        self.options.remove(code)
+    }
+
+    /// Returns the client subnet option, see [RFC 7871](https://tools.ietf.org/html/rfc7871)
+    pub fn client_subnet(&self) -> Option<&ClientSubnet> {
+        match self.option(EdnsCode::Subnet) {
+            Some(&EdnsOption::Subnet(ref subnet)) => Some(subnet),
+            _ => None,
+        }
+    }
 }
 
 impl<'a> From<&'a Record> for Edns {
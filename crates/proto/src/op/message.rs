@@ -82,7 +82,8 @@ pub struct Message {
     edns: Option<Edns>,
 }
 
-/// Returns a new Header with accurate counts for each Message section
+/// Returns a new Header with accurate counts for each Message section, and truncated if it was
+///  already, or if `is_truncated`
 pub fn update_header_counts(
     current_header: &Header,
     is_truncated: bool,
@@ -98,7 +99,7 @@ pub fn update_header_counts(
     header.set_answer_count(counts.answer_count as u16);
     header.set_name_server_count(counts.nameserver_count as u16);
     header.set_additional_count(counts.additional_count as u16);
-    header.set_truncated(is_truncated);
+    header.set_truncated(current_header.truncated() || is_truncated);
 
     header
 }
@@ -485,6 +486,11 @@ impl Message {
         self.edns.as_mut().unwrap()
     }
 
+    /// Removes the EDNS section, returning it if there was one
+    pub fn take_edns(&mut self) -> Option<Edns> {
+        self.edns.take()
+    }
+
     /// # Return value
     ///
     /// the max payload value as it's defined in the EDNS section.
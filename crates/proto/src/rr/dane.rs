@@ -0,0 +1,415 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! DANE, verification of TLS certificates with TLSA records
+//!
+//! See [RFC 6698](https://tools.ietf.org/html/rfc6698) and the updates of
+//!  [RFC 7671](https://tools.ietf.org/html/rfc7671). The TLSA records must have been validated with DNSSEC, otherwise
+//!  they do not authenticate anything.
+
+use error::*;
+use rr::dnssec::DigestType;
+use rr::rdata::tlsa::{CertUsage, Matching, Selector, TLSA};
+
+const DER_SEQUENCE: u8 = 0x30;
+const DER_INTEGER: u8 = 0x02;
+/// `[0] EXPLICIT`, the version of a certificate
+const DER_CONTEXT_0: u8 = 0xa0;
+
+/// Returns true if the usage, selector and matching type of the record are all known
+///
+/// RFC 6698 section 4.1, records which are not usable are ignored. If none of the records of a service is usable,
+///  DANE does not apply to it and the certificate is verified as it would be without TLSA records.
+pub fn is_usable(tlsa: &TLSA) -> bool {
+    let usage = match *tlsa.cert_usage() {
+        CertUsage::CA | CertUsage::Service | CertUsage::TrustAnchor | CertUsage::DomainIssued => {
+            true
+        }
+        CertUsage::Unassigned(_) | CertUsage::Private => false,
+    };
+    let selector = match *tlsa.selector() {
+        Selector::Full | Selector::Spki => true,
+        Selector::Unassigned(_) | Selector::Private => false,
+    };
+    let matching = match *tlsa.matching() {
+        Matching::Raw | Matching::Sha256 | Matching::Sha512 => true,
+        Matching::Unassigned(_) | Matching::Private => false,
+    };
+
+    usage && selector && matching
+}
+
+/// Returns true if the certificate association data of the record matches the DER encoded certificate
+///
+/// The usage of the record is not considered, records which are not usable never match.
+pub fn matches(tlsa: &TLSA, cert_der: &[u8]) -> ProtoResult<bool> {
+    let selected = match *tlsa.selector() {
+        Selector::Full => cert_der,
+        Selector::Spki => subject_public_key_info(cert_der)?,
+        Selector::Unassigned(_) | Selector::Private => return Ok(false),
+    };
+
+    let matches = match *tlsa.matching() {
+        Matching::Raw => selected == tlsa.cert_data(),
+        Matching::Sha256 => DigestType::SHA256.hash(selected)?.as_ref() == tlsa.cert_data(),
+        Matching::Sha512 => DigestType::SHA512.hash(selected)?.as_ref() == tlsa.cert_data(),
+        Matching::Unassigned(_) | Matching::Private => false,
+    };
+
+    Ok(matches)
+}
+
+/// Verifies the certificate chain presented by a TLS server with the TLSA records of the service
+///
+/// The chain is verified if any of the usable records is satisfied, see RFC 7671 section 5:
+///
+/// * `DomainIssued`, DANE-EE, the end entity certificate matches, neither its names nor its validity are checked
+/// * `TrustAnchor`, DANE-TA, a certificate of the chain matches and `chains_to` verifies the chain up to it
+/// * `Service`, PKIX-EE, the end entity certificate matches and `pkix_valid` verifies the chain
+/// * `CA`, PKIX-TA, a certificate of the chain above the end entity matches and `pkix_valid` verifies the chain on a
+///   path through it
+///
+/// # Arguments
+///
+/// * `tlsas` - the validated TLSA records of the service
+/// * `chain` - the DER encoded certificates presented by the server, the end entity certificate first
+/// * `pkix_valid` - verifies the chain with the trusted roots of the client, and the name of the server, on a path
+///   through the certificate at the index of the chain (0 for the end entity); it is only called if a PKIX record is
+///   considered
+/// * `chains_to` - verifies the chain, and the name of the server, with the certificate at the index of the chain as
+///   the only trust anchor
+///
+/// # Return value
+///
+/// The usage of the record which verified the chain, an error if no usable record is satisfied or there are none.
+pub fn verify_chain<P, T>(
+    tlsas: &[TLSA],
+    chain: &[&[u8]],
+    mut pkix_valid: P,
+    mut chains_to: T,
+) -> ProtoResult<CertUsage>
+where
+    P: FnMut(usize) -> bool,
+    T: FnMut(usize) -> bool,
+{
+    let end_entity = match chain.first() {
+        Some(end_entity) => *end_entity,
+        None => return Err("no certificates presented".into()),
+    };
+
+    let mut pkix_checked = vec![None; chain.len()];
+    let mut is_pkix_valid =
+        |index: usize| *pkix_checked[index].get_or_insert_with(|| pkix_valid(index));
+
+    let mut any_usable = false;
+    for tlsa in tlsas.iter().filter(|tlsa| is_usable(tlsa)) {
+        any_usable = true;
+
+        let usage = *tlsa.cert_usage();
+        let verified = match usage {
+            CertUsage::DomainIssued => matches(tlsa, end_entity)?,
+            CertUsage::Service => matches(tlsa, end_entity)? && is_pkix_valid(0),
+            CertUsage::TrustAnchor => {
+                let mut verified = false;
+                for (index, cert) in chain.iter().enumerate().skip(1) {
+                    if matches(tlsa, cert)? && chains_to(index) {
+                        verified = true;
+                        break;
+                    }
+                }
+                verified
+            }
+            CertUsage::CA => {
+                let mut verified = false;
+                for (index, cert) in chain.iter().enumerate().skip(1) {
+                    if matches(tlsa, cert)? && is_pkix_valid(index) {
+                        verified = true;
+                        break;
+                    }
+                }
+                verified
+            }
+            CertUsage::Unassigned(_) | CertUsage::Private => false,
+        };
+
+        if verified {
+            debug!("certificate chain verified by TLSA record: {:?}", tlsa);
+            return Ok(usage);
+        }
+    }
+
+    if any_usable {
+        Err("the certificate chain does not match any TLSA record".into())
+    } else {
+        Err("no usable TLSA records".into())
+    }
+}
+
+/// Returns the DER encoded SubjectPublicKeyInfo of the DER encoded X.509 certificate
+///
+/// ```text
+/// Certificate  ::=  SEQUENCE  {
+///      tbsCertificate       TBSCertificate,
+///      ...  }
+///
+/// TBSCertificate  ::=  SEQUENCE  {
+///      version         [0]  EXPLICIT Version DEFAULT v1,
+///      serialNumber         CertificateSerialNumber,
+///      signature            AlgorithmIdentifier,
+///      issuer               Name,
+///      validity             Validity,
+///      subject              Name,
+///      subjectPublicKeyInfo SubjectPublicKeyInfo,
+///      ...  }
+/// ```
+pub fn subject_public_key_info(cert_der: &[u8]) -> ProtoResult<&[u8]> {
+    let (certificate, _) = der_element(cert_der, DER_SEQUENCE)?;
+    let (tbs_certificate, _) = der_element(certificate, DER_SEQUENCE)?;
+
+    let mut fields = tbs_certificate;
+    if fields.first() == Some(&DER_CONTEXT_0) {
+        fields = der_element(fields, DER_CONTEXT_0)?.1;
+    }
+    // serialNumber
+    fields = der_element(fields, DER_INTEGER)?.1;
+    // signature, issuer, validity and subject
+    for _ in 0..4 {
+        fields = der_element(fields, DER_SEQUENCE)?.1;
+    }
+
+    let (spki, rest) = der_element(fields, DER_SEQUENCE)?;
+    let header_len = fields.len() - rest.len() - spki.len();
+    Ok(&fields[..header_len + spki.len()])
+}
+
+/// Reads the DER element with the tag from the start of the data, returns its contents and the remaining data
+fn der_element(data: &[u8], tag: u8) -> ProtoResult<(&[u8], &[u8])> {
+    if data.len() < 2 || data[0] != tag {
+        return Err(format!("expected DER element with tag {:#x}", tag).into());
+    }
+
+    let (len, header_len) = match data[1] {
+        len if len < 0x80 => (len as usize, 2),
+        len_of_len @ 0x81...0x84 => {
+            let len_of_len = (len_of_len & 0x7f) as usize;
+            if data.len() < 2 + len_of_len {
+                return Err("truncated DER length".into());
+            }
+            let len = data[2..2 + len_of_len]
+                .iter()
+                .fold(0usize, |len, byte| len << 8 | *byte as usize);
+            (len, 2 + len_of_len)
+        }
+        _ => return Err("unsupported DER length".into()),
+    };
+
+    if data.len() - header_len < len {
+        return Err("truncated DER element".into());
+    }
+
+    let (contents, rest) = data[header_len..].split_at(len);
+    Ok((contents, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A structurally valid certificate, the DER of the fields is minimal
+    fn test_cert(spki_key: &[u8]) -> (Vec<u8>, Vec<u8>) {
+        let mut spki = vec![DER_SEQUENCE, spki_key.len() as u8];
+        spki.extend_from_slice(spki_key);
+
+        #[rustfmt::skip]
+        let mut tbs = vec![
+            DER_CONTEXT_0, 3, DER_INTEGER, 1, 2, // version
+            DER_INTEGER, 1, 1, // serialNumber
+            DER_SEQUENCE, 0, // signature
+            DER_SEQUENCE, 0, // issuer
+            DER_SEQUENCE, 0, // validity
+            DER_SEQUENCE, 0, // subject
+        ];
+        tbs.extend_from_slice(&spki);
+
+        let mut cert = vec![
+            DER_SEQUENCE,
+            tbs.len() as u8 + 2,
+            DER_SEQUENCE,
+            tbs.len() as u8,
+        ];
+        cert.extend_from_slice(&tbs);
+        (cert, spki)
+    }
+
+    fn sha256(data: &[u8]) -> Vec<u8> {
+        DigestType::SHA256.hash(data).unwrap().as_ref().to_vec()
+    }
+
+    #[test]
+    fn test_subject_public_key_info() {
+        let (cert, spki) = test_cert(&[5, 0]);
+        assert_eq!(subject_public_key_info(&cert).unwrap(), &spki[..]);
+
+        assert!(subject_public_key_info(&cert[..cert.len() - 1]).is_err());
+        assert!(subject_public_key_info(&spki).is_err());
+    }
+
+    #[test]
+    fn test_matches() {
+        let (cert, spki) = test_cert(&[5, 0]);
+
+        let full = TLSA::new(
+            CertUsage::DomainIssued,
+            Selector::Full,
+            Matching::Raw,
+            cert.clone(),
+        );
+        assert!(matches(&full, &cert).unwrap());
+
+        let spki_sha256 = TLSA::new(
+            CertUsage::DomainIssued,
+            Selector::Spki,
+            Matching::Sha256,
+            sha256(&spki),
+        );
+        assert!(matches(&spki_sha256, &cert).unwrap());
+
+        let other = TLSA::new(
+            CertUsage::DomainIssued,
+            Selector::Spki,
+            Matching::Sha256,
+            sha256(&[]),
+        );
+        assert!(!matches(&other, &cert).unwrap());
+    }
+
+    #[test]
+    fn test_verify_chain() {
+        let (end_entity, _) = test_cert(&[5, 0]);
+        let (ca, _) = test_cert(&[5, 1]);
+        let chain = [&end_entity[..], &ca[..]];
+        let tlsa =
+            |usage, cert: &[u8]| TLSA::new(usage, Selector::Full, Matching::Sha256, sha256(cert));
+
+        // DANE-EE, regardless of PKIX
+        assert_eq!(
+            verify_chain(
+                &[tlsa(CertUsage::DomainIssued, &end_entity)],
+                &chain,
+                |_| false,
+                |_| false
+            )
+            .unwrap(),
+            CertUsage::DomainIssued
+        );
+
+        // PKIX-EE, only with PKIX
+        assert!(verify_chain(
+            &[tlsa(CertUsage::Service, &end_entity)],
+            &chain,
+            |_| false,
+            |_| true
+        )
+        .is_err());
+        assert!(verify_chain(
+            &[tlsa(CertUsage::Service, &end_entity)],
+            &chain,
+            |_| true,
+            |_| false
+        )
+        .is_ok());
+
+        // DANE-TA, if the chain verifies up to the matching certificate
+        assert!(verify_chain(
+            &[tlsa(CertUsage::TrustAnchor, &ca)],
+            &chain,
+            |_| false,
+            |i| i == 1
+        )
+        .is_ok());
+        assert!(verify_chain(
+            &[tlsa(CertUsage::TrustAnchor, &ca)],
+            &chain,
+            |_| true,
+            |_| false
+        )
+        .is_err());
+        // the end entity is never a trust anchor
+        assert!(verify_chain(
+            &[tlsa(CertUsage::TrustAnchor, &end_entity)],
+            &chain,
+            |_| true,
+            |_| true
+        )
+        .is_err());
+
+        // PKIX-TA, the CA must be in the chain above the end entity
+        assert!(verify_chain(&[tlsa(CertUsage::CA, &ca)], &chain, |_| true, |_| false).is_ok());
+        assert!(verify_chain(
+            &[tlsa(CertUsage::CA, &end_entity)],
+            &chain,
+            |_| true,
+            |_| false
+        )
+        .is_err());
+
+        // any satisfied record verifies the chain, unusable records are ignored
+        let unusable = TLSA::new(
+            CertUsage::Private,
+            Selector::Full,
+            Matching::Raw,
+            end_entity.clone(),
+        );
+        assert!(verify_chain(&[unusable.clone()], &chain, |_| true, |_| true).is_err());
+        assert!(verify_chain(
+            &[
+                unusable,
+                tlsa(CertUsage::Service, &ca),
+                tlsa(CertUsage::DomainIssued, &end_entity)
+            ],
+            &chain,
+            |_| false,
+            |_| false
+        )
+        .is_ok());
+    }
+
+    #[cfg(feature = "openssl")]
+    #[test]
+    fn test_openssl_subject_public_key_info() {
+        use openssl::asn1::Asn1Time;
+        use openssl::bn::BigNum;
+        use openssl::hash::MessageDigest;
+        use openssl::pkey::PKey;
+        use openssl::rsa::Rsa;
+        use openssl::x509::{X509Builder, X509NameBuilder};
+
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_text("CN", "www.example.com").unwrap();
+        let name = name.build();
+
+        let mut cert = X509Builder::new().unwrap();
+        cert.set_version(2).unwrap();
+        cert.set_serial_number(&BigNum::from_u32(1).unwrap().to_asn1_integer().unwrap())
+            .unwrap();
+        cert.set_subject_name(&name).unwrap();
+        cert.set_issuer_name(&name).unwrap();
+        cert.set_pubkey(&key).unwrap();
+        cert.set_not_before(&Asn1Time::days_from_now(0).unwrap())
+            .unwrap();
+        cert.set_not_after(&Asn1Time::days_from_now(1).unwrap())
+            .unwrap();
+        cert.sign(&key, MessageDigest::sha256()).unwrap();
+        let cert = cert.build();
+
+        assert_eq!(
+            subject_public_key_info(&cert.to_der().unwrap()).unwrap(),
+            &key.public_key_to_der().unwrap()[..]
+        );
+    }
+}
@@ -16,9 +16,9 @@
 
 //! negative cache proof for non-existence
 
-use super::nsec3;
 use error::*;
 use rr::dnssec::rdata::DNSSECRecordType;
+use rr::type_bit_map::{decode_type_bit_maps, encode_bit_maps};
 use rr::{Name, RecordType};
 use serialize::binary::*;
 
@@ -139,7 +139,7 @@ pub fn read(decoder: &mut BinDecoder, rdata_length: Restrict<u16>) -> ProtoResul
         .map(|u| u as usize)
         .checked_sub(decoder.index() - start_idx)
         .map_err(|_| ProtoError::from("invalid rdata length in NSEC"))?;
-    let record_types = nsec3::decode_type_bit_maps(decoder, bit_map_len)?;
+    let record_types = decode_type_bit_maps(decoder, bit_map_len)?;
 
     Ok(NSEC::new(next_domain_name, record_types))
 }
@@ -158,7 +158,7 @@ pub fn emit(encoder: &mut BinEncoder, rdata: &NSEC) -> ProtoResult<()> {
     let is_canonical_names = encoder.is_canonical_names();
     encoder.set_canonical_names(true);
     rdata.next_domain_name().emit(encoder)?;
-    nsec3::encode_bit_maps(encoder, rdata.type_bit_maps())?;
+    encode_bit_maps(encoder, rdata.type_bit_maps())?;
     encoder.set_canonical_names(is_canonical_names);
 
     Ok(())
@@ -16,6 +16,8 @@
 
 //! Resource record related components, e.g. `Name` aka label, `Record`, `RData`, ...
 
+#[cfg(any(feature = "dnssec-openssl", feature = "dnssec-ring"))]
+pub mod dane;
 pub mod dns_class;
 // TODO: rename to sec
 #[cfg(feature = "dnssec")]
@@ -26,6 +28,9 @@ pub mod record_data;
 pub mod record_type;
 pub mod resource;
 mod rr_set;
+mod type_bit_map;
+#[cfg(any(feature = "dnssec-openssl", feature = "dnssec-ring"))]
+pub mod zone_digest;
 
 pub use self::dns_class::DNSClass;
 pub use self::domain::{IntoName, Name, TryParseIp};
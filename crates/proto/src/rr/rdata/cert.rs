@@ -0,0 +1,317 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! certificate records for storing certificates and revocation lists
+
+use std::fmt;
+use std::str::FromStr;
+
+use data_encoding::BASE64;
+
+use error::*;
+use serialize::binary::*;
+
+/// [RFC 4398, Storing Certificates in the DNS, March 2006](https://tools.ietf.org/html/rfc4398#section-2)
+///
+/// ```text
+/// 2.  The CERT Resource Record
+///
+///    The CERT resource record (RR) has the structure given below.  Its RR
+///    type code is 37.
+///
+///                        1 1 1 1 1 1 1 1 1 1 2 2 2 2 2 2 2 2 2 2 3 3
+///    0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///    |             type              |             key tag           |
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///    |   algorithm   |                                               /
+///    +---------------+            certificate or CRL                 /
+///    /                                                               /
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-|
+///
+///    The type field is the certificate type as defined in Section 2.1
+///    below.
+///
+///    The key tag field is the 16-bit value computed for the key embedded
+///    in the certificate, using the RRSIG Key Tag algorithm described in
+///    Appendix B of [12].  This field is used as an efficiency measure to
+///    pick which CERT RRs may be applicable to a particular key.  The key
+///    tag can be calculated for the key in question, and then only CERT
+///    RRs with the same key tag need to be examined.  Note that two
+///    different keys can have the same key tag.  However, the key MUST be
+///    transformed to the format it would have as the public key portion of
+///    a DNSKEY RR before the key tag is computed.  This is only possible if
+///    the key is applicable to an algorithm and complies to limits (such as
+///    key size) defined for DNS security.  If it is not, the algorithm
+///    field MUST be zero and the tag field is meaningless and SHOULD be
+///    zero.
+///
+///    The algorithm field has the same meaning as the algorithm field in
+///    DNSKEY and RRSIG RRs [12], except that a zero algorithm field
+///    indicates that the algorithm is unknown to a secure DNS, which may
+///    simply be the result of the algorithm not having been standardized
+///    for DNSSEC [11].
+/// ```
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct CERT {
+    cert_type: CertType,
+    key_tag: u16,
+    algorithm: u8,
+    cert_data: Vec<u8>,
+}
+
+/// [RFC 4398, Storing Certificates in the DNS, March 2006](https://tools.ietf.org/html/rfc4398#section-2.1)
+///
+/// ```text
+/// 2.1.  Certificate Types
+///
+///    The type field in the CERT RR defines the type of certificate/CRL
+///    present.
+/// ```
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum CertType {
+    /// X.509 as per PKIX
+    PKIX,
+    /// SPKI certificate
+    SPKI,
+    /// OpenPGP packet
+    PGP,
+    /// The URL of an X.509 data object
+    IPKIX,
+    /// The URL of an SPKI certificate
+    ISPKI,
+    /// The fingerprint and URL of an OpenPGP packet
+    IPGP,
+    /// Attribute Certificate
+    ACPKIX,
+    /// The URL of an Attribute Certificate
+    IACPKIX,
+    /// URI private
+    URI,
+    /// OID private
+    OID,
+    /// Experimental, 65280-65534
+    Experimental(u16),
+    /// Reserved or unassigned at the time of this writing
+    Unassigned(u16),
+}
+
+impl From<u16> for CertType {
+    fn from(cert_type: u16) -> Self {
+        match cert_type {
+            1 => CertType::PKIX,
+            2 => CertType::SPKI,
+            3 => CertType::PGP,
+            4 => CertType::IPKIX,
+            5 => CertType::ISPKI,
+            6 => CertType::IPGP,
+            7 => CertType::ACPKIX,
+            8 => CertType::IACPKIX,
+            253 => CertType::URI,
+            254 => CertType::OID,
+            65280...65534 => CertType::Experimental(cert_type),
+            _ => CertType::Unassigned(cert_type),
+        }
+    }
+}
+
+impl From<CertType> for u16 {
+    fn from(cert_type: CertType) -> Self {
+        match cert_type {
+            CertType::PKIX => 1,
+            CertType::SPKI => 2,
+            CertType::PGP => 3,
+            CertType::IPKIX => 4,
+            CertType::ISPKI => 5,
+            CertType::IPGP => 6,
+            CertType::ACPKIX => 7,
+            CertType::IACPKIX => 8,
+            CertType::URI => 253,
+            CertType::OID => 254,
+            CertType::Experimental(cert_type) | CertType::Unassigned(cert_type) => cert_type,
+        }
+    }
+}
+
+/// [RFC 4398, Storing Certificates in the DNS, March 2006](https://tools.ietf.org/html/rfc4398#section-2.2)
+///
+/// ```text
+///    The type field can be represented as either an unsigned decimal
+///    integer or as a mnemonic symbol as listed in Section 2.1, above.
+/// ```
+impl FromStr for CertType {
+    type Err = ProtoError;
+
+    fn from_str(cert_type: &str) -> ProtoResult<Self> {
+        let cert_type = match cert_type {
+            "PKIX" => CertType::PKIX,
+            "SPKI" => CertType::SPKI,
+            "PGP" => CertType::PGP,
+            "IPKIX" => CertType::IPKIX,
+            "ISPKI" => CertType::ISPKI,
+            "IPGP" => CertType::IPGP,
+            "ACPKIX" => CertType::ACPKIX,
+            "IACPKIX" => CertType::IACPKIX,
+            "URI" => CertType::URI,
+            "OID" => CertType::OID,
+            _ => CertType::from(
+                u16::from_str(cert_type)
+                    .map_err(|_| ProtoError::from(format!("unknown CERT type: {}", cert_type)))?,
+            ),
+        };
+
+        Ok(cert_type)
+    }
+}
+
+impl fmt::Display for CertType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CertType::PKIX => f.write_str("PKIX"),
+            CertType::SPKI => f.write_str("SPKI"),
+            CertType::PGP => f.write_str("PGP"),
+            CertType::IPKIX => f.write_str("IPKIX"),
+            CertType::ISPKI => f.write_str("ISPKI"),
+            CertType::IPGP => f.write_str("IPGP"),
+            CertType::ACPKIX => f.write_str("ACPKIX"),
+            CertType::IACPKIX => f.write_str("IACPKIX"),
+            CertType::URI => f.write_str("URI"),
+            CertType::OID => f.write_str("OID"),
+            CertType::Experimental(cert_type) | CertType::Unassigned(cert_type) => {
+                write!(f, "{}", cert_type)
+            }
+        }
+    }
+}
+
+impl CERT {
+    /// Creates a new CERT record data
+    ///
+    /// # Arguments
+    ///
+    /// * `cert_type` - the type of the certificate or CRL
+    /// * `key_tag` - the key tag of the key in the certificate, see the DNSKEY key tag
+    /// * `algorithm` - the DNSSEC algorithm of the key in the certificate, zero if unknown
+    /// * `cert_data` - the certificate or CRL
+    pub fn new(cert_type: CertType, key_tag: u16, algorithm: u8, cert_data: Vec<u8>) -> Self {
+        CERT {
+            cert_type,
+            key_tag,
+            algorithm,
+            cert_data,
+        }
+    }
+
+    /// The type of the certificate or CRL
+    pub fn cert_type(&self) -> CertType {
+        self.cert_type
+    }
+
+    /// The key tag of the key embedded in the certificate
+    pub fn key_tag(&self) -> u16 {
+        self.key_tag
+    }
+
+    /// The DNSSEC algorithm number of the key in the certificate, zero if it is unknown to DNSSEC
+    pub fn algorithm(&self) -> u8 {
+        self.algorithm
+    }
+
+    /// The certificate or CRL, formatted according to the type
+    pub fn cert_data(&self) -> &[u8] {
+        &self.cert_data
+    }
+}
+
+/// [RFC 4398, Storing Certificates in the DNS, March 2006](https://tools.ietf.org/html/rfc4398#section-2.2)
+///
+/// ```text
+///    The "type" field, the "key tag" field, and the "algorithm" field are
+///    represented as unsigned decimal integers or mnemonics, and the
+///    certificate/CRL is represented in base-64.
+/// ```
+///
+/// The algorithm is always displayed as a decimal integer.
+impl fmt::Display for CERT {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {}",
+            self.cert_type,
+            self.key_tag,
+            self.algorithm,
+            BASE64.encode(&self.cert_data)
+        )
+    }
+}
+
+/// Read the RData from the given Decoder
+pub fn read(decoder: &mut BinDecoder, rdata_length: Restrict<u16>) -> ProtoResult<CERT> {
+    let cert_type = decoder.read_u16()?.unverified(/*CertType is safe with any u16*/);
+    let key_tag = decoder.read_u16()?.unverified(/*any u16 is valid*/);
+    let algorithm = decoder.read_u8()?.unverified(/*any u8 is valid*/);
+
+    let cert_len = rdata_length
+        .map(|u| u as usize)
+        .checked_sub(5)
+        .map_err(|_| ProtoError::from("invalid rdata length in CERT"))?
+        .unverified(/*used only as length safely*/);
+    let cert_data = decoder.read_vec(cert_len)?.unverified(/*the certificate is opaque*/);
+
+    Ok(CERT::new(
+        CertType::from(cert_type),
+        key_tag,
+        algorithm,
+        cert_data,
+    ))
+}
+
+/// Write the RData from the given Decoder
+pub fn emit(encoder: &mut BinEncoder, cert: &CERT) -> ProtoResult<()> {
+    encoder.emit_u16(cert.cert_type.into())?;
+    encoder.emit_u16(cert.key_tag)?;
+    encoder.emit(cert.algorithm)?;
+    encoder.emit_vec(&cert.cert_data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cert_type() {
+        for cert_type in 0..=u16::max_value() {
+            assert_eq!(u16::from(CertType::from(cert_type)), cert_type);
+        }
+
+        assert_eq!(CertType::from_str("IPGP").unwrap(), CertType::IPGP);
+        assert_eq!(CertType::from_str("254").unwrap(), CertType::OID);
+        assert_eq!(
+            CertType::from_str("65280").unwrap(),
+            CertType::Experimental(65280)
+        );
+        assert!(CertType::from_str("X509").is_err());
+    }
+
+    #[test]
+    fn test_emit_and_read() {
+        let rdata = CERT::new(CertType::PGP, 12345, 8, vec![0x99, 0x01, 0x0d, 0x04]);
+
+        let mut bytes = Vec::new();
+        {
+            let mut encoder = BinEncoder::new(&mut bytes);
+            emit(&mut encoder, &rdata).unwrap();
+        }
+        assert_eq!(bytes, b"\x00\x03\x30\x39\x08\x99\x01\x0d\x04");
+
+        let len = bytes.len() as u16;
+        let mut decoder = BinDecoder::new(&bytes);
+        assert_eq!(read(&mut decoder, Restrict::new(len)).unwrap(), rdata);
+        assert!(decoder.is_empty());
+        assert_eq!(rdata.to_string(), "PGP 12345 8 mQENBA==");
+    }
+}
@@ -0,0 +1,216 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! child-to-parent synchronization records
+
+use std::fmt;
+
+use error::*;
+use rr::type_bit_map::{decode_type_bit_maps, encode_bit_maps};
+use rr::RecordType;
+use serialize::binary::*;
+
+/// [RFC 7477, Child-to-Parent Synchronization in DNS, March 2015](https://tools.ietf.org/html/rfc7477#section-2.1.1)
+///
+/// ```text
+/// 2.1.1.  The CSYNC Resource Record Wire Format
+///
+///    The CSYNC RDATA consists of the following fields:
+///
+///                         1 1 1 1 1 1 1 1 1 1 2 2 2 2 2 2 2 2 2 2 3 3
+///     0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///    |                          SOA Serial                           |
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///    |       Flags                   |            Type Bit Map       /
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///    /                     Type Bit Map (continued)                  /
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// ```
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct CSYNC {
+    soa_serial: u32,
+    immediate: bool,
+    soa_minimum: bool,
+    type_bit_maps: Vec<RecordType>,
+}
+
+const IMMEDIATE: u16 = 0b0000_0000_0000_0001;
+const SOA_MINIMUM: u16 = 0b0000_0000_0000_0010;
+
+impl CSYNC {
+    /// Creates a new CSYNC record data
+    ///
+    /// # Arguments
+    ///
+    /// * `soa_serial` - the serial of the child zone's SOA this record was created for
+    /// * `immediate` - the parent may process the record without waiting for the serial to be reached
+    /// * `soa_minimum` - the parent must only process the record if the child's serial is at least `soa_serial`
+    /// * `type_bit_maps` - the types of the records at the child's apex that should be synchronized
+    pub fn new(
+        soa_serial: u32,
+        immediate: bool,
+        soa_minimum: bool,
+        type_bit_maps: Vec<RecordType>,
+    ) -> Self {
+        CSYNC {
+            soa_serial,
+            immediate,
+            soa_minimum,
+            type_bit_maps,
+        }
+    }
+
+    /// ```text
+    /// 2.1.1.1.  The SOA Serial Field
+    ///
+    ///    The SOA Serial field contains a copy of the 32-bit SOA serial number
+    ///    from the child zone.  If the soaminimum flag is set, parental agents
+    ///    querying children's authoritative servers MUST NOT act on data from
+    ///    zones advertising an SOA serial number less than this value.
+    /// ```
+    pub fn soa_serial(&self) -> u32 {
+        self.soa_serial
+    }
+
+    /// ```text
+    ///    o  "immediate": If not set, a parental agent MUST NOT process the
+    ///       CSYNC record until the zone administrator approves the operation
+    ///       through an out-of-band mechanism (such as through pushing a
+    ///       button via a web interface).  If set, a parental agent MAY process
+    ///       the CSYNC record without requiring manual intervention.
+    /// ```
+    pub fn immediate(&self) -> bool {
+        self.immediate
+    }
+
+    /// ```text
+    ///    o  "soaminimum": If set, a parental agent querying child
+    ///       authoritative servers MUST NOT act on data from zones advertising
+    ///       an SOA serial number less than the SOA Serial field.
+    /// ```
+    pub fn soa_minimum(&self) -> bool {
+        self.soa_minimum
+    }
+
+    /// ```text
+    /// 2.1.1.3.  The Type Bit Map Field
+    ///
+    ///    The Type Bit Map field indicates the record types to be processed by
+    ///    the parental agent, according to the procedures in Section 3.  The
+    ///    Type Bit Map field is encoded in the same way as the Type Bit Map
+    ///    field of the NSEC record, described in [RFC4034], Section 4.1.2.
+    /// ```
+    pub fn type_bit_maps(&self) -> &[RecordType] {
+        &self.type_bit_maps
+    }
+
+    fn flags(&self) -> u16 {
+        let mut flags = 0;
+        if self.immediate {
+            flags |= IMMEDIATE;
+        }
+        if self.soa_minimum {
+            flags |= SOA_MINIMUM;
+        }
+        flags
+    }
+}
+
+/// [RFC 7477, Child-to-Parent Synchronization in DNS, March 2015](https://tools.ietf.org/html/rfc7477#section-2.1.2)
+///
+/// ```text
+///    The CSYNC presentation format is as follows:
+///
+///       The SOA Serial field is represented as an unsigned decimal integer.
+///
+///       The Flags field is represented as an unsigned decimal integer.
+///
+///       The Type Bit Map field is represented as a sequence of RR type
+///       mnemonics.
+/// ```
+impl fmt::Display for CSYNC {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.soa_serial, self.flags())?;
+        for rr_type in &self.type_bit_maps {
+            match *rr_type {
+                RecordType::Unknown(code) => write!(f, " TYPE{}", code)?,
+                rr_type => write!(f, " {}", <&'static str>::from(rr_type))?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Read the RData from the given Decoder
+pub fn read(decoder: &mut BinDecoder, rdata_length: Restrict<u16>) -> ProtoResult<CSYNC> {
+    let soa_serial = decoder.read_u32()?.unverified(/*any u32 is valid*/);
+    // unknown flags are ignored, RFC 7477 section 2.1.1.2
+    let flags = decoder.read_u16()?.unverified(/*any u16 is valid*/);
+
+    let bit_map_len = rdata_length
+        .map(|u| u as usize)
+        .checked_sub(6)
+        .map_err(|_| ProtoError::from("invalid rdata length in CSYNC"))?;
+    let type_bit_maps = decode_type_bit_maps(decoder, bit_map_len)?;
+
+    Ok(CSYNC::new(
+        soa_serial,
+        flags & IMMEDIATE == IMMEDIATE,
+        flags & SOA_MINIMUM == SOA_MINIMUM,
+        type_bit_maps,
+    ))
+}
+
+/// Write the RData from the given Decoder
+pub fn emit(encoder: &mut BinEncoder, csync: &CSYNC) -> ProtoResult<()> {
+    encoder.emit_u32(csync.soa_serial)?;
+    encoder.emit_u16(csync.flags())?;
+    encode_bit_maps(encoder, &csync.type_bit_maps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_emit_and_read() {
+        let rdata = CSYNC::new(
+            66,
+            true,
+            true,
+            vec![RecordType::A, RecordType::NS, RecordType::AAAA],
+        );
+
+        let mut bytes = Vec::new();
+        {
+            let mut encoder = BinEncoder::new(&mut bytes);
+            emit(&mut encoder, &rdata).unwrap();
+        }
+        // RFC 7477 section 2.2
+        assert_eq!(
+            bytes,
+            b"\x00\x00\x00\x42\x00\x03\x00\x04\x60\x00\x00\x08".to_vec()
+        );
+
+        let len = bytes.len() as u16;
+        let mut decoder = BinDecoder::new(&bytes);
+        assert_eq!(read(&mut decoder, Restrict::new(len)).unwrap(), rdata);
+        assert!(decoder.is_empty());
+    }
+
+    #[test]
+    fn test_display() {
+        let rdata = CSYNC::new(
+            66,
+            true,
+            true,
+            vec![RecordType::A, RecordType::NS, RecordType::Unknown(65280)],
+        );
+        assert_eq!(rdata.to_string(), "66 3 A NS TYPE65280");
+    }
+}
@@ -0,0 +1,106 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! DHCP identifier records
+
+use std::fmt;
+
+use data_encoding::BASE64;
+
+use error::*;
+use serialize::binary::*;
+
+/// [RFC 4701, A DNS Resource Record (RR) for Encoding DHCP Information, October 2006](https://tools.ietf.org/html/rfc4701#section-3)
+///
+/// ```text
+/// 3.1.  DHCID RDATA Format
+///
+///    The RDATA section of a DHCID RR in transmission contains RDLENGTH
+///    octets of binary data.  The format of this data and its
+///    interpretation by DHCP servers and clients are described below.  DNS
+///    software should consider the RDATA section to be opaque.  DHCP
+///    clients or servers use the DHCID RR to associate a DHCP client's
+///    identity with a DNS name, so that multiple DHCP clients and servers
+///    may deterministically perform dynamic DNS updates to the same zone.
+///
+/// 3.2.  DHCID Presentation Format
+///
+///    In DNS master files, the RDATA is represented as a single block in
+///    base-64 encoding identical to that used for representing binary data
+///    in [3], Section 3.  The data may be divided up into any number of
+///    white-space-separated substrings, each of which is a valid base-64
+///    encoding, that are concatenated to form the complete RDATA.
+/// ```
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct DHCID {
+    digest: Vec<u8>,
+}
+
+impl DHCID {
+    /// Creates a new DHCID record data
+    ///
+    /// # Arguments
+    ///
+    /// * `digest` - the opaque identifier type code, digest type code and digest of the client
+    pub fn new(digest: Vec<u8>) -> Self {
+        DHCID { digest }
+    }
+
+    /// The opaque identifier, the identifier type code and digest type code are followed by the digest
+    pub fn digest(&self) -> &[u8] {
+        &self.digest
+    }
+}
+
+impl fmt::Display for DHCID {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&BASE64.encode(&self.digest))
+    }
+}
+
+/// Read the RData from the given Decoder
+pub fn read(decoder: &mut BinDecoder, rdata_length: Restrict<u16>) -> ProtoResult<DHCID> {
+    let rdata_length = rdata_length.map(|u| u as usize).unverified(/*bounded by read_vec*/);
+    let digest = decoder.read_vec(rdata_length)?.unverified(/*the data is opaque*/);
+
+    Ok(DHCID::new(digest))
+}
+
+/// Write the RData from the given Decoder
+pub fn emit(encoder: &mut BinEncoder, dhcid: &DHCID) -> ProtoResult<()> {
+    encoder.emit_vec(&dhcid.digest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_emit_and_read() {
+        // RFC 4701 section 3.6, example 1
+        let rdata = DHCID::new(
+            BASE64
+                .decode(b"AAIBY2/AuCccgoJbsaxcQc9TUapptP69lOjxfNuVAA2kjEA=")
+                .unwrap(),
+        );
+
+        let mut bytes = Vec::new();
+        {
+            let mut encoder = BinEncoder::new(&mut bytes);
+            emit(&mut encoder, &rdata).unwrap();
+        }
+
+        let len = bytes.len() as u16;
+        let mut decoder = BinDecoder::new(&bytes);
+        assert_eq!(read(&mut decoder, Restrict::new(len)).unwrap(), rdata);
+        assert!(decoder.is_empty());
+        assert_eq!(
+            rdata.to_string(),
+            "AAIBY2/AuCccgoJbsaxcQc9TUapptP69lOjxfNuVAA2kjEA="
+        );
+    }
+}
@@ -0,0 +1,121 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! host information records
+
+use std::fmt;
+
+use super::naptr::write_character_string;
+use error::*;
+use serialize::binary::*;
+
+/// [RFC 1035, DOMAIN NAMES - IMPLEMENTATION AND SPECIFICATION, November 1987](https://tools.ietf.org/html/rfc1035#section-3.3.2)
+///
+/// ```text
+/// 3.3.2. HINFO RDATA format
+///
+///     +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///     /                      CPU                      /
+///     +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///     /                       OS                      /
+///     +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///
+/// where:
+///
+/// CPU             A <character-string> which specifies the CPU type.
+///
+/// OS              A <character-string> which specifies the operating
+///                 system type.
+///
+/// Standard values for CPU and OS can be found in [RFC-1010].
+///
+/// HINFO records are used to acquire general information about a host.  The
+/// main use is for protocols such as FTP that can use special procedures
+/// when talking between machines or operating systems of the same type.
+/// ```
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct HINFO {
+    cpu: Box<[u8]>,
+    os: Box<[u8]>,
+}
+
+impl HINFO {
+    /// Creates a new HINFO record data
+    ///
+    /// # Arguments
+    ///
+    /// * `cpu` - the CPU type of the host
+    /// * `os` - the operating system type of the host
+    pub fn new(cpu: String, os: String) -> Self {
+        HINFO {
+            cpu: cpu.into_bytes().into_boxed_slice(),
+            os: os.into_bytes().into_boxed_slice(),
+        }
+    }
+
+    /// A <character-string> which specifies the CPU type
+    pub fn cpu(&self) -> &[u8] {
+        &self.cpu
+    }
+
+    /// A <character-string> which specifies the operating system type
+    pub fn os(&self) -> &[u8] {
+        &self.os
+    }
+}
+
+/// The CPU and OS are quoted <character-string>s
+impl fmt::Display for HINFO {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_character_string(f, &self.cpu)?;
+        f.write_str(" ")?;
+        write_character_string(f, &self.os)
+    }
+}
+
+/// Read the RData from the given Decoder
+pub fn read(decoder: &mut BinDecoder) -> ProtoResult<HINFO> {
+    let cpu = decoder.read_character_data()?.unverified(/*any data is valid*/);
+    let cpu = cpu.to_vec().into_boxed_slice();
+    let os = decoder.read_character_data()?.unverified(/*any data is valid*/);
+    let os = os.to_vec().into_boxed_slice();
+
+    Ok(HINFO { cpu, os })
+}
+
+/// Write the RData from the given Decoder
+pub fn emit(encoder: &mut BinEncoder, hinfo: &HINFO) -> ProtoResult<()> {
+    encoder.emit_character_data(&hinfo.cpu)?;
+    encoder.emit_character_data(&hinfo.os)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_emit_and_read() {
+        let rdata = HINFO::new("DEC-2060".to_string(), "TOPS20".to_string());
+
+        let mut bytes = Vec::new();
+        {
+            let mut encoder = BinEncoder::new(&mut bytes);
+            emit(&mut encoder, &rdata).unwrap();
+        }
+        assert_eq!(bytes, b"\x08DEC-2060\x06TOPS20");
+
+        let mut decoder = BinDecoder::new(&bytes);
+        assert_eq!(read(&mut decoder).unwrap(), rdata);
+        assert!(decoder.is_empty());
+    }
+
+    #[test]
+    fn test_display() {
+        let rdata = HINFO::new("VAX-11/780".to_string(), "UNIX".to_string());
+        assert_eq!(rdata.to_string(), r#""VAX-11/780" "UNIX""#);
+    }
+}
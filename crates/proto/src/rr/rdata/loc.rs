@@ -0,0 +1,334 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! location records for the geographical position of hosts, networks and subnets
+
+use std::fmt;
+
+use error::*;
+use serialize::binary::*;
+
+/// The latitude and longitude of the equator and prime meridian, 2^31
+pub const LOC_EQUATOR: u32 = 0x8000_0000;
+
+/// The altitude of the WGS 84 reference spheroid, 100,000m above the base of the altitude in centimeters
+pub const LOC_REFERENCE_ALTITUDE: u32 = 10_000_000;
+
+/// [RFC 1876, Location Information in the DNS, January 1996](https://tools.ietf.org/html/rfc1876#section-2)
+///
+/// ```text
+/// 2. RDATA Format
+///
+///        MSB                                           LSB
+///        +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///       0|        VERSION        |         SIZE          |
+///        +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///       2|       HORIZ PRE       |       VERT PRE        |
+///        +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///       4|                   LATITUDE                    |
+///        +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///       6|                   LATITUDE                    |
+///        +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///       8|                   LONGITUDE                   |
+///        +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///      10|                   LONGITUDE                   |
+///        +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///      12|                   ALTITUDE                    |
+///        +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///      14|                   ALTITUDE                    |
+///        +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///
+/// VERSION      Version number of the representation.  This must be zero.
+///              Implementations are required to check this field and make
+///              no assumptions about the format of unrecognized versions.
+/// ```
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct LOC {
+    size: u8,
+    horiz_pre: u8,
+    vert_pre: u8,
+    latitude: u32,
+    longitude: u32,
+    altitude: u32,
+}
+
+impl LOC {
+    /// Creates a new LOC record data from the values of the wire format
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - the encoded diameter of the sphere enclosing the entity, see `precision_from_cm`
+    /// * `horiz_pre` - the encoded horizontal precision, see `precision_from_cm`
+    /// * `vert_pre` - the encoded vertical precision, see `precision_from_cm`
+    /// * `latitude` - the latitude in thousandths of a second of arc, `LOC_EQUATOR` is the equator
+    /// * `longitude` - the longitude in thousandths of a second of arc, `LOC_EQUATOR` is the prime meridian
+    /// * `altitude` - the altitude in centimeters, `LOC_REFERENCE_ALTITUDE` is the WGS 84 reference spheroid
+    pub fn new(
+        size: u8,
+        horiz_pre: u8,
+        vert_pre: u8,
+        latitude: u32,
+        longitude: u32,
+        altitude: u32,
+    ) -> Self {
+        LOC {
+            size,
+            horiz_pre,
+            vert_pre,
+            latitude,
+            longitude,
+            altitude,
+        }
+    }
+
+    /// ```text
+    /// SIZE         The diameter of a sphere enclosing the described entity, in
+    ///              centimeters, expressed as a pair of four-bit unsigned
+    ///              integers, each ranging from zero to nine, with the most
+    ///              significant four bits representing the base and the second
+    ///              number representing the power of ten by which to multiply
+    ///              the base.
+    /// ```
+    pub fn size(&self) -> u8 {
+        self.size
+    }
+
+    /// ```text
+    /// HORIZ PRE    The horizontal precision of the data, in centimeters,
+    ///              expressed using the same representation as SIZE.  This is
+    ///              the diameter of the horizontal "circle of error", rather
+    ///              than a "plus or minus" value.
+    /// ```
+    pub fn horiz_pre(&self) -> u8 {
+        self.horiz_pre
+    }
+
+    /// ```text
+    /// VERT PRE     The vertical precision of the data, in centimeters,
+    ///              expressed using the sane representation as for SIZE.  This
+    ///              is the total potential vertical error, rather than a "plus
+    ///              or minus" value.
+    /// ```
+    pub fn vert_pre(&self) -> u8 {
+        self.vert_pre
+    }
+
+    /// ```text
+    /// LATITUDE     The latitude of the center of the sphere described by the
+    ///              SIZE field, expressed as a 32-bit integer, most significant
+    ///              octet first (network standard byte order), in thousandths
+    ///              of a second of arc.  2^31 represents the equator; numbers
+    ///              above that are north latitude.
+    /// ```
+    pub fn latitude(&self) -> u32 {
+        self.latitude
+    }
+
+    /// ```text
+    /// LONGITUDE    The longitude of the center of the sphere described by the
+    ///              SIZE field, expressed as a 32-bit integer, most significant
+    ///              octet first (network standard byte order), in thousandths
+    ///              of a second of arc, rounded away from the prime meridian.
+    ///              2^31 represents the prime meridian; numbers above that are
+    ///              east longitude.
+    /// ```
+    pub fn longitude(&self) -> u32 {
+        self.longitude
+    }
+
+    /// ```text
+    /// ALTITUDE     The altitude of the center of the sphere described by the
+    ///              SIZE field, expressed as a 32-bit integer, most significant
+    ///              octet first (network standard byte order), in centimeters,
+    ///              from a base of 100,000m below the [WGS 84] reference
+    ///              spheroid used by GPS (semimajor axis a=6378137.0,
+    ///              reciprocal flattening rf=298.257223563).
+    /// ```
+    pub fn altitude(&self) -> u32 {
+        self.altitude
+    }
+}
+
+/// Encodes the centimeters as the base and power of ten of SIZE, HORIZ PRE and VERT PRE, rounding down
+pub fn precision_from_cm(cm: u64) -> ProtoResult<u8> {
+    let mut base = cm;
+    let mut exponent = 0;
+    while base > 9 {
+        base /= 10;
+        exponent += 1;
+    }
+
+    if exponent > 9 {
+        return Err(format!("precision of LOC is too large: {}cm", cm).into());
+    }
+    Ok((base as u8) << 4 | exponent)
+}
+
+/// Decodes the base and power of ten of SIZE, HORIZ PRE and VERT PRE as centimeters
+pub fn precision_to_cm(precision: u8) -> u64 {
+    u64::from(precision >> 4) * 10_u64.pow(u32::from(precision & 0x0F))
+}
+
+fn is_valid_precision(precision: u8) -> bool {
+    precision >> 4 <= 9 && precision & 0x0F <= 9
+}
+
+fn write_angle(f: &mut fmt::Formatter, angle: u32, positive: char, negative: char) -> fmt::Result {
+    let (thousandths, hemisphere) = if angle >= LOC_EQUATOR {
+        (angle - LOC_EQUATOR, positive)
+    } else {
+        (LOC_EQUATOR - angle, negative)
+    };
+
+    write!(
+        f,
+        "{} {} {}.{:03} {}",
+        thousandths / 3_600_000,
+        thousandths % 3_600_000 / 60_000,
+        thousandths % 60_000 / 1000,
+        thousandths % 1000,
+        hemisphere
+    )
+}
+
+fn write_meters(f: &mut fmt::Formatter, cm: u64) -> fmt::Result {
+    if cm % 100 == 0 {
+        write!(f, "{}m", cm / 100)
+    } else {
+        write!(f, "{}.{:02}m", cm / 100, cm % 100)
+    }
+}
+
+/// [RFC 1876, Location Information in the DNS, January 1996](https://tools.ietf.org/html/rfc1876#section-3)
+///
+/// ```text
+/// The LOC record is expressed in a master file in the following format:
+///
+/// <owner> <TTL> <class> LOC ( d1 [m1 [s1]] {"N"|"S"} d2 [m2 [s2]]
+///                             {"E"|"W"} alt["m"] [siz["m"] [hp["m"]
+///                             [vp["m"]]]] )
+/// ```
+impl fmt::Display for LOC {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_angle(f, self.latitude, 'N', 'S')?;
+        f.write_str(" ")?;
+        write_angle(f, self.longitude, 'E', 'W')?;
+
+        let altitude = i64::from(self.altitude) - i64::from(LOC_REFERENCE_ALTITUDE);
+        let sign = if altitude < 0 { "-" } else { "" };
+        let altitude = altitude.abs();
+        write!(f, " {}{}.{:02}m ", sign, altitude / 100, altitude % 100)?;
+
+        write_meters(f, precision_to_cm(self.size))?;
+        f.write_str(" ")?;
+        write_meters(f, precision_to_cm(self.horiz_pre))?;
+        f.write_str(" ")?;
+        write_meters(f, precision_to_cm(self.vert_pre))
+    }
+}
+
+/// Read the RData from the given Decoder
+pub fn read(decoder: &mut BinDecoder) -> ProtoResult<LOC> {
+    decoder
+        .read_u8()?
+        .verify_unwrap(|version| *version == 0)
+        .map_err(|version| ProtoError::from(format!("unsupported LOC version: {}", version)))?;
+
+    let mut read_precision = || {
+        decoder
+            .read_u8()?
+            .verify_unwrap(|precision| is_valid_precision(*precision))
+            .map_err(|precision| ProtoError::from(format!("invalid LOC precision: {}", precision)))
+    };
+    let size = read_precision()?;
+    let horiz_pre = read_precision()?;
+    let vert_pre = read_precision()?;
+
+    let latitude = decoder.read_u32()?.unverified(/*any u32 is valid*/);
+    let longitude = decoder.read_u32()?.unverified(/*any u32 is valid*/);
+    let altitude = decoder.read_u32()?.unverified(/*any u32 is valid*/);
+
+    Ok(LOC::new(
+        size, horiz_pre, vert_pre, latitude, longitude, altitude,
+    ))
+}
+
+/// Write the RData from the given Decoder
+pub fn emit(encoder: &mut BinEncoder, loc: &LOC) -> ProtoResult<()> {
+    // the version
+    encoder.emit(0)?;
+    encoder.emit(loc.size)?;
+    encoder.emit(loc.horiz_pre)?;
+    encoder.emit(loc.vert_pre)?;
+    encoder.emit_u32(loc.latitude)?;
+    encoder.emit_u32(loc.longitude)?;
+    encoder.emit_u32(loc.altitude)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 1876 section 4, cambridge-net.kei.com. LOC 42 21 54 N 71 06 18 W -24m 30m
+    fn loc() -> LOC {
+        LOC::new(
+            0x33,
+            0x16,
+            0x13,
+            LOC_EQUATOR + ((42 * 60 + 21) * 60 + 54) * 1000,
+            LOC_EQUATOR - ((71 * 60 + 6) * 60 + 18) * 1000,
+            LOC_REFERENCE_ALTITUDE - 2400,
+        )
+    }
+
+    #[test]
+    fn test_emit_and_read() {
+        let rdata = loc();
+
+        let mut bytes = Vec::new();
+        {
+            let mut encoder = BinEncoder::new(&mut bytes);
+            emit(&mut encoder, &rdata).unwrap();
+        }
+        assert_eq!(bytes.len(), 16);
+
+        let mut decoder = BinDecoder::new(&bytes);
+        assert_eq!(read(&mut decoder).unwrap(), rdata);
+        assert!(decoder.is_empty());
+    }
+
+    #[test]
+    fn test_read_invalid() {
+        let mut bytes = vec![1, 0x12, 0x16, 0x13];
+        bytes.extend_from_slice(&[0; 12]);
+        assert!(read(&mut BinDecoder::new(&bytes)).is_err());
+
+        bytes[0] = 0;
+        bytes[1] = 0x1a;
+        assert!(read(&mut BinDecoder::new(&bytes)).is_err());
+    }
+
+    #[test]
+    fn test_precision() {
+        assert_eq!(precision_from_cm(3000).unwrap(), 0x33);
+        assert_eq!(precision_from_cm(1_000_000).unwrap(), 0x16);
+        assert_eq!(precision_from_cm(1).unwrap(), 0x10);
+        assert_eq!(precision_from_cm(0).unwrap(), 0x00);
+        assert!(precision_from_cm(10_000_000_000).is_err());
+
+        assert_eq!(precision_to_cm(0x33), 3000);
+        assert_eq!(precision_to_cm(0x99), 9_000_000_000);
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            loc().to_string(),
+            "42 21 54.000 N 71 6 18.000 W -24.00m 30m 10000m 10m"
+        );
+    }
+}
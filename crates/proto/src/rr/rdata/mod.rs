@@ -22,24 +22,44 @@
 pub mod a;
 pub mod aaaa;
 pub mod caa;
+pub mod cert;
+pub mod csync;
+pub mod dhcid;
+pub mod hinfo;
+pub mod loc;
 pub mod mx;
 pub mod name;
+pub mod naptr;
 pub mod null;
 pub mod openpgpkey;
 pub mod opt;
+pub mod rp;
 pub mod soa;
 pub mod srv;
 pub mod sshfp;
+pub mod svcb;
 pub mod tlsa;
 pub mod txt;
+pub mod uri;
+pub mod zonemd;
 
 pub use self::caa::CAA;
+pub use self::cert::CERT;
+pub use self::csync::CSYNC;
+pub use self::dhcid::DHCID;
+pub use self::hinfo::HINFO;
+pub use self::loc::LOC;
 pub use self::mx::MX;
+pub use self::naptr::NAPTR;
 pub use self::null::NULL;
 pub use self::openpgpkey::OPENPGPKEY;
 pub use self::opt::OPT;
+pub use self::rp::RP;
 pub use self::soa::SOA;
 pub use self::srv::SRV;
 pub use self::sshfp::SSHFP;
+pub use self::svcb::SVCB;
 pub use self::tlsa::TLSA;
 pub use self::txt::TXT;
+pub use self::uri::URI;
+pub use self::zonemd::ZONEMD;
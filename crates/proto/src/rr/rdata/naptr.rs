@@ -0,0 +1,298 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! NAPTR records for the rules of the Dynamic Delegation Discovery System (DDDS),
+//!  see [RFC 3403](https://tools.ietf.org/html/rfc3403)
+
+use std::fmt;
+
+use error::*;
+use rr::domain::Name;
+use serialize::binary::*;
+
+/// [RFC 3403, DDDS Part Three: The DNS Database, October 2002](https://tools.ietf.org/html/rfc3403#section-4)
+///
+/// ```text
+/// 4.1 Packet Format
+///
+///   The packet format of the NAPTR RR is given below.  The DNS type code
+///   for NAPTR is 35.
+///
+///      The packet format for the NAPTR record is as follows
+///                                       1  1  1  1  1  1
+///         0  1  2  3  4  5  6  7  8  9  0  1  2  3  4  5
+///       +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///       |                     ORDER                     |
+///       +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///       |                   PREFERENCE                  |
+///       +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///       /                     FLAGS                     /
+///       +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///       /                   SERVICES                    /
+///       +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///       /                    REGEXP                     /
+///       +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///       /                  REPLACEMENT                  /
+///       /                                               /
+///       +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///
+///   <character-string> and <domain-name> as used here are defined in RFC
+///   1035 [7].
+/// ```
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct NAPTR {
+    order: u16,
+    preference: u16,
+    flags: Box<[u8]>,
+    services: Box<[u8]>,
+    regexp: Box<[u8]>,
+    replacement: Name,
+}
+
+impl NAPTR {
+    /// Creates a new NAPTR record data
+    ///
+    /// # Arguments
+    ///
+    /// * `order` - the order in which the records must be processed, lowest first
+    /// * `preference` - the order in which records of equal order should be processed, lowest first
+    /// * `flags` - the flags which control the rewriting and interpretation of the fields, e.g. `S`, `A` or `U`
+    /// * `services` - the services available down the rewrite path, e.g. `E2U+sip`
+    /// * `regexp` - the substitution expression applied to the original string, empty if `replacement` is used
+    /// * `replacement` - the next domain name to query, the root if `regexp` is used
+    pub fn new(
+        order: u16,
+        preference: u16,
+        flags: Box<[u8]>,
+        services: Box<[u8]>,
+        regexp: Box<[u8]>,
+        replacement: Name,
+    ) -> Self {
+        NAPTR {
+            order,
+            preference,
+            flags,
+            services,
+            regexp,
+            replacement,
+        }
+    }
+
+    /// ```text
+    ///   ORDER
+    ///      A 16-bit unsigned integer specifying the order in which the NAPTR
+    ///      records MUST be processed in order to accurately represent the
+    ///      ordered list of Rules.
+    /// ```
+    pub fn order(&self) -> u16 {
+        self.order
+    }
+
+    /// ```text
+    ///   PREFERENCE
+    ///      Although it is called "preference" in deference to DNS
+    ///      terminology, this field is equivalent to the Priority value in the
+    ///      DDDS Algorithm.  It is a 16-bit unsigned integer that specifies
+    ///      the order in which NAPTR records with equal Order values SHOULD be
+    ///      processed, low numbers being processed before high numbers.
+    /// ```
+    pub fn preference(&self) -> u16 {
+        self.preference
+    }
+
+    /// ```text
+    ///   FLAGS
+    ///      A <character-string> containing flags to control aspects of the
+    ///      rewriting and interpretation of the fields in the record.  Flags
+    ///      are single characters from the set A-Z and 0-9.
+    /// ```
+    pub fn flags(&self) -> &[u8] {
+        &self.flags
+    }
+
+    /// ```text
+    ///   SERVICES
+    ///      A <character-string> that specifies the Service Parameters
+    ///      applicable to this this delegation path.
+    /// ```
+    pub fn services(&self) -> &[u8] {
+        &self.services
+    }
+
+    /// ```text
+    ///   REGEXP
+    ///      A <character-string> containing a substitution expression that is
+    ///      applied to the original string held by the client in order to
+    ///      construct the next domain name to lookup.
+    /// ```
+    pub fn regexp(&self) -> &[u8] {
+        &self.regexp
+    }
+
+    /// ```text
+    ///   REPLACEMENT
+    ///      A <domain-name> which is the next domain-name to query for
+    ///      depending on the potential values found in the flags field.  This
+    ///      field is used when the regular expression is a simple replacement
+    ///      operation.
+    /// ```
+    pub fn replacement(&self) -> &Name {
+        &self.replacement
+    }
+
+    /// Returns true if the flags contain the flag, flags are case insensitive
+    pub fn has_flag(&self, flag: u8) -> bool {
+        self.flags.iter().any(|f| f.eq_ignore_ascii_case(&flag))
+    }
+
+    /// Returns true if the record is terminal, i.e. one of the `S`, `A`, `U` or `P` flags is set
+    pub fn is_terminal(&self) -> bool {
+        self.flags
+            .iter()
+            .any(|f| b"SAUP".contains(&f.to_ascii_uppercase()))
+    }
+}
+
+/// Writes the character-string in the presentation format, quoted with `"` and `\` escaped
+pub(crate) fn write_character_string(f: &mut fmt::Formatter, data: &[u8]) -> fmt::Result {
+    f.write_str("\"")?;
+    for &b in data {
+        match b {
+            b'"' | b'\\' => write!(f, "\\{}", b as char)?,
+            0x20...0x7e => write!(f, "{}", b as char)?,
+            _ => write!(f, "\\{:03}", b)?,
+        }
+    }
+    f.write_str("\"")
+}
+
+/// [RFC 3403, DDDS Part Three: The DNS Database, October 2002](https://tools.ietf.org/html/rfc3403#section-4.1)
+///
+/// ```text
+///   The master file format follows the standard rules in RFC-1035 [1].
+///   Order and preference, being 16-bit unsigned integers, shall be an
+///   integer between 0 and 65535.  The Flags and Services and Regexp
+///   fields are all quoted <character-string>s.
+/// ```
+impl fmt::Display for NAPTR {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} ", self.order, self.preference)?;
+        write_character_string(f, &self.flags)?;
+        f.write_str(" ")?;
+        write_character_string(f, &self.services)?;
+        f.write_str(" ")?;
+        write_character_string(f, &self.regexp)?;
+        write!(f, " {}", self.replacement)
+    }
+}
+
+/// Read the RData from the given Decoder
+pub fn read(decoder: &mut BinDecoder) -> ProtoResult<NAPTR> {
+    let order = decoder.read_u16()?.unverified(/*any u16 is valid*/);
+    let preference = decoder.read_u16()?.unverified(/*any u16 is valid*/);
+
+    let flags = decoder
+        .read_character_data()?
+        .verify_unwrap(|flags| flags.iter().all(u8::is_ascii_alphanumeric))
+        .map_err(|_| ProtoError::from("flags of NAPTR are not alphanumeric"))?
+        .to_vec()
+        .into_boxed_slice();
+    let services = decoder.read_character_data()?.unverified(/*any services are valid*/);
+    let services = services.to_vec().into_boxed_slice();
+    let regexp = decoder.read_character_data()?.unverified(/*verified when applied*/);
+    let regexp = regexp.to_vec().into_boxed_slice();
+    let replacement = Name::read(decoder)?;
+
+    Ok(NAPTR::new(
+        order,
+        preference,
+        flags,
+        services,
+        regexp,
+        replacement,
+    ))
+}
+
+/// Write the RData from the given Decoder
+///
+/// The replacement is never compressed, RFC 3403 section 4.1, and is lowercased in the canonical form, RFC 4034
+///  section 6.2.
+pub fn emit(encoder: &mut BinEncoder, naptr: &NAPTR) -> ProtoResult<()> {
+    encoder.emit_u16(naptr.order)?;
+    encoder.emit_u16(naptr.preference)?;
+    encoder.emit_character_data(&naptr.flags)?;
+    encoder.emit_character_data(&naptr.services)?;
+    encoder.emit_character_data(&naptr.regexp)?;
+    if encoder.is_canonical_names() {
+        naptr
+            .replacement
+            .to_lowercase()
+            .emit_as_canonical(encoder, true)
+    } else {
+        naptr.replacement.emit_as_canonical(encoder, true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn naptr(flags: &str, services: &str, regexp: &str, replacement: &str) -> NAPTR {
+        NAPTR::new(
+            100,
+            10,
+            flags.as_bytes().to_vec().into_boxed_slice(),
+            services.as_bytes().to_vec().into_boxed_slice(),
+            regexp.as_bytes().to_vec().into_boxed_slice(),
+            Name::from_str(replacement).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_emit_and_read() {
+        for rdata in vec![
+            naptr("S", "SIP+D2U", "", "_sip._udp.example.com."),
+            naptr("u", "E2U+sip", "!^.*$!sip:info@example.com!", "."),
+            naptr("", "", "", "example.com."),
+        ] {
+            let mut bytes = Vec::new();
+            {
+                let mut encoder = BinEncoder::new(&mut bytes);
+                emit(&mut encoder, &rdata).unwrap();
+            }
+
+            let mut decoder = BinDecoder::new(&bytes);
+            assert_eq!(read(&mut decoder).unwrap(), rdata);
+            assert!(decoder.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_read_invalid_flags() {
+        let bytes = b"\x00\x64\x00\x0a\x02S+\x00\x00\x00";
+        assert!(read(&mut BinDecoder::new(bytes)).is_err());
+    }
+
+    #[test]
+    fn test_flags() {
+        let rdata = naptr("s", "SIP+D2U", "", "_sip._udp.example.com.");
+        assert!(rdata.has_flag(b'S'));
+        assert!(!rdata.has_flag(b'U'));
+        assert!(rdata.is_terminal());
+        assert!(!naptr("", "", "", "example.com.").is_terminal());
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            naptr("u", "E2U+sip", "!^.*$!sip:\"i\"@example.com!", ".").to_string(),
+            r#"100 10 "u" "E2U+sip" "!^.*$!sip:\"i\"@example.com!" ."#
+        );
+    }
+}
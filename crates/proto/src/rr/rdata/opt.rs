@@ -17,6 +17,7 @@
 //! option record for passing protocol options between the client and server
 
 use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 
 use error::*;
 use serialize::binary::*;
@@ -193,6 +194,11 @@ impl OPT {
     pub fn insert(&mut self, option: EdnsOption) {
         self.options.insert((&option).into(), option);
     }
+
+    /// Remove the option with the code, returning it if it was present
+    pub fn remove(&mut self, code: EdnsCode) -> Option<EdnsOption> {
+        self.options.remove(&code)
+    }
 }
 
 /// Read the RData from the given Decoder
@@ -306,7 +312,7 @@ pub enum EdnsCode {
     /// [RFC 6975, NSEC3 Hash Understood](https://tools.ietf.org/html/rfc6975)
     N3U,
 
-    /// [edns-client-subnet, Optional](https://tools.ietf.org/html/draft-vandergaast-edns-client-subnet-02)
+    /// [RFC 7871, Client Subnet, Optional](https://tools.ietf.org/html/rfc7871)
     Subnet,
 
     /// [RFC 7314, EDNS EXPIRE, Optional](https://tools.ietf.org/html/rfc7314)
@@ -392,6 +398,9 @@ pub enum EdnsOption {
     #[cfg(feature = "dnssec")]
     N3U(SupportedAlgorithms),
 
+    /// [RFC 7871, Client Subnet, Optional](https://tools.ietf.org/html/rfc7871)
+    Subnet(ClientSubnet),
+
     /// Unknown, used to deal with unknown or unsupported codes
     Unknown(u16, Vec<u8>),
 }
@@ -404,6 +413,7 @@ impl EdnsOption {
             EdnsOption::DAU(ref algorithms)
             | EdnsOption::DHU(ref algorithms)
             | EdnsOption::N3U(ref algorithms) => algorithms.len(),
+            EdnsOption::Subnet(ref subnet) => subnet.len(),
             EdnsOption::Unknown(_, ref data) => data.len() as u16, // TODO: should we verify?
         }
     }
@@ -415,6 +425,7 @@ impl EdnsOption {
             EdnsOption::DAU(ref algorithms)
             | EdnsOption::DHU(ref algorithms)
             | EdnsOption::N3U(ref algorithms) => algorithms.is_empty(),
+            EdnsOption::Subnet(..) => false,
             EdnsOption::Unknown(_, ref data) => data.is_empty(),
         }
     }
@@ -427,6 +438,7 @@ impl BinEncodable for EdnsOption {
             EdnsOption::DAU(ref algorithms)
             | EdnsOption::DHU(ref algorithms)
             | EdnsOption::N3U(ref algorithms) => algorithms.emit(encoder),
+            EdnsOption::Subnet(ref subnet) => subnet.emit(encoder),
             EdnsOption::Unknown(_, ref data) => encoder.emit_vec(data), // gah, clone needed or make a crazy api.
         }
     }
@@ -442,6 +454,17 @@ impl<'a> From<(EdnsCode, &'a [u8])> for EdnsOption {
             EdnsCode::DHU => EdnsOption::DHU(value.1.into()),
             #[cfg(feature = "dnssec")]
             EdnsCode::N3U => EdnsOption::N3U(value.1.into()),
+            EdnsCode::Subnet => {
+                let mut decoder = BinDecoder::new(value.1);
+                match ClientSubnet::read(&mut decoder) {
+                    Ok(subnet) if decoder.is_empty() => EdnsOption::Subnet(subnet),
+                    // malformed options are kept as is, servers answer them with FORMERR
+                    _ => {
+                        warn!("invalid client subnet option: {:?}", value.1);
+                        EdnsOption::Unknown(value.0.into(), value.1.to_vec())
+                    }
+                }
+            }
             _ => EdnsOption::Unknown(value.0.into(), value.1.to_vec()),
         }
     }
@@ -454,6 +477,16 @@ impl<'a> From<&'a EdnsOption> for Vec<u8> {
             EdnsOption::DAU(ref algorithms)
             | EdnsOption::DHU(ref algorithms)
             | EdnsOption::N3U(ref algorithms) => algorithms.into(),
+            EdnsOption::Subnet(ref subnet) => {
+                let mut bytes = Vec::with_capacity(subnet.len() as usize);
+                {
+                    let mut encoder = BinEncoder::new(&mut bytes);
+                    subnet
+                        .emit(&mut encoder)
+                        .expect("client subnet is always encodable"); // valid panic, encoding into a Vec never fails
+                }
+                bytes
+            }
             EdnsOption::Unknown(_, ref data) => data.clone(), // gah, clone needed or make a crazy api.
         }
     }
@@ -468,11 +501,203 @@ impl<'a> From<&'a EdnsOption> for EdnsCode {
             EdnsOption::DHU(..) => EdnsCode::DHU,
             #[cfg(feature = "dnssec")]
             EdnsOption::N3U(..) => EdnsCode::N3U,
+            EdnsOption::Subnet(..) => EdnsCode::Subnet,
             EdnsOption::Unknown(code, _) => EdnsCode::Unknown(code),
         }
     }
 }
 
+/// [RFC 7871, Client Subnet in DNS Queries, May 2016](https://tools.ietf.org/html/rfc7871#section-6)
+///
+/// ```text
+/// 6.  Option Format
+///
+///    This protocol uses an EDNS0 [RFC6891] option to include client
+///    address information in DNS messages.  The option is structured as
+///    follows:
+///
+///                 +0 (MSB)                            +1 (LSB)
+///       +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///    0: |                          OPTION-CODE                          |
+///       +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///    2: |                         OPTION-LENGTH                         |
+///       +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///    4: |                            FAMILY                             |
+///       +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///    6: |     SOURCE PREFIX-LENGTH      |     SCOPE PREFIX-LENGTH       |
+///       +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///    8: |                           ADDRESS...                          /
+///       +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+/// ```
+///
+/// The address is always truncated to the source prefix length, the family is derived from it.
+#[derive(Debug, PartialOrd, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct ClientSubnet {
+    address: IpAddr,
+    source_prefix: u8,
+    scope_prefix: u8,
+}
+
+impl ClientSubnet {
+    /// Creates a new client subnet
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - an address of the client, it is truncated to `source_prefix` bits
+    /// * `source_prefix` - the number of leading bits of the address to send, at most 32 or 128
+    /// * `scope_prefix` - the number of leading bits the answer applies to, 0 in queries
+    pub fn new(address: IpAddr, source_prefix: u8, scope_prefix: u8) -> Self {
+        let max_prefix = max_prefix(address);
+        let source_prefix = source_prefix.min(max_prefix);
+        ClientSubnet {
+            address: truncate(address, source_prefix),
+            source_prefix,
+            scope_prefix: scope_prefix.min(max_prefix),
+        }
+    }
+
+    /// The address family, 1 for IPv4 and 2 for IPv6
+    pub fn family(&self) -> u16 {
+        match self.address {
+            IpAddr::V4(..) => 1,
+            IpAddr::V6(..) => 2,
+        }
+    }
+
+    /// The address of the client, truncated to the source prefix length
+    pub fn address(&self) -> IpAddr {
+        self.address
+    }
+
+    /// ```text
+    /// SOURCE PREFIX-LENGTH, an unsigned octet representing the leftmost
+    ///    number of significant bits of ADDRESS to be used for the lookup.
+    ///    In responses, it mirrors the same value as in the queries.
+    /// ```
+    pub fn source_prefix(&self) -> u8 {
+        self.source_prefix
+    }
+
+    /// ```text
+    /// SCOPE PREFIX-LENGTH, an unsigned octet representing the leftmost
+    ///    number of significant bits of ADDRESS that the response covers.
+    ///    In queries, it MUST be set to 0.
+    /// ```
+    pub fn scope_prefix(&self) -> u8 {
+        self.scope_prefix
+    }
+
+    /// Returns this subnet with the scope prefix length of a response
+    pub fn with_scope_prefix(self, scope_prefix: u8) -> Self {
+        ClientSubnet::new(self.address, self.source_prefix, scope_prefix)
+    }
+
+    /// Returns true if the other subnet is within this one, i.e. its source prefix is at least as long and the
+    ///  addresses share the source prefix of this subnet
+    pub fn contains(&self, other: &ClientSubnet) -> bool {
+        self.family() == other.family()
+            && other.source_prefix >= self.source_prefix
+            && truncate(other.address, self.source_prefix) == self.address
+    }
+
+    /// Returns the length in bytes of the option data
+    fn len(&self) -> u16 {
+        4 + u16::from(address_len(self.source_prefix))
+    }
+}
+
+impl BinEncodable for ClientSubnet {
+    fn emit(&self, encoder: &mut BinEncoder) -> ProtoResult<()> {
+        encoder.emit_u16(self.family())?;
+        encoder.emit(self.source_prefix)?;
+        encoder.emit(self.scope_prefix)?;
+
+        let len = address_len(self.source_prefix) as usize;
+        match self.address {
+            IpAddr::V4(ip) => encoder.emit_vec(&ip.octets()[..len]),
+            IpAddr::V6(ip) => encoder.emit_vec(&ip.octets()[..len]),
+        }
+    }
+}
+
+impl<'r> BinDecodable<'r> for ClientSubnet {
+    fn read(decoder: &mut BinDecoder<'r>) -> ProtoResult<Self> {
+        let family = decoder.read_u16()?.unverified(/*verified in the match*/);
+        let max_prefix = match family {
+            1 => 32,
+            2 => 128,
+            _ => return Err(format!("unsupported client subnet family: {}", family).into()),
+        };
+
+        let source_prefix = decoder
+            .read_u8()?
+            .verify_unwrap(|prefix| *prefix <= max_prefix)
+            .map_err(|prefix| ProtoError::from(format!("invalid source prefix: {}", prefix)))?;
+        let scope_prefix = decoder
+            .read_u8()?
+            .verify_unwrap(|prefix| *prefix <= max_prefix)
+            .map_err(|prefix| ProtoError::from(format!("invalid scope prefix: {}", prefix)))?;
+
+        // RFC 7871 section 6, the address must not be longer than the source prefix
+        let len = address_len(source_prefix) as usize;
+        let bytes = decoder.read_slice(len)?.unverified(/*verified below*/);
+        let address = if family == 1 {
+            let mut octets = [0u8; 4];
+            octets[..len].copy_from_slice(bytes);
+            IpAddr::V4(Ipv4Addr::from(octets))
+        } else {
+            let mut octets = [0u8; 16];
+            octets[..len].copy_from_slice(bytes);
+            IpAddr::V6(Ipv6Addr::from(octets))
+        };
+
+        // and the bits beyond the source prefix must be 0
+        if truncate(address, source_prefix) != address {
+            return Err(format!(
+                "client subnet address longer than its source prefix: {}/{}",
+                address, source_prefix
+            )
+            .into());
+        }
+
+        Ok(ClientSubnet {
+            address,
+            source_prefix,
+            scope_prefix,
+        })
+    }
+}
+
+fn max_prefix(address: IpAddr) -> u8 {
+    match address {
+        IpAddr::V4(..) => 32,
+        IpAddr::V6(..) => 128,
+    }
+}
+
+/// The number of octets needed for the prefix
+fn address_len(prefix: u8) -> u8 {
+    (prefix + 7) / 8
+}
+
+/// Sets all bits after the first `prefix` bits of the address to 0
+fn truncate(address: IpAddr, prefix: u8) -> IpAddr {
+    match address {
+        IpAddr::V4(ip) => {
+            let mask = u32::max_value()
+                .checked_shl(32 - u32::from(prefix))
+                .unwrap_or(0);
+            IpAddr::V4(Ipv4Addr::from(u32::from(ip) & mask))
+        }
+        IpAddr::V6(ip) => {
+            let mask = u128::max_value()
+                .checked_shl(128 - u32::from(prefix))
+                .unwrap_or(0);
+            IpAddr::V6(Ipv6Addr::from(u128::from(ip) & mask))
+        }
+    }
+}
+
 #[test]
 #[cfg(feature = "dnssec")]
 pub fn test() {
@@ -494,3 +719,77 @@ pub fn test() {
     );
     assert_eq!(rdata, read_rdata.unwrap());
 }
+
+#[test]
+pub fn test_client_subnet() {
+    let subnet = ClientSubnet::new("192.0.2.255".parse().unwrap(), 24, 0);
+    assert_eq!(subnet.address(), "192.0.2.0".parse::<IpAddr>().unwrap());
+    assert_eq!(subnet.family(), 1);
+
+    let mut rdata = OPT::default();
+    rdata.insert(EdnsOption::Subnet(subnet));
+
+    let mut bytes = Vec::new();
+    {
+        let mut encoder = BinEncoder::new(&mut bytes);
+        emit(&mut encoder, &rdata).unwrap();
+    }
+    assert_eq!(
+        bytes,
+        b"\x00\x08\x00\x07\x00\x01\x18\x00\xc0\x00\x02".to_vec()
+    );
+
+    let mut decoder = BinDecoder::new(&bytes);
+    assert_eq!(
+        read(&mut decoder, Restrict::new(bytes.len() as u16)).unwrap(),
+        rdata
+    );
+
+    let subnet = ClientSubnet::new("2001:db8::1".parse().unwrap(), 56, 0).with_scope_prefix(48);
+    let bytes: Vec<u8> = (&EdnsOption::Subnet(subnet)).into();
+    assert_eq!(
+        bytes,
+        b"\x00\x02\x38\x30\x20\x01\x0d\xb8\x00\x00\x00".to_vec()
+    );
+    assert_eq!(
+        EdnsOption::from((EdnsCode::Subnet, &bytes as &[u8])),
+        EdnsOption::Subnet(subnet)
+    );
+}
+
+#[test]
+pub fn test_client_subnet_invalid() {
+    // the address is longer than the source prefix
+    let option = EdnsOption::from((EdnsCode::Subnet, b"\x00\x01\x10\x00\xc0\x00\x02" as &[u8]));
+    assert_eq!(
+        option,
+        EdnsOption::Unknown(8, b"\x00\x01\x10\x00\xc0\x00\x02".to_vec())
+    );
+
+    // bits beyond the source prefix are set
+    let option = EdnsOption::from((EdnsCode::Subnet, b"\x00\x01\x17\x00\xc0\x00\x03" as &[u8]));
+    assert_eq!(
+        option,
+        EdnsOption::Unknown(8, b"\x00\x01\x17\x00\xc0\x00\x03".to_vec())
+    );
+
+    // unknown family
+    let option = EdnsOption::from((EdnsCode::Subnet, b"\x00\x03\x00\x00" as &[u8]));
+    assert_eq!(option, EdnsOption::Unknown(8, b"\x00\x03\x00\x00".to_vec()));
+}
+
+#[test]
+pub fn test_client_subnet_contains() {
+    let scope = ClientSubnet::new("192.0.2.0".parse().unwrap(), 16, 0);
+    assert!(scope.contains(&ClientSubnet::new("192.0.3.1".parse().unwrap(), 24, 0)));
+    assert!(!scope.contains(&ClientSubnet::new("192.1.3.1".parse().unwrap(), 24, 0)));
+    assert!(!scope.contains(&ClientSubnet::new("192.0.3.1".parse().unwrap(), 8, 0)));
+    assert!(!scope.contains(&ClientSubnet::new("::".parse().unwrap(), 24, 0)));
+    assert!(
+        ClientSubnet::new("::".parse().unwrap(), 0, 0).contains(&ClientSubnet::new(
+            "2001:db8::".parse().unwrap(),
+            56,
+            0
+        ))
+    );
+}
@@ -0,0 +1,134 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! responsible person records
+
+use std::fmt;
+
+use error::*;
+use rr::domain::Name;
+use serialize::binary::*;
+
+/// [RFC 1183, New DNS RR Definitions, October 1990](https://tools.ietf.org/html/rfc1183#section-2.2)
+///
+/// ```text
+/// 2.2. The Responsible Person RR
+///
+///    The method uses a new RR type with mnemonic RP and type code of 17
+///    (decimal).
+///
+///    RP has the following format:
+///
+///    <owner> <ttl> <class> RP <mbox-dname> <txt-dname>
+///
+///    Both RDATA fields are required in all RP RRs.
+///
+///    The first field, <mbox-dname>, is a domain name that specifies the
+///    mailbox for the responsible person.  Its format in master files uses
+///    the DNS convention for mailbox encoding, identical to that used for
+///    the RNAME mailbox field in the SOA RR.  The root domain name (just
+///    ".") may be specified for <mbox-dname> to indicate that no mailbox is
+///    available.
+///
+///    The second field, <txt-dname>, is a domain name for which TXT RR's
+///    exist.  A subsequent query can be performed to retrieve the
+///    associated TXT resource records at <txt-dname>.  This provides a
+///    level of indirection so that the entity can be referred to from
+///    multiple places in the DNS.  The root domain name (just ".") may be
+///    specified for <txt-dname> to indicate that no associated TXT RR
+///    exists.
+/// ```
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct RP {
+    mbox: Name,
+    txt: Name,
+}
+
+impl RP {
+    /// Creates a new RP record data
+    ///
+    /// # Arguments
+    ///
+    /// * `mbox` - the mailbox of the responsible person, the root if there is none
+    /// * `txt` - the name of the TXT records describing the person, the root if there are none
+    pub fn new(mbox: Name, txt: Name) -> Self {
+        RP { mbox, txt }
+    }
+
+    /// The mailbox of the responsible person, encoded like the RNAME of the SOA
+    pub fn mbox(&self) -> &Name {
+        &self.mbox
+    }
+
+    /// The name with the TXT records associated with the responsible person
+    pub fn txt(&self) -> &Name {
+        &self.txt
+    }
+}
+
+impl fmt::Display for RP {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.mbox, self.txt)
+    }
+}
+
+/// Read the RData from the given Decoder
+pub fn read(decoder: &mut BinDecoder) -> ProtoResult<RP> {
+    Ok(RP::new(Name::read(decoder)?, Name::read(decoder)?))
+}
+
+/// Write the RData from the given Decoder
+///
+/// The names are never compressed, RFC 3597 section 4, and are lowercased in the canonical form, RFC 4034
+///  section 6.2.
+pub fn emit(encoder: &mut BinEncoder, rp: &RP) -> ProtoResult<()> {
+    if encoder.is_canonical_names() {
+        rp.mbox.to_lowercase().emit_as_canonical(encoder, true)?;
+        rp.txt.to_lowercase().emit_as_canonical(encoder, true)
+    } else {
+        rp.mbox.emit_as_canonical(encoder, true)?;
+        rp.txt.emit_as_canonical(encoder, true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn test_emit_and_read() {
+        let rdata = RP::new(
+            Name::from_str("Louie.trantor.umd.edu.").unwrap(),
+            Name::from_str("LAM1.people.umd.edu.").unwrap(),
+        );
+
+        let mut bytes = Vec::new();
+        {
+            let mut encoder = BinEncoder::new(&mut bytes);
+            emit(&mut encoder, &rdata).unwrap();
+        }
+
+        let mut decoder = BinDecoder::new(&bytes);
+        assert_eq!(read(&mut decoder).unwrap(), rdata);
+        assert!(decoder.is_empty());
+    }
+
+    #[test]
+    fn test_emit_canonical() {
+        let rdata = RP::new(Name::from_str("Louie.UMD.edu.").unwrap(), Name::root());
+
+        let mut bytes = Vec::new();
+        {
+            let mut encoder = BinEncoder::new(&mut bytes);
+            encoder.set_canonical_names(true);
+            emit(&mut encoder, &rdata).unwrap();
+        }
+        assert_eq!(bytes, b"\x05louie\x03umd\x03edu\x00\x00");
+    }
+}
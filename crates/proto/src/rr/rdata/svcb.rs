@@ -0,0 +1,594 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! SVCB and HTTPS records for service binding, see [RFC 9460](https://tools.ietf.org/html/rfc9460)
+
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use data_encoding::BASE64;
+
+use error::*;
+use rr::domain::Name;
+use serialize::binary::*;
+
+/// [RFC 9460, SVCB and HTTPS RRs](https://tools.ietf.org/html/rfc9460#section-2.2)
+///
+/// ```text
+/// 2.2.  RDATA wire format
+///
+///    The RDATA for the SVCB RR consists of:
+///
+///    *  a 2-octet field for SvcPriority as an integer in network byte
+///       order.
+///
+///    *  the uncompressed, fully qualified TargetName, represented as a
+///       sequence of length-prefixed labels per Section 3.1 of [RFC1035].
+///
+///    *  the SvcParams, consuming the remainder of the record (so smaller
+///       than 65535 octets and constrained by the RDATA and DNS message
+///       sizes).
+///
+///    When the list of SvcParams is non-empty, it contains a series of
+///    SvcParamKey=SvcParamValue pairs, represented as:
+///
+///    *  a 2-octet field containing the SvcParamKey as an integer in
+///       network byte order.  (See Section 14.3.2 for the defined values.)
+///
+///    *  a 2-octet field containing the length of the SvcParamValue as an
+///       integer between 0 and 65535 in network byte order.
+///
+///    *  an octet string of this length whose contents are the SvcParamValue
+///       in a format determined by the SvcParamKey.
+///
+///    SvcParamKeys SHALL appear in increasing numeric order.
+/// ```
+///
+/// The HTTPS RR has the same format, see `RData::HTTPS`.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct SVCB {
+    svc_priority: u16,
+    target_name: Name,
+    svc_params: Vec<(SvcParamKey, SvcParamValue)>,
+}
+
+impl SVCB {
+    /// Creates a new SVCB record data
+    ///
+    /// # Arguments
+    ///
+    /// * `svc_priority` - 0 for AliasMode, otherwise the priority of the ServiceMode endpoint, lower is preferred
+    /// * `target_name` - the alias, or the name of the endpoint, `.` is the owner of the record in ServiceMode
+    /// * `svc_params` - the parameters of the endpoint, these are sorted by key
+    pub fn new(
+        svc_priority: u16,
+        target_name: Name,
+        mut svc_params: Vec<(SvcParamKey, SvcParamValue)>,
+    ) -> Self {
+        svc_params.sort_by_key(|&(key, _)| u16::from(key));
+
+        SVCB {
+            svc_priority,
+            target_name,
+            svc_params,
+        }
+    }
+
+    /// The priority of the endpoint, 0 for AliasMode
+    pub fn svc_priority(&self) -> u16 {
+        self.svc_priority
+    }
+
+    /// The alias in AliasMode, the name of the endpoint in ServiceMode
+    pub fn target_name(&self) -> &Name {
+        &self.target_name
+    }
+
+    /// The parameters of the endpoint, in increasing order of their keys
+    pub fn svc_params(&self) -> &[(SvcParamKey, SvcParamValue)] {
+        &self.svc_params
+    }
+
+    /// Returns true if this is an alias for the service, i.e. the priority is 0
+    pub fn is_alias_mode(&self) -> bool {
+        self.svc_priority == 0
+    }
+
+    /// Returns the value of the parameter with the key
+    pub fn svc_param(&self, key: SvcParamKey) -> Option<&SvcParamValue> {
+        self.svc_params
+            .iter()
+            .find(|&&(k, _)| k == key)
+            .map(|&(_, ref value)| value)
+    }
+
+    /// The protocols of the endpoint from the `alpn` parameter
+    pub fn alpn(&self) -> &[String] {
+        match self.svc_param(SvcParamKey::Alpn) {
+            Some(&SvcParamValue::Alpn(ref alpn)) => alpn,
+            _ => &[],
+        }
+    }
+
+    /// The port of the endpoint from the `port` parameter
+    pub fn port(&self) -> Option<u16> {
+        match self.svc_param(SvcParamKey::Port) {
+            Some(&SvcParamValue::Port(port)) => Some(port),
+            _ => None,
+        }
+    }
+
+    /// The IPv4 addresses of the endpoint from the `ipv4hint` parameter
+    pub fn ipv4_hint(&self) -> &[Ipv4Addr] {
+        match self.svc_param(SvcParamKey::Ipv4Hint) {
+            Some(&SvcParamValue::Ipv4Hint(ref hint)) => hint,
+            _ => &[],
+        }
+    }
+
+    /// The IPv6 addresses of the endpoint from the `ipv6hint` parameter
+    pub fn ipv6_hint(&self) -> &[Ipv6Addr] {
+        match self.svc_param(SvcParamKey::Ipv6Hint) {
+            Some(&SvcParamValue::Ipv6Hint(ref hint)) => hint,
+            _ => &[],
+        }
+    }
+}
+
+/// [RFC 9460, SVCB and HTTPS RRs](https://tools.ietf.org/html/rfc9460#section-14.3.2)
+///
+/// ```text
+/// 14.3.2.  Initial Contents
+///
+///    +===========+=================+================+=========+==========+
+///    |   Number  | Name            | Meaning        |Reference|Change    |
+///    |           |                 |                |         |Controller|
+///    +===========+=================+================+=========+==========+
+///    |     0     | mandatory       | Mandatory      |RFC 9460,|IETF      |
+///    |           |                 | keys in this   |Section 8|          |
+///    |           |                 | RR             |         |          |
+///    |     1     | alpn            | Additional     |RFC 9460,|IETF      |
+///    |           |                 | supported      |Section  |          |
+///    |           |                 | protocols      |7.1      |          |
+///    |     2     | no-default-alpn | No support     |RFC 9460,|IETF      |
+///    |           |                 | for default    |Section  |          |
+///    |           |                 | protocol       |7.1      |          |
+///    |     3     | port            | Port for       |RFC 9460,|IETF      |
+///    |           |                 | alternative    |Section  |          |
+///    |           |                 | endpoint       |7.2      |          |
+///    |     4     | ipv4hint        | IPv4 address   |RFC 9460,|IETF      |
+///    |           |                 | hints          |Section  |          |
+///    |           |                 |                |7.3      |          |
+///    |     5     | ech             | RESERVED       |N/A      |IETF      |
+///    |           |                 | (held for      |         |          |
+///    |           |                 | Encrypted      |         |          |
+///    |           |                 | ClientHello)   |         |          |
+///    |     6     | ipv6hint        | IPv6 address   |RFC 9460,|IETF      |
+///    |           |                 | hints          |Section  |          |
+///    |           |                 |                |7.3      |          |
+///    |65280-65534| N/A             | Reserved for   |RFC 9460 |IETF      |
+///    |           |                 | Private Use    |         |          |
+///    |   65535   | N/A             | Reserved       |RFC 9460 |IETF      |
+///    |           |                 | ("Invalid      |         |          |
+///    |           |                 | key")          |         |          |
+///    +-----------+-----------------+----------------+---------+----------+
+/// ```
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum SvcParamKey {
+    /// Keys which must be supported to use the endpoint
+    Mandatory,
+    /// The protocols supported by the endpoint, in addition to the default
+    Alpn,
+    /// The default protocol is not supported by the endpoint
+    NoDefaultAlpn,
+    /// The port of the endpoint
+    Port,
+    /// IPv4 addresses of the endpoint
+    Ipv4Hint,
+    /// The Encrypted ClientHello configuration of the endpoint
+    Ech,
+    /// IPv6 addresses of the endpoint
+    Ipv6Hint,
+    /// Unassigned or private keys, presented as `keyNNNNN`
+    Key(u16),
+}
+
+impl From<u16> for SvcParamKey {
+    fn from(key: u16) -> Self {
+        match key {
+            0 => SvcParamKey::Mandatory,
+            1 => SvcParamKey::Alpn,
+            2 => SvcParamKey::NoDefaultAlpn,
+            3 => SvcParamKey::Port,
+            4 => SvcParamKey::Ipv4Hint,
+            5 => SvcParamKey::Ech,
+            6 => SvcParamKey::Ipv6Hint,
+            _ => SvcParamKey::Key(key),
+        }
+    }
+}
+
+impl From<SvcParamKey> for u16 {
+    fn from(key: SvcParamKey) -> Self {
+        match key {
+            SvcParamKey::Mandatory => 0,
+            SvcParamKey::Alpn => 1,
+            SvcParamKey::NoDefaultAlpn => 2,
+            SvcParamKey::Port => 3,
+            SvcParamKey::Ipv4Hint => 4,
+            SvcParamKey::Ech => 5,
+            SvcParamKey::Ipv6Hint => 6,
+            SvcParamKey::Key(key) => key,
+        }
+    }
+}
+
+impl fmt::Display for SvcParamKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SvcParamKey::Mandatory => f.write_str("mandatory"),
+            SvcParamKey::Alpn => f.write_str("alpn"),
+            SvcParamKey::NoDefaultAlpn => f.write_str("no-default-alpn"),
+            SvcParamKey::Port => f.write_str("port"),
+            SvcParamKey::Ipv4Hint => f.write_str("ipv4hint"),
+            SvcParamKey::Ech => f.write_str("ech"),
+            SvcParamKey::Ipv6Hint => f.write_str("ipv6hint"),
+            SvcParamKey::Key(key) => write!(f, "key{}", key),
+        }
+    }
+}
+
+/// The value of a parameter, the variant matches the key, see `SvcParamKey`
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub enum SvcParamValue {
+    /// `mandatory`, the keys which must be supported
+    Mandatory(Vec<SvcParamKey>),
+    /// `alpn`, the protocol identifiers
+    Alpn(Vec<String>),
+    /// `no-default-alpn`, no value
+    NoDefaultAlpn,
+    /// `port`
+    Port(u16),
+    /// `ipv4hint`
+    Ipv4Hint(Vec<Ipv4Addr>),
+    /// `ech`, the ECHConfigList
+    Ech(Vec<u8>),
+    /// `ipv6hint`
+    Ipv6Hint(Vec<Ipv6Addr>),
+    /// The opaque value of other keys
+    Unknown(Vec<u8>),
+}
+
+impl fmt::Display for SvcParamValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn write_list<T: fmt::Display>(f: &mut fmt::Formatter, list: &[T]) -> fmt::Result {
+            for (i, item) in list.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(",")?;
+                }
+                write!(f, "{}", item)?;
+            }
+            Ok(())
+        }
+
+        match *self {
+            SvcParamValue::Mandatory(ref keys) => write_list(f, keys),
+            SvcParamValue::Alpn(ref alpn) => {
+                // commas and backslashes in the identifiers are escaped, RFC 9460 appendix A.1
+                let alpn = alpn
+                    .iter()
+                    .map(|id| id.replace('\\', "\\\\").replace(',', "\\,"))
+                    .collect::<Vec<_>>();
+                write_list(f, &alpn)
+            }
+            SvcParamValue::NoDefaultAlpn => Ok(()),
+            SvcParamValue::Port(port) => write!(f, "{}", port),
+            SvcParamValue::Ipv4Hint(ref hint) => write_list(f, hint),
+            SvcParamValue::Ech(ref ech) => f.write_str(&BASE64.encode(ech)),
+            SvcParamValue::Ipv6Hint(ref hint) => write_list(f, hint),
+            SvcParamValue::Unknown(ref value) => {
+                f.write_str("\"")?;
+                for &b in value {
+                    match b {
+                        b'"' | b'\\' => write!(f, "\\{}", b as char)?,
+                        0x21...0x7e => write!(f, "{}", b as char)?,
+                        _ => write!(f, "\\{:03}", b)?,
+                    }
+                }
+                f.write_str("\"")
+            }
+        }
+    }
+}
+
+/// [RFC 9460, SVCB and HTTPS RRs](https://tools.ietf.org/html/rfc9460#section-2.1)
+///
+/// ```text
+/// 2.1.  Zone-file presentation format
+///
+///    The presentation format <RDATA> of the record is:
+///
+///    SvcPriority TargetName SvcParams
+/// ```
+impl fmt::Display for SVCB {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.svc_priority, self.target_name)?;
+        for &(key, ref value) in &self.svc_params {
+            match *value {
+                SvcParamValue::NoDefaultAlpn => write!(f, " {}", key)?,
+                _ => write!(f, " {}={}", key, value)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Read the RData from the given Decoder
+pub fn read(decoder: &mut BinDecoder, rdata_length: Restrict<u16>) -> ProtoResult<SVCB> {
+    let start_idx = decoder.index();
+    let rdata_length =
+        rdata_length.map(|u| u as usize).unverified(/*used as the end of the params*/);
+
+    let svc_priority = decoder.read_u16()?.unverified(/*any u16 is valid*/);
+    let target_name = Name::read(decoder)?;
+
+    let mut svc_params: Vec<(SvcParamKey, SvcParamValue)> = Vec::new();
+    while decoder.index() - start_idx < rdata_length {
+        let key = decoder.read_u16()?.unverified(/*verified in order below*/);
+        if let Some(&(last, _)) = svc_params.last() {
+            if key <= u16::from(last) {
+                return Err(format!("SvcParamKey {} is not in increasing order", key).into());
+            }
+        }
+        if key == u16::max_value() {
+            return Err("SvcParamKey 65535 is invalid".into());
+        }
+
+        let len = decoder.read_u16()?.unverified(/*length checked by each value*/) as usize;
+        let key = SvcParamKey::from(key);
+        let value = read_value(decoder, key, len)?;
+        svc_params.push((key, value));
+    }
+
+    Ok(SVCB {
+        svc_priority,
+        target_name,
+        svc_params,
+    })
+}
+
+fn read_value(
+    decoder: &mut BinDecoder,
+    key: SvcParamKey,
+    len: usize,
+) -> ProtoResult<SvcParamValue> {
+    let data = decoder.read_slice(len)?.unverified(/*each value is verified below*/);
+    let mut decoder = BinDecoder::new(data);
+
+    let value = match key {
+        SvcParamKey::Mandatory => {
+            if len == 0 || len % 2 != 0 {
+                return Err("invalid length of mandatory SvcParam".into());
+            }
+
+            let mut keys = Vec::with_capacity(len / 2);
+            while !decoder.is_empty() {
+                keys.push(SvcParamKey::from(
+                    decoder.read_u16()?.unverified(/*any key is valid*/),
+                ));
+            }
+            SvcParamValue::Mandatory(keys)
+        }
+        SvcParamKey::Alpn => {
+            let mut alpn = Vec::new();
+            while !decoder.is_empty() {
+                let id = decoder.read_character_data()?.unverified(/*any identifier is valid*/);
+                alpn.push(String::from_utf8_lossy(id).into_owned());
+            }
+            if alpn.is_empty() {
+                return Err("empty alpn SvcParam".into());
+            }
+            SvcParamValue::Alpn(alpn)
+        }
+        SvcParamKey::NoDefaultAlpn => {
+            if len != 0 {
+                return Err("no-default-alpn SvcParam has a value".into());
+            }
+            SvcParamValue::NoDefaultAlpn
+        }
+        SvcParamKey::Port => {
+            if len != 2 {
+                return Err("invalid length of port SvcParam".into());
+            }
+            SvcParamValue::Port(decoder.read_u16()?.unverified(/*any port is valid*/))
+        }
+        SvcParamKey::Ipv4Hint => {
+            if len == 0 || len % 4 != 0 {
+                return Err("invalid length of ipv4hint SvcParam".into());
+            }
+
+            let mut hint = Vec::with_capacity(len / 4);
+            while !decoder.is_empty() {
+                hint.push(super::a::read(&mut decoder)?);
+            }
+            SvcParamValue::Ipv4Hint(hint)
+        }
+        SvcParamKey::Ech => SvcParamValue::Ech(data.to_vec()),
+        SvcParamKey::Ipv6Hint => {
+            if len == 0 || len % 16 != 0 {
+                return Err("invalid length of ipv6hint SvcParam".into());
+            }
+
+            let mut hint = Vec::with_capacity(len / 16);
+            while !decoder.is_empty() {
+                hint.push(super::aaaa::read(&mut decoder)?);
+            }
+            SvcParamValue::Ipv6Hint(hint)
+        }
+        SvcParamKey::Key(_) => SvcParamValue::Unknown(data.to_vec()),
+    };
+
+    Ok(value)
+}
+
+/// Write the RData from the given Decoder
+///
+/// The TargetName is never compressed or lowercased, RFC 9460 section 2.2.
+pub fn emit(encoder: &mut BinEncoder, svcb: &SVCB) -> ProtoResult<()> {
+    encoder.emit_u16(svcb.svc_priority)?;
+    svcb.target_name.emit_as_canonical(encoder, true)?;
+
+    for &(key, ref value) in &svcb.svc_params {
+        encoder.emit_u16(key.into())?;
+
+        // the length is written once the value is
+        let place = encoder.place::<u16>()?;
+        emit_value(encoder, value)?;
+        let len = encoder.len_since_place(&place);
+        if len > u16::max_value() as usize {
+            return Err(format!("SvcParam {} is too long", key).into());
+        }
+
+        place.replace(encoder, len as u16)?;
+    }
+
+    Ok(())
+}
+
+fn emit_value(encoder: &mut BinEncoder, value: &SvcParamValue) -> ProtoResult<()> {
+    match *value {
+        SvcParamValue::Mandatory(ref keys) => {
+            for &key in keys {
+                encoder.emit_u16(key.into())?;
+            }
+        }
+        SvcParamValue::Alpn(ref alpn) => {
+            for id in alpn {
+                encoder.emit_character_data(id)?;
+            }
+        }
+        SvcParamValue::NoDefaultAlpn => (),
+        SvcParamValue::Port(port) => encoder.emit_u16(port)?,
+        SvcParamValue::Ipv4Hint(ref hint) => {
+            for &address in hint {
+                super::a::emit(encoder, address)?;
+            }
+        }
+        SvcParamValue::Ipv6Hint(ref hint) => {
+            for address in hint {
+                super::aaaa::emit(encoder, address)?;
+            }
+        }
+        SvcParamValue::Ech(ref value) | SvcParamValue::Unknown(ref value) => {
+            encoder.emit_vec(value)?
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn svcb() -> SVCB {
+        SVCB::new(
+            1,
+            Name::from_str("svc.example.net.").unwrap(),
+            vec![
+                (SvcParamKey::Port, SvcParamValue::Port(8443)),
+                (
+                    SvcParamKey::Alpn,
+                    SvcParamValue::Alpn(vec!["h2".to_string(), "h3,x".to_string()]),
+                ),
+                (
+                    SvcParamKey::Mandatory,
+                    SvcParamValue::Mandatory(vec![SvcParamKey::Alpn, SvcParamKey::Port]),
+                ),
+                (SvcParamKey::NoDefaultAlpn, SvcParamValue::NoDefaultAlpn),
+                (
+                    SvcParamKey::Ipv4Hint,
+                    SvcParamValue::Ipv4Hint(vec![Ipv4Addr::new(192, 0, 2, 1)]),
+                ),
+                (SvcParamKey::Ech, SvcParamValue::Ech(vec![1, 2, 3])),
+                (
+                    SvcParamKey::Ipv6Hint,
+                    SvcParamValue::Ipv6Hint(vec![Ipv6Addr::from_str("2001:db8::1").unwrap()]),
+                ),
+                (
+                    SvcParamKey::Key(667),
+                    SvcParamValue::Unknown(b"hi\"".to_vec()),
+                ),
+            ],
+        )
+    }
+
+    fn to_bytes(svcb: &SVCB) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        {
+            let mut encoder = BinEncoder::new(&mut bytes);
+            emit(&mut encoder, svcb).unwrap();
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_emit_and_read() {
+        let rdata = svcb();
+        let bytes = to_bytes(&rdata);
+
+        let mut decoder = BinDecoder::new(&bytes);
+        let read_rdata = read(&mut decoder, Restrict::new(bytes.len() as u16)).unwrap();
+        assert_eq!(read_rdata, rdata);
+        assert_eq!(read_rdata.port(), Some(8443));
+        assert_eq!(read_rdata.alpn(), &["h2".to_string(), "h3,x".to_string()]);
+        assert!(!read_rdata.is_alias_mode());
+    }
+
+    #[test]
+    fn test_read_alias_mode() {
+        // RFC 9460 appendix D.1, `example.com. HTTPS 0 foo.example.com.`
+        let bytes = b"\x00\x00\x03foo\x07example\x03com\x00";
+        let mut decoder = BinDecoder::new(bytes);
+        let rdata = read(&mut decoder, Restrict::new(bytes.len() as u16)).unwrap();
+
+        assert!(rdata.is_alias_mode());
+        assert_eq!(
+            *rdata.target_name(),
+            Name::from_str("foo.example.com.").unwrap()
+        );
+        assert!(rdata.svc_params().is_empty());
+    }
+
+    #[test]
+    fn test_read_invalid() {
+        let read_bytes = |bytes: &[u8]| {
+            let mut decoder = BinDecoder::new(bytes);
+            read(&mut decoder, Restrict::new(bytes.len() as u16))
+        };
+
+        // keys out of order
+        assert!(read_bytes(b"\x00\x01\x00\x00\x03\x00\x02\x01\xbb\x01\x00\x03\x02h2").is_err());
+        // port of the wrong length
+        assert!(read_bytes(b"\x00\x01\x00\x00\x03\x00\x01\x01").is_err());
+        // no-default-alpn with a value
+        assert!(read_bytes(b"\x00\x01\x00\x00\x02\x00\x01\x01").is_err());
+        // the invalid key
+        assert!(read_bytes(b"\x00\x01\x00\xff\xff\x00\x00").is_err());
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            svcb().to_string(),
+            "1 svc.example.net. mandatory=alpn,port alpn=h2,h3\\,x no-default-alpn port=8443 \
+             ipv4hint=192.0.2.1 ech=AQID ipv6hint=2001:db8::1 key667=\"hi\\\"\""
+        );
+    }
+}
@@ -0,0 +1,175 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! uniform resource identifier records
+
+use std::fmt;
+
+use super::naptr::write_character_string;
+use error::*;
+use serialize::binary::*;
+
+/// [RFC 7553, The URI DNS Resource Record, June 2015](https://tools.ietf.org/html/rfc7553#section-4.5)
+///
+/// ```text
+/// 4.5.  URI RDATA Wire Format
+///
+///    The RDATA for a URI RR consists of a 2-octet Priority field, a
+///    2-octet Weight field, and a variable-length Target field.
+///
+///    Priority and Weight are unsigned integers in network byte order.
+///
+///    The remaining data in the RDATA contains the Target field.  The
+///    Target field contains the URI as a sequence of octets (without the
+///    enclosing double-quote characters used in the presentation format).
+///
+///    The length of the Target field MUST be greater than zero.
+///
+///                         1 1 1 1 1 1 1 1 1 1 2 2 2 2 2 2 2 2 2 2 3 3
+///     0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///    |          Priority             |          Weight               |
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///    /                                                               /
+///    /                             Target                            /
+///    /                                                               /
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// ```
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct URI {
+    priority: u16,
+    weight: u16,
+    target: Box<[u8]>,
+}
+
+impl URI {
+    /// Creates a new URI record data
+    ///
+    /// # Arguments
+    ///
+    /// * `priority` - the priority of the target, lower values are preferred
+    /// * `weight` - the relative weight of targets with the same priority
+    /// * `target` - the URI, must not be empty
+    pub fn new(priority: u16, weight: u16, target: Box<[u8]>) -> Self {
+        URI {
+            priority,
+            weight,
+            target,
+        }
+    }
+
+    /// ```text
+    /// 4.2.  Priority
+    ///
+    ///    This field holds the priority of the target URI in this RR.  Its
+    ///    range is 0-65535.  A client MUST attempt to contact the URI with the
+    ///    lowest-numbered priority it can reach; URIs with the same priority
+    ///    SHOULD be selected according to probabilities defined by the weight
+    ///    field.
+    /// ```
+    pub fn priority(&self) -> u16 {
+        self.priority
+    }
+
+    /// ```text
+    /// 4.3.  Weight
+    ///
+    ///    This field holds the server selection mechanism.  The weight field
+    ///    specifies a relative weight for entries with the same priority.
+    /// ```
+    pub fn weight(&self) -> u16 {
+        self.weight
+    }
+
+    /// ```text
+    /// 4.4.  Target
+    ///
+    ///    This field holds the URI of the target, enclosed in double-quote
+    ///    characters ('"'), where the URI is as specified in RFC 3986
+    ///    [RFC3986].
+    /// ```
+    pub fn target(&self) -> &[u8] {
+        &self.target
+    }
+}
+
+/// The target is a quoted string, RFC 7553 section 4.4
+impl fmt::Display for URI {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} ", self.priority, self.weight)?;
+        write_character_string(f, &self.target)
+    }
+}
+
+/// Read the RData from the given Decoder
+pub fn read(decoder: &mut BinDecoder, rdata_length: Restrict<u16>) -> ProtoResult<URI> {
+    let priority = decoder.read_u16()?.unverified(/*any u16 is valid*/);
+    let weight = decoder.read_u16()?.unverified(/*any u16 is valid*/);
+
+    let target_len = rdata_length
+        .map(|u| u as usize)
+        .checked_sub(4)
+        .map_err(|_| ProtoError::from("invalid rdata length in URI"))?
+        .verify_unwrap(|len| *len > 0)
+        .map_err(|_| ProtoError::from("target of URI is empty"))?;
+    let target = decoder.read_vec(target_len)?.unverified(/*any target is valid*/);
+
+    Ok(URI::new(priority, weight, target.into_boxed_slice()))
+}
+
+/// Write the RData from the given Decoder
+pub fn emit(encoder: &mut BinEncoder, uri: &URI) -> ProtoResult<()> {
+    if uri.target.is_empty() {
+        return Err("target of URI is empty".into());
+    }
+
+    encoder.emit_u16(uri.priority)?;
+    encoder.emit_u16(uri.weight)?;
+    encoder.emit_vec(&uri.target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uri(target: &str) -> URI {
+        URI::new(10, 1, target.as_bytes().to_vec().into_boxed_slice())
+    }
+
+    #[test]
+    fn test_emit_and_read() {
+        let rdata = uri("ftp://ftp1.example.com/public");
+
+        let mut bytes = Vec::new();
+        {
+            let mut encoder = BinEncoder::new(&mut bytes);
+            emit(&mut encoder, &rdata).unwrap();
+        }
+
+        let len = bytes.len() as u16;
+        let mut decoder = BinDecoder::new(&bytes);
+        assert_eq!(read(&mut decoder, Restrict::new(len)).unwrap(), rdata);
+        assert!(decoder.is_empty());
+    }
+
+    #[test]
+    fn test_empty_target() {
+        let mut bytes = Vec::new();
+        assert!(emit(&mut BinEncoder::new(&mut bytes), &uri("")).is_err());
+
+        let bytes = b"\x00\x0a\x00\x01";
+        assert!(read(&mut BinDecoder::new(bytes), Restrict::new(4)).is_err());
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            uri("http://www.example.com/path").to_string(),
+            r#"10 1 "http://www.example.com/path""#
+        );
+    }
+}
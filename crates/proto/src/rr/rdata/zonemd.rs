@@ -0,0 +1,284 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! message digests for DNS zones
+
+use std::fmt;
+
+use data_encoding::HEXLOWER;
+
+use error::*;
+use serialize::binary::*;
+
+/// [RFC 8976, Message Digest for DNS Zones, February 2021](https://tools.ietf.org/html/rfc8976#section-2.2)
+///
+/// ```text
+/// 2.2.  ZONEMD RDATA Wire Format
+///
+///    The ZONEMD RDATA wire format is encoded as follows:
+///
+///                         1 1 1 1 1 1 1 1 1 1 2 2 2 2 2 2 2 2 2 2 3 3
+///     0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///    |                             Serial                            |
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///    |    Scheme     |Hash Algorithm |                               |
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+                               |
+///    |                             Digest                            |
+///    /                                                               /
+///    /                                                               /
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// ```
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct ZONEMD {
+    serial: u32,
+    scheme: ZonemdScheme,
+    hash_algorithm: ZonemdHashAlgorithm,
+    digest: Vec<u8>,
+}
+
+/// [RFC 8976, Message Digest for DNS Zones, February 2021](https://tools.ietf.org/html/rfc8976#section-2.2.2)
+///
+/// ```text
+/// 2.2.2.  The Scheme Field
+///
+///    The Scheme field is an 8-bit unsigned integer that identifies the
+///    methods by which data is collated and presented as input to the
+///    hashing function.
+/// ```
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum ZonemdScheme {
+    /// The SIMPLE scheme, RFC 8976 section 3.3
+    Simple,
+    /// Private use, 240-254
+    Private(u8),
+    /// Reserved or unassigned at the time of this writing
+    Unassigned(u8),
+}
+
+impl From<u8> for ZonemdScheme {
+    fn from(scheme: u8) -> Self {
+        match scheme {
+            1 => ZonemdScheme::Simple,
+            240...254 => ZonemdScheme::Private(scheme),
+            _ => ZonemdScheme::Unassigned(scheme),
+        }
+    }
+}
+
+impl From<ZonemdScheme> for u8 {
+    fn from(scheme: ZonemdScheme) -> Self {
+        match scheme {
+            ZonemdScheme::Simple => 1,
+            ZonemdScheme::Private(scheme) | ZonemdScheme::Unassigned(scheme) => scheme,
+        }
+    }
+}
+
+/// [RFC 8976, Message Digest for DNS Zones, February 2021](https://tools.ietf.org/html/rfc8976#section-2.2.3)
+///
+/// ```text
+/// 2.2.3.  The Hash Algorithm Field
+///
+///    The Hash Algorithm field is an 8-bit unsigned integer that identifies
+///    the cryptographic hash algorithm used to construct the digest.
+/// ```
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum ZonemdHashAlgorithm {
+    /// SHA-384
+    SHA384,
+    /// SHA-512
+    SHA512,
+    /// Private use, 240-254
+    Private(u8),
+    /// Reserved or unassigned at the time of this writing
+    Unassigned(u8),
+}
+
+impl ZonemdHashAlgorithm {
+    /// The length of the digest of this algorithm, if it is known
+    pub fn digest_len(self) -> Option<usize> {
+        match self {
+            ZonemdHashAlgorithm::SHA384 => Some(48),
+            ZonemdHashAlgorithm::SHA512 => Some(64),
+            _ => None,
+        }
+    }
+}
+
+impl From<u8> for ZonemdHashAlgorithm {
+    fn from(hash_algorithm: u8) -> Self {
+        match hash_algorithm {
+            1 => ZonemdHashAlgorithm::SHA384,
+            2 => ZonemdHashAlgorithm::SHA512,
+            240...254 => ZonemdHashAlgorithm::Private(hash_algorithm),
+            _ => ZonemdHashAlgorithm::Unassigned(hash_algorithm),
+        }
+    }
+}
+
+impl From<ZonemdHashAlgorithm> for u8 {
+    fn from(hash_algorithm: ZonemdHashAlgorithm) -> Self {
+        match hash_algorithm {
+            ZonemdHashAlgorithm::SHA384 => 1,
+            ZonemdHashAlgorithm::SHA512 => 2,
+            ZonemdHashAlgorithm::Private(hash_algorithm)
+            | ZonemdHashAlgorithm::Unassigned(hash_algorithm) => hash_algorithm,
+        }
+    }
+}
+
+impl ZONEMD {
+    /// Creates a new ZONEMD record data
+    ///
+    /// # Arguments
+    ///
+    /// * `serial` - the serial of the SOA the digest was computed for
+    /// * `scheme` - the method used to collate the zone data for the digest
+    /// * `hash_algorithm` - the hash algorithm of the digest
+    /// * `digest` - the digest of the zone
+    pub fn new(
+        serial: u32,
+        scheme: ZonemdScheme,
+        hash_algorithm: ZonemdHashAlgorithm,
+        digest: Vec<u8>,
+    ) -> Self {
+        ZONEMD {
+            serial,
+            scheme,
+            hash_algorithm,
+            digest,
+        }
+    }
+
+    /// ```text
+    /// 2.2.1.  The Serial Field
+    ///
+    ///    The Serial field is a 32-bit unsigned integer in network byte order.
+    ///    It is the serial number from the zone's SOA record ([RFC1035],
+    ///    Section 3.3.13) for which the zone digest was generated.
+    /// ```
+    pub fn serial(&self) -> u32 {
+        self.serial
+    }
+
+    /// The method used to collate the zone data for the digest
+    pub fn scheme(&self) -> ZonemdScheme {
+        self.scheme
+    }
+
+    /// The hash algorithm of the digest
+    pub fn hash_algorithm(&self) -> ZonemdHashAlgorithm {
+        self.hash_algorithm
+    }
+
+    /// ```text
+    /// 2.2.4.  The Digest Field
+    ///
+    ///    The Digest field is a variable-length sequence of octets containing
+    ///    the output of the hash algorithm.  The length of the Digest field is
+    ///    determined by deducting the fixed size of the Serial, Scheme, and
+    ///    Hash Algorithm fields from the RDATA size in the ZONEMD RR header.
+    /// ```
+    pub fn digest(&self) -> &[u8] {
+        &self.digest
+    }
+}
+
+/// [RFC 8976, Message Digest for DNS Zones, February 2021](https://tools.ietf.org/html/rfc8976#section-2.3)
+///
+/// ```text
+///    The Serial field is represented as an unsigned decimal integer.
+///
+///    The Scheme field is represented as an unsigned decimal integer.
+///
+///    The Hash Algorithm field is represented as an unsigned decimal
+///    integer.
+///
+///    The Digest is represented as a sequence of case-insensitive
+///    hexadecimal digits.  Whitespace is allowed within the hexadecimal
+///    text.
+/// ```
+impl fmt::Display for ZONEMD {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {}",
+            self.serial,
+            u8::from(self.scheme),
+            u8::from(self.hash_algorithm),
+            HEXLOWER.encode(&self.digest)
+        )
+    }
+}
+
+/// Read the RData from the given Decoder
+pub fn read(decoder: &mut BinDecoder, rdata_length: Restrict<u16>) -> ProtoResult<ZONEMD> {
+    let serial = decoder.read_u32()?.unverified(/*any u32 is valid*/);
+    let scheme = decoder.read_u8()?.unverified(/*ZonemdScheme is safe with any u8*/);
+    let hash_algorithm = decoder.read_u8()?.unverified(/*ZonemdHashAlgorithm is safe with any u8*/);
+
+    let digest_len = rdata_length
+        .map(|u| u as usize)
+        .checked_sub(6)
+        .map_err(|_| ProtoError::from("invalid rdata length in ZONEMD"))?
+        .unverified(/*used only as length safely*/);
+    let digest = decoder.read_vec(digest_len)?.unverified(/*verified with the zone*/);
+
+    Ok(ZONEMD::new(
+        serial,
+        ZonemdScheme::from(scheme),
+        ZonemdHashAlgorithm::from(hash_algorithm),
+        digest,
+    ))
+}
+
+/// Write the RData from the given Decoder
+pub fn emit(encoder: &mut BinEncoder, zonemd: &ZONEMD) -> ProtoResult<()> {
+    encoder.emit_u32(zonemd.serial)?;
+    encoder.emit(zonemd.scheme.into())?;
+    encoder.emit(zonemd.hash_algorithm.into())?;
+    encoder.emit_vec(&zonemd.digest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_emit_and_read() {
+        let rdata = ZONEMD::new(
+            2018031900,
+            ZonemdScheme::Simple,
+            ZonemdHashAlgorithm::SHA384,
+            vec![0xc6; 48],
+        );
+
+        let mut bytes = Vec::new();
+        {
+            let mut encoder = BinEncoder::new(&mut bytes);
+            emit(&mut encoder, &rdata).unwrap();
+        }
+        assert_eq!(&bytes[..6], b"\x78\x48\xb9\x1c\x01\x01");
+
+        let len = bytes.len() as u16;
+        let mut decoder = BinDecoder::new(&bytes);
+        assert_eq!(read(&mut decoder, Restrict::new(len)).unwrap(), rdata);
+        assert!(decoder.is_empty());
+    }
+
+    #[test]
+    fn test_display() {
+        let rdata = ZONEMD::new(
+            1,
+            ZonemdScheme::Private(240),
+            ZonemdHashAlgorithm::SHA512,
+            vec![0xab, 0xcd, 0xef],
+        );
+        assert_eq!(rdata.to_string(), "1 240 2 abcdef");
+    }
+}
@@ -23,7 +23,10 @@ use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 
 use super::domain::Name;
 use super::rdata;
-use super::rdata::{CAA, MX, NULL, OPENPGPKEY, OPT, SOA, SRV, SSHFP, TLSA, TXT};
+use super::rdata::{
+    CAA, CERT, CSYNC, DHCID, HINFO, LOC, MX, NAPTR, NULL, OPENPGPKEY, OPT, RP, SOA, SRV, SSHFP,
+    SVCB, TLSA, TXT, URI, ZONEMD,
+};
 use super::record_type::RecordType;
 use error::*;
 use serialize::binary::*;
@@ -117,6 +120,16 @@ pub enum RData {
     /// ```
     CAA(CAA),
 
+    /// [RFC 4398, Storing Certificates in the DNS, March 2006](https://tools.ietf.org/html/rfc4398#section-2)
+    ///
+    /// ```text
+    /// 2.  The CERT Resource Record
+    ///
+    ///    The CERT resource record (RR) has the structure given below.  Its RR
+    ///    type code is 37.
+    /// ```
+    CERT(CERT),
+
     /// ```text
     ///   3.3. Standard RRs
     ///
@@ -150,6 +163,63 @@ pub enum RData {
     /// ```
     CNAME(Name),
 
+    /// [RFC 7477, Child-to-Parent Synchronization in DNS, March 2015](https://tools.ietf.org/html/rfc7477#section-2.1)
+    ///
+    /// ```text
+    /// 2.1.  The CSYNC Resource Record Format
+    ///
+    ///    The CSYNC RDATA consists of the SOA serial of the child zone, flags
+    ///    and a type bit map of the record types to be synchronized.
+    /// ```
+    CSYNC(CSYNC),
+
+    /// [RFC 4701, A DNS Resource Record (RR) for Encoding DHCP Information, October 2006](https://tools.ietf.org/html/rfc4701#section-3)
+    ///
+    /// ```text
+    /// 3.1.  DHCID RDATA Format
+    ///
+    ///    The RDATA section of a DHCID RR in transmission contains RDLENGTH
+    ///    octets of binary data.
+    /// ```
+    DHCID(DHCID),
+
+    /// ```text
+    /// 3.3.2. HINFO RDATA format
+    ///
+    ///     +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    ///     /                      CPU                      /
+    ///     +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    ///     /                       OS                      /
+    ///     +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    ///
+    /// where:
+    ///
+    /// CPU             A <character-string> which specifies the CPU type.
+    ///
+    /// OS              A <character-string> which specifies the operating
+    ///                 system type.
+    /// ```
+    HINFO(HINFO),
+
+    /// [RFC 9460, SVCB and HTTPS RRs](https://tools.ietf.org/html/rfc9460#section-9)
+    ///
+    /// ```text
+    /// The HTTPS RR is a variation of SVCB for use with HTTP [HTTP].  For
+    /// this use case, the HTTPS RR has the same RDATA format as SVCB.
+    /// ```
+    HTTPS(SVCB),
+
+    /// [RFC 1876, Location Information in the DNS, January 1996](https://tools.ietf.org/html/rfc1876#section-2)
+    ///
+    /// ```text
+    /// 2. RDATA Format
+    ///
+    ///    The LOC RDATA carries the version, the size and the horizontal and
+    ///    vertical precision, followed by the latitude, the longitude and the
+    ///    altitude.
+    /// ```
+    LOC(LOC),
+
     /// ```text
     /// 3.3.9. MX RDATA format
     ///
@@ -175,6 +245,19 @@ pub enum RData {
     /// ```
     MX(MX),
 
+    /// [RFC 3403, DDDS Part Three: The DNS Database, October 2002](https://tools.ietf.org/html/rfc3403#section-4)
+    ///
+    /// ```text
+    /// 4. NAPTR RR Format
+    ///
+    ///    The format of the NAPTR RR is given below.  The DNS type code for
+    ///    NAPTR is 35.
+    ///
+    ///       Domain TTL Class Type Order Preference Flags Service Regexp
+    ///       Replacement
+    /// ```
+    NAPTR(NAPTR),
+
     /// ```text
     /// 3.3.10. NULL RDATA format (EXPERIMENTAL)
     ///
@@ -279,6 +362,15 @@ pub enum RData {
     /// ```
     PTR(Name),
 
+    /// [RFC 1183, New DNS RR Definitions, October 1990](https://tools.ietf.org/html/rfc1183#section-2.2)
+    ///
+    /// ```text
+    /// 2.2. The Responsible Person RR
+    ///
+    ///    <owner> <ttl> <class> RP <mbox-dname> <txt-dname>
+    /// ```
+    RP(RP),
+
     /// ```text
     /// 3.3.13. SOA RDATA format
     ///
@@ -418,6 +510,16 @@ pub enum RData {
     /// [RFC 7479](https://tools.ietf.org/html/rfc7479).
     SSHFP(SSHFP),
 
+    /// [RFC 9460, SVCB and HTTPS RRs](https://tools.ietf.org/html/rfc9460#section-2)
+    ///
+    /// ```text
+    /// The SVCB record allows a service to be provided from multiple
+    /// alternative endpoints, each with associated parameters (such as
+    /// transport protocol configuration), and are extensible to support
+    /// future uses (such as keys for encrypting the TLS ClientHello).
+    /// ```
+    SVCB(SVCB),
+
     /// [RFC 6698, DNS-Based Authentication for TLS](https://tools.ietf.org/html/rfc6698#section-2.1)
     ///
     /// ```text
@@ -449,6 +551,26 @@ pub enum RData {
     /// ```
     TXT(TXT),
 
+    /// [RFC 7553, The URI DNS Resource Record, June 2015](https://tools.ietf.org/html/rfc7553#section-4.5)
+    ///
+    /// ```text
+    /// 4.5.  URI RDATA Wire Format
+    ///
+    ///    The RDATA for a URI RR consists of a 2-octet Priority field, a
+    ///    2-octet Weight field, and a variable-length Target field.
+    /// ```
+    URI(URI),
+
+    /// [RFC 8976, Message Digest for DNS Zones, February 2021](https://tools.ietf.org/html/rfc8976#section-2)
+    ///
+    /// ```text
+    /// 2.  The ZONEMD Resource Record
+    ///
+    ///    This section describes the ZONEMD resource record, including its
+    ///    fields, wire format, and presentation format.
+    /// ```
+    ZONEMD(ZONEMD),
+
     /// A DNSSEC- or SIG(0)- specific record. See `DNSSECRData` for details.
     ///
     /// These types are in `DNSSECRData` to make them easy to disable when
@@ -504,18 +626,46 @@ impl RData {
                 debug!("reading CAA");
                 rdata::caa::read(decoder, rdata_length).map(RData::CAA)
             }
+            RecordType::CERT => {
+                debug!("reading CERT");
+                rdata::cert::read(decoder, rdata_length).map(RData::CERT)
+            }
             RecordType::CNAME => {
                 debug!("reading CNAME");
                 rdata::name::read(decoder).map(RData::CNAME)
             }
+            RecordType::CSYNC => {
+                debug!("reading CSYNC");
+                rdata::csync::read(decoder, rdata_length).map(RData::CSYNC)
+            }
+            RecordType::DHCID => {
+                debug!("reading DHCID");
+                rdata::dhcid::read(decoder, rdata_length).map(RData::DHCID)
+            }
             RecordType::ZERO => {
                 debug!("reading EMPTY");
                 return Ok(RData::ZERO);
             }
+            RecordType::HINFO => {
+                debug!("reading HINFO");
+                rdata::hinfo::read(decoder).map(RData::HINFO)
+            }
+            RecordType::HTTPS => {
+                debug!("reading HTTPS");
+                rdata::svcb::read(decoder, rdata_length).map(RData::HTTPS)
+            }
+            RecordType::LOC => {
+                debug!("reading LOC");
+                rdata::loc::read(decoder).map(RData::LOC)
+            }
             RecordType::MX => {
                 debug!("reading MX");
                 rdata::mx::read(decoder).map(RData::MX)
             }
+            RecordType::NAPTR => {
+                debug!("reading NAPTR");
+                rdata::naptr::read(decoder).map(RData::NAPTR)
+            }
             RecordType::NULL => {
                 debug!("reading NULL");
                 rdata::null::read(decoder, rdata_length).map(RData::NULL)
@@ -536,6 +686,10 @@ impl RData {
                 debug!("reading PTR");
                 rdata::name::read(decoder).map(RData::PTR)
             }
+            RecordType::RP => {
+                debug!("reading RP");
+                rdata::rp::read(decoder).map(RData::RP)
+            }
             RecordType::SOA => {
                 debug!("reading SOA");
                 rdata::soa::read(decoder).map(RData::SOA)
@@ -548,6 +702,10 @@ impl RData {
                 debug!("reading SSHFP");
                 rdata::sshfp::read(decoder, rdata_length).map(RData::SSHFP)
             }
+            RecordType::SVCB => {
+                debug!("reading SVCB");
+                rdata::svcb::read(decoder, rdata_length).map(RData::SVCB)
+            }
             RecordType::TLSA => {
                 debug!("reading TLSA");
                 rdata::tlsa::read(decoder, rdata_length).map(RData::TLSA)
@@ -556,6 +714,14 @@ impl RData {
                 debug!("reading TXT");
                 rdata::txt::read(decoder, rdata_length).map(RData::TXT)
             }
+            RecordType::URI => {
+                debug!("reading URI");
+                rdata::uri::read(decoder, rdata_length).map(RData::URI)
+            }
+            RecordType::ZONEMD => {
+                debug!("reading ZONEMD");
+                rdata::zonemd::read(decoder, rdata_length).map(RData::ZONEMD)
+            }
             #[cfg(feature = "dnssec")]
             RecordType::DNSSEC(record_type) => {
                 DNSSECRData::read(decoder, record_type, rdata_length).map(RData::DNSSEC)
@@ -602,23 +768,34 @@ impl RData {
             RData::A(address) => rdata::a::emit(encoder, address),
             RData::AAAA(ref address) => rdata::aaaa::emit(encoder, address),
             RData::CAA(ref caa) => rdata::caa::emit(encoder, caa),
+            RData::CERT(ref cert) => rdata::cert::emit(encoder, cert),
             // to_lowercase for rfc4034 and rfc6840
             RData::CNAME(ref name) | RData::NS(ref name) | RData::PTR(ref name) => {
                 rdata::name::emit(encoder, name)
             }
+            RData::CSYNC(ref csync) => rdata::csync::emit(encoder, csync),
+            RData::DHCID(ref dhcid) => rdata::dhcid::emit(encoder, dhcid),
             RData::ZERO => Ok(()),
+            RData::HINFO(ref hinfo) => rdata::hinfo::emit(encoder, hinfo),
+            RData::HTTPS(ref svcb) | RData::SVCB(ref svcb) => rdata::svcb::emit(encoder, svcb),
+            RData::LOC(ref loc) => rdata::loc::emit(encoder, loc),
             // to_lowercase for rfc4034 and rfc6840
             RData::MX(ref mx) => rdata::mx::emit(encoder, mx),
+            RData::NAPTR(ref naptr) => rdata::naptr::emit(encoder, naptr),
             RData::NULL(ref null) => rdata::null::emit(encoder, null),
             RData::OPENPGPKEY(ref openpgpkey) => rdata::openpgpkey::emit(encoder, openpgpkey),
             RData::OPT(ref opt) => rdata::opt::emit(encoder, opt),
             // to_lowercase for rfc4034 and rfc6840
+            RData::RP(ref rp) => rdata::rp::emit(encoder, rp),
+            // to_lowercase for rfc4034 and rfc6840
             RData::SOA(ref soa) => rdata::soa::emit(encoder, soa),
             // to_lowercase for rfc4034 and rfc6840
             RData::SRV(ref srv) => rdata::srv::emit(encoder, srv),
             RData::SSHFP(ref sshfp) => rdata::sshfp::emit(encoder, sshfp),
             RData::TLSA(ref tlsa) => rdata::tlsa::emit(encoder, tlsa),
             RData::TXT(ref txt) => rdata::txt::emit(encoder, txt),
+            RData::URI(ref uri) => rdata::uri::emit(encoder, uri),
+            RData::ZONEMD(ref zonemd) => rdata::zonemd::emit(encoder, zonemd),
             #[cfg(feature = "dnssec")]
             RData::DNSSEC(ref rdata) => rdata.emit(encoder),
             RData::Unknown { ref rdata, .. } => rdata::null::emit(encoder, rdata),
@@ -631,18 +808,29 @@ impl RData {
             RData::A(..) => RecordType::A,
             RData::AAAA(..) => RecordType::AAAA,
             RData::CAA(..) => RecordType::CAA,
+            RData::CERT(..) => RecordType::CERT,
             RData::CNAME(..) => RecordType::CNAME,
+            RData::CSYNC(..) => RecordType::CSYNC,
+            RData::DHCID(..) => RecordType::DHCID,
+            RData::HINFO(..) => RecordType::HINFO,
+            RData::HTTPS(..) => RecordType::HTTPS,
+            RData::LOC(..) => RecordType::LOC,
             RData::MX(..) => RecordType::MX,
+            RData::NAPTR(..) => RecordType::NAPTR,
             RData::NS(..) => RecordType::NS,
             RData::NULL(..) => RecordType::NULL,
             RData::OPENPGPKEY(..) => RecordType::OPENPGPKEY,
             RData::OPT(..) => RecordType::OPT,
             RData::PTR(..) => RecordType::PTR,
+            RData::RP(..) => RecordType::RP,
             RData::SOA(..) => RecordType::SOA,
             RData::SRV(..) => RecordType::SRV,
             RData::SSHFP(..) => RecordType::SSHFP,
+            RData::SVCB(..) => RecordType::SVCB,
             RData::TLSA(..) => RecordType::TLSA,
             RData::TXT(..) => RecordType::TXT,
+            RData::URI(..) => RecordType::URI,
+            RData::ZONEMD(..) => RecordType::ZONEMD,
             #[cfg(feature = "dnssec")]
             RData::DNSSEC(ref rdata) => RecordType::DNSSEC(DNSSECRData::to_record_type(rdata)),
             RData::Unknown { code, .. } => RecordType::Unknown(code),
@@ -875,18 +1063,29 @@ mod tests {
             RData::A(..) => RecordType::A,
             RData::AAAA(..) => RecordType::AAAA,
             RData::CAA(..) => RecordType::CAA,
+            RData::CERT(..) => RecordType::CERT,
             RData::CNAME(..) => RecordType::CNAME,
+            RData::CSYNC(..) => RecordType::CSYNC,
+            RData::DHCID(..) => RecordType::DHCID,
+            RData::HINFO(..) => RecordType::HINFO,
+            RData::HTTPS(..) => RecordType::HTTPS,
+            RData::LOC(..) => RecordType::LOC,
             RData::MX(..) => RecordType::MX,
+            RData::NAPTR(..) => RecordType::NAPTR,
             RData::NS(..) => RecordType::NS,
             RData::NULL(..) => RecordType::NULL,
             RData::OPENPGPKEY(..) => RecordType::OPENPGPKEY,
             RData::OPT(..) => RecordType::OPT,
             RData::PTR(..) => RecordType::PTR,
+            RData::RP(..) => RecordType::RP,
             RData::SOA(..) => RecordType::SOA,
             RData::SRV(..) => RecordType::SRV,
             RData::SSHFP(..) => RecordType::SSHFP,
+            RData::SVCB(..) => RecordType::SVCB,
             RData::TLSA(..) => RecordType::TLSA,
             RData::TXT(..) => RecordType::TXT,
+            RData::URI(..) => RecordType::URI,
+            RData::ZONEMD(..) => RecordType::ZONEMD,
             #[cfg(feature = "dnssec")]
             RData::DNSSEC(ref rdata) => RecordType::DNSSEC(rdata.to_record_type()),
             RData::Unknown { code, .. } => RecordType::Unknown(code),
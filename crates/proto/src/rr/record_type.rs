@@ -50,20 +50,30 @@ pub enum RecordType {
     AXFR,
     /// RFC 6844 Certification Authority Authorization
     CAA,
-    //  CERT,       //	37	RFC 4398	Certificate record
+    /// RFC 4398	Certificate record
+    CERT,
     /// RFC 1035[1]	Canonical name record
     CNAME,
-    //  DHCID,      //	49	RFC 4701	DHCP identifier
+    /// RFC 7477	Child-to-Parent Synchronization
+    CSYNC,
+    /// RFC 4701	DHCP identifier
+    DHCID,
     //  DNAME,      //	39	RFC 2672	Delegation Name
+    /// RFC 1035[1]	Host information
+    HINFO,
     //  HIP,        //	55	RFC 5205	Host Identity Protocol
+    /// RFC 9460	HTTPS service binding
+    HTTPS,
     //  IPSECKEY,   //	45	RFC 4025	IPsec Key
     /// RFC 1996	Incremental Zone Transfer
     IXFR,
     //  KX,         //	36	RFC 2230	Key eXchanger record
-    //  LOC,        //	29	RFC 1876	Location record
+    /// RFC 1876	Location record
+    LOC,
     /// RFC 1035[1]	Mail exchange record
     MX,
-    //  NAPTR,      //	35	RFC 3403	Naming Authority Pointer
+    /// RFC 3403	Naming Authority Pointer
+    NAPTR,
     /// RFC 1035[1]	Name server record
     NS,
     /// RFC 1035[1]	Null server record, for testing
@@ -74,13 +84,16 @@ pub enum RecordType {
     OPT,
     /// RFC 1035[1]	Pointer record
     PTR,
-    //  RP,         //	17	RFC 1183	Responsible person
+    /// RFC 1183	Responsible person
+    RP,
     /// RFC 1035[1] and RFC 2308[9]	Start of [a zone of] authority record
     SOA,
     /// RFC 2782	Service locator
     SRV,
     /// RFC 4255	SSH Public Key Fingerprint
     SSHFP,
+    /// RFC 9460	Service binding
+    SVCB,
     //  TA,         //	32768	N/A	DNSSEC Trust Authorities
     //  TKEY,       //	249	RFC 2930	Secret key record
     ///	RFC 6698	TLSA certificate association
@@ -88,6 +101,10 @@ pub enum RecordType {
     //  TSIG,       //	250	RFC 2845	Transaction Signature
     /// RFC 1035[1]	Text record
     TXT,
+    /// RFC 7553	Uniform Resource Identifier
+    URI,
+    /// RFC 8976	Message Digest for DNS Zones
+    ZONEMD,
 
     /// A DNSSEC- or SIG(0)- specific record type.
     ///
@@ -150,17 +167,28 @@ impl FromStr for RecordType {
             "A" => Ok(RecordType::A),
             "AAAA" => Ok(RecordType::AAAA),
             "CAA" => Ok(RecordType::CAA),
+            "CERT" => Ok(RecordType::CERT),
             "CNAME" => Ok(RecordType::CNAME),
+            "CSYNC" => Ok(RecordType::CSYNC),
+            "DHCID" => Ok(RecordType::DHCID),
+            "HINFO" => Ok(RecordType::HINFO),
+            "HTTPS" => Ok(RecordType::HTTPS),
+            "LOC" => Ok(RecordType::LOC),
             "NULL" => Ok(RecordType::NULL),
             "MX" => Ok(RecordType::MX),
+            "NAPTR" => Ok(RecordType::NAPTR),
             "NS" => Ok(RecordType::NS),
             "OPENPGPKEY" => Ok(RecordType::OPENPGPKEY),
             "PTR" => Ok(RecordType::PTR),
+            "RP" => Ok(RecordType::RP),
             "SOA" => Ok(RecordType::SOA),
             "SRV" => Ok(RecordType::SRV),
             "SSHFP" => Ok(RecordType::SSHFP),
+            "SVCB" => Ok(RecordType::SVCB),
             "TLSA" => Ok(RecordType::TLSA),
             "TXT" => Ok(RecordType::TXT),
+            "URI" => Ok(RecordType::URI),
+            "ZONEMD" => Ok(RecordType::ZONEMD),
             "ANY" | "*" => Ok(RecordType::ANY),
             "AXFR" => Ok(RecordType::AXFR),
             _ => Err(ProtoErrorKind::UnknownRecordTypeStr(str.to_string()).into()),
@@ -184,19 +212,30 @@ impl From<u16> for RecordType {
             255 => RecordType::ANY,
             252 => RecordType::AXFR,
             257 => RecordType::CAA,
+            37 => RecordType::CERT,
             5 => RecordType::CNAME,
+            62 => RecordType::CSYNC,
+            49 => RecordType::DHCID,
             0 => RecordType::ZERO,
+            13 => RecordType::HINFO,
+            65 => RecordType::HTTPS,
+            29 => RecordType::LOC,
             15 => RecordType::MX,
+            35 => RecordType::NAPTR,
             2 => RecordType::NS,
             10 => RecordType::NULL,
             61 => RecordType::OPENPGPKEY,
             41 => RecordType::OPT,
             12 => RecordType::PTR,
+            17 => RecordType::RP,
             6 => RecordType::SOA,
             33 => RecordType::SRV,
             44 => RecordType::SSHFP,
+            64 => RecordType::SVCB,
             52 => RecordType::TLSA,
             16 => RecordType::TXT,
+            256 => RecordType::URI,
+            63 => RecordType::ZONEMD,
             #[cfg(feature = "dnssec")]
             48/*DNSKEY*/ |
             43/*DS*/ |
@@ -249,20 +288,31 @@ impl From<RecordType> for &'static str {
             RecordType::ANY => "ANY",
             RecordType::AXFR => "AXFR",
             RecordType::CAA => "CAA",
+            RecordType::CERT => "CERT",
             RecordType::CNAME => "CNAME",
+            RecordType::CSYNC => "CSYNC",
+            RecordType::DHCID => "DHCID",
             RecordType::ZERO => "",
+            RecordType::HINFO => "HINFO",
+            RecordType::HTTPS => "HTTPS",
             RecordType::IXFR => "IXFR",
+            RecordType::LOC => "LOC",
             RecordType::MX => "MX",
+            RecordType::NAPTR => "NAPTR",
             RecordType::NULL => "NULL",
             RecordType::NS => "NS",
             RecordType::OPENPGPKEY => "OPENPGPKEY",
             RecordType::OPT => "OPT",
             RecordType::PTR => "PTR",
+            RecordType::RP => "RP",
             RecordType::SOA => "SOA",
             RecordType::SRV => "SRV",
             RecordType::SSHFP => "SSHFP",
+            RecordType::SVCB => "SVCB",
             RecordType::TLSA => "TLSA",
             RecordType::TXT => "TXT",
+            RecordType::URI => "URI",
+            RecordType::ZONEMD => "ZONEMD",
             #[cfg(feature = "dnssec")]
             RecordType::DNSSEC(rt) => rt.into(),
             RecordType::Unknown(_) => "Unknown",
@@ -287,20 +337,31 @@ impl From<RecordType> for u16 {
             RecordType::ANY => 255,
             RecordType::AXFR => 252,
             RecordType::CAA => 257,
+            RecordType::CERT => 37,
             RecordType::CNAME => 5,
+            RecordType::CSYNC => 62,
+            RecordType::DHCID => 49,
             RecordType::ZERO => 0,
+            RecordType::HINFO => 13,
+            RecordType::HTTPS => 65,
             RecordType::IXFR => 251,
+            RecordType::LOC => 29,
             RecordType::MX => 15,
+            RecordType::NAPTR => 35,
             RecordType::NS => 2,
             RecordType::NULL => 10,
             RecordType::OPENPGPKEY => 61,
             RecordType::OPT => 41,
             RecordType::PTR => 12,
+            RecordType::RP => 17,
             RecordType::SOA => 6,
             RecordType::SRV => 33,
             RecordType::SSHFP => 44,
+            RecordType::SVCB => 64,
             RecordType::TLSA => 52,
             RecordType::TXT => 16,
+            RecordType::URI => 256,
+            RecordType::ZONEMD => 63,
             #[cfg(feature = "dnssec")]
             RecordType::DNSSEC(rt) => rt.into(),
             RecordType::Unknown(code) => code,
@@ -446,7 +446,7 @@ impl IntoIterator for RecordSet {
 
 /// An iterator over all the records and their signatures
 #[cfg(feature = "dnssec")]
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct RecordsAndRrsigsIter<'r>(Chain<Iter<'r, Record>, RrsigsByAlgorithms<'r>>);
 
 #[cfg(feature = "dnssec")]
@@ -460,7 +460,7 @@ impl<'r> Iterator for RecordsAndRrsigsIter<'r> {
 
 /// An iterator that limits the record signatures by SupportedAlgorithms
 #[cfg(feature = "dnssec")]
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct RrsigsByAlgorithms<'r> {
     rrsigs: Iter<'r, Record>,
     supported_algorithms: SupportedAlgorithms,
@@ -501,7 +501,7 @@ impl<'r> Iterator for RrsigsByAlgorithms<'r> {
 }
 
 /// An iterator over the RecordSet data
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum RrsetRecords<'r> {
     /// There are no records in the record set
     Empty,
@@ -0,0 +1,335 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! ZONEMD, computation and verification of message digests of zones
+//!
+//! See [RFC 8976](https://tools.ietf.org/html/rfc8976). The digest covers the RRSIGs of the zone, so in a signed
+//!  zone it must be computed after signing, and then only the apex ZONEMD RRset is signed.
+
+use error::*;
+use rr::dnssec::rdata::{DNSSECRData, DNSSECRecordType};
+use rr::dnssec::DigestType;
+use rr::rdata::zonemd::{ZonemdHashAlgorithm, ZonemdScheme, ZONEMD};
+use rr::{Name, RData, Record, RecordType};
+use serialize::binary::*;
+
+/// Returns true if the digest of a zone can be computed with the scheme and hash algorithm
+pub fn is_supported(scheme: ZonemdScheme, hash_algorithm: ZonemdHashAlgorithm) -> bool {
+    digest_type(scheme, hash_algorithm).is_ok()
+}
+
+fn digest_type(
+    scheme: ZonemdScheme,
+    hash_algorithm: ZonemdHashAlgorithm,
+) -> ProtoResult<DigestType> {
+    match (scheme, hash_algorithm) {
+        (ZonemdScheme::Simple, ZonemdHashAlgorithm::SHA384) => Ok(DigestType::SHA384),
+        (ZonemdScheme::Simple, ZonemdHashAlgorithm::SHA512) => Ok(DigestType::SHA512),
+        _ => Err(format!(
+            "unsupported ZONEMD scheme or hash algorithm: {}, {}",
+            u8::from(scheme),
+            u8::from(hash_algorithm)
+        )
+        .into()),
+    }
+}
+
+/// Returns true if the record is a ZONEMD at the apex, or an RRSIG covering one
+fn is_apex_zonemd(origin: &Name, record: &Record) -> bool {
+    if record.name() != origin {
+        return false;
+    }
+
+    match (record.rr_type(), record.rdata()) {
+        (RecordType::ZONEMD, _) => true,
+        (
+            RecordType::DNSSEC(DNSSECRecordType::RRSIG),
+            &RData::DNSSEC(DNSSECRData::SIG(ref sig)),
+        ) => sig.type_covered() == RecordType::ZONEMD,
+        _ => false,
+    }
+}
+
+/// Computes the digest of the zone, RFC 8976 section 3
+///
+/// # Arguments
+///
+/// * `origin` - the apex of the zone
+/// * `records` - all records of the zone, including the RRSIGs; the apex ZONEMD RRset and the RRSIGs covering it are
+///               excluded from the digest, duplicate records are only included once
+/// * `scheme` - the scheme to collate the records with, only SIMPLE is supported
+/// * `hash_algorithm` - the hash algorithm of the digest
+pub fn zone_digest(
+    origin: &Name,
+    records: &[Record],
+    scheme: ZonemdScheme,
+    hash_algorithm: ZonemdHashAlgorithm,
+) -> ProtoResult<Vec<u8>> {
+    let digest_type = digest_type(scheme, hash_algorithm)?;
+
+    // RFC 8976 section 3.3.1, the records are in canonical order and form, RFC 4034 section 6
+    let mut canonical_records = records
+        .iter()
+        .filter(|record| !is_apex_zonemd(origin, record))
+        .map(|record| {
+            let mut rdata = Vec::new();
+            {
+                let mut encoder = BinEncoder::new(&mut rdata);
+                encoder.set_canonical_names(true);
+                record.rdata().emit(&mut encoder)?;
+            }
+            Ok((record, rdata))
+        })
+        .collect::<ProtoResult<Vec<_>>>()?;
+
+    canonical_records.sort_by(|&(a, ref a_rdata), &(b, ref b_rdata)| {
+        a.name()
+            .cmp(b.name())
+            .then(a.rr_type().cmp(&b.rr_type()))
+            .then(a_rdata.cmp(b_rdata))
+    });
+    canonical_records.dedup_by(|&mut (a, ref a_rdata), &mut (b, ref b_rdata)| {
+        a.name() == b.name()
+            && a.rr_type() == b.rr_type()
+            && a.dns_class() == b.dns_class()
+            && a_rdata == b_rdata
+    });
+
+    let mut buf = Vec::new();
+    {
+        let mut encoder = BinEncoder::new(&mut buf);
+        encoder.set_canonical_names(true);
+        for (record, rdata) in canonical_records {
+            record
+                .name()
+                .to_lowercase()
+                .emit_as_canonical(&mut encoder, true)?;
+            record.rr_type().emit(&mut encoder)?;
+            record.dns_class().emit(&mut encoder)?;
+            encoder.emit_u32(record.ttl())?;
+            encoder.emit_u16(rdata.len() as u16)?;
+            encoder.emit_vec(&rdata)?;
+        }
+    }
+
+    Ok(digest_type.hash(&buf)?.as_ref().to_vec())
+}
+
+/// Verifies the zone with the ZONEMD records at its apex, RFC 8976 section 4
+///
+/// The zone is verified if any ZONEMD with the serial of the SOA and a supported scheme and hash algorithm has the
+///  digest of the zone. It is an error if there is no such ZONEMD, or if the zone has more than one ZONEMD with the
+///  same scheme and hash algorithm.
+pub fn verify(origin: &Name, records: &[Record]) -> ProtoResult<()> {
+    let serial = records
+        .iter()
+        .filter(|record| record.name() == origin)
+        .filter_map(|record| match *record.rdata() {
+            RData::SOA(ref soa) => Some(soa.serial()),
+            _ => None,
+        })
+        .next()
+        .ok_or_else(|| ProtoError::from(format!("no SOA at the apex of the zone: {}", origin)))?;
+
+    let zonemds: Vec<&ZONEMD> = records
+        .iter()
+        .filter(|record| record.name() == origin)
+        .filter_map(|record| match *record.rdata() {
+            RData::ZONEMD(ref zonemd) => Some(zonemd),
+            _ => None,
+        })
+        .collect();
+
+    for (i, zonemd) in zonemds.iter().enumerate() {
+        if zonemds[..i].iter().any(|other| {
+            other.scheme() == zonemd.scheme() && other.hash_algorithm() == zonemd.hash_algorithm()
+        }) {
+            return Err(format!(
+                "more than one ZONEMD with the same scheme and hash algorithm: {}",
+                origin
+            )
+            .into());
+        }
+    }
+
+    let mut verifiable = zonemds
+        .iter()
+        .filter(|zonemd| zonemd.serial() == serial)
+        .filter(|zonemd| is_supported(zonemd.scheme(), zonemd.hash_algorithm()))
+        .peekable();
+    if verifiable.peek().is_none() {
+        return Err(format!(
+            "no ZONEMD with the serial {} and a supported scheme and hash algorithm: {}",
+            serial, origin
+        )
+        .into());
+    }
+
+    for zonemd in verifiable {
+        let digest = zone_digest(origin, records, zonemd.scheme(), zonemd.hash_algorithm())?;
+        if digest == zonemd.digest() {
+            return Ok(());
+        }
+    }
+
+    Err(format!("digest of the zone does not match the ZONEMD: {}", origin).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+    use std::str::FromStr;
+
+    use data_encoding::HEXLOWER;
+
+    use super::*;
+    use rr::rdata::SOA;
+
+    fn name(name: &str) -> Name {
+        Name::from_str(name).unwrap()
+    }
+
+    fn zonemd(digest: Vec<u8>) -> Record {
+        Record::from_rdata(
+            name("example."),
+            86400,
+            RecordType::ZONEMD,
+            RData::ZONEMD(ZONEMD::new(
+                2018031900,
+                ZonemdScheme::Simple,
+                ZonemdHashAlgorithm::SHA384,
+                digest,
+            )),
+        )
+    }
+
+    /// RFC 8976 appendix A.1, the simple example zone
+    fn zone() -> Vec<Record> {
+        vec![
+            Record::from_rdata(
+                name("example."),
+                86400,
+                RecordType::SOA,
+                RData::SOA(SOA::new(
+                    name("ns1.example."),
+                    name("admin.example."),
+                    2018031900,
+                    1800,
+                    900,
+                    604800,
+                    86400,
+                )),
+            ),
+            Record::from_rdata(
+                name("example."),
+                86400,
+                RecordType::NS,
+                RData::NS(name("ns1.example.")),
+            ),
+            Record::from_rdata(
+                name("example."),
+                86400,
+                RecordType::NS,
+                RData::NS(name("ns2.example.")),
+            ),
+            zonemd(
+                HEXLOWER
+                    .decode(
+                        b"c68090d90a7aed716bc459f9340e3d7c1370d4d24b7e2fc3\
+                          a1ddc0b9a87153b9a9713b3c9ae5cc27777f98b8e730044c",
+                    )
+                    .unwrap(),
+            ),
+            Record::from_rdata(
+                name("ns1.example."),
+                3600,
+                RecordType::A,
+                RData::A(Ipv4Addr::new(203, 0, 113, 63)),
+            ),
+            Record::from_rdata(
+                name("NS2.example."),
+                3600,
+                RecordType::AAAA,
+                RData::AAAA(Ipv6Addr::from_str("2001:db8::63").unwrap()),
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_zone_digest() {
+        let mut records = zone();
+        let digest = zone_digest(
+            &name("example."),
+            &records,
+            ZonemdScheme::Simple,
+            ZonemdHashAlgorithm::SHA384,
+        )
+        .unwrap();
+        assert_eq!(
+            HEXLOWER.encode(&digest),
+            "c68090d90a7aed716bc459f9340e3d7c1370d4d24b7e2fc3\
+             a1ddc0b9a87153b9a9713b3c9ae5cc27777f98b8e730044c"
+        );
+
+        // the order, duplicates and the apex ZONEMD don't change the digest
+        records.reverse();
+        records.push(records[0].clone());
+        records.push(zonemd(vec![0; 48]));
+        assert_eq!(
+            zone_digest(
+                &name("example."),
+                &records,
+                ZonemdScheme::Simple,
+                ZonemdHashAlgorithm::SHA384,
+            )
+            .unwrap(),
+            digest
+        );
+
+        assert!(zone_digest(
+            &name("example."),
+            &records,
+            ZonemdScheme::Private(240),
+            ZonemdHashAlgorithm::SHA384,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_verify() {
+        let mut records = zone();
+        verify(&name("example."), &records).unwrap();
+
+        records.push(Record::from_rdata(
+            name("ns3.example."),
+            3600,
+            RecordType::A,
+            RData::A(Ipv4Addr::new(203, 0, 113, 64)),
+        ));
+        assert!(verify(&name("example."), &records).is_err());
+    }
+
+    #[test]
+    fn test_verify_invalid_zonemds() {
+        let mut records = zone();
+        records.retain(|record| record.rr_type() != RecordType::ZONEMD);
+        assert!(verify(&name("example."), &records).is_err());
+
+        // the serial doesn't match the SOA
+        let mut records = zone();
+        for record in &mut records {
+            if let RData::SOA(ref mut soa) = *record.rdata_mut() {
+                soa.increment_serial();
+            }
+        }
+        assert!(verify(&name("example."), &records).is_err());
+
+        let mut records = zone();
+        records.push(zonemd(vec![0; 48]));
+        assert!(verify(&name("example."), &records).is_err());
+    }
+}
@@ -14,6 +14,7 @@ use rand;
 
 use error::*;
 use op::{Message, MessageType, OpCode, Query};
+use rr::rdata::opt::EdnsOption;
 use xfer::{ignore_send, DnsRequest, DnsRequestOptions, DnsResponse, SerialMessage};
 
 // TODO: this should be configurable
@@ -150,6 +151,9 @@ pub trait DnsHandle: 'static + Clone + Send {
             let edns = message.edns_mut();
             edns.set_max_payload(MAX_PAYLOAD_LEN);
             edns.set_version(0);
+            if let Some(client_subnet) = options.client_subnet {
+                edns.set_option(EdnsOption::Subnet(client_subnet));
+            }
         }
 
         self.send(DnsRequest::new(message, options))
@@ -10,6 +10,7 @@
 use std::ops::{Deref, DerefMut};
 
 use op::Message;
+use rr::rdata::opt::ClientSubnet;
 
 /// A set of options for expressing options to how requests should be treated
 #[derive(Clone, Default)]
@@ -21,6 +22,10 @@ pub struct DnsRequestOptions {
     // /// If set, then the request will terminate early if all types have been received
     // pub expected_record_types: Option<SmallVec<[RecordType; 2]>>,
     // TODO: add EDNS options here?
+    /// The subnet of the client the request is made for, sent with EDNS, see [RFC 7871](https://tools.ietf.org/html/rfc7871)
+    ///
+    /// Used by `DnsHandle::lookup`, requests created otherwise carry their EDNS options in the message.
+    pub client_subnet: Option<ClientSubnet>,
 }
 
 /// A DNS reqeust object
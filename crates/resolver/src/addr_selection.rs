@@ -0,0 +1,312 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Destination address selection, see [RFC 6724](https://tools.ietf.org/html/rfc6724)
+//!
+//! Orders the addresses of a name the way connections to them should be attempted, e.g. IPv6 addresses are only
+//!  preferred when the host has a global IPv6 address to reach them from. The source address for each destination
+//!  is the one the host would use to connect to it.
+//!
+//! Of the rules of section 6, the rules 3 (avoid deprecated addresses), 4 (prefer home addresses) and 7 (prefer
+//!  native transport) are not applied, the information for them is not available portably.
+
+use std::cmp::Ordering;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+
+/// The default policy table of RFC 6724 section 2.1: prefix, prefix length, precedence and label
+///
+/// The entries are sorted from the longest to the shortest prefix, so that the first match is the longest.
+const POLICY_TABLE: [(u128, u8, u8, u8); 9] = [
+    // ::1/128, loopback
+    (0x0000_0000_0000_0000_0000_0000_0000_0001, 128, 50, 0),
+    // ::ffff:0:0/96, IPv4-mapped
+    (0x0000_0000_0000_0000_0000_ffff_0000_0000, 96, 35, 4),
+    // ::/96, IPv4-compatible
+    (0x0000_0000_0000_0000_0000_0000_0000_0000, 96, 1, 3),
+    // 2001::/32, Teredo
+    (0x2001_0000_0000_0000_0000_0000_0000_0000, 32, 5, 5),
+    // 2002::/16, 6to4
+    (0x2002_0000_0000_0000_0000_0000_0000_0000, 16, 30, 2),
+    // 3ffe::/16, 6bone
+    (0x3ffe_0000_0000_0000_0000_0000_0000_0000, 16, 1, 12),
+    // fec0::/10, site-local
+    (0xfec0_0000_0000_0000_0000_0000_0000_0000, 10, 1, 11),
+    // fc00::/7, unique local
+    (0xfc00_0000_0000_0000_0000_0000_0000_0000, 7, 3, 13),
+    // ::/0
+    (0x0000_0000_0000_0000_0000_0000_0000_0000, 0, 40, 1),
+];
+
+const SCOPE_LINK_LOCAL: u8 = 0x2;
+const SCOPE_SITE_LOCAL: u8 = 0x5;
+const SCOPE_GLOBAL: u8 = 0xe;
+
+/// Sorts the addresses in the order in which connections to them should be attempted
+///
+/// The sort is stable, addresses which are equally preferable keep their order.
+pub fn sort_destinations(addrs: &mut [IpAddr]) {
+    sort_destinations_with(addrs, source_address)
+}
+
+/// Sorts the addresses like `sort_destinations`, with the source address for each destination from `source_for`
+///
+/// Destinations without a source address, i.e. which are unreachable, are sorted last.
+pub fn sort_destinations_with<F>(addrs: &mut [IpAddr], source_for: F)
+where
+    F: FnMut(IpAddr) -> Option<IpAddr>,
+{
+    sort_by_destination(addrs, |ip| Some(*ip), source_for)
+}
+
+/// Sorts the items by their destination address, items without an address are sorted last
+pub(crate) fn sort_by_destination<T, K, F>(items: &mut [T], mut ip_of: K, mut source_for: F)
+where
+    T: Clone,
+    K: FnMut(&T) -> Option<IpAddr>,
+    F: FnMut(IpAddr) -> Option<IpAddr>,
+{
+    let mut keyed = items
+        .iter()
+        .map(|item| {
+            let destination = ip_of(item).map(|ip| Destination {
+                ip,
+                source: source_for(ip),
+            });
+            (item.clone(), destination)
+        })
+        .collect::<Vec<_>>();
+
+    keyed.sort_by(|(_, a), (_, b)| match (a, b) {
+        (Some(a), Some(b)) => a.cmp_preference(b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    });
+
+    for (item, (sorted, _)) in items.iter_mut().zip(keyed) {
+        *item = sorted;
+    }
+}
+
+/// Returns the source address the host would use to connect to the destination, `None` if it is unreachable
+///
+/// No packets are sent, connecting a UDP socket only selects the route to the destination.
+pub fn source_address(destination: IpAddr) -> Option<IpAddr> {
+    let unspecified = match destination {
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    };
+
+    let socket = UdpSocket::bind(SocketAddr::new(unspecified, 0)).ok()?;
+    socket.connect(SocketAddr::new(destination, 53)).ok()?;
+    socket.local_addr().ok().map(|addr| addr.ip())
+}
+
+struct Destination {
+    ip: IpAddr,
+    source: Option<IpAddr>,
+}
+
+impl Destination {
+    /// Orders by the rules of RFC 6724 section 6, the preferred destination is the lesser
+    fn cmp_preference(&self, other: &Self) -> Ordering {
+        let (source_a, source_b) = match (self.source, other.source) {
+            (Some(a), Some(b)) => (a, b),
+            // Rule 1: Avoid unusable destinations
+            (Some(_), None) => return Ordering::Less,
+            (None, Some(_)) => return Ordering::Greater,
+            (None, None) => return Ordering::Equal,
+        };
+
+        let (scope_a, scope_b) = (scope(self.ip), scope(other.ip));
+        let (policy_a, policy_b) = (policy(self.ip), policy(other.ip));
+
+        // Rule 2: Prefer matching scope
+        (scope_a == scope(source_a))
+            .cmp(&(scope_b == scope(source_b)))
+            .reverse()
+            // Rule 5: Prefer matching label
+            .then_with(|| {
+                let matches_a = policy_a.label == policy(source_a).label;
+                let matches_b = policy_b.label == policy(source_b).label;
+                matches_a.cmp(&matches_b).reverse()
+            })
+            // Rule 6: Prefer higher precedence
+            .then_with(|| policy_a.precedence.cmp(&policy_b.precedence).reverse())
+            // Rule 8: Prefer smaller scope
+            .then_with(|| scope_a.cmp(&scope_b))
+            // Rule 9: Use longest matching prefix, only for IPv6, IPv4 prefixes say little about the topology
+            .then_with(|| match (self.ip, source_a, other.ip, source_b) {
+                (IpAddr::V6(a), IpAddr::V6(sa), IpAddr::V6(b), IpAddr::V6(sb)) => {
+                    common_prefix_len(a, sa)
+                        .cmp(&common_prefix_len(b, sb))
+                        .reverse()
+                }
+                _ => Ordering::Equal,
+            })
+        // Rule 10: Otherwise, leave the order unchanged, the sort is stable
+    }
+}
+
+struct Policy {
+    precedence: u8,
+    label: u8,
+}
+
+/// Returns the entry of the policy table for the address, IPv4 addresses are looked up as IPv4-mapped addresses
+fn policy(ip: IpAddr) -> Policy {
+    let ip = match ip {
+        IpAddr::V4(ip) => ip.to_ipv6_mapped(),
+        IpAddr::V6(ip) => ip,
+    };
+    let ip = u128::from(ip);
+
+    let (_, _, precedence, label) = POLICY_TABLE
+        .iter()
+        .find(|&&(prefix, len, _, _)| len == 0 || (ip ^ prefix) >> (128 - u32::from(len)) == 0)
+        .expect("::/0 matches all addresses");
+
+    Policy {
+        precedence: *precedence,
+        label: *label,
+    }
+}
+
+/// Returns the scope of the address, see RFC 6724 section 3.1 and 3.2
+fn scope(ip: IpAddr) -> u8 {
+    match ip {
+        IpAddr::V4(ip) => {
+            if ip.is_loopback() || ip.is_link_local() {
+                SCOPE_LINK_LOCAL
+            } else {
+                SCOPE_GLOBAL
+            }
+        }
+        IpAddr::V6(ip) => {
+            let first = ip.segments()[0];
+            if first & 0xff00 == 0xff00 {
+                // multicast addresses carry their scope
+                (first & 0x000f) as u8
+            } else if ip.is_loopback() || first & 0xffc0 == 0xfe80 {
+                SCOPE_LINK_LOCAL
+            } else if first & 0xffc0 == 0xfec0 {
+                SCOPE_SITE_LOCAL
+            } else {
+                SCOPE_GLOBAL
+            }
+        }
+    }
+}
+
+/// Returns the length of the common prefix, at most the 64 bits of the network part
+fn common_prefix_len(a: Ipv6Addr, b: Ipv6Addr) -> u32 {
+    (u128::from(a) ^ u128::from(b)).leading_zeros().min(64)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    /// Sorts the destinations, each with the source address following it
+    fn sorted(destinations: &[(&str, &str)]) -> Vec<IpAddr> {
+        let destinations = destinations
+            .iter()
+            .map(|&(dst, src)| (IpAddr::from_str(dst).unwrap(), IpAddr::from_str(src).ok()))
+            .collect::<Vec<_>>();
+
+        let mut addrs = destinations.iter().map(|&(dst, _)| dst).collect::<Vec<_>>();
+        sort_destinations_with(&mut addrs, |ip| {
+            destinations
+                .iter()
+                .find(|&&(dst, _)| dst == ip)
+                .and_then(|&(_, src)| src)
+        });
+        addrs
+    }
+
+    fn ips(ips: &[&str]) -> Vec<IpAddr> {
+        ips.iter().map(|ip| IpAddr::from_str(ip).unwrap()).collect()
+    }
+
+    // the examples of RFC 6724 section 10.2
+    #[test]
+    fn test_matching_scope() {
+        assert_eq!(
+            sorted(&[
+                ("198.51.100.121", "169.254.13.78"),
+                ("2001:db8:1::1", "2001:db8:1::2"),
+            ]),
+            ips(&["2001:db8:1::1", "198.51.100.121"])
+        );
+        assert_eq!(
+            sorted(&[
+                ("2001:db8:1::1", "fe80::1"),
+                ("198.51.100.121", "198.51.100.117"),
+            ]),
+            ips(&["198.51.100.121", "2001:db8:1::1"])
+        );
+    }
+
+    #[test]
+    fn test_precedence() {
+        assert_eq!(
+            sorted(&[("10.1.2.3", "10.1.2.4"), ("2001:db8:1::1", "2001:db8:1::2")]),
+            ips(&["2001:db8:1::1", "10.1.2.3"])
+        );
+        assert_eq!(
+            sorted(&[
+                ("2002:c633:6401::1", "2002:c633:6401::2"),
+                ("2001:db8:1::1", "2001:db8:1::2"),
+            ]),
+            ips(&["2001:db8:1::1", "2002:c633:6401::1"])
+        );
+    }
+
+    #[test]
+    fn test_smaller_scope() {
+        assert_eq!(
+            sorted(&[("2001:db8:1::1", "2001:db8:1::2"), ("fe80::1", "fe80::2")]),
+            ips(&["fe80::1", "2001:db8:1::1"])
+        );
+    }
+
+    #[test]
+    fn test_longest_matching_prefix() {
+        assert_eq!(
+            sorted(&[
+                ("2001:db8:3ffe::1", "2001:db8:3f44::2"),
+                ("2001:db8:1::1", "2001:db8:1::2"),
+            ]),
+            ips(&["2001:db8:1::1", "2001:db8:3ffe::1"])
+        );
+    }
+
+    #[test]
+    fn test_matching_label() {
+        assert_eq!(
+            sorted(&[
+                ("2001:db8:1::1", "2002:c633:6401::2"),
+                ("2002:c633:6401::1", "2002:c633:6401::2"),
+            ]),
+            ips(&["2002:c633:6401::1", "2001:db8:1::1"])
+        );
+    }
+
+    #[test]
+    fn test_unusable_and_stable() {
+        assert_eq!(
+            sorted(&[
+                ("2001:db8:1::1", "none"),
+                ("192.0.2.1", "192.0.2.100"),
+                ("192.0.2.2", "192.0.2.100"),
+            ]),
+            ips(&["192.0.2.1", "192.0.2.2", "2001:db8:1::1"])
+        );
+    }
+}
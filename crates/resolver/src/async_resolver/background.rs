@@ -1,4 +1,7 @@
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime};
 
 use futures::{future, sync::mpsc, Async, Future, Poll, Stream};
 #[cfg(feature = "dnssec")]
@@ -9,14 +12,15 @@ use proto::{
     xfer::{DnsRequestOptions, RetryDnsHandle},
 };
 
-use config::{ResolverConfig, ResolverOpts};
+use config::{ReloadOpts, ResolverConfig, ResolverOpts};
 use dns_lru::DnsLru;
-use hosts::Hosts;
+use hosts::{read_hosts_conf, Hosts};
 use lookup::{Lookup, LookupEither, LookupFuture};
 use lookup_ip::LookupIpFuture;
 use lookup_state::CachingClient;
-use name_server::{ConnectionHandle, NameServerPool, StandardConnection};
+use name_server::{ConnectionHandle, ForwardingHandle, NameServerPool, StandardConnection};
 use proto::op::Query;
+use system_conf::read_resolv_conf;
 
 use super::Request;
 
@@ -33,57 +37,226 @@ pub(super) fn task(
     config: ResolverConfig,
     options: ResolverOpts,
     lru: Arc<Mutex<DnsLru>>,
+    pools: Arc<Mutex<Vec<NameServerPool<ConnectionHandle, StandardConnection>>>>,
+    reload: Option<Reload>,
     request_rx: mpsc::UnboundedReceiver<Request>,
 ) -> impl Future<Item = (), Error = ()> {
     future::lazy(move || {
-        let pool =
-            NameServerPool::<ConnectionHandle, StandardConnection>::from_config(&config, &options);
-        let either;
-        let client = RetryDnsHandle::new(pool.clone(), options.attempts);
-        if options.validate {
-            #[cfg(feature = "dnssec")]
-            {
-                either = LookupEither::Secure(SecureDnsHandle::new(client));
-            }
-
-            #[cfg(not(feature = "dnssec"))]
-            {
-                // TODO: should this just be a panic, or a pinned error?
-                warn!("validate option is only available with 'dnssec' feature");
-                either = LookupEither::Retry(client);
-            }
-        } else {
-            either = LookupEither::Retry(client);
-        }
+        let client_cache = client_cache(&config, &options, &lru, &pools);
 
-        let hosts = if options.use_hosts_file {
-            Some(Arc::new(Hosts::new()))
-        } else {
-            None
-        };
+        let system_hosts = reload.as_ref().map(|reload| reload.hosts.as_path());
+        let hosts = hosts(&config, &options, system_hosts);
 
         Task {
             config,
             options,
-            client_cache: CachingClient::with_cache(lru, either),
+            client_cache,
             hosts,
+            lru,
+            pools,
+            reload,
             request_rx,
         }
     })
 }
 
+/// Returns the hosts of the system hosts file, if enabled, of the hosts files of the config and its static records
+///
+/// The system hosts file is read from `system_hosts`, or the default location if `None`.
+fn hosts(
+    config: &ResolverConfig,
+    options: &ResolverOpts,
+    system_hosts: Option<&Path>,
+) -> Option<Arc<Hosts>> {
+    if !options.use_hosts_file
+        && config.hosts_files().is_empty()
+        && config.static_records().is_empty()
+    {
+        return None;
+    }
+
+    let mut hosts = match (options.use_hosts_file, system_hosts) {
+        (true, Some(path)) => read_hosts_conf(path).unwrap_or_default(),
+        (true, None) => Hosts::new(),
+        (false, _) => Hosts::default(),
+    };
+    for path in config.hosts_files() {
+        if let Err(e) = hosts.read_hosts_file(path) {
+            warn!("failed to read hosts file {}: {}", path.display(), e);
+        }
+    }
+    for record in config.static_records() {
+        hosts.insert_record(record);
+    }
+
+    Some(Arc::new(hosts))
+}
+
+/// Returns the caching client for the name servers of the config and its forward zones
+fn client_cache(
+    config: &ResolverConfig,
+    options: &ResolverOpts,
+    lru: &Arc<Mutex<DnsLru>>,
+    pools: &Mutex<Vec<NameServerPool<ConnectionHandle, StandardConnection>>>,
+) -> ClientCache {
+    let default = client(config, options, options.validate, pools);
+    let either = if config.forward_zones().is_empty() {
+        default
+    } else {
+        let zones = config
+            .forward_zones()
+            .iter()
+            .map(|zone| {
+                let zone_config =
+                    ResolverConfig::from_parts(None, vec![], zone.name_servers().to_vec());
+                let client = client(&zone_config, options, zone.validate(), pools);
+                (zone.domain().clone(), client)
+            }).collect();
+
+        LookupEither::Forward(ForwardingHandle::new(default, zones))
+    };
+
+    let dns64 = config.dns64().cloned().map(Arc::new);
+    CachingClient::with_cache(lru.clone(), either)
+        .with_dns64(dns64)
+        .with_client_subnet(options.client_subnet)
+}
+
+/// Returns the client for the name servers of the config, validating responses if `validate` is true
+///
+/// The pool of the name servers is added to `pools`, for their statistics.
+fn client(
+    config: &ResolverConfig,
+    options: &ResolverOpts,
+    validate: bool,
+    pools: &Mutex<Vec<NameServerPool<ConnectionHandle, StandardConnection>>>,
+) -> LookupEither<ConnectionHandle, StandardConnection> {
+    let pool = NameServerPool::<ConnectionHandle, StandardConnection>::from_config(config, options);
+    match pools.lock() {
+        Ok(mut pools) => pools.push(pool.clone()),
+        Err(e) => warn!("failed to register name server pool: {}", e),
+    }
+    let client = RetryDnsHandle::new(pool, options.attempts);
+    if validate {
+        #[cfg(feature = "dnssec")]
+        {
+            LookupEither::Secure(SecureDnsHandle::new(client))
+        }
+
+        #[cfg(not(feature = "dnssec"))]
+        {
+            // TODO: should this just be a panic, or a pinned error?
+            warn!("validate option is only available with 'dnssec' feature");
+            LookupEither::Retry(client)
+        }
+    } else {
+        LookupEither::Retry(client)
+    }
+}
+
 type ClientCache = CachingClient<LookupEither<ConnectionHandle, StandardConnection>>;
 
+/// The files of the system configuration, which are reloaded when they change
+pub(crate) struct Reload {
+    opts: ReloadOpts,
+    resolv_conf: PathBuf,
+    hosts: PathBuf,
+    resolv_conf_version: Option<FileVersion>,
+    hosts_version: Option<FileVersion>,
+    next_check: Instant,
+}
+
+/// The modification time and length of a file, either changes when the file is written
+type FileVersion = (SystemTime, u64);
+
+fn file_version(path: &Path) -> Option<FileVersion> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+impl Reload {
+    /// Watches the files, which are expected to have just been read
+    pub(crate) fn new(opts: ReloadOpts, resolv_conf: PathBuf, hosts: PathBuf) -> Self {
+        Reload {
+            opts,
+            resolv_conf_version: file_version(&resolv_conf),
+            hosts_version: file_version(&hosts),
+            resolv_conf,
+            hosts,
+            next_check: Instant::now() + opts.interval,
+        }
+    }
+}
+
 /// Background task that resolves DNS queries.
 struct Task {
     config: ResolverConfig,
     options: ResolverOpts,
     client_cache: ClientCache,
     hosts: Option<Arc<Hosts>>,
+    lru: Arc<Mutex<DnsLru>>,
+    pools: Arc<Mutex<Vec<NameServerPool<ConnectionHandle, StandardConnection>>>>,
+    reload: Option<Reload>,
     request_rx: mpsc::UnboundedReceiver<Request>,
 }
 
 impl Task {
+    /// Reloads the configuration and hosts file if they changed since they were read
+    ///
+    /// Lookups which are in progress finish with the previous name servers.
+    fn reload_if_changed(&mut self) {
+        let now = Instant::now();
+        let (resolv_conf_changed, hosts_changed, flush_cache) = match self.reload {
+            Some(ref mut reload) if now >= reload.next_check => {
+                reload.next_check = now + reload.opts.interval;
+
+                let resolv_conf_version = file_version(&reload.resolv_conf);
+                let hosts_version = file_version(&reload.hosts);
+                let changed = (
+                    resolv_conf_version != reload.resolv_conf_version,
+                    hosts_version != reload.hosts_version,
+                    reload.opts.flush_cache,
+                );
+                reload.resolv_conf_version = resolv_conf_version;
+                reload.hosts_version = hosts_version;
+                changed
+            }
+            _ => return,
+        };
+
+        if !resolv_conf_changed && !hosts_changed {
+            return;
+        }
+
+        let reload = self.reload.as_ref().expect("reload is set");
+        if resolv_conf_changed {
+            match read_resolv_conf(&reload.resolv_conf) {
+                Ok((config, options)) => {
+                    debug!("reloaded {}", reload.resolv_conf.display());
+                    match self.pools.lock() {
+                        Ok(mut pools) => pools.clear(),
+                        Err(e) => warn!("failed to clear name server pools: {}", e),
+                    }
+                    self.client_cache = client_cache(&config, &options, &self.lru, &self.pools);
+                    self.config = config;
+                    self.options = options;
+                }
+                Err(e) => warn!("failed to reload {}: {}", reload.resolv_conf.display(), e),
+            }
+        }
+
+        // also when only the options changed, which may have enabled the hosts file
+        debug!("reloading {}", reload.hosts.display());
+        self.hosts = hosts(&self.config, &self.options, Some(&reload.hosts));
+
+        if flush_cache {
+            match self.lru.lock() {
+                Ok(mut lru) => lru.clear(),
+                Err(e) => warn!("failed to flush cache: {}", e),
+            }
+        }
+    }
+
     fn lookup(
         &self,
         name: Name,
@@ -91,7 +264,13 @@ impl Task {
         options: DnsRequestOptions,
     ) -> LookupFuture {
         let names = self.build_names(name);
-        LookupFuture::lookup(names, record_type, options, self.client_cache.clone())
+        LookupFuture::lookup(
+            names,
+            record_type,
+            options,
+            self.client_cache.clone(),
+            self.hosts.clone(),
+        )
     }
 
     fn lookup_ip(&self, maybe_name: ProtoResult<Name>, maybe_ip: Option<RData>) -> LookupIpFuture {
@@ -135,7 +314,9 @@ impl Task {
             DnsRequestOptions::default(),
             hosts,
             finally_ip_addr,
-        )
+        ).with_single_request(self.options.single_request)
+        .with_sortlist(self.config.sortlist().to_vec())
+        .with_sort_destinations(self.options.sort_destinations)
     }
 
     fn push_name(name: Name, names: &mut Vec<Name>) {
@@ -163,7 +344,15 @@ impl Task {
                 names.push(name.clone());
             }
 
-            for search in self.config.search().iter().rev() {
+            // the search domains of the forward zones follow those of the config
+            let zone_searches = self
+                .config
+                .forward_zones()
+                .iter()
+                .flat_map(|zone| zone.search().iter());
+            let searches = self.config.search().iter().chain(zone_searches).collect::<Vec<_>>();
+
+            for search in searches.into_iter().rev() {
                 let name_search = name.clone().append_domain(search);
                 Self::push_name(name_search, &mut names);
             }
@@ -193,7 +382,12 @@ impl Future for Task {
             let poll = self.request_rx.poll().map_err(|e| {
                 error!("AsyncResolver poisoned: {:?}", e);
             });
-            match try_ready!(poll) {
+            let request = try_ready!(poll);
+            if request.is_some() {
+                self.reload_if_changed();
+            }
+
+            match request {
                 None => {
                     // mpsc::UnboundedReceiver::poll() returns `None` when the sender
                     // has been dropped.
@@ -6,9 +6,16 @@
 // copied, modified, or distributed except according to those terms.
 
 //! Structs for creating and using a AsyncResolver
+use std::cmp::Reverse;
 use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
 use std::net::IpAddr;
+use std::path::Path;
+#[cfg(unix)]
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime};
 
 use futures::{
     self, future,
@@ -17,14 +24,24 @@ use futures::{
 };
 use proto::error::ProtoResult;
 use proto::rr::domain::TryParseIp;
+use proto::rr::rdata::opt::ClientSubnet;
 use proto::rr::{IntoName, Name, RData, RecordType};
 use proto::xfer::DnsRequestOptions;
 
+use cache::ResolverCache;
+#[cfg(unix)]
+use config::ReloadOpts;
 use config::{ResolverConfig, ResolverOpts};
+#[cfg(feature = "dnssec")]
+use dane;
+use ddds;
 use dns_lru::{self, DnsLru};
 use error::*;
+#[cfg(unix)]
+use hosts;
 use lookup::{self, LookupFuture};
-use lookup_ip::LookupIpFuture;
+use lookup_ip::{LookupIpFuture, LookupIpStream};
+use name_server::{ConnectionHandle, NameServerPool, ServerStats, StandardConnection};
 
 mod background;
 
@@ -58,6 +75,12 @@ mod background;
 #[derive(Clone)]
 pub struct AsyncResolver {
     request_tx: mpsc::UnboundedSender<Request>,
+    lru: Arc<Mutex<DnsLru>>,
+    pools: Arc<Mutex<Vec<NameServerPool<ConnectionHandle, StandardConnection>>>>,
+    /// responses are validated with DNSSec, see `ResolverOpts::validate`
+    validate: bool,
+    /// the domains of the forward zones, the longest first, and whether their responses are validated with DNSSec
+    forward_zones: Arc<Vec<(Name, bool)>>,
 }
 
 /// A future that represents sending a request to a background task,
@@ -161,7 +184,48 @@ impl AsyncResolver {
         let lru = DnsLru::new(options.cache_size, dns_lru::TtlConfig::from_opts(&options));
         let lru = Arc::new(Mutex::new(lru));
 
-        Self::with_cache(config, options, lru)
+        Self::with_cache(config, options, lru, None)
+    }
+
+    /// Construct a new `AsyncResolver` with the cache restored from a file written by `save_cache`.
+    ///
+    /// Entries which expired since the cache was saved are discarded, the remaining TTLs are limited to
+    ///  `positive_max_ttl` and `negative_max_ttl` of the options. If the file does not exist or can not be read, the
+    ///  resolver starts with an empty cache.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - configuration, name_servers, etc. for the Resolver
+    /// * `options` - basic lookup options for the resolver
+    /// * `path` - the file the cache was saved to
+    ///
+    /// # Returns
+    ///
+    /// A tuple containing the new `AsyncResolver` and a future that drives the
+    /// background task that runs resolutions for the `AsyncResolver`. See the
+    /// documentation for `AsyncResolver` for more information on how to use
+    /// the background future.
+    pub fn new_with_cache_file(
+        config: ResolverConfig,
+        options: ResolverOpts,
+        path: &Path,
+    ) -> (Self, impl Future<Item = (), Error = ()>) {
+        let mut lru = DnsLru::new(options.cache_size, dns_lru::TtlConfig::from_opts(&options));
+
+        match File::open(path) {
+            Ok(file) => {
+                match lru.load(&mut BufReader::new(file), Instant::now(), SystemTime::now()) {
+                    Ok(count) => debug!("restored {} cache entries from {}", count, path.display()),
+                    Err(e) => warn!("failed to restore cache from {}: {}", path.display(), e),
+                }
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+                debug!("no cache to restore at {}", path.display())
+            }
+            Err(e) => warn!("failed to open cache {}: {}", path.display(), e),
+        }
+
+        Self::with_cache(config, options, Arc::new(Mutex::new(lru)), None)
     }
 
     /// Construct a new `AsyncResolver` with the associated Client and configuration.
@@ -171,6 +235,7 @@ impl AsyncResolver {
     /// * `config` - configuration, name_servers, etc. for the Resolver
     /// * `options` - basic lookup options for the resolver
     /// * `lru` - the cache to be used with the resolver
+    /// * `reload` - the files of the configuration to reload when they change
     ///
     /// # Returns
     ///
@@ -182,13 +247,71 @@ impl AsyncResolver {
         config: ResolverConfig,
         options: ResolverOpts,
         lru: Arc<Mutex<DnsLru>>,
+        reload: Option<background::Reload>,
     ) -> (Self, impl Future<Item = (), Error = ()>) {
         let (request_tx, request_rx) = mpsc::unbounded();
-        let background = background::task(config, options, lru, request_rx);
-        let handle = Self { request_tx };
+        let pools = Arc::new(Mutex::new(Vec::new()));
+        let validate = options.validate;
+        let mut forward_zones = config
+            .forward_zones()
+            .iter()
+            .map(|zone| (zone.domain().clone(), zone.validate()))
+            .collect::<Vec<_>>();
+        forward_zones.sort_by_key(|(zone, _)| Reverse(zone.num_labels()));
+        let background = background::task(
+            config,
+            options,
+            lru.clone(),
+            pools.clone(),
+            reload,
+            request_rx,
+        );
+        let handle = Self {
+            request_tx,
+            lru,
+            pools,
+            validate,
+            forward_zones: Arc::new(forward_zones),
+        };
         (handle, background)
     }
 
+    /// Returns a handle to the cache of this resolver, to inspect or flush it
+    pub fn cache(&self) -> ResolverCache {
+        ResolverCache::new(self.lru.clone())
+    }
+
+    /// Returns the statistics of the name servers, including their smoothed round trip times
+    ///
+    /// The name servers of the forward zones follow those of the config. The list is empty until the background
+    ///  future has been polled for the first time.
+    pub fn name_server_stats(&self) -> ResolveResult<Vec<ServerStats>> {
+        Ok(self.pools.lock()?.iter().flat_map(NameServerPool::stats).collect())
+    }
+
+    /// Saves the current entries of the cache to a file, to be restored with `new_with_cache_file`.
+    ///
+    /// The snapshot is written to a temporary file next to `path`, which then replaces `path`.
+    ///
+    /// # Returns
+    ///
+    /// The number of entries saved
+    pub fn save_cache(&self, path: &Path) -> ResolveResult<usize> {
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+
+        // the entries are written after the lock is released, lookups are not blocked by the file system
+        let snapshot = self.lru.lock()?.snapshot(Instant::now());
+        let count = {
+            let mut writer = BufWriter::new(File::create(&tmp_path)?);
+            snapshot.save(&mut writer, SystemTime::now())?
+        };
+
+        fs::rename(&tmp_path, path)?;
+        debug!("saved {} cache entries to {}", count, path.display());
+        Ok(count)
+    }
+
     /// Constructs a new Resolver with the system configuration.
     ///
     /// This will use `/etc/resolv.conf` on Unix OSes and the registry on Windows.
@@ -198,6 +321,41 @@ impl AsyncResolver {
         Ok(Self::new(config, options))
     }
 
+    /// Constructs a new Resolver with the system configuration, which is reloaded when it changes.
+    ///
+    /// The name servers and options are reloaded when `/etc/resolv.conf` changes, and the hosts file when it
+    ///  changes, e.g. after the network was changed by DHCP or a VPN. The files are checked for changes by their
+    ///  modification time and length, see [`ReloadOpts`] for how often. Lookups which are in progress while the
+    ///  configuration is reloaded finish with the previous name servers.
+    #[cfg(unix)]
+    pub fn from_system_conf_with_reload(
+        reload: ReloadOpts,
+    ) -> ResolveResult<(Self, impl Future<Item = (), Error = ()>)> {
+        Self::from_resolv_conf_with_reload(
+            PathBuf::from("/etc/resolv.conf"),
+            PathBuf::from(hosts::hosts_path()),
+            reload,
+        )
+    }
+
+    #[cfg(unix)]
+    fn from_resolv_conf_with_reload(
+        resolv_conf: PathBuf,
+        hosts: PathBuf,
+        reload: ReloadOpts,
+    ) -> ResolveResult<(Self, impl Future<Item = (), Error = ()>)> {
+        let (config, options) = super::system_conf::read_resolv_conf(&resolv_conf)?;
+        let lru = DnsLru::new(options.cache_size, dns_lru::TtlConfig::from_opts(&options));
+        let reload = background::Reload::new(reload, resolv_conf, hosts);
+
+        Ok(Self::with_cache(
+            config,
+            options,
+            Arc::new(Mutex::new(lru)),
+            Some(reload),
+        ))
+    }
+
     /// Generic lookup for any RecordType
     ///
     /// *WARNING* this interface may change in the future, see if one of the specializations would be better.
@@ -219,6 +377,35 @@ impl AsyncResolver {
         self.inner_lookup(name, record_type, DnsRequestOptions::default())
     }
 
+    /// Generic lookup for any RecordType, made for a client in the subnet
+    ///
+    /// The subnet is sent to the name servers with EDNS, unless `ResolverOpts::client_subnet` strips it, and answers
+    ///  tailored to it are only served from the cache to clients within their scope, see
+    ///  [RFC 7871](https://tools.ietf.org/html/rfc7871).
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - name of the record to lookup, if name is not a valid domain name, an error will be returned
+    /// * `record_type` - type of record to lookup, all RecordData responses will be filtered to this type
+    /// * `client_subnet` - the subnet of the client the lookup is made for
+    pub fn lookup_with_client_subnet<N: IntoName>(
+        &self,
+        name: N,
+        record_type: RecordType,
+        client_subnet: ClientSubnet,
+    ) -> BackgroundLookup {
+        let name = match name.into_name() {
+            Ok(name) => name,
+            Err(err) => return err.into(),
+        };
+
+        let options = DnsRequestOptions {
+            client_subnet: Some(client_subnet),
+            ..DnsRequestOptions::default()
+        };
+        self.inner_lookup(name, record_type, options)
+    }
+
     fn oneshot_canceled(_: oneshot::Canceled) -> ResolveError {
         ResolveErrorKind::Message("oneshot canceled unexpectedly, this is a bug").into()
     }
@@ -275,6 +462,34 @@ impl AsyncResolver {
         BackgroundLookupIp::from(f)
     }
 
+    /// Performs a dual-stack DNS lookup for the IPs of the given hostname, yielding them as they arrive.
+    ///
+    /// This is intended for establishing connections with Happy Eyeballs, [RFC 8305](https://tools.ietf.org/html/rfc8305):
+    ///  the addresses are yielded in the order in which connections to them should be attempted, the first as soon as
+    ///  possible, see `LookupIpStream`. The AAAA and A lookups are always both sent, regardless of
+    ///  `ResolverOpts::ip_strategy`.
+    ///
+    /// # Arguments
+    /// * `host` - string hostname, if this is an invalid hostname, an error will be returned.
+    pub fn lookup_ip_stream<N: IntoName + TryParseIp>(&self, host: N) -> LookupIpStream {
+        if let Some(ip) = host.try_parse_ip().and_then(|ip| ip.to_ip_addr()) {
+            return LookupIpStream::ip(ip);
+        }
+        let name = match host.into_name() {
+            Ok(name) => name,
+            Err(err) => return LookupIpStream::error(err.into()),
+        };
+
+        let aaaa: BackgroundLookup = self.inner_lookup(
+            name.clone(),
+            RecordType::AAAA,
+            DnsRequestOptions::default(),
+        );
+        let a: BackgroundLookup =
+            self.inner_lookup(name, RecordType::A, DnsRequestOptions::default());
+        LookupIpStream::new(Box::new(aaaa), Box::new(a))
+    }
+
     /// Performs a DNS lookup for an SRV record for the specified service type and protocol at the given name.
     ///
     /// This is a convenience method over `lookup_srv`, it combines the service, protocol and name into a single name: `_service._protocol.name`.
@@ -314,9 +529,200 @@ impl AsyncResolver {
     lookup_fn!(ipv4_lookup, lookup::Ipv4LookupFuture, RecordType::A);
     lookup_fn!(ipv6_lookup, lookup::Ipv6LookupFuture, RecordType::AAAA);
     lookup_fn!(mx_lookup, lookup::MxLookupFuture, RecordType::MX);
+    lookup_fn!(naptr_lookup, lookup::NaptrLookupFuture, RecordType::NAPTR);
     #[deprecated(note = "use lookup_srv instead, this interface is none ideal")]
     lookup_fn!(srv_lookup, lookup::SrvLookupFuture, RecordType::SRV);
     lookup_fn!(txt_lookup, lookup::TxtLookupFuture, RecordType::TXT);
+    lookup_fn!(caa_lookup, lookup::CaaLookupFuture, RecordType::CAA);
+    lookup_fn!(tlsa_lookup, lookup::TlsaLookupFuture, RecordType::TLSA);
+    lookup_fn!(sshfp_lookup, lookup::SshfpLookupFuture, RecordType::SSHFP);
+    lookup_fn!(
+        openpgpkey_lookup,
+        lookup::OpenpgpkeyLookupFuture,
+        RecordType::OPENPGPKEY
+    );
+    lookup_fn!(svcb_lookup, lookup::SvcbLookupFuture, RecordType::SVCB);
+    lookup_fn!(https_lookup, lookup::HttpsLookupFuture, RecordType::HTTPS);
+
+    /// Performs a lookup of the name servers of the zone, with the addresses of the name servers.
+    ///
+    /// The addresses are resolved with `lookup_ip` once the NS records are found.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the name of the zone, failure to parse will return an error
+    pub fn ns_lookup<N: IntoName>(&self, name: N) -> lookup::NsLookupFuture {
+        let name = match name.into_name() {
+            Ok(name) => name,
+            Err(err) => return lookup::NsLookupFuture::error(err.into()),
+        };
+
+        let ns: BackgroundLookup =
+            self.inner_lookup(name, RecordType::NS, DnsRequestOptions::default());
+        let resolver = self.clone();
+        lookup::NsLookupFuture::new(ns, move |name_server| resolver.lookup_ip(name_server))
+    }
+
+    /// Performs a lookup of the SOA of the zone which contains the name.
+    ///
+    /// If there is no SOA at the name, the SOA of its parent is looked up, up to the root.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the name, failure to parse will return an error
+    pub fn soa_lookup<N: IntoName>(&self, name: N) -> lookup::SoaLookupFuture {
+        let name = match name.into_name() {
+            Ok(name) => name,
+            Err(err) => return lookup::SoaLookupFuture::error(err.into()),
+        };
+
+        let resolver = self.clone();
+        lookup::SoaLookupFuture::new(name, move |name| -> BackgroundLookup {
+            resolver.inner_lookup(name, RecordType::SOA, DnsRequestOptions::default())
+        })
+    }
+
+    /// Performs a lookup of the endpoints of a service from its SVCB or HTTPS records.
+    ///
+    /// AliasMode records are followed to the ServiceMode records, which are returned sorted by priority.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the name of the service, e.g. `_8443._https.www.example.com.` or `www.example.com.` for HTTPS on
+    ///   port 443, failure to parse will return an error
+    /// * `record_type` - the type of the records, `SVCB` or `HTTPS`
+    pub fn service_lookup<N: IntoName>(
+        &self,
+        name: N,
+        record_type: RecordType,
+    ) -> lookup::ServiceLookupFuture {
+        let name = match name.into_name() {
+            Ok(name) => name,
+            Err(err) => return lookup::ServiceLookupFuture::error(err.into()),
+        };
+
+        match record_type {
+            RecordType::SVCB | RecordType::HTTPS => (),
+            _ => {
+                return lookup::ServiceLookupFuture::error(
+                    format!("not a service binding record type: {}", record_type).into(),
+                )
+            }
+        }
+
+        let resolver = self.clone();
+        lookup::ServiceLookupFuture::new(name, move |name| -> BackgroundLookup {
+            resolver.inner_lookup(name, record_type, DnsRequestOptions::default())
+        })
+    }
+
+    /// Performs a lookup of the validated TLSA records of a TLS service.
+    ///
+    /// If the host is an alias, the records of the service at the target of its CNAMEs are preferred, and those at the
+    ///  host are used only if the target has none, see RFC 7671 section 7. Without TLSA records, a `NoRecordsFound`
+    ///  error, DANE does not apply to the service.
+    ///
+    /// The responses for all names of the lookup, including the targets of the CNAMEs, must be validated with DNSSEC,
+    ///  see `ResolverOpts::validate` and `ForwardZone::validate`, the lookup fails at the first name which is not.
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - the name of the host, failure to parse will return an error
+    /// * `port` - the port of the service
+    /// * `transport` - the transport protocol of the service
+    #[cfg(feature = "dnssec")]
+    pub fn dane_lookup<N: IntoName>(
+        &self,
+        host: N,
+        port: u16,
+        transport: dane::Transport,
+    ) -> dane::DaneLookupFuture {
+        let host = match host.into_name() {
+            Ok(host) => host,
+            Err(err) => return dane::DaneLookupFuture::error(err.into()),
+        };
+
+        let resolver = self.clone();
+        dane::DaneLookupFuture::new(host, port, transport, move |name, record_type| {
+            if resolver.is_validated(&name) {
+                future::Either::A(resolver.inner_lookup::<LookupFuture>(
+                    name,
+                    record_type,
+                    DnsRequestOptions::default(),
+                ))
+            } else {
+                future::Either::B(future::err(
+                    ResolveErrorKind::Msg(format!(
+                        "DANE requires DNSSEC validation of {}, see ResolverOpts::validate and \
+                         ForwardZone::validate",
+                        name
+                    ))
+                    .into(),
+                ))
+            }
+        })
+    }
+
+    /// Returns true if the responses for the name are validated with DNSSec, by its forward zone or the options
+    #[cfg(feature = "dnssec")]
+    fn is_validated(&self, name: &Name) -> bool {
+        self.forward_zones
+            .iter()
+            .find(|(zone, _)| zone.zone_of(name))
+            .map_or(self.validate, |&(_, validate)| validate)
+    }
+
+    /// Resolves the Application Unique String (AUS) with the NAPTR records of the application, starting at the key.
+    ///
+    /// The rules are processed in order of the order and preference of the NAPTR records, the first rule whose
+    ///  services match the service and whose regexp matches the AUS is applied. Non-terminal rules continue with the
+    ///  NAPTR records of their result, terminal rules end with the URI, the SRV records or the addresses of their
+    ///  result, for the `U`, `S` and `A` flags respectively. The protocol specific `P` flag is not supported.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - the name of the first NAPTR records, failure to parse will return an error
+    /// * `aus` - the Application Unique String to which the rules are applied
+    /// * `service` - the service of the application, e.g. `E2U+sip`, an empty string matches all services
+    pub fn ddds_lookup<N: IntoName>(
+        &self,
+        key: N,
+        aus: &str,
+        service: &str,
+    ) -> ddds::DddsLookupFuture {
+        let key = match key.into_name() {
+            Ok(key) => key,
+            Err(err) => return ddds::DddsLookupFuture::error(err.into()),
+        };
+
+        let resolver = self.clone();
+        let ip_resolver = self.clone();
+        ddds::DddsLookupFuture::new(
+            key,
+            aus.to_string(),
+            service.to_string(),
+            move |name, record_type| -> BackgroundLookup {
+                resolver.inner_lookup(name, record_type, DnsRequestOptions::default())
+            },
+            move |name| ip_resolver.lookup_ip(name),
+        )
+    }
+
+    /// Resolves the telephone number to a URI with ENUM, see `ddds_lookup`.
+    ///
+    /// # Arguments
+    ///
+    /// * `number` - the E.164 telephone number with a leading `+`, e.g. `+44 20 7946 0000`
+    /// * `service` - the enumservice, e.g. `E2U+sip`
+    pub fn enum_lookup(&self, number: &str, service: &str) -> ddds::DddsLookupFuture {
+        // the AUS is the number without any non-digit characters except for the leading '+', RFC 6116 section 3.4
+        let (key, aus) = match (ddds::enum_name(number), ddds::e164_digits(number)) {
+            (Ok(key), Ok(digits)) => (key, format!("+{}", digits)),
+            (Err(e), _) | (_, Err(e)) => return ddds::DddsLookupFuture::error(e),
+        };
+
+        self.ddds_lookup(key, &aus, service)
+    }
 }
 
 impl fmt::Debug for AsyncResolver {
@@ -380,11 +786,13 @@ mod tests {
     use failure::Fail;
     use std::net::*;
     use std::str::FromStr;
+    use std::time::Duration;
 
     use self::tokio::runtime::current_thread::Runtime;
+    use proto::rr::Record;
     use proto::xfer::DnsRequest;
 
-    use config::{LookupIpStrategy, NameServerConfig};
+    use config::{ForwardZone, LookupIpStrategy, NameServerConfig, NameServerConfigGroup};
 
     use super::*;
 
@@ -978,4 +1386,262 @@ mod tests {
             IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0xc633, 0x6423))
         );
     }
+
+    #[test]
+    fn test_save_and_restore_cache() {
+        use std::{env, process};
+
+        let path = env::temp_dir().join(format!("trust-dns-resolver-cache-{}", process::id()));
+        let query = proto::op::Query::query(
+            Name::from_str("www.example.com.").unwrap(),
+            RecordType::A,
+        );
+
+        // nothing to restore yet
+        let (resolver, _bg) = AsyncResolver::new_with_cache_file(
+            ResolverConfig::default(),
+            ResolverOpts::default(),
+            &path,
+        );
+        resolver.lru.lock().unwrap().insert(
+            query.clone(),
+            None,
+            vec![(RData::A(Ipv4Addr::new(127, 0, 0, 1)), 300)],
+            Instant::now(),
+        );
+        assert_eq!(resolver.save_cache(&path).expect("failed to save cache"), 1);
+
+        let (restored, _bg) = AsyncResolver::new_with_cache_file(
+            ResolverConfig::default(),
+            ResolverOpts::default(),
+            &path,
+        );
+        fs::remove_file(&path).expect("failed to remove cache");
+
+        let lookup = restored
+            .lru
+            .lock()
+            .unwrap()
+            .get(&query, None, Instant::now())
+            .expect("cache was not restored");
+        assert_eq!(
+            lookup.iter().cloned().collect::<Vec<_>>(),
+            vec![RData::A(Ipv4Addr::new(127, 0, 0, 1))]
+        );
+    }
+
+    #[test]
+    fn test_name_server_stats() {
+        let mut io_loop = Runtime::new().unwrap();
+        let mut config = ResolverConfig::default();
+        config.add_forward_zone(ForwardZone::new(
+            Name::from_str("example.").unwrap(),
+            NameServerConfigGroup::from_ips_clear(&[IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1))], 53),
+        ));
+        let (resolver, mut bg) = AsyncResolver::new(config.clone(), ResolverOpts::default());
+        assert!(resolver.name_server_stats().unwrap().is_empty());
+
+        // the name servers are set up on the first poll of the background
+        io_loop.block_on(future::lazy(|| bg.poll())).unwrap();
+
+        let stats = resolver.name_server_stats().unwrap();
+        assert_eq!(stats.len(), config.name_servers().len() + 2);
+        assert!(stats.iter().all(|stats| stats.is_healthy() && stats.srtt().is_none()));
+        assert_eq!(
+            stats.last().unwrap().config().socket_addr,
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 53)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "dnssec")]
+    fn test_dane_lookup_not_validated() {
+        let mut config = ResolverConfig::default();
+        let mut validated = ForwardZone::new(
+            Name::from_str("secure.example.").unwrap(),
+            NameServerConfigGroup::from_ips_clear(&[IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1))], 53),
+        );
+        validated.set_validate(true);
+        config.add_forward_zone(validated);
+        config.add_forward_zone(ForwardZone::new(
+            Name::from_str("example.").unwrap(),
+            NameServerConfigGroup::from_ips_clear(&[IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1))], 53),
+        ));
+        let (resolver, _bg) = AsyncResolver::new(
+            config,
+            ResolverOpts {
+                validate: true,
+                ..ResolverOpts::default()
+            },
+        );
+
+        assert!(resolver.is_validated(&Name::from_str("www.example.com.").unwrap()));
+        assert!(!resolver.is_validated(&Name::from_str("www.example.").unwrap()));
+        assert!(resolver.is_validated(&Name::from_str("www.secure.example.").unwrap()));
+
+        // fails before any query is sent to the name servers of the forward zone
+        let mut io_loop = Runtime::new().unwrap();
+        let err = io_loop
+            .block_on(resolver.dane_lookup("www.example.", 443, dane::Transport::Tcp))
+            .unwrap_err();
+        assert!(err.to_string().contains("DANE requires DNSSEC validation"));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_reload_system_conf() {
+        use std::{env, process};
+
+        let dir = env::temp_dir();
+        let resolv_conf = dir.join(format!("trust-dns-resolver-resolv.conf-{}", process::id()));
+        let hosts = dir.join(format!("trust-dns-resolver-hosts-{}", process::id()));
+        fs::write(&resolv_conf, "nameserver 127.0.0.1\n").unwrap();
+        fs::write(&hosts, "10.0.0.1 host.example.\n").unwrap();
+
+        let mut io_loop = Runtime::new().unwrap();
+        let reload = ReloadOpts {
+            interval: Duration::from_secs(0),
+            flush_cache: true,
+        };
+        let (resolver, bg) =
+            AsyncResolver::from_resolv_conf_with_reload(resolv_conf.clone(), hosts.clone(), reload)
+                .expect("failed to read config");
+        io_loop.spawn(bg);
+
+        let lookup = io_loop.block_on(resolver.lookup_ip("host.example.")).unwrap();
+        assert_eq!(
+            lookup.iter().collect::<Vec<_>>(),
+            vec![IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))]
+        );
+        let name_servers = |resolver: &AsyncResolver| {
+            resolver
+                .name_server_stats()
+                .unwrap()
+                .iter()
+                .map(|stats| stats.config().socket_addr.ip())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            name_servers(&resolver),
+            vec![IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)); 2]
+        );
+
+        resolver.lru.lock().unwrap().insert(
+            proto::op::Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A),
+            None,
+            vec![(RData::A(Ipv4Addr::new(127, 0, 0, 1)), 300)],
+            Instant::now(),
+        );
+
+        // the lengths change, as the modification times may be within the same second
+        fs::write(&resolv_conf, "nameserver 127.0.0.2\nnameserver 127.0.0.3\n").unwrap();
+        fs::write(&hosts, "10.0.0.2 host.example. other.example.\n").unwrap();
+
+        let lookup = io_loop.block_on(resolver.lookup_ip("host.example.")).unwrap();
+        fs::remove_file(&resolv_conf).unwrap();
+        fs::remove_file(&hosts).unwrap();
+
+        assert_eq!(
+            lookup.iter().collect::<Vec<_>>(),
+            vec![IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2))]
+        );
+        assert_eq!(
+            name_servers(&resolver),
+            vec![
+                IpAddr::V4(Ipv4Addr::new(127, 0, 0, 2)),
+                IpAddr::V4(Ipv4Addr::new(127, 0, 0, 3)),
+                IpAddr::V4(Ipv4Addr::new(127, 0, 0, 2)),
+                IpAddr::V4(Ipv4Addr::new(127, 0, 0, 3)),
+            ]
+        );
+        assert_eq!(resolver.cache().stats().unwrap().entries, 0);
+    }
+
+    #[test]
+    fn test_hosts_files_and_static_records() {
+        use std::{env, process};
+
+        let hosts =
+            env::temp_dir().join(format!("trust-dns-resolver-hosts-files-{}", process::id()));
+        fs::write(&hosts, "10.0.0.1 host.example. alias.example.\n").unwrap();
+
+        let mut config = ResolverConfig::new();
+        config.add_hosts_file(hosts.clone());
+        config.add_static_record(Record::from_rdata(
+            Name::from_str("static.example.").unwrap(),
+            300,
+            RecordType::A,
+            RData::A(Ipv4Addr::new(10, 0, 0, 2)),
+        ));
+        let options = ResolverOpts {
+            use_hosts_file: false,
+            ..ResolverOpts::default()
+        };
+
+        let mut io_loop = Runtime::new().unwrap();
+        let (resolver, bg) = AsyncResolver::new(config, options);
+        io_loop.spawn(bg);
+
+        let lookup =
+            io_loop.block_on(resolver.reverse_lookup(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))));
+        fs::remove_file(&hosts).unwrap();
+        assert_eq!(
+            lookup.unwrap().iter().collect::<Vec<_>>(),
+            vec![&Name::from_str("host.example.").unwrap()]
+        );
+
+        let lookup = io_loop.block_on(resolver.lookup_ip("alias.example.")).unwrap();
+        assert_eq!(
+            lookup.iter().collect::<Vec<_>>(),
+            vec![IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))]
+        );
+        let lookup = io_loop.block_on(resolver.lookup_ip("static.example.")).unwrap();
+        assert_eq!(
+            lookup.iter().collect::<Vec<_>>(),
+            vec![IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2))]
+        );
+    }
+
+    #[test]
+    fn test_lookup_ip_stream() {
+        use futures::Stream;
+
+        let name = Name::from_str("dual.example.").unwrap();
+        let v4 = Ipv4Addr::new(10, 0, 0, 1);
+        let v6 = Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 1);
+
+        let mut config = ResolverConfig::new();
+        config.add_static_record(Record::from_rdata(
+            name.clone(),
+            300,
+            RecordType::A,
+            RData::A(v4),
+        ));
+        config.add_static_record(Record::from_rdata(
+            name.clone(),
+            300,
+            RecordType::AAAA,
+            RData::AAAA(v6),
+        ));
+        let options = ResolverOpts {
+            use_hosts_file: false,
+            ..ResolverOpts::default()
+        };
+
+        let mut io_loop = Runtime::new().unwrap();
+        let (resolver, bg) = AsyncResolver::new(config, options);
+        io_loop.spawn(bg);
+
+        // the order depends on the addresses of the host
+        let mut ips = io_loop
+            .block_on(resolver.lookup_ip_stream(name).collect())
+            .unwrap();
+        ips.sort();
+        assert_eq!(ips, vec![IpAddr::V4(v4), IpAddr::V6(v6)]);
+
+        let ips = io_loop
+            .block_on(resolver.lookup_ip_stream("10.0.0.2").collect())
+            .unwrap();
+        assert_eq!(ips, vec![IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2))]);
+    }
 }
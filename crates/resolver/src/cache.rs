@@ -0,0 +1,243 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Inspection and management of the lookup cache of the Resolver
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use proto::op::Query;
+use proto::rr::Name;
+
+use dns_lru::DnsLru;
+use error::*;
+use lookup::Lookup;
+
+/// A handle to the cache of an `AsyncResolver` or `Resolver`
+///
+/// All clones of the handle, and the resolver it was taken from, share the same cache.
+#[derive(Clone)]
+pub struct ResolverCache {
+    lru: Arc<Mutex<DnsLru>>,
+}
+
+impl ResolverCache {
+    pub(crate) fn new(lru: Arc<Mutex<DnsLru>>) -> Self {
+        ResolverCache { lru }
+    }
+
+    /// Removes all entries from the cache
+    pub fn flush(&self) -> ResolveResult<()> {
+        self.lru.lock()?.clear();
+        Ok(())
+    }
+
+    /// Removes all entries for the name from the cache, of any record type
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the name to remove the entries of
+    /// * `subtree` - if true, the entries of all names below `name` are removed as well
+    ///
+    /// # Returns
+    ///
+    /// The number of entries removed
+    pub fn flush_name(&self, name: &Name, subtree: bool) -> ResolveResult<usize> {
+        Ok(self.lru.lock()?.remove_name(name, subtree))
+    }
+
+    /// Returns the counters of the usage of the cache
+    pub fn stats(&self) -> ResolveResult<CacheStats> {
+        Ok(self.lru.lock()?.stats())
+    }
+
+    /// Returns a copy of all entries in the cache, from the least to the most recently used
+    ///
+    /// This is intended for debugging, the entries include expired entries which have not yet been removed.
+    pub fn entries(&self) -> ResolveResult<Vec<CacheEntry>> {
+        Ok(self.lru.lock()?.entries())
+    }
+}
+
+impl fmt::Debug for ResolverCache {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ResolverCache").finish()
+    }
+}
+
+/// Counters of the usage of the cache
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Number of lookups answered with records from the cache
+    pub hits: u64,
+    /// Number of lookups for which there was no current entry in the cache
+    pub misses: u64,
+    /// Number of lookups for which the cache held a current negative (`NXDOMAIN` or no data) entry
+    pub negative_hits: u64,
+    /// Number of entries removed to make room for new entries
+    pub evictions: u64,
+    /// Number of entries currently in the cache
+    pub entries: usize,
+}
+
+/// An entry of the cache, see `ResolverCache::entries`
+#[derive(Clone, Debug)]
+pub struct CacheEntry {
+    query: Query,
+    lookup: Option<Lookup>,
+    valid_until: Instant,
+}
+
+impl CacheEntry {
+    pub(crate) fn new(query: Query, lookup: Option<Lookup>, valid_until: Instant) -> Self {
+        CacheEntry {
+            query,
+            lookup,
+            valid_until,
+        }
+    }
+
+    /// The `Query` this entry is cached for
+    pub fn query(&self) -> &Query {
+        &self.query
+    }
+
+    /// The cached records, `None` for negative entries
+    pub fn lookup(&self) -> Option<&Lookup> {
+        self.lookup.as_ref()
+    }
+
+    /// Returns true if this caches a negative response, i.e. the records do not exist
+    pub fn is_negative(&self) -> bool {
+        self.lookup.is_none()
+    }
+
+    /// Returns the `Instant` at which this entry expires
+    pub fn valid_until(&self) -> Instant {
+        self.valid_until
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use std::str::FromStr;
+    use std::time::Duration;
+
+    use proto::rr::{RData, RecordType};
+
+    use super::*;
+    use dns_lru::TtlConfig;
+
+    fn query(name: &str, record_type: RecordType) -> Query {
+        Query::query(Name::from_str(name).unwrap(), record_type)
+    }
+
+    fn cache() -> ResolverCache {
+        let now = Instant::now();
+        let mut lru = DnsLru::new(4, TtlConfig::default());
+        for name in &["example.com.", "www.example.com.", "www.example.org."] {
+            lru.insert(
+                query(name, RecordType::A),
+                None,
+                vec![(RData::A(Ipv4Addr::new(127, 0, 0, 1)), 300)],
+                now,
+            );
+        }
+        lru.negative(query("www.example.com.", RecordType::AAAA), None, 300, now);
+
+        ResolverCache::new(Arc::new(Mutex::new(lru)))
+    }
+
+    #[test]
+    fn test_entries() {
+        let cache = cache();
+        let entries = cache.entries().unwrap();
+
+        assert_eq!(entries.len(), 4);
+        assert_eq!(entries[0].query(), &query("example.com.", RecordType::A));
+        assert!(!entries[0].is_negative());
+        assert_eq!(
+            entries[0].lookup().unwrap().iter().collect::<Vec<_>>(),
+            vec![&RData::A(Ipv4Addr::new(127, 0, 0, 1))]
+        );
+        assert!(entries[0].valid_until() > Instant::now() + Duration::from_secs(200));
+        assert_eq!(
+            entries[3].query(),
+            &query("www.example.com.", RecordType::AAAA)
+        );
+        assert!(entries[3].is_negative());
+    }
+
+    #[test]
+    fn test_flush() {
+        let cache = cache();
+        cache.flush().unwrap();
+
+        assert!(cache.entries().unwrap().is_empty());
+        assert_eq!(cache.stats().unwrap().entries, 0);
+    }
+
+    #[test]
+    fn test_flush_name() {
+        let cache = cache();
+
+        // all record types of the name
+        let www = Name::from_str("WWW.example.com").unwrap();
+        assert_eq!(cache.flush_name(&www, false).unwrap(), 2);
+        assert_eq!(cache.entries().unwrap().len(), 2);
+
+        // the name and all names below it
+        let cache = self::cache();
+        let example = Name::from_str("example.com.").unwrap();
+        assert_eq!(cache.flush_name(&example, true).unwrap(), 3);
+
+        let entries = cache.entries().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].query(), &query("www.example.org.", RecordType::A));
+    }
+
+    #[test]
+    fn test_stats() {
+        let cache = cache();
+        let now = Instant::now();
+
+        {
+            let mut lru = cache.lru.lock().unwrap();
+            assert!(lru.get(&query("www.example.com.", RecordType::A), None, now).is_some());
+            assert!(lru.get(&query("www.example.com.", RecordType::AAAA), None, now).is_none());
+            assert!(lru.get(&query("www.example.net.", RecordType::A), None, now).is_none());
+
+            // evicts the least recently used entry
+            lru.insert(
+                query("www.example.net.", RecordType::A),
+                None,
+                vec![(RData::A(Ipv4Addr::new(127, 0, 0, 1)), 300)],
+                now,
+            );
+            // replaces an entry
+            lru.insert(
+                query("www.example.net.", RecordType::A),
+                None,
+                vec![(RData::A(Ipv4Addr::new(127, 0, 0, 2)), 300)],
+                now,
+            );
+        }
+
+        assert_eq!(
+            cache.stats().unwrap(),
+            CacheStats {
+                hits: 1,
+                misses: 1,
+                negative_hits: 1,
+                evictions: 1,
+                entries: 4,
+            }
+        );
+    }
+}
@@ -8,9 +8,13 @@
 //! Configuration for a resolver
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::ops::{Deref, DerefMut};
+use std::path::PathBuf;
 use std::time::Duration;
 
-use proto::rr::Name;
+use proto::rr::rdata::opt::ClientSubnet;
+use proto::rr::{Name, Record};
+
+use dns64::Dns64Config;
 
 /// Configuration for the upstream nameservers to use for resolution
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -22,6 +26,21 @@ pub struct ResolverConfig {
     search: Vec<Name>,
     // nameservers to use for resolution.
     name_servers: NameServerConfigGroup,
+    // domains resolved with their own nameservers
+    #[cfg_attr(feature = "serde-config", serde(default))]
+    forward_zones: Vec<ForwardZone>,
+    // networks by which the addresses of ip lookups are ordered
+    #[cfg_attr(feature = "serde-config", serde(default))]
+    sortlist: Vec<SortlistEntry>,
+    // hosts files read in addition to the one of the system
+    #[cfg_attr(feature = "serde-config", serde(default))]
+    hosts_files: Vec<PathBuf>,
+    // records answered like those of the hosts files
+    #[cfg_attr(feature = "serde-config", serde(skip))]
+    static_records: Vec<Record>,
+    // synthesis of AAAA records for NAT64
+    #[cfg_attr(feature = "serde-config", serde(default))]
+    dns64: Option<Dns64Config>,
 }
 
 impl ResolverConfig {
@@ -32,6 +51,11 @@ impl ResolverConfig {
             domain: None,
             search: vec![],
             name_servers: NameServerConfigGroup::new(),
+            forward_zones: vec![],
+            sortlist: vec![],
+            hosts_files: vec![],
+            static_records: vec![],
+            dns64: None,
         }
     }
 
@@ -46,6 +70,11 @@ impl ResolverConfig {
             domain: None,
             search: vec![],
             name_servers: NameServerConfigGroup::google(),
+            forward_zones: vec![],
+            sortlist: vec![],
+            hosts_files: vec![],
+            static_records: vec![],
+            dns64: None,
         }
     }
 
@@ -60,6 +89,11 @@ impl ResolverConfig {
             domain: None,
             search: vec![],
             name_servers: NameServerConfigGroup::cloudflare(),
+            forward_zones: vec![],
+            sortlist: vec![],
+            hosts_files: vec![],
+            static_records: vec![],
+            dns64: None,
         }
     }
 
@@ -75,6 +109,11 @@ impl ResolverConfig {
             domain: None,
             search: vec![],
             name_servers: NameServerConfigGroup::cloudflare_tls(),
+            forward_zones: vec![],
+            sortlist: vec![],
+            hosts_files: vec![],
+            static_records: vec![],
+            dns64: None,
         }
     }
 
@@ -90,6 +129,11 @@ impl ResolverConfig {
             domain: None,
             search: vec![],
             name_servers: NameServerConfigGroup::cloudflare_https(),
+            forward_zones: vec![],
+            sortlist: vec![],
+            hosts_files: vec![],
+            static_records: vec![],
+            dns64: None,
         }
     }
 
@@ -104,6 +148,11 @@ impl ResolverConfig {
             domain: None,
             search: vec![],
             name_servers: NameServerConfigGroup::quad9(),
+            forward_zones: vec![],
+            sortlist: vec![],
+            hosts_files: vec![],
+            static_records: vec![],
+            dns64: None,
         }
     }
 
@@ -119,6 +168,11 @@ impl ResolverConfig {
             domain: None,
             search: vec![],
             name_servers: NameServerConfigGroup::quad9_tls(),
+            forward_zones: vec![],
+            sortlist: vec![],
+            hosts_files: vec![],
+            static_records: vec![],
+            dns64: None,
         }
     }
 
@@ -138,6 +192,11 @@ impl ResolverConfig {
             domain,
             search,
             name_servers: name_servers.into(),
+            forward_zones: vec![],
+            sortlist: vec![],
+            hosts_files: vec![],
+            static_records: vec![],
+            dns64: None,
         }
     }
 
@@ -176,6 +235,67 @@ impl ResolverConfig {
     pub fn name_servers(&self) -> &[NameServerConfig] {
         &self.name_servers
     }
+
+    /// Add a domain which is resolved with its own name servers
+    ///
+    /// Names are resolved with the name servers of the forward zone with the longest matching domain, names not
+    ///  within any of the forward zones are resolved with the name servers of this configuration.
+    pub fn add_forward_zone(&mut self, forward_zone: ForwardZone) {
+        self.forward_zones.push(forward_zone);
+    }
+
+    /// Returns the domains which are resolved with their own name servers
+    pub fn forward_zones(&self) -> &[ForwardZone] {
+        &self.forward_zones
+    }
+
+    /// Add a network to the sortlist, the `sortlist` option of resolv.conf
+    ///
+    /// The addresses of ip lookups are ordered by the first network of the sortlist containing them, in the order the
+    ///  networks were added. Addresses not within any of the networks follow, in the order they were received.
+    pub fn add_sortlist(&mut self, entry: SortlistEntry) {
+        self.sortlist.push(entry);
+    }
+
+    /// Returns the networks by which the addresses of ip lookups are ordered
+    pub fn sortlist(&self) -> &[SortlistEntry] {
+        &self.sortlist
+    }
+
+    /// Add a hosts file, which is read after the hosts file of the system
+    ///
+    /// The files are read regardless of `ResolverOpts::use_hosts_file`, which only controls the file of the system.
+    pub fn add_hosts_file<P: Into<PathBuf>>(&mut self, path: P) {
+        self.hosts_files.push(path.into());
+    }
+
+    /// Returns the hosts files read in addition to the one of the system
+    pub fn hosts_files(&self) -> &[PathBuf] {
+        &self.hosts_files
+    }
+
+    /// Add a record of any type, which is answered without querying the name servers, like the hosts file
+    ///
+    /// A CNAME record makes its name an alias of the target, so that lookups of the name are answered with the
+    ///  records of the target. See `Hosts::insert_record`.
+    pub fn add_static_record(&mut self, record: Record) {
+        self.static_records.push(record);
+    }
+
+    /// Returns the records answered without querying the name servers
+    pub fn static_records(&self) -> &[Record] {
+        &self.static_records
+    }
+
+    /// Enable DNS64, AAAA records are synthesized from A records for names without AAAA records, see `dns64`
+    pub fn set_dns64(&mut self, dns64: Dns64Config) {
+        self.dns64 = Some(dns64);
+    }
+
+    /// Returns the configuration of DNS64, `None` if disabled
+    pub fn dns64(&self) -> Option<&Dns64Config> {
+        self.dns64.as_ref()
+    }
 }
 
 impl Default for ResolverConfig {
@@ -187,6 +307,110 @@ impl Default for ResolverConfig {
     }
 }
 
+/// A domain which is resolved with its own name servers, i.e. conditional forwarding or split DNS
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-config", derive(Serialize, Deserialize))]
+pub struct ForwardZone {
+    // the domain, all names at or below it are resolved with the name servers
+    domain: Name,
+    // nameservers to use for resolution of the domain
+    name_servers: NameServerConfigGroup,
+    // additional search domains
+    #[cfg_attr(feature = "serde-config", serde(default))]
+    search: Vec<Name>,
+    // validate responses with DNSSec
+    #[cfg_attr(feature = "serde-config", serde(default))]
+    validate: bool,
+}
+
+impl ForwardZone {
+    /// Creates a forward zone, resolving all names at or below `domain` with `name_servers`
+    ///
+    /// The responses are not validated with DNSSec, regardless of `ResolverOpts::validate`, see `set_validate`.
+    pub fn new<G: Into<NameServerConfigGroup>>(domain: Name, name_servers: G) -> Self {
+        ForwardZone {
+            domain,
+            name_servers: name_servers.into(),
+            search: vec![],
+            validate: false,
+        }
+    }
+
+    /// Returns the domain of this zone
+    pub fn domain(&self) -> &Name {
+        &self.domain
+    }
+
+    /// Returns a reference to the name servers of this zone
+    pub fn name_servers(&self) -> &[NameServerConfig] {
+        &self.name_servers
+    }
+
+    /// Returns the search domains of this zone
+    ///
+    /// These are queried after the search domains of the `ResolverConfig`, names found through them are resolved
+    ///  with the name servers of the forward zone matching them, generally this zone.
+    pub fn search(&self) -> &[Name] {
+        &self.search
+    }
+
+    /// Add a search domain
+    pub fn add_search(&mut self, search: Name) {
+        self.search.push(search)
+    }
+
+    /// Returns true if responses from the name servers of this zone are validated with DNSSec
+    pub fn validate(&self) -> bool {
+        self.validate
+    }
+
+    /// Set whether responses from the name servers of this zone are validated with DNSSec, requires the `dnssec`
+    ///  feature
+    pub fn set_validate(&mut self, validate: bool) {
+        self.validate = validate;
+    }
+}
+
+/// A network of the sortlist, see `ResolverConfig::add_sortlist`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-config", derive(Serialize, Deserialize))]
+pub struct SortlistEntry {
+    address: IpAddr,
+    netmask: IpAddr,
+}
+
+impl SortlistEntry {
+    /// Creates a network from its address and netmask, which should both be of the same family
+    pub fn new(address: IpAddr, netmask: IpAddr) -> Self {
+        SortlistEntry { address, netmask }
+    }
+
+    /// Returns the address of the network
+    pub fn address(&self) -> IpAddr {
+        self.address
+    }
+
+    /// Returns the netmask of the network
+    pub fn netmask(&self) -> IpAddr {
+        self.netmask
+    }
+
+    /// Returns true if the address is within this network
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.address, self.netmask, ip) {
+            (IpAddr::V4(address), IpAddr::V4(netmask), IpAddr::V4(ip)) => {
+                let netmask = u32::from(netmask);
+                u32::from(address) & netmask == u32::from(ip) & netmask
+            }
+            (IpAddr::V6(address), IpAddr::V6(netmask), IpAddr::V6(ip)) => {
+                let netmask = u128::from(netmask);
+                u128::from(address) & netmask == u128::from(ip) & netmask
+            }
+            _ => false,
+        }
+    }
+}
+
 /// The protocol on which a NameServer should be communicated with
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 #[cfg_attr(feature = "serde-config", derive(Serialize, Deserialize))]
@@ -495,6 +719,92 @@ impl Default for LookupIpStrategy {
     }
 }
 
+/// QNAME minimization for iterative resolution, [RFC 9156](https://tools.ietf.org/html/rfc9156)
+///
+/// Only the labels needed to find the next zone cut are sent to each name server, rather than the
+///  full query name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-config", derive(Serialize, Deserialize))]
+pub enum QnameMinimization {
+    /// Always send the full query name
+    Disabled,
+    /// Minimize, falling back to the full query name if a server fails or responds NXDOMAIN to a
+    ///  minimized query, as some servers do for empty non-terminals (default)
+    Relaxed,
+    /// Minimize, an NXDOMAIN for a minimized query is final and failures are errors
+    Strict,
+}
+
+impl Default for QnameMinimization {
+    /// Returns Relaxed as the default.
+    fn default() -> Self {
+        QnameMinimization::Relaxed
+    }
+}
+
+/// The EDNS client subnet sent to the name servers, [RFC 7871](https://tools.ietf.org/html/rfc7871)
+///
+/// Answers tailored to a subnet are only served from the cache to requests from within their scope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-config", derive(Serialize, Deserialize))]
+pub enum ClientSubnetStrategy {
+    /// Send the client subnet of the lookup, if any, see `AsyncResolver::lookup_with_client_subnet` (default)
+    Keep,
+    /// Remove the client subnet from all requests
+    Strip,
+    /// Send the subnet of the address to requests which don't carry one
+    ///
+    /// RFC 7871 section 11.1 recommends a `source_prefix` of at most 24 for IPv4 and 56 for IPv6, for privacy.
+    Attach {
+        /// An address of the clients
+        address: IpAddr,
+        /// The number of leading bits of the address to send
+        source_prefix: u8,
+    },
+}
+
+impl ClientSubnetStrategy {
+    /// Returns the client subnet sent for a request created with `client_subnet`
+    pub fn apply(self, client_subnet: Option<ClientSubnet>) -> Option<ClientSubnet> {
+        match self {
+            ClientSubnetStrategy::Keep => client_subnet,
+            ClientSubnetStrategy::Strip => None,
+            ClientSubnetStrategy::Attach {
+                address,
+                source_prefix,
+            } => client_subnet.or_else(|| Some(ClientSubnet::new(address, source_prefix, 0))),
+        }
+    }
+}
+
+impl Default for ClientSubnetStrategy {
+    /// Returns Keep as the default.
+    fn default() -> Self {
+        ClientSubnetStrategy::Keep
+    }
+}
+
+/// Reloading of the system configuration while the resolver is running, see `AsyncResolver::from_system_conf_with_reload`
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde-config", derive(Serialize, Deserialize))]
+pub struct ReloadOpts {
+    /// Minimum time between checks of the files for changes, the default is 5 seconds
+    ///
+    /// The files are only checked when a lookup is requested, an idle resolver doesn't access them.
+    pub interval: Duration,
+    /// Remove all entries from the cache when the configuration is reloaded, the default is true
+    pub flush_cache: bool,
+}
+
+impl Default for ReloadOpts {
+    fn default() -> Self {
+        ReloadOpts {
+            interval: Duration::from_secs(5),
+            flush_cache: true,
+        }
+    }
+}
+
 /// Configuration for the Resolver
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 #[cfg_attr(feature = "serde-config", derive(Serialize, Deserialize))]
@@ -509,17 +819,41 @@ pub struct ResolverOpts {
     pub timeout: Duration,
     /// Number of attempts before giving up. Defaults to 2
     pub attempts: usize,
-    /// Rotate through the resource records in the response (if there is more than one for a given name)
-    pub(crate) rotate: bool,
+    /// Rotate through the name servers, sending each request first to the one after the previous, the default is false
+    ///
+    /// By default the name servers are selected by their round trip times.
+    pub rotate: bool,
     /// Validate the names in the response, not implemented don't really see the point unless you need to support
     ///  badly configured DNS
     pub(crate) check_names: bool,
-    /// Enable edns, for larger records
-    pub(crate) edns0: bool,
+    /// Send requests with EDNS, for larger records over UDP, the default is true
+    ///
+    /// When disabled, EDNS is still sent with requests which need it for DNSSec or a client subnet. The options of
+    ///  resolv.conf only enable it with the `edns0` option, like the libc resolver.
+    pub edns0: bool,
+    /// Send all requests over TCP (or the other stream based protocols), the default is false
+    pub use_vc: bool,
+    /// With `LookupIpStrategy::Ipv4AndIpv6`, send the A and AAAA requests one after the other, the default is false
+    ///
+    /// This works around name servers and firewalls which drop one of two simultaneous requests.
+    pub single_request: bool,
+    /// Set the authentic data (AD) bit in requests, the default is false
+    ///
+    /// Only enable this if the name servers validate DNSSec and the path to them is trusted. The AD bit of responses
+    ///  is passed through as received, except for the options of resolv.conf: like the libc resolver it is then
+    ///  cleared in all responses unless this is enabled with the `trust-ad` option.
+    pub trust_ad: bool,
+    /// Clear the AD bit of responses unless `trust_ad` is enabled, set for the options of resolv.conf
+    pub(crate) clear_untrusted_ad: bool,
     /// Use DNSSec to validate the request
     pub validate: bool,
     /// The ip_strategy for the Resolver to use when lookup Ipv4 or Ipv6 addresses
     pub ip_strategy: LookupIpStrategy,
+    /// Order the addresses of IP lookups by destination address selection, the default is false
+    ///
+    /// The addresses are ordered the way connections to them should be attempted, see `addr_selection`. The
+    ///  `ResolverConfig::add_sortlist` order still takes precedence.
+    pub sort_destinations: bool,
     /// Cache size is in number of records (some records can be large)
     pub cache_size: usize,
     /// Check /ect/hosts file before dns requery (only works for unix like OS)
@@ -548,6 +882,17 @@ pub struct ResolverOpts {
     ///
     /// [`MAX_TTL`]: ../dns_lru/const.MAX_TTL.html
     pub negative_max_ttl: Option<Duration>,
+    /// Optional window during which expired records are kept and served stale, see [RFC 8767](https://tools.ietf.org/html/rfc8767)
+    ///
+    /// If this is set, records whose TTL has expired are only returned when all attempts to refresh them from the
+    ///  name servers fail or time out, and then with a TTL of [`STALE_TTL`]. Defaults to `None`, i.e. disabled.
+    ///
+    /// [`STALE_TTL`]: ../dns_lru/const.STALE_TTL.html
+    pub serve_stale: Option<Duration>,
+    /// Refresh cached records in the background when they are used shortly before they expire, the default is false
+    pub prefetch: bool,
+    /// Percentage of the TTL remaining at which `prefetch` refreshes a record, the default is 10
+    pub prefetch_threshold: u8,
     /// Default is to distrust negative responses from upstream nameservers
     ///
     /// Currently only SERVFAIL responses are continued on, this may be expanded to include NXDOMAIN or NoError/Empty responses
@@ -556,6 +901,10 @@ pub struct ResolverOpts {
     ///
     /// 0 or 1 will configure this to execute all requests serially
     pub num_concurrent_reqs: usize,
+    /// QNAME minimization when resolving iteratively with the `Recursor`, the default is relaxed
+    pub qname_minimization: QnameMinimization,
+    /// The EDNS client subnet sent to the name servers, the default is to keep the one of the lookup, if any
+    pub client_subnet: ClientSubnetStrategy,
 }
 
 impl Default for ResolverOpts {
@@ -569,17 +918,27 @@ impl Default for ResolverOpts {
             attempts: 2,
             rotate: false,
             check_names: true,
-            edns0: false,
+            edns0: true,
+            use_vc: false,
+            single_request: false,
+            trust_ad: false,
+            clear_untrusted_ad: false,
             validate: false,
             ip_strategy: LookupIpStrategy::default(),
+            sort_destinations: false,
             cache_size: 32,
             use_hosts_file: true,
             positive_min_ttl: None,
             negative_min_ttl: None,
             positive_max_ttl: None,
             negative_max_ttl: None,
+            serve_stale: None,
+            prefetch: false,
+            prefetch_threshold: 10,
             distrust_nx_responses: true,
             num_concurrent_reqs: 2,
+            qname_minimization: QnameMinimization::default(),
+            client_subnet: ClientSubnetStrategy::default(),
         }
     }
 }
@@ -0,0 +1,232 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! DANE, TLSA lookups for TLS services, see [RFC 6698](https://tools.ietf.org/html/rfc6698) and
+//!  [RFC 7671](https://tools.ietf.org/html/rfc7671)
+//!
+//! The TLSA records only authenticate a service if they are validated with DNSSEC, DANE lookups therefore require
+//!  `ResolverOpts::validate`, or `ForwardZone::validate` for the names in forward zones. The certificate chain
+//!  presented by the service is verified with the records by `trust_dns_proto::rr::dane::verify_chain`, which the
+//!  `trust-dns-rustls` and `trust-dns-openssl` crates provide verifiers for, with their `dane` feature.
+#![cfg(feature = "dnssec")]
+
+use std::sync::Arc;
+
+use futures::future::{self, Loop};
+use futures::{Future, Poll};
+
+use proto::rr::{Name, RData, RecordType};
+
+use error::*;
+use lookup::{Lookup, TlsaLookup};
+
+/// The maximum number of CNAMEs followed from the host to its target
+const MAX_CNAME_CHAIN: usize = 8;
+
+/// The transport protocol of a TLS service, e.g. TCP for HTTPS and UDP for DTLS
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Transport {
+    /// `_tcp`
+    Tcp,
+    /// `_udp`
+    Udp,
+    /// `_sctp`
+    Sctp,
+}
+
+impl Transport {
+    fn label(self) -> &'static str {
+        match self {
+            Transport::Tcp => "_tcp",
+            Transport::Udp => "_udp",
+            Transport::Sctp => "_sctp",
+        }
+    }
+}
+
+/// Returns the name of the TLSA records of a service, e.g. `_443._tcp.www.example.com.`
+pub fn tlsa_name(host: &Name, port: u16, transport: Transport) -> ResolveResult<Name> {
+    let port = format!("_{}", port);
+    let prefix = Name::from_labels(vec![port.as_str(), transport.label()])?;
+    Ok(prefix.append_name(host))
+}
+
+/// A Future which resolves to the TLSA records of a service
+pub struct DaneLookupFuture(Box<Future<Item = TlsaLookup, Error = ResolveError> + Send>);
+
+impl DaneLookupFuture {
+    /// Follows the CNAMEs of the host, then looks up the TLSA records of the service at the target and the host
+    pub(crate) fn new<L, F>(host: Name, port: u16, transport: Transport, lookup: L) -> Self
+    where
+        L: Fn(Name, RecordType) -> F + Send + Sync + 'static,
+        F: Future<Item = Lookup, Error = ResolveError> + Send + 'static,
+    {
+        let lookup = Arc::new(lookup);
+
+        let cname_lookup = lookup.clone();
+        let target = future::loop_fn((host.clone(), 0), move |(name, depth)| {
+            let host = host.clone();
+            cname_lookup(name.clone(), RecordType::CNAME).then(move |result| {
+                let cname = match result {
+                    Ok(lookup) => lookup.iter().find_map(|rdata| match *rdata {
+                        RData::CNAME(ref cname) => Some(cname.clone()),
+                        _ => None,
+                    }),
+                    Err(ref e) if is_no_records(e) => None,
+                    Err(e) => return Err(e),
+                };
+
+                match cname {
+                    Some(_) if depth >= MAX_CNAME_CHAIN => {
+                        Err(ResolveErrorKind::Msg(format!("too many CNAMEs for {}", host)).into())
+                    }
+                    Some(cname) => Ok(Loop::Continue((cname, depth + 1))),
+                    None => Ok(Loop::Break((host, name))),
+                }
+            })
+        });
+
+        let tlsa = target.and_then(move |(host, target)| {
+            let host_tlsa = tlsa_name(&host, port, transport);
+            let target_tlsa = tlsa_name(&target, port, transport);
+            let (host_tlsa, target_tlsa) = match (host_tlsa, target_tlsa) {
+                (Ok(host_tlsa), Ok(target_tlsa)) => (host_tlsa, target_tlsa),
+                (Err(e), _) | (_, Err(e)) => {
+                    return Box::new(future::err(e))
+                        as Box<Future<Item = Lookup, Error = ResolveError> + Send>
+                }
+            };
+
+            if host == target {
+                return Box::new(lookup(host_tlsa, RecordType::TLSA));
+            }
+
+            debug!("TLSA lookup of {} at its target {}", host, target);
+            Box::new(lookup(target_tlsa, RecordType::TLSA).or_else(move |e| {
+                if is_no_records(&e) {
+                    future::Either::A(lookup(host_tlsa, RecordType::TLSA))
+                } else {
+                    future::Either::B(future::err(e))
+                }
+            }))
+        });
+
+        DaneLookupFuture(Box::new(tlsa.map(TlsaLookup::from)))
+    }
+
+    pub(crate) fn error(error: ResolveError) -> Self {
+        DaneLookupFuture(Box::new(future::err(error)))
+    }
+}
+
+impl Future for DaneLookupFuture {
+    type Item = TlsaLookup;
+    type Error = ResolveError;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        self.0.poll()
+    }
+}
+
+fn is_no_records(error: &ResolveError) -> bool {
+    match *error.kind() {
+        ResolveErrorKind::NoRecordsFound { .. } => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use proto::rr::rdata::tlsa::{CertUsage, Matching, Selector, TLSA};
+
+    use super::*;
+    use lookup::tests::mock_lookup;
+
+    fn tlsa() -> RData {
+        RData::TLSA(TLSA::new(
+            CertUsage::DomainIssued,
+            Selector::Spki,
+            Matching::Sha256,
+            vec![0; 32],
+        ))
+    }
+
+    fn dane_lookup(records: Vec<(&'static str, RData)>) -> ResolveResult<TlsaLookup> {
+        DaneLookupFuture::new(
+            Name::from_str("www.example.com.").unwrap(),
+            443,
+            Transport::Tcp,
+            mock_lookup(records),
+        )
+        .wait()
+    }
+
+    #[test]
+    fn test_tlsa_name() {
+        assert_eq!(
+            tlsa_name(
+                &Name::from_str("www.example.com.").unwrap(),
+                443,
+                Transport::Tcp
+            )
+            .unwrap(),
+            Name::from_str("_443._tcp.www.example.com.").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_dane_lookup() {
+        let lookup = dane_lookup(vec![("_443._tcp.www.example.com.", tlsa())]).unwrap();
+        assert_eq!(lookup.iter().count(), 1);
+        assert_eq!(
+            *lookup.query().name(),
+            Name::from_str("_443._tcp.www.example.com.").unwrap()
+        );
+
+        assert!(dane_lookup(vec![]).is_err());
+    }
+
+    #[test]
+    fn test_dane_lookup_cname_target() {
+        let cname = |target| RData::CNAME(Name::from_str(target).unwrap());
+
+        // the records at the target are preferred
+        let lookup = dane_lookup(vec![
+            ("www.example.com.", cname("cdn.example.net.")),
+            ("_443._tcp.cdn.example.net.", tlsa()),
+            ("_443._tcp.www.example.com.", tlsa()),
+        ])
+        .unwrap();
+        assert_eq!(
+            *lookup.query().name(),
+            Name::from_str("_443._tcp.cdn.example.net.").unwrap()
+        );
+
+        // without records at the target, those at the host are used
+        let lookup = dane_lookup(vec![
+            ("www.example.com.", cname("cdn.example.net.")),
+            ("_443._tcp.www.example.com.", tlsa()),
+        ])
+        .unwrap();
+        assert_eq!(
+            *lookup.query().name(),
+            Name::from_str("_443._tcp.www.example.com.").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_dane_lookup_cname_loop() {
+        let cname = |target| RData::CNAME(Name::from_str(target).unwrap());
+        assert!(dane_lookup(vec![
+            ("www.example.com.", cname("loop.example.com.")),
+            ("loop.example.com.", cname("www.example.com.")),
+        ])
+        .is_err());
+    }
+}
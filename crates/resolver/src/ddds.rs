@@ -0,0 +1,593 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Dynamic Delegation Discovery System (DDDS) resolution with NAPTR records, see
+//!  [RFC 3402](https://tools.ietf.org/html/rfc3402) and [RFC 3403](https://tools.ietf.org/html/rfc3403), and ENUM,
+//!  the mapping of telephone numbers to URIs, see [RFC 6116](https://tools.ietf.org/html/rfc6116)
+
+use std::str::FromStr;
+use std::sync::Arc;
+
+use futures::future::{self, Loop};
+use futures::{Future, Poll};
+use regex::{self, Captures, Regex, RegexBuilder};
+
+use proto::rr::rdata::NAPTR;
+use proto::rr::{Name, RData, RecordType};
+
+use error::*;
+use lookup::{Lookup, SrvLookup};
+use lookup_ip::LookupIp;
+
+/// The maximum number of non-terminal NAPTR rules followed from the first key
+const MAX_NAPTR_CHAIN: usize = 8;
+
+/// Returns the ENUM domain of a telephone number, e.g. `4.3.2.1.e164.arpa.` for `+1234`
+///
+/// The number must be in the E.164 format, with a leading `+`, the visual separators `-`, `.`, ` `, `(` and `)` are
+///  ignored.
+pub fn enum_name(number: &str) -> ResolveResult<Name> {
+    let digits = e164_digits(number)?;
+    let labels = digits
+        .chars()
+        .rev()
+        .map(|digit| digit.to_string())
+        .chain(vec!["e164".to_string(), "arpa".to_string()])
+        .collect::<Vec<_>>();
+    Ok(Name::from_labels(labels)?)
+}
+
+/// Returns the digits of the E.164 number without the `+` and visual separators
+pub(crate) fn e164_digits(number: &str) -> ResolveResult<String> {
+    let number = number.trim();
+    if !number.starts_with('+') {
+        return Err(ResolveErrorKind::Msg(format!("not an E.164 number: {}", number)).into());
+    }
+
+    let mut digits = String::with_capacity(number.len());
+    for ch in number[1..].chars() {
+        match ch {
+            '0'...'9' => digits.push(ch),
+            '-' | '.' | ' ' | '(' | ')' => (),
+            _ => {
+                return Err(
+                    ResolveErrorKind::Msg(format!("not an E.164 number: {}", number)).into(),
+                )
+            }
+        }
+    }
+
+    // E.164 numbers have at most 15 digits
+    if digits.is_empty() || digits.len() > 15 {
+        return Err(ResolveErrorKind::Msg(format!("not an E.164 number: {}", number)).into());
+    }
+    Ok(digits)
+}
+
+/// The result of the application of a NAPTR rule
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Rewrite {
+    /// The domain name of the next lookup, of NAPTR records, or of SRV or address records for the `S` and `A` flags
+    Name(Name),
+    /// The URI from a rule with the `U` flag
+    Uri(String),
+}
+
+/// Applies the rule of the NAPTR record to the Application Unique String (AUS)
+///
+/// The substitution expression in the regexp field is applied to the AUS, the replacement field is used if the regexp
+///  is empty. Returns `None` if the regular expression does not match the AUS.
+pub fn apply_rule(naptr: &NAPTR, aus: &str) -> ResolveResult<Option<Rewrite>> {
+    let is_uri = naptr.has_flag(b'U');
+    if naptr.regexp().is_empty() {
+        if is_uri {
+            return Err(ResolveErrorKind::Message("NAPTR with the U flag has no regexp").into());
+        }
+        return Ok(Some(Rewrite::Name(naptr.replacement().clone())));
+    }
+    if !naptr.replacement().is_root() {
+        return Err(ResolveErrorKind::Message("NAPTR has both a regexp and a replacement").into());
+    }
+
+    let regexp = ::std::str::from_utf8(naptr.regexp()).map_err(|_| {
+        ResolveError::from(ResolveErrorKind::Message("regexp of NAPTR is not UTF-8"))
+    })?;
+    let result = match SubstitutionExpression::from_str(regexp)?.apply(aus) {
+        Some(result) => result,
+        None => return Ok(None),
+    };
+
+    if is_uri {
+        return Ok(Some(Rewrite::Uri(result)));
+    }
+
+    let mut name = Name::from_str(&result)?;
+    name.set_fqdn(true);
+    Ok(Some(Rewrite::Name(name)))
+}
+
+/// The substitution expression of the regexp field, RFC 3402 section 3.2
+///
+/// ```text
+///  subst-expr   = delim-char  ere  delim-char  repl  delim-char  *flags
+///  delim-char   = "/" / "!" / <Any octet not in 'POS-DIGIT' or 'flags'>
+///  repl         = *(string / backref)
+///  backref      = "\" POS-DIGIT
+///  flags        = "i"
+/// ```
+struct SubstitutionExpression {
+    ere: Regex,
+    repl: String,
+}
+
+impl SubstitutionExpression {
+    fn from_str(expression: &str) -> ResolveResult<Self> {
+        let invalid = || {
+            ResolveError::from(ResolveErrorKind::Msg(format!(
+                "invalid NAPTR substitution expression: {}",
+                expression
+            )))
+        };
+
+        let mut chars = expression.chars();
+        let delim = chars.next().ok_or_else(invalid)?;
+        if delim.is_ascii_digit() || delim == 'i' || delim == '\\' {
+            return Err(invalid());
+        }
+
+        // the delimiter is escaped with a backslash in the ere and repl, it is matched literally in the ere
+        let mut parts = vec![String::new()];
+        while let Some(ch) = chars.next() {
+            match ch {
+                '\\' => match chars.next() {
+                    Some(ch) if ch == delim && parts.len() == 1 => {
+                        parts[0].push_str(&regex::escape(&ch.to_string()))
+                    }
+                    Some(ch) if ch == delim => parts.last_mut().ok_or_else(invalid)?.push(ch),
+                    Some(ch) => {
+                        let part = parts.last_mut().ok_or_else(invalid)?;
+                        part.push('\\');
+                        part.push(ch);
+                    }
+                    None => return Err(invalid()),
+                },
+                ch if ch == delim => parts.push(String::new()),
+                ch => parts.last_mut().ok_or_else(invalid)?.push(ch),
+            }
+        }
+
+        if parts.len() != 3 {
+            return Err(invalid());
+        }
+        let case_insensitive = match parts[2].as_str() {
+            "" => false,
+            "i" => true,
+            _ => return Err(invalid()),
+        };
+
+        let ere = RegexBuilder::new(&parts[0])
+            .case_insensitive(case_insensitive)
+            .build()
+            .map_err(|e| ResolveErrorKind::Msg(format!("invalid NAPTR regexp: {}", e)))?;
+        Ok(SubstitutionExpression {
+            ere,
+            repl: parts.swap_remove(1),
+        })
+    }
+
+    /// Replaces the first match of the ere in the string with the repl, None if there is no match
+    fn apply(&self, string: &str) -> Option<String> {
+        let captures = self.ere.captures(string)?;
+        let matched = captures.get(0)?;
+
+        let mut result = String::with_capacity(string.len() + self.repl.len());
+        result.push_str(&string[..matched.start()]);
+        self.expand(&captures, &mut result);
+        result.push_str(&string[matched.end()..]);
+        Some(result)
+    }
+
+    /// Expands the back references, `\1` to `\9`, of the repl
+    fn expand(&self, captures: &Captures, result: &mut String) {
+        let mut chars = self.repl.chars();
+        while let Some(ch) = chars.next() {
+            match (ch, chars.clone().next()) {
+                ('\\', Some(digit @ '1'...'9')) => {
+                    chars.next();
+                    let index = digit as usize - '0' as usize;
+                    if let Some(group) = captures.get(index) {
+                        result.push_str(group.as_str());
+                    }
+                }
+                ('\\', Some(escaped)) => {
+                    chars.next();
+                    result.push(escaped);
+                }
+                (ch, _) => result.push(ch),
+            }
+        }
+    }
+}
+
+/// Returns true if the services of the record are empty or contain all the `+` separated parts of the service, e.g.
+///  `E2U+sip` for `E2U+sip+pres`, case insensitively
+fn matches_service(naptr: &NAPTR, service: &str) -> bool {
+    if naptr.services().is_empty() {
+        return true;
+    }
+
+    let services = String::from_utf8_lossy(naptr.services()).to_ascii_lowercase();
+    let services = services.split('+').collect::<Vec<_>>();
+    service
+        .to_ascii_lowercase()
+        .split('+')
+        .all(|part| services.contains(&part))
+}
+
+/// Returns true if all the flags of the record are known, records with unknown flags are ignored, RFC 3403 section
+///  4.1
+fn has_known_flags(naptr: &NAPTR) -> bool {
+    naptr
+        .flags()
+        .iter()
+        .all(|f| b"SAUP".contains(&f.to_ascii_uppercase()))
+}
+
+/// The result of a DDDS resolution
+#[derive(Debug, Clone)]
+pub enum DddsLookup {
+    /// The URI from a terminal rule with the `U` flag
+    Uri(String),
+    /// The SRV records from a terminal rule with the `S` flag
+    Srv(SrvLookup),
+    /// The addresses from a terminal rule with the `A` flag
+    Ip(LookupIp),
+}
+
+/// The result of the terminal rule of a DDDS resolution
+enum Terminal {
+    Uri(String),
+    Srv(Name),
+    Host(Name),
+}
+
+/// A Future which resolves to the result of a DDDS resolution
+pub struct DddsLookupFuture(Box<Future<Item = DddsLookup, Error = ResolveError> + Send>);
+
+impl DddsLookupFuture {
+    /// Follows the NAPTR rules from the key, then looks up the result of the terminal rule
+    pub(crate) fn new<L, F, I, G>(
+        key: Name,
+        aus: String,
+        service: String,
+        lookup: L,
+        lookup_ip: I,
+    ) -> Self
+    where
+        L: Fn(Name, RecordType) -> F + Send + Sync + 'static,
+        F: Future<Item = Lookup, Error = ResolveError> + Send + 'static,
+        I: Fn(Name) -> G + Send + 'static,
+        G: Future<Item = LookupIp, Error = ResolveError> + Send + 'static,
+    {
+        let lookup = Arc::new(lookup);
+
+        let naptr_lookup = lookup.clone();
+        let terminal = future::loop_fn((key, 0), move |(key, depth)| {
+            let aus = aus.clone();
+            let service = service.clone();
+            naptr_lookup(key, RecordType::NAPTR).and_then(move |lookup| {
+                let mut naptrs = lookup
+                    .iter()
+                    .filter_map(|rdata| match *rdata {
+                        RData::NAPTR(ref naptr) => Some(naptr),
+                        _ => None,
+                    })
+                    .filter(|naptr| has_known_flags(naptr) && matches_service(naptr, &service))
+                    .collect::<Vec<_>>();
+                naptrs.sort_by_key(|naptr| (naptr.order(), naptr.preference()));
+
+                let rule = naptrs
+                    .into_iter()
+                    .find_map(|naptr| match apply_rule(naptr, &aus) {
+                        Ok(Some(rewrite)) => Some((naptr, rewrite)),
+                        Ok(None) => None,
+                        Err(e) => {
+                            debug!("ignoring NAPTR {}: {}", naptr, e);
+                            None
+                        }
+                    });
+
+                let (naptr, rewrite) = match rule {
+                    Some(rule) => rule,
+                    None => {
+                        return Err(ResolveErrorKind::NoRecordsFound {
+                            query: lookup.query().clone(),
+                            valid_until: Some(lookup.valid_until()),
+                        }
+                        .into())
+                    }
+                };
+
+                match rewrite {
+                    Rewrite::Uri(uri) => Ok(Loop::Break(Terminal::Uri(uri))),
+                    Rewrite::Name(name) if naptr.has_flag(b'S') => {
+                        Ok(Loop::Break(Terminal::Srv(name)))
+                    }
+                    Rewrite::Name(name) if naptr.has_flag(b'A') => {
+                        Ok(Loop::Break(Terminal::Host(name)))
+                    }
+                    Rewrite::Name(_) if naptr.has_flag(b'P') => Err(ResolveErrorKind::Message(
+                        "the protocol specific NAPTR flag P is not supported",
+                    )
+                    .into()),
+                    Rewrite::Name(_) if depth >= MAX_NAPTR_CHAIN => {
+                        Err(ResolveErrorKind::Message("too many non-terminal NAPTR rules").into())
+                    }
+                    Rewrite::Name(name) => Ok(Loop::Continue((name, depth + 1))),
+                }
+            })
+        });
+
+        let result = terminal.and_then(move |terminal| match terminal {
+            Terminal::Uri(uri) => Box::new(future::ok(DddsLookup::Uri(uri)))
+                as Box<Future<Item = DddsLookup, Error = ResolveError> + Send>,
+            Terminal::Srv(name) => Box::new(
+                lookup(name, RecordType::SRV)
+                    .map(|lookup| DddsLookup::Srv(SrvLookup::from(lookup))),
+            ),
+            Terminal::Host(name) => Box::new(lookup_ip(name).map(DddsLookup::Ip)),
+        });
+
+        DddsLookupFuture(Box::new(result))
+    }
+
+    pub(crate) fn error(error: ResolveError) -> Self {
+        DddsLookupFuture(Box::new(future::err(error)))
+    }
+}
+
+impl Future for DddsLookupFuture {
+    type Item = DddsLookup;
+    type Error = ResolveError;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        self.0.poll()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use proto::op::Query;
+    use proto::rr::rdata::SRV;
+
+    use super::*;
+    use lookup::tests::mock_lookup;
+
+    fn naptr(order: u16, flags: &str, services: &str, regexp: &str, replacement: &str) -> NAPTR {
+        NAPTR::new(
+            order,
+            10,
+            flags.as_bytes().to_vec().into_boxed_slice(),
+            services.as_bytes().to_vec().into_boxed_slice(),
+            regexp.as_bytes().to_vec().into_boxed_slice(),
+            Name::from_str(replacement).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_enum_name() {
+        assert_eq!(
+            enum_name("+1-555-123-4567").unwrap(),
+            Name::from_str("7.6.5.4.3.2.1.5.5.5.1.e164.arpa.").unwrap()
+        );
+        assert_eq!(
+            enum_name("+44 (20) 7946.0000").unwrap(),
+            Name::from_str("0.0.0.0.6.4.9.7.0.2.4.4.e164.arpa.").unwrap()
+        );
+        assert!(enum_name("555-1234").is_err());
+        assert!(enum_name("+1555x1234").is_err());
+        assert!(enum_name("+").is_err());
+        assert!(enum_name("+1234567890123456").is_err());
+    }
+
+    #[test]
+    fn test_apply_rule() {
+        // RFC 6116 section 6.2
+        let rule = naptr(100, "u", "E2U+sip", "!^.*$!sip:info@example.com!", ".");
+        assert_eq!(
+            apply_rule(&rule, "+441632960083").unwrap(),
+            Some(Rewrite::Uri("sip:info@example.com".to_string()))
+        );
+
+        // RFC 3403 section 6.2, back references and a case insensitive match
+        let rule = naptr(100, "", "", "!^CID:.+@([^.]+\\.)(.*)$!\\2!i", ".");
+        assert_eq!(
+            apply_rule(&rule, "cid:199608050105.ABC@mail.gsmb.com").unwrap(),
+            Some(Rewrite::Name(Name::from_str("gsmb.com.").unwrap()))
+        );
+
+        // an escaped delimiter and a partial match
+        let rule = naptr(100, "u", "", "/4\\/2/x\\/y/", ".");
+        assert_eq!(
+            apply_rule(&rule, "+14/23").unwrap(),
+            Some(Rewrite::Uri("+1x/y3".to_string()))
+        );
+
+        let rule = naptr(100, "s", "SIP+D2U", "", "_sip._udp.example.com.");
+        assert_eq!(
+            apply_rule(&rule, "example.com").unwrap(),
+            Some(Rewrite::Name(
+                Name::from_str("_sip._udp.example.com.").unwrap()
+            ))
+        );
+
+        let rule = naptr(100, "u", "E2U+sip", "!^\\+1.*$!sip:us@example.com!", ".");
+        assert_eq!(apply_rule(&rule, "+441632960083").unwrap(), None);
+
+        assert!(apply_rule(&naptr(100, "u", "", "", "."), "+1").is_err());
+        assert!(apply_rule(&naptr(100, "", "", "!a!b!", "example.com."), "a").is_err());
+        assert!(apply_rule(&naptr(100, "u", "", "!a!b", "."), "a").is_err());
+        assert!(apply_rule(&naptr(100, "u", "", "1a1b1", "."), "a").is_err());
+        assert!(apply_rule(&naptr(100, "u", "", "!(!b!", "."), "a").is_err());
+    }
+
+    #[test]
+    fn test_matches_service() {
+        let rule = naptr(100, "u", "E2U+sip+pres", "!^.*$!sip:a@example.com!", ".");
+        assert!(matches_service(&rule, "E2U+sip"));
+        assert!(matches_service(&rule, "e2u+SIP"));
+        assert!(!matches_service(&rule, "E2U+email"));
+        assert!(matches_service(
+            &naptr(100, "", "", "", "example.com."),
+            "E2U+sip"
+        ));
+    }
+
+    fn lookup_ip(name: Name) -> future::FutureResult<LookupIp, ResolveError> {
+        let query = Query::query(name, RecordType::A);
+        future::ok(LookupIp::from(Lookup::from_rdata(
+            query,
+            RData::A(Ipv4Addr::new(192, 0, 2, 1)),
+        )))
+    }
+
+    fn ddds(
+        records: Vec<(&'static str, RData)>,
+        key: &str,
+        aus: &str,
+        service: &str,
+    ) -> DddsLookup {
+        DddsLookupFuture::new(
+            Name::from_str(key).unwrap(),
+            aus.to_string(),
+            service.to_string(),
+            mock_lookup(records),
+            lookup_ip,
+        )
+        .wait()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_ddds_lookup_uri() {
+        let records = vec![
+            (
+                "3.8.0.0.6.9.2.3.6.1.4.4.e164.arpa.",
+                RData::NAPTR(naptr(
+                    100,
+                    "u",
+                    "E2U+sip",
+                    "!^.*$!sip:info@example.com!",
+                    ".",
+                )),
+            ),
+            (
+                "3.8.0.0.6.9.2.3.6.1.4.4.e164.arpa.",
+                RData::NAPTR(naptr(
+                    50,
+                    "u",
+                    "E2U+email",
+                    "!^.*$!mailto:info@example.com!",
+                    ".",
+                )),
+            ),
+            (
+                "3.8.0.0.6.9.2.3.6.1.4.4.e164.arpa.",
+                RData::NAPTR(naptr(
+                    10,
+                    "u",
+                    "E2U+sip",
+                    "!^\\+1.*$!sip:us@example.com!",
+                    ".",
+                )),
+            ),
+        ];
+
+        match ddds(
+            records,
+            "3.8.0.0.6.9.2.3.6.1.4.4.e164.arpa.",
+            "+441632960083",
+            "E2U+sip",
+        ) {
+            DddsLookup::Uri(uri) => assert_eq!(uri, "sip:info@example.com"),
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_ddds_lookup_srv_and_a() {
+        let srv = SRV::new(0, 0, 5060, Name::from_str("sip.example.com.").unwrap());
+        let records = vec![
+            (
+                "example.com.",
+                RData::NAPTR(naptr(100, "", "", "", "sip.example.net.")),
+            ),
+            (
+                "sip.example.net.",
+                RData::NAPTR(naptr(100, "s", "SIP+D2U", "", "_sip._udp.example.net.")),
+            ),
+            (
+                "sip.example.net.",
+                RData::NAPTR(naptr(100, "a", "SIPS+D2T", "", "sips.example.net.")),
+            ),
+            ("_sip._udp.example.net.", RData::SRV(srv.clone())),
+        ];
+
+        match ddds(records.clone(), "example.com.", "example.com", "SIP+D2U") {
+            DddsLookup::Srv(lookup) => assert_eq!(lookup.iter().collect::<Vec<_>>(), vec![&srv]),
+            result => panic!("unexpected result: {:?}", result),
+        }
+
+        match ddds(records, "example.com.", "example.com", "SIPS+D2T") {
+            DddsLookup::Ip(lookup) => assert_eq!(
+                lookup.iter().collect::<Vec<_>>(),
+                vec![IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1))]
+            ),
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_ddds_lookup_errors() {
+        let records = vec![
+            (
+                "a.example.com.",
+                RData::NAPTR(naptr(100, "", "", "", "b.example.com.")),
+            ),
+            (
+                "b.example.com.",
+                RData::NAPTR(naptr(100, "", "", "", "a.example.com.")),
+            ),
+            (
+                "p.example.com.",
+                RData::NAPTR(naptr(100, "p", "", "", "b.example.com.")),
+            ),
+        ];
+        let error = |key: &str| {
+            DddsLookupFuture::new(
+                Name::from_str(key).unwrap(),
+                "aus".to_string(),
+                "".to_string(),
+                mock_lookup(records.clone()),
+                lookup_ip,
+            )
+            .wait()
+            .unwrap_err()
+        };
+
+        assert!(error("a.example.com.").to_string().contains("too many"));
+        assert!(error("p.example.com.")
+            .to_string()
+            .contains("not supported"));
+        match *error("c.example.com.").kind() {
+            ResolveErrorKind::NoRecordsFound { .. } => (),
+            ref kind => panic!("unexpected error: {:?}", kind),
+        }
+    }
+}
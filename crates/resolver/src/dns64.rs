@@ -0,0 +1,479 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! DNS64, synthesis of AAAA records from A records for NAT64, see [RFC 6147](https://tools.ietf.org/html/rfc6147)
+//!
+//! With DNS64 enabled, see `ResolverConfig::set_dns64`, AAAA lookups of names without (usable) AAAA records are
+//!  answered with the addresses of their A records embedded in the NAT64 prefixes. PTR lookups of such synthesized
+//!  addresses are answered with the PTR records of the embedded IPv4 address.
+
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use proto::op::Query;
+use proto::rr::{Name, RData, Record, RecordType};
+
+use error::*;
+use lookup::Lookup;
+
+/// The Well-Known Prefix of [RFC 6052](https://tools.ietf.org/html/rfc6052#section-2.1), `64:ff9b::/96`
+pub const WELL_KNOWN_PREFIX: Ipv6Addr = Ipv6Addr::new(0x64, 0xff9b, 0, 0, 0, 0, 0, 0);
+
+/// A NAT64 prefix, in which IPv4 addresses are embedded as described in [RFC 6052](https://tools.ietf.org/html/rfc6052#section-2.2)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-config", derive(Serialize, Deserialize))]
+pub struct Dns64Prefix {
+    prefix: Ipv6Addr,
+    prefix_len: u8,
+}
+
+impl Dns64Prefix {
+    /// Creates a new prefix
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - the network of the prefix, the bits after `prefix_len` are ignored
+    /// * `prefix_len` - the length of the prefix, one of 32, 40, 48, 56, 64 or 96
+    pub fn new(prefix: Ipv6Addr, prefix_len: u8) -> ResolveResult<Self> {
+        let dns64_prefix = Dns64Prefix { prefix, prefix_len };
+        dns64_prefix.validate()?;
+
+        Ok(Dns64Prefix {
+            prefix: Ipv6Addr::from(u128::from(prefix) & mask(prefix_len)),
+            prefix_len,
+        })
+    }
+
+    /// The Well-Known Prefix, `64:ff9b::/96`
+    pub fn well_known() -> Self {
+        Dns64Prefix {
+            prefix: WELL_KNOWN_PREFIX,
+            prefix_len: 96,
+        }
+    }
+
+    /// The network of the prefix
+    pub fn prefix(&self) -> Ipv6Addr {
+        self.prefix
+    }
+
+    /// The length of the prefix in bits
+    pub fn prefix_len(&self) -> u8 {
+        self.prefix_len
+    }
+
+    fn validate(&self) -> ResolveResult<()> {
+        match self.prefix_len {
+            32 | 40 | 48 | 56 | 64 | 96 => (),
+            len => {
+                return Err(ResolveErrorKind::Msg(format!(
+                    "invalid DNS64 prefix length {}, must be one of 32, 40, 48, 56, 64 or 96",
+                    len
+                ))
+                .into())
+            }
+        }
+
+        // bits 64 to 71, the u octet, must be zero
+        if self.prefix_len > 64 && self.prefix.octets()[8] != 0 {
+            return Err(ResolveErrorKind::Msg(format!(
+                "invalid DNS64 prefix {}, bits 64 to 71 must be zero",
+                self
+            ))
+            .into());
+        }
+
+        Ok(())
+    }
+
+    /// Returns the positions of the octets of the embedded IPv4 address, which skip the u octet
+    fn positions(&self) -> impl Iterator<Item = usize> {
+        (usize::from(self.prefix_len) / 8..16)
+            .filter(|&i| i != 8)
+            .take(4)
+    }
+
+    /// Returns true if the address is within the prefix
+    pub fn contains(&self, ip: Ipv6Addr) -> bool {
+        u128::from(ip) & mask(self.prefix_len) == u128::from(self.prefix)
+    }
+
+    /// Returns the IPv6 address with the IPv4 address embedded in the prefix
+    pub fn embed(&self, ip: Ipv4Addr) -> Ipv6Addr {
+        let mut octets = self.prefix.octets();
+        for (position, octet) in self.positions().zip(ip.octets().iter()) {
+            octets[position] = *octet;
+        }
+
+        Ipv6Addr::from(octets)
+    }
+
+    /// Returns the IPv4 address embedded in the address, `None` if it is not within the prefix
+    pub fn extract(&self, ip: Ipv6Addr) -> Option<Ipv4Addr> {
+        if !self.contains(ip) {
+            return None;
+        }
+
+        let octets = ip.octets();
+        let mut ipv4 = [0; 4];
+        for (octet, position) in ipv4.iter_mut().zip(self.positions()) {
+            *octet = octets[position];
+        }
+
+        Some(Ipv4Addr::from(ipv4))
+    }
+}
+
+impl fmt::Display for Dns64Prefix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.prefix, self.prefix_len)
+    }
+}
+
+impl FromStr for Dns64Prefix {
+    type Err = ResolveError;
+
+    /// Parses a prefix like `64:ff9b::/96`
+    fn from_str(s: &str) -> ResolveResult<Self> {
+        let (prefix, prefix_len) = parse_network(s)?;
+        match prefix {
+            IpAddr::V6(prefix) => Self::new(prefix, prefix_len),
+            IpAddr::V4(_) => Err(ResolveErrorKind::Msg(format!("DNS64 prefix is not IPv6: {}", s)).into()),
+        }
+    }
+}
+
+/// A network whose addresses are ignored by DNS64, see [RFC 6147 section 5.1.4](https://tools.ietf.org/html/rfc6147#section-5.1.4)
+///
+/// AAAA records within an excluded IPv6 network are treated as if they did not exist, so that AAAA records are
+///  synthesized instead. A records within an excluded IPv4 network are not used for synthesis.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-config", derive(Serialize, Deserialize))]
+pub struct Dns64Exclusion {
+    network: IpAddr,
+    prefix_len: u8,
+}
+
+impl Dns64Exclusion {
+    /// Creates a new exclusion of the network, `prefix_len` must not exceed the length of the address
+    pub fn new(network: IpAddr, prefix_len: u8) -> ResolveResult<Self> {
+        let max_len = if network.is_ipv4() { 32 } else { 128 };
+        if prefix_len > max_len {
+            return Err(ResolveErrorKind::Msg(format!(
+                "invalid prefix length {} for {}",
+                prefix_len, network
+            ))
+            .into());
+        }
+
+        Ok(Dns64Exclusion {
+            network,
+            prefix_len,
+        })
+    }
+
+    /// The network
+    pub fn network(&self) -> IpAddr {
+        self.network
+    }
+
+    /// The length of the network prefix in bits
+    pub fn prefix_len(&self) -> u8 {
+        self.prefix_len
+    }
+
+    /// Returns true if the address is within the network
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.network, ip) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = (mask(self.prefix_len) >> 96) as u32;
+                u32::from(network) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = mask(self.prefix_len);
+                u128::from(network) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Dns64Exclusion {
+    type Err = ResolveError;
+
+    /// Parses a network like `::ffff:0:0/96` or `10.0.0.0/8`
+    fn from_str(s: &str) -> ResolveResult<Self> {
+        let (network, prefix_len) = parse_network(s)?;
+        Self::new(network, prefix_len)
+    }
+}
+
+/// Configuration of DNS64
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-config", derive(Serialize, Deserialize))]
+pub struct Dns64Config {
+    prefixes: Vec<Dns64Prefix>,
+    exclusions: Vec<Dns64Exclusion>,
+}
+
+impl Default for Dns64Config {
+    /// The Well-Known Prefix, excluding the IPv4-mapped addresses `::ffff:0:0/96`
+    fn default() -> Self {
+        Self::new(vec![Dns64Prefix::well_known()])
+    }
+}
+
+impl Dns64Config {
+    /// Creates a configuration which synthesizes addresses in all of the prefixes
+    ///
+    /// The IPv4-mapped addresses, `::ffff:0:0/96`, are excluded, as recommended by RFC 6147.
+    pub fn new(prefixes: Vec<Dns64Prefix>) -> Self {
+        let mapped = Dns64Exclusion {
+            network: IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0xffff, 0, 0)),
+            prefix_len: 96,
+        };
+
+        Dns64Config {
+            prefixes,
+            exclusions: vec![mapped],
+        }
+    }
+
+    /// The prefixes in which addresses are synthesized
+    pub fn prefixes(&self) -> &[Dns64Prefix] {
+        &self.prefixes
+    }
+
+    /// Add a network whose addresses are ignored, see `Dns64Exclusion`
+    pub fn add_exclusion(&mut self, exclusion: Dns64Exclusion) {
+        self.exclusions.push(exclusion);
+    }
+
+    /// The networks whose addresses are ignored
+    pub fn exclusions(&self) -> &[Dns64Exclusion] {
+        &self.exclusions
+    }
+
+    /// Checks the prefixes and exclusions, e.g. after deserialization
+    pub fn validate(&self) -> ResolveResult<()> {
+        if self.prefixes.is_empty() {
+            return Err(ResolveErrorKind::Message("no DNS64 prefixes").into());
+        }
+        for prefix in &self.prefixes {
+            prefix.validate()?;
+        }
+        for exclusion in &self.exclusions {
+            Dns64Exclusion::new(exclusion.network, exclusion.prefix_len)?;
+        }
+
+        Ok(())
+    }
+
+    fn is_excluded(&self, ip: IpAddr) -> bool {
+        self.exclusions.iter().any(|exclusion| exclusion.contains(ip))
+    }
+
+    /// Returns the lookup without the excluded AAAA records
+    pub(crate) fn remove_excluded(&self, lookup: Lookup) -> Lookup {
+        let excluded = |rdata: &RData| match *rdata {
+            RData::AAAA(ip) => self.is_excluded(IpAddr::V6(ip)),
+            _ => false,
+        };
+        if !lookup.iter().any(&excluded) {
+            return lookup;
+        }
+
+        let records = lookup
+            .records()
+            .iter()
+            .filter(|record| !excluded(record.rdata()))
+            .cloned()
+            .collect::<Vec<_>>();
+        Lookup::new_with_records(lookup.query().clone(), Arc::new(records), lookup.valid_until())
+    }
+
+    /// Returns the AAAA records synthesized from the A records of the lookup, as the answer to `query`
+    ///
+    /// The CNAME records which lead to the name of the A records are kept.
+    pub(crate) fn synthesize(&self, query: Query, a_lookup: &Lookup) -> Lookup {
+        let records = a_lookup
+            .records()
+            .iter()
+            .flat_map(|record| match *record.rdata() {
+                RData::A(ip) if !self.is_excluded(IpAddr::V4(ip)) => self
+                    .prefixes
+                    .iter()
+                    .map(|prefix| {
+                        Record::from_rdata(
+                            record.name().clone(),
+                            record.ttl(),
+                            RecordType::AAAA,
+                            RData::AAAA(prefix.embed(ip)),
+                        )
+                    })
+                    .collect(),
+                RData::CNAME(_) => vec![record.clone()],
+                _ => vec![],
+            })
+            .collect::<Vec<_>>();
+
+        Lookup::new_with_records(query, Arc::new(records), a_lookup.valid_until())
+    }
+
+    /// Returns the PTR query for the embedded IPv4 address, if the query is for a synthesized address
+    pub(crate) fn reverse_query(&self, query: &Query) -> Option<Query> {
+        let ip = parse_ip6_arpa(query.name())?;
+        let ipv4 = self.prefixes.iter().filter_map(|prefix| prefix.extract(ip)).next()?;
+
+        let mut reverse = Query::query(Name::from(IpAddr::V4(ipv4)), RecordType::PTR);
+        reverse.set_query_class(query.query_class());
+        Some(reverse)
+    }
+}
+
+/// Returns the mask of the first `prefix_len` bits
+fn mask(prefix_len: u8) -> u128 {
+    match prefix_len {
+        0 => 0,
+        len => !0 << (128 - u32::from(len.min(128))),
+    }
+}
+
+fn parse_network(s: &str) -> ResolveResult<(IpAddr, u8)> {
+    let mut parts = s.splitn(2, '/');
+    let network = parts.next().and_then(|ip| IpAddr::from_str(ip).ok());
+    let prefix_len = parts.next().and_then(|len| u8::from_str(len).ok());
+
+    match (network, prefix_len) {
+        (Some(network), Some(prefix_len)) => Ok((network, prefix_len)),
+        _ => Err(ResolveErrorKind::Msg(format!("invalid network, expected address/length: {}", s)).into()),
+    }
+}
+
+/// Parses a name like `1.0.0.0.(...).b.9.f.f.4.6.0.0.ip6.arpa.`, only names of complete addresses are accepted
+fn parse_ip6_arpa(name: &Name) -> Option<Ipv6Addr> {
+    if name.num_labels() != 34 {
+        return None;
+    }
+
+    let labels = name.iter().collect::<Vec<_>>();
+    if !labels[32].eq_ignore_ascii_case(b"ip6") || !labels[33].eq_ignore_ascii_case(b"arpa") {
+        return None;
+    }
+
+    let mut ip = 0_u128;
+    for label in labels[..32].iter().rev() {
+        if label.len() != 1 {
+            return None;
+        }
+        let nibble = char::from(label[0]).to_digit(16)?;
+        ip = ip << 4 | u128::from(nibble);
+    }
+
+    Some(Ipv6Addr::from(ip))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v4(ip: &str) -> Ipv4Addr {
+        Ipv4Addr::from_str(ip).unwrap()
+    }
+
+    fn v6(ip: &str) -> Ipv6Addr {
+        Ipv6Addr::from_str(ip).unwrap()
+    }
+
+    // the examples of RFC 6052 section 2.4
+    #[test]
+    fn test_embed_and_extract() {
+        let examples = [
+            ("2001:db8::/32", "2001:db8:c000:221::"),
+            ("2001:db8:100::/40", "2001:db8:1c0:2:21::"),
+            ("2001:db8:122::/48", "2001:db8:122:c000:2:2100::"),
+            ("2001:db8:122:300::/56", "2001:db8:122:3c0:0:221::"),
+            ("2001:db8:122:344::/64", "2001:db8:122:344:c0:2:2100:0"),
+            ("2001:db8:122:344::/96", "2001:db8:122:344::192.0.2.33"),
+            ("64:ff9b::/96", "64:ff9b::192.0.2.33"),
+        ];
+
+        for &(prefix, synthesized) in &examples {
+            let prefix = Dns64Prefix::from_str(prefix).unwrap();
+            assert_eq!(prefix.embed(v4("192.0.2.33")), v6(synthesized), "{}", prefix);
+            assert_eq!(prefix.extract(v6(synthesized)), Some(v4("192.0.2.33")));
+        }
+
+        assert_eq!(Dns64Prefix::well_known().extract(v6("2001:db8::1")), None);
+    }
+
+    #[test]
+    fn test_invalid_prefixes() {
+        assert!(Dns64Prefix::from_str("2001:db8::/33").is_err());
+        assert!(Dns64Prefix::from_str("2001:db8:0:0:ff00::/96").is_err());
+        assert!(Dns64Prefix::from_str("192.0.2.0/24").is_err());
+        assert!(Dns64Prefix::from_str("64:ff9b::").is_err());
+    }
+
+    #[test]
+    fn test_synthesize() {
+        let mut config = Dns64Config::default();
+        config.add_exclusion(Dns64Exclusion::from_str("10.0.0.0/8").unwrap());
+
+        let query = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::AAAA);
+        let a_lookup = Lookup::new_with_max_ttl(
+            Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A),
+            Arc::new(vec![RData::A(v4("192.0.2.33")), RData::A(v4("10.0.0.1"))]),
+        );
+        let lookup = config.synthesize(query.clone(), &a_lookup);
+
+        assert_eq!(lookup.query(), &query);
+        assert_eq!(
+            lookup.iter().cloned().collect::<Vec<_>>(),
+            vec![RData::AAAA(v6("64:ff9b::192.0.2.33"))]
+        );
+    }
+
+    #[test]
+    fn test_remove_excluded() {
+        let config = Dns64Config::default();
+        let lookup = Lookup::new_with_max_ttl(
+            Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::AAAA),
+            Arc::new(vec![
+                RData::AAAA(v6("::ffff:192.0.2.33")),
+                RData::AAAA(v6("2001:db8::1")),
+            ]),
+        );
+
+        assert_eq!(
+            config
+                .remove_excluded(lookup)
+                .iter()
+                .cloned()
+                .collect::<Vec<_>>(),
+            vec![RData::AAAA(v6("2001:db8::1"))]
+        );
+    }
+
+    #[test]
+    fn test_reverse_query() {
+        let config = Dns64Config::default();
+
+        let synthesized = Name::from(IpAddr::V6(v6("64:ff9b::192.0.2.33")));
+        let query = config
+            .reverse_query(&Query::query(synthesized, RecordType::PTR))
+            .unwrap();
+        assert_eq!(query.name(), &Name::from_str("33.2.0.192.in-addr.arpa.").unwrap());
+
+        let other = Name::from(IpAddr::V6(v6("2001:db8::1")));
+        assert!(config
+            .reverse_query(&Query::query(other, RecordType::PTR))
+            .is_none());
+    }
+}
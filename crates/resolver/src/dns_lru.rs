@@ -7,12 +7,16 @@
 
 //! An LRU cache designed for work with DNS lookups
 
+use std::io::{Read, Write};
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use proto::op::Query;
-use proto::rr::RData;
+use proto::rr::rdata::opt::ClientSubnet;
+use proto::rr::{Name, RData, Record};
+use proto::serialize::binary::{BinDecodable, BinDecoder, BinEncodable, BinEncoder};
 
+use cache::{CacheEntry, CacheStats};
 use config;
 use error::*;
 use lookup::Lookup;
@@ -22,11 +26,33 @@ use lru_cache::LruCache;
 ///   Setting this to a value of 1 day, in seconds
 pub const MAX_TTL: u32 = 86400_u32;
 
+/// TTL of records served stale, as recommended in https://tools.ietf.org/html/rfc8767, 30 seconds
+///
+/// This is also the interval at which a stale record is served from the cache directly, before the name servers are
+///  tried again.
+pub const STALE_TTL: u32 = 30_u32;
+
+/// The maximum number of answers for different client subnets kept per query, see RFC 7871 section 7.3.2
+const MAX_SCOPED_ANSWERS: usize = 8;
+
+/// Identifies a snapshot of the cache, see `Snapshot::save`
+const SNAPSHOT_MAGIC: &[u8; 8] = b"TDNSLRU\0";
+/// Version of the snapshot format, incremented on incompatible changes
+const SNAPSHOT_VERSION: u8 = 1;
+
 #[derive(Debug)]
 struct LruValue {
     // In the None case, this represents an NXDomain
     lookup: Option<Lookup>,
     valid_until: Instant,
+    // The TTL this value was inserted with
+    ttl: Duration,
+    // Set once the stale lookup has been served, until then the name servers are not tried again
+    stale_until: Option<Instant>,
+    // Set once a prefetch of this value has been requested
+    prefetching: bool,
+    // The client subnet the answer is tailored to, None if it is valid for all clients
+    scope: Option<ClientSubnet>,
 }
 
 impl LruValue {
@@ -34,11 +60,49 @@ impl LruValue {
     fn is_current(&self, now: Instant) -> bool {
         now <= self.valid_until
     }
+
+    /// Returns true if this is an expired set of ips which may still be served stale
+    fn is_stale(&self, now: Instant, serve_stale: Duration) -> bool {
+        self.lookup.is_some() && !self.is_current(now) && now <= self.valid_until + serve_stale
+    }
+
+    /// Returns true if this value may be used for a request with the client subnet, see RFC 7871 section 7.3.1
+    fn is_in_scope(&self, client_subnet: Option<&ClientSubnet>) -> bool {
+        match (self.scope.as_ref(), client_subnet) {
+            (None, _) => true,
+            (Some(scope), Some(client_subnet)) => scope.contains(client_subnet),
+            (Some(_), None) => false,
+        }
+    }
+
+    /// The length of the prefix of the client subnet the answer is tailored to, None if it is valid for all clients
+    fn scope_prefix(&self) -> Option<u8> {
+        self.scope.as_ref().map(ClientSubnet::source_prefix)
+    }
+
+    /// Returns true if less than `threshold` percent of the TTL remain
+    fn is_expiring(&self, now: Instant, threshold: u8) -> bool {
+        self.lookup.is_some()
+            && self.is_current(now)
+            && (self.valid_until - now) * 100 <= self.ttl * u32::from(threshold)
+    }
+}
+
+/// Returns the most specific of the values for the client subnet, see RFC 7871 section 7.3.1
+fn value_in_scope<'a>(
+    values: &'a mut [LruValue],
+    client_subnet: Option<&ClientSubnet>,
+) -> Option<&'a mut LruValue> {
+    values
+        .iter_mut()
+        .filter(|value| value.is_in_scope(client_subnet))
+        .max_by_key(|value| value.scope_prefix())
 }
 
 #[derive(Debug)]
 pub(crate) struct DnsLru {
-    cache: LruCache<Query, LruValue>,
+    /// The answers for each query, at most one per client subnet, see `MAX_SCOPED_ANSWERS`
+    cache: LruCache<Query, Vec<LruValue>>,
     /// A minimum TTL value for positive responses.
     ///
     /// Positive responses with TTLs under `positive_max_ttl` will use
@@ -75,6 +139,18 @@ pub(crate) struct DnsLru {
     ///
     /// [`MAX_TTL`]: const.MAX_TTL.html
     negative_max_ttl: Duration,
+    /// The window after expiration during which positive responses are kept to be served stale.
+    ///
+    /// If this value is not set on the `TtlConfig` used to construct this
+    /// `DnsLru`, it will default to 0, i.e. expired responses are dropped.
+    serve_stale: Duration,
+    /// The percentage of the TTL remaining under which positive responses should be prefetched.
+    ///
+    /// If this value is not set on the `TtlConfig` used to construct this
+    /// `DnsLru`, responses are never prefetched.
+    prefetch_threshold: Option<u8>,
+    /// Counters of the usage of this cache, see `stats`.
+    stats: CacheStats,
 }
 
 /// The time-to-live, TTL, configuration for use by the cache.
@@ -106,6 +182,15 @@ pub(crate) struct TtlConfig {
     /// `NXDOMAIN` responses with TTLs over `negative_max_ttl` will use
     /// `negative_max_ttl` instead.
     pub negative_max_ttl: Option<Duration>,
+    /// An optional window after expiration during which positive responses
+    /// may be served stale.
+    ///
+    /// Expired responses are only returned by `DnsLru::get_stale`, which is
+    /// used when the name servers could not be reached.
+    pub serve_stale: Option<Duration>,
+    /// An optional percentage of the TTL, positive responses used when less
+    /// than this remains should be refreshed ahead of their expiration.
+    pub prefetch_threshold: Option<u8>,
 }
 
 impl TtlConfig {
@@ -115,6 +200,12 @@ impl TtlConfig {
             negative_min_ttl: opts.negative_min_ttl,
             positive_max_ttl: opts.positive_max_ttl,
             negative_max_ttl: opts.negative_max_ttl,
+            serve_stale: opts.serve_stale,
+            prefetch_threshold: if opts.prefetch {
+                Some(opts.prefetch_threshold)
+            } else {
+                None
+            },
         }
     }
 }
@@ -126,6 +217,8 @@ impl DnsLru {
             negative_min_ttl,
             positive_max_ttl,
             negative_max_ttl,
+            serve_stale,
+            prefetch_threshold,
         } = ttl_cfg;
         let cache = LruCache::new(capacity);
         Self {
@@ -136,26 +229,68 @@ impl DnsLru {
                 .unwrap_or_else(|| Duration::from_secs(u64::from(MAX_TTL))),
             negative_max_ttl: negative_max_ttl
                 .unwrap_or_else(|| Duration::from_secs(u64::from(MAX_TTL))),
+            serve_stale: serve_stale.unwrap_or_else(|| Duration::from_secs(0)),
+            prefetch_threshold,
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// Inserts the value, counting the least recently used entry as evicted if there is no room for it
+    ///
+    /// The value replaces the answer for the same client subnet, answers for other client subnets are kept up to
+    ///  `MAX_SCOPED_ANSWERS`, dropping the oldest.
+    fn insert_value(&mut self, query: Query, value: LruValue) {
+        if let Some(values) = self.cache.get_mut(&query) {
+            values.retain(|other| other.scope != value.scope);
+            if values.len() >= MAX_SCOPED_ANSWERS {
+                values.remove(0);
+            }
+            values.push(value);
+            return;
         }
+
+        if self.cache.len() >= self.cache.capacity() {
+            self.stats.evictions += 1;
+        }
+
+        self.cache.insert(query, vec![value]);
     }
 
+    /// Inserts the records of an answer, valid for the clients within `scope`, see `answer_scope`
+    ///
+    /// The records are owned by the name of the query, see `insert_records`.
     pub(crate) fn insert(
         &mut self,
         query: Query,
+        scope: Option<ClientSubnet>,
         rdatas_and_ttl: Vec<(RData, u32)>,
         now: Instant,
     ) -> Lookup {
-        let len = rdatas_and_ttl.len();
+        let records = rdatas_and_ttl
+            .into_iter()
+            .map(|(rdata, ttl)| {
+                Record::from_rdata(query.name().clone(), ttl, rdata.to_record_type(), rdata)
+            }).collect();
+
+        self.insert_records(query, scope, records, now)
+    }
+
+    /// Inserts the records of an answer, valid for the clients within `scope`, see `answer_scope`
+    ///
+    /// The records include the CNAME records which lead to the name of the answer, the lookup is valid for the
+    ///  minimum TTL of all of them.
+    pub(crate) fn insert_records(
+        &mut self,
+        query: Query,
+        scope: Option<ClientSubnet>,
+        records: Vec<Record>,
+        now: Instant,
+    ) -> Lookup {
         // collapse the values, we're going to take the Minimum TTL as the correct one
-        let (rdatas, ttl): (Vec<RData>, Duration) = rdatas_and_ttl.into_iter().fold(
-            (Vec::with_capacity(len), self.positive_max_ttl),
-            |(mut rdatas, mut min_ttl), (rdata, ttl)| {
-                rdatas.push(rdata);
-                let ttl = Duration::from_secs(u64::from(ttl));
-                min_ttl = min_ttl.min(ttl);
-                (rdatas, min_ttl)
-            },
-        );
+        let ttl = records
+            .iter()
+            .map(|record| Duration::from_secs(u64::from(record.ttl())))
+            .fold(self.positive_max_ttl, Duration::min);
 
         // If the cache was configured with a minimum TTL, and that value is higher
         // than the minimum TTL in the values, use it instead.
@@ -163,12 +298,16 @@ impl DnsLru {
         let valid_until = now + ttl;
 
         // insert into the LRU
-        let lookup = Lookup::new_with_deadline(query.clone(), Arc::new(rdatas), valid_until);
-        self.cache.insert(
+        let lookup = Lookup::new_with_records(query.clone(), Arc::new(records), valid_until);
+        self.insert_value(
             query,
             LruValue {
                 lookup: Some(lookup.clone()),
                 valid_until,
+                ttl,
+                stale_until: None,
+                prefetching: false,
+                scope,
             },
         );
 
@@ -179,6 +318,7 @@ impl DnsLru {
     pub(crate) fn duplicate(
         &mut self,
         query: Query,
+        scope: Option<ClientSubnet>,
         lookup: Lookup,
         ttl: u32,
         now: Instant,
@@ -186,11 +326,15 @@ impl DnsLru {
         let ttl = Duration::from_secs(u64::from(ttl));
         let valid_until = now + ttl;
 
-        self.cache.insert(
+        self.insert_value(
             query,
             LruValue {
                 lookup: Some(lookup.clone()),
                 valid_until,
+                ttl,
+                stale_until: None,
+                prefetching: false,
+                scope,
             },
         );
 
@@ -201,7 +345,14 @@ impl DnsLru {
         ResolveErrorKind::NoRecordsFound { query, valid_until }.into()
     }
 
-    pub(crate) fn negative(&mut self, query: Query, ttl: u32, now: Instant) -> ResolveError {
+    /// Inserts a negative response, `NXDomain` or `NoError` without records of the type, see RFC 2308
+    pub(crate) fn negative(
+        &mut self,
+        query: Query,
+        scope: Option<ClientSubnet>,
+        ttl: u32,
+        now: Instant,
+    ) -> ResolveError {
         // TODO: if we are getting a negative response, should we instead fallback to cache?
         //   this would cache indefinitely, probably not correct
 
@@ -212,11 +363,15 @@ impl DnsLru {
             .min(self.negative_max_ttl);
         let valid_until = now + ttl;
 
-        self.cache.insert(
+        self.insert_value(
             query.clone(),
             LruValue {
                 lookup: None,
                 valid_until,
+                ttl,
+                stale_until: None,
+                prefetching: false,
+                scope,
             },
         );
 
@@ -224,27 +379,408 @@ impl DnsLru {
     }
 
     /// This needs to be mut b/c it's an LRU, meaning the ordering of elements will potentially change on retrieval...
-    pub(crate) fn get(&mut self, query: &Query, now: Instant) -> Option<Lookup> {
+    ///
+    /// Expired entries are only returned while a previously served stale lookup is still valid, see `get_stale`.
+    ///  Entries tailored to a client subnet are only returned for requests with a `client_subnet` in their scope,
+    ///  the most specific of those is used.
+    pub(crate) fn get(
+        &mut self,
+        query: &Query,
+        client_subnet: Option<&ClientSubnet>,
+        now: Instant,
+    ) -> Option<Lookup> {
+        let serve_stale = self.serve_stale;
         let mut out_of_date = false;
-        let lookup = self.cache.get_mut(query).and_then(|value| {
+        let mut negative = false;
+        let lookup = self.cache.get_mut(query).and_then(|values| {
+            // in this case, we can preemtively remove out of data elements
+            // this assumes time is always moving forward, this would only not be true in contrived situations where
+            //  now is not current time, like tests...
+            values.retain(|value| value.is_current(now) || value.is_stale(now, serve_stale));
+            out_of_date = values.is_empty();
+
+            let value = value_in_scope(values, client_subnet)?;
             if value.is_current(now) {
-                out_of_date = false;
+                negative = value.lookup.is_none();
                 value.lookup.clone()
             } else {
-                out_of_date = true;
-                None
+                // keep the stale entry around, it may be needed if the name servers fail
+                match (value.stale_until, value.lookup.as_ref()) {
+                    (Some(stale_until), Some(lookup)) if now <= stale_until => {
+                        Some(lookup.with_valid_until(stale_until))
+                    }
+                    _ => None,
+                }
             }
         });
 
-        // in this case, we can preemtively remove out of data elements
-        // this assumes time is always moving forward, this would only not be true in contrived situations where now
-        //  is not current time, like tests...
         if out_of_date {
             self.cache.remove(query);
         }
 
+        if lookup.is_some() {
+            self.stats.hits += 1;
+        } else if negative {
+            self.stats.negative_hits += 1;
+        } else {
+            self.stats.misses += 1;
+        }
+
         lookup
     }
+
+    /// Returns an expired lookup that is still within the serve-stale window, see https://tools.ietf.org/html/rfc8767
+    ///
+    /// The returned `Lookup` is valid for `STALE_TTL` seconds, during which `get` will return it as well.
+    pub(crate) fn get_stale(
+        &mut self,
+        query: &Query,
+        client_subnet: Option<&ClientSubnet>,
+        now: Instant,
+    ) -> Option<Lookup> {
+        let serve_stale = self.serve_stale;
+        self.cache.get_mut(query).and_then(|values| {
+            let value = value_in_scope(values, client_subnet)?;
+            if !value.is_stale(now, serve_stale) {
+                return None;
+            }
+
+            let stale_until = now + Duration::from_secs(u64::from(STALE_TTL));
+            value.stale_until = Some(stale_until);
+            value
+                .lookup
+                .as_ref()
+                .map(|lookup| lookup.with_valid_until(stale_until))
+        })
+    }
+
+    /// Removes all entries
+    pub(crate) fn clear(&mut self) {
+        self.cache.clear();
+    }
+
+    /// Removes all entries for the name, of any type and class
+    ///
+    /// If `subtree` is true, the entries of all names below the name are removed as well.
+    ///
+    /// # Returns
+    ///
+    /// The number of entries removed
+    pub(crate) fn remove_name(&mut self, name: &Name, subtree: bool) -> usize {
+        let queries = self
+            .cache
+            .iter()
+            .map(|(query, _)| query)
+            .filter(|query| {
+                if subtree {
+                    name.zone_of(query.name())
+                } else {
+                    name == query.name()
+                }
+            }).cloned()
+            .collect::<Vec<_>>();
+
+        for query in &queries {
+            self.cache.remove(query);
+        }
+
+        queries.len()
+    }
+
+    /// Returns the counters of the usage of this cache
+    pub(crate) fn stats(&self) -> CacheStats {
+        CacheStats {
+            entries: self.cache.iter().map(|(_, values)| values.len()).sum(),
+            ..self.stats
+        }
+    }
+
+    /// Returns all entries, from the least to the most recently used
+    pub(crate) fn entries(&self) -> Vec<CacheEntry> {
+        self.cache
+            .iter()
+            .flat_map(|(query, values)| {
+                values.iter().map(move |value| {
+                    CacheEntry::new(query.clone(), value.lookup.clone(), value.valid_until)
+                })
+            }).collect()
+    }
+
+    /// Copies all current entries, to be written with `Snapshot::save` after the lock of the cache is released
+    ///
+    /// Expired entries and entries tailored to a client subnet are skipped.
+    pub(crate) fn snapshot(&self, now: Instant) -> Snapshot {
+        let entries = self
+            .cache
+            .iter()
+            .flat_map(|(query, values)| values.iter().map(move |value| (query, value)))
+            .filter(|&(_, value)| value.is_current(now) && value.scope.is_none())
+            .map(|(query, value)| SnapshotEntry {
+                query: query.clone(),
+                lookup: value.lookup.clone(),
+                ttl: value.ttl,
+                remaining: value.valid_until - now,
+            }).collect();
+
+        Snapshot { entries }
+    }
+
+    /// Restores the entries of a snapshot written with `Snapshot::save`
+    ///
+    /// The remaining TTLs are reduced by the time passed since the snapshot was taken, entries which expired in the
+    ///  meantime are discarded. The TTLs are then limited to the configured maximum TTLs of this cache.
+    ///
+    /// # Returns
+    ///
+    /// The number of entries restored
+    pub(crate) fn load<R: Read>(
+        &mut self,
+        reader: &mut R,
+        now: Instant,
+        system_now: SystemTime,
+    ) -> ResolveResult<usize> {
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf)?;
+
+        let mut decoder = BinDecoder::new(&buf);
+        let magic = decoder.read_slice(SNAPSHOT_MAGIC.len())?.unverified();
+        if magic != SNAPSHOT_MAGIC {
+            return Err(ResolveErrorKind::Message("not a snapshot of the resolver cache").into());
+        }
+
+        let version = decoder.read_u8()?.unverified();
+        if version != SNAPSHOT_VERSION {
+            return Err(ResolveErrorKind::Msg(format!(
+                "unsupported version of the resolver cache snapshot: {}",
+                version
+            )).into());
+        }
+
+        let saved_at = u64::from(decoder.read_u32()?.unverified()) << 32
+            | u64::from(decoder.read_u32()?.unverified());
+        let elapsed = system_now
+            .duration_since(UNIX_EPOCH + Duration::from_secs(saved_at))
+            .unwrap_or_else(|_| Duration::from_secs(0));
+
+        let mut count = 0;
+        while !decoder.is_empty() {
+            let header = read_chunk(&mut decoder)?;
+            let mut header = BinDecoder::new(header);
+
+            let query = Query::read(&mut header)?;
+            let positive = header.read_u8()?.unverified() != 0;
+            let ttl = Duration::from_secs(u64::from(header.read_u32()?.unverified()));
+            let remaining = Duration::from_secs(u64::from(header.read_u32()?.unverified()));
+
+            let lookup = if positive {
+                let lookup_query = Query::read(&mut header)?;
+                let len = header.read_u16()?.unverified();
+
+                let mut records = Vec::with_capacity(len as usize);
+                for _ in 0..len {
+                    let record = read_chunk(&mut decoder)?;
+                    records.push(Record::read(&mut BinDecoder::new(record))?);
+                }
+
+                Some((lookup_query, records))
+            } else {
+                None
+            };
+
+            if remaining <= elapsed {
+                continue;
+            }
+
+            let max_ttl = if positive {
+                self.positive_max_ttl
+            } else {
+                self.negative_max_ttl
+            };
+            let remaining = (remaining - elapsed).min(max_ttl);
+            let valid_until = now + remaining;
+
+            self.insert_value(
+                query,
+                LruValue {
+                    lookup: lookup.map(|(lookup_query, records)| {
+                        Lookup::new_with_records(lookup_query, Arc::new(records), valid_until)
+                    }),
+                    valid_until,
+                    ttl: ttl.min(max_ttl),
+                    stale_until: None,
+                    prefetching: false,
+                    scope: None,
+                },
+            );
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    /// Returns true if the current lookup should be refreshed before it expires
+    ///
+    /// This is true once per inserted lookup, when it is used with less than the configured prefetch threshold of
+    ///  its TTL remaining. The lookup is the one `get` returns for the client subnet.
+    pub(crate) fn should_prefetch(
+        &mut self,
+        query: &Query,
+        client_subnet: Option<&ClientSubnet>,
+        now: Instant,
+    ) -> bool {
+        let threshold = match self.prefetch_threshold {
+            Some(threshold) => threshold,
+            None => return false,
+        };
+
+        match self
+            .cache
+            .get_mut(query)
+            .and_then(|values| value_in_scope(values, client_subnet))
+        {
+            Some(ref mut value) if !value.prefetching && value.is_expiring(now, threshold) => {
+                value.prefetching = true;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Returns the client subnet an answer is valid for, None if it is valid for all clients
+///
+/// See RFC 7871 section 7.3.1, the scope of the answer is limited to the source prefix of the request.
+pub(crate) fn answer_scope(
+    request: Option<&ClientSubnet>,
+    response: Option<&ClientSubnet>,
+) -> Option<ClientSubnet> {
+    match (request, response) {
+        (Some(request), Some(response)) if response.scope_prefix() > 0 => Some(ClientSubnet::new(
+            request.address(),
+            response.scope_prefix().min(request.source_prefix()),
+            0,
+        )),
+        _ => None,
+    }
+}
+
+/// The current entries of a `DnsLru`, see `DnsLru::snapshot`
+#[derive(Debug)]
+pub(crate) struct Snapshot {
+    entries: Vec<SnapshotEntry>,
+}
+
+#[derive(Debug)]
+struct SnapshotEntry {
+    query: Query,
+    // In the None case, this represents an NXDomain
+    lookup: Option<Lookup>,
+    // The TTL the entry was inserted with
+    ttl: Duration,
+    // The TTL remaining at the time of the snapshot
+    remaining: Duration,
+}
+
+impl Snapshot {
+    /// Writes the entries, to be restored with `DnsLru::load`
+    ///
+    /// The snapshot starts with a header of `SNAPSHOT_MAGIC`, `SNAPSHOT_VERSION` and the time of the snapshot in
+    ///  seconds since the UNIX epoch. Each entry follows as a length prefixed header with the `Query`, whether it is
+    ///  positive, the original and the remaining TTL, and for positive entries the `Query` of the `Lookup` and the
+    ///  number of records, each of which follows as a length prefixed `Record` with its owner name, including the
+    ///  CNAME records of the answer. All values are in DNS wire format.
+    ///
+    /// Entries are written from the least to the most recently used.
+    ///
+    /// # Returns
+    ///
+    /// The number of entries written
+    pub(crate) fn save<W: Write>(
+        &self,
+        writer: &mut W,
+        system_now: SystemTime,
+    ) -> ResolveResult<usize> {
+        let saved_at = system_now
+            .duration_since(UNIX_EPOCH)
+            .map_err(|e| ResolveErrorKind::Msg(format!("invalid system time: {}", e)))?
+            .as_secs();
+
+        let mut header = Vec::new();
+        {
+            let mut encoder = BinEncoder::new(&mut header);
+            encoder.emit_vec(SNAPSHOT_MAGIC)?;
+            encoder.emit(SNAPSHOT_VERSION)?;
+            encoder.emit_u32((saved_at >> 32) as u32)?;
+            encoder.emit_u32(saved_at as u32)?;
+        }
+        writer.write_all(&header)?;
+
+        let mut count = 0;
+        for entry in &self.entries {
+            let ttl = entry.ttl.as_secs().min(u64::from(u32::max_value())) as u32;
+            let remaining = entry.remaining.as_secs().min(u64::from(u32::max_value())) as u32;
+
+            let mut header = Vec::new();
+            {
+                let mut encoder = BinEncoder::new(&mut header);
+                entry.query.emit(&mut encoder)?;
+                encoder.emit(entry.lookup.is_some() as u8)?;
+                encoder.emit_u32(ttl)?;
+                encoder.emit_u32(remaining)?;
+
+                if let Some(ref lookup) = entry.lookup {
+                    lookup.query().emit(&mut encoder)?;
+                    encoder.emit_u16(lookup.records().len() as u16)?;
+                }
+            }
+            write_chunk(writer, &header)?;
+
+            let lookup = match entry.lookup {
+                Some(ref lookup) => lookup,
+                None => {
+                    count += 1;
+                    continue;
+                }
+            };
+
+            for record in lookup.records() {
+                let record = Record::from_rdata(
+                    record.name().clone(),
+                    remaining,
+                    record.rr_type(),
+                    record.rdata().clone(),
+                );
+
+                let mut bytes = Vec::new();
+                record.emit(&mut BinEncoder::new(&mut bytes))?;
+                write_chunk(writer, &bytes)?;
+            }
+
+            count += 1;
+        }
+
+        writer.flush()?;
+        Ok(count)
+    }
+}
+
+/// Writes the bytes prefixed with their length
+fn write_chunk<W: Write>(writer: &mut W, bytes: &[u8]) -> ResolveResult<()> {
+    if bytes.len() > u16::max_value() as usize {
+        return Err(
+            ResolveErrorKind::Message("entry too large for the resolver cache snapshot").into(),
+        );
+    }
+
+    writer.write_all(&[(bytes.len() >> 8) as u8, bytes.len() as u8])?;
+    writer.write_all(bytes)?;
+    Ok(())
+}
+
+/// Reads bytes prefixed with their length
+fn read_chunk<'r>(decoder: &mut BinDecoder<'r>) -> ResolveResult<&'r [u8]> {
+    let len = decoder.read_u16()?.unverified();
+    Ok(decoder.read_slice(len as usize)?.unverified())
 }
 
 // see also the lookup_tests.rs in integration-tests crate
@@ -269,6 +805,10 @@ mod tests {
         let value = LruValue {
             lookup: None,
             valid_until: future,
+            ttl: Duration::from_secs(5),
+            stale_until: None,
+            prefetching: false,
+            scope: None,
         };
 
         assert!(value.is_current(now));
@@ -293,7 +833,7 @@ mod tests {
         };
         let mut lru = DnsLru::new(1, ttls);
 
-        let rc_ips = lru.insert(name.clone(), ips_ttl, now);
+        let rc_ips = lru.insert(name.clone(), None, ips_ttl, now);
         assert_eq!(*rc_ips.iter().next().unwrap(), ips[0]);
         // the returned lookup should use the cache's min TTL, since the
         // query's TTL was below the minimum.
@@ -302,7 +842,7 @@ mod tests {
         // record should have TTL of 3 seconds.
         let ips_ttl = vec![(RData::A(Ipv4Addr::new(127, 0, 0, 1)), 3)];
 
-        let rc_ips = lru.insert(name.clone(), ips_ttl, now);
+        let rc_ips = lru.insert(name.clone(), None, ips_ttl, now);
         assert_eq!(*rc_ips.iter().next().unwrap(), ips[0]);
         // the returned lookup should use the record's TTL, since it's
         // greater than the cache's minimum.
@@ -323,7 +863,7 @@ mod tests {
         let mut lru = DnsLru::new(1, ttls);
 
         // neg response should have TTL of 1 seconds.
-        let nx_error = lru.negative(name.clone(), 1, now);
+        let nx_error = lru.negative(name.clone(), None, 1, now);
         match nx_error.kind() {
             &ResolveErrorKind::NoRecordsFound { valid_until, .. } => {
                 let valid_until = valid_until.expect("resolve error should have a deadline");
@@ -334,7 +874,7 @@ mod tests {
         }
 
         // neg response should have TTL of 3 seconds.
-        let nx_error = lru.negative(name.clone(), 3, now);
+        let nx_error = lru.negative(name.clone(), None, 3, now);
         match nx_error.kind() {
             &ResolveErrorKind::NoRecordsFound { valid_until, .. } => {
                 let valid_until = valid_until.expect("ResolveError should have a deadline");
@@ -362,7 +902,7 @@ mod tests {
         };
         let mut lru = DnsLru::new(1, ttls);
 
-        let rc_ips = lru.insert(name.clone(), ips_ttl, now);
+        let rc_ips = lru.insert(name.clone(), None, ips_ttl, now);
         assert_eq!(*rc_ips.iter().next().unwrap(), ips[0]);
         // the returned lookup should use the cache's min TTL, since the
         // query's TTL was above the maximum.
@@ -371,7 +911,7 @@ mod tests {
         // record should have TTL of 59 seconds.
         let ips_ttl = vec![(RData::A(Ipv4Addr::new(127, 0, 0, 1)), 59)];
 
-        let rc_ips = lru.insert(name.clone(), ips_ttl, now);
+        let rc_ips = lru.insert(name.clone(), None, ips_ttl, now);
         assert_eq!(*rc_ips.iter().next().unwrap(), ips[0]);
         // the returned lookup should use the record's TTL, since it's
         // below than the cache's maximum.
@@ -392,7 +932,7 @@ mod tests {
         let mut lru = DnsLru::new(1, ttls);
 
         // neg response should have TTL of 62 seconds.
-        let nx_error = lru.negative(name.clone(), 62, now);
+        let nx_error = lru.negative(name.clone(), None, 62, now);
         match nx_error.kind() {
             &ResolveErrorKind::NoRecordsFound { valid_until, .. } => {
                 let valid_until = valid_until.expect("resolve error should have a deadline");
@@ -403,7 +943,7 @@ mod tests {
         }
 
         // neg response should have TTL of 59 seconds.
-        let nx_error = lru.negative(name.clone(), 59, now);
+        let nx_error = lru.negative(name.clone(), None, 59, now);
         match nx_error.kind() {
             &ResolveErrorKind::NoRecordsFound { valid_until, .. } => {
                 let valid_until = valid_until.expect("resolve error should have a deadline");
@@ -423,10 +963,10 @@ mod tests {
         let ips = vec![RData::A(Ipv4Addr::new(127, 0, 0, 1))];
         let mut lru = DnsLru::new(1, TtlConfig::default());
 
-        let rc_ips = lru.insert(name.clone(), ips_ttl, now);
+        let rc_ips = lru.insert(name.clone(), None, ips_ttl, now);
         assert_eq!(*rc_ips.iter().next().unwrap(), ips[0]);
 
-        let rc_ips = lru.get(&name, now).unwrap();
+        let rc_ips = lru.get(&name, None, now).unwrap();
         assert_eq!(*rc_ips.iter().next().unwrap(), ips[0]);
     }
 
@@ -445,14 +985,14 @@ mod tests {
         ];
         let mut lru = DnsLru::new(1, TtlConfig::default());
 
-        lru.insert(name.clone(), ips_ttl, now);
+        lru.insert(name.clone(), None, ips_ttl, now);
 
         // still valid
-        let rc_ips = lru.get(&name, now + Duration::from_secs(1)).unwrap();
+        let rc_ips = lru.get(&name, None, now + Duration::from_secs(1)).unwrap();
         assert_eq!(*rc_ips.iter().next().unwrap(), ips[0]);
 
         // 2 should be one too far
-        let rc_ips = lru.get(&name, now + Duration::from_secs(2));
+        let rc_ips = lru.get(&name, None, now + Duration::from_secs(2));
         assert!(rc_ips.is_none());
     }
 
@@ -477,26 +1017,26 @@ mod tests {
             ..Default::default()
         };
         let mut lru = DnsLru::new(1, ttls);
-        lru.insert(name.clone(), ips_ttl, now);
+        lru.insert(name.clone(), None, ips_ttl, now);
 
         // still valid
-        let rc_ips = lru.get(&name, now + Duration::from_secs(1)).unwrap();
+        let rc_ips = lru.get(&name, None, now + Duration::from_secs(1)).unwrap();
         for (rc_ip, ip) in rc_ips.iter().zip(ips.iter()) {
             assert_eq!(rc_ip, ip, "after 1 second");
         }
 
-        let rc_ips = lru.get(&name, now + Duration::from_secs(2)).unwrap();
+        let rc_ips = lru.get(&name, None, now + Duration::from_secs(2)).unwrap();
         for (rc_ip, ip) in rc_ips.iter().zip(ips.iter()) {
             assert_eq!(rc_ip, ip, "after 2 seconds");
         }
 
-        let rc_ips = lru.get(&name, now + Duration::from_secs(3)).unwrap();
+        let rc_ips = lru.get(&name, None, now + Duration::from_secs(3)).unwrap();
         for (rc_ip, ip) in rc_ips.iter().zip(ips.iter()) {
             assert_eq!(rc_ip, ip, "after 3 seconds");
         }
 
         // after 4 seconds, the records should be invalid.
-        let rc_ips = lru.get(&name, now + Duration::from_secs(4));
+        let rc_ips = lru.get(&name, None, now + Duration::from_secs(4));
         assert!(rc_ips.is_none());
     }
 
@@ -521,21 +1061,363 @@ mod tests {
             ..Default::default()
         };
         let mut lru = DnsLru::new(1, ttls);
-        lru.insert(name.clone(), ips_ttl, now);
+        lru.insert(name.clone(), None, ips_ttl, now);
 
         // still valid
-        let rc_ips = lru.get(&name, now + Duration::from_secs(1)).unwrap();
+        let rc_ips = lru.get(&name, None, now + Duration::from_secs(1)).unwrap();
         for (rc_ip, ip) in rc_ips.iter().zip(ips.iter()) {
             assert_eq!(rc_ip, ip, "after 1 second");
         }
 
-        let rc_ips = lru.get(&name, now + Duration::from_secs(2)).unwrap();
+        let rc_ips = lru.get(&name, None, now + Duration::from_secs(2)).unwrap();
         for (rc_ip, ip) in rc_ips.iter().zip(ips.iter()) {
             assert_eq!(rc_ip, ip, "after 2 seconds");
         }
 
         // after 3 seconds, the records should be invalid.
-        let rc_ips = lru.get(&name, now + Duration::from_secs(3));
+        let rc_ips = lru.get(&name, None, now + Duration::from_secs(3));
         assert!(rc_ips.is_none());
     }
+
+    #[test]
+    fn test_get_stale() {
+        let now = Instant::now();
+        let name = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+        let ips_ttl = vec![(RData::A(Ipv4Addr::new(127, 0, 0, 1)), 1)];
+        let ips = vec![RData::A(Ipv4Addr::new(127, 0, 0, 1))];
+
+        // keep expired records for 60 seconds
+        let ttls = TtlConfig {
+            serve_stale: Some(Duration::from_secs(60)),
+            ..Default::default()
+        };
+        let mut lru = DnsLru::new(1, ttls);
+        lru.insert(name.clone(), None, ips_ttl, now);
+
+        // expired records are not returned by get, but are kept
+        let expired = now + Duration::from_secs(2);
+        assert!(lru.get(&name, None, expired).is_none());
+
+        let stale = lru.get_stale(&name, None, expired).unwrap();
+        assert_eq!(stale.rdatas(), &ips[..]);
+        assert_eq!(
+            stale.valid_until(),
+            expired + Duration::from_secs(u64::from(STALE_TTL))
+        );
+
+        // once served stale, get returns the stale records until they expire again
+        let rc_ips = lru
+            .get(&name, None, expired + Duration::from_secs(1))
+            .unwrap();
+        assert_eq!(rc_ips.rdatas(), &ips[..]);
+        assert_eq!(rc_ips.valid_until(), stale.valid_until());
+        assert!(lru
+            .get(
+                &name,
+                None,
+                expired + Duration::from_secs(u64::from(STALE_TTL) + 1)
+            )
+            .is_none());
+
+        // after the stale window the records are dropped
+        let past_window = now + Duration::from_secs(62);
+        assert!(lru.get_stale(&name, None, past_window).is_none());
+        assert!(lru.get(&name, None, past_window).is_none());
+        assert!(lru.get_stale(&name, None, expired).is_none());
+    }
+
+    #[test]
+    fn test_get_stale_disabled() {
+        let now = Instant::now();
+        let name = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+        let ips_ttl = vec![(RData::A(Ipv4Addr::new(127, 0, 0, 1)), 1)];
+        let mut lru = DnsLru::new(1, TtlConfig::default());
+        lru.insert(name.clone(), None, ips_ttl, now);
+
+        assert!(lru.get_stale(&name, None, now).is_none());
+
+        let expired = now + Duration::from_secs(2);
+        assert!(lru.get(&name, None, expired).is_none());
+        assert!(lru.get_stale(&name, None, expired).is_none());
+    }
+
+    #[test]
+    fn test_negative_not_stale() {
+        let now = Instant::now();
+        let name = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+        let ttls = TtlConfig {
+            serve_stale: Some(Duration::from_secs(60)),
+            ..Default::default()
+        };
+        let mut lru = DnsLru::new(1, ttls);
+        lru.negative(name.clone(), None, 1, now);
+
+        let expired = now + Duration::from_secs(2);
+        assert!(lru.get_stale(&name, None, expired).is_none());
+        assert!(lru.get(&name, None, expired).is_none());
+    }
+
+    #[test]
+    fn test_should_prefetch() {
+        let now = Instant::now();
+        let name = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+        let ips_ttl = vec![(RData::A(Ipv4Addr::new(127, 0, 0, 1)), 100)];
+
+        // prefetch in the last 10% of the TTL
+        let ttls = TtlConfig {
+            prefetch_threshold: Some(10),
+            ..Default::default()
+        };
+        let mut lru = DnsLru::new(1, ttls);
+        lru.insert(name.clone(), None, ips_ttl.clone(), now);
+
+        assert!(!lru.should_prefetch(&name, None, now + Duration::from_secs(50)));
+        assert!(!lru.should_prefetch(&name, None, now + Duration::from_secs(89)));
+        assert!(lru.should_prefetch(&name, None, now + Duration::from_secs(95)));
+        // only once per inserted lookup
+        assert!(!lru.should_prefetch(&name, None, now + Duration::from_secs(96)));
+
+        lru.insert(name.clone(), None, ips_ttl, now + Duration::from_secs(96));
+        assert!(!lru.should_prefetch(&name, None, now + Duration::from_secs(100)));
+        assert!(lru.should_prefetch(&name, None, now + Duration::from_secs(190)));
+
+        // expired lookups are not prefetched
+        assert!(!lru.should_prefetch(&name, None, now + Duration::from_secs(300)));
+    }
+
+    #[test]
+    fn test_should_prefetch_disabled() {
+        let now = Instant::now();
+        let name = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+        let ips_ttl = vec![(RData::A(Ipv4Addr::new(127, 0, 0, 1)), 100)];
+        let mut lru = DnsLru::new(1, TtlConfig::default());
+        lru.insert(name.clone(), None, ips_ttl, now);
+
+        assert!(!lru.should_prefetch(&name, None, now + Duration::from_secs(99)));
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let now = Instant::now();
+        // snapshots are taken with a precision of seconds
+        let system_now = UNIX_EPOCH + Duration::from_secs(1_500_000_000);
+        let www = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+        let alias = Query::query(Name::from_str("alias.example.com.").unwrap(), RecordType::A);
+        let nx = Query::query(Name::from_str("nx.example.com.").unwrap(), RecordType::A);
+        let expired = Query::query(Name::from_str("old.example.com.").unwrap(), RecordType::A);
+        let ips = vec![
+            RData::A(Ipv4Addr::new(127, 0, 0, 1)),
+            RData::A(Ipv4Addr::new(127, 0, 0, 2)),
+        ];
+
+        let mut lru = DnsLru::new(4, TtlConfig::default());
+        let lookup = lru.insert(
+            www.clone(),
+            None,
+            ips.iter().cloned().map(|ip| (ip, 300)).collect(),
+            now,
+        );
+        lru.duplicate(alias.clone(), None, lookup, 200, now);
+        lru.negative(nx.clone(), None, 100, now);
+        lru.insert(
+            expired.clone(),
+            None,
+            vec![(ips[0].clone(), 1)],
+            now - Duration::from_secs(2),
+        );
+
+        let mut snapshot = Vec::new();
+        assert_eq!(
+            lru.snapshot(now).save(&mut snapshot, system_now).unwrap(),
+            3
+        );
+
+        // restored 60 seconds later
+        let later = now + Duration::from_secs(60);
+        let mut restored = DnsLru::new(4, TtlConfig::default());
+        let count = restored
+            .load(&mut &snapshot[..], later, system_now + Duration::from_secs(60))
+            .unwrap();
+        assert_eq!(count, 3);
+
+        let lookup = restored.get(&www, None, later).unwrap();
+        assert_eq!(lookup.rdatas(), &ips[..]);
+        assert_eq!(lookup.query(), &www);
+        assert_eq!(lookup.valid_until(), later + Duration::from_secs(240));
+
+        // the lookup of a duplicated entry keeps its query
+        let lookup = restored.get(&alias, None, later).unwrap();
+        assert_eq!(lookup.rdatas(), &ips[..]);
+        assert_eq!(lookup.query(), &www);
+        assert_eq!(lookup.valid_until(), later + Duration::from_secs(140));
+
+        // negative entries are restored, but return no lookup
+        assert!(restored.get(&nx, None, later).is_none());
+        assert_eq!(
+            restored.cache.get_mut(&nx).unwrap()[0].valid_until,
+            later + Duration::from_secs(40)
+        );
+
+        assert!(restored.cache.get_mut(&expired).is_none());
+    }
+
+    #[test]
+    fn test_load_discards_expired_and_limits_ttl() {
+        let now = Instant::now();
+        // snapshots are taken with a precision of seconds
+        let system_now = UNIX_EPOCH + Duration::from_secs(1_500_000_000);
+        let www = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+        let nx = Query::query(Name::from_str("nx.example.com.").unwrap(), RecordType::A);
+
+        let mut lru = DnsLru::new(2, TtlConfig::default());
+        lru.insert(
+            www.clone(),
+            None,
+            vec![(RData::A(Ipv4Addr::new(127, 0, 0, 1)), 300)],
+            now,
+        );
+        lru.negative(nx.clone(), None, 100, now);
+
+        let mut snapshot = Vec::new();
+        lru.snapshot(now).save(&mut snapshot, system_now).unwrap();
+
+        // the negative entry expired in the meantime, the positive one is limited by the max TTL
+        let ttls = TtlConfig {
+            positive_max_ttl: Some(Duration::from_secs(60)),
+            ..Default::default()
+        };
+        let mut restored = DnsLru::new(2, ttls);
+        let count = restored
+            .load(&mut &snapshot[..], now, system_now + Duration::from_secs(120))
+            .unwrap();
+        assert_eq!(count, 1);
+
+        let lookup = restored.get(&www, None, now).unwrap();
+        assert_eq!(lookup.valid_until(), now + Duration::from_secs(60));
+        assert!(restored.cache.get_mut(&nx).is_none());
+    }
+
+    #[test]
+    fn test_load_invalid() {
+        let now = Instant::now();
+        let mut lru = DnsLru::new(1, TtlConfig::default());
+
+        assert!(lru.load(&mut &b"garbage"[..], now, SystemTime::now()).is_err());
+        assert!(lru
+            .load(&mut &b"TDNSLRU\0\x02\0\0\0\0\0\0\0\0"[..], now, SystemTime::now())
+            .is_err());
+
+        // truncated entries
+        let mut snapshot = Vec::new();
+        lru.insert(
+            Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A),
+            None,
+            vec![(RData::A(Ipv4Addr::new(127, 0, 0, 1)), 300)],
+            now,
+        );
+        lru.snapshot(now)
+            .save(&mut snapshot, SystemTime::now())
+            .unwrap();
+        let truncated = &snapshot[..snapshot.len() - 2];
+        assert!(DnsLru::new(1, TtlConfig::default())
+            .load(&mut &truncated[..], now, SystemTime::now())
+            .is_err());
+    }
+
+    #[test]
+    fn test_client_subnet_scope() {
+        let now = Instant::now();
+        let name = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+        let ips_ttl = vec![(RData::A(Ipv4Addr::new(127, 0, 0, 1)), 60)];
+        let subnet =
+            |a, b, prefix| ClientSubnet::new(IpAddr::V4(Ipv4Addr::new(192, a, b, 1)), prefix, 0);
+
+        let mut lru = DnsLru::new(1, TtlConfig::default());
+        let scope = answer_scope(
+            Some(&subnet(0, 2, 24)),
+            Some(&subnet(0, 2, 24).with_scope_prefix(16)),
+        );
+        assert_eq!(scope, Some(subnet(0, 0, 16)));
+        lru.insert(name.clone(), scope, ips_ttl.clone(), now);
+
+        assert!(lru.get(&name, Some(&subnet(0, 2, 24)), now).is_some());
+        assert!(lru.get(&name, Some(&subnet(0, 3, 24)), now).is_some());
+        assert!(lru.get(&name, Some(&subnet(1, 2, 24)), now).is_none());
+        assert!(lru.get(&name, Some(&subnet(0, 2, 8)), now).is_none());
+        assert!(lru.get(&name, None, now).is_none());
+
+        // scoped entries are not saved
+        let mut snapshot = Vec::new();
+        assert_eq!(
+            lru.snapshot(now)
+                .save(&mut snapshot, SystemTime::now())
+                .unwrap(),
+            0
+        );
+
+        // answers with a scope of 0 are valid for all clients
+        assert_eq!(
+            answer_scope(Some(&subnet(0, 2, 24)), Some(&subnet(0, 2, 24))),
+            None
+        );
+        assert_eq!(
+            answer_scope(None, Some(&subnet(0, 2, 24).with_scope_prefix(24))),
+            None
+        );
+        lru.insert(name.clone(), None, ips_ttl, now);
+        assert!(lru.get(&name, None, now).is_some());
+        assert!(lru.get(&name, Some(&subnet(0, 2, 24)), now).is_some());
+    }
+
+    #[test]
+    fn test_client_subnet_answers() {
+        let now = Instant::now();
+        let name = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+        let ip_ttl = |last| vec![(RData::A(Ipv4Addr::new(127, 0, 0, last)), 100)];
+        let subnet =
+            |a, b, prefix| ClientSubnet::new(IpAddr::V4(Ipv4Addr::new(192, a, b, 1)), prefix, 0);
+        let get = |lru: &mut DnsLru, client_subnet: Option<&ClientSubnet>| {
+            lru.get(&name, client_subnet, now)
+                .map(|lookup| lookup.iter().cloned().collect::<Vec<_>>())
+        };
+
+        let ttls = TtlConfig {
+            prefetch_threshold: Some(10),
+            ..Default::default()
+        };
+        let mut lru = DnsLru::new(1, ttls);
+        lru.insert(name.clone(), Some(subnet(0, 2, 24)), ip_ttl(1), now);
+        lru.insert(name.clone(), Some(subnet(0, 3, 24)), ip_ttl(2), now);
+        lru.insert(name.clone(), None, ip_ttl(3), now);
+
+        // the answers for other subnets are kept, the most specific one is returned
+        assert_eq!(
+            get(&mut lru, Some(&subnet(0, 2, 24))),
+            Some(vec![RData::A(Ipv4Addr::new(127, 0, 0, 1))])
+        );
+        assert_eq!(
+            get(&mut lru, Some(&subnet(0, 3, 24))),
+            Some(vec![RData::A(Ipv4Addr::new(127, 0, 0, 2))])
+        );
+        assert_eq!(
+            get(&mut lru, Some(&subnet(0, 4, 24))),
+            Some(vec![RData::A(Ipv4Addr::new(127, 0, 0, 3))])
+        );
+        assert_eq!(lru.stats().entries, 3);
+
+        // the answer for the same subnet is replaced
+        lru.insert(name.clone(), Some(subnet(0, 2, 24)), ip_ttl(4), now);
+        assert_eq!(
+            get(&mut lru, Some(&subnet(0, 2, 24))),
+            Some(vec![RData::A(Ipv4Addr::new(127, 0, 0, 4))])
+        );
+        assert_eq!(lru.stats().entries, 3);
+
+        // prefetching is decided per answer
+        let later = now + Duration::from_secs(95);
+        assert!(lru.should_prefetch(&name, Some(&subnet(0, 2, 24)), later));
+        assert!(!lru.should_prefetch(&name, Some(&subnet(0, 2, 24)), later));
+        assert!(lru.should_prefetch(&name, Some(&subnet(0, 3, 24)), later));
+        assert!(lru.should_prefetch(&name, None, later));
+    }
 }
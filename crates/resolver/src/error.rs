@@ -10,7 +10,8 @@
 use failure::{Backtrace, Context, Fail};
 use std::{fmt, io, sync, time::Instant};
 use proto::error::{ProtoError, ProtoErrorKind};
-use proto::op::Query;
+use proto::op::{Query, ResponseCode};
+use proto::rr::Record;
 
 /// An alias for results returned by functions of this crate
 pub type ResolveResult<T> = ::std::result::Result<T, ResolveError>;
@@ -69,10 +70,19 @@ impl Clone for ResolveErrorKind {
     }
 }
 
+/// The response code and the SOA record of a negative response, see RFC 2308
+#[derive(Clone, Debug)]
+struct NegativeResponse {
+    response_code: ResponseCode,
+    soa: Option<Record>,
+}
+
 /// The error type for errors that get returned in the crate
 #[derive(Debug)]
 pub struct ResolveError {
     inner: Context<ResolveErrorKind>,
+    /// set for `NoRecordsFound` errors from a response of the name servers
+    negative: Option<Box<NegativeResponse>>,
 }
 
 impl ResolveError {
@@ -80,12 +90,40 @@ impl ResolveError {
     pub fn kind(&self) -> &ResolveErrorKind {
         self.inner.get_context()
     }
+
+    /// Returns the response code of the negative response for a `NoRecordsFound` error
+    ///
+    /// `NXDomain` if the name does not exist, `NoError` if it has no records of the type. `None` if the error is not
+    ///  from a response of the name servers, e.g. for special use names like `localhost`.
+    pub fn response_code(&self) -> Option<ResponseCode> {
+        self.negative
+            .as_ref()
+            .map(|negative| negative.response_code)
+    }
+
+    /// Returns the SOA record of the authority section of the negative response for a `NoRecordsFound` error
+    pub fn soa(&self) -> Option<&Record> {
+        self.negative
+            .as_ref()
+            .and_then(|negative| negative.soa.as_ref())
+    }
+
+    /// Returns the error with the response code and the SOA record of the negative response it is from
+    pub(crate) fn with_negative(
+        mut self,
+        response_code: ResponseCode,
+        soa: Option<Record>,
+    ) -> Self {
+        self.negative = Some(Box::new(NegativeResponse { response_code, soa }));
+        self
+    }
 }
 
 impl Clone for ResolveError {
     fn clone(&self) -> Self {
         ResolveError {
             inner: Context::new(self.inner.get_context().clone()),
+            negative: self.negative.clone(),
         }
     }
 }
@@ -110,13 +148,17 @@ impl From<ResolveErrorKind> for ResolveError {
     fn from(kind: ResolveErrorKind) -> ResolveError {
         ResolveError {
             inner: Context::new(kind),
+            negative: None,
         }
     }
 }
 
 impl From<Context<ResolveErrorKind>> for ResolveError {
     fn from(inner: Context<ResolveErrorKind>) -> ResolveError {
-        ResolveError { inner }
+        ResolveError {
+            inner,
+            negative: None,
+        }
     }
 }
 
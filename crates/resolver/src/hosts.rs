@@ -9,23 +9,24 @@ use std::sync::Arc;
 
 use lookup::Lookup;
 use proto::op::Query;
-#[cfg(any(unix, windows))]
-use proto::rr::RData;
-use proto::rr::{Name, RecordType};
+use proto::rr::{Name, RData, Record, RecordType};
 
 #[derive(Debug, Default)]
-struct LookupType {
-    /// represents the A record type
-    a: Option<Lookup>,
-    /// represents the AAAA record type
-    aaaa: Option<Lookup>,
+struct HostEntry {
+    /// the records of the name, by their type
+    records: HashMap<RecordType, Lookup>,
+    /// the canonical name, if the name is an alias of it
+    canonical: Option<Name>,
 }
 
 /// Configuration for the local hosts file
+///
+/// The first name of each line of a hosts file is the canonical name of the address, the following names are
+///  aliases of it. Addresses are also answered for PTR queries, with the canonical name.
 #[derive(Debug, Default)]
 pub struct Hosts {
     /// Name -> RDatas map
-    by_name: HashMap<Name, LookupType>,
+    by_name: HashMap<Name, HostEntry>,
 }
 
 impl Hosts {
@@ -36,67 +37,181 @@ impl Hosts {
         read_hosts_conf(hosts_path()).unwrap_or_default()
     }
 
-    /// lookup_static_host looks up the addresses
-    /// for the given host from the system hosts file.
+    /// lookup_static_host looks up the records
+    /// for the given name and type from the hosts files and static records.
+    ///
+    /// Lookups of aliases return the records of the canonical name, CNAME lookups of aliases the canonical name.
     pub fn lookup_static_host(&self, query: &Query) -> Option<Lookup> {
-        if !self.by_name.is_empty() {
-            if let Some(val) = self.by_name.get(query.name()) {
-                let result = match query.query_type() {
-                    RecordType::A => val.a.clone(),
-                    RecordType::AAAA => val.aaaa.clone(),
-                    _ => None,
-                };
-
-                return result;
-            }
+        let entry = self.by_name.get(query.name())?;
+        if let Some(lookup) = entry.records.get(&query.query_type()) {
+            return Some(lookup.clone());
         }
-        None
+
+        let canonical = entry.canonical.as_ref()?;
+        if query.query_type() == RecordType::CNAME {
+            let rdata = RData::CNAME(canonical.clone());
+            let lookup = Lookup::new_with_max_ttl(query.clone(), Arc::new(vec![rdata]));
+            return Some(lookup);
+        }
+
+        self.by_name
+            .get(canonical)
+            .and_then(|entry| entry.records.get(&query.query_type()))
+            .cloned()
     }
 
     /// Insert a new Lookup for the associated `Name` and `RecordType`
+    ///
+    /// The records are appended to those already present for the name and type.
     pub fn insert(&mut self, name: Name, record_type: RecordType, lookup: Lookup) {
-        assert!(record_type == RecordType::A || record_type == RecordType::AAAA);
-
-        let lookup_type = self
+        let entry = self
             .by_name
             .entry(name.clone())
-            .or_insert_with(LookupType::default);
-
-        let new_lookup = {
-            let mut old_lookup = match record_type {
-                RecordType::A => lookup_type.a.get_or_insert_with(|| {
-                    let query = Query::query(name.clone(), record_type);
-                    Lookup::new_with_max_ttl(query, Arc::new(vec![]))
-                }),
-                RecordType::AAAA => lookup_type.aaaa.get_or_insert_with(|| {
-                    let query = Query::query(name.clone(), record_type);
-                    Lookup::new_with_max_ttl(query, Arc::new(vec![]))
-                }),
-                _ => {
-                    warn!("unsupported IP type from Hosts file: {:#?}", record_type);
-                    return;
-                }
-            };
+            .or_insert_with(HostEntry::default);
 
-            old_lookup.append(lookup)
+        let new_lookup = match entry.records.get(&record_type) {
+            Some(old_lookup) => old_lookup.append(lookup),
+            None => {
+                let query = Query::query(name, record_type);
+                Lookup::new_with_max_ttl(query, Arc::new(vec![])).append(lookup)
+            }
         };
 
         // replace the appended version
-        match record_type {
-            RecordType::A => lookup_type.a = Some(new_lookup),
-            RecordType::AAAA => lookup_type.aaaa = Some(new_lookup),
-            _ => warn!("unsupported IP type from Hosts file"),
+        entry.records.insert(record_type, new_lookup);
+    }
+
+    /// Makes `alias` an alias of `canonical`, unless it already is an alias of another name
+    ///
+    /// Lookups of the alias return the records of the canonical name, unless the alias has records of the type itself.
+    pub fn insert_alias(&mut self, alias: Name, canonical: Name) {
+        let entry = self.by_name.entry(alias).or_insert_with(HostEntry::default);
+        if entry.canonical.is_none() {
+            entry.canonical = Some(canonical);
+        }
+    }
+
+    /// Insert a static record, of any type
+    ///
+    /// CNAME records make their name an alias, see `insert_alias`. The TTL of the record is ignored, static records
+    ///  never expire.
+    pub fn insert_record(&mut self, record: &Record) {
+        if let RData::CNAME(ref canonical) = *record.rdata() {
+            self.insert_alias(record.name().clone(), canonical.clone());
+            return;
+        }
+
+        let query = Query::query(record.name().clone(), record.rr_type());
+        let lookup = Lookup::new_with_max_ttl(query, Arc::new(vec![record.rdata().clone()]));
+        self.insert(record.name().clone(), record.rr_type(), lookup);
+    }
+
+    /// Insert the address for the names of a line of a hosts file, the first is the canonical name
+    fn insert_host(&mut self, addr: &RData, names: &[Name]) {
+        let (canonical, aliases) = match names.split_first() {
+            Some(names) => names,
+            None => return,
+        };
+        let record_type = match *addr {
+            RData::A(..) => RecordType::A,
+            RData::AAAA(..) => RecordType::AAAA,
+            _ => {
+                warn!("unsupported IP type from Hosts file: {:#?}", addr);
+                return;
+            }
+        };
+
+        let query = Query::query(canonical.clone(), record_type);
+        let lookup = Lookup::new_with_max_ttl(query, Arc::new(vec![addr.clone()]));
+        self.insert(canonical.clone(), record_type, lookup);
+
+        for alias in aliases {
+            self.insert_alias(alias.clone(), canonical.clone());
+        }
+
+        // the reverse lookup is answered with the first canonical name of the address
+        if let Some(ip) = addr.to_ip_addr() {
+            let reverse = Name::from(ip);
+            let has_ptr = self
+                .by_name
+                .get(&reverse)
+                .map_or(false, |entry| entry.records.contains_key(&RecordType::PTR));
+            if !has_ptr {
+                let query = Query::query(reverse.clone(), RecordType::PTR);
+                let rdata = RData::PTR(canonical.clone());
+                let lookup = Lookup::new_with_max_ttl(query, Arc::new(vec![rdata]));
+                self.insert(reverse, RecordType::PTR, lookup);
+            }
         }
     }
+
+    /// Reads the entries of the hosts file at `path` into this configuration
+    ///
+    /// This allows to combine several hosts files, for names and addresses in more than one file the first
+    ///  canonical name is used.
+    #[cfg(any(unix, windows))]
+    pub fn read_hosts_file<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        use std::fs::File;
+        use std::io::{BufRead, BufReader};
+
+        use proto::rr::domain::TryParseIp;
+
+        // lines in the file should have the form `addr host1 host2 host3 ...`
+        // line starts with `#` will be regarded with comments and ignored,
+        // also empty line also will be ignored,
+        // if line only include `addr` without `host` will be ignored,
+        // file will parsed to map in the form `Name -> LookUp`.
+        let file = File::open(path)?;
+
+        for line in BufReader::new(file).lines() {
+            // Remove comments from the line
+            let line = line
+                .as_ref()
+                .map(|line| line.split('#').next().unwrap().trim())
+                .unwrap_or_default();
+            if line.is_empty() {
+                continue;
+            }
+
+            let fields: Vec<_> = line.split_whitespace().collect();
+            if fields.len() < 2 {
+                continue;
+            }
+            let addr = if let Some(a) = fields[0].try_parse_ip() {
+                a
+            } else {
+                warn!("could not parse an IP from hosts file");
+                continue;
+            };
+
+            let names = fields
+                .iter()
+                .skip(1)
+                .filter_map(|domain| Name::from_str(&domain.to_lowercase()).ok())
+                .collect::<Vec<_>>();
+            self.insert_host(&addr, &names);
+        }
+
+        Ok(())
+    }
+
+    /// Only Windows and Unix-like hosts files are supported
+    #[cfg(not(any(unix, windows)))]
+    pub fn read_hosts_file<P: AsRef<Path>>(&mut self, _path: P) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "Only Windows or Unix-like hosts file is supported".to_string(),
+        ))
+    }
 }
 
 #[cfg(unix)]
-fn hosts_path() -> &'static str {
+pub(crate) fn hosts_path() -> &'static str {
     "/etc/hosts"
 }
 
 #[cfg(windows)]
-fn hosts_path() -> std::path::PathBuf {
+pub(crate) fn hosts_path() -> std::path::PathBuf {
     let system_root =
         std::env::var_os("SystemRoot").expect("Environtment variable SystemRoot not found");
     let system_root = Path::new(&system_root);
@@ -104,86 +219,20 @@ fn hosts_path() -> std::path::PathBuf {
 }
 
 /// parse configuration from `path`
-#[cfg(any(unix, windows))]
 pub fn read_hosts_conf<P: AsRef<Path>>(path: P) -> io::Result<Hosts> {
-    use std::fs::File;
-    use std::io::{BufRead, BufReader};
-
-    use proto::rr::domain::TryParseIp;
-
-    let mut hosts = Hosts {
-        by_name: HashMap::new(),
-    };
-
-    // lines in the file should have the form `addr host1 host2 host3 ...`
-    // line starts with `#` will be regarded with comments and ignored,
-    // also empty line also will be ignored,
-    // if line only include `addr` without `host` will be ignored,
-    // file will parsed to map in the form `Name -> LookUp`.
-    let file = File::open(path)?;
-
-    for line in BufReader::new(file).lines() {
-        // Remove comments from the line
-        let line = line
-            .as_ref()
-            .map(|line| line.split('#').next().unwrap().trim())
-            .unwrap_or_default();
-        if line.is_empty() {
-            continue;
-        }
-
-        let fields: Vec<_> = line.split_whitespace().collect();
-        if fields.len() < 2 {
-            continue;
-        }
-        let addr = if let Some(a) = fields[0].try_parse_ip() {
-            a
-        } else {
-            warn!("could not parse an IP from hosts file");
-            continue;
-        };
-
-        for domain in fields.iter().skip(1).map(|domain| domain.to_lowercase()) {
-            if let Ok(name) = Name::from_str(&domain) {
-                match addr {
-                    RData::A(..) => {
-                        let query = Query::query(name.clone(), RecordType::A);
-                        let lookup = Lookup::new_with_max_ttl(query, Arc::new(vec![addr.clone()]));
-                        hosts.insert(name.clone(), RecordType::A, lookup);
-                    }
-                    RData::AAAA(..) => {
-                        let query = Query::query(name.clone(), RecordType::AAAA);
-                        let lookup = Lookup::new_with_max_ttl(query, Arc::new(vec![addr.clone()]));
-                        hosts.insert(name.clone(), RecordType::AAAA, lookup);
-                    }
-                    _ => {
-                        warn!("unsupported IP type from Hosts file: {:#?}", addr);
-                        continue;
-                    }
-                };
-
-                // TODO: insert reverse lookup as well.
-            };
-        }
-    }
-
+    let mut hosts = Hosts::default();
+    hosts.read_hosts_file(path)?;
     Ok(hosts)
 }
 
-#[cfg(not(any(unix, windows)))]
-pub fn read_hosts_conf<P: AsRef<Path>>(_path: P) -> io::Result<Hosts> {
-    Err(io::Error::new(
-        io::ErrorKind::Other,
-        "Only Windows or Unix-like hosts file is supported".to_string(),
-    ))
-}
-
 #[cfg(any(unix, windows))]
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::env;
-    use std::net::{Ipv4Addr, Ipv6Addr};
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    use proto::rr::rdata::TXT;
 
     fn tests_dir() -> String {
         let server_path = env::var("TDNS_SERVER_SRC_ROOT").unwrap_or_else(|_| ".".to_owned());
@@ -253,4 +302,77 @@ mod tests {
             .collect::<Vec<RData>>();
         assert_eq!(rdatas, vec![RData::A(Ipv4Addr::new(10, 0, 1, 111))]);
     }
+
+    fn rdatas(hosts: &Hosts, name: &str, record_type: RecordType) -> Vec<RData> {
+        let name = Name::from_str(name).unwrap();
+        hosts
+            .lookup_static_host(&Query::query(name, record_type))
+            .map(|lookup| lookup.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn test_reverse_and_aliases() {
+        let path = format!("{}/hosts", tests_dir());
+        let hosts = read_hosts_conf(&path).unwrap();
+
+        let reverse = Name::from(IpAddr::V4(Ipv4Addr::new(10, 0, 1, 111))).to_string();
+        assert_eq!(
+            rdatas(&hosts, &reverse, RecordType::PTR),
+            vec![RData::PTR(Name::from_str("a.example.com").unwrap())]
+        );
+
+        // only aliases have a canonical name
+        assert_eq!(
+            rdatas(&hosts, "b.example.com", RecordType::CNAME),
+            vec![RData::CNAME(Name::from_str("a.example.com").unwrap())]
+        );
+        assert!(rdatas(&hosts, "a.example.com", RecordType::CNAME).is_empty());
+    }
+
+    #[test]
+    fn test_multiple_files_and_records() {
+        use std::{env, fs, process};
+
+        let path = env::temp_dir().join(format!("trust-dns-resolver-hosts-test-{}", process::id()));
+        fs::write(&path, "10.0.1.111 c.example.com\n10.0.2.1 a.example.com\n").unwrap();
+
+        let mut hosts = read_hosts_conf(format!("{}/hosts", tests_dir())).unwrap();
+        let result = hosts.read_hosts_file(&path);
+        fs::remove_file(&path).unwrap();
+        result.unwrap();
+
+        // the first canonical name of an address is kept, addresses of the same name are combined
+        let reverse = Name::from(IpAddr::V4(Ipv4Addr::new(10, 0, 1, 111))).to_string();
+        assert_eq!(
+            rdatas(&hosts, &reverse, RecordType::PTR),
+            vec![RData::PTR(Name::from_str("a.example.com").unwrap())]
+        );
+        assert_eq!(
+            rdatas(&hosts, "b.example.com", RecordType::A),
+            vec![
+                RData::A(Ipv4Addr::new(10, 0, 1, 111)),
+                RData::A(Ipv4Addr::new(10, 0, 2, 1)),
+            ]
+        );
+
+        let name = Name::from_str("service.example.com").unwrap();
+        let txt = RData::TXT(TXT::new(vec!["static".to_string()]));
+        hosts.insert_record(&Record::from_rdata(
+            name,
+            3600,
+            RecordType::TXT,
+            txt.clone(),
+        ));
+        hosts.insert_record(&Record::from_rdata(
+            Name::from_str("alias.example.com").unwrap(),
+            3600,
+            RecordType::CNAME,
+            RData::CNAME(Name::from_str("service.example.com").unwrap()),
+        ));
+        assert_eq!(
+            rdatas(&hosts, "alias.example.com", RecordType::TXT),
+            vec![txt]
+        );
+    }
 }
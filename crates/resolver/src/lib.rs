@@ -194,6 +194,7 @@ extern crate lazy_static;
 #[macro_use]
 extern crate log;
 extern crate lru_cache;
+extern crate regex;
 extern crate resolv_conf;
 #[cfg(feature = "serde-config")]
 #[macro_use]
@@ -204,6 +205,7 @@ extern crate smallvec;
 #[cfg(any(feature = "tokio", test))]
 extern crate tokio;
 extern crate tokio_executor;
+extern crate tokio_timer;
 #[cfg(feature = "dns-over-https")]
 extern crate trust_dns_https;
 #[cfg(feature = "dns-over-native-tls")]
@@ -214,8 +216,13 @@ pub extern crate trust_dns_proto as proto;
 #[cfg(feature = "dns-over-rustls")]
 extern crate trust_dns_rustls;
 
+pub mod addr_selection;
 mod async_resolver;
+pub mod cache;
 pub mod config;
+pub mod dane;
+pub mod ddds;
+pub mod dns64;
 mod dns_lru;
 pub mod dns_sd;
 pub mod error;
@@ -227,6 +234,7 @@ pub mod lookup_ip;
 pub mod lookup_state;
 #[doc(hidden)]
 pub mod name_server;
+pub mod recursor;
 #[cfg(any(feature = "tokio", test))]
 mod resolver;
 pub mod system_conf;
@@ -237,7 +245,10 @@ mod tls;
 pub use self::proto::rr::{IntoName, Name, TryParseIp};
 
 pub use async_resolver::{AsyncResolver, Background, BackgroundLookup, BackgroundLookupIp};
+pub use cache::ResolverCache;
 pub use hosts::Hosts;
+pub use name_server::ServerStats;
+pub use recursor::Recursor;
 #[cfg(any(feature = "tokio", test))]
 pub use resolver::Resolver;
 
@@ -8,17 +8,18 @@
 //! Lookup result from a resolution of ipv4 and ipv6 records with a Resolver.
 
 use std::cmp::min;
-use std::net::{Ipv4Addr, Ipv6Addr};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::slice::Iter;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
+use futures::future::Loop;
 use futures::{future, Async, Future, Poll};
 
 use proto::error::ProtoError;
 use proto::op::Query;
 use proto::rr::rdata;
-use proto::rr::{Name, RData, RecordType};
+use proto::rr::{Name, RData, Record, RecordType};
 use proto::xfer::{DnsRequest, DnsRequestOptions, DnsResponse};
 #[cfg(feature = "dnssec")]
 use proto::SecureDnsHandle;
@@ -26,9 +27,12 @@ use proto::{DnsHandle, RetryDnsHandle};
 
 use dns_lru::MAX_TTL;
 use error::*;
-use lookup_ip::LookupIpIter;
+use hosts::Hosts;
+use lookup_ip::{hosts_lookup, LookupIp, LookupIpIter};
 use lookup_state::CachingClient;
-use name_server::{ConnectionHandle, ConnectionProvider, NameServerPool, StandardConnection};
+use name_server::{
+    ConnectionHandle, ConnectionProvider, ForwardingHandle, NameServerPool, StandardConnection,
+};
 
 /// Result of a DNS query when querying for any record type supported by the Trust-DNS Proto library.
 ///
@@ -36,7 +40,7 @@ use name_server::{ConnectionHandle, ConnectionProvider, NameServerPool, Standard
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Lookup {
     query: Query,
-    rdatas: Arc<Vec<RData>>,
+    records: Arc<Vec<Record>>,
     valid_until: Instant,
 }
 
@@ -49,18 +53,42 @@ impl Lookup {
     /// Return new instance with given rdatas and the maximum TTL.
     pub fn new_with_max_ttl(query: Query, rdatas: Arc<Vec<RData>>) -> Self {
         let valid_until = Instant::now() + Duration::from_secs(u64::from(MAX_TTL));
-        Lookup {
-            query,
-            rdatas,
-            valid_until,
-        }
+        Self::new_with_deadline(query, rdatas, valid_until)
     }
 
     /// Return a new instance with the given rdatas and deadline.
+    ///
+    /// The records are owned by the name of the query.
     pub fn new_with_deadline(query: Query, rdatas: Arc<Vec<RData>>, valid_until: Instant) -> Self {
+        let now = Instant::now();
+        let ttl = if valid_until > now {
+            (valid_until - now)
+                .as_secs()
+                .min(u64::from(u32::max_value())) as u32
+        } else {
+            0
+        };
+        let records = rdatas
+            .iter()
+            .map(|rdata| {
+                Record::from_rdata(
+                    query.name().clone(),
+                    ttl,
+                    rdata.to_record_type(),
+                    rdata.clone(),
+                )
+            }).collect();
+
+        Self::new_with_records(query, Arc::new(records), valid_until)
+    }
+
+    /// Return a new instance with the given records and deadline.
+    ///
+    /// The records are those of the answer, preceded by the CNAME records which lead to the name of the answer.
+    pub fn new_with_records(query: Query, records: Arc<Vec<Record>>, valid_until: Instant) -> Self {
         Lookup {
             query,
-            rdatas,
+            records,
             valid_until,
         }
     }
@@ -71,8 +99,20 @@ impl Lookup {
     }
 
     /// Returns a borrowed iterator of the returned IPs
+    ///
+    /// The CNAME records which lead to the name of the answer are skipped, unless CNAME or ANY records were queried.
     pub fn iter(&self) -> LookupIter {
-        LookupIter(self.rdatas.iter())
+        let query_type = self.query.query_type();
+        LookupIter {
+            records: self.records.iter(),
+            skip_cnames: !query_type.is_cname() && !query_type.is_any(),
+        }
+    }
+
+    /// Returns the records of the answer, with their owner names, including the CNAME records which lead to the
+    ///  name of the answer.
+    pub fn records(&self) -> &[Record] {
+        self.records.as_ref()
     }
 
     /// Returns the `Instant` at which this `Lookup` is no longer valid.
@@ -81,38 +121,64 @@ impl Lookup {
     }
 
     pub(crate) fn is_empty(&self) -> bool {
-        self.rdatas.is_empty()
+        self.iter().next().is_none()
     }
 
     pub(crate) fn len(&self) -> usize {
-        self.rdatas.len()
+        self.iter().count()
+    }
+
+    /// Returns the same records with a different deadline
+    pub(crate) fn with_valid_until(&self, valid_until: Instant) -> Self {
+        Lookup {
+            query: self.query.clone(),
+            records: Arc::clone(&self.records),
+            valid_until,
+        }
     }
 
     #[cfg(test)]
-    pub fn rdatas(&self) -> &[RData] {
-        self.rdatas.as_ref()
+    pub fn rdatas(&self) -> Vec<RData> {
+        self.iter().cloned().collect()
+    }
+
+    /// Returns the records preceded by `records`, e.g. the CNAME records which lead to the name of this lookup
+    pub(crate) fn prepend_records(&self, mut records: Vec<Record>) -> Self {
+        if records.is_empty() {
+            return self.clone();
+        }
+
+        records.extend_from_slice(&*self.records);
+        Self::new_with_records(self.query.clone(), Arc::new(records), self.valid_until)
     }
 
     /// Clones the inner vec, appends the other vec
     pub(crate) fn append(&self, other: Lookup) -> Self {
-        let mut rdatas = Vec::with_capacity(self.len() + other.len());
-        rdatas.extend_from_slice(&*self.rdatas);
-        rdatas.extend_from_slice(&*other.rdatas);
+        let mut records = Vec::with_capacity(self.records.len() + other.records.len());
+        records.extend_from_slice(&*self.records);
+        records.extend_from_slice(&*other.records);
 
         // Choose the sooner deadline of the two lookups.
         let valid_until = min(self.valid_until(), other.valid_until());
-        Self::new_with_deadline(self.query.clone(), Arc::new(rdatas), valid_until)
+        Self::new_with_records(self.query.clone(), Arc::new(records), valid_until)
     }
 }
 
 /// Borrowed view of set of RDatas returned from a Lookup
-pub struct LookupIter<'a>(Iter<'a, RData>);
+pub struct LookupIter<'a> {
+    records: Iter<'a, Record>,
+    skip_cnames: bool,
+}
 
 impl<'a> Iterator for LookupIter<'a> {
     type Item = &'a RData;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.0.next()
+        let skip_cnames = self.skip_cnames;
+        self.records
+            .by_ref()
+            .find(|record| !skip_cnames || record.rr_type() != RecordType::CNAME)
+            .map(Record::rdata)
     }
 }
 
@@ -123,6 +189,7 @@ pub enum LookupEither<C: DnsHandle + 'static, P: ConnectionProvider<ConnHandle =
     Retry(RetryDnsHandle<NameServerPool<C, P>>),
     #[cfg(feature = "dnssec")]
     Secure(SecureDnsHandle<RetryDnsHandle<NameServerPool<C, P>>>),
+    Forward(ForwardingHandle<LookupEither<C, P>>),
 }
 
 impl<C: DnsHandle, P: ConnectionProvider<ConnHandle = C>> DnsHandle for LookupEither<C, P> {
@@ -133,6 +200,7 @@ impl<C: DnsHandle, P: ConnectionProvider<ConnHandle = C>> DnsHandle for LookupEi
             LookupEither::Retry(ref c) => c.is_verifying_dnssec(),
             #[cfg(feature = "dnssec")]
             LookupEither::Secure(ref c) => c.is_verifying_dnssec(),
+            LookupEither::Forward(ref c) => c.is_verifying_dnssec(),
         }
     }
 
@@ -141,6 +209,7 @@ impl<C: DnsHandle, P: ConnectionProvider<ConnHandle = C>> DnsHandle for LookupEi
             LookupEither::Retry(ref mut c) => c.send(request),
             #[cfg(feature = "dnssec")]
             LookupEither::Secure(ref mut c) => c.send(request),
+            LookupEither::Forward(ref mut c) => c.send(request),
         }
     }
 }
@@ -156,6 +225,7 @@ where
     record_type: RecordType,
     options: DnsRequestOptions,
     query: Box<Future<Item = Lookup, Error = ResolveError> + Send>,
+    hosts: Option<Arc<Hosts>>,
 }
 
 impl<C: DnsHandle + 'static> LookupFuture<C> {
@@ -166,21 +236,26 @@ impl<C: DnsHandle + 'static> LookupFuture<C> {
     /// * `names` - a set of DNS names to attempt to resolve, they will be attempted in queue order, i.e. the first is `names.pop()`. Upon each failure, the next will be attempted.
     /// * `record_type` - type of record being sought
     /// * `client_cache` - cache with a connection to use for performing all lookups
+    /// * `hosts` - the hosts file and static records, which are used instead of the cache and name servers
     #[doc(hidden)]
     pub fn lookup(
         mut names: Vec<Name>,
         record_type: RecordType,
         options: DnsRequestOptions,
-        mut client_cache: CachingClient<C>,
+        client_cache: CachingClient<C>,
+        hosts: Option<Arc<Hosts>>,
     ) -> Self {
         let name = names.pop().ok_or_else(|| {
             ResolveError::from(ResolveErrorKind::Message("can not lookup for no names"))
         });
 
         let query: Box<Future<Item = Lookup, Error = ResolveError> + Send> = match name {
-            Ok(name) => {
-                Box::new(client_cache.lookup(Query::query(name, record_type), options.clone()))
-            }
+            Ok(name) => hosts_lookup(
+                Query::query(name, record_type),
+                client_cache.clone(),
+                options.clone(),
+                hosts.clone(),
+            ),
             Err(err) => Box::new(future::err(err)),
         };
 
@@ -190,6 +265,7 @@ impl<C: DnsHandle + 'static> LookupFuture<C> {
             record_type,
             options,
             query,
+            hosts,
         }
     }
 }
@@ -210,7 +286,7 @@ impl<C: DnsHandle + 'static> Future for LookupFuture<C> {
                 // If the query returned a successful lookup, we will attempt
                 // to retry if the lookup is empty. Otherwise, we will return
                 // that lookup.
-                Ok(Async::Ready(ref lookup)) => lookup.rdatas.len() == 0,
+                Ok(Async::Ready(ref lookup)) => lookup.is_empty(),
                 // If the query failed, we will attempt to retry.
                 Err(_) => true,
             };
@@ -219,9 +295,12 @@ impl<C: DnsHandle + 'static> Future for LookupFuture<C> {
                 if let Some(name) = self.names.pop() {
                     // If there's another name left to try, build a new query
                     // for that next name and continue looping.
-                    self.query = self
-                        .client_cache
-                        .lookup(Query::query(name, self.record_type), self.options.clone());
+                    self.query = hosts_lookup(
+                        Query::query(name, self.record_type),
+                        self.client_cache.clone(),
+                        self.options.clone(),
+                        self.hosts.clone(),
+                    );
                     // Continue looping with the new query. It will be polled
                     // on the next iteration of the loop.
                     continue;
@@ -305,6 +384,308 @@ impl Future for SrvLookupFuture {
     }
 }
 
+/// The result of an NS lookup, the name servers of a zone and their addresses
+#[derive(Debug, Clone)]
+pub struct NsLookup {
+    lookup: Lookup,
+    addresses: Arc<Vec<(Name, Vec<IpAddr>)>>,
+}
+
+impl NsLookup {
+    /// Returns an iterator over the names of the name servers
+    pub fn iter(&self) -> NsLookupIter {
+        NsLookupIter(self.lookup.iter())
+    }
+
+    /// Returns a reference to the Query that was used to produce this result.
+    pub fn query(&self) -> &Query {
+        self.lookup.query()
+    }
+
+    /// Returns the addresses of the name server, empty if they could not be resolved
+    pub fn addresses(&self, name_server: &Name) -> &[IpAddr] {
+        self.addresses
+            .iter()
+            .find(|(name, _)| name == name_server)
+            .map_or(&[][..], |(_, ips)| &ips[..])
+    }
+
+    /// Returns the addresses of all the name servers
+    pub fn ip_iter<'i>(&'i self) -> impl Iterator<Item = IpAddr> + 'i {
+        self.addresses
+            .iter()
+            .flat_map(|(_, ips)| ips.iter().cloned())
+    }
+}
+
+/// An iterator over the names of the name servers of an NsLookup
+pub struct NsLookupIter<'i>(LookupIter<'i>);
+
+impl<'i> Iterator for NsLookupIter<'i> {
+    type Item = &'i Name;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let iter: &mut _ = &mut self.0;
+        iter.filter_map(|rdata| match *rdata {
+            RData::NS(ref data) => Some(data),
+            _ => None,
+        }).next()
+    }
+}
+
+/// A Future which resolves to the NsLookup type, once the addresses of all name servers are resolved
+pub struct NsLookupFuture(Box<Future<Item = NsLookup, Error = ResolveError> + Send>);
+
+impl NsLookupFuture {
+    /// Resolves the addresses of the name servers of the NS lookup with `lookup_ip`
+    ///
+    /// Failures to resolve the addresses of a name server are not errors, its addresses are empty.
+    pub(crate) fn new<F, L, I>(ns_lookup: F, lookup_ip: L) -> Self
+    where
+        F: Future<Item = Lookup, Error = ResolveError> + Send + 'static,
+        L: Fn(Name) -> I + Send + 'static,
+        I: Future<Item = LookupIp, Error = ResolveError> + Send + 'static,
+    {
+        let future = ns_lookup.and_then(move |lookup| {
+            let addresses = NsLookupIter(lookup.iter())
+                .cloned()
+                .map(|name| {
+                    lookup_ip(name.clone()).then(move |ips| {
+                        let ips = match ips {
+                            Ok(ips) => ips.iter().collect(),
+                            Err(e) => {
+                                debug!("failed to resolve name server {}: {}", name, e);
+                                vec![]
+                            }
+                        };
+                        Ok((name, ips))
+                    })
+                }).collect::<Vec<_>>();
+
+            future::join_all(addresses).map(move |addresses| NsLookup {
+                lookup,
+                addresses: Arc::new(addresses),
+            })
+        });
+
+        NsLookupFuture(Box::new(future))
+    }
+
+    pub(crate) fn error(error: ResolveError) -> Self {
+        NsLookupFuture(Box::new(future::err(error)))
+    }
+}
+
+impl Future for NsLookupFuture {
+    type Item = NsLookup;
+    type Error = ResolveError;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        self.0.poll()
+    }
+}
+
+/// The result of an SOA lookup, the SOA of the zone which contains the name
+#[derive(Debug, Clone)]
+pub struct SoaLookup(Lookup);
+
+impl SoaLookup {
+    /// Returns an iterator over the SOA records, generally there is exactly one
+    pub fn iter(&self) -> SoaLookupIter {
+        SoaLookupIter(self.0.iter())
+    }
+
+    /// Returns a reference to the Query that was used to produce this result.
+    pub fn query(&self) -> &Query {
+        self.0.query()
+    }
+
+    /// Returns the name of the zone, i.e. the name at which the SOA was found
+    pub fn zone(&self) -> &Name {
+        self.0.query().name()
+    }
+}
+
+impl From<Lookup> for SoaLookup {
+    fn from(lookup: Lookup) -> Self {
+        SoaLookup(lookup)
+    }
+}
+
+/// An iterator over the SOA records of an SoaLookup
+pub struct SoaLookupIter<'i>(LookupIter<'i>);
+
+impl<'i> Iterator for SoaLookupIter<'i> {
+    type Item = &'i rdata::SOA;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let iter: &mut _ = &mut self.0;
+        iter.filter_map(|rdata| match *rdata {
+            RData::SOA(ref data) => Some(data),
+            _ => None,
+        }).next()
+    }
+}
+
+/// A Future which resolves to the SOA of the zone containing a name
+pub struct SoaLookupFuture(Box<Future<Item = SoaLookup, Error = ResolveError> + Send>);
+
+impl SoaLookupFuture {
+    /// Looks up the SOA of the name with `lookup_soa`, and of its parents until one is found
+    ///
+    /// Only the absence of records, i.e. NXDOMAIN or no SOA at the name, continues with the parent, other errors end
+    ///  the lookup. The name is made fully qualified, so that the walk ends with the root.
+    pub(crate) fn new<L, F>(mut name: Name, lookup_soa: L) -> Self
+    where
+        L: Fn(Name) -> F + Send + 'static,
+        F: Future<Item = Lookup, Error = ResolveError> + Send + 'static,
+    {
+        name.set_fqdn(true);
+
+        let future = future::loop_fn(name, move |name| {
+            lookup_soa(name.clone()).then(move |result| {
+                let error = match result {
+                    Ok(ref lookup) if SoaLookupIter(lookup.iter()).next().is_some() => {
+                        return Ok(Loop::Break(SoaLookup(lookup.clone())));
+                    }
+                    Ok(lookup) => ResolveError::from(ResolveErrorKind::NoRecordsFound {
+                        query: lookup.query().clone(),
+                        valid_until: Some(lookup.valid_until()),
+                    }),
+                    Err(e) => e,
+                };
+
+                match *error.kind() {
+                    ResolveErrorKind::NoRecordsFound { .. } if name.num_labels() > 0 => {
+                        Ok(Loop::Continue(name.base_name()))
+                    }
+                    _ => Err(error),
+                }
+            })
+        });
+
+        SoaLookupFuture(Box::new(future))
+    }
+
+    pub(crate) fn error(error: ResolveError) -> Self {
+        SoaLookupFuture(Box::new(future::err(error)))
+    }
+}
+
+impl Future for SoaLookupFuture {
+    type Item = SoaLookup;
+    type Error = ResolveError;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        self.0.poll()
+    }
+}
+
+/// The maximum number of AliasMode records followed by a ServiceLookupFuture
+const MAX_ALIAS_CHAIN: usize = 8;
+
+/// The result of a service binding lookup, the ServiceMode SVCB or HTTPS records of the service
+#[derive(Debug, Clone)]
+pub struct ServiceLookup {
+    name: Name,
+    endpoints: Vec<rdata::SVCB>,
+}
+
+impl ServiceLookup {
+    /// Returns an iterator over the endpoints of the service, in order of priority
+    pub fn iter(&self) -> Iter<rdata::SVCB> {
+        self.endpoints.iter()
+    }
+
+    /// Returns the name at which the ServiceMode records were found, after following AliasMode records
+    pub fn name(&self) -> &Name {
+        &self.name
+    }
+}
+
+/// A Future which resolves to the endpoints of a service from its SVCB or HTTPS records
+pub struct ServiceLookupFuture(Box<Future<Item = ServiceLookup, Error = ResolveError> + Send>);
+
+impl ServiceLookupFuture {
+    /// Looks up the SVCB or HTTPS records of the name with `lookup_svcb`, following AliasMode records
+    ///
+    /// As required by RFC 9460, ServiceMode records are ignored in a set with an AliasMode record, an AliasMode
+    ///  record with the root as target means that the service does not exist. The TargetName of the `.` in the
+    ///  returned ServiceMode records is replaced with the name at which they were found.
+    pub(crate) fn new<L, F>(name: Name, lookup_svcb: L) -> Self
+    where
+        L: Fn(Name) -> F + Send + 'static,
+        F: Future<Item = Lookup, Error = ResolveError> + Send + 'static,
+    {
+        let future = future::loop_fn(vec![name], move |mut chain| {
+            let name = chain.last().cloned().expect("chain is never empty");
+            lookup_svcb(name.clone()).and_then(move |lookup| {
+                let records = lookup
+                    .iter()
+                    .filter_map(|rdata| match *rdata {
+                        RData::SVCB(ref svcb) | RData::HTTPS(ref svcb) => Some(svcb),
+                        _ => None,
+                    }).collect::<Vec<_>>();
+                let no_records = || {
+                    ResolveError::from(ResolveErrorKind::NoRecordsFound {
+                        query: lookup.query().clone(),
+                        valid_until: Some(lookup.valid_until()),
+                    })
+                };
+
+                if let Some(alias) = records.iter().find(|svcb| svcb.is_alias_mode()) {
+                    let target = alias.target_name();
+                    if target.is_root() {
+                        return Err(no_records());
+                    }
+                    if chain.contains(target) {
+                        return Err(format!("AliasMode loop at {}", target).into());
+                    }
+                    if chain.len() > MAX_ALIAS_CHAIN {
+                        return Err(format!("too many AliasMode records from {}", chain[0]).into());
+                    }
+
+                    chain.push(target.clone());
+                    return Ok(Loop::Continue(chain));
+                }
+
+                if records.is_empty() {
+                    return Err(no_records());
+                }
+
+                let mut endpoints = records
+                    .into_iter()
+                    .map(|svcb| {
+                        let target = if svcb.target_name().is_root() {
+                            name.clone()
+                        } else {
+                            svcb.target_name().clone()
+                        };
+                        rdata::SVCB::new(svcb.svc_priority(), target, svcb.svc_params().to_vec())
+                    }).collect::<Vec<_>>();
+                endpoints.sort_by_key(rdata::SVCB::svc_priority);
+
+                Ok(Loop::Break(ServiceLookup { name, endpoints }))
+            })
+        });
+
+        ServiceLookupFuture(Box::new(future))
+    }
+
+    pub(crate) fn error(error: ResolveError) -> Self {
+        ServiceLookupFuture(Box::new(future::err(error)))
+    }
+}
+
+impl Future for ServiceLookupFuture {
+    type Item = ServiceLookup;
+    type Error = ResolveError;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        self.0.poll()
+    }
+}
+
 /// Creates a Lookup result type from the specified components
 macro_rules! lookup_type {
     ($l:ident, $i:ident, $f:ident, $r:path, $t:path) => {
@@ -392,6 +773,13 @@ lookup_type!(
     Ipv6Addr
 );
 lookup_type!(MxLookup, MxLookupIter, MxLookupFuture, RData::MX, rdata::MX);
+lookup_type!(
+    NaptrLookup,
+    NaptrLookupIter,
+    NaptrLookupFuture,
+    RData::NAPTR,
+    rdata::NAPTR
+);
 lookup_type!(
     TxtLookup,
     TxtLookupIter,
@@ -399,10 +787,53 @@ lookup_type!(
     RData::TXT,
     rdata::TXT
 );
+lookup_type!(
+    CaaLookup,
+    CaaLookupIter,
+    CaaLookupFuture,
+    RData::CAA,
+    rdata::CAA
+);
+lookup_type!(
+    TlsaLookup,
+    TlsaLookupIter,
+    TlsaLookupFuture,
+    RData::TLSA,
+    rdata::TLSA
+);
+lookup_type!(
+    SshfpLookup,
+    SshfpLookupIter,
+    SshfpLookupFuture,
+    RData::SSHFP,
+    rdata::SSHFP
+);
+lookup_type!(
+    OpenpgpkeyLookup,
+    OpenpgpkeyLookupIter,
+    OpenpgpkeyLookupFuture,
+    RData::OPENPGPKEY,
+    rdata::OPENPGPKEY
+);
+lookup_type!(
+    SvcbLookup,
+    SvcbLookupIter,
+    SvcbLookupFuture,
+    RData::SVCB,
+    rdata::SVCB
+);
+lookup_type!(
+    HttpsLookup,
+    HttpsLookupIter,
+    HttpsLookupFuture,
+    RData::HTTPS,
+    rdata::SVCB
+);
 
 #[cfg(test)]
 pub mod tests {
     use std::net::{IpAddr, Ipv4Addr};
+    use std::str::FromStr;
     use std::sync::{Arc, Mutex};
 
     use futures::{future, Future};
@@ -454,6 +885,32 @@ pub mod tests {
         }
     }
 
+    /// Looks up the records of the name and type in `records`, any other name or type has no records
+    pub fn mock_lookup(
+        records: Vec<(&'static str, RData)>,
+    ) -> impl Fn(Name, RecordType) -> future::FutureResult<Lookup, ResolveError> + Send + Sync {
+        move |name, record_type| {
+            let query = Query::query(name.clone(), record_type);
+            let rdatas = records
+                .iter()
+                .filter(|&&(owner, ref rdata)| {
+                    Name::from_str(owner).unwrap() == name && rdata.to_record_type() == record_type
+                })
+                .map(|&(_, ref rdata)| rdata.clone())
+                .collect::<Vec<_>>();
+            if rdatas.is_empty() {
+                future::err(
+                    ResolveErrorKind::NoRecordsFound {
+                        query,
+                        valid_until: None,
+                    }.into(),
+                )
+            } else {
+                future::ok(Lookup::new_with_max_ttl(query, Arc::new(rdatas)))
+            }
+        }
+    }
+
     #[test]
     fn test_lookup() {
         assert_eq!(
@@ -462,6 +919,7 @@ pub mod tests {
                 RecordType::A,
                 DnsRequestOptions::default(),
                 CachingClient::new(0, mock(vec![v4_message()])),
+                None,
             ).wait()
             .unwrap()
             .iter()
@@ -479,6 +937,7 @@ pub mod tests {
                 RecordType::A,
                 DnsRequestOptions::default(),
                 CachingClient::new(0, mock(vec![error()])),
+                None,
             ).wait()
             .is_err()
         );
@@ -492,6 +951,7 @@ pub mod tests {
                 RecordType::A,
                 DnsRequestOptions::default(),
                 CachingClient::new(0, mock(vec![empty()])),
+                None,
             ).wait()
             .unwrap_err()
             .kind(),
@@ -501,4 +961,215 @@ pub mod tests {
             }
         );
     }
+
+    #[test]
+    fn test_ns_lookup_addresses() {
+        let zone = Name::from_str("example.com.").unwrap();
+        let ns1 = Name::from_str("ns1.example.com.").unwrap();
+        let ns2 = Name::from_str("ns2.example.net.").unwrap();
+        let ns = Lookup::new_with_max_ttl(
+            Query::query(zone, RecordType::NS),
+            Arc::new(vec![RData::NS(ns1.clone()), RData::NS(ns2.clone())]),
+        );
+
+        let lookup = NsLookupFuture::new(future::ok(ns), |name: Name| {
+            if name.to_string() == "ns1.example.com." {
+                future::ok(LookupIp::from(Lookup::from_rdata(
+                    Query::query(name, RecordType::A),
+                    RData::A(Ipv4Addr::new(192, 0, 2, 1)),
+                )))
+            } else {
+                future::err(ResolveErrorKind::Message("unreachable").into())
+            }
+        }).wait()
+        .unwrap();
+
+        assert_eq!(lookup.iter().collect::<Vec<_>>(), vec![&ns1, &ns2]);
+        assert_eq!(
+            lookup.addresses(&ns1),
+            &[IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1))]
+        );
+        assert!(lookup.addresses(&ns2).is_empty());
+        assert_eq!(lookup.ip_iter().count(), 1);
+    }
+
+    #[test]
+    fn test_soa_lookup_walks_up() {
+        let zone = Name::from_str("example.com.").unwrap();
+        let soa = rdata::SOA::new(
+            Name::from_str("ns1.example.com.").unwrap(),
+            Name::from_str("hostmaster.example.com.").unwrap(),
+            1,
+            3600,
+            600,
+            86400,
+            300,
+        );
+        let queried = Arc::new(Mutex::new(vec![]));
+
+        let lookup = {
+            let zone = zone.clone();
+            let soa = soa.clone();
+            let queried = queried.clone();
+            SoaLookupFuture::new(
+                Name::from_str("www.sub.example.com.").unwrap(),
+                move |name: Name| {
+                    queried.lock().unwrap().push(name.to_string());
+                    let query = Query::query(name.clone(), RecordType::SOA);
+                    if name == zone {
+                        future::ok(Lookup::from_rdata(query, RData::SOA(soa.clone())))
+                    } else {
+                        future::err(
+                            ResolveErrorKind::NoRecordsFound {
+                                query,
+                                valid_until: None,
+                            }.into(),
+                        )
+                    }
+                },
+            )
+        }.wait()
+        .unwrap();
+
+        assert_eq!(lookup.zone(), &zone);
+        assert_eq!(lookup.iter().collect::<Vec<_>>(), vec![&soa]);
+        assert_eq!(
+            *queried.lock().unwrap(),
+            vec!["www.sub.example.com.", "sub.example.com.", "example.com."]
+        );
+    }
+
+    fn svcb_lookup(
+        records: Vec<(&'static str, rdata::SVCB)>,
+        queried: Arc<Mutex<Vec<String>>>,
+    ) -> impl Fn(Name) -> future::FutureResult<Lookup, ResolveError> {
+        let lookup = mock_lookup(
+            records
+                .into_iter()
+                .map(|(owner, svcb)| (owner, RData::HTTPS(svcb)))
+                .collect(),
+        );
+        move |name: Name| {
+            queried.lock().unwrap().push(name.to_string());
+            lookup(name, RecordType::HTTPS)
+        }
+    }
+
+    #[test]
+    fn test_service_lookup_follows_alias() {
+        let name = |n| Name::from_str(n).unwrap();
+        let queried = Arc::new(Mutex::new(vec![]));
+        let records = vec![
+            ("example.com.", rdata::SVCB::new(0, name("svc.example.net."), vec![])),
+            ("svc.example.net.", rdata::SVCB::new(2, name("b.example.net."), vec![])),
+            ("svc.example.net.", rdata::SVCB::new(1, name("."), vec![])),
+        ];
+
+        let lookup = ServiceLookupFuture::new(
+            name("example.com."),
+            svcb_lookup(records, queried.clone()),
+        ).wait()
+        .unwrap();
+
+        assert_eq!(*lookup.name(), name("svc.example.net."));
+        assert_eq!(
+            lookup
+                .iter()
+                .map(|svcb| (svcb.svc_priority(), svcb.target_name().to_string()))
+                .collect::<Vec<_>>(),
+            vec![
+                (1, "svc.example.net.".to_string()),
+                (2, "b.example.net.".to_string()),
+            ]
+        );
+        assert_eq!(
+            *queried.lock().unwrap(),
+            vec!["example.com.", "svc.example.net."]
+        );
+    }
+
+    #[test]
+    fn test_service_lookup_alias_errors() {
+        let name = |n| Name::from_str(n).unwrap();
+        let lookup = |records| {
+            ServiceLookupFuture::new(
+                name("example.com."),
+                svcb_lookup(records, Arc::new(Mutex::new(vec![]))),
+            ).wait()
+        };
+
+        // the service does not exist
+        let error = lookup(vec![
+            ("example.com.", rdata::SVCB::new(0, name("."), vec![])),
+            ("example.com.", rdata::SVCB::new(1, name("."), vec![])),
+        ]).unwrap_err();
+        match *error.kind() {
+            ResolveErrorKind::NoRecordsFound { .. } => (),
+            ref kind => panic!("unexpected error: {:?}", kind),
+        }
+
+        // a loop
+        assert!(
+            lookup(vec![
+                ("example.com.", rdata::SVCB::new(0, name("a.example.com."), vec![])),
+                ("a.example.com.", rdata::SVCB::new(0, name("example.com."), vec![])),
+            ]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_soa_lookup_not_found() {
+        let error = SoaLookupFuture::new(Name::from_str("example.").unwrap(), |name: Name| {
+            future::err::<Lookup, _>(
+                ResolveErrorKind::NoRecordsFound {
+                    query: Query::query(name, RecordType::SOA),
+                    valid_until: None,
+                }.into(),
+            )
+        }).wait()
+        .unwrap_err();
+
+        assert_eq!(
+            *error.kind(),
+            ResolveErrorKind::NoRecordsFound {
+                query: Query::query(Name::root(), RecordType::SOA),
+                valid_until: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_soa_lookup_not_fqdn() {
+        let queried = Arc::new(Mutex::new(vec![]));
+        let queried_clone = queried.clone();
+        let lookup_soa = move |name: Name| {
+            queried_clone.lock().unwrap().push(name.clone());
+            future::err::<Lookup, _>(
+                ResolveErrorKind::NoRecordsFound {
+                    query: Query::query(name, RecordType::SOA),
+                    valid_until: None,
+                }.into(),
+            )
+        };
+        let error = SoaLookupFuture::new(Name::from_str("www.example").unwrap(), lookup_soa)
+            .wait()
+            .unwrap_err();
+
+        // the walk ends with the root, and does not loop on the empty relative name
+        assert_eq!(
+            *queried.lock().unwrap(),
+            vec![
+                Name::from_str("www.example.").unwrap(),
+                Name::from_str("example.").unwrap(),
+                Name::root(),
+            ]
+        );
+        assert_eq!(
+            *error.kind(),
+            ResolveErrorKind::NoRecordsFound {
+                query: Query::query(Name::root(), RecordType::SOA),
+                valid_until: None,
+            }
+        );
+    }
 }
@@ -9,19 +9,22 @@
 //!
 //! At it's heart LookupIp uses Lookup for performing all lookups. It is unlike other standard lookups in that there are customizations around A and AAAA resolutions.
 
+use std::collections::VecDeque;
 use std::net::IpAddr;
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use failure::Fail;
 
-use futures::{future, Async, Future, Poll};
+use futures::{future, Async, Future, Poll, Stream};
+use tokio_timer::Delay;
 
 use proto::op::Query;
 use proto::rr::{Name, RData, RecordType};
 use proto::xfer::{DnsHandle, DnsRequestOptions};
 
-use config::LookupIpStrategy;
+use addr_selection::{self, source_address};
+use config::{LookupIpStrategy, SortlistEntry};
 use error::*;
 use hosts::Hosts;
 use lookup::{Lookup, LookupEither, LookupIter};
@@ -83,10 +86,13 @@ where
     client_cache: CachingClient<C>,
     names: Vec<Name>,
     strategy: LookupIpStrategy,
+    single_request: bool,
     options: DnsRequestOptions,
     query: Box<Future<Item = Lookup, Error = ResolveError> + Send>,
     hosts: Option<Arc<Hosts>>,
     finally_ip_addr: Option<RData>,
+    sortlist: Vec<SortlistEntry>,
+    sort_destinations: bool,
 }
 
 impl<C: DnsHandle + 'static> Future for LookupIpFuture<C> {
@@ -117,6 +123,7 @@ impl<C: DnsHandle + 'static> Future for LookupIpFuture<C> {
                     self.query = strategic_lookup(
                         name,
                         self.strategy,
+                        self.single_request,
                         self.client_cache.clone(),
                         self.options.clone(),
                         self.hosts.clone(),
@@ -135,7 +142,11 @@ impl<C: DnsHandle + 'static> Future for LookupIpFuture<C> {
             // If we didn't have to retry the query, or we weren't able to
             // retry because we've exhausted the names to search and have no
             // fallback IP address, return the current query.
-            return query.map(|async| async.map(LookupIp::from));
+            let sortlist = &self.sortlist;
+            let sort_destinations = self.sort_destinations;
+            return query.map(|async| {
+                async.map(|lookup| sort_lookup(lookup, sortlist, sort_destinations).into())
+            });
             // If we skipped retrying the  query, this will return the
             // successful lookup, otherwise, if the retry failed, this will
             // return the last  query result --- either an empty lookup or the
@@ -168,6 +179,7 @@ where
         LookupIpFuture {
             names,
             strategy,
+            single_request: false,
             client_cache,
             // If there are no names remaining, this will be returned immediately,
             // otherwise, it will be retried.
@@ -175,21 +187,45 @@ where
             options,
             hosts,
             finally_ip_addr,
+            sortlist: vec![],
+            sort_destinations: false,
         }
     }
 
+    /// Sends the A and AAAA requests of `LookupIpStrategy::Ipv4AndIpv6` one after the other, see
+    ///  `ResolverOpts::single_request`
+    pub fn with_single_request(mut self, single_request: bool) -> Self {
+        self.single_request = single_request;
+        self
+    }
+
+    /// Orders the addresses by the networks containing them, see `ResolverConfig::add_sortlist`
+    pub fn with_sortlist(mut self, sortlist: Vec<SortlistEntry>) -> Self {
+        self.sortlist = sortlist;
+        self
+    }
+
+    /// Orders the addresses by destination address selection, see `ResolverOpts::sort_destinations`
+    pub fn with_sort_destinations(mut self, sort_destinations: bool) -> Self {
+        self.sort_destinations = sort_destinations;
+        self
+    }
+
     pub(crate) fn error<E: Fail>(client_cache: CachingClient<C>, error: E) -> Self {
         LookupIpFuture {
             // errors on names don't need to be cheap... i.e. this clone is unfortunate in this case.
             client_cache,
             names: vec![],
             strategy: LookupIpStrategy::default(),
+            single_request: false,
             options: DnsRequestOptions::default(),
             query: Box::new(future::err(
                 ResolveErrorKind::Msg(format!("{}", error)).into(),
             )),
             hosts: None,
             finally_ip_addr: None,
+            sortlist: vec![],
+            sort_destinations: false,
         }
     }
 
@@ -198,17 +234,212 @@ where
             client_cache,
             names: vec![],
             strategy: LookupIpStrategy::default(),
+            single_request: false,
             options: DnsRequestOptions::default(),
             query: Box::new(future::ok(lp)),
             hosts: None,
             finally_ip_addr: None,
+            sortlist: vec![],
+            sort_destinations: false,
+        }
+    }
+}
+
+/// Time to wait for the AAAA answers when the A answers arrive first, see [RFC 8305 section 3](https://tools.ietf.org/html/rfc8305#section-3)
+pub const RESOLUTION_DELAY: Duration = Duration::from_millis(50);
+
+/// The Stream returned from `AsyncResolver::lookup_ip_stream`, it yields the addresses of a name in the order in which
+///  connections to them should be attempted.
+///
+/// This implements the asynchronous resolution of Happy Eyeballs, [RFC 8305](https://tools.ietf.org/html/rfc8305):
+///  the AAAA and A queries are sent at the same time, and the addresses of each are yielded as soon as they arrive.
+///  Only when the A answers arrive first they are held back for the `RESOLUTION_DELAY`, to give the AAAA answers a
+///  chance to arrive. The addresses which are yielded together are ordered by destination address selection, see
+///  `addr_selection`, and interleaved by address family, starting with the preferred one.
+///
+/// The stream only fails if no addresses were found and one of the queries failed.
+#[must_use = "streams do nothing unless polled"]
+pub struct LookupIpStream {
+    aaaa: Option<Box<Future<Item = Lookup, Error = ResolveError> + Send>>,
+    a: Option<Box<Future<Item = Lookup, Error = ResolveError> + Send>>,
+    held: Option<(Vec<IpAddr>, Delay)>,
+    ready: VecDeque<IpAddr>,
+    resolution_delay: Duration,
+    found: bool,
+    error: Option<ResolveError>,
+    source_for: fn(IpAddr) -> Option<IpAddr>,
+}
+
+impl LookupIpStream {
+    pub(crate) fn new(
+        aaaa: Box<Future<Item = Lookup, Error = ResolveError> + Send>,
+        a: Box<Future<Item = Lookup, Error = ResolveError> + Send>,
+    ) -> Self {
+        Self::from_parts(Some(aaaa), Some(a), None, None)
+    }
+
+    /// A stream which only yields the address, for lookups of IP addresses
+    pub(crate) fn ip(ip: IpAddr) -> Self {
+        Self::from_parts(None, None, Some(ip), None)
+    }
+
+    pub(crate) fn error(error: ResolveError) -> Self {
+        Self::from_parts(None, None, None, Some(error))
+    }
+
+    fn from_parts(
+        aaaa: Option<Box<Future<Item = Lookup, Error = ResolveError> + Send>>,
+        a: Option<Box<Future<Item = Lookup, Error = ResolveError> + Send>>,
+        ip: Option<IpAddr>,
+        error: Option<ResolveError>,
+    ) -> Self {
+        LookupIpStream {
+            aaaa,
+            a,
+            held: None,
+            ready: ip.into_iter().collect(),
+            resolution_delay: RESOLUTION_DELAY,
+            found: false,
+            error,
+            source_for: source_address,
+        }
+    }
+
+    /// Sets the time to wait for the AAAA answers when the A answers arrive first, the default is `RESOLUTION_DELAY`
+    pub fn with_resolution_delay(mut self, resolution_delay: Duration) -> Self {
+        self.resolution_delay = resolution_delay;
+        self
+    }
+
+    /// Polls the lookup, returns its addresses once it completed, a failed lookup has none
+    fn poll_lookup(
+        lookup: &mut Option<Box<Future<Item = Lookup, Error = ResolveError> + Send>>,
+        error: &mut Option<ResolveError>,
+    ) -> Option<Vec<IpAddr>> {
+        let ips = match lookup.as_mut().map(|lookup| lookup.poll()) {
+            None | Some(Ok(Async::NotReady)) => return None,
+            Some(Ok(Async::Ready(lookup))) => LookupIp::from(lookup).iter().collect(),
+            Some(Err(e)) => {
+                debug!("lookup of addresses failed: {}", e);
+                *error = Some(e);
+                vec![]
+            }
+        };
+
+        *lookup = None;
+        Some(ips)
+    }
+
+    /// Sorts the addresses and interleaves them by family, then queues them to be yielded
+    fn push_ready(&mut self, mut ips: Vec<IpAddr>) {
+        addr_selection::sort_destinations_with(&mut ips, self.source_for);
+
+        let first_is_ipv6 = match ips.first() {
+            Some(ip) => ip.is_ipv6(),
+            None => return,
+        };
+        let (first, second): (Vec<IpAddr>, Vec<IpAddr>) = ips
+            .into_iter()
+            .partition(|ip| ip.is_ipv6() == first_is_ipv6);
+
+        let mut second = second.into_iter();
+        for ip in first {
+            self.ready.push_back(ip);
+            self.ready.extend(second.next());
+        }
+        self.ready.extend(second);
+    }
+}
+
+impl Stream for LookupIpStream {
+    type Item = IpAddr;
+    type Error = ResolveError;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        loop {
+            if let Some(ip) = self.ready.pop_front() {
+                self.found = true;
+                return Ok(Async::Ready(Some(ip)));
+            }
+
+            if let Some(aaaa) = Self::poll_lookup(&mut self.aaaa, &mut self.error) {
+                // the A answers held back are yielded together with the AAAA answers
+                let mut ips = aaaa;
+                if let Some((a, _)) = self.held.take() {
+                    ips.extend(a);
+                }
+                self.push_ready(ips);
+                continue;
+            }
+
+            if let Some(a) = Self::poll_lookup(&mut self.a, &mut self.error) {
+                if self.aaaa.is_some() && !a.is_empty() {
+                    let delay = Delay::new(Instant::now() + self.resolution_delay);
+                    self.held = Some((a, delay));
+                } else {
+                    self.push_ready(a);
+                }
+                continue;
+            }
+
+            let delay_elapsed = match self.held {
+                Some((_, ref mut delay)) => match delay.poll() {
+                    Ok(Async::NotReady) => false,
+                    Ok(Async::Ready(())) => true,
+                    Err(e) => {
+                        warn!("failed to wait for AAAA answers: {}", e);
+                        true
+                    }
+                },
+                None => false,
+            };
+            if delay_elapsed {
+                if let Some((a, _)) = self.held.take() {
+                    self.push_ready(a);
+                }
+                continue;
+            }
+
+            if self.aaaa.is_some() || self.a.is_some() || self.held.is_some() {
+                return Ok(Async::NotReady);
+            }
+
+            return match self.error.take() {
+                Some(e) if !self.found => Err(e),
+                _ => Ok(Async::Ready(None)),
+            };
         }
     }
 }
+
+/// Orders the addresses by the first network of the sortlist containing them, the others follow
+///
+/// With `sort_destinations` the addresses are first ordered by destination address selection, the sortlist only
+///  reorders them further.
+fn sort_lookup(lookup: Lookup, sortlist: &[SortlistEntry], sort_destinations: bool) -> Lookup {
+    if sortlist.is_empty() && !sort_destinations {
+        return lookup;
+    }
+
+    let mut rdatas = lookup.iter().cloned().collect::<Vec<_>>();
+    if sort_destinations {
+        addr_selection::sort_by_destination(&mut rdatas, RData::to_ip_addr, source_address);
+    }
+    rdatas.sort_by_key(|rdata| {
+        rdata
+            .to_ip_addr()
+            .and_then(|ip| sortlist.iter().position(|entry| entry.contains(ip)))
+            .unwrap_or(sortlist.len())
+    });
+
+    Lookup::new_with_deadline(lookup.query().clone(), Arc::new(rdatas), lookup.valid_until())
+}
+
 /// returns a new future for lookup
 fn strategic_lookup<C: DnsHandle + 'static>(
     name: Name,
     strategy: LookupIpStrategy,
+    single_request: bool,
     client: CachingClient<C>,
     options: DnsRequestOptions,
     hosts: Option<Arc<Hosts>>,
@@ -216,6 +447,9 @@ fn strategic_lookup<C: DnsHandle + 'static>(
     match strategy {
         LookupIpStrategy::Ipv4Only => ipv4_only(name, client, options, hosts),
         LookupIpStrategy::Ipv6Only => ipv6_only(name, client, options, hosts),
+        LookupIpStrategy::Ipv4AndIpv6 if single_request => {
+            ipv4_and_ipv6_serial(name, client, options, hosts)
+        }
         LookupIpStrategy::Ipv4AndIpv6 => ipv4_and_ipv6(name, client, options, hosts),
         LookupIpStrategy::Ipv6thenIpv4 => ipv6_then_ipv4(name, client, options, hosts),
         LookupIpStrategy::Ipv4thenIpv6 => ipv4_then_ipv6(name, client, options, hosts),
@@ -223,7 +457,7 @@ fn strategic_lookup<C: DnsHandle + 'static>(
 }
 
 /// first lookups in hosts, then performs the query
-fn hosts_lookup<C: DnsHandle + 'static>(
+pub(crate) fn hosts_lookup<C: DnsHandle + 'static>(
     query: Query,
     mut client: CachingClient<C>,
     options: DnsRequestOptions,
@@ -302,6 +536,39 @@ fn ipv4_and_ipv6<C: DnsHandle + 'static>(
     )
 }
 
+/// queries for A and then for AAAA, never sending both requests at the same time
+fn ipv4_and_ipv6_serial<C: DnsHandle + 'static>(
+    name: Name,
+    client: CachingClient<C>,
+    options: DnsRequestOptions,
+    hosts: Option<Arc<Hosts>>,
+) -> Box<Future<Item = Lookup, Error = ResolveError> + Send> {
+    let aaaa_client = client.clone();
+    let aaaa_options = options.clone();
+    let aaaa_hosts = hosts.clone();
+    Box::new(
+        hosts_lookup(
+            Query::query(name.clone(), RecordType::A),
+            client,
+            options,
+            hosts,
+        ).then(move |a_res| {
+            hosts_lookup(
+                Query::query(name, RecordType::AAAA),
+                aaaa_client,
+                aaaa_options,
+                aaaa_hosts,
+            ).then(move |aaaa_res| match (a_res, aaaa_res) {
+                // join A and AAAA results
+                (Ok(ips), Ok(rem_ips)) => Ok(ips.append(rem_ips)),
+                // One failed, just return the other
+                (Ok(ips), Err(_)) | (Err(_), Ok(ips)) => Ok(ips),
+                (Err(err), Err(_)) => Err(err),
+            })
+        }),
+    )
+}
+
 /// queries only for AAAA and on no results queries for A
 fn ipv6_then_ipv4<C: DnsHandle + 'static>(
     name: Name,
@@ -386,6 +653,7 @@ pub mod tests {
     use std::sync::{Arc, Mutex};
 
     use futures::{future, Future};
+    use tokio::runtime::current_thread::Runtime;
 
     use proto::error::{ProtoError, ProtoResult};
     use proto::op::Message;
@@ -656,4 +924,161 @@ pub mod tests {
             vec![Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1)]
         );
     }
+
+    #[test]
+    fn test_ipv4_and_ipv6_single_request() {
+        // A is queried first, and AAAA only after it
+        assert_eq!(
+            strategic_lookup(
+                Name::root(),
+                LookupIpStrategy::Ipv4AndIpv6,
+                true,
+                CachingClient::new(0, mock(vec![v6_message(), v4_message()])),
+                Default::default(),
+                None,
+            ).wait()
+            .unwrap()
+            .iter()
+            .map(|r| r.to_ip_addr().unwrap())
+            .collect::<Vec<IpAddr>>(),
+            vec![
+                IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+                IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1)),
+            ]
+        );
+
+        // error then ipv6
+        assert_eq!(
+            strategic_lookup(
+                Name::root(),
+                LookupIpStrategy::Ipv4AndIpv6,
+                true,
+                CachingClient::new(0, mock(vec![v6_message(), error()])),
+                Default::default(),
+                None,
+            ).wait()
+            .unwrap()
+            .iter()
+            .map(|r| r.to_ip_addr().unwrap())
+            .collect::<Vec<IpAddr>>(),
+            vec![IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1))]
+        );
+    }
+
+    #[test]
+    fn test_sortlist() {
+        let ips = vec![
+            RData::A(Ipv4Addr::new(10, 0, 0, 1)),
+            RData::AAAA(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1)),
+            RData::A(Ipv4Addr::new(172, 16, 0, 1)),
+            RData::A(Ipv4Addr::new(192, 168, 1, 1)),
+        ];
+        let lookup = Lookup::new_with_max_ttl(Query::new(), Arc::new(ips));
+        let sortlist = vec![
+            SortlistEntry::new(
+                Ipv4Addr::new(192, 168, 0, 0).into(),
+                Ipv4Addr::new(255, 255, 0, 0).into(),
+            ),
+            SortlistEntry::new(
+                Ipv4Addr::new(10, 0, 0, 0).into(),
+                Ipv4Addr::new(255, 0, 0, 0).into(),
+            ),
+        ];
+
+        assert_eq!(
+            sort_lookup(lookup, &sortlist, false)
+                .iter()
+                .map(|r| r.to_ip_addr().unwrap())
+                .collect::<Vec<IpAddr>>(),
+            vec![
+                IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1)),
+                IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+                IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1)),
+                IpAddr::V4(Ipv4Addr::new(172, 16, 0, 1)),
+            ]
+        );
+    }
+
+    fn addrs_lookup(ips: &[IpAddr]) -> Lookup {
+        let rdatas = ips
+            .iter()
+            .map(|ip| match *ip {
+                IpAddr::V4(ip) => RData::A(ip),
+                IpAddr::V6(ip) => RData::AAAA(ip),
+            })
+            .collect();
+        Lookup::new_with_max_ttl(Query::new(), Arc::new(rdatas))
+    }
+
+    /// A lookup of the addresses which completes after the delay
+    fn delayed(
+        millis: u64,
+        ips: &[IpAddr],
+    ) -> Box<Future<Item = Lookup, Error = ResolveError> + Send> {
+        let lookup = addrs_lookup(ips);
+        Box::new(
+            Delay::new(Instant::now() + Duration::from_millis(millis))
+                .map_err(|e| ResolveErrorKind::Msg(format!("{}", e)).into())
+                .map(move |()| lookup),
+        )
+    }
+
+    fn failed() -> Box<Future<Item = Lookup, Error = ResolveError> + Send> {
+        Box::new(future::err(
+            ResolveErrorKind::Message("forced test failure").into(),
+        ))
+    }
+
+    /// Collects the stream, every address is its own source so that IPv6 is preferred
+    fn collect(mut stream: LookupIpStream) -> ResolveResult<Vec<IpAddr>> {
+        stream.source_for = Some;
+        Runtime::new().unwrap().block_on(stream.collect())
+    }
+
+    #[test]
+    fn test_stream_aaaa_first() {
+        let v6 = IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1));
+        let v4 = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+
+        let stream = LookupIpStream::new(delayed(0, &[v6]), delayed(100, &[v4]));
+        assert_eq!(collect(stream).unwrap(), vec![v6, v4]);
+    }
+
+    #[test]
+    fn test_stream_resolution_delay() {
+        let v6 = [
+            IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)),
+            IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 2)),
+        ];
+        let v4 = [
+            IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)),
+            IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2)),
+        ];
+
+        // the AAAA answers arrive within the resolution delay, the families are interleaved
+        let stream = LookupIpStream::new(delayed(20, &v6), delayed(0, &v4))
+            .with_resolution_delay(Duration::from_secs(5));
+        assert_eq!(collect(stream).unwrap(), vec![v6[0], v4[0], v6[1], v4[1]]);
+
+        // the AAAA answers arrive too late
+        let stream = LookupIpStream::new(delayed(500, &v6), delayed(0, &v4))
+            .with_resolution_delay(Duration::from_millis(10));
+        assert_eq!(collect(stream).unwrap(), vec![v4[0], v4[1], v6[0], v6[1]]);
+    }
+
+    #[test]
+    fn test_stream_failures() {
+        let v4 = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+
+        let stream = LookupIpStream::new(failed(), delayed(0, &[v4]));
+        assert_eq!(collect(stream).unwrap(), vec![v4]);
+
+        let stream = LookupIpStream::new(delayed(0, &[]), delayed(0, &[]));
+        assert!(collect(stream).unwrap().is_empty());
+
+        let stream = LookupIpStream::new(failed(), failed());
+        assert!(collect(stream).is_err());
+
+        assert_eq!(collect(LookupIpStream::ip(v4)).unwrap(), vec![v4]);
+    }
 }
@@ -15,15 +15,20 @@ use std::sync::{Arc, Mutex, TryLockError};
 use std::time::Instant;
 
 use futures::{future, task, Async, Future, Poll};
+use tokio_executor::{DefaultExecutor, Executor};
 
+use proto::op::Edns;
 use proto::op::{Message, Query, ResponseCode};
 use proto::rr::domain::usage::{
     ResolverUsage, DEFAULT, INVALID, IN_ADDR_ARPA_127, IP6_ARPA_1, LOCAL,
     LOCALHOST as LOCALHOST_usage,
 };
+use proto::rr::rdata::opt::ClientSubnet;
 use proto::rr::{DNSClass, Name, RData, Record, RecordType};
 use proto::xfer::{DnsHandle, DnsRequestOptions, DnsResponse};
 
+use config::ClientSubnetStrategy;
+use dns64::Dns64Config;
 use dns_lru;
 use dns_lru::DnsLru;
 use error::*;
@@ -49,6 +54,8 @@ pub struct CachingClient<C: DnsHandle> {
     // TODO: switch to FuturesMutex (Mutex will have some undesireable locking)
     lru: Arc<Mutex<DnsLru>>,
     client: C,
+    dns64: Option<Arc<Dns64Config>>,
+    client_subnet: ClientSubnetStrategy,
 }
 
 impl<C: DnsHandle + 'static> CachingClient<C> {
@@ -61,15 +68,35 @@ impl<C: DnsHandle + 'static> CachingClient<C> {
     }
 
     pub(crate) fn with_cache(lru: Arc<Mutex<DnsLru>>, client: C) -> Self {
-        CachingClient { lru, client }
+        CachingClient {
+            lru,
+            client,
+            dns64: None,
+            client_subnet: ClientSubnetStrategy::default(),
+        }
+    }
+
+    /// Synthesizes AAAA records for names without them, see `ResolverConfig::set_dns64`
+    pub(crate) fn with_dns64(mut self, dns64: Option<Arc<Dns64Config>>) -> Self {
+        self.dns64 = dns64;
+        self
+    }
+
+    /// Attaches or strips the client subnet of lookups, see `ResolverOpts::client_subnet`
+    pub(crate) fn with_client_subnet(mut self, client_subnet: ClientSubnetStrategy) -> Self {
+        self.client_subnet = client_subnet;
+        self
     }
 
     /// Perform a lookup against this caching client, looking first in the cache for a result
     pub fn lookup(
         &mut self,
         query: Query,
-        options: DnsRequestOptions,
+        mut options: DnsRequestOptions,
     ) -> Box<Future<Item = Lookup, Error = ResolveError> + Send> {
+        // the cache is looked up with the client subnet that is sent to the name servers
+        options.client_subnet = self.client_subnet.apply(options.client_subnet);
+
         // see https://tools.ietf.org/html/rfc6761
         //
         // ```text
@@ -119,6 +146,19 @@ impl<C: DnsHandle + 'static> CachingClient<C> {
             }
         }
 
+        if let Some(dns64) = self.dns64.clone() {
+            match query.query_type() {
+                RecordType::AAAA => return self.dns64_lookup(query, options, dns64),
+                RecordType::PTR => {
+                    if let Some(reverse) = dns64.reverse_query(&query) {
+                        debug!("DNS64 reverse lookup of {} for {}", reverse, query);
+                        return self.lookup(reverse, options);
+                    }
+                }
+                _ => (),
+            }
+        }
+
         Box::new(QueryState::lookup(
             query,
             options,
@@ -126,12 +166,53 @@ impl<C: DnsHandle + 'static> CachingClient<C> {
             self.lru.clone(),
         ))
     }
+
+    /// Looks up the AAAA records, if there are none, synthesizes them from the A records, see RFC 6147 section 5.1
+    ///
+    /// Failed AAAA lookups are treated like lookups without records, as the name may still have A records.
+    fn dns64_lookup(
+        &mut self,
+        query: Query,
+        options: DnsRequestOptions,
+        dns64: Arc<Dns64Config>,
+    ) -> Box<Future<Item = Lookup, Error = ResolveError> + Send> {
+        let mut client = self.clone();
+        let aaaa = QueryState::lookup(
+            query.clone(),
+            options.clone(),
+            &mut self.client,
+            self.lru.clone(),
+        );
+
+        Box::new(aaaa.then(move |aaaa| {
+            let aaaa = aaaa.map(|lookup| dns64.remove_excluded(lookup));
+            if let Ok(ref lookup) = aaaa {
+                if !lookup.is_empty() {
+                    return Box::new(future::ok(lookup.clone()))
+                        as Box<Future<Item = Lookup, Error = ResolveError> + Send>;
+                }
+            }
+
+            let mut a_query = Query::query(query.name().clone(), RecordType::A);
+            a_query.set_query_class(query.query_class());
+            Box::new(client.lookup(a_query, options).then(move |a| {
+                let synthesized = a.map(|a| dns64.synthesize(query, &a));
+                match synthesized {
+                    Ok(ref lookup) if !lookup.is_empty() => synthesized,
+                    // without any A records to synthesize from, the result of the AAAA lookup stands
+                    _ => aaaa,
+                }
+            }))
+        }))
+    }
 }
 
 struct FromCache {
     query: Query,
     options: DnsRequestOptions,
     cache: Arc<Mutex<DnsLru>>,
+    /// the cached lookup is about to expire and should be refreshed
+    prefetch: bool,
 }
 
 impl Future for FromCache {
@@ -150,11 +231,50 @@ impl Future for FromCache {
             Err(TryLockError::Poisoned(poison)) => {
                 Err(ResolveErrorKind::Msg(format!("poisoned: {}", poison)).into())
             }
-            Ok(mut lru) => Ok(Async::Ready(lru.get(&self.query, Instant::now()))),
+            Ok(mut lru) => {
+                let now = Instant::now();
+                let client_subnet = self.options.client_subnet.as_ref();
+                let lookup = lru.get(&self.query, client_subnet, now);
+                self.prefetch =
+                    lookup.is_some() && lru.should_prefetch(&self.query, client_subnet, now);
+                Ok(Async::Ready(lookup))
+            }
         }
     }
 }
 
+/// Queries the name servers for the record in the background, updating the cache on success
+///
+/// This is used to refresh stale records and to prefetch records which are about to expire.
+fn spawn_refresh<C: DnsHandle + 'static>(
+    query: Query,
+    options: DnsRequestOptions,
+    mut client: C,
+    cache: Arc<Mutex<DnsLru>>,
+) {
+    let refresh = QueryState::Query(QueryFuture {
+        message_future: client.lookup(query.clone(), options.clone()),
+        query: query.clone(),
+        cache: cache.clone(),
+        dnssec: client.is_verifying_dnssec(),
+        options,
+        client: CachingClient::with_cache(cache, client),
+        refresh: true,
+        scope: None,
+    });
+
+    let refresh = refresh.then(move |result| {
+        if let Err(error) = result {
+            debug!("failed to refresh {}: {}", query, error);
+        }
+        Ok(())
+    });
+
+    if let Err(error) = DefaultExecutor::current().spawn(Box::new(refresh)) {
+        debug!("could not spawn refresh of cached record: {}", error);
+    }
+}
+
 /// This is the Future responsible for performing an actual query.
 struct QueryFuture<C: DnsHandle + 'static> {
     message_future: <C as DnsHandle>::Response,
@@ -164,24 +284,43 @@ struct QueryFuture<C: DnsHandle + 'static> {
     dnssec: bool, // TODO: move to DnsRequestOptions?
     options: DnsRequestOptions,
     client: CachingClient<C>,
+    /// is this a background refresh of a cached record? these are never served stale
+    refresh: bool,
+    /// the client subnet the response is tailored to, see `dns_lru::answer_scope`
+    scope: Option<ClientSubnet>,
 }
 
 enum Records {
-    /// The records exists, a vec of rdata with ttl
-    Exists(Vec<(RData, u32)>),
-    /// Records do not exist, ttl for negative caching
-    NoData { ttl: Option<u32> },
+    /// The records exists, preceded by the CNAME records which lead to their name
+    Exists(Vec<Record>),
+    /// Records do not exist, ttl for negative caching, the response code and SOA record of the response
+    NoData {
+        ttl: Option<u32>,
+        response_code: ResponseCode,
+        soa: Option<Record>,
+    },
     /// Future lookup for recursive cname records
     CnameChain {
         next: Box<Future<Item = Lookup, Error = ResolveError> + Send>,
         min_ttl: u32,
+        cnames: Vec<Record>,
     },
     /// Already cached, chained queries
-    Chained { cached: Lookup, min_ttl: u32 },
+    Chained {
+        cached: Lookup,
+        min_ttl: u32,
+        cnames: Vec<Record>,
+    },
 }
 
 impl<C: DnsHandle + 'static> QueryFuture<C> {
-    fn next_query(&mut self, query: Query, cname_ttl: u32, message: DnsResponse) -> Records {
+    fn next_query(
+        &mut self,
+        query: Query,
+        cname_ttl: u32,
+        cnames: Vec<Record>,
+        message: DnsResponse,
+    ) -> Records {
         if QUERY_DEPTH.with(|c| *c.borrow() >= MAX_QUERY_DEPTH) {
             // TODO: This should return an error
             self.handle_nxdomain(message, true)
@@ -192,6 +331,7 @@ impl<C: DnsHandle + 'static> QueryFuture<C> {
             Records::CnameChain {
                 next: self.client.lookup(query, self.options.clone()),
                 min_ttl: cname_ttl,
+                cnames,
             }
         }
     }
@@ -201,28 +341,30 @@ impl<C: DnsHandle + 'static> QueryFuture<C> {
         const INITIAL_TTL: u32 = dns_lru::MAX_TTL;
 
         // seek out CNAMES, this is only performed if the query is not a CNAME, ANY, or SRV
-        let (search_name, cname_ttl, was_cname) = {
+        let (search_name, cname_ttl, was_cname, cnames) = {
             // this will only search for CNAMEs if the request was not meant to be for one of the triggers for recursion
-            let (search_name, cname_ttl, was_cname) =
+            let (search_name, cname_ttl, was_cname, cnames) =
                 if self.query.query_type().is_any() || self.query.query_type().is_cname() {
-                    (Cow::Borrowed(self.query.name()), INITIAL_TTL, false)
+                    (Cow::Borrowed(self.query.name()), INITIAL_TTL, false, vec![])
                 } else {
                     // Folds any cnames from the answers section, into the final cname in the answers section
                     //   this works by folding the last CNAME found into the final folded result.
                     //   it assumes that the CNAMEs are in chained order in the DnsResponse Message...
                     // For SRV, the name added for the search becomes the target name.
+                    //  The CNAME records of the chain are kept, they are part of the answer.
                     //
                     // TODO: should this include the additionals?
                     response.messages().flat_map(Message::answers).fold(
-                        (Cow::Borrowed(self.query.name()), INITIAL_TTL, false),
-                        |(search_name, cname_ttl, was_cname), r| {
+                        (Cow::Borrowed(self.query.name()), INITIAL_TTL, false, vec![]),
+                        |(search_name, cname_ttl, was_cname, mut cnames), r| {
                             match *r.rdata() {
                                 RData::CNAME(ref cname) => {
                                     // take the minimum TTL of the cname_ttl and the next record in the chain
                                     let ttl = cname_ttl.min(r.ttl());
                                     debug_assert_eq!(r.rr_type(), RecordType::CNAME);
                                     if search_name.as_ref() == r.name() {
-                                        return (Cow::Owned(cname.clone()), ttl, true);
+                                        cnames.push(r.clone());
+                                        return (Cow::Owned(cname.clone()), ttl, true, cnames);
                                     }
                                 }
                                 RData::SRV(ref srv) => {
@@ -231,12 +373,12 @@ impl<C: DnsHandle + 'static> QueryFuture<C> {
                                     debug_assert_eq!(r.rr_type(), RecordType::SRV);
 
                                     // the search name becomes the srv.target
-                                    return (Cow::Owned(srv.target().clone()), ttl, true);
+                                    return (Cow::Owned(srv.target().clone()), ttl, true, cnames);
                                 }
                                 _ => (),
                             }
 
-                            (search_name, cname_ttl, was_cname)
+                            (search_name, cname_ttl, was_cname, cnames)
                         },
                     )
                 };
@@ -256,9 +398,10 @@ impl<C: DnsHandle + 'static> QueryFuture<C> {
                 .into_iter()
                 // Chained records will generally exist in the additionals section
                 .chain(additionals.into_iter())
-                .filter_map(|r| {
+                .filter_map(|mut r| {
                     // because this resolved potentially recursively, we want the min TTL from the chain
                     let ttl = cname_ttl.min(r.ttl());
+                    r.set_ttl(ttl);
 
                     // TODO: disable name validation with ResolverOpts? glibc feature...
                     // restrict to the RData type requested
@@ -270,7 +413,7 @@ impl<C: DnsHandle + 'static> QueryFuture<C> {
                         if ((self.query.query_type().is_any() || self.query.query_type() == r.rr_type()) &&
                             (search_name.as_ref() == r.name() || self.query.name() == r.name())) || 
                             (self.query.query_type().is_srv() && r.rr_type().is_ip_addr() && search_name.as_ref() == r.name()) {
-                            Some((r.unwrap_rdata(), ttl))
+                            Some(r)
                         } else {
                             None
                         }
@@ -281,10 +424,12 @@ impl<C: DnsHandle + 'static> QueryFuture<C> {
                 .collect::<Vec<_>>();
 
             if !records.is_empty() {
-                return Ok(Async::Ready(Records::Exists(records)));
+                let mut chain = cnames;
+                chain.extend(records);
+                return Ok(Async::Ready(Records::Exists(chain)));
             }
 
-            (search_name.into_owned(), cname_ttl, was_cname)
+            (search_name.into_owned(), cname_ttl, was_cname, cnames)
         };
 
         // TODO: for SRV records we *could* do an implicit lookup, but, this requires knowing the type of IP desired
@@ -293,7 +438,7 @@ impl<C: DnsHandle + 'static> QueryFuture<C> {
         if was_cname {
             let next_query = Query::query(search_name, self.query.query_type());
             Ok(Async::Ready(
-                self.next_query(next_query, cname_ttl, response),
+                self.next_query(next_query, cname_ttl, cnames, response),
             ))
         } else {
             // TODO: review See https://tools.ietf.org/html/rfc2308 for NoData section
@@ -317,23 +462,65 @@ impl<C: DnsHandle + 'static> QueryFuture<C> {
     /// * `message` - message to extract SOA, etc, from for caching failed requests
     /// * `valid_nsec` - species that in DNSSec mode, this request is safe to cache
     fn handle_nxdomain(&self, mut message: DnsResponse, valid_nsec: bool) -> Records {
+        let response_code = message.response_code();
+        let soa = message
+            .take_name_servers()
+            .into_iter()
+            .find(|r| r.rr_type() == RecordType::SOA);
+
         if valid_nsec || !self.dnssec {
             //  if there were validated NSEC records
-            let soa = message
-                .take_name_servers()
-                .into_iter()
-                .find(|r| r.rr_type() == RecordType::SOA);
-
-            let ttl = if let Some(RData::SOA(soa)) = soa.map(|r| r.unwrap_rdata()) {
+            let ttl = if let Some(RData::SOA(ref soa)) = soa.as_ref().map(Record::rdata) {
                 Some(soa.minimum())
             } else {
                 // TODO: figure out a looping lookup to get SOA
                 None
             };
 
-            Records::NoData { ttl }
+            Records::NoData {
+                ttl,
+                response_code,
+                soa,
+            }
         } else {
-            Records::NoData { ttl: None }
+            Records::NoData {
+                ttl: None,
+                response_code,
+                soa,
+            }
+        }
+    }
+
+    /// See https://tools.ietf.org/html/rfc8767
+    ///
+    /// When the name servers failed or timed out, an expired record still within the serve-stale window of the cache
+    ///  is returned instead of the error, and a refresh of the record is started in the background.
+    fn serve_stale(&mut self, error: ResolveError) -> Poll<Lookup, ResolveError> {
+        if self.refresh {
+            return Err(error);
+        }
+
+        let stale = match self.cache.lock() {
+            Ok(mut lru) => lru.get_stale(
+                &self.query,
+                self.options.client_subnet.as_ref(),
+                Instant::now(),
+            ),
+            Err(_) => None,
+        };
+
+        match stale {
+            Some(lookup) => {
+                debug!("serving stale {} after error: {}", self.query, error);
+                spawn_refresh(
+                    self.query.clone(),
+                    self.options.clone(),
+                    self.client.client.clone(),
+                    self.cache.clone(),
+                );
+                Ok(Async::Ready(lookup))
+            }
+            None => Err(error),
         }
     }
 }
@@ -347,6 +534,10 @@ impl<C: DnsHandle + 'static> Future for QueryFuture<C> {
             Ok(Async::Ready(message)) => {
                 // TODO: take all records and cache them?
                 //  if it's DNSSec they must be signed, otherwise?
+                self.scope = dns_lru::answer_scope(
+                    self.options.client_subnet.as_ref(),
+                    message.edns().and_then(Edns::client_subnet),
+                );
 
                 match message.response_code() {
                     ResponseCode::NXDomain => Ok(Async::Ready(self.handle_nxdomain(
@@ -365,6 +556,7 @@ impl<C: DnsHandle + 'static> Future for QueryFuture<C> {
 struct InsertCache {
     rdatas: Records,
     query: Query,
+    scope: Option<ClientSubnet>,
     cache: Arc<Mutex<DnsLru>>,
 }
 
@@ -387,27 +579,46 @@ impl Future for InsertCache {
             Ok(mut lru) => {
                 // this will put this object into an inconsistent state, but no one should call poll again...
                 let query = mem::replace(&mut self.query, Query::new());
-                let rdata = mem::replace(&mut self.rdatas, Records::NoData { ttl: None });
+                let rdata = mem::replace(
+                    &mut self.rdatas,
+                    Records::NoData {
+                        ttl: None,
+                        response_code: ResponseCode::NoError,
+                        soa: None,
+                    },
+                );
 
                 match rdata {
-                    Records::Exists(rdata) => {
-                        Ok(Async::Ready(lru.insert(query, rdata, Instant::now())))
-                    }
+                    Records::Exists(records) => Ok(Async::Ready(lru.insert_records(
+                        query,
+                        self.scope,
+                        records,
+                        Instant::now(),
+                    ))),
                     Records::Chained {
                         cached: lookup,
                         min_ttl: ttl,
+                        cnames,
                     } => Ok(Async::Ready(lru.duplicate(
                         query,
-                        lookup,
+                        self.scope,
+                        lookup.prepend_records(cnames),
                         ttl,
                         Instant::now(),
                     ))),
-                    Records::NoData { ttl: Some(ttl) } => {
-                        Err(lru.negative(query, ttl, Instant::now()))
-                    }
-                    Records::NoData { ttl: None } | Records::CnameChain { .. } => {
-                        Err(DnsLru::nx_error(query, None))
-                    }
+                    Records::NoData {
+                        ttl: Some(ttl),
+                        response_code,
+                        soa,
+                    } => Err(lru
+                        .negative(query, self.scope, ttl, Instant::now())
+                        .with_negative(response_code, soa)),
+                    Records::NoData {
+                        ttl: None,
+                        response_code,
+                        soa,
+                    } => Err(DnsLru::nx_error(query, None).with_negative(response_code, soa)),
+                    Records::CnameChain { .. } => Err(DnsLru::nx_error(query, None)),
                 }
             }
         }
@@ -424,6 +635,8 @@ enum QueryState<C: DnsHandle + 'static> {
         Box<Future<Item = Lookup, Error = ResolveError> + Send>,
         Query,
         u32,
+        Vec<Record>,
+        Option<ClientSubnet>,
         Arc<Mutex<DnsLru>>,
     ),
     /// State of adding the item to the cache
@@ -444,6 +657,7 @@ impl<C: DnsHandle + 'static> QueryState<C> {
                 query,
                 options,
                 cache,
+                prefetch: false,
             },
             client.clone(),
         )
@@ -473,6 +687,8 @@ impl<C: DnsHandle + 'static> QueryState<C> {
                         dnssec: client.is_verifying_dnssec(),
                         options,
                         client: CachingClient::with_cache(cache, client),
+                        refresh: false,
+                        scope: None,
                     }),
                 );
             }
@@ -484,6 +700,7 @@ impl<C: DnsHandle + 'static> QueryState<C> {
         &mut self,
         future: Box<Future<Item = Lookup, Error = ResolveError> + Send>,
         cname_ttl: u32,
+        cnames: Vec<Record>,
     ) {
         // The error state, this query is complete...
         let query_state = mem::replace(self, QueryState::Error);
@@ -496,10 +713,12 @@ impl<C: DnsHandle + 'static> QueryState<C> {
                 dnssec: _d,
                 options: _o,
                 client: _c,
+                refresh: _r,
+                scope,
             }) => {
                 mem::replace(
                     self,
-                    QueryState::CnameChain(future, query, cname_ttl, cache),
+                    QueryState::CnameChain(future, query, cname_ttl, cnames, scope, cache),
                 );
             }
             _ => panic!("bad state, expected Query"),
@@ -518,6 +737,8 @@ impl<C: DnsHandle + 'static> QueryState<C> {
                 dnssec: _d,
                 options: _o,
                 client: _c,
+                refresh: _r,
+                scope,
             }) => {
                 match rdatas {
                     // There are Cnames to lookup
@@ -530,13 +751,14 @@ impl<C: DnsHandle + 'static> QueryState<C> {
                             QueryState::InsertCache(InsertCache {
                                 rdatas,
                                 query,
+                                scope,
                                 cache,
                             }),
                         );
                     }
                 }
             }
-            QueryState::CnameChain(_, query, _, cache) => {
+            QueryState::CnameChain(_, query, _, _, scope, cache) => {
                 match rdatas {
                     // There are Cnames to lookup
                     Records::CnameChain { .. } => {
@@ -548,6 +770,7 @@ impl<C: DnsHandle + 'static> QueryState<C> {
                             QueryState::InsertCache(InsertCache {
                                 rdatas,
                                 query,
+                                scope,
                                 cache,
                             }),
                         );
@@ -567,11 +790,21 @@ impl<C: DnsHandle + 'static> Future for QueryState<C> {
         // first transition any polling that is needed (mutable refs...)
         let records: Option<Records>;
         match *self {
-            QueryState::FromCache(ref mut from_cache, ..) => {
+            QueryState::FromCache(ref mut from_cache, ref client) => {
                 match from_cache.poll() {
                     // need to query since it wasn't in the cache
                     Ok(Async::Ready(None)) => (), // handled below
-                    Ok(Async::Ready(Some(ips))) => return Ok(Async::Ready(ips)),
+                    Ok(Async::Ready(Some(ips))) => {
+                        if from_cache.prefetch {
+                            spawn_refresh(
+                                from_cache.query.clone(),
+                                from_cache.options.clone(),
+                                client.clone(),
+                                from_cache.cache.clone(),
+                            );
+                        }
+                        return Ok(Async::Ready(ips));
+                    }
                     Ok(Async::NotReady) => return Ok(Async::NotReady),
                     Err(error) => return Err(error),
                 };
@@ -586,11 +819,11 @@ impl<C: DnsHandle + 'static> Future for QueryState<C> {
                     }
                     Ok(Async::Ready(rdatas)) => records = Some(rdatas), // handled in next match
                     Err(e) => {
-                        return Err(e);
+                        return query.serve_stale(e);
                     }
                 }
             }
-            QueryState::CnameChain(ref mut future, _, ttl, _) => {
+            QueryState::CnameChain(ref mut future, _, ttl, ref mut cnames, _, _) => {
                 let poll = future.poll();
                 match poll {
                     Ok(Async::NotReady) => {
@@ -600,6 +833,7 @@ impl<C: DnsHandle + 'static> Future for QueryState<C> {
                         records = Some(Records::Chained {
                             cached: lookup,
                             min_ttl: ttl,
+                            cnames: mem::replace(cnames, vec![]),
                         });
                     }
                     Err(e) => {
@@ -620,7 +854,8 @@ impl<C: DnsHandle + 'static> Future for QueryState<C> {
                 Some(Records::CnameChain {
                     next: future,
                     min_ttl: ttl,
-                }) => self.cname(future, ttl),
+                    cnames,
+                }) => self.cname(future, ttl, cnames),
                 Some(records) => {
                     self.cache(records);
                 }
@@ -674,11 +909,95 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_negative_response() {
+        use proto::rr::rdata::SOA;
+
+        let cache = Arc::new(Mutex::new(DnsLru::new(1, dns_lru::TtlConfig::default())));
+        let query = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+        let soa = Record::from_rdata(
+            Name::from_str("example.com.").unwrap(),
+            3600,
+            RecordType::SOA,
+            RData::SOA(SOA::new(
+                Name::from_str("ns.example.com.").unwrap(),
+                Name::from_str("hostmaster.example.com.").unwrap(),
+                1,
+                3600,
+                600,
+                86400,
+                300,
+            )),
+        );
+        let mut nxdomain = Message::new();
+        nxdomain.set_response_code(ResponseCode::NXDomain);
+        nxdomain.add_name_server(soa.clone());
+        let mut client = mock(vec![Ok(nxdomain.into())]);
+
+        let error = QueryState::lookup(query, Default::default(), &mut client, cache)
+            .wait()
+            .unwrap_err();
+        assert_eq!(error.response_code(), Some(ResponseCode::NXDomain));
+        assert_eq!(error.soa(), Some(&soa));
+
+        // not from a response
+        let cache = Arc::new(Mutex::new(DnsLru::new(1, dns_lru::TtlConfig::default())));
+        let query = Query::query(Name::from_str("localhost.").unwrap(), RecordType::MX);
+        let error = CachingClient::with_cache(cache, mock(vec![]))
+            .lookup(query, Default::default())
+            .wait()
+            .unwrap_err();
+        assert_eq!(error.response_code(), None);
+    }
+
+    #[test]
+    fn test_client_subnet_answers() {
+        use proto::rr::rdata::opt::EdnsOption;
+
+        let cache = Arc::new(Mutex::new(DnsLru::new(1, dns_lru::TtlConfig::default())));
+        let query = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+        let subnet = |b| ClientSubnet::new(IpAddr::V4(Ipv4Addr::new(192, 0, b, 1)), 24, 0);
+        let options = |b| DnsRequestOptions {
+            client_subnet: Some(subnet(b)),
+            ..DnsRequestOptions::default()
+        };
+        let tailored = |b| -> ProtoResult<DnsResponse> {
+            let mut message = Message::new();
+            message.add_answer(Record::from_rdata(
+                Name::from_str("www.example.com.").unwrap(),
+                86400,
+                RecordType::A,
+                RData::A(Ipv4Addr::new(127, 0, 0, b)),
+            ));
+            let mut edns = Edns::new();
+            edns.set_option(EdnsOption::Subnet(subnet(b).with_scope_prefix(24)));
+            message.set_edns(edns);
+            Ok(message.into())
+        };
+        let lookup = |b, message| {
+            let mut client = mock(vec![message]);
+            QueryState::lookup(query.clone(), options(b), &mut client, cache.clone())
+                .wait()
+                .unwrap()
+                .iter()
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+        let ip = |b| vec![RData::A(Ipv4Addr::new(127, 0, 0, b))];
+
+        // the answers for both subnets are cached
+        assert_eq!(lookup(1, tailored(1)), ip(1));
+        assert_eq!(lookup(2, tailored(2)), ip(2));
+        assert_eq!(lookup(1, error()), ip(1));
+        assert_eq!(lookup(2, error()), ip(2));
+    }
+
     #[test]
     fn test_from_cache() {
         let cache = Arc::new(Mutex::new(DnsLru::new(1, dns_lru::TtlConfig::default())));
         cache.lock().unwrap().insert(
             Query::new(),
+            None,
             vec![(RData::A(Ipv4Addr::new(127, 0, 0, 1)), u32::max_value())],
             Instant::now(),
         );
@@ -750,6 +1069,45 @@ mod tests {
         Ok(message.into())
     }
 
+    #[test]
+    fn test_cname_records_keep_owner() {
+        let cache = Arc::new(Mutex::new(DnsLru::new(1, dns_lru::TtlConfig::default())));
+
+        let mut message = cname_message().unwrap();
+        message.add_answer(Record::from_rdata(
+            Name::from_str("actual.example.com.").unwrap(),
+            86400,
+            RecordType::A,
+            RData::A(Ipv4Addr::new(127, 0, 0, 1)),
+        ));
+        let mut client = mock(vec![Ok(message)]);
+
+        let lookup = QueryState::lookup(
+            Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A),
+            Default::default(),
+            &mut client,
+            cache,
+        ).wait()
+        .expect("lookup failed");
+
+        // the CNAME record is returned as it came, iter() only yields the addresses
+        let records = lookup.records();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].rr_type(), RecordType::CNAME);
+        assert_eq!(
+            *records[0].name(),
+            Name::from_str("www.example.com.").unwrap()
+        );
+        assert_eq!(
+            *records[1].name(),
+            Name::from_str("actual.example.com.").unwrap()
+        );
+        assert_eq!(
+            lookup.iter().cloned().collect::<Vec<_>>(),
+            vec![RData::A(Ipv4Addr::new(127, 0, 0, 1))]
+        );
+    }
+
     fn no_recursion_on_query_test(query_type: RecordType) {
         let cache = Arc::new(Mutex::new(DnsLru::new(1, dns_lru::TtlConfig::default())));
 
@@ -925,6 +1283,8 @@ mod tests {
             dnssec: false,
             options: Default::default(),
             client,
+            refresh: false,
+            scope: None,
         };
 
         let mut message = Message::new();
@@ -948,7 +1308,9 @@ mod tests {
         assert!(poll.is_ready());
         if let Async::Ready(records) = poll {
             if let Records::Exists(records) = records {
-                assert!(records.iter().all(|&(_, ttl)| ttl == 1));
+                // the CNAME record of the chain comes first
+                assert_eq!(records[0].rr_type(), RecordType::CNAME);
+                assert!(records[1..].iter().all(|record| record.ttl() == 1));
             } else {
                 panic!("records don't exist");
             }
@@ -967,7 +1329,7 @@ mod tests {
     fn test_early_return_localhost() {
         let cache = Arc::new(Mutex::new(DnsLru::new(0, dns_lru::TtlConfig::default())));
         let client = mock(vec![empty()]);
-        let mut client = CachingClient { lru: cache, client };
+        let mut client = CachingClient::with_cache(cache, client);
 
         {
             let query = Query::query(Name::from_ascii("localhost.").unwrap(), RecordType::A);
@@ -1047,7 +1409,7 @@ mod tests {
     fn test_early_return_invalid() {
         let cache = Arc::new(Mutex::new(DnsLru::new(0, dns_lru::TtlConfig::default())));
         let client = mock(vec![empty()]);
-        let mut client = CachingClient { lru: cache, client };
+        let mut client = CachingClient::with_cache(cache, client);
 
         assert!(
             client
@@ -1075,7 +1437,7 @@ mod tests {
         ));
 
         let client = mock(vec![error(), Ok(message)]);
-        let mut client = CachingClient { lru: cache, client };
+        let mut client = CachingClient::with_cache(cache, client);
 
         assert!(
             client
@@ -1089,4 +1451,173 @@ mod tests {
                 .is_ok()
         );
     }
+
+    fn stale_cache(now: Instant) -> Arc<Mutex<DnsLru>> {
+        let ttls = dns_lru::TtlConfig {
+            serve_stale: Some(Duration::from_secs(60)),
+            ..Default::default()
+        };
+        let cache = Arc::new(Mutex::new(DnsLru::new(1, ttls)));
+
+        // expired one second ago
+        cache.lock().unwrap().insert(
+            Query::new(),
+            None,
+            vec![(RData::A(Ipv4Addr::new(127, 0, 0, 2)), 1)],
+            now - Duration::from_secs(2),
+        );
+
+        cache
+    }
+
+    #[test]
+    fn test_serve_stale() {
+        let now = Instant::now();
+        let cache = stale_cache(now);
+        let mut client = mock(vec![error()]);
+
+        let ips = QueryState::lookup(Query::new(), Default::default(), &mut client, cache)
+            .wait()
+            .unwrap();
+
+        assert_eq!(
+            ips.iter().cloned().collect::<Vec<_>>(),
+            vec![RData::A(Ipv4Addr::new(127, 0, 0, 2))]
+        );
+        let stale_ttl = Duration::from_secs(u64::from(dns_lru::STALE_TTL));
+        assert!(ips.valid_until() <= Instant::now() + stale_ttl);
+        assert!(ips.valid_until() > now);
+    }
+
+    #[test]
+    fn test_stale_not_served_on_success() {
+        let cache = stale_cache(Instant::now());
+        let mut client = mock(vec![v4_message()]);
+
+        let ips = QueryState::lookup(Query::new(), Default::default(), &mut client, cache)
+            .wait()
+            .unwrap();
+
+        assert_eq!(
+            ips.iter().cloned().collect::<Vec<_>>(),
+            vec![RData::A(Ipv4Addr::new(127, 0, 0, 1))]
+        );
+    }
+
+    #[test]
+    fn test_serve_stale_refreshes() {
+        use tokio::runtime::current_thread::Runtime;
+
+        let cache = stale_cache(Instant::now());
+        // the lookup fails, the background refresh succeeds
+        let mut client = mock(vec![v4_message(), error()]);
+
+        let mut io_loop = Runtime::new().unwrap();
+        let ips = io_loop
+            .block_on(QueryState::lookup(
+                Query::new(),
+                Default::default(),
+                &mut client,
+                cache.clone(),
+            )).unwrap();
+        assert_eq!(
+            ips.iter().cloned().collect::<Vec<_>>(),
+            vec![RData::A(Ipv4Addr::new(127, 0, 0, 2))]
+        );
+
+        io_loop.run().unwrap();
+
+        let ips = cache
+            .lock()
+            .unwrap()
+            .get(&Query::new(), None, Instant::now())
+            .unwrap();
+        assert_eq!(
+            ips.iter().cloned().collect::<Vec<_>>(),
+            vec![RData::A(Ipv4Addr::new(127, 0, 0, 1))]
+        );
+    }
+
+    #[test]
+    fn test_prefetch() {
+        use tokio::runtime::current_thread::Runtime;
+
+        let ttls = dns_lru::TtlConfig {
+            prefetch_threshold: Some(10),
+            ..Default::default()
+        };
+        let cache = Arc::new(Mutex::new(DnsLru::new(1, ttls)));
+
+        // 5 of 100 seconds remaining
+        cache.lock().unwrap().insert(
+            Query::new(),
+            None,
+            vec![(RData::A(Ipv4Addr::new(127, 0, 0, 2)), 100)],
+            Instant::now() - Duration::from_secs(95),
+        );
+        let mut client = mock(vec![v4_message()]);
+
+        // the cached lookup is returned, and refreshed in the background
+        let mut io_loop = Runtime::new().unwrap();
+        let ips = io_loop
+            .block_on(QueryState::lookup(
+                Query::new(),
+                Default::default(),
+                &mut client,
+                cache.clone(),
+            )).unwrap();
+        assert_eq!(
+            ips.iter().cloned().collect::<Vec<_>>(),
+            vec![RData::A(Ipv4Addr::new(127, 0, 0, 2))]
+        );
+
+        io_loop.run().unwrap();
+
+        let ips = cache
+            .lock()
+            .unwrap()
+            .get(&Query::new(), None, Instant::now())
+            .unwrap();
+        assert_eq!(
+            ips.iter().cloned().collect::<Vec<_>>(),
+            vec![RData::A(Ipv4Addr::new(127, 0, 0, 1))]
+        );
+    }
+
+    #[test]
+    fn test_dns64_synthesis() {
+        let cache = Arc::new(Mutex::new(DnsLru::new(2, dns_lru::TtlConfig::default())));
+        // the AAAA lookup is answered without records, the A lookup with 127.0.0.1
+        let client = mock(vec![v4_message(), empty()]);
+        let mut client = CachingClient::with_cache(cache, client)
+            .with_dns64(Some(Arc::new(Dns64Config::default())));
+
+        let ips = client
+            .lookup(Query::query(Name::root(), RecordType::AAAA), Default::default())
+            .wait()
+            .unwrap();
+
+        assert_eq!(
+            ips.iter().cloned().collect::<Vec<_>>(),
+            vec![RData::AAAA(Ipv6Addr::from_str("64:ff9b::7f00:1").unwrap())]
+        );
+    }
+
+    #[test]
+    fn test_dns64_native_aaaa() {
+        let cache = Arc::new(Mutex::new(DnsLru::new(2, dns_lru::TtlConfig::default())));
+        let client = mock(vec![v4_message(), v6_message()]);
+        let mut client = CachingClient::with_cache(cache, client)
+            .with_dns64(Some(Arc::new(Dns64Config::default())));
+
+        let ips = client
+            .lookup(Query::query(Name::root(), RecordType::AAAA), Default::default())
+            .wait()
+            .unwrap();
+
+        assert_eq!(
+            ips.iter().cloned().collect::<Vec<_>>(),
+            vec![RData::AAAA(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1))]
+        );
+    }
 }
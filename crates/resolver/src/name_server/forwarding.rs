@@ -0,0 +1,124 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::cmp::Reverse;
+
+use proto::rr::Name;
+use proto::xfer::{DnsHandle, DnsRequest};
+
+/// Sends each request to the handle of the most specific domain containing the name of its query
+///
+/// This implements conditional forwarding, see `ResolverConfig::add_forward_zone`. Requests for names outside of all
+///  the domains are sent to the default handle.
+#[derive(Clone)]
+pub struct ForwardingHandle<H: DnsHandle> {
+    default: Box<H>,
+    // sorted from the most to the least specific domain
+    zones: Vec<(Name, H)>,
+}
+
+impl<H: DnsHandle> ForwardingHandle<H> {
+    /// Creates a new handle
+    ///
+    /// # Arguments
+    ///
+    /// * `default` - handle for all names not within any of the `zones`
+    /// * `zones` - domains and the handles for all names within them
+    pub fn new(default: H, mut zones: Vec<(Name, H)>) -> Self {
+        zones.sort_by_key(|(zone, _)| Reverse(zone.num_labels()));
+
+        ForwardingHandle {
+            default: Box::new(default),
+            zones,
+        }
+    }
+
+    /// Returns the handle for the longest domain containing the name
+    fn handle_for(&self, name: &Name) -> &H {
+        self.zones
+            .iter()
+            .find(|(zone, _)| zone.zone_of(name))
+            .map_or(&*self.default, |(_, handle)| handle)
+    }
+}
+
+impl<H: DnsHandle> DnsHandle for ForwardingHandle<H> {
+    type Response = H::Response;
+
+    fn is_verifying_dnssec(&self) -> bool {
+        // negative responses are only cached when none of the handles is validating
+        self.default.is_verifying_dnssec()
+            || self.zones.iter().any(|(_, handle)| handle.is_verifying_dnssec())
+    }
+
+    fn send<R: Into<DnsRequest>>(&mut self, request: R) -> Self::Response {
+        let request = request.into();
+        let mut handle = match request.queries().first() {
+            Some(query) => self.handle_for(query.name()).clone(),
+            None => (*self.default).clone(),
+        };
+
+        handle.send(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use futures::{future, Future};
+
+    use proto::error::ProtoError;
+    use proto::op::{Message, Query};
+    use proto::rr::RecordType;
+    use proto::xfer::{DnsRequestOptions, DnsResponse};
+
+    use super::*;
+
+    /// Responds with a message with its id
+    #[derive(Clone)]
+    struct IdHandle(u16);
+
+    impl DnsHandle for IdHandle {
+        type Response = Box<Future<Item = DnsResponse, Error = ProtoError> + Send>;
+
+        fn send<R: Into<DnsRequest>>(&mut self, _: R) -> Self::Response {
+            let mut message = Message::new();
+            message.set_id(self.0);
+            Box::new(future::ok(message.into()))
+        }
+    }
+
+    fn id_for(handle: &mut ForwardingHandle<IdHandle>, name: &str) -> u16 {
+        let query = Query::query(Name::from_str(name).unwrap(), RecordType::A);
+        handle
+            .lookup(query, DnsRequestOptions::default())
+            .wait()
+            .unwrap()
+            .id()
+    }
+
+    #[test]
+    fn test_longest_suffix() {
+        let mut handle = ForwardingHandle::new(
+            IdHandle(0),
+            vec![
+                (Name::from_str("example.").unwrap(), IdHandle(1)),
+                (Name::from_str("corp.example.").unwrap(), IdHandle(2)),
+                (Name::from_str("consul.").unwrap(), IdHandle(3)),
+            ],
+        );
+
+        assert_eq!(id_for(&mut handle, "www.example.com."), 0);
+        assert_eq!(id_for(&mut handle, "example."), 1);
+        assert_eq!(id_for(&mut handle, "www.example."), 1);
+        assert_eq!(id_for(&mut handle, "corp.example."), 2);
+        assert_eq!(id_for(&mut handle, "host.CORP.example."), 2);
+        assert_eq!(id_for(&mut handle, "service.consul."), 3);
+        assert_eq!(id_for(&mut handle, "consul.example.com."), 0);
+    }
+}
@@ -6,6 +6,7 @@
 // copied, modified, or distributed except according to those terms.
 
 mod connection_provider;
+mod forwarding;
 mod name_server_pool;
 mod name_server_state;
 mod name_server_stats;
@@ -14,8 +15,10 @@ mod name_server;
 
 use self::name_server_state::NameServerState;
 use self::name_server_stats::NameServerStats;
+pub use self::name_server_stats::ServerStats;
 pub use self::name_server_pool::NameServerPool;
 pub use self::connection_provider::ConnectionProvider;
+pub use self::forwarding::ForwardingHandle;
 pub(crate) use self::connection_provider::{StandardConnection, ConnectionHandle};
 pub use self::name_server::NameServer;
 #[cfg(feature = "mdns")]
@@ -22,7 +22,7 @@ use proto::xfer::{DnsHandle, DnsRequest, DnsResponse};
 use config::Protocol;
 use config::{NameServerConfig, ResolverOpts};
 use name_server::NameServerState;
-use name_server::NameServerStats;
+use name_server::{NameServerStats, ServerStats};
 use name_server::{ConnectionHandle, ConnectionProvider, StandardConnection};
 
 /// Specifies the details of a remote NameServer used for lookups
@@ -105,6 +105,19 @@ impl<C: DnsHandle, P: ConnectionProvider<ConnHandle = C>> NameServer<C, P> {
             .as_mut()
             .expect("bad state, client should be connected"))
     }
+
+    /// Returns a snapshot of the statistics of this NameServer
+    pub fn stats(&self) -> ServerStats {
+        ServerStats::new(self.config.clone(), !self.state.is_failed(), &self.stats)
+    }
+
+    /// The key to sort NameServers by, the lowest is the one to use first
+    ///
+    /// This prefers servers which have not failed on their last request, and then the fastest of those.
+    pub(crate) fn selection_key(&self, now: Instant) -> (bool, u64, usize) {
+        let (srtt, failures) = self.stats.selection_key(now);
+        (self.state.is_failed(), srtt, failures)
+    }
 }
 
 impl<C, P> DnsHandle for NameServer<C, P>
@@ -121,6 +134,7 @@ where
     // TODO: there needs to be some way of customizing the connection based on EDNS options from the server side...
     fn send<R: Into<DnsRequest>>(&mut self, request: R) -> Self::Response {
         let distrust_nx_responses = self.options.distrust_nx_responses;
+        let timeout = self.options.timeout;
 
         // grab a reference to the stats for this NameServer
         let stats1 = self.stats.clone();
@@ -134,6 +148,8 @@ where
             Err(e) => return Box::new(future::err(e)) as Self::Response,
         };
 
+        let sent = Instant::now();
+
         // Becuase a Poisoned lock error could have occured, make sure to create a new Mutex...
         Box::new(
             client
@@ -160,8 +176,8 @@ where
                     // take the remote edns options and store them
                     state1.establish(remote_edns);
 
-                    // record the success
-                    stats1.next_success();
+                    // record the success, along with the round trip time
+                    stats1.next_success(sent.elapsed());
 
                     future::ok(response)
                 })
@@ -172,7 +188,7 @@ where
                     state2.fail(Instant::now());
 
                     // recrod the failure
-                    stats2.next_failure();
+                    stats2.next_failure(timeout);
 
                     // These are connection failures, not lookup failures, that is handled in the resolver layer
                     future::err(error)
@@ -189,21 +205,8 @@ impl<C: DnsHandle, P: ConnectionProvider<ConnHandle = C>> Ord for NameServer<C,
             return Ordering::Equal;
         }
 
-        // otherwise, run our evaluation to determine the next to be returned from the Heap
-        //   this will prefer established connections, we should try other connections after
-        //   some number to make sure that all are used. This is more important for when
-        //   letency is started to be used.
-        match self
-            .state
-            .cmp(&other.state)
-        {
-            Ordering::Equal => (),
-            o => {
-                return o;
-            }
-        }
-
-        self.stats.cmp(&other.stats)
+        let now = Instant::now();
+        self.selection_key(now).cmp(&other.selection_key(now))
     }
 }
 
@@ -304,4 +307,49 @@ mod tests {
             )))
             .is_err());
     }
+
+    #[test]
+    fn test_selection_order() {
+        let name_server = |octet| {
+            let config = NameServerConfig {
+                socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, octet)), 53),
+                protocol: Protocol::Udp,
+                tls_dns_name: None,
+            };
+            NameServer::<_, StandardConnection>::new(config, ResolverOpts::default())
+        };
+
+        let slow = name_server(1);
+        slow.stats.next_success(Duration::from_millis(200));
+        let fast = name_server(2);
+        fast.stats.next_success(Duration::from_millis(20));
+        let failed = name_server(3);
+        failed.stats.next_success(Duration::from_millis(5));
+        failed.state.fail(Instant::now());
+        let untried = name_server(4);
+
+        let mut name_servers = vec![failed, slow, fast, untried];
+        let now = Instant::now();
+        name_servers.sort_by_cached_key(|name_server| name_server.selection_key(now));
+
+        let order = name_servers
+            .iter()
+            .map(|name_server| name_server.config.socket_addr.ip())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            order,
+            vec![
+                IpAddr::V4(Ipv4Addr::new(127, 0, 0, 4)),
+                IpAddr::V4(Ipv4Addr::new(127, 0, 0, 2)),
+                IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+                IpAddr::V4(Ipv4Addr::new(127, 0, 0, 3)),
+            ]
+        );
+
+        let stats = name_servers[3].stats();
+        assert!(!stats.is_healthy());
+        assert_eq!(stats.successes(), 1);
+        assert_eq!(stats.srtt(), Some(Duration::from_millis(5)));
+        assert_eq!(name_servers[0].stats().srtt(), None);
+    }
 }
@@ -5,21 +5,27 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex, TryLockError};
+use std::time::Instant;
 
 use futures::future::Loop;
 use futures::{future, task, Async, Future, IntoFuture, Poll};
 use smallvec::SmallVec;
 
 use proto::error::ProtoError;
-use proto::op::ResponseCode;
+use proto::op::{Edns, ResponseCode};
+use proto::rr::rdata::opt::{EdnsCode, EdnsOption};
 use proto::xfer::{DnsHandle, DnsRequest, DnsResponse};
 
 use config::{ResolverConfig, ResolverOpts};
-use name_server::{NameServer, ConnectionHandle, ConnectionProvider, StandardConnection};
+use name_server::{NameServer, ConnectionHandle, ConnectionProvider, ServerStats, StandardConnection};
 #[cfg(feature = "mdns")]
 use name_server;
 
+/// Every this many requests a server other than the fastest is tried first, to refresh its round trip time
+const EXPLORATION_INTERVAL: usize = 16;
+
 /// A pool of NameServers
 ///
 /// This is not expected to be used directly, see `ResolverFuture`.
@@ -32,6 +38,7 @@ pub struct NameServerPool<C: DnsHandle + 'static, P: ConnectionProvider<ConnHand
     mdns_conns: NameServer<C, P>, /* All NameServers must be the same type */
     options: ResolverOpts,
     conn_provider: P,
+    requests: Arc<AtomicUsize>,
 }
 
 impl NameServerPool<ConnectionHandle, StandardConnection> {
@@ -77,6 +84,7 @@ impl<C: DnsHandle + 'static, P: ConnectionProvider<ConnHandle = C> + 'static> Na
             mdns_conns: name_server::mdns_nameserver(*options, conn_provider.clone()),
             options: *options,
             conn_provider,
+            requests: Arc::new(AtomicUsize::new(0)),
         }
     }
 
@@ -93,6 +101,7 @@ impl<C: DnsHandle + 'static, P: ConnectionProvider<ConnHandle = C> + 'static> Na
             stream_conns: Arc::new(Mutex::new(stream_conns.into_iter().collect())),
             options: *options,
             conn_provider,
+            requests: Arc::new(AtomicUsize::new(0)),
         }
     }
 
@@ -111,18 +120,31 @@ impl<C: DnsHandle + 'static, P: ConnectionProvider<ConnHandle = C> + 'static> Na
             mdns_conns,
             options: *options,
             conn_provider,
+            requests: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Returns a snapshot of the statistics of all NameServers, the datagram ones first
+    pub fn stats(&self) -> Vec<ServerStats> {
+        let mut stats = Vec::new();
+        for conns in &[&self.datagram_conns, &self.stream_conns] {
+            let conns = conns.lock().expect("poisoned lock");
+            stats.extend(conns.iter().map(NameServer::stats));
         }
+        stats
     }
 
     fn try_send(
         opts: ResolverOpts,
         conns: Arc<Mutex<Vec<NameServer<C, P>>>>,
         request: DnsRequest,
+        count: usize,
     ) -> TrySend<C, P> {
         TrySend::Lock {
             opts,
             conns,
             request: Some(request),
+            count,
         }
     }
 }
@@ -136,7 +158,32 @@ where
 
     fn send<R: Into<DnsRequest>>(&mut self, request: R) -> Self::Response {
         let opts = self.options;
-        let request = request.into();
+        let mut request = request.into();
+
+        let client_subnet = request.edns().and_then(Edns::client_subnet).cloned();
+        match opts.client_subnet.apply(client_subnet) {
+            Some(client_subnet) => request
+                .edns_mut()
+                .set_option(EdnsOption::Subnet(client_subnet)),
+            None => {
+                if request.edns().is_some() {
+                    request.edns_mut().remove_option(EdnsCode::Subnet);
+                }
+            }
+        }
+
+        // EDNS is always kept when it's needed for DNSSec or the client subnet
+        if !opts.edns0
+            && !request.edns().map_or(false, |edns| {
+                edns.dnssec_ok() || edns.client_subnet().is_some()
+            })
+        {
+            request.take_edns();
+        }
+        if opts.trust_ad {
+            request.set_authentic_data(true);
+        }
+
         let datagram_conns = Arc::clone(&self.datagram_conns);
         let stream_conns1 = Arc::clone(&self.stream_conns);
         let stream_conns2 = Arc::clone(&self.stream_conns);
@@ -144,6 +191,9 @@ where
         let tcp_message1 = request.clone();
         let tcp_message2 = request.clone();
 
+        // the number of this request, for rotating through the servers and exploration
+        let count = self.requests.fetch_add(1, Ordering::Relaxed) + 1;
+
         // if it's a .local. query, then we *only* query mDNS, these should never be sent on to upstream resolvers
         #[cfg(feature = "mdns")]
         let mdns = mdns::maybe_local(&mut self.mdns_conns, request);
@@ -161,23 +211,44 @@ where
 
         // it wasn't a local query, continue with standard looup path
         let request = mdns.take_request();
-        Box::new(
+
+        // all requests are sent over the stream connections
+        if opts.use_vc {
+            let future = Self::try_send(opts, stream_conns2, request, count);
+            return Box::new(future.map(move |response| clear_authentic_data(opts, response)));
+        }
+
+        let future = Box::new(
             // First try the UDP connections
-            Self::try_send(opts, datagram_conns, request)
+            Self::try_send(opts, datagram_conns, request, count)
                 .and_then(move |response| {
                     // handling promotion from datagram to stream base on truncation in message
                     if ResponseCode::NoError == response.response_code() && response.truncated() {
                         // TCP connections should not truncate
-                        future::Either::A(Self::try_send(opts, stream_conns1, tcp_message1))
+                        future::Either::A(Self::try_send(opts, stream_conns1, tcp_message1, count))
                     } else {
                         // Return the result from the UDP connection
                         future::Either::B(future::ok(response))
                     }
                 })
                 // if UDP fails, try TCP
-                .or_else(move |_| Self::try_send(opts, stream_conns2, tcp_message2)),
-        )
+                .or_else(move |_| Self::try_send(opts, stream_conns2, tcp_message2, count)),
+        );
+
+        Box::new(future.map(move |response| clear_authentic_data(opts, response)))
+    }
+}
+
+/// Clears the AD bit of the response for the options of resolv.conf, unless the name servers are trusted to set it,
+///  see `ResolverOpts::trust_ad`
+fn clear_authentic_data(opts: ResolverOpts, mut response: DnsResponse) -> DnsResponse {
+    if opts.clear_untrusted_ad && !opts.trust_ad {
+        for message in response.messages_mut() {
+            message.set_authentic_data(false);
+        }
     }
+
+    response
 }
 
 #[allow(clippy::large_enum_variant)]
@@ -186,6 +257,7 @@ enum TrySend<C: DnsHandle + 'static, P: ConnectionProvider<ConnHandle = C> + 'st
         opts: ResolverOpts,
         conns: Arc<Mutex<Vec<NameServer<C, P>>>>,
         request: Option<DnsRequest>,
+        count: usize,
     },
     DoSend(Box<Future<Item = DnsResponse, Error = ProtoError> + Send>),
 }
@@ -207,6 +279,7 @@ where
                 ref opts,
                 ref conns,
                 ref mut request,
+                count,
             } => {
                 // pull a lock on the shared connections, lock releases at the end of the method
                 let conns = conns.try_lock();
@@ -225,13 +298,30 @@ where
 
                         // select the highest priority connection
                         //   reorder the connections based on current view...
-                        //   this reorders the inner set
-                        conns.sort_unstable();
+                        //   this reorders the inner set, the keys are taken once as the stats change concurrently
+                        if !opts.rotate {
+                            let now = Instant::now();
+                            conns.sort_by_cached_key(|conn| conn.selection_key(now));
+                        }
 
                         // TODO: restrict this size to a maximum # of NameServers to try
                         // get a stable view for trying all connections
                         //   we split into chunks of the numeber of parallel requests to issue
                         let mut conns: Vec<NameServer<C, P>> = conns.clone();
+
+                        if conns.len() > 1 {
+                            if opts.rotate {
+                                // start with the server after the one the previous request started with
+                                let len = conns.len();
+                                conns.rotate_left(count % len);
+                            } else if count % EXPLORATION_INTERVAL == 0 {
+                                // cycle through the slower servers, moving one to the front
+                                let round = count / EXPLORATION_INTERVAL;
+                                let index = 1 + round % (conns.len() - 1);
+                                let conn = conns.remove(index);
+                                conns.insert(0, conn);
+                            }
+                        }
                         let request = request.take();
                         let request = request.expect("bad state, mesage should never be None");
                         let request_loop = request.clone();
@@ -380,7 +470,7 @@ mod tests {
 
     use tokio::runtime::current_thread::Runtime;
 
-    use proto::op::Query;
+    use proto::op::{Message, Query};
     use proto::rr::{Name, RecordType};
     use proto::xfer::{DnsHandle, DnsRequestOptions};
 
@@ -388,6 +478,24 @@ mod tests {
     use config::Protocol;
     use config::NameServerConfig;
 
+    #[test]
+    fn test_clear_authentic_data() {
+        let mut message = Message::new();
+        message.set_authentic_data(true);
+        let response = DnsResponse::from(message);
+
+        // passed through by default
+        let mut options = ResolverOpts::default();
+        assert!(clear_authentic_data(options, response.clone()).authentic_data());
+
+        // cleared for the options of resolv.conf, unless trusted
+        options.clear_untrusted_ad = true;
+        assert!(!clear_authentic_data(options, response.clone()).authentic_data());
+
+        options.trust_ad = true;
+        assert!(clear_authentic_data(options, response).authentic_data());
+    }
+
     #[ignore]
     // because of there is a real connection that needs a reasonable timeout
     #[test]
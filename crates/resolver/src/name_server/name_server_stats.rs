@@ -6,13 +6,48 @@
 // copied, modified, or distributed except according to those terms.
 
 use std::cmp::Ordering;
-
 use std::sync::atomic::{self, AtomicUsize};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use config::NameServerConfig;
+
+/// Weight of each new sample in the smoothed round trip time, the same as for the SRTT of RFC 6298
+const RTT_ALPHA: f64 = 0.125;
+
+/// Seconds after which the smoothed round trip time of a server halves while no new samples are recorded
+///
+/// Without this decay a server which was slow or failed once would never be selected again.
+const RTT_HALF_LIFE_SECS: f64 = 60.0;
 
 pub(crate) struct NameServerStats {
     successes: AtomicUsize,
     failures: AtomicUsize,
-    // TODO: incorporate latency
+    rtt: Mutex<Option<SmoothedRtt>>,
+}
+
+/// An exponentially weighted moving average of the round trip times
+#[derive(Clone, Copy)]
+struct SmoothedRtt {
+    micros: f64,
+    updated: Instant,
+}
+
+impl SmoothedRtt {
+    /// The average decayed by the time since the last sample
+    fn decayed(&self, now: Instant) -> f64 {
+        if now <= self.updated {
+            return self.micros;
+        }
+
+        let elapsed = now.duration_since(self.updated);
+        let elapsed = elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) / 1e9;
+        self.micros * 0.5_f64.powf(elapsed / RTT_HALF_LIFE_SECS)
+    }
+}
+
+fn to_micros(duration: Duration) -> f64 {
+    duration.as_secs() as f64 * 1e6 + f64::from(duration.subsec_micros())
 }
 
 impl Default for NameServerStats {
@@ -26,67 +61,145 @@ impl NameServerStats {
         NameServerStats {
             successes: AtomicUsize::new(successes),
             failures: AtomicUsize::new(failures),
+            rtt: Mutex::new(None),
         }
     }
 
-    pub fn next_success(&self) {
+    /// Records a response, received `rtt` after the request was sent
+    pub fn next_success(&self, rtt: Duration) {
         self.successes.fetch_add(1, atomic::Ordering::Release);
-   }
+        self.record_rtt(to_micros(rtt), Instant::now());
+    }
 
-    pub fn next_failure(&self) {
+    /// Records a failed request
+    ///
+    /// The failure counts as a round trip time of double the current one, at most `timeout`, so that repeated
+    ///  failures back off the server like retransmission timeouts do.
+    pub fn next_failure(&self, timeout: Duration) {
         self.failures.fetch_add(1, atomic::Ordering::Release);
+
+        let timeout = to_micros(timeout);
+        let now = Instant::now();
+        let penalty = match *self.rtt.lock().expect("poisoned lock") {
+            Some(ref rtt) => (rtt.micros * 2.0).min(timeout),
+            None => timeout,
+        };
+        self.record_rtt(penalty, now);
+    }
+
+    fn record_rtt(&self, sample: f64, now: Instant) {
+        let mut rtt = self.rtt.lock().expect("poisoned lock");
+        let micros = match *rtt {
+            Some(ref rtt) => rtt.micros + RTT_ALPHA * (sample - rtt.micros),
+            None => sample,
+        };
+
+        *rtt = Some(SmoothedRtt {
+            micros,
+            updated: now,
+        });
+    }
+
+    /// The smoothed round trip time, `None` if no request has completed yet
+    pub fn srtt(&self) -> Option<Duration> {
+        self.rtt
+            .lock()
+            .expect("poisoned lock")
+            .map(|rtt| Duration::from_micros(rtt.micros as u64))
+    }
+
+    /// The smoothed round trip time decayed until `now`, in microseconds
+    ///
+    /// Servers without any samples have a round trip time of zero, so that they are tried first.
+    pub fn decayed_srtt(&self, now: Instant) -> u64 {
+        self.rtt
+            .lock()
+            .expect("poisoned lock")
+            .map_or(0, |rtt| rtt.decayed(now) as u64)
+    }
+
+    pub fn successes(&self) -> usize {
+        self.successes.load(atomic::Ordering::Acquire)
     }
 
-    fn noload_eq(self_successes: usize, other_successes: usize, self_failures: usize, other_failures: usize) -> bool {
-        self_successes == other_successes && self_failures == other_failures
+    pub fn failures(&self) -> usize {
+        self.failures.load(atomic::Ordering::Acquire)
+    }
+
+    /// The key to sort servers by, the lowest is the one to use first
+    pub fn selection_key(&self, now: Instant) -> (u64, usize) {
+        (self.decayed_srtt(now), self.failures())
     }
 }
 
 impl PartialEq for NameServerStats {
     fn eq(&self, other: &Self) -> bool {
-        let self_successes = self.successes.load(atomic::Ordering::Acquire);
-        let other_successes = other.successes.load(atomic::Ordering::Acquire);
-
-        let self_failures = self.failures.load(atomic::Ordering::Acquire);
-        let other_failures = other.failures.load(atomic::Ordering::Acquire);
-
-        // if they are literally equal, just return
-        Self::noload_eq(self_successes, other_successes, self_failures, other_failures)
+        self.cmp(other) == Ordering::Equal
     }
 }
 
 impl Eq for NameServerStats {}
 
-
 impl Ord for NameServerStats {
-    /// Custom implementation of Ord for NameServer which incorporates the performance of the connection into it's ranking
+    /// Orders by the decayed round trip time, then by the number of failures, the faster server is the lesser
     fn cmp(&self, other: &Self) -> Ordering {
-        let self_successes = self.successes.load(atomic::Ordering::Acquire);
-        let other_successes = other.successes.load(atomic::Ordering::Acquire);
+        let now = Instant::now();
+        self.selection_key(now).cmp(&other.selection_key(now))
+    }
+}
+
+impl PartialOrd for NameServerStats {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
 
-        let self_failures = self.failures.load(atomic::Ordering::Acquire);
-        let other_failures = other.failures.load(atomic::Ordering::Acquire);
+/// A snapshot of the statistics of a name server, see `AsyncResolver::name_server_stats`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ServerStats {
+    config: NameServerConfig,
+    healthy: bool,
+    successes: usize,
+    failures: usize,
+    srtt: Option<Duration>,
+}
 
-        // if they are literally equal, just return
-        if Self::noload_eq(self_successes, other_successes, self_failures, other_failures) {
-            return Ordering::Equal;
+impl ServerStats {
+    pub(crate) fn new(config: NameServerConfig, healthy: bool, stats: &NameServerStats) -> Self {
+        ServerStats {
+            config,
+            healthy,
+            successes: stats.successes(),
+            failures: stats.failures(),
+            srtt: stats.srtt(),
         }
+    }
+
+    /// The configuration of the name server
+    pub fn config(&self) -> &NameServerConfig {
+        &self.config
+    }
 
-        // TODO: track latency and use lowest latency connection...
+    /// False if the last request to the server failed
+    pub fn is_healthy(&self) -> bool {
+        self.healthy
+    }
 
-        // invert failure comparison, i.e. the one with the least failures, wins
-        if self_failures <= other_failures {
-            return Ordering::Greater;
-        }
+    /// Number of requests answered by the server
+    pub fn successes(&self) -> usize {
+        self.successes
+    }
 
-        // at this point we'll go with the lesser of successes to make sure there is ballance
-        self_successes.cmp(&other_successes)
+    /// Number of requests which failed, e.g. timed out
+    pub fn failures(&self) -> usize {
+        self.failures
     }
-}
 
-impl PartialOrd for NameServerStats {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
+    /// The smoothed round trip time of requests to the server, `None` if none has completed yet
+    ///
+    /// Failed requests count as a round trip time of at most the timeout of the `ResolverOpts`.
+    pub fn srtt(&self) -> Option<Duration> {
+        self.srtt
     }
 }
 
@@ -107,12 +220,66 @@ mod tests {
 
     #[test]
     fn test_state_cmp() {
-        let nil = NameServerStats::new(0,0);
-        let successes = NameServerStats::new(1,0);
-        let failures = NameServerStats::new(0,1);
+        let nil = NameServerStats::new(0, 0);
+        let successes = NameServerStats::new(1, 0);
+        let failures = NameServerStats::new(0, 1);
 
         assert_eq!(nil.cmp(&nil), Ordering::Equal);
-        assert_eq!(nil.cmp(&successes), Ordering::Greater);
-        assert_eq!(successes.cmp(&failures), Ordering::Greater);
+        assert_eq!(nil.cmp(&successes), Ordering::Equal);
+        assert_eq!(successes.cmp(&failures), Ordering::Less);
+
+        let fast = NameServerStats::default();
+        fast.next_success(Duration::from_millis(10));
+        let slow = NameServerStats::default();
+        slow.next_success(Duration::from_millis(100));
+
+        assert_eq!(fast.cmp(&slow), Ordering::Less);
+        assert_eq!(nil.cmp(&fast), Ordering::Less);
+    }
+
+    #[test]
+    fn test_smoothed_rtt() {
+        let stats = NameServerStats::default();
+        assert_eq!(stats.srtt(), None);
+
+        let now = Instant::now();
+        stats.record_rtt(80_000.0, now);
+        assert_eq!(stats.srtt(), Some(Duration::from_millis(80)));
+
+        // a single outlier moves the average by an eighth of the difference
+        stats.record_rtt(160_000.0, now);
+        assert_eq!(stats.srtt(), Some(Duration::from_millis(90)));
+    }
+
+    #[test]
+    fn test_failure_backoff() {
+        let timeout = Duration::from_secs(5);
+        let stats = NameServerStats::default();
+        stats.next_failure(timeout);
+        assert_eq!(stats.srtt(), Some(timeout));
+        assert_eq!(stats.failures(), 1);
+
+        let stats = NameServerStats::default();
+        stats.record_rtt(10_000.0, Instant::now());
+        stats.next_failure(timeout);
+        // 10ms + (20ms - 10ms) / 8
+        assert_eq!(stats.srtt(), Some(Duration::from_micros(11_250)));
+    }
+
+    #[test]
+    fn test_decay() {
+        let now = Instant::now();
+        let stats = NameServerStats::default();
+        stats.record_rtt(100_000.0, now);
+
+        assert_eq!(stats.decayed_srtt(now), 100_000);
+        assert_eq!(stats.decayed_srtt(now + Duration::from_secs(60)), 50_000);
+        assert_eq!(stats.decayed_srtt(now + Duration::from_secs(120)), 25_000);
+
+        // the decayed server is eventually preferred over a faster one which is in use
+        let fast = NameServerStats::default();
+        fast.record_rtt(10_000.0, now + Duration::from_secs(300));
+        let later = now + Duration::from_secs(300);
+        assert!(stats.selection_key(later) < fast.selection_key(later));
     }
 }
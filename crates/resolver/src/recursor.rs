@@ -0,0 +1,1249 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Iterative resolution starting from a set of root hints.
+//!
+//! Unlike the `AsyncResolver`, which forwards recursive queries to upstream resolvers, the
+//! `Recursor` follows referrals itself, beginning at the root servers and walking down the
+//! delegation chain until an authoritative answer is found.
+
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures::future::{self, Loop};
+use futures::Future;
+use lru_cache::LruCache;
+
+use proto::op::{Message, MessageType, OpCode, Query, ResponseCode};
+use proto::rr::{Name, RData, Record, RecordType};
+use proto::xfer::{DnsHandle, DnsRequest, DnsRequestOptions};
+
+use config::{
+    LookupIpStrategy, NameServerConfigGroup, QnameMinimization, ResolverConfig, ResolverOpts,
+};
+use error::*;
+use name_server::{ConnectionHandle, ConnectionProvider, NameServerPool, StandardConnection};
+
+const MAX_QUERY_DEPTH: u8 = 8; // arbitrarily chosen number...
+const MAX_REFERRALS: usize = 16;
+const MAX_CNAME_CHAIN: usize = 8;
+const MAX_MINIMIZE_COUNT: usize = 10;
+const ZONE_CUT_CACHE_SIZE: usize = 1024;
+const MAX_PAYLOAD_LEN: u16 = 1232;
+
+/// The result of a full resolution
+pub type RecursorFuture = Box<Future<Item = Message, Error = ResolveError> + Send>;
+
+type StepFuture<C, P> =
+    Box<Future<Item = Loop<Message, Iteration<C, P>>, Error = ResolveError> + Send>;
+
+/// The state of resolution while walking down the delegation chain
+struct Iteration<C: DnsHandle + 'static, P: ConnectionProvider<ConnHandle = C> + 'static> {
+    zone: Name,
+    pool: NameServerPool<C, P>,
+    referrals: usize,
+    minimize: bool,
+    /// the number of labels of the query name sent to the zone's name servers, when minimizing
+    labels: usize,
+}
+
+impl<C, P> Iteration<C, P>
+where
+    C: DnsHandle + 'static,
+    P: ConnectionProvider<ConnHandle = C> + 'static,
+{
+    fn new(zone: Name, pool: NameServerPool<C, P>, referrals: usize, minimize: bool) -> Self {
+        let labels = zone.num_labels() as usize + 1;
+
+        Iteration {
+            zone,
+            pool,
+            referrals,
+            minimize,
+            labels,
+        }
+    }
+
+    /// The query to send to the zone's name servers, and whether it is minimized
+    ///
+    /// A minimized query asks for the A record of the query name trimmed to one label more than
+    ///  is known to exist below the zone, RFC 9156.
+    fn query(&self, query: &Query) -> (Query, bool) {
+        let full_labels = query.name().num_labels() as usize;
+        let added_labels = self.labels - self.zone.num_labels() as usize;
+
+        if self.minimize && self.labels < full_labels && added_labels <= MAX_MINIMIZE_COUNT {
+            let name = query.name().trim_to(self.labels);
+            (Query::query(name, RecordType::A), true)
+        } else {
+            (query.clone(), false)
+        }
+    }
+
+    /// The minimized name exists, but is not a zone cut, ask for one more label
+    fn next_label(mut self) -> Self {
+        self.labels += 1;
+        self
+    }
+
+    /// The zone's name servers don't handle minimized queries, send the full name
+    fn without_minimization(mut self) -> Self {
+        self.minimize = false;
+        self
+    }
+}
+
+#[derive(Clone)]
+struct ZoneCut<C: DnsHandle + 'static, P: ConnectionProvider<ConnHandle = C> + 'static> {
+    pool: NameServerPool<C, P>,
+    valid_until: Instant,
+}
+
+#[derive(Clone)]
+struct CachedResponse {
+    message: Message,
+    valid_until: Instant,
+}
+
+/// What a single response from an authoritative server tells us to do next
+#[derive(Debug, PartialEq)]
+enum Step {
+    /// The answer to the query, including any CNAME records leading to it
+    Answer(Vec<Record>),
+    /// A CNAME chain which ends outside of the answers in this response
+    Cname(Vec<Record>, Name),
+    /// The name or the record type doesn't exist, with the SOA for negative caching
+    Negative(ResponseCode, Vec<Record>),
+    /// A delegation to a child zone
+    Referral {
+        zone: Name,
+        ns_names: Vec<Name>,
+        glue: Vec<IpAddr>,
+        ttl: u32,
+    },
+}
+
+/// An iterative resolver, following referrals down from the root servers
+///
+/// Delegations and responses are cached, such that subsequent queries begin at the closest known
+///  zone cut rather than at the root. Query names are minimized as configured by
+///  `ResolverOpts::qname_minimization`, so each name server only learns the next label.
+#[derive(Clone)]
+pub struct Recursor<C = ConnectionHandle, P = StandardConnection>
+where
+    C: DnsHandle + 'static,
+    P: ConnectionProvider<ConnHandle = C> + 'static,
+{
+    roots: NameServerPool<C, P>,
+    port: u16,
+    opts: ResolverOpts,
+    conn_provider: P,
+    zone_cuts: Arc<Mutex<LruCache<Name, ZoneCut<C, P>>>>,
+    responses: Arc<Mutex<LruCache<Query, CachedResponse>>>,
+}
+
+impl Recursor<ConnectionHandle, StandardConnection> {
+    /// Construct a new Recursor
+    ///
+    /// # Arguments
+    ///
+    /// * `root_hints` - addresses of the root name servers, where all resolution begins
+    /// * `port` - the port on which all name servers will be contacted, 53 in the real world
+    /// * `opts` - timeouts, attempts, cache size and TTL bounds used for resolution
+    pub fn new(root_hints: &[IpAddr], port: u16, opts: ResolverOpts) -> Self {
+        Self::with_provider(root_hints, port, opts, StandardConnection)
+    }
+}
+
+impl<C, P> Recursor<C, P>
+where
+    C: DnsHandle + 'static,
+    P: ConnectionProvider<ConnHandle = C> + 'static,
+{
+    /// Construct a new Recursor with a custom `ConnectionProvider`
+    pub fn with_provider(
+        root_hints: &[IpAddr],
+        port: u16,
+        opts: ResolverOpts,
+        conn_provider: P,
+    ) -> Self {
+        let roots = Self::pool(root_hints, port, &opts, &conn_provider);
+
+        Recursor {
+            roots,
+            port,
+            opts,
+            conn_provider,
+            zone_cuts: Arc::new(Mutex::new(LruCache::new(ZONE_CUT_CACHE_SIZE))),
+            responses: Arc::new(Mutex::new(LruCache::new(opts.cache_size.max(1)))),
+        }
+    }
+
+    /// Resolve the query, following referrals from the closest known zone cut
+    ///
+    /// The returned `Message` carries the response code of the final answer, `NXDomain` and empty
+    ///  `NoError` responses are not considered errors.
+    pub fn resolve(&self, query: Query) -> RecursorFuture {
+        self.resolve_with_depth(query, 0)
+    }
+
+    /// Removes all cached responses and zone cuts, resolution begins at the roots again
+    pub fn flush(&self) {
+        self.zone_cuts
+            .lock()
+            .expect("zone cut cache poisoned")
+            .clear();
+        self.responses
+            .lock()
+            .expect("response cache poisoned")
+            .clear();
+    }
+
+    fn resolve_with_depth(&self, query: Query, depth: u8) -> RecursorFuture {
+        if depth > MAX_QUERY_DEPTH {
+            return Box::new(future::err(
+                ResolveErrorKind::Msg(format!("maximum query depth exceeded resolving {}", query))
+                    .into(),
+            ));
+        }
+
+        if let Some(message) = self.cached_response(&query) {
+            debug!("recursor cache hit: {}", query);
+            return Box::new(future::ok(message));
+        }
+
+        let (zone, pool) = self.closest_zone_cut(query.name());
+        let this = self.clone();
+        let iteration = Iteration::new(zone, pool, 0, self.minimize());
+
+        Box::new(future::loop_fn(iteration, move |mut iteration| {
+            let this = this.clone();
+            let query = query.clone();
+            let (sent, minimized) = iteration.query(&query);
+            debug!("recursor querying {} for {}", iteration.zone, sent);
+
+            iteration
+                .pool
+                .send(Self::request(&sent))
+                .then(move |response| {
+                    let step = response
+                        .map_err(ResolveError::from)
+                        .and_then(|response| classify(&sent, &iteration.zone, response.into()));
+
+                    if minimized {
+                        this.minimized_step(query, iteration, step, depth)
+                    } else {
+                        match step {
+                            Ok(step) => this.step(query, iteration, step, depth),
+                            Err(e) => Box::new(future::err(e)),
+                        }
+                    }
+                })
+        }))
+    }
+
+    fn minimize(&self) -> bool {
+        self.opts.qname_minimization != QnameMinimization::Disabled
+    }
+
+    /// Handles the response to a minimized query, only a referral moves resolution forward
+    fn minimized_step(
+        &self,
+        query: Query,
+        iteration: Iteration<C, P>,
+        step: ResolveResult<Step>,
+        depth: u8,
+    ) -> StepFuture<C, P> {
+        let relaxed = self.opts.qname_minimization == QnameMinimization::Relaxed;
+
+        match step {
+            Ok(step @ Step::Referral { .. }) => self.step(query, iteration, step, depth),
+            // NXDOMAIN means nothing exists below the name either, RFC 8020
+            Ok(Step::Negative(ResponseCode::NXDomain, soa)) if !relaxed => {
+                self.step(query, iteration, Step::Negative(ResponseCode::NXDomain, soa), depth)
+            }
+            Ok(Step::Negative(ResponseCode::NXDomain, _)) => {
+                debug!(
+                    "{} responded NXDOMAIN to a minimized query for {}, sending the full name",
+                    iteration.zone, query
+                );
+                Box::new(future::ok(Loop::Continue(iteration.without_minimization())))
+            }
+            // the name exists, but isn't a zone cut
+            Ok(_) => Box::new(future::ok(Loop::Continue(iteration.next_label()))),
+            Err(e) => {
+                if !relaxed {
+                    return Box::new(future::err(e));
+                }
+
+                debug!(
+                    "minimized query to {} failed, sending the full name: {}",
+                    iteration.zone, e
+                );
+                Box::new(future::ok(Loop::Continue(iteration.without_minimization())))
+            }
+        }
+    }
+
+    fn step(
+        &self,
+        query: Query,
+        iteration: Iteration<C, P>,
+        step: Step,
+        depth: u8,
+    ) -> StepFuture<C, P> {
+        match step {
+            Step::Answer(answers) => {
+                let message = response_message(&query, ResponseCode::NoError, answers, vec![]);
+                self.cache_response(&query, &message);
+                Box::new(future::ok(Loop::Break(message)))
+            }
+            Step::Negative(code, soa) => {
+                let message = response_message(&query, code, vec![], soa);
+                self.cache_response(&query, &message);
+                Box::new(future::ok(Loop::Break(message)))
+            }
+            Step::Cname(chain, target) => {
+                let this = self.clone();
+                let target_query = Query::query(target, query.query_type());
+
+                Box::new(
+                    self.resolve_with_depth(target_query, depth + 1)
+                        .map(move |target| {
+                            let mut answers = chain;
+                            answers.extend(target.answers().iter().cloned());
+
+                            let message = response_message(
+                                &query,
+                                target.response_code(),
+                                answers,
+                                target.name_servers().to_vec(),
+                            );
+                            this.cache_response(&query, &message);
+                            Loop::Break(message)
+                        }),
+                )
+            }
+            Step::Referral {
+                zone: child,
+                ns_names,
+                glue,
+                ttl,
+            } => {
+                let referrals = iteration.referrals + 1;
+                if referrals > MAX_REFERRALS {
+                    return Box::new(future::err(
+                        ResolveErrorKind::Msg(format!("too many referrals resolving {}", query))
+                            .into(),
+                    ));
+                }
+
+                debug!("{} referred {} to {}", iteration.zone, query, child);
+                let minimize = self.minimize();
+                let ip_strategy = self.opts.ip_strategy;
+                let glue = glue
+                    .into_iter()
+                    .filter(|ip| is_in_strategy(ip_strategy, ip))
+                    .collect::<Vec<_>>();
+                if !glue.is_empty() {
+                    let pool = self.cache_zone_cut(&child, &glue, ttl);
+                    return Box::new(future::ok(Loop::Continue(Iteration::new(
+                        child, pool, referrals, minimize,
+                    ))));
+                }
+
+                // name servers inside of the child zone can't be found without glue
+                let lookups = ns_names
+                    .into_iter()
+                    .filter(|ns| !child.zone_of(ns))
+                    .map(|ns| self.resolve_name_server(ns, depth + 1))
+                    .collect::<Vec<_>>();
+
+                let this = self.clone();
+                Box::new(future::join_all(lookups).and_then(move |addrs| {
+                    let addrs = addrs.into_iter().flatten().collect::<Vec<_>>();
+
+                    if addrs.is_empty() {
+                        return Err(ResolveErrorKind::Msg(format!(
+                            "no addresses found for the name servers of {}",
+                            child
+                        )).into());
+                    }
+
+                    let pool = this.cache_zone_cut(&child, &addrs, ttl);
+                    Ok(Loop::Continue(Iteration::new(child, pool, referrals, minimize)))
+                }))
+            }
+        }
+    }
+
+    /// Resolves the addresses of a name server, with the A and/or AAAA queries of `ResolverOpts::ip_strategy`
+    ///
+    /// Failed resolutions are treated like resolutions without addresses.
+    fn resolve_name_server(
+        &self,
+        ns: Name,
+        depth: u8,
+    ) -> Box<Future<Item = Vec<IpAddr>, Error = ResolveError> + Send> {
+        let this = self.clone();
+        let lookup = move |record_type| {
+            this.resolve_with_depth(Query::query(ns.clone(), record_type), depth)
+                .then(|result| {
+                    Ok::<_, ResolveError>(result.map_or(vec![], |message| {
+                        message
+                            .answers()
+                            .iter()
+                            .filter_map(|record| address(record.rdata()))
+                            .collect()
+                    }))
+                })
+        };
+
+        match self.opts.ip_strategy {
+            LookupIpStrategy::Ipv4Only => Box::new(lookup(RecordType::A)),
+            LookupIpStrategy::Ipv6Only => Box::new(lookup(RecordType::AAAA)),
+            LookupIpStrategy::Ipv4AndIpv6 => {
+                Box::new(lookup(RecordType::A).join(lookup(RecordType::AAAA)).map(
+                    |(mut v4, v6)| {
+                        v4.extend(v6);
+                        v4
+                    },
+                ))
+            }
+            LookupIpStrategy::Ipv4thenIpv6 => Box::new(lookup(RecordType::A).and_then(move |v4| {
+                if v4.is_empty() {
+                    future::Either::A(lookup(RecordType::AAAA))
+                } else {
+                    future::Either::B(future::ok(v4))
+                }
+            })),
+            LookupIpStrategy::Ipv6thenIpv4 => {
+                Box::new(lookup(RecordType::AAAA).and_then(move |v6| {
+                    if v6.is_empty() {
+                        future::Either::A(lookup(RecordType::A))
+                    } else {
+                        future::Either::B(future::ok(v6))
+                    }
+                }))
+            }
+        }
+    }
+
+    fn request(query: &Query) -> DnsRequest {
+        let mut message = Message::new();
+        message.add_query(query.clone());
+        message
+            .set_message_type(MessageType::Query)
+            .set_op_code(OpCode::Query)
+            .set_recursion_desired(false);
+
+        {
+            let edns = message.edns_mut();
+            edns.set_max_payload(MAX_PAYLOAD_LEN);
+            edns.set_version(0);
+        }
+
+        DnsRequest::new(message, DnsRequestOptions::default())
+    }
+
+    fn pool(
+        addrs: &[IpAddr],
+        port: u16,
+        opts: &ResolverOpts,
+        conn_provider: &P,
+    ) -> NameServerPool<C, P> {
+        let config = ResolverConfig::from_parts(
+            None,
+            vec![],
+            NameServerConfigGroup::from_ips_clear(addrs, port),
+        );
+
+        NameServerPool::from_config_with_provider(&config, opts, conn_provider.clone())
+    }
+
+    /// Returns the deepest cached zone cut at or above `name`, falling back to the roots
+    fn closest_zone_cut(&self, name: &Name) -> (Name, NameServerPool<C, P>) {
+        let now = Instant::now();
+        let mut zone_cuts = self.zone_cuts.lock().expect("zone cut cache poisoned");
+
+        let mut name = name.clone();
+        while !name.is_root() {
+            if let Some(cut) = zone_cuts.get_mut(&name) {
+                if cut.valid_until > now {
+                    return (name, cut.pool.clone());
+                }
+            }
+
+            name = name.base_name();
+        }
+
+        (Name::root(), self.roots.clone())
+    }
+
+    fn cache_zone_cut(&self, zone: &Name, addrs: &[IpAddr], ttl: u32) -> NameServerPool<C, P> {
+        let pool = Self::pool(addrs, self.port, &self.opts, &self.conn_provider);
+        let ttl = clamp(
+            Duration::from_secs(u64::from(ttl)),
+            self.opts.positive_min_ttl,
+            self.opts.positive_max_ttl,
+        );
+
+        self.zone_cuts.lock().expect("zone cut cache poisoned").insert(
+            zone.clone(),
+            ZoneCut {
+                pool: pool.clone(),
+                valid_until: Instant::now() + ttl,
+            },
+        );
+
+        pool
+    }
+
+    fn cached_response(&self, query: &Query) -> Option<Message> {
+        let now = Instant::now();
+        let mut responses = self.responses.lock().expect("response cache poisoned");
+
+        let (cached, valid_until) = match responses.get_mut(query) {
+            Some(ref cached) if cached.valid_until > now => {
+                (cached.message.clone(), cached.valid_until)
+            }
+            Some(_) => {
+                responses.remove(query);
+                return None;
+            }
+            None => return None,
+        };
+
+        // the TTLs handed out should reflect the time remaining in the cache
+        let remaining = (valid_until - now).as_secs() as u32;
+        let with_remaining = |records: &[Record]| {
+            records
+                .iter()
+                .cloned()
+                .map(|mut record| {
+                    let ttl = record.ttl().min(remaining);
+                    record.set_ttl(ttl);
+                    record
+                }).collect::<Vec<_>>()
+        };
+
+        Some(response_message(
+            query,
+            cached.response_code(),
+            with_remaining(cached.answers()),
+            with_remaining(cached.name_servers()),
+        ))
+    }
+
+    fn cache_response(&self, query: &Query, message: &Message) {
+        let ttl = if message.answers().is_empty() {
+            // negative responses may only be cached with an SOA, RFC 2308
+            let ttl = message
+                .name_servers()
+                .iter()
+                .filter_map(|record| match *record.rdata() {
+                    RData::SOA(ref soa) => Some(record.ttl().min(soa.minimum())),
+                    _ => None,
+                }).next();
+
+            match ttl {
+                Some(ttl) => clamp(
+                    Duration::from_secs(u64::from(ttl)),
+                    self.opts.negative_min_ttl,
+                    self.opts.negative_max_ttl,
+                ),
+                None => return,
+            }
+        } else {
+            let ttl = message
+                .answers()
+                .iter()
+                .map(Record::ttl)
+                .min()
+                .unwrap_or(0);
+
+            clamp(
+                Duration::from_secs(u64::from(ttl)),
+                self.opts.positive_min_ttl,
+                self.opts.positive_max_ttl,
+            )
+        };
+
+        self.responses.lock().expect("response cache poisoned").insert(
+            query.clone(),
+            CachedResponse {
+                message: message.clone(),
+                valid_until: Instant::now() + ttl,
+            },
+        );
+    }
+}
+
+fn clamp(ttl: Duration, min: Option<Duration>, max: Option<Duration>) -> Duration {
+    let ttl = min.map_or(ttl, |min| ttl.max(min));
+    max.map_or(ttl, |max| ttl.min(max))
+}
+
+/// Returns true if the name servers at the address are queried with the IP strategy, i.e. it is not of the other family
+///  of an `Ipv4Only` or `Ipv6Only` strategy
+fn is_in_strategy(ip_strategy: LookupIpStrategy, ip: &IpAddr) -> bool {
+    match ip_strategy {
+        LookupIpStrategy::Ipv4Only => ip.is_ipv4(),
+        LookupIpStrategy::Ipv6Only => ip.is_ipv6(),
+        _ => true,
+    }
+}
+
+fn address(rdata: &RData) -> Option<IpAddr> {
+    match *rdata {
+        RData::A(ip) => Some(IpAddr::V4(ip)),
+        RData::AAAA(ip) => Some(IpAddr::V6(ip)),
+        _ => None,
+    }
+}
+
+fn response_message(
+    query: &Query,
+    response_code: ResponseCode,
+    answers: Vec<Record>,
+    name_servers: Vec<Record>,
+) -> Message {
+    let mut message = Message::new();
+    message
+        .set_message_type(MessageType::Response)
+        .set_op_code(OpCode::Query)
+        .set_recursion_desired(true)
+        .set_recursion_available(true)
+        .set_response_code(response_code);
+    message.add_query(query.clone());
+    message.insert_answers(answers);
+    message.insert_name_servers(name_servers);
+    message
+}
+
+/// Decide what to do with a response from a server authoritative for `zone`
+///
+/// Only records within the bailiwick of `zone` are considered, anything else the server sent
+///  could be an attempt to poison the cache.
+fn classify(query: &Query, zone: &Name, mut response: Message) -> ResolveResult<Step> {
+    let authority = response
+        .take_name_servers()
+        .into_iter()
+        .filter(|record| zone.zone_of(record.name()))
+        .collect::<Vec<_>>();
+    let soa = || {
+        authority
+            .iter()
+            .filter(|record| record.rr_type() == RecordType::SOA)
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+
+    match response.response_code() {
+        ResponseCode::NoError => (),
+        ResponseCode::NXDomain => return Ok(Step::Negative(ResponseCode::NXDomain, soa())),
+        code => {
+            return Err(ResolveErrorKind::Msg(format!(
+                "name server for {} responded with {} to {}",
+                zone, code, query
+            )).into())
+        }
+    }
+
+    let answers = response
+        .take_answers()
+        .into_iter()
+        .filter(|record| zone.zone_of(record.name()))
+        .collect::<Vec<_>>();
+
+    if !answers.is_empty() {
+        let mut chain = vec![];
+        let mut name = query.name().clone();
+
+        for _ in 0..MAX_CNAME_CHAIN {
+            let matching = answers
+                .iter()
+                .filter(|record| {
+                    record.name() == &name
+                        && (query.query_type() == RecordType::ANY
+                            || record.rr_type() == query.query_type())
+                }).cloned()
+                .collect::<Vec<_>>();
+
+            if !matching.is_empty() {
+                chain.extend(matching);
+                return Ok(Step::Answer(chain));
+            }
+
+            let cname = answers
+                .iter()
+                .filter(|record| record.name() == &name)
+                .filter_map(|record| match *record.rdata() {
+                    RData::CNAME(ref target) => Some((record, target.clone())),
+                    _ => None,
+                }).next();
+
+            match cname {
+                Some((record, target)) => {
+                    chain.push(record.clone());
+                    name = target;
+                }
+                None => break,
+            }
+        }
+
+        if !chain.is_empty() {
+            return Ok(Step::Cname(chain, name));
+        }
+    }
+
+    // the deepest delegation below this zone which still contains the query name
+    let referral = authority
+        .iter()
+        .filter(|record| {
+            record.rr_type() == RecordType::NS
+                && record.name() != zone
+                && record.name().zone_of(query.name())
+        }).map(|record| record.name().clone())
+        .max_by_key(Name::num_labels);
+
+    if let Some(child) = referral {
+        let ns_records = authority
+            .iter()
+            .filter(|record| record.rr_type() == RecordType::NS && record.name() == &child)
+            .collect::<Vec<_>>();
+        let ttl = ns_records.iter().map(|record| record.ttl()).min().unwrap_or(0);
+        let ns_names = ns_records
+            .iter()
+            .filter_map(|record| match *record.rdata() {
+                RData::NS(ref name) => Some(name.clone()),
+                _ => None,
+            }).collect::<Vec<_>>();
+
+        // glue is only trusted for name servers within the bailiwick of the referring zone
+        let glue = response
+            .additionals()
+            .iter()
+            .filter(|record| zone.zone_of(record.name()) && ns_names.contains(record.name()))
+            .filter_map(|record| address(record.rdata()))
+            .collect::<Vec<_>>();
+
+        return Ok(Step::Referral {
+            zone: child,
+            ns_names,
+            glue,
+            ttl,
+        });
+    }
+
+    if !response.authoritative() {
+        return Err(ResolveErrorKind::Msg(format!(
+            "lame delegation, name server for {} is not authoritative for {}",
+            zone, query
+        )).into());
+    }
+
+    Ok(Step::Negative(ResponseCode::NoError, soa()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::net::Ipv4Addr;
+    use std::str::FromStr;
+
+    use tokio::runtime::current_thread::Runtime;
+
+    use proto::error::ProtoError;
+    use proto::rr::rdata::SOA;
+    use proto::xfer::DnsResponse;
+
+    use config::NameServerConfig;
+
+    use super::*;
+
+    type Responses = HashMap<(IpAddr, Query), Message>;
+
+    #[derive(Clone)]
+    struct MockProvider {
+        responses: Arc<Responses>,
+        sent: Arc<Mutex<Vec<(IpAddr, Query)>>>,
+    }
+
+    #[derive(Clone)]
+    struct MockHandle {
+        ip: IpAddr,
+        provider: MockProvider,
+    }
+
+    impl ConnectionProvider for MockProvider {
+        type ConnHandle = MockHandle;
+
+        fn new_connection(&self, config: &NameServerConfig, _: &ResolverOpts) -> MockHandle {
+            MockHandle {
+                ip: config.socket_addr.ip(),
+                provider: self.clone(),
+            }
+        }
+    }
+
+    impl DnsHandle for MockHandle {
+        type Response = Box<Future<Item = DnsResponse, Error = ProtoError> + Send>;
+
+        fn send<R: Into<DnsRequest>>(&mut self, request: R) -> Self::Response {
+            let request = request.into();
+            let query = request.queries()[0].clone();
+            assert!(!request.recursion_desired());
+
+            self.provider
+                .sent
+                .lock()
+                .unwrap()
+                .push((self.ip, query.clone()));
+
+            let message = match self.provider.responses.get(&(self.ip, query.clone())) {
+                Some(message) => message.clone(),
+                None => {
+                    let mut message = Message::new();
+                    message.set_response_code(ResponseCode::Refused);
+                    message
+                }
+            };
+
+            Box::new(future::ok(message.into()))
+        }
+    }
+
+    fn ip(last: u8) -> IpAddr {
+        IpAddr::V4(Ipv4Addr::new(10, 0, 0, last))
+    }
+
+    fn name(name: &str) -> Name {
+        Name::from_str(name).unwrap()
+    }
+
+    fn a(owner: &str, addr: IpAddr) -> Record {
+        match addr {
+            IpAddr::V4(addr) => Record::from_rdata(name(owner), 300, RecordType::A, RData::A(addr)),
+            IpAddr::V6(addr) => {
+                Record::from_rdata(name(owner), 300, RecordType::AAAA, RData::AAAA(addr))
+            }
+        }
+    }
+
+    fn ns(owner: &str, target: &str) -> Record {
+        Record::from_rdata(name(owner), 3600, RecordType::NS, RData::NS(name(target)))
+    }
+
+    fn soa(owner: &str) -> Record {
+        Record::from_rdata(
+            name(owner),
+            3600,
+            RecordType::SOA,
+            RData::SOA(SOA::new(
+                name(owner),
+                name(owner),
+                1,
+                3600,
+                600,
+                86400,
+                60,
+            )),
+        )
+    }
+
+    fn referral(ns_records: Vec<Record>, glue: Vec<Record>) -> Message {
+        let mut message = Message::new();
+        message.insert_name_servers(ns_records);
+        message.insert_additionals(glue);
+        message
+    }
+
+    fn answer(answers: Vec<Record>) -> Message {
+        let mut message = Message::new();
+        message.set_authoritative(true);
+        message.insert_answers(answers);
+        message
+    }
+
+    fn a_query(qname: &str) -> Query {
+        Query::query(name(qname), RecordType::A)
+    }
+
+    /// root (10.0.0.1) -> com (10.0.0.2) -> example.com (10.0.0.3)
+    fn hierarchy(qname: &str, final_response: Message) -> Responses {
+        let query = a_query(qname);
+        let mut responses = Responses::new();
+        responses.insert(
+            (ip(1), query.clone()),
+            referral(vec![ns("com.", "a.gtld.com.")], vec![a("a.gtld.com.", ip(2))]),
+        );
+        responses.insert(
+            (ip(2), query.clone()),
+            referral(
+                vec![ns("example.com.", "ns1.example.com.")],
+                vec![a("ns1.example.com.", ip(3))],
+            ),
+        );
+        responses.insert((ip(3), query), final_response);
+        responses
+    }
+
+    /// A recursor sending the full query name, see `minimizing_recursor`
+    fn recursor(responses: Responses) -> (Recursor<MockHandle, MockProvider>, MockProvider) {
+        minimizing_recursor(responses, QnameMinimization::Disabled)
+    }
+
+    fn minimizing_recursor(
+        responses: Responses,
+        qname_minimization: QnameMinimization,
+    ) -> (Recursor<MockHandle, MockProvider>, MockProvider) {
+        let provider = MockProvider {
+            responses: Arc::new(responses),
+            sent: Arc::new(Mutex::new(vec![])),
+        };
+
+        let opts = ResolverOpts {
+            qname_minimization,
+            ..ResolverOpts::default()
+        };
+        let recursor = Recursor::with_provider(&[ip(1)], 53, opts, provider.clone());
+        (recursor, provider)
+    }
+
+    fn sent_names(provider: &MockProvider) -> Vec<(IpAddr, String)> {
+        provider
+            .sent
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|&(ip, ref query)| (ip, query.name().to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_follow_referrals_with_glue() {
+        let mut responses = hierarchy(
+            "www.example.com.",
+            answer(vec![a("www.example.com.", ip(80))]),
+        );
+        responses.insert(
+            (ip(3), a_query("mail.example.com.")),
+            answer(vec![a("mail.example.com.", ip(25))]),
+        );
+        let (recursor, provider) = recursor(responses);
+        let mut io_loop = Runtime::new().unwrap();
+
+        let message = io_loop
+            .block_on(recursor.resolve(a_query("www.example.com.")))
+            .expect("resolution failed");
+        assert_eq!(message.response_code(), ResponseCode::NoError);
+        assert_eq!(message.answers(), &[a("www.example.com.", ip(80))]);
+        assert_eq!(provider.sent.lock().unwrap().len(), 3);
+
+        // answered from the cache
+        io_loop
+            .block_on(recursor.resolve(a_query("www.example.com.")))
+            .expect("resolution failed");
+        assert_eq!(provider.sent.lock().unwrap().len(), 3);
+
+        // the delegation to example.com is cached, the root and com are skipped
+        let message = io_loop
+            .block_on(recursor.resolve(a_query("mail.example.com.")))
+            .expect("resolution failed");
+        assert_eq!(message.answers(), &[a("mail.example.com.", ip(25))]);
+        assert_eq!(
+            provider.sent.lock().unwrap()[3],
+            (ip(3), a_query("mail.example.com."))
+        );
+        assert_eq!(provider.sent.lock().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_resolve_name_server_addresses() {
+        let query = a_query("www.example.com.");
+        let mut responses = Responses::new();
+
+        // com refers to a name server in net, along with glue outside of its bailiwick
+        responses.insert(
+            (ip(1), query.clone()),
+            referral(vec![ns("com.", "a.gtld.com.")], vec![a("a.gtld.com.", ip(2))]),
+        );
+        responses.insert(
+            (ip(2), query.clone()),
+            referral(
+                vec![ns("example.com.", "ns.example.net.")],
+                vec![a("ns.example.net.", ip(66))],
+            ),
+        );
+        responses.insert(
+            (ip(1), a_query("ns.example.net.")),
+            referral(vec![ns("net.", "a.gtld.net.")], vec![a("a.gtld.net.", ip(4))]),
+        );
+        responses.insert(
+            (ip(4), a_query("ns.example.net.")),
+            answer(vec![a("ns.example.net.", ip(5))]),
+        );
+        responses.insert((ip(5), query.clone()), answer(vec![a("www.example.com.", ip(80))]));
+        let (recursor, provider) = recursor(responses);
+        let mut io_loop = Runtime::new().unwrap();
+
+        let message = io_loop
+            .block_on(recursor.resolve(query))
+            .expect("resolution failed");
+        assert_eq!(message.answers(), &[a("www.example.com.", ip(80))]);
+        assert!(provider
+            .sent
+            .lock()
+            .unwrap()
+            .iter()
+            .all(|&(ip, _)| ip != super::tests::ip(66)));
+    }
+
+    #[test]
+    fn test_resolve_name_server_ipv6_addresses() {
+        use std::net::Ipv6Addr;
+
+        let ip6 = IpAddr::V6(Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 5));
+        let query = a_query("www.example.com.");
+        let aaaa_query = Query::query(name("ns.example.net."), RecordType::AAAA);
+        let mut responses = Responses::new();
+        responses.insert(
+            (ip(1), query.clone()),
+            referral(vec![ns("com.", "a.gtld.com.")], vec![a("a.gtld.com.", ip(2))]),
+        );
+        responses.insert(
+            (ip(2), query.clone()),
+            referral(vec![ns("example.com.", "ns.example.net.")], vec![]),
+        );
+        for net_query in &[a_query("ns.example.net."), aaaa_query.clone()] {
+            responses.insert(
+                (ip(1), net_query.clone()),
+                referral(vec![ns("net.", "a.gtld.net.")], vec![a("a.gtld.net.", ip(4))]),
+            );
+        }
+        // the name server only has an IPv6 address
+        let mut no_data = answer(vec![]);
+        no_data.insert_name_servers(vec![soa("example.net.")]);
+        responses.insert((ip(4), a_query("ns.example.net.")), no_data);
+        responses.insert((ip(4), aaaa_query.clone()), answer(vec![a("ns.example.net.", ip6)]));
+        responses.insert((ip6, query.clone()), answer(vec![a("www.example.com.", ip(80))]));
+
+        let resolve = |ip_strategy| {
+            let provider = MockProvider {
+                responses: Arc::new(responses.clone()),
+                sent: Arc::new(Mutex::new(vec![])),
+            };
+            let opts = ResolverOpts {
+                ip_strategy,
+                ..ResolverOpts::default()
+            };
+            let recursor = Recursor::with_provider(&[ip(1)], 53, opts, provider.clone());
+            let result = Runtime::new()
+                .unwrap()
+                .block_on(recursor.resolve(query.clone()));
+            let sent = provider.sent.lock().unwrap().clone();
+            (result, sent)
+        };
+
+        // the AAAA records are queried when there are no A records
+        let (result, sent) = resolve(LookupIpStrategy::Ipv4thenIpv6);
+        assert_eq!(
+            result.expect("resolution failed").answers(),
+            &[a("www.example.com.", ip(80))]
+        );
+        assert!(sent.contains(&(ip(4), aaaa_query.clone())));
+
+        let (result, sent) = resolve(LookupIpStrategy::Ipv4AndIpv6);
+        assert!(result.is_ok());
+        assert!(sent.contains(&(ip(4), a_query("ns.example.net."))));
+
+        let (result, sent) = resolve(LookupIpStrategy::Ipv6thenIpv4);
+        assert!(result.is_ok());
+        assert!(!sent.contains(&(ip(4), a_query("ns.example.net."))));
+
+        // only IPv4 addresses are used
+        let (result, sent) = resolve(LookupIpStrategy::Ipv4Only);
+        assert!(result.is_err());
+        assert!(!sent.contains(&(ip(4), aaaa_query)));
+    }
+
+    #[test]
+    fn test_follow_cname() {
+        let cname = Record::from_rdata(
+            name("www.example.com."),
+            300,
+            RecordType::CNAME,
+            RData::CNAME(name("web.example.org.")),
+        );
+        let mut responses = hierarchy("www.example.com.", answer(vec![cname.clone()]));
+        responses.insert(
+            (ip(1), a_query("web.example.org.")),
+            referral(
+                vec![ns("example.org.", "ns.example.org.")],
+                vec![a("ns.example.org.", ip(6))],
+            ),
+        );
+        responses.insert(
+            (ip(6), a_query("web.example.org.")),
+            answer(vec![a("web.example.org.", ip(81))]),
+        );
+        let (recursor, _) = recursor(responses);
+        let mut io_loop = Runtime::new().unwrap();
+
+        let message = io_loop
+            .block_on(recursor.resolve(a_query("www.example.com.")))
+            .expect("resolution failed");
+        assert_eq!(message.answers(), &[cname, a("web.example.org.", ip(81))]);
+    }
+
+    #[test]
+    fn test_nxdomain() {
+        let mut nxdomain = answer(vec![]);
+        nxdomain.set_response_code(ResponseCode::NXDomain);
+        nxdomain.insert_name_servers(vec![soa("example.com.")]);
+        let (recursor, provider) = recursor(hierarchy("nope.example.com.", nxdomain));
+        let mut io_loop = Runtime::new().unwrap();
+
+        let message = io_loop
+            .block_on(recursor.resolve(a_query("nope.example.com.")))
+            .expect("resolution failed");
+        assert_eq!(message.response_code(), ResponseCode::NXDomain);
+        assert!(message.answers().is_empty());
+        assert_eq!(message.name_servers()[0].rr_type(), RecordType::SOA);
+
+        // negative answers are cached as well
+        io_loop
+            .block_on(recursor.resolve(a_query("nope.example.com.")))
+            .expect("resolution failed");
+        assert_eq!(provider.sent.lock().unwrap().len(), 3);
+    }
+
+    /// root (10.0.0.1) -> com (10.0.0.2) -> example.com (10.0.0.3), queried with minimized names
+    fn minimized_hierarchy() -> Responses {
+        let mut responses = Responses::new();
+        responses.insert(
+            (ip(1), a_query("com.")),
+            referral(vec![ns("com.", "a.gtld.com.")], vec![a("a.gtld.com.", ip(2))]),
+        );
+        responses.insert(
+            (ip(2), a_query("example.com.")),
+            referral(
+                vec![ns("example.com.", "ns1.example.com.")],
+                vec![a("ns1.example.com.", ip(3))],
+            ),
+        );
+
+        // b.example.com. is an empty non-terminal
+        let mut no_data = answer(vec![]);
+        no_data.insert_name_servers(vec![soa("example.com.")]);
+        responses.insert((ip(3), a_query("b.example.com.")), no_data);
+        responses.insert(
+            (ip(3), a_query("a.b.example.com.")),
+            answer(vec![a("a.b.example.com.", ip(80))]),
+        );
+        responses
+    }
+
+    #[test]
+    fn test_qname_minimization() {
+        let (recursor, provider) =
+            minimizing_recursor(minimized_hierarchy(), QnameMinimization::Strict);
+        let mut io_loop = Runtime::new().unwrap();
+
+        let message = io_loop
+            .block_on(recursor.resolve(a_query("a.b.example.com.")))
+            .expect("resolution failed");
+        assert_eq!(message.answers(), &[a("a.b.example.com.", ip(80))]);
+        assert_eq!(
+            sent_names(&provider),
+            vec![
+                (ip(1), "com.".to_string()),
+                (ip(2), "example.com.".to_string()),
+                (ip(3), "b.example.com.".to_string()),
+                (ip(3), "a.b.example.com.".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_qname_minimization_fallback() {
+        // this com server doesn't handle minimized queries, only the full name is referred
+        let mut responses = minimized_hierarchy();
+        let mut nxdomain = answer(vec![]);
+        nxdomain.set_response_code(ResponseCode::NXDomain);
+        responses.insert((ip(2), a_query("example.com.")), nxdomain);
+        responses.insert(
+            (ip(2), a_query("a.b.example.com.")),
+            referral(
+                vec![ns("example.com.", "ns1.example.com.")],
+                vec![a("ns1.example.com.", ip(3))],
+            ),
+        );
+
+        let (recursor, provider) =
+            minimizing_recursor(responses.clone(), QnameMinimization::Relaxed);
+        let mut io_loop = Runtime::new().unwrap();
+        let message = io_loop
+            .block_on(recursor.resolve(a_query("a.b.example.com.")))
+            .expect("resolution failed");
+        assert_eq!(message.answers(), &[a("a.b.example.com.", ip(80))]);
+
+        // minimization is resumed at the next zone
+        assert_eq!(
+            sent_names(&provider)[1..],
+            [
+                (ip(2), "example.com.".to_string()),
+                (ip(2), "a.b.example.com.".to_string()),
+                (ip(3), "b.example.com.".to_string()),
+                (ip(3), "a.b.example.com.".to_string()),
+            ]
+        );
+
+        // strict minimization believes the NXDOMAIN
+        let (recursor, _) = minimizing_recursor(responses, QnameMinimization::Strict);
+        let message = io_loop
+            .block_on(recursor.resolve(a_query("a.b.example.com.")))
+            .expect("resolution failed");
+        assert_eq!(message.response_code(), ResponseCode::NXDomain);
+    }
+
+    #[test]
+    fn test_classify_bailiwick() {
+        let query = a_query("www.example.com.");
+
+        // answers for names outside of the zone are dropped
+        let response = answer(vec![
+            a("www.example.net.", ip(66)),
+            a("www.example.com.", ip(80)),
+        ]);
+        assert_eq!(
+            classify(&query, &name("example.com."), response).unwrap(),
+            Step::Answer(vec![a("www.example.com.", ip(80))])
+        );
+
+        // a server for com can't delegate net
+        let response = referral(
+            vec![ns("net.", "ns.evil.net."), ns("example.com.", "ns.example.com.")],
+            vec![a("ns.example.com.", ip(3))],
+        );
+        assert_eq!(
+            classify(&query, &name("com."), response).unwrap(),
+            Step::Referral {
+                zone: name("example.com."),
+                ns_names: vec![name("ns.example.com.")],
+                glue: vec![ip(3)],
+                ttl: 3600,
+            }
+        );
+
+        // a delegation which isn't below the current zone is lame
+        let response = referral(vec![ns("org.", "ns.example.org.")], vec![]);
+        assert!(classify(&query, &name("com."), response).is_err());
+    }
+}
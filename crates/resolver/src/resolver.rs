@@ -8,16 +8,23 @@
 //! Structs for creating and using a Resolver
 use std::io;
 use std::net::IpAddr;
+use std::path::Path;
 use std::sync::Mutex;
 
+use futures::Future;
+use proto::rr::rdata::opt::ClientSubnet;
 use proto::rr::RecordType;
 use tokio::runtime::{self, Runtime};
 
+use cache::ResolverCache;
+#[cfg(unix)]
+use config::ReloadOpts;
 use config::{ResolverConfig, ResolverOpts};
 use error::*;
 use lookup;
 use lookup::Lookup;
 use lookup_ip::LookupIp;
+use name_server::ServerStats;
 use AsyncResolver;
 
 /// The Resolver is used for performing DNS queries.
@@ -74,12 +81,34 @@ impl Resolver {
     ///
     /// A new Resolver or an error if there was an error with the configuration.
     pub fn new(config: ResolverConfig, options: ResolverOpts) -> io::Result<Self> {
+        let (async_resolver, bg) = AsyncResolver::new(config, options);
+        Self::with_async_resolver(async_resolver, bg)
+    }
+
+    /// Constructs a new Resolver with the cache restored from a file written by `save_cache`.
+    ///
+    /// See [`AsyncResolver::new_with_cache_file`] for more information.
+    ///
+    /// # Returns
+    ///
+    /// A new Resolver or an error if there was an error with the configuration.
+    pub fn new_with_cache_file(
+        config: ResolverConfig,
+        options: ResolverOpts,
+        path: &Path,
+    ) -> io::Result<Self> {
+        let (async_resolver, bg) = AsyncResolver::new_with_cache_file(config, options, path);
+        Self::with_async_resolver(async_resolver, bg)
+    }
+
+    fn with_async_resolver<F>(async_resolver: AsyncResolver, bg: F) -> io::Result<Self>
+    where
+        F: Future<Item = (), Error = ()> + Send + 'static,
+    {
         let mut builder = runtime::Builder::new();
         builder.core_threads(1);
 
         let mut runtime = builder.build()?;
-        let (async_resolver, bg) = AsyncResolver::new(config, options);
-
         runtime.spawn(bg);
 
         Ok(Resolver {
@@ -88,6 +117,25 @@ impl Resolver {
         })
     }
 
+    /// Returns a handle to the cache of this resolver, to inspect or flush it
+    pub fn cache(&self) -> ResolverCache {
+        self.async_resolver.cache()
+    }
+
+    /// Returns the statistics of the name servers, including their smoothed round trip times
+    ///
+    /// See [`AsyncResolver::name_server_stats`] for more information.
+    pub fn name_server_stats(&self) -> ResolveResult<Vec<ServerStats>> {
+        self.async_resolver.name_server_stats()
+    }
+
+    /// Saves the current entries of the cache to a file, to be restored with `new_with_cache_file`.
+    ///
+    /// See [`AsyncResolver::save_cache`] for more information.
+    pub fn save_cache(&self, path: &Path) -> ResolveResult<usize> {
+        self.async_resolver.save_cache(path)
+    }
+
     /// Constructs a new Resolver with default config and default options.
     ///
     /// See [`ResolverConfig::default`] and [`ResolverOpts::default`] for more information.
@@ -108,6 +156,15 @@ impl Resolver {
         Self::new(config, options)
     }
 
+    /// Constructs a new Resolver with the system configuration, which is reloaded when it changes.
+    ///
+    /// See [`AsyncResolver::from_system_conf_with_reload`] for more information.
+    #[cfg(unix)]
+    pub fn from_system_conf_with_reload(reload: ReloadOpts) -> io::Result<Self> {
+        let (async_resolver, bg) = AsyncResolver::from_system_conf_with_reload(reload)?;
+        Self::with_async_resolver(async_resolver, bg)
+    }
+
     /// Generic lookup for any RecordType
     ///
     /// *WARNING* This interface may change in the future, please use [`Self::lookup_ip`] or another variant for more stable interfaces.
@@ -121,6 +178,27 @@ impl Resolver {
         self.runtime.lock()?.block_on(lookup)
     }
 
+    /// Generic lookup for any RecordType, made for a client in the subnet
+    ///
+    /// See [`AsyncResolver::lookup_with_client_subnet`] for more information.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - name of the record to lookup, if name is not a valid domain name, an error will be returned
+    /// * `record_type` - type of record to lookup
+    /// * `client_subnet` - the subnet of the client the lookup is made for
+    pub fn lookup_with_client_subnet(
+        &self,
+        name: &str,
+        record_type: RecordType,
+        client_subnet: ClientSubnet,
+    ) -> ResolveResult<Lookup> {
+        let lookup =
+            self.async_resolver
+                .lookup_with_client_subnet(name, record_type, client_subnet);
+        self.runtime.lock()?.block_on(lookup)
+    }
+
     /// Performs a dual-stack DNS lookup for the IP for the given hostname.
     ///
     /// See the configuration and options parameters for controlling the way in which A(Ipv4) and AAAA(Ipv6) lookups will be performed. For the least expensive query a fully-qualified-domain-name, FQDN, which ends in a final `.`, e.g. `www.example.com.`, will only issue one query. Anything else will always incur the cost of querying the `ResolverConfig::domain` and `ResolverConfig::search`.
@@ -164,8 +242,29 @@ impl Resolver {
     lookup_fn!(ipv4_lookup, lookup::Ipv4Lookup);
     lookup_fn!(ipv6_lookup, lookup::Ipv6Lookup);
     lookup_fn!(mx_lookup, lookup::MxLookup);
+    lookup_fn!(naptr_lookup, lookup::NaptrLookup);
     lookup_fn!(srv_lookup, lookup::SrvLookup);
     lookup_fn!(txt_lookup, lookup::TxtLookup);
+    lookup_fn!(caa_lookup, lookup::CaaLookup);
+    lookup_fn!(tlsa_lookup, lookup::TlsaLookup);
+    lookup_fn!(sshfp_lookup, lookup::SshfpLookup);
+    lookup_fn!(openpgpkey_lookup, lookup::OpenpgpkeyLookup);
+    lookup_fn!(svcb_lookup, lookup::SvcbLookup);
+    lookup_fn!(https_lookup, lookup::HttpsLookup);
+    lookup_fn!(ns_lookup, lookup::NsLookup);
+    lookup_fn!(soa_lookup, lookup::SoaLookup);
+
+    /// Performs a lookup of the endpoints of a service from its SVCB or HTTPS records.
+    ///
+    /// See `AsyncResolver::service_lookup`
+    pub fn service_lookup(
+        &self,
+        name: &str,
+        record_type: RecordType,
+    ) -> ResolveResult<lookup::ServiceLookup> {
+        let lookup = self.async_resolver.service_lookup(name, record_type);
+        self.runtime.lock()?.block_on(lookup)
+    }
 }
 
 #[cfg(test)]
@@ -12,11 +12,30 @@
 //!  system, e.g. most Unixes have this written to `/etc/resolv.conf`
 #![allow(missing_docs, unused_extern_crates)]
 
+#[cfg(not(unix))]
+use std::io;
+#[cfg(not(unix))]
+use std::path::Path;
+
+#[cfg(not(unix))]
+use config::{ResolverConfig, ResolverOpts};
+
 #[cfg(unix)]
 mod unix;
 
 #[cfg(unix)]
 pub use self::unix::read_system_conf;
+#[cfg(unix)]
+pub(crate) use self::unix::read_resolv_conf;
+
+/// Only Unix systems are configured through resolv.conf
+#[cfg(not(unix))]
+pub(crate) fn read_resolv_conf<P: AsRef<Path>>(_path: P) -> io::Result<(ResolverConfig, ResolverOpts)> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "resolv.conf is only supported on Unix",
+    ))
+}
 
 #[cfg(windows)]
 mod windows;
@@ -16,7 +16,7 @@ use std::io;
 use std::io::Read;
 use std::net::SocketAddr;
 use std::path::Path;
-use std::str::FromStr;
+use std::str::{self, FromStr};
 use std::time::Duration;
 
 use resolv_conf;
@@ -30,7 +30,7 @@ pub fn read_system_conf() -> io::Result<(ResolverConfig, ResolverOpts)> {
     Ok(read_resolv_conf("/etc/resolv.conf")?)
 }
 
-fn read_resolv_conf<P: AsRef<Path>>(path: P) -> io::Result<(ResolverConfig, ResolverOpts)> {
+pub(crate) fn read_resolv_conf<P: AsRef<Path>>(path: P) -> io::Result<(ResolverConfig, ResolverOpts)> {
     let mut data = String::new();
     let mut file = File::open(path)?;
     file.read_to_string(&mut data)?;
@@ -38,18 +38,59 @@ fn read_resolv_conf<P: AsRef<Path>>(path: P) -> io::Result<(ResolverConfig, Reso
 }
 
 fn parse_resolv_conf<T: AsRef<[u8]>>(data: T) -> io::Result<(ResolverConfig, ResolverOpts)> {
+    let (data, extra_options) = extract_extra_options(data.as_ref());
     let parsed_conf = resolv_conf::Config::parse(&data).map_err(|e| {
         io::Error::new(
             io::ErrorKind::Other,
             format!("Error parsing resolv.conf: {:?}", e),
         )
     })?;
-    into_resolver_config(parsed_conf)
+    into_resolver_config(parsed_conf, extra_options)
+}
+
+/// Options of resolv.conf which the `resolv_conf` crate does not know
+#[derive(Debug, Default, PartialEq)]
+struct ExtraOptions {
+    no_aaaa: bool,
+    trust_ad: bool,
+}
+
+/// Removes the options unknown to `resolv_conf` from the data, as it fails to parse them
+fn extract_extra_options(data: &[u8]) -> (Vec<u8>, ExtraOptions) {
+    let mut extra_options = ExtraOptions::default();
+    let mut stripped = Vec::with_capacity(data.len());
+
+    for line in data.split(|&c| c == b'\n') {
+        // invalid utf-8 is left for resolv_conf to report, or ignore in comments
+        let text = str::from_utf8(line)
+            .ok()
+            .and_then(|line| line.split(|c| c == ';' || c == '#').next());
+        let mut words = text.map(str::split_whitespace);
+
+        match words.as_mut().map(|words| (words.next(), words)) {
+            Some((Some("options"), words)) => {
+                let mut options = vec!["options"];
+                for word in words {
+                    match word {
+                        "no-aaaa" => extra_options.no_aaaa = true,
+                        "trust-ad" => extra_options.trust_ad = true,
+                        option => options.push(option),
+                    }
+                }
+                stripped.extend_from_slice(options.join(" ").as_bytes());
+            }
+            _ => stripped.extend_from_slice(line),
+        }
+        stripped.push(b'\n');
+    }
+
+    (stripped, extra_options)
 }
 
 // TODO: use a custom parsing error type maybe?
 fn into_resolver_config(
     parsed_config: resolv_conf::Config,
+    extra_options: ExtraOptions,
 ) -> io::Result<(ResolverConfig, ResolverOpts)> {
     let domain = if let Some(domain) = parsed_config.get_system_domain() {
         Some(Name::from_str(domain.as_str())?)
@@ -86,12 +127,34 @@ fn into_resolver_config(
         })?);
     }
 
-    let config = ResolverConfig::from_parts(domain, search, nameservers);
+    let mut config = ResolverConfig::from_parts(domain, search, nameservers);
+
+    // sortlist
+    for network in &parsed_config.sortlist {
+        let entry = match *network {
+            resolv_conf::Network::V4(address, netmask) => {
+                SortlistEntry::new(address.into(), netmask.into())
+            }
+            resolv_conf::Network::V6(address, netmask) => {
+                SortlistEntry::new(address.into(), netmask.into())
+            }
+        };
+        config.add_sortlist(entry);
+    }
 
     let mut options = ResolverOpts::default();
     options.ndots = parsed_config.ndots as usize;
     options.timeout = Duration::from_secs(u64::from(parsed_config.timeout));
     options.attempts = parsed_config.attempts as usize;
+    options.rotate = parsed_config.rotate;
+    options.edns0 = parsed_config.edns0;
+    options.use_vc = parsed_config.use_vc;
+    options.single_request = parsed_config.single_request;
+    options.trust_ad = extra_options.trust_ad;
+    options.clear_untrusted_ad = true;
+    if extra_options.no_aaaa {
+        options.ip_strategy = LookupIpStrategy::Ipv4Only;
+    }
 
     Ok((config, options))
 }
@@ -129,6 +192,14 @@ mod tests {
         format!{"{}/../resolver/tests", server_path}
     }
 
+    /// The options of a resolv.conf without any options
+    fn system_opts() -> ResolverOpts {
+        let mut options = ResolverOpts::default();
+        options.edns0 = false;
+        options.clear_untrusted_ad = true;
+        options
+    }
+
     #[test]
     fn test_name_server() {
         let parsed = parse_resolv_conf("nameserver 127.0.0.1").expect("failed");
@@ -137,7 +208,7 @@ mod tests {
         cfg.add_name_server(nameservers[0].clone());
         cfg.add_name_server(nameservers[1].clone());
         assert_eq!(cfg.name_servers(), parsed.0.name_servers());
-        assert_eq!(ResolverOpts::default(), parsed.1);
+        assert_eq!(system_opts(), parsed.1);
     }
 
     #[test]
@@ -146,7 +217,7 @@ mod tests {
         let mut cfg = empty_config();
         cfg.add_search(Name::from_str("localnet.").unwrap());
         assert_eq!(cfg.search(), parsed.0.search());
-        assert_eq!(ResolverOpts::default(), parsed.1);
+        assert_eq!(system_opts(), parsed.1);
     }
 
     #[test]
@@ -155,7 +226,7 @@ mod tests {
         let mut cfg = empty_config();
         cfg.add_search(Name::from_str_relaxed("Speedport_000.").unwrap());
         assert_eq!(cfg.search(), parsed.0.search());
-        assert_eq!(ResolverOpts::default(), parsed.1);
+        assert_eq!(system_opts(), parsed.1);
     }
 
     #[test]
@@ -164,7 +235,7 @@ mod tests {
         let mut cfg = empty_config();
         cfg.set_domain(Name::from_str("example.com").unwrap());
         assert_eq!(cfg, parsed.0);
-        assert_eq!(ResolverOpts::default(), parsed.1);
+        assert_eq!(system_opts(), parsed.1);
     }
 
     #[test]
@@ -173,4 +244,49 @@ mod tests {
         read_resolv_conf(format!("{}/resolv.conf-macos", tests_dir())).expect("macos failed");
         read_resolv_conf(format!("{}/resolv.conf-linux", tests_dir())).expect("linux failed");
     }
+
+    #[test]
+    fn test_options() {
+        let parsed = parse_resolv_conf(
+            "options rotate edns0 use-vc single-request no-aaaa trust-ad ndots:2 # no-aaaa",
+        ).expect("failed");
+        let options = parsed.1;
+
+        assert!(options.rotate);
+        assert!(options.edns0);
+        assert!(options.use_vc);
+        assert!(options.single_request);
+        assert!(options.trust_ad);
+        assert_eq!(options.ip_strategy, LookupIpStrategy::Ipv4Only);
+        assert_eq!(options.ndots, 2);
+    }
+
+    #[test]
+    fn test_extra_options_in_comments() {
+        let (data, extra_options) =
+            extract_extra_options(b"# options no-aaaa\noptions rotate ; trust-ad\n");
+
+        assert_eq!(extra_options, ExtraOptions::default());
+        assert_eq!(data, b"# options no-aaaa\noptions rotate\n\n".to_vec());
+    }
+
+    #[test]
+    fn test_sortlist() {
+        let parsed =
+            parse_resolv_conf("sortlist 130.155.160.0/255.255.240.0 130.155.0.0").expect("failed");
+
+        assert_eq!(
+            parsed.0.sortlist(),
+            &[
+                SortlistEntry::new(
+                    IpAddr::from_str("130.155.160.0").unwrap(),
+                    IpAddr::from_str("255.255.240.0").unwrap(),
+                ),
+                SortlistEntry::new(
+                    IpAddr::from_str("130.155.0.0").unwrap(),
+                    IpAddr::from_str("255.255.0.0").unwrap(),
+                ),
+            ]
+        );
+    }
 }
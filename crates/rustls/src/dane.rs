@@ -0,0 +1,199 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! DANE, a rustls certificate verifier for the TLSA records of a service
+//!
+//! The TLSA records must have been validated with DNSSEC, e.g. with `AsyncResolver::dane_lookup` of the resolver.
+
+use std::time::SystemTime;
+
+use rustls::{Certificate, RootCertStore, ServerCertVerified, ServerCertVerifier, TLSError};
+use untrusted::Input;
+use webpki::{
+    self, DNSNameRef, EndEntityCert, SignatureAlgorithm, TLSServerTrustAnchors, TrustAnchor,
+};
+
+use trust_dns_proto::rr::dane;
+use trust_dns_proto::rr::rdata::tlsa::TLSA;
+
+/// The signature algorithms trusted in certificates, the same as those of rustls
+static SUPPORTED_SIG_ALGS: &[&SignatureAlgorithm] = &[
+    &webpki::ECDSA_P256_SHA256,
+    &webpki::ECDSA_P256_SHA384,
+    &webpki::ECDSA_P384_SHA256,
+    &webpki::ECDSA_P384_SHA384,
+    &webpki::RSA_PSS_2048_8192_SHA256_LEGACY_KEY,
+    &webpki::RSA_PSS_2048_8192_SHA384_LEGACY_KEY,
+    &webpki::RSA_PSS_2048_8192_SHA512_LEGACY_KEY,
+    &webpki::RSA_PKCS1_2048_8192_SHA256,
+    &webpki::RSA_PKCS1_2048_8192_SHA384,
+    &webpki::RSA_PKCS1_2048_8192_SHA512,
+    &webpki::RSA_PKCS1_3072_8192_SHA384,
+];
+
+/// Verifies the certificates of a server with its TLSA records, see `trust_dns_proto::rr::dane::verify_chain`
+///
+/// If none of the records is usable, DANE does not apply and the certificates are verified with the roots of the
+///  `ClientConfig`, as rustls does by default.
+///
+/// ```rust,ignore
+/// config.dangerous().set_certificate_verifier(Arc::new(DaneVerifier::new(tlsas)));
+/// ```
+pub struct DaneVerifier {
+    tlsas: Vec<TLSA>,
+}
+
+impl DaneVerifier {
+    /// Creates a verifier for the validated TLSA records of the server
+    pub fn new(tlsas: Vec<TLSA>) -> Self {
+        DaneVerifier { tlsas }
+    }
+}
+
+impl ServerCertVerifier for DaneVerifier {
+    fn verify_server_cert(
+        &self,
+        roots: &RootCertStore,
+        presented_certs: &[Certificate],
+        dns_name: DNSNameRef,
+        _ocsp_response: &[u8],
+    ) -> Result<ServerCertVerified, TLSError> {
+        if presented_certs.is_empty() {
+            return Err(TLSError::NoCertificatesPresented);
+        }
+
+        let anchors = roots
+            .roots
+            .iter()
+            .map(|root| root.to_trust_anchor())
+            .collect::<Vec<_>>();
+        let chain = presented_certs
+            .iter()
+            .map(|cert| cert.0.as_slice())
+            .collect::<Vec<_>>();
+        let pkix = || verify_cert(&chain, &chain[1..], &anchors, dns_name);
+
+        if !self.tlsas.iter().any(dane::is_usable) {
+            return pkix().map(|_| ServerCertVerified::assertion());
+        }
+
+        let usage = dane::verify_chain(
+            &self.tlsas,
+            &chain,
+            |index| pkix_through(&chain, index, &anchors, dns_name),
+            |index| {
+                cert_as_trust_anchor(chain[index])
+                    .and_then(|anchor| verify_cert(&chain, &chain[1..index], &[anchor], dns_name))
+                    .is_ok()
+            },
+        )
+        .map_err(|e| TLSError::General(e.to_string()))?;
+
+        debug!(
+            "certificate of {:?} verified with DANE, {:?}",
+            dns_name, usage
+        );
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+/// Returns true if the chain is valid with the trusted roots on a path through the certificate at the index
+///
+/// webpki does not return the path it built, so the certificate must either be one of the roots and verify the chain
+///  up to it, or the chain must only be valid with it among the intermediates.
+fn pkix_through(
+    chain: &[&[u8]],
+    index: usize,
+    anchors: &[TrustAnchor],
+    dns_name: DNSNameRef,
+) -> bool {
+    if verify_cert(chain, &chain[1..], anchors, dns_name).is_err() {
+        return false;
+    }
+    if index == 0 {
+        return true;
+    }
+
+    let anchor = match cert_as_trust_anchor(chain[index]) {
+        Ok(anchor) => anchor,
+        Err(_) => return false,
+    };
+    if anchors
+        .iter()
+        .any(|root| root.subject == anchor.subject && root.spki == anchor.spki)
+    {
+        return verify_cert(chain, &chain[1..index], &[anchor], dns_name).is_ok();
+    }
+
+    let without = chain[1..]
+        .iter()
+        .enumerate()
+        .filter(|&(i, _)| i + 1 != index)
+        .map(|(_, cert)| *cert)
+        .collect::<Vec<_>>();
+    verify_cert(chain, &without, anchors, dns_name).is_err()
+}
+
+/// Returns the DER encoded certificate as a trust anchor
+fn cert_as_trust_anchor<'a>(cert: &'a [u8]) -> Result<TrustAnchor<'a>, TLSError> {
+    webpki::trust_anchor_util::cert_der_as_trust_anchor(Input::from(cert))
+        .map_err(TLSError::WebPKIError)
+}
+
+/// Verifies the end entity certificate, the first of the chain, with the intermediates, and the name
+fn verify_cert(
+    chain: &[&[u8]],
+    intermediates: &[&[u8]],
+    anchors: &[TrustAnchor],
+    dns_name: DNSNameRef,
+) -> Result<(), TLSError> {
+    let cert = EndEntityCert::from(Input::from(chain[0])).map_err(TLSError::WebPKIError)?;
+    let intermediates = intermediates
+        .iter()
+        .map(|cert| Input::from(cert))
+        .collect::<Vec<_>>();
+    let now =
+        webpki::Time::try_from(SystemTime::now()).map_err(|_| TLSError::FailedToGetCurrentTime)?;
+
+    cert.verify_is_valid_tls_server_cert(
+        SUPPORTED_SIG_ALGS,
+        &TLSServerTrustAnchors(anchors),
+        &intermediates,
+        now,
+    )
+    .and_then(|_| cert.verify_is_valid_for_dns_name(dns_name))
+    .map_err(TLSError::WebPKIError)
+}
+
+#[cfg(test)]
+mod tests {
+    use trust_dns_proto::rr::rdata::tlsa::{CertUsage, Matching, Selector};
+
+    use super::*;
+
+    const CERT: &[u8] = include_bytes!("../../../tests/test-data/cert.der");
+
+    fn verify(tlsas: Vec<TLSA>) -> Result<ServerCertVerified, TLSError> {
+        DaneVerifier::new(tlsas).verify_server_cert(
+            &RootCertStore::empty(),
+            &[Certificate(CERT.to_vec())],
+            DNSNameRef::try_from_ascii_str("ns.example.com").unwrap(),
+            &[],
+        )
+    }
+
+    #[test]
+    fn test_dane_verifier() {
+        let tlsa = |usage, data| TLSA::new(usage, Selector::Full, Matching::Raw, data);
+
+        assert!(verify(vec![tlsa(CertUsage::DomainIssued, CERT.to_vec())]).is_ok());
+        assert!(verify(vec![tlsa(CertUsage::DomainIssued, vec![0; 32])]).is_err());
+
+        // not usable, the certificate is verified with the empty roots
+        assert!(verify(vec![tlsa(CertUsage::Private, CERT.to_vec())]).is_err());
+    }
+}
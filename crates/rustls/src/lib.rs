@@ -23,10 +23,14 @@ extern crate tokio;
 extern crate tokio_rustls;
 extern crate tokio_tcp;
 extern crate trust_dns_proto;
+#[cfg(feature = "dane")]
+extern crate untrusted;
 extern crate webpki;
 #[macro_use]
 extern crate log;
 
+#[cfg(feature = "dane")]
+pub mod dane;
 pub mod tls_client_stream;
 pub mod tls_server;
 pub mod tls_stream;
@@ -126,6 +126,7 @@ impl<'a> IntoIterator for &'a AuthLookup {
 
 /// An iterator over an Authority Lookup
 #[allow(clippy::large_enum_variant)]
+#[derive(Clone)]
 pub enum AuthLookupIter<'r> {
     /// The empty set
     Empty,
@@ -223,6 +224,7 @@ impl<'r> IntoIterator for &'r AnyRecords {
 }
 
 /// An iteration over a lookup for any Records
+#[derive(Clone)]
 pub struct AnyRecordsIter<'r> {
     is_secure: bool,
     supported_algorithms: SupportedAlgorithms,
@@ -362,6 +364,7 @@ impl<'a> IntoIterator for &'a LookupRecords {
 }
 
 /// Iteratof over lookup records
+#[derive(Clone)]
 pub enum LookupRecordsIter<'r> {
     /// An iteration over batch record type results
     AnyRecordsIter(AnyRecordsIter<'r>),
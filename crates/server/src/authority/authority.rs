@@ -7,12 +7,16 @@
 
 //! All authority related types
 
-use trust_dns::op::LowerQuery;
+use trust_dns::op::{LowerQuery, Query};
 use trust_dns::rr::dnssec::{DnsSecError, DnsSecResult, Signer, SupportedAlgorithms};
+use trust_dns::rr::rdata::opt::ClientSubnet;
 use trust_dns::rr::{LowerName, Name, RecordType};
 use trust_dns::proto::rr::dnssec::rdata::key::KEY;
+use trust_dns_resolver::recursor::RecursorFuture;
+use trust_dns_resolver::Recursor;
 
 use authority::{AuthLookup, MessageRequest, UpdateResult, ZoneType};
+use server::{count_resolved, Metrics};
 
 /// Authority implementations can be used with a `Catalog`
 pub trait Authority: Send {
@@ -76,14 +80,21 @@ pub trait Authority: Send {
         //  for AXFR the first and last record must be the SOA
         if RecordType::AXFR == record_type {
             // TODO: support more advanced AXFR options
+            let zone = self.origin().to_string();
             if !self.is_axfr_allowed() {
+                Metrics::global().zone_transfers.inc(&[&zone, "refused"]);
                 return AuthLookup::Refused;
             }
 
             match self.zone_type() {
-                ZoneType::Master | ZoneType::Slave => (),
+                ZoneType::Master | ZoneType::Slave => {
+                    Metrics::global().zone_transfers.inc(&[&zone, "success"])
+                }
                 // TODO: Forward?
-                _ => return AuthLookup::NxDomain, // TODO: this sould be an error.
+                _ => {
+                    Metrics::global().zone_transfers.inc(&[&zone, "refused"]);
+                    return AuthLookup::NxDomain; // TODO: this sould be an error.
+                }
             }
         }
 
@@ -112,6 +123,21 @@ pub trait Authority: Send {
         }
     }
 
+    /// Performs the lookup of `search` for a request with the client subnet, see RFC 7871
+    ///
+    /// Geo-aware authorities override this to tailor the answer to the subnet of the client. The returned scope prefix
+    ///  is the number of leading bits of the address the answer applies to, and is sent back in the response. The
+    ///  default ignores the subnet and returns the answer of `search` with a scope prefix of 0, i.e. for all clients.
+    fn search_with_client_subnet(
+        &self,
+        query: &LowerQuery,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+        _client_subnet: Option<&ClientSubnet>,
+    ) -> (AuthLookup, u8) {
+        (self.search(query, is_secure, supported_algorithms), 0)
+    }
+
     /// Get the NS, NameServer, record for the zone
     fn ns(&self, is_secure: bool, supported_algorithms: SupportedAlgorithms) -> AuthLookup {
         self.lookup(
@@ -160,6 +186,34 @@ pub trait Authority: Send {
         )
     }
 
+    /// Returns true if the zone has zone cuts below its origin, i.e. NS records of other names
+    ///
+    /// Names of zones without delegations are never answered with a referral. By default every
+    ///  zone may have delegations.
+    fn has_delegations(&self) -> bool {
+        true
+    }
+
+    /// The resolver used to answer queries with the RD bit set, only `ZoneType::Hint` zones have one
+    fn recursor(&self) -> Option<&Recursor> {
+        None
+    }
+
+    /// Resolves a query with the RD bit set, `None` if the query is answered from the zone's records
+    ///
+    /// By default the query is resolved with the `recursor`, if there is one.
+    fn resolve(&self, query: &Query) -> Option<RecursorFuture> {
+        self.recursor()
+            .map(|recursor| count_resolved(self.origin(), recursor.resolve(query.clone())))
+    }
+
+    /// Removes all cached answers of a zone which resolves queries, false if the zone has no cache
+    ///
+    /// By default the cache of the `recursor` is flushed, if there is one.
+    fn flush_cache(&self) -> bool {
+        self.recursor().map(Recursor::flush).is_some()
+    }
+
     // TODO: this should probably be a general purpose higher level component?
     /// Add a (Sig0) key that is authorized to perform updates against this authority
     fn add_update_auth_key(&mut self, _name: Name, _key: KEY) -> DnsSecResult<()> {
@@ -16,24 +16,38 @@
 // TODO, I've implemented this as a seperate entity from the cache, but I wonder if the cache
 //  should be the only "front-end" for lookups, where if that misses, then we go to the catalog
 //  then, if requested, do a recursive lookup... i.e. the catalog would only point to files.
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::io;
-use std::sync::RwLock;
+use std::net::IpAddr;
+use std::sync::{Arc, RwLock};
 
-use server::{Request, RequestHandler, ResponseHandler};
+use futures::Future;
+use tokio_executor;
+
+use server::{Metrics, Request, RequestHandler, ResponseHandler};
 use trust_dns::op::{Edns, Header, LowerQuery, MessageType, OpCode, ResponseCode};
 use trust_dns::rr::dnssec::{Algorithm, SupportedAlgorithms};
-use trust_dns::rr::rdata::opt::{EdnsCode, EdnsOption};
-use trust_dns::rr::{LowerName, RecordType};
+use trust_dns::rr::rdata::opt::{ClientSubnet, EdnsCode, EdnsOption};
+use trust_dns::rr::{LowerName, RData, Record, RecordSet, RecordType};
+use trust_dns::serialize::binary::BinDecoder;
+use trust_dns_resolver::recursor::RecursorFuture;
 
 use authority::{
-    AuthLookup, Authority, MessageRequest, MessageResponse, MessageResponseBuilder, ZoneType,
+    AuthLookup, Authority, LookupRecords, MessageRequest, MessageResponse, MessageResponseBuilder,
+    NetworkPrefix, Queries, ZoneType,
 };
 
 /// Set of authorities, zones, available to this server.
 #[derive(Default)]
 pub struct Catalog {
     authorities: HashMap<LowerName, RwLock<Box<dyn Authority>>>,
+    frozen: HashSet<LowerName>,
+    /// zones with zone cuts below their origin, updated when a zone is added or updated
+    delegated: RwLock<HashSet<LowerName>>,
+    /// names below the zone cuts of Master and Slave zones are answered with referrals, see `set_referrals`
+    referrals: bool,
+    /// clients whose queries with the RD bit set are resolved
+    allow_recursion: Vec<NetworkPrefix>,
 }
 
 fn send_response<R: ResponseHandler + 'static>(
@@ -62,6 +76,158 @@ fn send_response<R: ResponseHandler + 'static>(
     response_handle.send_response(response)
 }
 
+/// Echoes the client subnet of the request with the scope prefix of the answer, see RFC 7871 section 7.2.1
+fn set_client_subnet(
+    response_edns: &mut Option<Edns>,
+    client_subnet: Option<&ClientSubnet>,
+    scope_prefix: u8,
+) {
+    if let (Some(resp_edns), Some(client_subnet)) = (response_edns.as_mut(), client_subnet) {
+        resp_edns.set_option(EdnsOption::Subnet(
+            client_subnet.with_scope_prefix(scope_prefix),
+        ));
+    }
+}
+
+/// Sends the response once the resolution of the query completes
+fn recurse<R: ResponseHandler + 'static>(
+    resolve: RecursorFuture,
+    request: &MessageRequest,
+    response_edns: Option<Edns>,
+    response_handle: R,
+) -> io::Result<()> {
+    // the request is borrowed, the queries are reread from their original bytes for the response
+    let id = request.id();
+    let query_count = request.queries().len();
+    let raw_queries = request.raw_queries().as_bytes().to_vec();
+
+    let resolve = resolve.then(move |result| {
+        let queries = Queries::read(&mut BinDecoder::new(&raw_queries), query_count)
+            .map_err(|e| warn!("failed to read queries of request: {} error: {}", id, e))?;
+
+        let mut response_header = Header::new();
+        response_header.set_id(id);
+        response_header.set_op_code(OpCode::Query);
+        response_header.set_message_type(MessageType::Response);
+        response_header.set_recursion_desired(true);
+        response_header.set_recursion_available(true);
+
+        let (answers, name_servers) = match result {
+            Ok(mut message) => {
+                response_header.set_response_code(message.response_code());
+                (
+                    record_sets(message.take_answers()),
+                    record_sets(message.take_name_servers()),
+                )
+            }
+            Err(e) => {
+                info!("request: {} recursion failed: {}", id, e);
+                response_header.set_response_code(ResponseCode::ServFail);
+                (AuthLookup::default(), AuthLookup::default())
+            }
+        };
+
+        let soa = AuthLookup::default();
+        let response = MessageResponseBuilder::new(Some(&queries)).build(
+            response_header,
+            answers.iter(),
+            name_servers.iter(),
+            soa.iter(),
+        );
+
+        send_response(response_edns, response, response_handle)
+            .map_err(|e| warn!("failed to send response to request: {} error: {}", id, e))
+    });
+
+    tokio_executor::spawn(resolve);
+    Ok(())
+}
+
+/// DS records are served from the parent side of a zone cut, and never referred
+#[cfg(feature = "dnssec")]
+fn is_ds(rtype: RecordType) -> bool {
+    use trust_dns::rr::rdata::DNSSECRecordType;
+
+    rtype == RecordType::DNSSEC(DNSSECRecordType::DS)
+}
+
+#[cfg(not(feature = "dnssec"))]
+fn is_ds(_rtype: RecordType) -> bool {
+    false
+}
+
+/// Finds the zone cut closest to the origin of the authority above or at `name`
+///
+/// Returns the NS records of the delegation and any glue for name servers within the authority.
+fn find_delegation(
+    authority: &dyn Authority,
+    name: &LowerName,
+    is_secure: bool,
+    supported_algorithms: SupportedAlgorithms,
+) -> Option<(AuthLookup, Vec<AuthLookup>)> {
+    let origin = authority.origin();
+    let mut cut = name.clone();
+    let mut delegation = None;
+
+    // records below the highest zone cut are occluded, so keep walking up to the origin
+    while cut != *origin && origin.zone_of(&cut) {
+        let ns = authority.lookup(&cut, RecordType::NS, is_secure, supported_algorithms);
+        if !ns.is_empty() {
+            delegation = Some(ns);
+        }
+
+        cut = cut.base_name();
+    }
+
+    let ns = delegation?;
+    let glue = ns
+        .iter()
+        .filter_map(|record| match *record.rdata() {
+            RData::NS(ref target) => Some(LowerName::new(target)),
+            _ => None,
+        })
+        .filter(|target| origin.zone_of(target))
+        .flat_map(|target| {
+            [RecordType::A, RecordType::AAAA]
+                .iter()
+                .map(|rtype| authority.lookup(&target, *rtype, is_secure, supported_algorithms))
+                .collect::<Vec<_>>()
+        })
+        .filter(|glue| !glue.is_empty())
+        .collect();
+
+    Some((ns, glue))
+}
+
+/// Groups the records into RecordSets, keeping the order in which names and types first appear
+fn record_sets(records: Vec<Record>) -> AuthLookup {
+    let mut rrsets: Vec<RecordSet> = vec![];
+    for record in records {
+        let position = rrsets.iter().position(|rrset| {
+            rrset.name() == record.name() && rrset.record_type() == record.rr_type()
+        });
+
+        let rrset = match position {
+            Some(position) => &mut rrsets[position],
+            None => {
+                rrsets.push(RecordSet::new(record.name(), record.rr_type(), 0));
+                rrsets.last_mut().expect("rrset was just added")
+            }
+        };
+        rrset.insert(record, 0);
+    }
+
+    if rrsets.is_empty() {
+        return AuthLookup::default();
+    }
+
+    LookupRecords::many(
+        false,
+        SupportedAlgorithms::new(),
+        rrsets.into_iter().map(Arc::new).collect(),
+    ).into()
+}
+
 impl RequestHandler for Catalog {
     /// Determine's what needs to happen given the type of request, i.e. Query or Update.
     ///
@@ -107,6 +273,15 @@ impl RequestHandler for Catalog {
                 return response_handle.send_response(response.build_no_records(response_header));
             }
 
+            // the client subnet option is only kept as unknown bytes if it was malformed, RFC 7871 section 7.1.2
+            if let Some(&EdnsOption::Unknown(..)) = req_edns.option(EdnsCode::Subnet) {
+                warn!("request: {} malformed client subnet", request_message.id());
+                response_header.set_response_code(ResponseCode::FormErr);
+                response.edns(resp_edns);
+
+                return response_handle.send_response(response.build_no_records(response_header));
+            }
+
             response_edns = Some(resp_edns);
         } else {
             response_edns = None;
@@ -116,7 +291,12 @@ impl RequestHandler for Catalog {
             // TODO think about threading query lookups for multiple lookups, this could be a huge improvement
             //  especially for recursive lookups
             MessageType::Query => match request_message.op_code() {
-                OpCode::Query => self.lookup(request_message, response_edns, response_handle),
+                OpCode::Query => self.lookup_with_recursion(
+                    request_message,
+                    self.is_recursion_allowed(request.src.ip()),
+                    response_edns,
+                    response_handle,
+                ),
                 OpCode::Update => self.update(request_message, response_edns, response_handle),
                 c => {
                     error!("unimplemented op_code: {:?}", c);
@@ -150,6 +330,10 @@ impl Catalog {
     pub fn new() -> Self {
         Catalog {
             authorities: HashMap::new(),
+            frozen: HashSet::new(),
+            delegated: RwLock::new(HashSet::new()),
+            referrals: false,
+            allow_recursion: vec![],
         }
     }
 
@@ -160,14 +344,77 @@ impl Catalog {
     /// * `name` - zone name, e.g. example.com.
     /// * `authority` - the zone data
     pub fn upsert(&mut self, name: LowerName, authority: Box<dyn Authority>) {
+        self.set_delegated(&name, authority.has_delegations());
         self.authorities.insert(name, RwLock::new(authority));
     }
 
     /// Remove a zone from the catalog
     pub fn remove(&mut self, name: &LowerName) -> Option<RwLock<Box<dyn Authority>>> {
+        self.frozen.remove(name);
+        self.set_delegated(name, false);
         self.authorities.remove(name)
     }
 
+    fn set_delegated(&self, name: &LowerName, has_delegations: bool) {
+        let mut delegated = self.delegated.write().expect("poisoned lock");
+        if has_delegations {
+            delegated.insert(name.clone());
+        } else {
+            delegated.remove(name);
+        }
+    }
+
+    /// Answer queries for names below the zone cuts of Master and Slave zones with referrals to the child zones
+    ///
+    /// Disabled by default, the names are then answered from the records of the zone, as if there were no zone cuts.
+    ///  Referrals are needed by iterative resolvers, e.g. `ZoneType::Hint` zones, to follow the delegations.
+    pub fn set_referrals(&mut self, referrals: bool) {
+        self.referrals = referrals;
+    }
+
+    /// Resolve queries with the RD bit set only for clients in these networks, none by default
+    pub fn set_allow_recursion(&mut self, clients: Vec<NetworkPrefix>) {
+        self.allow_recursion = clients;
+    }
+
+    /// Returns true if queries with the RD bit set are resolved for the client
+    pub fn is_recursion_allowed(&self, client: IpAddr) -> bool {
+        self.allow_recursion
+            .iter()
+            .any(|network| network.contains(client))
+    }
+
+    /// Freeze a zone, all dynamic updates to it are refused until it is thawed
+    ///
+    /// The zone stays frozen when it is replaced with `upsert`, e.g. on reload.
+    ///
+    /// # Return value
+    ///
+    /// false if the zone is not in the catalog
+    pub fn freeze(&mut self, name: &LowerName) -> bool {
+        if !self.contains(name) {
+            return false;
+        }
+
+        self.frozen.insert(name.clone());
+        true
+    }
+
+    /// Thaw a frozen zone, allowing dynamic updates again
+    ///
+    /// # Return value
+    ///
+    /// false if the zone is not in the catalog
+    pub fn thaw(&mut self, name: &LowerName) -> bool {
+        self.frozen.remove(name);
+        self.contains(name)
+    }
+
+    /// Returns true if the zone is frozen, see `freeze`
+    pub fn is_frozen(&self, name: &LowerName) -> bool {
+        self.frozen.contains(name)
+    }
+
     /// Update the zone given the Update request.
     ///
     /// [RFC 2136](https://tools.ietf.org/html/rfc2136), DNS Update, April 1997
@@ -273,14 +520,28 @@ impl Catalog {
                         response_handle,
                     );
                 }
+                ZoneType::Master if self.is_frozen(authority.origin()) => {
+                    warn!("refusing update to frozen zone: {}", authority.origin());
+                    response_header.set_response_code(ResponseCode::Refused);
+
+                    return send_response(
+                        response_edns,
+                        response.build_no_records(response_header),
+                        response_handle,
+                    );
+                }
                 ZoneType::Master => {
                     let update_result = authority.update(update);
+                    self.set_delegated(authority.origin(), authority.has_delegations());
+                    let zone = authority.origin().to_string();
                     match update_result {
                         // successful update
                         Ok(..) => {
+                            Metrics::global().updates.inc(&[&zone, "success"]);
                             response_header.set_response_code(ResponseCode::NoError);
                         }
                         Err(response_code) => {
+                            Metrics::global().updates.inc(&[&zone, "failure"]);
                             response_header.set_response_code(response_code);
                         }
                     }
@@ -325,8 +586,15 @@ impl Catalog {
         self.authorities.contains_key(name)
     }
 
+    /// Returns the names of all zones in the catalog
+    pub fn zone_names(&self) -> impl Iterator<Item = &LowerName> {
+        self.authorities.keys()
+    }
+
     /// Given the requested query, lookup and return any matching results.
     ///
+    /// The client of the request is not known, so queries are never resolved recursively.
+    ///
     /// # Arguments
     ///
     /// * `request` - the query message.
@@ -336,6 +604,17 @@ impl Catalog {
         request: &'q MessageRequest,
         response_edns: Option<Edns>,
         response_handle: R,
+    ) -> io::Result<()> {
+        self.lookup_with_recursion(request, false, response_edns, response_handle)
+    }
+
+    /// Lookup the query, and resolve it with Hint and Forward zones if `is_recursion_allowed`
+    fn lookup_with_recursion<'q, R: ResponseHandler + 'static>(
+        &self,
+        request: &'q MessageRequest,
+        is_recursion_allowed: bool,
+        mut response_edns: Option<Edns>,
+        response_handle: R,
     ) -> io::Result<()> {
         // TODO: the spec is very unclear on what to do with multiple queries
         //  we will search for each, in the future, maybe make this threaded to respond even faster.
@@ -347,6 +626,16 @@ impl Catalog {
                     request.id(),
                     authority.origin()
                 );
+                Metrics::global()
+                    .zone_queries
+                    .inc(&[&authority.origin().to_string()]);
+
+                if request.recursion_desired() && is_recursion_allowed {
+                    if let Some(resolve) = authority.resolve(query.original()) {
+                        set_client_subnet(&mut response_edns, request.client_subnet(), 0);
+                        return recurse(resolve, request, response_edns, response_handle);
+                    }
+                }
 
                 let mut response = MessageResponseBuilder::new(Some(request.raw_queries()));
                 let mut response_header = Header::new();
@@ -382,7 +671,47 @@ impl Catalog {
                     );
                 }
 
-                let records = authority.search(query, is_dnssec, supported_algorithms);
+                // names below a zone cut are answered with a referral to the child zone
+                let is_delegated = self.referrals
+                    && self
+                        .delegated
+                        .read()
+                        .expect("poisoned lock")
+                        .contains(authority.origin());
+                let delegation = match authority.zone_type() {
+                    ZoneType::Master | ZoneType::Slave
+                        if is_delegated && !is_ds(query.query_type()) =>
+                    {
+                        find_delegation(
+                            &***authority,
+                            query.name(),
+                            is_dnssec,
+                            supported_algorithms,
+                        )
+                    }
+                    _ => None,
+                };
+
+                if let Some((ns, glue)) = delegation {
+                    debug!("request: {} referral for: {}", request.id(), query.name());
+                    response_header.set_authoritative(false);
+                    response_header.set_response_code(ResponseCode::NoError);
+
+                    let empty = AuthLookup::default();
+                    let mut response =
+                        response.build(response_header, empty.iter(), ns.iter(), empty.iter());
+                    response.set_additionals(glue.iter().flat_map(AuthLookup::iter).collect());
+                    set_client_subnet(&mut response_edns, request.client_subnet(), 0);
+                    return send_response(response_edns, response, response_handle);
+                }
+
+                let (records, scope_prefix) = authority.search_with_client_subnet(
+                    query,
+                    is_dnssec,
+                    supported_algorithms,
+                    request.client_subnet(),
+                );
+                set_client_subnet(&mut response_edns, request.client_subnet(), scope_prefix);
 
                 // setup headers
                 //  and add records
@@ -456,8 +785,8 @@ impl Catalog {
     /// Recursively searches the catalog for a matching authority
     pub fn find(&self, name: &LowerName) -> Option<&RwLock<Box<dyn Authority>>> {
         self.authorities.get(name).or_else(|| {
-            let name = name.base_name();
             if !name.is_root() {
+                let name = name.base_name();
                 self.find(&name)
             } else {
                 None
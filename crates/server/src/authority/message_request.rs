@@ -8,10 +8,13 @@
 use proto::error::*;
 use proto::op::message::EmitAndCount;
 use proto::op::{message, Edns, Header, Message, MessageType, OpCode, ResponseCode};
+use proto::rr::rdata::opt::ClientSubnet;
 use proto::rr::Record;
 use proto::serialize::binary::{BinDecodable, BinDecoder, BinEncodable, BinEncoder};
 use trust_dns::op::LowerQuery;
 
+use authority::tsig::TSIG;
+
 /// A Message which captures the data from an inbound request
 #[derive(Debug, PartialEq)]
 pub struct MessageRequest<'q> {
@@ -21,6 +24,8 @@ pub struct MessageRequest<'q> {
     name_servers: Vec<Record>,
     additionals: Vec<Record>,
     sig0: Vec<Record>,
+    tsig: Option<Record>,
+    tsig_signed: &'q [u8],
     edns: Option<Edns>,
 }
 
@@ -145,11 +150,29 @@ impl<'q> MessageRequest<'q> {
         self.edns.as_ref()
     }
 
+    /// The client subnet of the EDNS record, see [RFC 7871](https://tools.ietf.org/html/rfc7871)
+    ///
+    /// Authorities may tailor their answers to the subnet, which is usually that of the client of the resolver the
+    ///  request is from rather than the source address of the request.
+    pub fn client_subnet(&self) -> Option<&ClientSubnet> {
+        self.edns.as_ref().and_then(Edns::client_subnet)
+    }
+
     /// Any SIG0 records for signed messages
     pub fn sig0(&self) -> &[Record] {
         &self.sig0
     }
 
+    /// The TSIG record of a signed message, which is the final additional record
+    pub fn tsig(&self) -> Option<&Record> {
+        self.tsig.as_ref()
+    }
+
+    /// The message as it was received up to the TSIG record, empty if there is none
+    pub fn tsig_signed_bytes(&self) -> &[u8] {
+        self.tsig_signed
+    }
+
     /// # Return value
     ///
     /// the max payload value as it's defined in the EDNS section.
@@ -179,6 +202,7 @@ impl<'q> BinDecodable<'q> for MessageRequest<'q> {
     // TODO: generify this with Message?
     /// Reads a MessageRequest from the decoder
     fn read(decoder: &mut BinDecoder<'q>) -> ProtoResult<Self> {
+        let message_start = decoder.index();
         let header = Header::read(decoder)?;
 
         // TODO/FIXME: return just header, and in the case of the rest of message getting an error.
@@ -195,7 +219,25 @@ impl<'q> BinDecodable<'q> for MessageRequest<'q> {
         let queries = Queries::read(decoder, query_count)?;
         let (answers, _, _) = Message::read_records(decoder, answer_count, false)?;
         let (name_servers, _, _) = Message::read_records(decoder, name_server_count, false)?;
-        let (additionals, edns, sig0) = Message::read_records(decoder, additional_count, true)?;
+        let additionals_start = decoder.index();
+        let (mut additionals, edns, sig0) = Message::read_records(decoder, additional_count, true)?;
+
+        // the TSIG covers all of the message before it, find where its record starts
+        let (tsig, tsig_signed) = if additionals.last().map_or(false, |r| r.rr_type() == TSIG) {
+            let mut records = decoder.clone(additionals_start as u16);
+            for _ in 1..additional_count {
+                Record::read(&mut records)?;
+            }
+            let tsig_signed = records.slice_from(message_start)?;
+
+            if Record::read(&mut records)?.rr_type() == TSIG {
+                (additionals.pop(), tsig_signed)
+            } else {
+                return Err("TSIG must be final resource record".into());
+            }
+        } else {
+            (None, &[][..])
+        };
 
         Ok(MessageRequest {
             header,
@@ -204,6 +246,8 @@ impl<'q> BinDecodable<'q> for MessageRequest<'q> {
             name_servers,
             additionals,
             sig0,
+            tsig,
+            tsig_signed,
             edns,
         })
     }
@@ -244,6 +288,11 @@ impl<'q> Queries<'q> {
         self.queries.is_empty()
     }
 
+    /// Returns the queries
+    pub fn queries(&self) -> &[LowerQuery] {
+        &self.queries
+    }
+
     /// returns the bytes as they were seen from the Client
     pub fn as_bytes(&self) -> &[u8] {
         self.original
@@ -8,7 +8,7 @@
 use proto::error::*;
 use proto::op::message::EmitAndCount;
 use proto::op::{message, Edns, Header, MessageType, OpCode, ResponseCode};
-use trust_dns::rr::Record;
+use trust_dns::rr::{Record, RecordType};
 use trust_dns::serialize::binary::BinEncoder;
 
 use authority::message_request::QueriesEmitAndCount;
@@ -70,6 +70,12 @@ where
         &self.header
     }
 
+    /// Set the records of the additional section, e.g. the glue of a referral
+    pub fn set_additionals(&mut self, additionals: Vec<&'a Record>) -> &mut Self {
+        self.additionals = additionals;
+        self
+    }
+
     /// Set the EDNS options for the Response
     pub fn set_edns(&mut self, edns: Edns) -> &mut Self {
         self.edns = Some(edns);
@@ -94,6 +100,39 @@ where
     }
 }
 
+impl<'q, 'a> MessageResponse<'q, 'a> {
+    /// Returns the queries the message responds to
+    pub fn queries(&self) -> Option<&'q Queries<'q>> {
+        self.queries
+    }
+
+    /// Returns the SOA record of the authority section, e.g. of a negative response
+    pub fn soa_record(&self) -> Option<&'a Record> {
+        self.soa
+            .clone()
+            .chain(self.name_servers.clone())
+            .find(|record| record.rr_type() == RecordType::SOA)
+    }
+
+    /// Converts the response into a truncated response with only the header, question section and
+    ///  EDNS, which asks the client to retry over TCP
+    pub fn into_truncated(self) -> Self {
+        let mut header = self.header;
+        header.set_truncated(true);
+
+        MessageResponse {
+            header,
+            queries: self.queries,
+            answers: Default::default(),
+            name_servers: Default::default(),
+            soa: Default::default(),
+            additionals: Default::default(),
+            sig0: Default::default(),
+            edns: self.edns,
+        }
+    }
+}
+
 /// A builder for MessageResponses
 pub struct MessageResponseBuilder<'q> {
     queries: Option<&'q Queries<'q>>,
@@ -40,6 +40,10 @@ mod authority;
 mod catalog;
 pub(crate) mod message_request;
 mod message_response;
+mod tsig;
+mod view;
+#[cfg(feature = "dnssec")]
+pub(crate) mod zone_digest;
 
 pub use self::auth_lookup::{
     AnyRecords, AuthLookup, AuthLookupIter, LookupRecords, LookupRecordsIter,
@@ -48,3 +52,5 @@ pub use self::authority::Authority;
 pub use self::catalog::Catalog;
 pub use self::message_request::{MessageRequest, Queries, UpdateRequest};
 pub use self::message_response::{MessageResponse, MessageResponseBuilder};
+pub use self::tsig::{TsigAlgorithm, TsigKey};
+pub use self::view::{NetworkPrefix, View, ViewCatalog};
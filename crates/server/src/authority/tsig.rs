@@ -0,0 +1,370 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Verification of requests signed with TSIG, [RFC 8945](https://tools.ietf.org/html/rfc8945)
+
+use std::fmt;
+use std::str::FromStr;
+
+use proto::error::{ProtoError, ProtoResult};
+use proto::serialize::binary::{BinDecodable, BinDecoder, BinEncoder};
+use trust_dns::rr::{DNSClass, LowerName, Name, RData, RecordType};
+
+use authority::MessageRequest;
+
+/// The record type of TSIG, which is not otherwise supported
+pub const TSIG: RecordType = RecordType::Unknown(250);
+
+/// The HMAC algorithms of TSIG keys, see [RFC 8945, section 6](https://tools.ietf.org/html/rfc8945#section-6)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TsigAlgorithm {
+    /// HMAC-SHA1, `hmac-sha1`
+    HmacSha1,
+    /// HMAC-SHA256, `hmac-sha256`
+    HmacSha256,
+    /// HMAC-SHA384, `hmac-sha384`
+    HmacSha384,
+    /// HMAC-SHA512, `hmac-sha512`
+    HmacSha512,
+}
+
+impl TsigAlgorithm {
+    /// The name of the algorithm as it appears in the TSIG record
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TsigAlgorithm::HmacSha1 => "hmac-sha1",
+            TsigAlgorithm::HmacSha256 => "hmac-sha256",
+            TsigAlgorithm::HmacSha384 => "hmac-sha384",
+            TsigAlgorithm::HmacSha512 => "hmac-sha512",
+        }
+    }
+
+    /// The block length of the hash function, which the key is padded to
+    #[cfg(any(feature = "dnssec-openssl", feature = "dnssec-ring"))]
+    fn block_len(self) -> usize {
+        match self {
+            TsigAlgorithm::HmacSha1 | TsigAlgorithm::HmacSha256 => 64,
+            TsigAlgorithm::HmacSha384 | TsigAlgorithm::HmacSha512 => 128,
+        }
+    }
+
+    /// HMAC of the concatenated `data`, [RFC 2104](https://tools.ietf.org/html/rfc2104)
+    #[cfg(any(feature = "dnssec-openssl", feature = "dnssec-ring"))]
+    pub fn mac(self, key: &[u8], data: &[&[u8]]) -> ProtoResult<Vec<u8>> {
+        use proto::rr::dnssec::DigestType;
+
+        let digest_type = match self {
+            TsigAlgorithm::HmacSha1 => DigestType::SHA1,
+            TsigAlgorithm::HmacSha256 => DigestType::SHA256,
+            TsigAlgorithm::HmacSha384 => DigestType::SHA384,
+            TsigAlgorithm::HmacSha512 => DigestType::SHA512,
+        };
+
+        let mut key = if key.len() > self.block_len() {
+            digest_type.hash(key)?.as_ref().to_vec()
+        } else {
+            key.to_vec()
+        };
+        key.resize(self.block_len(), 0);
+
+        let inner_pad = key.iter().map(|b| b ^ 0x36).collect::<Vec<u8>>();
+        let outer_pad = key.iter().map(|b| b ^ 0x5c).collect::<Vec<u8>>();
+
+        let mut inner = vec![&inner_pad[..]];
+        inner.extend_from_slice(data);
+        let inner = digest_type.digest_all(&inner)?;
+
+        Ok(digest_type
+            .digest_all(&[&outer_pad, inner.as_ref()])?
+            .as_ref()
+            .to_vec())
+    }
+
+    /// This will always error, enable the dnssec-openssl or dnssec-ring feature at compile time
+    #[cfg(not(any(feature = "dnssec-openssl", feature = "dnssec-ring")))]
+    pub fn mac(self, _key: &[u8], _data: &[&[u8]]) -> ProtoResult<Vec<u8>> {
+        Err("TSIG requires the dnssec-openssl or dnssec-ring feature".into())
+    }
+}
+
+impl FromStr for TsigAlgorithm {
+    type Err = ProtoError;
+
+    /// Parses the name of the algorithm, e.g. `hmac-sha256`, the trailing dot is optional
+    fn from_str(s: &str) -> ProtoResult<Self> {
+        match s.trim_end_matches('.').to_lowercase().as_str() {
+            "hmac-sha1" => Ok(TsigAlgorithm::HmacSha1),
+            "hmac-sha256" => Ok(TsigAlgorithm::HmacSha256),
+            "hmac-sha384" => Ok(TsigAlgorithm::HmacSha384),
+            "hmac-sha512" => Ok(TsigAlgorithm::HmacSha512),
+            _ => Err(format!("unsupported TSIG algorithm: {}", s).into()),
+        }
+    }
+}
+
+impl fmt::Display for TsigAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A secret shared with clients, with which they sign their requests
+#[derive(Clone)]
+pub struct TsigKey {
+    name: LowerName,
+    algorithm: TsigAlgorithm,
+    secret: Vec<u8>,
+}
+
+impl TsigKey {
+    /// Constructs a new key
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - name of the key, which signed requests carry as the name of their TSIG record
+    /// * `algorithm` - the HMAC algorithm of the key
+    /// * `secret` - the shared secret
+    pub fn new(name: Name, algorithm: TsigAlgorithm, secret: Vec<u8>) -> Self {
+        TsigKey {
+            name: LowerName::new(&name),
+            algorithm,
+            secret,
+        }
+    }
+
+    /// The name of the key
+    pub fn name(&self) -> &LowerName {
+        &self.name
+    }
+
+    /// The HMAC algorithm of the key
+    pub fn algorithm(&self) -> TsigAlgorithm {
+        self.algorithm
+    }
+
+    /// Verifies that the request was signed with this key, [RFC 8945, section 5.2](https://tools.ietf.org/html/rfc8945#section-5.2)
+    ///
+    /// *Note*: responses are not signed.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - the request as it was received
+    /// * `now` - the current time in seconds since the Unix epoch, the time the request was signed
+    ///           must be within its fudge of this
+    pub fn verify(&self, request: &MessageRequest, now: u64) -> ProtoResult<()> {
+        let record = request
+            .tsig()
+            .ok_or_else(|| ProtoError::from("request is not signed"))?;
+        if LowerName::new(record.name()) != self.name {
+            return Err(format!("request is signed with another key: {}", record.name()).into());
+        }
+        if record.dns_class() != DNSClass::ANY {
+            return Err(format!("bad class of TSIG: {}", record.dns_class()).into());
+        }
+
+        let rdata = match *record.rdata() {
+            RData::Unknown { ref rdata, .. } => rdata.anything().map_or(&[][..], |r| &r[..]),
+            _ => &[][..],
+        };
+        let tsig = TsigRData::read(rdata)?;
+
+        if tsig.algorithm.to_ascii().parse::<TsigAlgorithm>()? != self.algorithm {
+            return Err(format!(
+                "request is signed with another algorithm: {}",
+                tsig.algorithm
+            )
+            .into());
+        }
+        if tsig.error != 0 {
+            return Err(format!("TSIG of request has error: {}", tsig.error).into());
+        }
+
+        // the message as it was before the TSIG record was added, with the original id
+        let signed = request.tsig_signed_bytes();
+        if signed.len() < 12 {
+            return Err("message is too short".into());
+        }
+        let mut header = signed[..12].to_vec();
+        header[0] = (tsig.original_id >> 8) as u8;
+        header[1] = tsig.original_id as u8;
+        let additional_count =
+            (u16::from(header[10]) << 8 | u16::from(header[11])).saturating_sub(1);
+        header[10] = (additional_count >> 8) as u8;
+        header[11] = additional_count as u8;
+
+        let mut variables = Vec::with_capacity(64);
+        {
+            let mut encoder = BinEncoder::new(&mut variables);
+            record
+                .name()
+                .to_lowercase()
+                .emit_as_canonical(&mut encoder, true)?;
+            encoder.emit_u16(DNSClass::ANY.into())?;
+            encoder.emit_u32(record.ttl())?;
+            tsig.algorithm
+                .to_lowercase()
+                .emit_as_canonical(&mut encoder, true)?;
+            encoder.emit_u16((tsig.time_signed >> 32) as u16)?;
+            encoder.emit_u32(tsig.time_signed as u32)?;
+            encoder.emit_u16(tsig.fudge)?;
+            encoder.emit_u16(tsig.error)?;
+            encoder.emit_u16(tsig.other.len() as u16)?;
+            encoder.emit_vec(tsig.other)?;
+        }
+
+        let mac = self
+            .algorithm
+            .mac(&self.secret, &[&header, &signed[12..], &variables])?;
+        if !constant_time_eq(&mac, tsig.mac) {
+            return Err(format!("bad TSIG signature of key: {}", self.name).into());
+        }
+
+        // only checked with a verified MAC, RFC 8945 section 5.2.3
+        let skew = if now > tsig.time_signed {
+            now - tsig.time_signed
+        } else {
+            tsig.time_signed - now
+        };
+        if skew > u64::from(tsig.fudge) {
+            return Err(format!(
+                "TSIG of request was signed at {}, which is {}s off",
+                tsig.time_signed, skew
+            )
+            .into());
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Debug for TsigKey {
+    /// The secret is left out
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TsigKey")
+            .field("name", &self.name)
+            .field("algorithm", &self.algorithm)
+            .finish()
+    }
+}
+
+/// The RDATA of a TSIG record
+struct TsigRData<'r> {
+    algorithm: Name,
+    time_signed: u64,
+    fudge: u16,
+    mac: &'r [u8],
+    original_id: u16,
+    error: u16,
+    other: &'r [u8],
+}
+
+impl<'r> TsigRData<'r> {
+    fn read(rdata: &'r [u8]) -> ProtoResult<Self> {
+        let mut decoder = BinDecoder::new(rdata);
+
+        // the algorithm name is never compressed
+        let algorithm = Name::read(&mut decoder)?;
+        let time_high = u64::from(decoder.read_u16()?.unverified(/*any u16 is valid*/));
+        let time_low = u64::from(decoder.read_u32()?.unverified(/*any u32 is valid*/));
+        let fudge = decoder.read_u16()?.unverified(/*any u16 is valid*/);
+        let mac_len = decoder.read_u16()?.unverified(/*checked by read_slice*/);
+        let mac = decoder.read_slice(mac_len as usize)?.unverified(/*compared to the MAC*/);
+        let original_id = decoder.read_u16()?.unverified(/*any u16 is valid*/);
+        let error = decoder.read_u16()?.unverified(/*any u16 is valid*/);
+        let other_len = decoder.read_u16()?.unverified(/*checked by read_slice*/);
+        let other = decoder.read_slice(other_len as usize)?.unverified(/*covered by the MAC*/);
+
+        Ok(TsigRData {
+            algorithm,
+            time_signed: time_high << 32 | time_low,
+            fudge,
+            mac,
+            original_id,
+            error,
+            other,
+        })
+    }
+}
+
+/// Compares the MACs in time independent of where they differ
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+#[cfg(all(test, any(feature = "dnssec-openssl", feature = "dnssec-ring")))]
+mod tests {
+    use data_encoding::HEXLOWER;
+    use proto::serialize::binary::BinDecodable;
+
+    use super::*;
+
+    /// A query for `www.example.com.` with EDNS, signed at 1600000000 with `internal-key`
+    const SIGNED_QUERY: &str = "12340100000100000000000203777777076578616d706c6503636f6d0000010001\
+                                00002910000000000000000c696e7465726e616c2d6b65790000fa00ff00000000\
+                                003d0b686d61632d7368613235360000005f5e1000012c00208e57840d9dc2ffab\
+                                db3a0c95f131d6923e00e36402e421a2462d65047a462bfc123400000000";
+    const SIGNED_AT: u64 = 1_600_000_000;
+
+    fn key(name: &str, secret: &[u8]) -> TsigKey {
+        TsigKey::new(
+            Name::from_ascii(name).unwrap(),
+            TsigAlgorithm::HmacSha256,
+            secret.to_vec(),
+        )
+    }
+
+    fn internal_key() -> TsigKey {
+        key("internal-key.", b"0123456789abcdef0123456789abcdef")
+    }
+
+    #[test]
+    fn test_hmac() {
+        // RFC 4231, test case 1
+        let mac = TsigAlgorithm::HmacSha256
+            .mac(&[0x0b; 20], &[b"Hi ", b"There"])
+            .unwrap();
+        assert_eq!(
+            HEXLOWER.encode(&mac),
+            "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7"
+        );
+    }
+
+    #[test]
+    fn test_verify() {
+        let bytes = HEXLOWER.decode(SIGNED_QUERY.as_bytes()).unwrap();
+        let request = MessageRequest::from_bytes(&bytes).unwrap();
+        assert!(request.edns().is_some());
+        assert!(request.additionals().is_empty());
+
+        assert!(internal_key().verify(&request, SIGNED_AT).is_ok());
+        assert!(internal_key().verify(&request, SIGNED_AT + 300).is_ok());
+        assert!(internal_key().verify(&request, SIGNED_AT - 301).is_err());
+
+        // another secret or key name
+        assert!(key("internal-key.", b"secret")
+            .verify(&request, SIGNED_AT)
+            .is_err());
+        assert!(key("external-key.", b"0123456789abcdef0123456789abcdef")
+            .verify(&request, SIGNED_AT)
+            .is_err());
+    }
+
+    #[test]
+    fn test_verify_modified() {
+        let mut bytes = HEXLOWER.decode(SIGNED_QUERY.as_bytes()).unwrap();
+        // www -> wwx
+        bytes[15] = b'x';
+        let request = MessageRequest::from_bytes(&bytes).unwrap();
+        assert!(internal_key().verify(&request, SIGNED_AT).is_err());
+
+        // without the TSIG record
+        bytes[11] = 1;
+        let unsigned = MessageRequest::from_bytes(&bytes[..44]).unwrap();
+        assert!(unsigned.tsig().is_none());
+        assert!(internal_key().verify(&unsigned, SIGNED_AT).is_err());
+    }
+}
@@ -0,0 +1,404 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Split-horizon views, i.e. different `Catalog`s selected by properties of the request
+
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use proto::error::{ProtoError, ProtoResult};
+use trust_dns::op::ResponseCode;
+use trust_dns::rr::LowerName;
+
+use authority::{Catalog, MessageResponseBuilder, TsigKey};
+use server::{Request, RequestHandler, ResponseHandler};
+
+/// An IPv4 or IPv6 network, e.g. `10.0.0.0/8` or `2001:db8::/32`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NetworkPrefix {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl NetworkPrefix {
+    /// Constructs a new prefix, all bits of `addr` beyond `prefix_len` are cleared
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - network address
+    /// * `prefix_len` - number of significant bits, capped at 32 for IPv4 and 128 for IPv6
+    pub fn new(addr: IpAddr, prefix_len: u8) -> Self {
+        let (addr, prefix_len) = match addr {
+            IpAddr::V4(addr) => {
+                let prefix_len = prefix_len.min(32);
+                (
+                    IpAddr::V4(Ipv4Addr::from(u32::from(addr) & mask_u32(prefix_len))),
+                    prefix_len,
+                )
+            }
+            IpAddr::V6(addr) => {
+                let prefix_len = prefix_len.min(128);
+                (
+                    IpAddr::V6(Ipv6Addr::from(u128::from(addr) & mask_u128(prefix_len))),
+                    prefix_len,
+                )
+            }
+        };
+
+        NetworkPrefix { addr, prefix_len }
+    }
+
+    /// A prefix which matches any IPv4 or IPv6 address
+    pub fn any() -> [Self; 2] {
+        [
+            NetworkPrefix::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+            NetworkPrefix::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0),
+        ]
+    }
+
+    /// The network address of this prefix
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    /// The number of significant bits in the network address
+    pub fn prefix_len(&self) -> u8 {
+        self.prefix_len
+    }
+
+    /// Returns true if the `addr` is within this network
+    ///
+    /// IPv4-mapped IPv6 addresses, i.e. `::ffff:10.0.0.1`, are treated as their IPv4 equivalent.
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        match (self.addr, unmap_ipv4(addr)) {
+            (IpAddr::V4(net), IpAddr::V4(addr)) => {
+                u32::from(addr) & mask_u32(self.prefix_len) == u32::from(net)
+            }
+            (IpAddr::V6(net), IpAddr::V6(addr)) => {
+                u128::from(addr) & mask_u128(self.prefix_len) == u128::from(net)
+            }
+            _ => false,
+        }
+    }
+}
+
+fn mask_u32(prefix_len: u8) -> u32 {
+    if prefix_len == 0 {
+        0
+    } else {
+        !0u32 << (32 - u32::from(prefix_len))
+    }
+}
+
+fn mask_u128(prefix_len: u8) -> u128 {
+    if prefix_len == 0 {
+        0
+    } else {
+        !0u128 << (128 - u32::from(prefix_len))
+    }
+}
+
+fn unmap_ipv4(addr: IpAddr) -> IpAddr {
+    match addr {
+        IpAddr::V6(v6) if u128::from(v6) >> 32 == 0xffff => {
+            IpAddr::V4(Ipv4Addr::from(u128::from(v6) as u32))
+        }
+        addr => addr,
+    }
+}
+
+impl From<IpAddr> for NetworkPrefix {
+    /// A prefix matching exactly the single address
+    fn from(addr: IpAddr) -> Self {
+        match addr {
+            IpAddr::V4(_) => NetworkPrefix::new(addr, 32),
+            IpAddr::V6(_) => NetworkPrefix::new(addr, 128),
+        }
+    }
+}
+
+impl FromStr for NetworkPrefix {
+    type Err = ProtoError;
+
+    /// Parses either `addr/prefix_len` or a bare address, which is treated as a host prefix
+    fn from_str(s: &str) -> ProtoResult<Self> {
+        let mut split = s.splitn(2, '/');
+        let addr = split
+            .next()
+            .unwrap_or_default()
+            .parse::<IpAddr>()
+            .map_err(|e| ProtoError::from(format!("bad network address {}: {}", s, e)))?;
+
+        match split.next() {
+            Some(prefix_len) => {
+                let prefix_len = prefix_len
+                    .parse::<u8>()
+                    .map_err(|e| ProtoError::from(format!("bad prefix length {}: {}", s, e)))?;
+                let max = if addr.is_ipv4() { 32 } else { 128 };
+                if prefix_len > max {
+                    return Err(format!("prefix length too long: {}", s).into());
+                }
+                Ok(NetworkPrefix::new(addr, prefix_len))
+            }
+            None => Ok(NetworkPrefix::from(addr)),
+        }
+    }
+}
+
+impl fmt::Display for NetworkPrefix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+/// A named `Catalog` which is only used for requests matching all of its rules
+///
+/// Each set of rules is optional, an empty set matches all requests. When multiple entries are
+///  specified within one rule, any of them matching is sufficient.
+pub struct View {
+    name: String,
+    match_clients: Vec<NetworkPrefix>,
+    match_destinations: Vec<NetworkPrefix>,
+    match_keys: Vec<TsigKey>,
+    catalog: Catalog,
+}
+
+impl View {
+    /// Constructs a new View which matches all requests
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - name of the view, used in logging
+    /// * `catalog` - all zones which should be served to clients of this view
+    pub fn new(name: String, catalog: Catalog) -> Self {
+        View {
+            name,
+            match_clients: Vec::new(),
+            match_destinations: Vec::new(),
+            match_keys: Vec::new(),
+            catalog,
+        }
+    }
+
+    /// Restrict the view to requests from clients in any of the `prefixes`
+    pub fn set_match_clients(&mut self, prefixes: Vec<NetworkPrefix>) -> &mut Self {
+        self.match_clients = prefixes;
+        self
+    }
+
+    /// Restrict the view to requests received on any of the local addresses in `prefixes`
+    ///
+    /// Requests received on a UDP socket bound to the unspecified address, e.g. `0.0.0.0`, don't
+    ///  carry the local address they were sent to, and never match.
+    pub fn set_match_destinations(&mut self, prefixes: Vec<NetworkPrefix>) -> &mut Self {
+        self.match_destinations = prefixes;
+        self
+    }
+
+    /// Restrict the view to requests signed with any of the TSIG `keys`
+    ///
+    /// The MAC and time of the TSIG record are verified, responses are not signed.
+    pub fn set_match_keys(&mut self, keys: Vec<TsigKey>) -> &mut Self {
+        self.match_keys = keys;
+        self
+    }
+
+    /// The name of this view
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The zones available in this view
+    pub fn catalog(&self) -> &Catalog {
+        &self.catalog
+    }
+
+    /// Mutable access to the zones of this view, e.g. to add or remove authorities
+    pub fn catalog_mut(&mut self) -> &mut Catalog {
+        &mut self.catalog
+    }
+
+    /// Returns true if this view should be used to answer the request
+    pub fn matches(&self, request: &Request) -> bool {
+        let src = request.src.ip();
+        let dst = request.dst.ip();
+
+        (self.match_clients.is_empty() || self.match_clients.iter().any(|p| p.contains(src)))
+            && (self.match_destinations.is_empty()
+                || (!dst.is_unspecified()
+                    && self.match_destinations.iter().any(|p| p.contains(dst))))
+            && (self.match_keys.is_empty() || self.is_signed_by_key(request))
+    }
+
+    /// Returns true if the request carries a TSIG record which one of the keys verifies
+    fn is_signed_by_key(&self, request: &Request) -> bool {
+        let tsig = match request.message.tsig() {
+            Some(tsig) => tsig,
+            None => return false,
+        };
+        let key = match self
+            .match_keys
+            .iter()
+            .find(|key| *key.name() == LowerName::new(tsig.name()))
+        {
+            Some(key) => key,
+            None => return false,
+        };
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        match key.verify(&request.message, now) {
+            Ok(()) => true,
+            Err(e) => {
+                info!(
+                    "request: {} src: {} failed TSIG verification for view {}: {}",
+                    request.message.id(),
+                    request.src,
+                    self.name,
+                    e
+                );
+                false
+            }
+        }
+    }
+}
+
+/// An ordered set of `View`s, the first view which matches a request answers it
+///
+/// Requests which match no view are refused.
+#[derive(Default)]
+pub struct ViewCatalog {
+    views: Vec<View>,
+}
+
+impl ViewCatalog {
+    /// Constructs a new ViewCatalog without any views
+    pub fn new() -> Self {
+        ViewCatalog { views: Vec::new() }
+    }
+
+    /// Append a view, it will only be consulted for requests none of the prior views match
+    pub fn push(&mut self, view: View) {
+        self.views.push(view);
+    }
+
+    /// Remove the view with the specified name
+    pub fn remove(&mut self, name: &str) -> Option<View> {
+        let position = self.views.iter().position(|v| v.name() == name)?;
+        Some(self.views.remove(position))
+    }
+
+    /// All views, in matching order
+    pub fn views(&self) -> &[View] {
+        &self.views
+    }
+
+    /// Mutable access to all views, in matching order
+    pub fn views_mut(&mut self) -> &mut [View] {
+        &mut self.views
+    }
+
+    /// Returns the view with the specified name
+    pub fn view(&self, name: &str) -> Option<&View> {
+        self.views.iter().find(|v| v.name() == name)
+    }
+
+    /// Returns the view with the specified name for modification
+    pub fn view_mut(&mut self, name: &str) -> Option<&mut View> {
+        self.views.iter_mut().find(|v| v.name() == name)
+    }
+
+    /// Returns the first view which matches the request
+    pub fn find_view(&self, request: &Request) -> Option<&View> {
+        self.views.iter().find(|v| v.matches(request))
+    }
+}
+
+impl From<Catalog> for ViewCatalog {
+    /// A ViewCatalog with a single view, named `default`, which matches all requests
+    fn from(catalog: Catalog) -> Self {
+        let mut views = ViewCatalog::new();
+        views.push(View::new("default".to_string(), catalog));
+        views
+    }
+}
+
+impl RequestHandler for ViewCatalog {
+    /// Passes the request to the `Catalog` of the first matching `View`
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - the requested action to perform.
+    /// * `response_handle` - sink for the response message to be sent
+    fn handle_request<'q, 'a, R: ResponseHandler + 'static>(
+        &'a self,
+        request: &'q Request,
+        response_handle: R,
+    ) -> io::Result<()> {
+        if let Some(view) = self.find_view(request) {
+            debug!(
+                "request: {} src: {} matched view: {}",
+                request.message.id(),
+                request.src,
+                view.name()
+            );
+            return view.catalog().handle_request(request, response_handle);
+        }
+
+        info!(
+            "request: {} src: {} matched no view, refusing",
+            request.message.id(),
+            request.src
+        );
+        let response = MessageResponseBuilder::new(Some(request.message.raw_queries()));
+        response_handle.send_response(response.error_msg(
+            request.message.id(),
+            request.message.op_code(),
+            ResponseCode::Refused,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prefix_contains() {
+        let prefix: NetworkPrefix = "10.1.0.0/16".parse().unwrap();
+        assert!(prefix.contains("10.1.2.3".parse().unwrap()));
+        assert!(prefix.contains("::ffff:10.1.2.3".parse().unwrap()));
+        assert!(!prefix.contains("10.2.0.1".parse().unwrap()));
+        assert!(!prefix.contains("::1".parse().unwrap()));
+
+        let prefix: NetworkPrefix = "2001:db8::/32".parse().unwrap();
+        assert!(prefix.contains("2001:db8:1::1".parse().unwrap()));
+        assert!(!prefix.contains("2001:db9::1".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_prefix_parse() {
+        let prefix: NetworkPrefix = "10.1.2.3/8".parse().unwrap();
+        assert_eq!(prefix.addr(), "10.0.0.0".parse::<IpAddr>().unwrap());
+        assert_eq!(prefix.prefix_len(), 8);
+        assert_eq!(prefix.to_string(), "10.0.0.0/8");
+
+        let prefix: NetworkPrefix = "127.0.0.1".parse().unwrap();
+        assert_eq!(prefix.prefix_len(), 32);
+
+        assert!("10.0.0.0/33".parse::<NetworkPrefix>().is_err());
+        assert!("10.0.0/8".parse::<NetworkPrefix>().is_err());
+
+        let any = NetworkPrefix::any();
+        assert!(any[0].contains("192.0.2.1".parse().unwrap()));
+        assert!(any[1].contains("2001:db8::1".parse().unwrap()));
+    }
+}
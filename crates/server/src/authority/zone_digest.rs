@@ -0,0 +1,279 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Signing of rr_sets and ZONEMD digests of zones, shared by the authorities which store their records in memory
+
+#[cfg(any(feature = "dnssec-openssl", feature = "dnssec-ring"))]
+use std::collections::BTreeMap;
+#[cfg(any(feature = "dnssec-openssl", feature = "dnssec-ring"))]
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use trust_dns::rr::dnssec::{DnsSecResult, Signer};
+use trust_dns::rr::{DNSClass, RData, Record, RecordSet, RecordType};
+#[cfg(any(feature = "dnssec-openssl", feature = "dnssec-ring"))]
+use trust_dns::rr::{LowerName, Name, RrKey};
+
+#[cfg(any(feature = "dnssec-openssl", feature = "dnssec-ring"))]
+use authority::ZoneType;
+
+/// Signs the rr_set with all the keys, replacing any existing RRSIGs
+pub(crate) fn sign_rrset(
+    rr_set: &mut RecordSet,
+    secure_keys: &[Signer],
+    zone_ttl: u32,
+    dns_class: DNSClass,
+    inception: DateTime<Utc>,
+) -> DnsSecResult<()> {
+    use trust_dns::rr::dnssec::tbs;
+    use trust_dns::rr::rdata::{DNSSECRData, DNSSECRecordType, SIG};
+
+    rr_set.clear_rrsigs();
+    let rrsig_temp = Record::with(
+        rr_set.name().clone(),
+        RecordType::DNSSEC(DNSSECRecordType::RRSIG),
+        zone_ttl,
+    );
+
+    for signer in secure_keys {
+        debug!(
+            "signing rr_set: {}, {} with: {}",
+            rr_set.name(),
+            rr_set.record_type(),
+            signer.algorithm(),
+        );
+
+        let expiration = inception + signer.sig_duration();
+
+        let tbs = tbs::rrset_tbs(
+            rr_set.name(),
+            dns_class,
+            rr_set.name().num_labels(),
+            rr_set.record_type(),
+            signer.algorithm(),
+            rr_set.ttl(),
+            expiration.timestamp() as u32,
+            inception.timestamp() as u32,
+            signer.calculate_key_tag()?,
+            signer.signer_name(),
+            // TODO: this is a nasty clone... the issue is that the vec
+            //  from records is of Vec<&R>, but we really want &[R]
+            &rr_set
+                .records_without_rrsigs()
+                .cloned()
+                .collect::<Vec<Record>>(),
+        );
+
+        // TODO, maybe chain these with some ETL operations instead?
+        let tbs = match tbs {
+            Ok(tbs) => tbs,
+            Err(err) => {
+                error!("could not serialize rrset to sign: {}", err);
+                continue;
+            }
+        };
+
+        let signature = signer.sign(&tbs);
+        let signature = match signature {
+            Ok(signature) => signature,
+            Err(err) => {
+                error!("could not sign rrset: {}", err);
+                continue;
+            }
+        };
+
+        let mut rrsig = rrsig_temp.clone();
+        rrsig.set_rdata(RData::DNSSEC(DNSSECRData::SIG(SIG::new(
+            // type_covered: RecordType,
+            rr_set.record_type(),
+            // algorithm: Algorithm,
+            signer.algorithm(),
+            // num_labels: u8,
+            rr_set.name().num_labels(),
+            // original_ttl: u32,
+            rr_set.ttl(),
+            // sig_expiration: u32,
+            expiration.timestamp() as u32,
+            // sig_inception: u32,
+            inception.timestamp() as u32,
+            // key_tag: u16,
+            signer.calculate_key_tag()?,
+            // signer_name: Name,
+            signer.signer_name().clone(),
+            // sig: Vec<u8>
+            signature,
+        ))));
+
+        rr_set.insert_rrsig(rrsig);
+    }
+
+    Ok(())
+}
+
+/// All records of the zone, including the RRSIGs
+#[cfg(any(feature = "dnssec-openssl", feature = "dnssec-ring"))]
+fn zone_records(records: &BTreeMap<RrKey, Arc<RecordSet>>) -> Vec<Record> {
+    records
+        .values()
+        .flat_map(|rr_set| rr_set.records_without_rrsigs().chain(rr_set.rrsigs()))
+        .cloned()
+        .collect()
+}
+
+/// Returns true if the zone has ZONEMD records at its apex
+#[cfg(any(feature = "dnssec-openssl", feature = "dnssec-ring"))]
+fn has_zonemd(origin: &LowerName, records: &BTreeMap<RrKey, Arc<RecordSet>>) -> bool {
+    records.contains_key(&RrKey::new(origin.clone(), RecordType::ZONEMD))
+}
+
+/// Recomputes the digests of the ZONEMD records at the apex of the zone, RFC 8976
+///
+/// The zone file carries placeholder ZONEMD records for the schemes and hash algorithms to publish, those that
+///  are supported are updated to the serial and digest of the zone. The digest covers the RRSIGs, so in a signed
+///  zone this must be called after the zone was signed, only the ZONEMD rr_set is then resigned.
+///
+/// # Arguments
+///
+/// * `origin` - the apex of the zone
+/// * `records` - the records of the zone, the ZONEMD rr_set is replaced
+/// * `serial` - the serial of the SOA of the zone
+/// * `secure_keys` - the keys to sign the ZONEMD rr_set with, none for an unsigned zone
+/// * `zone_ttl` - the minimum ttl of the zone, for the RRSIGs
+/// * `dns_class` - the class of the zone
+#[cfg(any(feature = "dnssec-openssl", feature = "dnssec-ring"))]
+pub(crate) fn update_zonemd(
+    origin: &LowerName,
+    records: &mut BTreeMap<RrKey, Arc<RecordSet>>,
+    serial: u32,
+    secure_keys: &[Signer],
+    zone_ttl: u32,
+    dns_class: DNSClass,
+) -> DnsSecResult<()> {
+    use trust_dns::proto::rr::zone_digest;
+    use trust_dns::rr::rdata::ZONEMD;
+
+    let rr_key = RrKey::new(origin.clone(), RecordType::ZONEMD);
+    let placeholders = match records.get(&rr_key) {
+        Some(rr_set) => rr_set
+            .records_without_rrsigs()
+            .cloned()
+            .collect::<Vec<Record>>(),
+        None => return Ok(()),
+    };
+
+    debug!("updating zonemd: {}", origin);
+    let origin: Name = origin.clone().into();
+    let zone_records = zone_records(records);
+
+    let mut rr_set = RecordSet::new(&origin, RecordType::ZONEMD, serial);
+    for mut record in placeholders {
+        let zonemd = match *record.rdata() {
+            RData::ZONEMD(ref zonemd)
+                if zone_digest::is_supported(zonemd.scheme(), zonemd.hash_algorithm()) =>
+            {
+                let digest = zone_digest::zone_digest(
+                    &origin,
+                    &zone_records,
+                    zonemd.scheme(),
+                    zonemd.hash_algorithm(),
+                )?;
+                ZONEMD::new(serial, zonemd.scheme(), zonemd.hash_algorithm(), digest)
+            }
+            _ => {
+                warn!("unsupported zonemd left as is: {:?}", record.rdata());
+                rr_set.insert(record, serial);
+                continue;
+            }
+        };
+
+        record.set_rdata(RData::ZONEMD(zonemd));
+        rr_set.insert(record, serial);
+    }
+
+    if !secure_keys.is_empty() {
+        sign_rrset(&mut rr_set, secure_keys, zone_ttl, dns_class, Utc::now())?;
+    }
+
+    records.insert(rr_key, Arc::new(rr_set));
+    Ok(())
+}
+
+/// Verifies the zone with the ZONEMD records at its apex, RFC 8976 section 4
+#[cfg(any(feature = "dnssec-openssl", feature = "dnssec-ring"))]
+pub(crate) fn verify_zonemd(
+    origin: &LowerName,
+    records: &BTreeMap<RrKey, Arc<RecordSet>>,
+) -> DnsSecResult<()> {
+    use trust_dns::proto::rr::zone_digest;
+
+    zone_digest::verify(&origin.clone().into(), &zone_records(records))?;
+    Ok(())
+}
+
+/// Returns true if all ZONEMD records at the apex of the zone are placeholders, i.e. have all-zero digests
+#[cfg(any(feature = "dnssec-openssl", feature = "dnssec-ring"))]
+fn has_placeholder_zonemd(origin: &LowerName, records: &BTreeMap<RrKey, Arc<RecordSet>>) -> bool {
+    records
+        .get(&RrKey::new(origin.clone(), RecordType::ZONEMD))
+        .map_or(false, |rr_set| {
+            rr_set
+                .records_without_rrsigs()
+                .all(|record| match *record.rdata() {
+                    RData::ZONEMD(ref zonemd) => zonemd.digest().iter().all(|b| *b == 0),
+                    _ => false,
+                })
+        })
+}
+
+/// Returns true if the zone carries RRSIGs, i.e. was signed before it was loaded
+#[cfg(any(feature = "dnssec-openssl", feature = "dnssec-ring"))]
+fn is_signed(records: &BTreeMap<RrKey, Arc<RecordSet>>) -> bool {
+    records.values().any(|rr_set| !rr_set.rrsigs().is_empty())
+}
+
+/// Verifies the ZONEMD of a zone that was loaded, RFC 8976 section 4
+///
+/// A secondary serves the zone of its primary as is, a `Slave` zone which fails verification is rejected. The
+///  zone of a primary may carry placeholder ZONEMD records with all-zero digests, these are computed. Any other
+///  ZONEMD that does not verify is served as is, as is that of a zone which was signed before it was loaded, its
+///  RRSIGs would not cover a recomputed digest.
+#[cfg(any(feature = "dnssec-openssl", feature = "dnssec-ring"))]
+pub(crate) fn load_zonemd(
+    origin: &LowerName,
+    zone_type: ZoneType,
+    records: &mut BTreeMap<RrKey, Arc<RecordSet>>,
+    serial: u32,
+) -> Result<(), String> {
+    if !has_zonemd(origin, records) {
+        return Ok(());
+    }
+
+    match verify_zonemd(origin, records) {
+        Ok(()) => debug!("zonemd verified: {}", origin),
+        Err(e) if zone_type == ZoneType::Slave => {
+            warn!("failed to verify zonemd of {}: {}", origin, e);
+            return Err(format!("failed to verify zonemd of {}: {}", origin, e));
+        }
+        Err(e) if is_signed(records) => {
+            warn!(
+                "zonemd of signed zone {} does not verify, serving it as is: {}",
+                origin, e
+            )
+        }
+        Err(_) if has_placeholder_zonemd(origin, records) => {
+            debug!("computing placeholder zonemd of {}", origin);
+            update_zonemd(origin, records, serial, &[], 0, DNSClass::IN)
+                .map_err(|e| format!("failed to update zonemd of {}: {}", origin, e))?;
+        }
+        Err(e) => warn!(
+            "zonemd of {} does not verify, serving it as is: {}",
+            origin, e
+        ),
+    }
+
+    Ok(())
+}
@@ -26,7 +26,7 @@ use trust_dns::rr::dnssec::Algorithm;
 use trust_dns::error::ParseResult;
 
 /// Key pair configuration for DNSSec keys for signing a zone
-#[derive(Deserialize, PartialEq, Debug)]
+#[derive(Deserialize, PartialEq, Debug, Clone)]
 pub struct KeyConfig {
     /// file path to the key
     pub key_path: String,
@@ -9,21 +9,24 @@
 
 pub mod dnssec;
 
+use std::fmt;
 use std::fs::File;
 use std::io::Read;
-use std::net::{Ipv4Addr, Ipv6Addr};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::time::Duration;
 
+use data_encoding::BASE64;
 use log;
 use toml;
 
-use proto::error::ProtoResult;
+use proto::error::{ProtoError, ProtoResult};
 use trust_dns::rr::Name;
 
-use authority::ZoneType;
+use authority::{NetworkPrefix, TsigAlgorithm, TsigKey, ZoneType};
 use error::{ConfigError, ConfigResult};
+use server::{QueryLogConfig, RateLimitConfig};
 use store::StoreConfig;
 
 static DEFAULT_PATH: &'static str = "/var/named"; // TODO what about windows (do I care? ;)
@@ -56,8 +59,28 @@ pub struct Config {
     /// List of configurations for zones
     #[serde(default)]
     zones: Vec<ZoneConfig>,
+    /// List of split-horizon views, each with their own zones
+    #[serde(default)]
+    views: Vec<ViewConfig>,
+    /// TSIG keys with which requests may be signed, to match views
+    #[serde(default)]
+    tsig_keys: Vec<TsigKeyConfig>,
     /// Certificate to associate to TLS connections (currently the same is used for HTTPS and TLS)
     tls_cert: Option<dnssec::TlsCertConfig>,
+    /// Response Rate Limiting for UDP responses
+    rate_limit: Option<RateLimitConfig>,
+    /// Path of the Unix domain socket for the control channel, disabled if not set
+    control_socket: Option<String>,
+    /// Address on which to serve statistics over HTTP, disabled if not set
+    metrics_listen_addr: Option<SocketAddr>,
+    /// Logging of every query and its response, as JSON and/or dnstap
+    query_log: Option<QueryLogConfig>,
+    /// Client networks, e.g. `127.0.0.0/8`, whose recursive queries are resolved, none if empty
+    #[serde(default)]
+    allow_recursion: Vec<String>,
+    /// Answer names below zone cuts with referrals to the child zones, disabled by default
+    #[serde(default)]
+    referrals: bool,
 }
 
 impl Config {
@@ -136,10 +159,50 @@ impl Config {
         &self.zones
     }
 
+    /// the set of views, in the order in which they should be matched against requests
+    pub fn get_views(&self) -> &[ViewConfig] {
+        &self.views
+    }
+
+    /// the TSIG keys, which views may reference by name
+    pub fn get_tsig_keys(&self) -> &[TsigKeyConfig] {
+        &self.tsig_keys
+    }
+
     /// the tls certificate to use for accepting tls connections
     pub fn get_tls_cert(&self) -> Option<&dnssec::TlsCertConfig> {
         self.tls_cert.as_ref()
     }
+
+    /// the rate limits for UDP responses, none if responses should not be limited
+    pub fn get_rate_limit(&self) -> Option<&RateLimitConfig> {
+        self.rate_limit.as_ref()
+    }
+
+    /// the path of the control socket, none if the control channel is disabled
+    pub fn get_control_socket(&self) -> Option<&Path> {
+        self.control_socket.as_ref().map(Path::new)
+    }
+
+    /// the address for the HTTP endpoint of the Prometheus statistics, none if disabled
+    pub fn get_metrics_listen_addr(&self) -> Option<SocketAddr> {
+        self.metrics_listen_addr
+    }
+
+    /// the configuration of the query log, none if queries are not logged
+    pub fn get_query_log(&self) -> Option<&QueryLogConfig> {
+        self.query_log.as_ref()
+    }
+
+    /// the client networks whose queries with the RD bit set are resolved by Hint and Forward zones
+    pub fn get_allow_recursion(&self) -> ProtoResult<Vec<NetworkPrefix>> {
+        self.allow_recursion.iter().map(|s| s.parse()).collect()
+    }
+
+    /// answer names below the zone cuts of Master and Slave zones with referrals, see `Catalog::set_referrals`
+    pub fn is_referrals_enabled(&self) -> bool {
+        self.referrals
+    }
 }
 
 impl FromStr for Config {
@@ -150,8 +213,104 @@ impl FromStr for Config {
     }
 }
 
+/// Configuration for a split-horizon view
+///
+/// Zones in a view are only visible to requests matching all of the configured rules, views are
+///  matched in the order of the configuration file. The same zone file may be used by zones in
+///  multiple views.
+#[derive(Deserialize, PartialEq, Debug, Clone)]
+pub struct ViewConfig {
+    /// name of the view
+    pub name: String,
+    /// client networks, e.g. `10.0.0.0/8`, which match this view, all clients if empty
+    #[serde(default)]
+    pub match_clients: Vec<String>,
+    /// local addresses or networks on which requests for this view arrive, all if empty
+    #[serde(default)]
+    pub match_destinations: Vec<String>,
+    /// names of the TSIG keys, see `Config::tsig_keys`, which match this view, no key required if empty
+    #[serde(default)]
+    pub match_keys: Vec<String>,
+    /// List of configurations for zones in this view
+    #[serde(default)]
+    pub zones: Vec<ZoneConfig>,
+}
+
+impl ViewConfig {
+    /// the name of the view
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// the client networks which match the view
+    pub fn get_match_clients(&self) -> ProtoResult<Vec<NetworkPrefix>> {
+        self.match_clients.iter().map(|s| s.parse()).collect()
+    }
+
+    /// the local addresses which match the view
+    pub fn get_match_destinations(&self) -> ProtoResult<Vec<NetworkPrefix>> {
+        self.match_destinations.iter().map(|s| s.parse()).collect()
+    }
+
+    /// the TSIG keys which match the view, looked up by name in `keys`
+    pub fn get_match_keys(&self, keys: &[TsigKeyConfig]) -> ProtoResult<Vec<TsigKey>> {
+        self.match_keys
+            .iter()
+            .map(|name| {
+                keys.iter()
+                    .find(|key| key.name == *name)
+                    .ok_or_else(|| ProtoError::from(format!("unknown TSIG key: {}", name)))
+                    .and_then(TsigKeyConfig::to_key)
+            }).collect()
+    }
+
+    /// the set of zones which should be loaded into this view
+    pub fn get_zones(&self) -> &[ZoneConfig] {
+        &self.zones
+    }
+}
+
+/// Configuration of a TSIG key, [RFC 8945](https://tools.ietf.org/html/rfc8945)
+#[derive(Deserialize, PartialEq, Clone)]
+pub struct TsigKeyConfig {
+    /// name of the key, e.g. `internal-key`
+    pub name: String,
+    /// HMAC algorithm, one of `hmac-sha1`, `hmac-sha256`, `hmac-sha384` or `hmac-sha512`
+    #[serde(default = "TsigKeyConfig::default_algorithm")]
+    pub algorithm: String,
+    /// the shared secret, base64 encoded as in the `secret` of BIND's `key` statement
+    pub secret: String,
+}
+
+impl TsigKeyConfig {
+    fn default_algorithm() -> String {
+        TsigAlgorithm::HmacSha256.to_string()
+    }
+
+    /// the key with its secret decoded
+    pub fn to_key(&self) -> ProtoResult<TsigKey> {
+        let name = Name::parse(&self.name, Some(&Name::new()))?;
+        let algorithm = self.algorithm.parse()?;
+        let secret = BASE64
+            .decode(self.secret.as_bytes())
+            .map_err(|e| format!("bad secret of TSIG key {}: {}", self.name, e))?;
+
+        Ok(TsigKey::new(name, algorithm, secret))
+    }
+}
+
+impl fmt::Debug for TsigKeyConfig {
+    /// The secret is left out
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TsigKeyConfig")
+            .field("name", &self.name)
+            .field("algorithm", &self.algorithm)
+            .finish()
+    }
+}
+
 /// Configuration for a zone
-#[derive(Deserialize, PartialEq, Debug)]
+#[derive(Deserialize, PartialEq, Debug, Clone)]
 pub struct ZoneConfig {
     /// name of the zone
     pub zone: String, // TODO: make Domain::Name decodable
@@ -171,6 +330,12 @@ pub struct ZoneConfig {
     /// Store configurations, TODO: allow chained Stores
     #[serde(default)]
     pub stores: Option<StoreConfig>,
+    /// Addresses of the masters a slave zone is transferred from, e.g. `192.0.2.1:53`
+    #[serde(default)]
+    pub masters: Vec<SocketAddr>,
+    /// Addresses sent NOTIFY messages for the zone, in addition to its name servers
+    #[serde(default)]
+    pub also_notify: Vec<SocketAddr>,
 }
 
 impl ZoneConfig {
@@ -203,6 +368,8 @@ impl ZoneConfig {
             enable_dnssec,
             keys,
             stores: None,
+            masters: Vec::new(),
+            also_notify: Vec::new(),
         }
     }
 
@@ -245,4 +412,14 @@ impl ZoneConfig {
     pub fn get_keys(&self) -> &[dnssec::KeyConfig] {
         &self.keys
     }
+
+    /// the masters of a slave zone, tried in order when the zone is transferred
+    pub fn get_masters(&self) -> &[SocketAddr] {
+        &self.masters
+    }
+
+    /// the addresses sent NOTIFY messages, besides the name servers of the zone
+    pub fn get_also_notify(&self) -> &[SocketAddr] {
+        &self.also_notify
+    }
 }
@@ -29,11 +29,15 @@
 
 extern crate bytes;
 extern crate chrono;
+extern crate data_encoding;
 extern crate env_logger;
 extern crate failure;
 extern crate futures;
 #[macro_use]
+extern crate lazy_static;
+#[macro_use]
 extern crate log;
+extern crate lru_cache;
 extern crate rusqlite;
 extern crate serde;
 #[macro_use]
@@ -56,6 +60,8 @@ extern crate tokio_rustls;
 extern crate tokio_tcp;
 extern crate tokio_timer;
 extern crate tokio_udp;
+#[cfg(unix)]
+extern crate tokio_uds;
 extern crate toml;
 extern crate trust_dns;
 #[cfg(feature = "dns-over-https")]
@@ -63,6 +69,7 @@ extern crate trust_dns_https;
 #[cfg(feature = "dns-over-openssl")]
 extern crate trust_dns_openssl;
 pub extern crate trust_dns_proto as proto;
+extern crate trust_dns_resolver;
 #[cfg(feature = "dns-over-rustls")]
 extern crate trust_dns_rustls;
 
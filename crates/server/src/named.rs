@@ -21,6 +21,10 @@
 //!    -p PORT, --port=PORT    Override the listening port
 //!    --tls-port=PORT         Override the listening port for TLS connections
 //! ```
+//!
+//! On `SIGHUP` the configuration is read again, and all views and zones which were added,
+//!  removed or changed are reloaded while serving continues. Changes to the listening addresses,
+//!  ports, TLS and rate limiting configuration require a restart.
 
 extern crate chrono;
 #[macro_use]
@@ -28,11 +32,16 @@ extern crate clap;
 extern crate futures;
 #[macro_use]
 extern crate log;
+extern crate rand;
 #[cfg(feature = "dns-over-rustls")]
 extern crate rustls;
 extern crate tokio;
+#[cfg(unix)]
+extern crate tokio_signal;
 extern crate tokio_tcp;
 extern crate tokio_udp;
+#[cfg(unix)]
+extern crate tokio_uds;
 extern crate trust_dns;
 #[cfg(feature = "dns-over-openssl")]
 extern crate trust_dns_openssl;
@@ -40,33 +49,52 @@ extern crate trust_dns_openssl;
 extern crate trust_dns_rustls;
 extern crate trust_dns_server;
 
+use std::collections::{HashMap, HashSet};
+use std::fs;
 use std::io;
+use std::mem;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr, ToSocketAddrs};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 
 use clap::{Arg, ArgMatches};
-use futures::{future, Future};
+use futures::{future, Future, Stream};
 use tokio::runtime::current_thread::Runtime;
 use tokio_tcp::TcpListener;
 use tokio_udp::UdpSocket;
+#[cfg(unix)]
+use tokio_uds::UnixListener;
 
-use trust_dns::rr::Name;
+use trust_dns::client::{Client, SyncClient};
+use trust_dns::op::{Message, MessageType, OpCode, Query, ResponseCode};
+use trust_dns::rr::dnssec::SupportedAlgorithms;
 #[cfg(feature = "dnssec")]
 use trust_dns::rr::rdata::key::KeyUsage;
-use trust_dns_server::authority::{Authority, Catalog, ZoneType};
-use trust_dns_server::config::{Config, ZoneConfig};
+use trust_dns::rr::{DNSClass, LowerName, Name, RData, Record, RecordSet, RecordType};
+use trust_dns::udp::UdpClientConnection;
+use trust_dns_server::authority::{
+    Authority, Catalog, NetworkPrefix, TsigKey, View, ViewCatalog, ZoneType,
+};
+use trust_dns_server::config::{Config, ViewConfig, ZoneConfig};
 #[cfg(any(feature = "dns-over-tls", feature = "dnssec"))]
 use trust_dns_server::config::dnssec::{self, TlsCertConfig};
 use trust_dns_server::logger;
-use trust_dns_server::server::ServerFuture;
+#[cfg(unix)]
+use trust_dns_server::server::ControlServer;
+use trust_dns_server::server::{
+    register_metrics_listener, ControlCommand, ControlHandler, ControlResult, Metrics,
+    QueryLogger, RateLimiter, ServerFuture,
+};
 use trust_dns_server::store::file::{FileAuthority, FileConfig};
+use trust_dns_server::store::forwarder::ForwardAuthority;
+use trust_dns_server::store::hint::{HintAuthority, HintConfig};
 use trust_dns_server::store::sqlite::{SqliteAuthority, SqliteConfig};
 use trust_dns_server::store::StoreConfig;
 
 #[cfg_attr(not(feature = "dnssec"), allow(unused_mut))]
 fn load_zone(zone_dir: &Path, zone_config: &ZoneConfig) -> Result<Box<dyn Authority>, String> {
-    use std::path::PathBuf;
-
     debug!("loading zone with config: {:#?}", zone_config);
 
     let zone_name: Name = zone_config.get_zone().expect("bad zone name");
@@ -108,6 +136,24 @@ fn load_zone(zone_dir: &Path, zone_config: &ZoneConfig) -> Result<Box<dyn Author
                 config,
             ).map(Box::new)?
         }
+        Some(StoreConfig::Hint(ref config)) => {
+            if zone_path.is_some() {
+                warn!("ignoring [[zones.file]] instead using [[zones.stores.root_hints_path]]");
+            }
+            HintAuthority::try_from_config(zone_name, Some(zone_dir), config).map(Box::new)?
+        }
+        Some(StoreConfig::Forward(ref config)) => {
+            if zone_path.is_some() {
+                warn!("ignoring [[zones.file]] for forward zone");
+            }
+            ForwardAuthority::try_from_config(zone_name, config).map(Box::new)?
+        }
+        None if zone_type == ZoneType::Hint => {
+            let config = HintConfig::new(
+                zone_path.ok_or_else(|| "file is a necessary parameter of zone_config")?,
+            );
+            HintAuthority::try_from_config(zone_name, Some(zone_dir), &config).map(Box::new)?
+        }
         None if zone_config.is_update_allowed() => {
             warn!(
                 "using deprecated SQLite load configuration, please move to [[zones.stores]] form"
@@ -211,6 +257,919 @@ fn load_zone(zone_dir: &Path, zone_config: &ZoneConfig) -> Result<Box<dyn Author
     Ok(authority)
 }
 
+/// Returns the path of the journal which will be written for dynamic updates to the zone, if any
+fn journal_path(zone_dir: &Path, zone_config: &ZoneConfig) -> Option<PathBuf> {
+    match zone_config.stores {
+        Some(StoreConfig::Sqlite(ref config)) => Some(zone_dir.join(&config.journal_file_path)),
+        Some(StoreConfig::File(..))
+        | Some(StoreConfig::Hint(..))
+        | Some(StoreConfig::Forward(..)) => None,
+        None if zone_config.is_update_allowed() => zone_config
+            .file
+            .as_ref()
+            .map(|file| zone_dir.join(file).with_extension("jrnl")),
+        None => None,
+    }
+}
+
+/// Returns the path of the zone file, i.e. the original records of the zone
+fn zone_file_path(zone_dir: &Path, zone_config: &ZoneConfig) -> Option<PathBuf> {
+    match zone_config.stores {
+        Some(StoreConfig::Sqlite(ref config)) => Some(zone_dir.join(&config.zone_file_path)),
+        Some(StoreConfig::File(ref config)) => Some(zone_dir.join(&config.zone_file_path)),
+        Some(StoreConfig::Hint(ref config)) => Some(zone_dir.join(&config.root_hints_path)),
+        Some(StoreConfig::Forward(..)) => None,
+        None => zone_config.file.as_ref().map(|file| zone_dir.join(file)),
+    }
+}
+
+/// Zone files may be shared between views, but each journal may only be used by a single zone
+fn check_journals(zone_dir: &Path, config: &Config) -> Result<(), String> {
+    let mut journals = HashSet::new();
+    for (_, _, zones) in configured_views(config) {
+        for zone in zones {
+            if let Some(journal) = journal_path(zone_dir, zone) {
+                if !journals.insert(journal.clone()) {
+                    return Err(format!(
+                        "journal {} for zone {} is already used by another zone",
+                        journal.display(),
+                        zone.zone
+                    ));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Name of the view for zones configured outside of any view
+const DEFAULT_VIEW: &str = "default";
+
+/// All views with their zones in matching order, zones outside of any view are served to all
+///  requests which no view matched
+fn configured_views(config: &Config) -> Vec<(&str, Option<&ViewConfig>, &[ZoneConfig])> {
+    let mut views: Vec<(&str, Option<&ViewConfig>, &[ZoneConfig])> = config
+        .get_views()
+        .iter()
+        .map(|v| (v.get_name(), Some(v), v.get_zones()))
+        .collect();
+
+    if !config.get_zones().is_empty() || config.get_views().is_empty() {
+        views.push((DEFAULT_VIEW, None, config.get_zones()));
+    }
+
+    views
+}
+
+/// The configured addresses to listen on, the unspecified IPv4 address if there are none
+fn listen_addrs(config: &Config) -> Vec<IpAddr> {
+    let mut listen_addrs: Vec<IpAddr> = config
+        .get_listen_addrs_ipv4()
+        .into_iter()
+        .map(IpAddr::V4)
+        .chain(config.get_listen_addrs_ipv6().into_iter().map(IpAddr::V6))
+        .collect();
+
+    if listen_addrs.is_empty() {
+        listen_addrs.push(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)));
+    }
+    listen_addrs
+}
+
+/// The matching rules of a view
+struct ViewRules {
+    clients: Vec<NetworkPrefix>,
+    destinations: Vec<NetworkPrefix>,
+    keys: Vec<TsigKey>,
+    /// clients whose recursive queries are resolved
+    recursion: Vec<NetworkPrefix>,
+    /// names below zone cuts are answered with referrals
+    referrals: bool,
+}
+
+impl ViewRules {
+    fn try_from_config(view_config: Option<&ViewConfig>, config: &Config) -> Result<Self, String> {
+        let recursion = config
+            .get_allow_recursion()
+            .map_err(|e| format!("bad allow_recursion: {}", e))?;
+
+        let view_config = match view_config {
+            Some(view_config) => view_config,
+            None => {
+                return Ok(ViewRules {
+                    clients: vec![],
+                    destinations: vec![],
+                    keys: vec![],
+                    recursion,
+                    referrals: config.is_referrals_enabled(),
+                })
+            }
+        };
+
+        let destinations = view_config
+            .get_match_destinations()
+            .map_err(|e| format!("bad match_destinations: {}", e))?;
+
+        // UDP sockets bound to the unspecified address don't see the destination of requests
+        if !destinations.is_empty() && listen_addrs(config).iter().any(IpAddr::is_unspecified) {
+            return Err(format!(
+                "match_destinations of view {} requires specific listen_addrs_ipv4 and listen_addrs_ipv6",
+                view_config.get_name()
+            ));
+        }
+
+        Ok(ViewRules {
+            clients: view_config
+                .get_match_clients()
+                .map_err(|e| format!("bad match_clients: {}", e))?,
+            destinations,
+            keys: view_config
+                .get_match_keys(config.get_tsig_keys())
+                .map_err(|e| format!("bad match_keys: {}", e))?,
+            recursion,
+            referrals: config.is_referrals_enabled(),
+        })
+    }
+
+    fn apply(self, view: &mut View) {
+        view.set_match_clients(self.clients)
+            .set_match_destinations(self.destinations)
+            .set_match_keys(self.keys);
+        view.catalog_mut().set_allow_recursion(self.recursion);
+        view.catalog_mut().set_referrals(self.referrals);
+    }
+}
+
+/// Returns the serial of the zone's SOA record
+fn soa_serial(authority: &dyn Authority) -> Option<u32> {
+    authority.soa().iter().next().and_then(|soa| match *soa.rdata() {
+        RData::SOA(ref soa) => Some(soa.serial()),
+        _ => None,
+    })
+}
+
+/// Serial number arithmetic, [RFC 1982](https://tools.ietf.org/html/rfc1982)
+fn is_serial_greater(new: u32, old: u32) -> bool {
+    new != old && new.wrapping_sub(old) < (1 << 31)
+}
+
+/// The modification time and size of a zone file, a change in either means the file changed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct ZoneFileStamp {
+    modified: SystemTime,
+    len: u64,
+}
+
+/// The configuration and the stamp of the zone file when the zone was loaded
+struct LoadedZone {
+    config: ZoneConfig,
+    stamp: Option<ZoneFileStamp>,
+}
+
+impl LoadedZone {
+    fn new(zone_dir: &Path, config: &ZoneConfig) -> Self {
+        let stamp = zone_file_path(zone_dir, config)
+            .and_then(|path| fs::metadata(path).ok())
+            .and_then(|metadata| {
+                metadata.modified().ok().map(|modified| ZoneFileStamp {
+                    modified,
+                    len: metadata.len(),
+                })
+            });
+
+        LoadedZone {
+            config: config.clone(),
+            stamp,
+        }
+    }
+}
+
+/// Loads all configured views and zones, and on reload only those which have changed
+struct ZoneLoader {
+    config_path: PathBuf,
+    /// zone directory from the command line, which overrides the configuration
+    zone_dir: Option<PathBuf>,
+    /// zone directory of the zones currently served
+    directory: PathBuf,
+    /// zones by view and zone name as they are currently served
+    loaded: HashMap<(String, LowerName), LoadedZone>,
+}
+
+impl ZoneLoader {
+    fn new(config_path: PathBuf, zone_dir: Option<PathBuf>) -> Self {
+        ZoneLoader {
+            config_path,
+            zone_dir,
+            directory: PathBuf::new(),
+            loaded: HashMap::new(),
+        }
+    }
+
+    fn zone_dir(&self, config: &Config) -> PathBuf {
+        self.zone_dir
+            .clone()
+            .unwrap_or_else(|| config.get_directory().to_path_buf())
+    }
+
+    /// Load all views and zones of the configuration
+    fn load(&mut self, config: &Config) -> Result<ViewCatalog, String> {
+        let zone_dir = self.zone_dir(config);
+        check_journals(&zone_dir, config)?;
+
+        let mut catalog = ViewCatalog::new();
+        for (view_name, view_config, zones) in configured_views(config) {
+            info!("loading view: {}", view_name);
+            let mut view = View::new(view_name.to_string(), Catalog::new());
+            ViewRules::try_from_config(view_config, config)
+                .map_err(|e| format!("could not load view {}: {}", view_name, e))?
+                .apply(&mut view);
+
+            for zone_config in zones {
+                let zone_name = zone_name(zone_config)?;
+                let authority = load_zone(&zone_dir, zone_config)
+                    .map_err(|e| format!("could not load zone {}: {}", zone_name, e))?;
+
+                view.catalog_mut().upsert(zone_name.clone(), authority);
+                self.loaded.insert(
+                    (view_name.to_string(), zone_name),
+                    LoadedZone::new(&zone_dir, zone_config),
+                );
+            }
+
+            catalog.push(view);
+        }
+
+        self.directory = zone_dir;
+        Ok(catalog)
+    }
+
+    /// Re-reads the configuration, and replaces all views and zones which have changed
+    ///
+    /// Zones are loaded while requests continue to be served from the current zones, the
+    ///  `catalog` is only locked to swap in the changes. A zone whose file has changed, but
+    ///  whose SOA serial was not increased, is not reloaded. On any error nothing is changed.
+    fn reload(&mut self, catalog: &Mutex<ViewCatalog>) -> Result<(), String> {
+        info!("reloading configuration from: {:?}", self.config_path);
+        let config = Config::read_config(&self.config_path).map_err(|e| {
+            format!(
+                "could not read config {}: {}",
+                self.config_path.display(),
+                e
+            )
+        })?;
+        let zone_dir = self.zone_dir(&config);
+        check_journals(&zone_dir, &config)?;
+
+        let views = configured_views(&config);
+        let mut rules = HashMap::new();
+        for &(view_name, view_config, _) in &views {
+            let view_rules = ViewRules::try_from_config(view_config, &config)
+                .map_err(|e| format!("could not load view {}: {}", view_name, e))?;
+            rules.insert(view_name, view_rules);
+        }
+
+        // the serials of the zones as they are currently served
+        let mut serials = HashMap::new();
+        {
+            let catalog = catalog.lock().expect("poisoned lock");
+            for view in catalog.views() {
+                for zone_name in view.catalog().zone_names() {
+                    let serial = view
+                        .catalog()
+                        .find(zone_name)
+                        .and_then(|authority| soa_serial(&**authority.read().unwrap()));
+
+                    if let Some(serial) = serial {
+                        serials.insert((view.name().to_string(), zone_name.clone()), serial);
+                    }
+                }
+            }
+        }
+
+        let mut loaded = HashMap::new();
+        let mut changed = HashMap::new();
+        for &(view_name, _, zones) in &views {
+            for zone_config in zones {
+                let zone_name = zone_name(zone_config)?;
+                let key = (view_name.to_string(), zone_name.clone());
+                let loaded_zone = LoadedZone::new(&zone_dir, zone_config);
+
+                let previous = self.loaded.get(&key);
+                if let Some(previous) = previous {
+                    if previous.config == loaded_zone.config && previous.stamp == loaded_zone.stamp
+                    {
+                        debug!("zone unchanged: {} in view: {}", zone_name, view_name);
+                        loaded.insert(key, loaded_zone);
+                        continue;
+                    }
+                }
+
+                let authority = load_zone(&zone_dir, zone_config)
+                    .map_err(|e| format!("could not load zone {}: {}", zone_name, e))?;
+
+                if let (Some(previous), Some(&old_serial)) = (previous, serials.get(&key)) {
+                    let new_serial = soa_serial(&*authority).unwrap_or(0);
+                    if previous.config == loaded_zone.config
+                        && !is_serial_greater(new_serial, old_serial)
+                    {
+                        warn!(
+                            "zone file changed, but serial {} is not greater than {}, not reloading: {} in view: {}",
+                            new_serial, old_serial, zone_name, view_name
+                        );
+
+                        // keep the prior stamp, so the file is checked again
+                        let stamp = previous.stamp;
+                        loaded.insert(
+                            key,
+                            LoadedZone {
+                                config: loaded_zone.config,
+                                stamp,
+                            },
+                        );
+                        continue;
+                    }
+                }
+
+                info!("reloaded zone: {} in view: {}", zone_name, view_name);
+                changed.insert(key.clone(), authority);
+                loaded.insert(key, loaded_zone);
+            }
+        }
+
+        let mut view_zones = Vec::with_capacity(views.len());
+        for (view_name, _, zones) in views {
+            let zone_names = zones
+                .iter()
+                .map(zone_name)
+                .collect::<Result<HashSet<LowerName>, String>>()?;
+            view_zones.push((view_name, zone_names));
+        }
+
+        // all zones are loaded, build the new catalog from the current views and swap it in,
+        //  nothing below may fail
+        let mut catalog = catalog.lock().expect("poisoned lock");
+        let mut previous_views = mem::replace(&mut *catalog, ViewCatalog::new());
+        let mut new_catalog = ViewCatalog::new();
+        for (view_name, zone_names) in view_zones {
+            let mut view = previous_views.remove(view_name).unwrap_or_else(|| {
+                info!("adding view: {}", view_name);
+                View::new(view_name.to_string(), Catalog::new())
+            });
+            if let Some(view_rules) = rules.remove(view_name) {
+                view_rules.apply(&mut view);
+            }
+
+            let removed = view
+                .catalog()
+                .zone_names()
+                .filter(|name| !zone_names.contains(*name))
+                .cloned()
+                .collect::<Vec<_>>();
+            for zone_name in removed {
+                info!("removing zone: {} from view: {}", zone_name, view_name);
+                view.catalog_mut().remove(&zone_name);
+            }
+
+            for zone_name in zone_names {
+                if let Some(authority) = changed.remove(&(view_name.to_string(), zone_name.clone())) {
+                    view.catalog_mut().upsert(zone_name, authority);
+                }
+            }
+
+            new_catalog.push(view);
+        }
+        *catalog = new_catalog;
+
+        for view in previous_views.views() {
+            info!("removing view: {}", view.name());
+        }
+
+        self.directory = zone_dir;
+        self.loaded = loaded;
+        Ok(())
+    }
+
+    /// Reloads the zone file of a single zone in all views, if its SOA serial was increased
+    fn reload_zone(&mut self, zone: &LowerName, catalog: &Mutex<ViewCatalog>) -> ControlResult {
+        let mut authorities = vec![];
+        for (key, loaded_zone) in &self.loaded {
+            if key.1 != *zone {
+                continue;
+            }
+
+            let authority = load_zone(&self.directory, &loaded_zone.config)
+                .map_err(|e| format!("could not load zone {}: {}", zone, e))?;
+            authorities.push((key.clone(), authority));
+        }
+
+        if authorities.is_empty() {
+            return Err(format!("no such zone: {}", zone));
+        }
+
+        let mut output = String::new();
+        let mut catalog = catalog.lock().expect("poisoned lock");
+        for ((view_name, zone_name), authority) in authorities {
+            let view = match catalog.view_mut(&view_name) {
+                Some(view) => view,
+                None => continue,
+            };
+
+            let new_serial = soa_serial(&*authority).unwrap_or(0);
+            let old_serial = view
+                .catalog()
+                .find(&zone_name)
+                .and_then(|authority| soa_serial(&**authority.read().unwrap()));
+            if let Some(old_serial) = old_serial {
+                if !is_serial_greater(new_serial, old_serial) {
+                    output.push_str(&format!(
+                        "{} in view {}: serial {} is up to date\n",
+                        zone_name, view_name, old_serial
+                    ));
+                    continue;
+                }
+            }
+
+            info!("reloaded zone: {} in view: {}", zone_name, view_name);
+            view.catalog_mut().upsert(zone_name.clone(), authority);
+            output.push_str(&format!(
+                "{} in view {}: reloaded serial {}\n",
+                zone_name, view_name, new_serial
+            ));
+
+            let key = (view_name, zone_name);
+            let loaded_zone = LoadedZone::new(&self.directory, &self.loaded[&key].config);
+            self.loaded.insert(key, loaded_zone);
+        }
+
+        Ok(output)
+    }
+
+    /// Returns the names of the views which serve the zone, with the configuration of the zone
+    fn zone_configs(&self, zone: &LowerName) -> Vec<(String, ZoneConfig)> {
+        let mut configs = self
+            .loaded
+            .iter()
+            .filter(|&(key, _)| key.1 == *zone)
+            .map(|(key, loaded_zone)| (key.0.clone(), loaded_zone.config.clone()))
+            .collect::<Vec<_>>();
+        configs.sort_by(|a, b| a.0.cmp(&b.0));
+        configs
+    }
+}
+
+fn zone_name(zone_config: &ZoneConfig) -> Result<LowerName, String> {
+    zone_config
+        .get_zone()
+        .map(LowerName::from)
+        .map_err(|e| format!("bad zone name {}: {}", zone_config.zone, e))
+}
+
+/// Reloads the zones on a new thread, so that requests continue to be served meanwhile
+fn spawn_reload(zone_loader: Arc<Mutex<ZoneLoader>>, catalog: Arc<Mutex<ViewCatalog>>) {
+    thread::Builder::new()
+        .name("named:reload".to_string())
+        .spawn(move || {
+            match zone_loader.lock().expect("poisoned lock").reload(&catalog) {
+                Ok(()) => info!("reload complete"),
+                Err(e) => error!("reload failed, continuing with the prior zones: {}", e),
+            }
+        }).expect("could not spawn reload thread");
+}
+
+/// Executes the commands received on the control channel
+struct NamedControl {
+    zone_loader: Arc<Mutex<ZoneLoader>>,
+    catalog: Arc<Mutex<ViewCatalog>>,
+    started: Instant,
+}
+
+impl NamedControl {
+    /// Applies `f` to the catalog of each view which serves the zone, and collects the output
+    fn with_zone<F>(&self, zone: &LowerName, mut f: F) -> ControlResult
+    where
+        F: FnMut(&mut Catalog) -> ControlResult,
+    {
+        let mut output = String::new();
+        let mut catalog = self.catalog.lock().expect("poisoned lock");
+        for view in catalog.views_mut() {
+            if !view.catalog().contains(zone) {
+                continue;
+            }
+
+            let view_output = f(view.catalog_mut())
+                .map_err(|e| format!("{} in view {}: {}", zone, view.name(), e))?;
+            output.push_str(&format!("{} in view {}: {}\n", zone, view.name(), view_output));
+        }
+
+        if output.is_empty() {
+            return Err(format!("no such zone: {}", zone));
+        }
+
+        Ok(output)
+    }
+
+    fn status(&self, zone: Option<&LowerName>) -> ControlResult {
+        let mut output = String::new();
+        let catalog = self.catalog.lock().expect("poisoned lock");
+        for view in catalog.views() {
+            let mut zone_names = view
+                .catalog()
+                .zone_names()
+                .filter(|name| zone.map_or(true, |zone| zone == *name))
+                .collect::<Vec<_>>();
+            zone_names.sort();
+
+            for zone_name in zone_names {
+                let authority = match view.catalog().find(zone_name) {
+                    Some(authority) => authority.read().unwrap(),
+                    None => continue,
+                };
+
+                output.push_str(&format!(
+                    "{} in view {}: type: {:?} serial: {}{}\n",
+                    zone_name,
+                    view.name(),
+                    authority.zone_type(),
+                    soa_serial(&**authority).map_or("none".to_string(), |s| s.to_string()),
+                    if view.catalog().is_frozen(zone_name) {
+                        " frozen"
+                    } else {
+                        ""
+                    }
+                ));
+            }
+        }
+
+        match zone {
+            Some(zone) if output.is_empty() => Err(format!("no such zone: {}", zone)),
+            _ => Ok(output),
+        }
+    }
+
+    fn stats(&self) -> ControlResult {
+        let catalog = self.catalog.lock().expect("poisoned lock");
+        let mut output = format!(
+            "uptime: {}s\nviews: {}\n",
+            self.started.elapsed().as_secs(),
+            catalog.views().len()
+        );
+
+        for view in catalog.views() {
+            output.push_str(&format!(
+                "view {}: zones: {}\n",
+                view.name(),
+                view.catalog().zone_names().count()
+            ));
+        }
+
+        output.push_str(&Metrics::global().encode());
+        Ok(output)
+    }
+
+    /// Sends NOTIFY messages for the zone in each view which serves it
+    ///
+    /// The name servers of the zone, except the primary master of its SOA, are notified on port
+    ///  53 at their addresses in the zone, and the `also_notify` addresses of the zone's config.
+    fn notify(&self, zone: &LowerName) -> ControlResult {
+        let configs = self
+            .zone_loader
+            .lock()
+            .expect("poisoned lock")
+            .zone_configs(zone);
+
+        // the catalog is not locked while waiting for the responses
+        let mut notifications = vec![];
+        let mut output = String::new();
+        {
+            let catalog = self.catalog.lock().expect("poisoned lock");
+            for (view_name, zone_config) in configs {
+                let authority = match catalog.view(&view_name) {
+                    Some(view) if view.catalog().contains(zone) => view.catalog().find(zone),
+                    _ => continue,
+                };
+                let authority = authority.expect("zone not found").read().unwrap();
+
+                let soa = match authority.soa().iter().next() {
+                    Some(soa) => soa.clone(),
+                    None => return Err(format!("{} in view {}: zone has no SOA", zone, view_name)),
+                };
+                let mname = match *soa.rdata() {
+                    RData::SOA(ref soa) => soa.mname().clone(),
+                    _ => return Err(format!("{} in view {}: bad SOA", zone, view_name)),
+                };
+
+                let mut addrs = vec![];
+                for ns in authority.ns(false, SupportedAlgorithms::new()).iter() {
+                    let ns = match *ns.rdata() {
+                        RData::NS(ref ns) if *ns != mname => LowerName::from(ns),
+                        _ => continue,
+                    };
+
+                    let mut found = false;
+                    for record_type in &[RecordType::A, RecordType::AAAA] {
+                        let lookup =
+                            authority.lookup(&ns, *record_type, false, SupportedAlgorithms::new());
+                        for record in lookup.iter() {
+                            let ip = match *record.rdata() {
+                                RData::A(ip) => IpAddr::V4(ip),
+                                RData::AAAA(ip) => IpAddr::V6(ip),
+                                _ => continue,
+                            };
+                            addrs.push(SocketAddr::new(ip, 53));
+                            found = true;
+                        }
+                    }
+
+                    if !found {
+                        output.push_str(&format!(
+                            "{} in view {}: {} has no address in the zone, not notified\n",
+                            zone, view_name, ns
+                        ));
+                    }
+                }
+
+                addrs.extend_from_slice(zone_config.get_also_notify());
+                addrs.sort();
+                addrs.dedup();
+                notifications.push((view_name, soa, addrs));
+            }
+        }
+
+        if notifications.is_empty() {
+            return Err(format!("no such zone: {}", zone));
+        }
+        if notifications
+            .iter()
+            .all(|notification| notification.2.is_empty())
+        {
+            return Err(format!(
+                "{}: no name servers or also_notify addresses to notify",
+                zone
+            ));
+        }
+
+        for (view_name, soa, addrs) in notifications {
+            for addr in addrs {
+                let result = match send_notify(addr, &soa) {
+                    Ok(response_code) => format!("{:?}", response_code),
+                    Err(e) => format!("failed: {}", e),
+                };
+                output.push_str(&format!(
+                    "{} in view {}: notified {}: {}\n",
+                    zone, view_name, addr, result
+                ));
+            }
+        }
+
+        Ok(output)
+    }
+
+    /// Transfers the slave zone from the first of its masters which answers, in each view which
+    ///  serves it
+    ///
+    /// The transferred zone is served from memory, the zone file is left as it is.
+    fn retransfer(&self, zone: &LowerName) -> ControlResult {
+        let configs = self
+            .zone_loader
+            .lock()
+            .expect("poisoned lock")
+            .zone_configs(zone);
+        if configs.is_empty() {
+            return Err(format!("no such zone: {}", zone));
+        }
+
+        let mut output = String::new();
+        for (view_name, zone_config) in configs {
+            let error = |e: String| format!("{} in view {}: {}", zone, view_name, e);
+            if zone_config.get_zone_type() != ZoneType::Slave {
+                return Err(error("not a slave zone".to_string()));
+            }
+            if let Some(StoreConfig::Sqlite(_)) = zone_config.stores {
+                return Err(error(
+                    "transfers into sqlite stores are not supported".to_string(),
+                ));
+            }
+
+            let origin = Name::from(zone.clone());
+            let mut transferred = Err("no masters configured".to_string());
+            for master in zone_config.get_masters() {
+                transferred = transfer_zone(*master, &origin)
+                    .map(|records| (*master, records))
+                    .map_err(|e| format!("transfer from {} failed: {}", master, e));
+                match transferred {
+                    Ok(..) => break,
+                    Err(ref e) => warn!("{}", error(e.clone())),
+                }
+            }
+
+            let (master, records) = transferred.map_err(&error)?;
+            let authority =
+                FileAuthority::try_from_transfer(origin, records, zone_config.is_axfr_allowed())
+                    .map_err(&error)?;
+            let serial = authority.serial();
+
+            let mut catalog = self.catalog.lock().expect("poisoned lock");
+            if let Some(view) = catalog.view_mut(&view_name) {
+                info!(
+                    "transferred zone: {} in view: {} from: {}",
+                    zone, view_name, master
+                );
+                view.catalog_mut().upsert(zone.clone(), Box::new(authority));
+                output.push_str(&format!(
+                    "{} in view {}: transferred serial {} from {}\n",
+                    zone, view_name, serial, master
+                ));
+            }
+        }
+
+        Ok(output)
+    }
+
+    /// Removes the cached answers of the zone, or of all zones which cache answers
+    fn flush(&self, zone: Option<&LowerName>) -> ControlResult {
+        if let Some(zone) = zone {
+            return self.with_zone(zone, |catalog| {
+                let authority = catalog
+                    .find(zone)
+                    .ok_or_else(|| format!("no such zone: {}", zone))?;
+                if authority.read().unwrap().flush_cache() {
+                    Ok("flushed".to_string())
+                } else {
+                    Err("the zone does not cache answers".to_string())
+                }
+            });
+        }
+
+        let mut output = String::new();
+        let catalog = self.catalog.lock().expect("poisoned lock");
+        for view in catalog.views() {
+            let mut zone_names = view.catalog().zone_names().collect::<Vec<_>>();
+            zone_names.sort();
+
+            for zone_name in zone_names {
+                let flushed = view
+                    .catalog()
+                    .find(zone_name)
+                    .map_or(false, |authority| authority.read().unwrap().flush_cache());
+                if flushed {
+                    output.push_str(&format!("{} in view {}: flushed\n", zone_name, view.name()));
+                }
+            }
+        }
+
+        Ok(output)
+    }
+}
+
+/// Time to wait for the response to a NOTIFY, and for each message of a zone transfer
+const CONTROL_NETWORK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Sends a NOTIFY message with the SOA of the zone to `addr`, and returns the response code
+fn send_notify(addr: SocketAddr, soa: &Record) -> Result<ResponseCode, String> {
+    let conn = UdpClientConnection::with_timeout(addr, CONTROL_NETWORK_TIMEOUT)
+        .map_err(|e| e.to_string())?;
+    let mut client = SyncClient::new(conn);
+
+    let mut rrset = RecordSet::new(soa.name(), RecordType::SOA, 0);
+    rrset.insert(soa.clone(), 0);
+
+    client
+        .notify(
+            soa.name().clone(),
+            DNSClass::IN,
+            RecordType::SOA,
+            Some(rrset),
+        )
+        .map(|response| response.response_code())
+        .map_err(|e| e.to_string())
+}
+
+/// Transfers the zone with an AXFR over TCP, and returns all records starting with the SOA
+///
+/// The transfer may span many messages, it is complete when the SOA is received a second time,
+///  [RFC 5936](https://tools.ietf.org/html/rfc5936) section 2.2.
+fn transfer_zone(master: SocketAddr, zone: &Name) -> Result<Vec<Record>, String> {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    let mut request = Message::new();
+    request
+        .set_id(rand::random())
+        .set_message_type(MessageType::Query)
+        .set_op_code(OpCode::Query)
+        .add_query(Query::query(zone.clone(), RecordType::AXFR));
+    let request_bytes = request.to_vec().map_err(|e| e.to_string())?;
+
+    let mut stream =
+        TcpStream::connect_timeout(&master, CONTROL_NETWORK_TIMEOUT).map_err(|e| e.to_string())?;
+    stream
+        .set_read_timeout(Some(CONTROL_NETWORK_TIMEOUT))
+        .map_err(|e| e.to_string())?;
+
+    // messages over TCP are prefixed with their length, RFC 1035 section 4.2.2
+    let len = request_bytes.len();
+    stream
+        .write_all(&[(len >> 8) as u8, len as u8])
+        .and_then(|()| stream.write_all(&request_bytes))
+        .map_err(|e| e.to_string())?;
+
+    let mut records: Vec<Record> = vec![];
+    loop {
+        let mut len = [0u8; 2];
+        stream.read_exact(&mut len).map_err(|e| e.to_string())?;
+        let mut buffer = vec![0u8; (usize::from(len[0]) << 8) | usize::from(len[1])];
+        stream.read_exact(&mut buffer).map_err(|e| e.to_string())?;
+
+        let mut response = Message::from_vec(&buffer).map_err(|e| e.to_string())?;
+        if response.id() != request.id() {
+            return Err(format!("unexpected message id: {}", response.id()));
+        }
+        if response.response_code() != ResponseCode::NoError {
+            return Err(format!("transfer refused: {:?}", response.response_code()));
+        }
+
+        for record in response.take_answers() {
+            let is_soa = record.rr_type() == RecordType::SOA;
+            if records.is_empty() && !is_soa {
+                return Err("transfer does not start with the SOA".to_string());
+            }
+
+            records.push(record);
+            if is_soa && records.len() > 1 {
+                return Ok(records);
+            }
+        }
+    }
+}
+
+impl ControlHandler for NamedControl {
+    fn handle_command(&self, command: ControlCommand) -> ControlResult {
+        let zone = command.zone().cloned().map(LowerName::from);
+
+        match (command, zone) {
+            (ControlCommand::Reload(_), None) => self
+                .zone_loader
+                .lock()
+                .expect("poisoned lock")
+                .reload(&self.catalog)
+                .map(|()| "reload complete\n".to_string()),
+            (ControlCommand::Reload(_), Some(zone)) => self
+                .zone_loader
+                .lock()
+                .expect("poisoned lock")
+                .reload_zone(&zone, &self.catalog),
+            (ControlCommand::Status(_), zone) => self.status(zone.as_ref()),
+            (ControlCommand::Freeze(_), Some(zone)) => self.with_zone(&zone, |catalog| {
+                catalog.freeze(&zone);
+                Ok("frozen".to_string())
+            }),
+            (ControlCommand::Thaw(_), Some(zone)) => self.with_zone(&zone, |catalog| {
+                catalog.thaw(&zone);
+                Ok("thawed".to_string())
+            }),
+            (ControlCommand::Sign(_), Some(zone)) => self.with_zone(&zone, |catalog| {
+                let authority = catalog
+                    .find(&zone)
+                    .ok_or_else(|| format!("no such zone: {}", zone))?;
+                let mut authority = authority.write().unwrap();
+                authority
+                    .secure_zone()
+                    .map(|()| "signed".to_string())
+                    .map_err(|e| e.to_string())
+            }),
+            (ControlCommand::Notify(_), Some(zone)) => self.notify(&zone),
+            (ControlCommand::Retransfer(_), Some(zone)) => self.retransfer(&zone),
+            (ControlCommand::Flush(_), zone) => self.flush(zone.as_ref()),
+            (ControlCommand::Stats, _) => self.stats(),
+            (command, None) => Err(format!("{} requires a zone", command)),
+        }
+    }
+}
+
+/// Binds the control socket, replacing a stale socket of a prior run
+///
+/// Only the user running named may connect to the socket, if its permissions can not be
+///  restricted the socket is removed again and an error is returned.
+#[cfg(unix)]
+fn bind_control_socket(path: &Path) -> io::Result<UnixListener> {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    if let Ok(metadata) = fs::symlink_metadata(path) {
+        if metadata.file_type().is_socket() {
+            fs::remove_file(path)?;
+        }
+    }
+
+    let listener = UnixListener::bind(path)?;
+    if let Err(e) = fs::set_permissions(path, fs::Permissions::from_mode(0o600)) {
+        fs::remove_file(path).ok();
+        return Err(e);
+    }
+
+    Ok(listener)
+}
+
 // argument name constants for the CLI options
 const QUIET_ARG: &str = "quiet";
 const DEBUG_ARG: &str = "debug";
@@ -329,34 +1288,23 @@ pub fn main() {
         .map(Path::new)
         .unwrap_or_else(|| &directory_config);
 
-    let mut catalog: Catalog = Catalog::new();
     // configure our server based on the config_path
-    for zone in config.get_zones() {
-        let zone_name = zone
-            .get_zone()
-            .unwrap_or_else(|_| panic!("bad zone name in {:?}", config_path));
-
-        match load_zone(zone_dir, zone) {
-            Ok(authority) => catalog.upsert(zone_name.into(), authority),
-            Err(error) => panic!("could not load zone {}: {}", zone_name, error),
-        }
-    }
+    info!("loading zones from: {}", zone_dir.display());
+    let mut zone_loader = ZoneLoader::new(
+        config_path.to_path_buf(),
+        args.flag_zonedir.as_ref().map(PathBuf::from),
+    );
+    let catalog = zone_loader
+        .load(&config)
+        .unwrap_or_else(|e| panic!("could not load zones in {:?}: {}", config_path, e));
+    let zone_loader = Arc::new(Mutex::new(zone_loader));
 
     // TODO: support all the IPs asked to listen on...
     // TODO:, there should be the option to listen on any port, IP and protocol option...
-    let v4addr = config.get_listen_addrs_ipv4();
-    let v6addr = config.get_listen_addrs_ipv6();
-    let mut listen_addrs: Vec<IpAddr> = v4addr
-        .into_iter()
-        .map(IpAddr::V4)
-        .chain(v6addr.into_iter().map(IpAddr::V6))
-        .collect();
+    let listen_addrs = listen_addrs(&config);
     let listen_port: u16 = args.flag_port.unwrap_or_else(|| config.get_listen_port());
     let tcp_request_timeout = config.get_tcp_request_timeout();
 
-    if listen_addrs.is_empty() {
-        listen_addrs.push(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)));
-    }
     let sockaddrs: Vec<SocketAddr> = listen_addrs
         .iter()
         .flat_map(|x| (*x, listen_port).to_socket_addrs().unwrap())
@@ -370,18 +1318,38 @@ pub fn main() {
         .map(|x| TcpListener::bind(x).unwrap_or_else(|_| panic!("could not bind to tcp: {}", x)))
         .collect();
 
+    let metrics_listener = config.get_metrics_listen_addr().map(|addr| {
+        TcpListener::bind(&addr)
+            .unwrap_or_else(|e| panic!("could not bind to metrics tcp {}: {}", addr, e))
+    });
+
     let mut io_loop = Runtime::new().expect("error when creating tokio Runtime");
 
     // now, run the server, based on the config
-    #[cfg_attr(not(feature = "dns-over-tls"), allow(unused_mut))]
     let mut server = ServerFuture::new(catalog);
 
+    if let Some(rate_limit) = config.get_rate_limit() {
+        info!("enabling response rate limiting: {:?}", rate_limit);
+        let rate_limiter = RateLimiter::try_from_config(rate_limit)
+            .unwrap_or_else(|e| panic!("bad rate_limit configuration: {}", e));
+        server.set_rate_limiter(rate_limiter);
+    }
+
+    if let Some(query_log) = config.get_query_log() {
+        info!("logging queries: {:?}", query_log);
+        let query_logger = QueryLogger::try_from_config(query_log)
+            .unwrap_or_else(|e| panic!("bad query_log configuration: {}", e));
+        server.set_query_logger(query_logger);
+    }
+
     let server_future: Box<Future<Item = (), Error = ()> + Send> =
         Box::new(future::lazy(move || {
             // load all the listeners
             for udp_socket in udp_sockets {
                 info!("listening for UDP on {:?}", udp_socket);
-                server.register_socket(udp_socket);
+                server
+                    .register_socket(udp_socket)
+                    .expect("could not register UDP socket");
             }
 
             // and TCP as necessary
@@ -421,6 +1389,48 @@ pub fn main() {
                 );
             }
 
+            // reload all zones which changed on SIGHUP
+            #[cfg(unix)]
+            {
+                use tokio_signal::unix::{Signal, SIGHUP};
+
+                let zone_loader = zone_loader.clone();
+                let catalog = server.request_handler();
+                tokio::spawn(
+                    Signal::new(SIGHUP)
+                        .flatten_stream()
+                        .for_each(move |_| {
+                            info!("received SIGHUP");
+                            spawn_reload(zone_loader.clone(), catalog.clone());
+                            Ok(())
+                        }).map_err(|e| error!("error handling SIGHUP: {}", e)),
+                );
+            }
+
+            // serve the statistics for Prometheus
+            if let Some(metrics_listener) = metrics_listener {
+                info!("serving metrics on {:?}", metrics_listener);
+                register_metrics_listener(metrics_listener);
+            }
+
+            // listen for commands on the control channel
+            #[cfg(unix)]
+            {
+                if let Some(control_socket) = config.get_control_socket() {
+                    let control_server = ControlServer::new(NamedControl {
+                        zone_loader,
+                        catalog: server.request_handler(),
+                        started: Instant::now(),
+                    });
+                    let listener = bind_control_socket(control_socket).unwrap_or_else(|e| {
+                        panic!("could not bind control socket {:?}: {}", control_socket, e)
+                    });
+
+                    info!("listening for control commands on {:?}", control_socket);
+                    control_server.register_listener(listener);
+                }
+            }
+
             // config complete, starting!
             banner();
             info!("awaiting connections...");
@@ -448,7 +1458,7 @@ pub fn main() {
 #[cfg(feature = "dns-over-tls")]
 fn config_tls(
     args: &Args,
-    server: &mut ServerFuture<Catalog>,
+    server: &mut ServerFuture<ViewCatalog>,
     config: &Config,
     tls_cert_config: &TlsCertConfig,
     zone_dir: &Path,
@@ -488,7 +1498,7 @@ fn config_tls(
 #[cfg(feature = "dns-over-https")]
 fn config_https(
     args: &Args,
-    server: &mut ServerFuture<Catalog>,
+    server: &mut ServerFuture<ViewCatalog>,
     config: &Config,
     tls_cert_config: &TlsCertConfig,
     zone_dir: &Path,
@@ -535,3 +1545,162 @@ fn banner() {
     info!("    o    o    o--o  o-o    o          o-o  o  o  o-o  ");
     info!("");
 }
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::*;
+
+    #[test]
+    fn test_serial_greater() {
+        assert!(is_serial_greater(2, 1));
+        assert!(!is_serial_greater(1, 1));
+        assert!(!is_serial_greater(1, 2));
+
+        // wraps around
+        assert!(is_serial_greater(0, u32::max_value()));
+        assert!(is_serial_greater(1 << 30, u32::max_value()));
+        assert!(!is_serial_greater(u32::max_value(), 0));
+    }
+
+    #[test]
+    fn test_destinations_require_listen_addrs() {
+        let view = "[[views]]\nname = \"internal\"\nmatch_destinations = [\"127.0.0.2\"]\n";
+
+        let rules = |listen: &str| {
+            let config = Config::from_str(&format!("{}{}", listen, view)).unwrap();
+            ViewRules::try_from_config(config.get_views().first(), &config)
+        };
+
+        // the default listen address is unspecified
+        assert!(rules("").is_err());
+        assert!(rules("listen_addrs_ipv4 = [\"0.0.0.0\"]\n").is_err());
+
+        let rules = rules("listen_addrs_ipv4 = [\"127.0.0.2\"]\n").unwrap();
+        assert_eq!(rules.destinations, vec!["127.0.0.2".parse().unwrap()]);
+    }
+
+    fn write_zone(dir: &Path, zone: &str, serial: u32) {
+        let records = format!(
+            "@ IN SOA ns.{zone}. root.{zone}. ({serial} 28800 7200 604800 86400)\n\
+             @ IN NS ns.{zone}.\n\
+             ns IN A 127.0.0.1\n",
+            zone = zone,
+            serial = serial
+        );
+        fs::write(dir.join(format!("{}.zone", zone)), records).expect("failed to write zone");
+    }
+
+    /// Sets the modification time of the zone file to `secs` after the epoch
+    fn set_zone_modified(dir: &Path, zone: &str, secs: u64) {
+        fs::OpenOptions::new()
+            .write(true)
+            .open(dir.join(format!("{}.zone", zone)))
+            .and_then(|file| file.set_modified(UNIX_EPOCH + Duration::from_secs(secs)))
+            .expect("failed to set modification time");
+    }
+
+    fn write_config(dir: &Path, zones: &[&str]) {
+        let mut config = String::new();
+        for zone in zones {
+            config.push_str(&format!(
+                "[[zones]]\nzone = \"{zone}\"\nzone_type = \"Master\"\nfile = \"{zone}.zone\"\n",
+                zone = zone
+            ));
+        }
+        fs::write(dir.join("named.toml"), config).expect("failed to write config");
+    }
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("trust-dns-reload-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("failed to create test dir");
+        dir
+    }
+
+    fn load(dir: &Path) -> (ZoneLoader, Mutex<ViewCatalog>) {
+        let config_path = dir.join("named.toml");
+        let config = Config::read_config(&config_path).expect("failed to read config");
+        let mut zone_loader = ZoneLoader::new(config_path, Some(dir.to_path_buf()));
+        let catalog = zone_loader.load(&config).expect("failed to load zones");
+        (zone_loader, Mutex::new(catalog))
+    }
+
+    fn served_serial(catalog: &Mutex<ViewCatalog>, zone: &str) -> Option<u32> {
+        let catalog = catalog.lock().unwrap();
+        let zone = LowerName::from(Name::from_str(&format!("{}.", zone)).unwrap());
+        catalog
+            .view(DEFAULT_VIEW)
+            .and_then(|view| view.catalog().find(&zone))
+            .and_then(|authority| soa_serial(&**authority.read().unwrap()))
+    }
+
+    #[test]
+    fn test_reload_add_and_remove_zones() {
+        let dir = test_dir("add-remove");
+        write_zone(&dir, "example.com", 1);
+        write_zone(&dir, "example.net", 1);
+        write_config(&dir, &["example.com"]);
+
+        let (mut zone_loader, catalog) = load(&dir);
+        assert_eq!(served_serial(&catalog, "example.com"), Some(1));
+        assert_eq!(served_serial(&catalog, "example.net"), None);
+
+        write_config(&dir, &["example.net"]);
+        zone_loader.reload(&catalog).expect("reload failed");
+        assert_eq!(served_serial(&catalog, "example.com"), None);
+        assert_eq!(served_serial(&catalog, "example.net"), Some(1));
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_reload_changed_zone() {
+        let dir = test_dir("changed");
+        write_zone(&dir, "example.com", 2);
+        set_zone_modified(&dir, "example.com", 1);
+        write_config(&dir, &["example.com"]);
+
+        let (mut zone_loader, catalog) = load(&dir);
+        assert_eq!(served_serial(&catalog, "example.com"), Some(2));
+
+        // the serial was not increased, the served zone is kept
+        write_zone(&dir, "example.com", 1);
+        set_zone_modified(&dir, "example.com", 2);
+        zone_loader.reload(&catalog).expect("reload failed");
+        assert_eq!(served_serial(&catalog, "example.com"), Some(2));
+
+        write_zone(&dir, "example.com", 3);
+        set_zone_modified(&dir, "example.com", 3);
+        zone_loader.reload(&catalog).expect("reload failed");
+        assert_eq!(served_serial(&catalog, "example.com"), Some(3));
+
+        // a change of size is detected, even if the modification time is unchanged
+        write_zone(&dir, "example.com", 10);
+        set_zone_modified(&dir, "example.com", 3);
+        zone_loader.reload(&catalog).expect("reload failed");
+        assert_eq!(served_serial(&catalog, "example.com"), Some(10));
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_reload_error_keeps_zones() {
+        let dir = test_dir("error");
+        write_zone(&dir, "example.com", 1);
+        write_config(&dir, &["example.com"]);
+
+        let (mut zone_loader, catalog) = load(&dir);
+
+        // the new zone file is missing, nothing may change
+        write_config(&dir, &["example.net"]);
+        assert!(zone_loader.reload(&catalog).is_err());
+        assert_eq!(served_serial(&catalog, "example.com"), Some(1));
+        assert_eq!(served_serial(&catalog, "example.net"), None);
+
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,116 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! The `named-control` binary for sending commands to a running `named`
+//!
+//! ```text
+//! Usage: named-control [options] <command> [zone]
+//!
+//! Commands:
+//!    reload [zone]           Reload the configuration and all changed zones, or only the zone
+//!    status [zone]           Show the status of all zones, or only of the zone
+//!    freeze <zone>           Refuse dynamic updates to the zone
+//!    thaw <zone>             Allow dynamic updates to the zone again
+//!    sign <zone>             Sign the zone again
+//!    notify <zone>           Send NOTIFY messages for the zone to its slaves
+//!    retransfer <zone>       Transfer the slave zone from its masters again
+//!    flush [zone]            Remove the cached answers of all forwarding and recursive zones
+//!    stats                   Show the statistics of the server
+//!
+//! Options:
+//!    -c FILE, --config=FILE  Path to the named configuration file, default is /etc/named.toml
+//!    -s PATH, --socket=PATH  Path to the control socket, overrides the configuration
+//! ```
+
+#[macro_use]
+extern crate clap;
+extern crate trust_dns_server;
+
+use std::path::{Path, PathBuf};
+use std::process;
+
+use clap::Arg;
+
+use trust_dns_server::config::Config;
+use trust_dns_server::server::ControlCommand;
+
+// argument name constants for the CLI options
+const CONFIG_ARG: &str = "config";
+const SOCKET_ARG: &str = "socket";
+const COMMAND_ARG: &str = "command";
+const ZONE_ARG: &str = "zone";
+
+/// Main method for running the named-control client
+pub fn main() {
+    let args = app_from_crate!()
+        .arg(
+            Arg::with_name(CONFIG_ARG)
+                .long(CONFIG_ARG)
+                .short("c")
+                .help("Path to the named configuration file, for the path of the control socket")
+                .value_name("FILE")
+                .default_value("/etc/named.toml"),
+        ).arg(
+            Arg::with_name(SOCKET_ARG)
+                .long(SOCKET_ARG)
+                .short("s")
+                .help("Path to the control socket, overrides the configuration")
+                .value_name("PATH"),
+        ).arg(
+            Arg::with_name(COMMAND_ARG)
+                .help("Command to send: reload, status, freeze, thaw, sign, notify, retransfer, flush or stats")
+                .required(true)
+                .index(1),
+        ).arg(
+            Arg::with_name(ZONE_ARG)
+                .help("Zone the command applies to")
+                .index(2),
+        ).get_matches();
+
+    let command = format!(
+        "{} {}",
+        args.value_of(COMMAND_ARG).expect("command is required"),
+        args.value_of(ZONE_ARG).unwrap_or_default()
+    );
+    let command: ControlCommand = command.parse().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(2)
+    });
+
+    let socket = args.value_of(SOCKET_ARG).map(PathBuf::from).unwrap_or_else(|| {
+        let config_path = args.value_of(CONFIG_ARG).expect("config path should have had default");
+        let config = Config::read_config(config_path.as_ref()).unwrap_or_else(|e| {
+            eprintln!("could not read config {}: {}", config_path, e);
+            process::exit(2)
+        });
+
+        config.get_control_socket().map(PathBuf::from).unwrap_or_else(|| {
+            eprintln!("no control_socket in config {}", config_path);
+            process::exit(2)
+        })
+    });
+
+    match send(&socket, &command) {
+        Ok(output) => print!("{}", output),
+        Err(e) => {
+            eprintln!("{}", e.trim_end());
+            process::exit(1)
+        }
+    }
+}
+
+#[cfg(unix)]
+fn send(socket: &Path, command: &ControlCommand) -> Result<String, String> {
+    trust_dns_server::server::send_command(socket, command)
+        .map_err(|e| format!("could not send command to {}: {}", socket.display(), e))
+        .and_then(|result| result.map_err(|e| format!("error: {}", e)))
+}
+
+#[cfg(not(unix))]
+fn send(_socket: &Path, _command: &ControlCommand) -> Result<String, String> {
+    Err("the control channel is only supported on Unix".to_string())
+}
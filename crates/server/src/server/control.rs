@@ -0,0 +1,323 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Remote control of a running server, similar to `rndc`
+//!
+//! The control channel listens on a Unix domain socket, access to it is restricted by the
+//!  permissions of the socket file, `named` creates it with mode 0600. Each connection carries a single command, as one line of
+//!  text, e.g. `reload example.com.`. The server answers with `ok` or `error` on the first line,
+//!  followed by the output of the command, and then closes the connection.
+
+use std::fmt;
+use std::str::FromStr;
+
+use proto::error::{ProtoError, ProtoResult};
+use trust_dns::rr::Name;
+
+#[cfg(unix)]
+pub use self::unix::{send_command, ControlServer};
+
+/// Maximum length of a command line, longer commands are rejected
+const MAX_COMMAND_LEN: usize = 1024;
+
+/// The outcome of a command, the output to show to the operator or an error message
+pub type ControlResult = Result<String, String>;
+
+/// Commands accepted on the control channel
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ControlCommand {
+    /// Reload the configuration and all changed zones, or only the specified zone
+    Reload(Option<Name>),
+    /// Show the status of all zones, or only of the specified zone
+    Status(Option<Name>),
+    /// Refuse dynamic updates to the zone
+    Freeze(Name),
+    /// Allow dynamic updates to a frozen zone again
+    Thaw(Name),
+    /// Sign the zone again with all of its keys
+    Sign(Name),
+    /// Send NOTIFY messages for the zone to its slaves
+    Notify(Name),
+    /// Transfer the slave zone from its masters again
+    Retransfer(Name),
+    /// Remove the cached answers of all forwarding and recursive zones, or only of the specified zone
+    Flush(Option<Name>),
+    /// Show the statistics of the server
+    Stats,
+}
+
+impl ControlCommand {
+    /// Returns the zone the command applies to, if any
+    pub fn zone(&self) -> Option<&Name> {
+        match *self {
+            ControlCommand::Reload(ref zone)
+            | ControlCommand::Status(ref zone)
+            | ControlCommand::Flush(ref zone) => zone.as_ref(),
+            ControlCommand::Freeze(ref zone)
+            | ControlCommand::Thaw(ref zone)
+            | ControlCommand::Sign(ref zone)
+            | ControlCommand::Notify(ref zone)
+            | ControlCommand::Retransfer(ref zone) => Some(zone),
+            ControlCommand::Stats => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match *self {
+            ControlCommand::Reload(..) => "reload",
+            ControlCommand::Status(..) => "status",
+            ControlCommand::Freeze(..) => "freeze",
+            ControlCommand::Thaw(..) => "thaw",
+            ControlCommand::Sign(..) => "sign",
+            ControlCommand::Notify(..) => "notify",
+            ControlCommand::Retransfer(..) => "retransfer",
+            ControlCommand::Flush(..) => "flush",
+            ControlCommand::Stats => "stats",
+        }
+    }
+}
+
+impl FromStr for ControlCommand {
+    type Err = ProtoError;
+
+    fn from_str(s: &str) -> ProtoResult<Self> {
+        let mut words = s.split_whitespace();
+        let command = words
+            .next()
+            .ok_or_else(|| ProtoError::from("empty command"))?;
+        let zone = words
+            .next()
+            .map(|zone| Name::parse(zone, Some(&Name::root())))
+            .map_or(Ok(None), |zone| zone.map(Some))?;
+
+        if let Some(extra) = words.next() {
+            return Err(format!("unexpected argument: {}", extra).into());
+        }
+
+        let required = |zone: Option<Name>| {
+            zone.ok_or_else(|| ProtoError::from(format!("{} requires a zone", command)))
+        };
+
+        match command.to_lowercase().as_str() {
+            "reload" => Ok(ControlCommand::Reload(zone)),
+            "status" => Ok(ControlCommand::Status(zone)),
+            "freeze" => required(zone).map(ControlCommand::Freeze),
+            "thaw" => required(zone).map(ControlCommand::Thaw),
+            "sign" => required(zone).map(ControlCommand::Sign),
+            "notify" => required(zone).map(ControlCommand::Notify),
+            "retransfer" => required(zone).map(ControlCommand::Retransfer),
+            "flush" => Ok(ControlCommand::Flush(zone)),
+            "stats" if zone.is_none() => Ok(ControlCommand::Stats),
+            "stats" => Err("stats does not take a zone".into()),
+            _ => Err(format!("unknown command: {}", command).into()),
+        }
+    }
+}
+
+impl fmt::Display for ControlCommand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.zone() {
+            Some(zone) => write!(f, "{} {}", self.name(), zone),
+            None => write!(f, "{}", self.name()),
+        }
+    }
+}
+
+/// Executes the commands received on the control channel
+pub trait ControlHandler: Send + Sync + 'static {
+    /// Execute the command
+    ///
+    /// This is called on a thread separate from the one serving requests, and may block, e.g.
+    ///  while loading zones.
+    fn handle_command(&self, command: ControlCommand) -> ControlResult;
+}
+
+fn encode_result(result: &ControlResult) -> String {
+    match *result {
+        Ok(ref output) => format!("ok\n{}", output),
+        Err(ref error) => format!("error\n{}", error),
+    }
+}
+
+fn decode_result(response: &str) -> ProtoResult<ControlResult> {
+    let mut parts = response.splitn(2, '\n');
+    let status = parts.next().unwrap_or_default();
+    let output = parts.next().unwrap_or_default().to_string();
+
+    match status {
+        "ok" => Ok(Ok(output)),
+        "error" => Ok(Err(output)),
+        _ => Err(format!("bad response status: {}", status).into()),
+    }
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::io::{self, BufReader, Read, Write};
+    use std::net::Shutdown;
+    use std::os::unix::net;
+    use std::path::Path;
+    use std::str;
+    use std::sync::Arc;
+    use std::thread;
+
+    use futures::sync::oneshot;
+    use futures::{Future, Stream};
+    use tokio_executor;
+    use tokio_io::{self, AsyncRead};
+    use tokio_uds::{UnixListener, UnixStream};
+
+    use super::*;
+
+    /// Listens for commands on the control channel, and passes them to the `ControlHandler`
+    pub struct ControlServer<H: ControlHandler> {
+        handler: Arc<H>,
+    }
+
+    impl<H: ControlHandler> ControlServer<H> {
+        /// Creates a new ControlServer with the specified handler
+        pub fn new(handler: H) -> Self {
+            ControlServer {
+                handler: Arc::new(handler),
+            }
+        }
+
+        /// Register a Unix domain socket. Should be bound before calling this function.
+        pub fn register_listener(&self, listener: UnixListener) {
+            debug!("registered control socket: {:?}", listener);
+            let handler = self.handler.clone();
+
+            tokio_executor::spawn(
+                listener
+                    .incoming()
+                    .for_each(move |stream| {
+                        tokio_executor::spawn(handle_connection(stream, handler.clone()));
+                        Ok(())
+                    }).map_err(|e| error!("error in control listener: {}", e)),
+            );
+        }
+    }
+
+    fn handle_connection<H: ControlHandler>(
+        stream: UnixStream,
+        handler: Arc<H>,
+    ) -> impl Future<Item = (), Error = ()> {
+        let (reader, writer) = stream.split();
+        // read one byte past the limit, to tell a long command from one at the limit
+        let reader = BufReader::new(reader.take(MAX_COMMAND_LEN as u64 + 1));
+
+        tokio_io::io::read_until(reader, b'\n', Vec::with_capacity(128))
+            .map_err(|e| warn!("error reading control command: {}", e))
+            .and_then(move |(_, line)| {
+                let command = if line.len() > MAX_COMMAND_LEN {
+                    Err(ProtoError::from(format!(
+                        "command is longer than {} bytes",
+                        MAX_COMMAND_LEN
+                    )))
+                } else {
+                    str::from_utf8(&line)
+                        .map_err(|_| ProtoError::from("command is not valid utf-8"))
+                        .and_then(ControlCommand::from_str)
+                };
+
+                let (sender, receiver) = oneshot::channel();
+                match command {
+                    Ok(command) => {
+                        info!("control command: {}", command);
+                        thread::Builder::new()
+                            .name("trust-dns-server:control".to_string())
+                            .spawn(move || {
+                                sender.send(handler.handle_command(command)).ok();
+                            }).expect("could not spawn control thread");
+                    }
+                    Err(e) => {
+                        warn!("bad control command: {}", e);
+                        sender.send(Err(e.to_string())).ok();
+                    }
+                }
+
+                receiver.map_err(|_| error!("control command was canceled"))
+            }).and_then(move |result| {
+                tokio_io::io::write_all(writer, encode_result(&result).into_bytes())
+                    .and_then(|(writer, _)| tokio_io::io::shutdown(writer))
+                    .map(|_| ())
+                    .map_err(|e| warn!("error writing control response: {}", e))
+            })
+    }
+
+    /// Sends the command to the server listening on the socket at `path`, and waits for the result
+    pub fn send_command(path: &Path, command: &ControlCommand) -> io::Result<ControlResult> {
+        let mut stream = net::UnixStream::connect(path)?;
+        writeln!(stream, "{}", command)?;
+        stream.shutdown(Shutdown::Write)?;
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response)?;
+
+        decode_result(&response).map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_command() {
+        let example = Name::from_str("example.com.").unwrap();
+
+        assert_eq!(
+            "reload".parse::<ControlCommand>().unwrap(),
+            ControlCommand::Reload(None)
+        );
+        assert_eq!(
+            "reload example.com".parse::<ControlCommand>().unwrap(),
+            ControlCommand::Reload(Some(example.clone()))
+        );
+        assert_eq!(
+            "FREEZE example.com.\n".parse::<ControlCommand>().unwrap(),
+            ControlCommand::Freeze(example.clone())
+        );
+        assert_eq!(
+            "stats".parse::<ControlCommand>().unwrap(),
+            ControlCommand::Stats
+        );
+
+        assert!("".parse::<ControlCommand>().is_err());
+        assert!("thaw".parse::<ControlCommand>().is_err());
+        assert!("stats example.com.".parse::<ControlCommand>().is_err());
+        assert!("sign example.com. now".parse::<ControlCommand>().is_err());
+        assert!("halt".parse::<ControlCommand>().is_err());
+        assert_eq!(
+            "flush".parse::<ControlCommand>().unwrap(),
+            ControlCommand::Flush(None)
+        );
+        assert_eq!(
+            "notify example.com.".parse::<ControlCommand>().unwrap(),
+            ControlCommand::Notify(example.clone())
+        );
+
+        assert!("retransfer".parse::<ControlCommand>().is_err());
+    }
+
+    #[test]
+    fn test_command_round_trip() {
+        let command = ControlCommand::Retransfer(Name::from_str("example.com.").unwrap());
+        assert_eq!(command.to_string(), "retransfer example.com.");
+        assert_eq!(command.to_string().parse::<ControlCommand>().unwrap(), command);
+    }
+
+    #[test]
+    fn test_result_encoding() {
+        let ok: ControlResult = Ok("zone reloaded\n".to_string());
+        let err: ControlResult = Err("no such zone".to_string());
+
+        assert_eq!(decode_result(&encode_result(&ok)).unwrap(), ok);
+        assert_eq!(decode_result(&encode_result(&err)).unwrap(), err);
+        assert!(decode_result("what\n").is_err());
+    }
+}
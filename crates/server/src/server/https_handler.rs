@@ -19,11 +19,14 @@ use authority::MessageResponse;
 use server::request_handler::RequestHandler;
 use server::response_handler::ResponseHandler;
 use server::server_future;
+use server::{Protocol, QueryLogRequest, QueryLogger};
 
 pub fn h2_handler<T, I>(
     handler: Arc<Mutex<T>>,
+    query_log: Option<Arc<QueryLogger>>,
     io: I,
     src_addr: SocketAddr,
+    dst_addr: SocketAddr,
     dns_hostname: Arc<String>,
 ) -> impl Future<Item = (), Error = io::Error>
 where
@@ -42,7 +45,7 @@ where
                     debug!("Received request: {:#?}", request);
                     let dns_hostname = dns_hostname.clone();
                     let handler = handler.clone();
-                    let responder = HttpsResponseHandle(respond);
+                    let query_log = query_log.clone();
 
                     https_server::message_from(dns_hostname, request)
                         .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("{}", e)))
@@ -50,9 +53,22 @@ where
                             let message = BinDecodable::from_bytes(&bytes)?;
                             debug!("reieved message: {:?}", message);
 
+                            let query_log = query_log.map(|query_log| {
+                                QueryLogRequest::new(
+                                    query_log,
+                                    src_addr,
+                                    dst_addr,
+                                    Protocol::Https,
+                                    &bytes,
+                                )
+                            });
+                            let responder = HttpsResponseHandle(respond, query_log);
+
                             server_future::handle_request(
                                 message,
                                 src_addr,
+                                dst_addr,
+                                Protocol::Https,
                                 handler.clone(),
                                 responder,
                             )
@@ -61,7 +77,10 @@ where
         }).map_err(|e| io::Error::new(io::ErrorKind::Other, format!("error in h2 handler: {}", e)))
 }
 
-struct HttpsResponseHandle(::h2::server::SendResponse<::bytes::Bytes>);
+struct HttpsResponseHandle(
+    ::h2::server::SendResponse<::bytes::Bytes>,
+    Option<QueryLogRequest>,
+);
 
 impl ResponseHandler for HttpsResponseHandle {
     fn send_response(mut self, response: MessageResponse) -> io::Result<()> {
@@ -77,6 +96,9 @@ impl ResponseHandler for HttpsResponseHandle {
             let mut encoder = BinEncoder::new(&mut bytes);
             response.destructive_emit(&mut encoder)?;
         };
+        if let Some(query_log) = self.1.take() {
+            query_log.log_response(&bytes);
+        }
         let bytes = Bytes::from(bytes);
         let response = response::new(bytes.len())?;
 
@@ -0,0 +1,584 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Statistics of the server, exposed in the [Prometheus text format](https://prometheus.io/docs/instrumenting/exposition_formats/)
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Write};
+use std::io::{self, BufReader, Read};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use futures::future::{self, Loop};
+use futures::{Future, Stream};
+use tokio_executor;
+use tokio_io::io::ReadHalf;
+use tokio_io::{self, AsyncRead};
+use tokio_tcp::{TcpListener, TcpStream};
+
+use tokio_timer::Timeout;
+
+use trust_dns::op::ResponseCode;
+#[cfg(feature = "dnssec")]
+use trust_dns::rr::rdata::DNSSECRecordType;
+use trust_dns::rr::{LowerName, RecordType};
+use trust_dns_resolver::recursor::RecursorFuture;
+
+use authority::MessageResponse;
+use server::ResponseHandler;
+
+/// Upper bounds of the buckets of the response latency, in seconds
+const LATENCY_BUCKETS: &[f64] = &[
+    0.0001, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0,
+];
+
+/// Maximum length of the request line or a header of an HTTP request for the metrics
+const MAX_HTTP_LINE_LEN: usize = 8192;
+
+/// Maximum number of headers of an HTTP request for the metrics
+const MAX_HTTP_HEADERS: usize = 100;
+
+/// Time in which an HTTP request for the metrics must be received
+const HTTP_READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Transport protocol on which a request was received
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Protocol {
+    /// DNS over UDP
+    Udp,
+    /// DNS over TCP
+    Tcp,
+    /// DNS over TLS
+    Tls,
+    /// DNS over HTTPS
+    Https,
+}
+
+impl Protocol {
+    /// Returns the lowercase name of the protocol, e.g. `udp`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Protocol::Udp => "udp",
+            Protocol::Tcp => "tcp",
+            Protocol::Tls => "tls",
+            Protocol::Https => "https",
+        }
+    }
+}
+
+impl fmt::Display for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Writes the labels, e.g. `{protocol="udp",type="A"}`, escaping the values
+fn write_labels(out: &mut String, names: &[&str], values: &[String], extra: Option<(&str, &str)>) {
+    let labels = names
+        .iter()
+        .cloned()
+        .zip(values.iter().map(String::as_str))
+        .chain(extra)
+        .collect::<Vec<_>>();
+
+    if labels.is_empty() {
+        return;
+    }
+
+    out.push('{');
+    for (i, (name, value)) in labels.into_iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+
+        let value = value
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n");
+        write!(out, "{}=\"{}\"", name, value).expect("write to string failed");
+    }
+    out.push('}');
+}
+
+fn write_header(out: &mut String, name: &str, help: &str, metric_type: &str) {
+    writeln!(out, "# HELP {} {}", name, help).expect("write to string failed");
+    writeln!(out, "# TYPE {} {}", name, metric_type).expect("write to string failed");
+}
+
+/// A counter, partitioned by the values of its labels
+pub struct CounterVec {
+    name: &'static str,
+    help: &'static str,
+    labels: &'static [&'static str],
+    values: Mutex<BTreeMap<Vec<String>, u64>>,
+}
+
+impl CounterVec {
+    fn new(name: &'static str, help: &'static str, labels: &'static [&'static str]) -> Self {
+        CounterVec {
+            name,
+            help,
+            labels,
+            values: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Increments the counter with the label values, which must be in the order of the labels
+    pub fn inc(&self, values: &[&str]) {
+        debug_assert_eq!(values.len(), self.labels.len());
+        let values = values.iter().map(|v| v.to_string()).collect();
+
+        *self
+            .values
+            .lock()
+            .expect("poisoned lock")
+            .entry(values)
+            .or_insert(0) += 1;
+    }
+
+    /// Returns the value of the counter with the label values
+    pub fn get(&self, values: &[&str]) -> u64 {
+        let values = values.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+
+        self.values
+            .lock()
+            .expect("poisoned lock")
+            .get(&values)
+            .cloned()
+            .unwrap_or(0)
+    }
+
+    fn encode(&self, out: &mut String) {
+        write_header(out, self.name, self.help, "counter");
+
+        for (values, count) in self.values.lock().expect("poisoned lock").iter() {
+            out.push_str(self.name);
+            write_labels(out, self.labels, values, None);
+            writeln!(out, " {}", count).expect("write to string failed");
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+struct Histogram {
+    buckets: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+/// A histogram of durations, partitioned by the values of its labels
+pub struct HistogramVec {
+    name: &'static str,
+    help: &'static str,
+    labels: &'static [&'static str],
+    buckets: &'static [f64],
+    values: Mutex<BTreeMap<Vec<String>, Histogram>>,
+}
+
+impl HistogramVec {
+    fn new(
+        name: &'static str,
+        help: &'static str,
+        labels: &'static [&'static str],
+        buckets: &'static [f64],
+    ) -> Self {
+        HistogramVec {
+            name,
+            help,
+            labels,
+            buckets,
+            values: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Records the duration with the label values, which must be in the order of the labels
+    pub fn observe(&self, values: &[&str], duration: Duration) {
+        debug_assert_eq!(values.len(), self.labels.len());
+        let values = values.iter().map(|v| v.to_string()).collect();
+        let seconds =
+            duration.as_secs() as f64 + f64::from(duration.subsec_nanos()) / 1_000_000_000.0;
+
+        let mut histograms = self.values.lock().expect("poisoned lock");
+        let histogram = histograms.entry(values).or_insert_with(|| Histogram {
+            buckets: vec![0; self.buckets.len()],
+            sum: 0.0,
+            count: 0,
+        });
+
+        if let Some(bucket) = self.buckets.iter().position(|le| seconds <= *le) {
+            histogram.buckets[bucket] += 1;
+        }
+        histogram.sum += seconds;
+        histogram.count += 1;
+    }
+
+    /// Returns the number of observations with the label values
+    pub fn count(&self, values: &[&str]) -> u64 {
+        let values = values.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+
+        self.values
+            .lock()
+            .expect("poisoned lock")
+            .get(&values)
+            .map_or(0, |histogram| histogram.count)
+    }
+
+    fn encode(&self, out: &mut String) {
+        write_header(out, self.name, self.help, "histogram");
+
+        for (values, histogram) in self.values.lock().expect("poisoned lock").iter() {
+            // buckets are cumulative
+            let mut cumulative = 0;
+            for (le, count) in self.buckets.iter().zip(&histogram.buckets) {
+                cumulative += count;
+                write!(out, "{}_bucket", self.name).expect("write to string failed");
+                write_labels(out, self.labels, values, Some(("le", &le.to_string())));
+                writeln!(out, " {}", cumulative).expect("write to string failed");
+            }
+
+            write!(out, "{}_bucket", self.name).expect("write to string failed");
+            write_labels(out, self.labels, values, Some(("le", "+Inf")));
+            writeln!(out, " {}", histogram.count).expect("write to string failed");
+
+            write!(out, "{}_sum", self.name).expect("write to string failed");
+            write_labels(out, self.labels, values, None);
+            writeln!(out, " {}", histogram.sum).expect("write to string failed");
+
+            write!(out, "{}_count", self.name).expect("write to string failed");
+            write_labels(out, self.labels, values, None);
+            writeln!(out, " {}", histogram.count).expect("write to string failed");
+        }
+    }
+}
+
+lazy_static! {
+    static ref METRICS: Metrics = Metrics::new();
+}
+
+/// The registry of all statistics of the server
+pub struct Metrics {
+    /// Requests received, by protocol and op code
+    pub requests: CounterVec,
+    /// Queries received, by protocol and record type
+    pub queries: CounterVec,
+    /// Responses sent, by protocol and response code
+    pub responses: CounterVec,
+    /// Time from receiving a request to sending its response, by protocol
+    pub response_duration: HistogramVec,
+    /// Queries answered from each zone
+    pub zone_queries: CounterVec,
+    /// Dynamic updates of each zone, by result `success` or `failure`
+    pub updates: CounterVec,
+    /// Zone transfers of each zone, by result `success` or `refused`
+    pub zone_transfers: CounterVec,
+    /// Queries of each zone resolved with other name servers, by result `success` or `failure`
+    pub resolved: CounterVec,
+    /// Connections accepted, by protocol
+    pub connections: CounterVec,
+    /// Failed TLS handshakes, by protocol
+    pub handshake_errors: CounterVec,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        Metrics {
+            requests: CounterVec::new(
+                "dns_requests_total",
+                "Requests received",
+                &["protocol", "op_code"],
+            ),
+            queries: CounterVec::new(
+                "dns_queries_total",
+                "Queries received",
+                &["protocol", "type"],
+            ),
+            responses: CounterVec::new(
+                "dns_responses_total",
+                "Responses sent",
+                &["protocol", "rcode"],
+            ),
+            response_duration: HistogramVec::new(
+                "dns_response_duration_seconds",
+                "Time from receiving a request to sending its response",
+                &["protocol"],
+                LATENCY_BUCKETS,
+            ),
+            zone_queries: CounterVec::new(
+                "dns_zone_queries_total",
+                "Queries answered from the zone",
+                &["zone"],
+            ),
+            updates: CounterVec::new(
+                "dns_updates_total",
+                "Dynamic updates of the zone",
+                &["zone", "result"],
+            ),
+            zone_transfers: CounterVec::new(
+                "dns_zone_transfers_total",
+                "Zone transfers of the zone",
+                &["zone", "result"],
+            ),
+            resolved: CounterVec::new(
+                "dns_resolved_total",
+                "Queries of the zone resolved with other name servers",
+                &["zone", "result"],
+            ),
+            connections: CounterVec::new(
+                "dns_connections_total",
+                "Connections accepted",
+                &["protocol"],
+            ),
+            handshake_errors: CounterVec::new(
+                "dns_handshake_errors_total",
+                "Failed TLS handshakes",
+                &["protocol"],
+            ),
+        }
+    }
+
+    /// Returns the statistics of this process
+    pub fn global() -> &'static Self {
+        &METRICS
+    }
+
+    /// Returns all statistics in the Prometheus text format
+    pub fn encode(&self) -> String {
+        let mut out = String::new();
+
+        self.requests.encode(&mut out);
+        self.queries.encode(&mut out);
+        self.responses.encode(&mut out);
+        self.response_duration.encode(&mut out);
+        self.zone_queries.encode(&mut out);
+        self.updates.encode(&mut out);
+        self.zone_transfers.encode(&mut out);
+        self.resolved.encode(&mut out);
+        self.connections.encode(&mut out);
+        self.handshake_errors.encode(&mut out);
+
+        out
+    }
+}
+
+/// Returns the label of the record type of a query, `other` for unknown types
+pub fn record_type_label(record_type: RecordType) -> &'static str {
+    match record_type {
+        RecordType::ZERO | RecordType::Unknown(_) => "other",
+        #[cfg(feature = "dnssec")]
+        RecordType::DNSSEC(DNSSECRecordType::Unknown(_)) => "other",
+        record_type => record_type.into(),
+    }
+}
+
+/// Counts the result of resolving a query of the zone with other name servers
+///
+/// # Arguments
+///
+/// * `zone` - the origin of the zone which resolves the query
+/// * `resolve` - the future of the response to the query
+pub fn count_resolved(zone: &LowerName, resolve: RecursorFuture) -> RecursorFuture {
+    let zone = zone.to_string();
+
+    Box::new(resolve.then(move |result| {
+        let label = if result.is_ok() { "success" } else { "failure" };
+        Metrics::global().resolved.inc(&[&zone, label]);
+        result
+    }))
+}
+
+/// Records the response code and latency of the response to a request
+pub struct MetricsResponseHandle<R: ResponseHandler> {
+    inner: R,
+    protocol: Protocol,
+    received: Instant,
+}
+
+impl<R: ResponseHandler> MetricsResponseHandle<R> {
+    /// Wraps the handler to which the response is sent
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - the handler which sends the response
+    /// * `protocol` - the protocol on which the request was received
+    /// * `received` - the time at which the request was received
+    pub fn new(inner: R, protocol: Protocol, received: Instant) -> Self {
+        MetricsResponseHandle {
+            inner,
+            protocol,
+            received,
+        }
+    }
+}
+
+impl<R: ResponseHandler> ResponseHandler for MetricsResponseHandle<R> {
+    fn send_response(self, response: MessageResponse) -> io::Result<()> {
+        let metrics = Metrics::global();
+        // the high bits of extended response codes are in the EDNS record, these are not known here
+        let response_code = ResponseCode::from(0, response.header().response_code());
+        metrics.responses.inc(&[
+            self.protocol.as_str(),
+            &format!("{:?}", response_code),
+        ]);
+
+        let result = self.inner.send_response(response);
+        metrics
+            .response_duration
+            .observe(&[self.protocol.as_str()], self.received.elapsed());
+
+        result
+    }
+}
+
+/// Serves the statistics over HTTP, `GET /metrics` returns them in the Prometheus text format
+///
+/// # Arguments
+/// * `listener` - a bound TCP socket
+pub fn register_metrics_listener(listener: TcpListener) {
+    debug!("registered metrics: {:?}", listener);
+
+    tokio_executor::spawn(
+        listener
+            .incoming()
+            .for_each(|tcp_stream| {
+                tokio_executor::spawn(handle_http(tcp_stream));
+                Ok(())
+            }).map_err(|e| error!("error in metrics listener: {}", e)),
+    );
+}
+
+fn handle_http(stream: TcpStream) -> impl Future<Item = (), Error = ()> {
+    let (reader, writer) = stream.split();
+    let reader = BufReader::new(reader);
+
+    // read the request line and all headers, up to the empty line
+    let read_request = future::loop_fn(
+        (reader, None, 0),
+        |(reader, request_line, headers): (
+            BufReader<ReadHalf<TcpStream>>,
+            Option<Vec<u8>>,
+            usize,
+        )| {
+            tokio_io::io::read_until(reader.take(MAX_HTTP_LINE_LEN as u64), b'\n', Vec::new())
+                .and_then(move |(reader, line)| {
+                    let reader = reader.into_inner();
+                    let end = line.is_empty() || line == b"\r\n" || line == b"\n";
+
+                    match request_line {
+                        Some(request_line) if end => Ok(Loop::Break(request_line)),
+                        None if line.is_empty() => Err(io::Error::new(
+                            io::ErrorKind::UnexpectedEof,
+                            "empty request",
+                        )),
+                        None => Ok(Loop::Continue((reader, Some(line), 0))),
+                        Some(_) if headers >= MAX_HTTP_HEADERS => Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "too many headers",
+                        )),
+                        request_line => Ok(Loop::Continue((reader, request_line, headers + 1))),
+                    }
+                })
+        },
+    );
+
+    Timeout::new(read_request, HTTP_READ_TIMEOUT)
+        .map_err(|e| {
+            if e.is_elapsed() {
+                io::Error::new(io::ErrorKind::TimedOut, "timed out reading the request")
+            } else {
+                e.into_inner().unwrap_or_else(|| {
+                    io::Error::new(io::ErrorKind::Other, "timer error reading the request")
+                })
+            }
+        })
+        .and_then(move |request_line: Vec<u8>| {
+            let request_line = String::from_utf8_lossy(&request_line);
+            let mut parts = request_line.split_whitespace();
+            let response = match (parts.next(), parts.next()) {
+                (Some("GET"), Some("/metrics")) => http_response(
+                    "200 OK",
+                    "text/plain; version=0.0.4",
+                    &Metrics::global().encode(),
+                ),
+                (Some("GET"), _) => http_response("404 Not Found", "text/plain", "not found\n"),
+                _ => http_response(
+                    "405 Method Not Allowed",
+                    "text/plain",
+                    "method not allowed\n",
+                ),
+            };
+
+            tokio_io::io::write_all(writer, response.into_bytes())
+                .and_then(|(writer, _)| tokio_io::io::shutdown(writer))
+        })
+        .map(|_| ())
+        .map_err(|e| debug!("error serving metrics: {}", e))
+}
+
+fn http_response(status: &str, content_type: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_counter() {
+        let counter = CounterVec::new("test_total", "Test counter", &["protocol", "type"]);
+        counter.inc(&["udp", "A"]);
+        counter.inc(&["udp", "A"]);
+        counter.inc(&["tcp", "quote\"d"]);
+
+        assert_eq!(counter.get(&["udp", "A"]), 2);
+        assert_eq!(counter.get(&["udp", "AAAA"]), 0);
+
+        let mut out = String::new();
+        counter.encode(&mut out);
+        assert_eq!(
+            out,
+            "# HELP test_total Test counter\n\
+             # TYPE test_total counter\n\
+             test_total{protocol=\"tcp\",type=\"quote\\\"d\"} 1\n\
+             test_total{protocol=\"udp\",type=\"A\"} 2\n"
+        );
+    }
+
+    #[test]
+    fn test_record_type_label() {
+        assert_eq!(record_type_label(RecordType::AAAA), "AAAA");
+        assert_eq!(record_type_label(RecordType::Unknown(65280)), "other");
+        assert_eq!(record_type_label(RecordType::ZERO), "other");
+    }
+
+    #[test]
+    fn test_encode_histogram() {
+        let histogram = HistogramVec::new(
+            "test_seconds",
+            "Test histogram",
+            &["protocol"],
+            &[0.001, 0.01],
+        );
+        histogram.observe(&["udp"], Duration::from_micros(500));
+        histogram.observe(&["udp"], Duration::from_millis(5));
+        histogram.observe(&["udp"], Duration::from_secs(1));
+
+        assert_eq!(histogram.count(&["udp"]), 3);
+
+        let mut out = String::new();
+        histogram.encode(&mut out);
+        assert!(out.contains("test_seconds_bucket{protocol=\"udp\",le=\"0.001\"} 1\n"));
+        assert!(out.contains("test_seconds_bucket{protocol=\"udp\",le=\"0.01\"} 2\n"));
+        assert!(out.contains("test_seconds_bucket{protocol=\"udp\",le=\"+Inf\"} 3\n"));
+        assert!(out.contains("test_seconds_count{protocol=\"udp\"} 3\n"));
+    }
+}
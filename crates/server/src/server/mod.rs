@@ -7,13 +7,28 @@
 
 //! `Server` component for hosting a domain name servers operations.
 
+mod control;
 #[cfg(feature = "dns-over-https")]
 mod https_handler;
+mod metrics;
+mod query_log;
+mod rate_limit;
 mod request_handler;
 mod response_handler;
 mod server_future;
 mod timeout_stream;
 
+#[cfg(unix)]
+pub use self::control::{send_command, ControlServer};
+pub use self::control::{ControlCommand, ControlHandler, ControlResult};
+pub use self::metrics::{
+    count_resolved, record_type_label, register_metrics_listener, CounterVec, HistogramVec,
+    Metrics, MetricsResponseHandle, Protocol,
+};
+pub use self::query_log::{QueryLogConfig, QueryLogRequest, QueryLogger};
+pub use self::rate_limit::{
+    RateLimitAction, RateLimitConfig, RateLimitedResponseHandle, RateLimiter,
+};
 pub use self::request_handler::{Request, RequestHandler};
 pub use self::response_handler::{ResponseHandle, ResponseHandler};
 pub use self::server_future::ServerFuture;
@@ -0,0 +1,777 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Logging of every query and its response, as JSON lines and/or [dnstap](http://dnstap.info)
+//!
+//! Entries are passed to a dedicated thread which writes them, the thread serving requests never
+//!  blocks on the log. If the writer can not keep up, entries are dropped and counted.
+
+use std::fmt::Write as FmtWrite;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Read, Write};
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use chrono::{DateTime, Utc};
+
+use proto::op::{Header, OpCode, Query, ResponseCode};
+use proto::serialize::binary::{BinDecodable, BinDecoder};
+
+use server::Protocol;
+
+/// Default number of entries waiting to be written, before new entries are dropped
+const DEFAULT_QUEUE_SIZE: usize = 10_000;
+
+/// Minimum time between attempts to connect to the dnstap collector
+const DNSTAP_RECONNECT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Content type of the Frame Streams carrying dnstap
+const DNSTAP_CONTENT_TYPE: &[u8] = b"protobuf:dnstap.Dnstap";
+
+/// Configuration of the query log
+#[derive(Deserialize, PartialEq, Debug, Clone, Default)]
+pub struct QueryLogConfig {
+    /// File to which each query is appended as one line of JSON, `-` for stdout
+    json_path: Option<String>,
+    /// File to which dnstap frames are written, replaced on start
+    dnstap_path: Option<String>,
+    /// Unix domain socket on which a dnstap collector, e.g. `fstrm_capture`, listens
+    dnstap_socket: Option<String>,
+    /// Identity of the server in dnstap messages
+    dnstap_identity: Option<String>,
+    /// Number of entries waiting to be written, before new entries are dropped
+    queue_size: Option<usize>,
+}
+
+impl QueryLogConfig {
+    /// Creates a configuration writing JSON lines to the file at `path`, `-` for stdout
+    pub fn json(path: String) -> Self {
+        QueryLogConfig {
+            json_path: Some(path),
+            ..QueryLogConfig::default()
+        }
+    }
+
+    /// Creates a configuration writing dnstap frames to the file at `path`
+    pub fn dnstap(path: String) -> Self {
+        QueryLogConfig {
+            dnstap_path: Some(path),
+            ..QueryLogConfig::default()
+        }
+    }
+
+    /// File to which each query is appended as one line of JSON, `-` for stdout
+    pub fn get_json_path(&self) -> Option<&Path> {
+        self.json_path.as_ref().map(Path::new)
+    }
+
+    /// File to which dnstap frames are written
+    pub fn get_dnstap_path(&self) -> Option<&Path> {
+        self.dnstap_path.as_ref().map(Path::new)
+    }
+
+    /// Unix domain socket on which a dnstap collector listens
+    pub fn get_dnstap_socket(&self) -> Option<&Path> {
+        self.dnstap_socket.as_ref().map(Path::new)
+    }
+
+    /// Identity of the server in dnstap messages, none by default
+    pub fn get_dnstap_identity(&self) -> Option<&str> {
+        self.dnstap_identity.as_ref().map(String::as_str)
+    }
+
+    /// Number of entries waiting to be written, before new entries are dropped
+    pub fn get_queue_size(&self) -> usize {
+        self.queue_size.unwrap_or(DEFAULT_QUEUE_SIZE)
+    }
+}
+
+/// A query and its response, as passed to the writer
+#[derive(Clone, Debug)]
+struct QueryLogEntry {
+    src: SocketAddr,
+    dst: SocketAddr,
+    protocol: Protocol,
+    id: u16,
+    op_code: OpCode,
+    query: Option<Query>,
+    query_time: SystemTime,
+    response_time: SystemTime,
+    latency: Duration,
+    response_header: Header,
+    /// The wire format of the query, only kept for dnstap
+    query_message: Option<Vec<u8>>,
+    /// The wire format of the response, only kept for dnstap
+    response_message: Option<Vec<u8>>,
+}
+
+/// Writes the log of queries on a separate thread
+pub struct QueryLogger {
+    sender: Mutex<SyncSender<QueryLogEntry>>,
+    keep_messages: bool,
+    dropped: AtomicUsize,
+}
+
+impl QueryLogger {
+    /// Opens the outputs of the configuration and starts the thread writing to them
+    pub fn try_from_config(config: &QueryLogConfig) -> io::Result<Self> {
+        let json = match config.get_json_path() {
+            Some(path) if path == Path::new("-") => {
+                Some(Box::new(io::stdout()) as Box<dyn Write + Send>)
+            }
+            Some(path) => Some(Box::new(BufWriter::new(
+                OpenOptions::new().create(true).append(true).open(path)?,
+            )) as Box<dyn Write + Send>),
+            None => None,
+        };
+
+        let dnstap_output = match (config.get_dnstap_path(), config.get_dnstap_socket()) {
+            (Some(_), Some(_)) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "only one of dnstap_path and dnstap_socket may be set",
+                ))
+            }
+            (Some(path), None) => Some(DnstapOutput::File(path.to_path_buf())),
+            (None, Some(path)) => Some(DnstapOutput::Socket(path.to_path_buf())),
+            (None, None) => None,
+        };
+        let keep_messages = dnstap_output.is_some();
+        let dnstap = dnstap_output.map(|output| {
+            DnstapWriter::new(output, config.get_dnstap_identity().map(str::to_string))
+        });
+
+        if json.is_none() && dnstap.is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "query log requires json_path, dnstap_path or dnstap_socket",
+            ));
+        }
+
+        let (sender, receiver) = mpsc::sync_channel(config.get_queue_size());
+        let mut writer = QueryLogWriter { json, dnstap };
+        // create the file now, so that errors are reported on start
+        if let Some(ref mut dnstap) = writer.dnstap {
+            if let DnstapOutput::File(..) = dnstap.output {
+                dnstap.connect()?;
+            }
+        }
+
+        thread::Builder::new()
+            .name("trust-dns-server:query-log".to_string())
+            .spawn(move || writer.run(&receiver))?;
+
+        Ok(QueryLogger {
+            sender: Mutex::new(sender),
+            keep_messages,
+            dropped: AtomicUsize::new(0),
+        })
+    }
+
+    /// Number of entries dropped because the writer could not keep up
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    fn log(&self, entry: QueryLogEntry) {
+        let result = self.sender.lock().expect("poisoned lock").try_send(entry);
+
+        match result {
+            Ok(()) => (),
+            Err(TrySendError::Full(..)) => {
+                if self.dropped.fetch_add(1, Ordering::Relaxed) == 0 {
+                    warn!("query log can not keep up, dropping entries");
+                }
+            }
+            Err(TrySendError::Disconnected(..)) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// Owned by the writer thread
+struct QueryLogWriter {
+    json: Option<Box<dyn Write + Send>>,
+    dnstap: Option<DnstapWriter>,
+}
+
+impl QueryLogWriter {
+    fn run(&mut self, receiver: &Receiver<QueryLogEntry>) {
+        // block for the next entry, then write all that are waiting before flushing
+        while let Ok(entry) = receiver.recv() {
+            self.write(&entry);
+            while let Ok(entry) = receiver.try_recv() {
+                self.write(&entry);
+            }
+            self.flush();
+        }
+
+        if let Some(ref mut dnstap) = self.dnstap {
+            dnstap.finish();
+        }
+    }
+
+    fn write(&mut self, entry: &QueryLogEntry) {
+        if let Some(ref mut json) = self.json {
+            if let Err(e) = json.write_all(json_line(entry).as_bytes()) {
+                warn!("error writing query log: {}", e);
+            }
+        }
+
+        if let Some(ref mut dnstap) = self.dnstap {
+            dnstap.write(entry);
+        }
+    }
+
+    fn flush(&mut self) {
+        if let Some(ref mut json) = self.json {
+            if let Err(e) = json.flush() {
+                warn!("error writing query log: {}", e);
+            }
+        }
+
+        if let Some(ref mut dnstap) = self.dnstap {
+            dnstap.flush();
+        }
+    }
+}
+
+/// Formats the entry as one line of JSON, including the trailing newline
+fn json_line(entry: &QueryLogEntry) -> String {
+    let header = &entry.response_header;
+    // the high bits of extended response codes are in the EDNS record, these are not known here
+    let response_code = ResponseCode::from(0, header.response_code());
+
+    let mut flags = Vec::new();
+    for &(set, flag) in &[
+        (true, "qr"),
+        (header.authoritative(), "aa"),
+        (header.truncated(), "tc"),
+        (header.recursion_desired(), "rd"),
+        (header.recursion_available(), "ra"),
+        (header.authentic_data(), "ad"),
+        (header.checking_disabled(), "cd"),
+    ] {
+        if set {
+            flags.push(flag);
+        }
+    }
+
+    let time: DateTime<Utc> = DateTime::from(entry.query_time);
+    let mut line = String::with_capacity(320);
+    write!(
+        line,
+        "{{\"time\":\"{}\",\"client\":\"{}\",\"client_port\":{},\"server\":\"{}\",\"server_port\":{},\"protocol\":\"{}\",\"id\":{},\"op_code\":\"{:?}\"",
+        time.to_rfc3339(),
+        entry.src.ip(),
+        entry.src.port(),
+        entry.dst.ip(),
+        entry.dst.port(),
+        entry.protocol,
+        entry.id,
+        entry.op_code,
+    ).expect("write to String failed");
+
+    if let Some(ref query) = entry.query {
+        write!(
+            line,
+            ",\"name\":\"{}\",\"type\":\"{}\",\"class\":\"{}\"",
+            json_escape(&query.name().to_string()),
+            query.query_type(),
+            query.query_class(),
+        ).expect("write to String failed");
+    }
+
+    writeln!(
+        line,
+        ",\"rcode\":\"{:?}\",\"flags\":\"{}\",\"answers\":{},\"authorities\":{},\"additionals\":{},\"latency_us\":{}}}",
+        response_code,
+        flags.join(" "),
+        header.answer_count(),
+        header.name_server_count(),
+        header.additional_count(),
+        entry.latency.as_secs() * 1_000_000 + u64::from(entry.latency.subsec_micros()),
+    ).expect("write to String failed");
+
+    line
+}
+
+/// Escapes the string for use inside a JSON string
+fn json_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if (c as u32) < 0x20 => {
+                write!(escaped, "\\u{:04x}", c as u32).expect("write to String failed")
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Where the dnstap frames are written to
+enum DnstapOutput {
+    File(PathBuf),
+    Socket(PathBuf),
+}
+
+/// Writes dnstap messages in a Frame Stream, reconnecting to the collector after errors
+struct DnstapWriter {
+    output: DnstapOutput,
+    identity: Option<String>,
+    stream: Option<Box<dyn Write + Send>>,
+    last_connect: Option<Instant>,
+}
+
+impl DnstapWriter {
+    fn new(output: DnstapOutput, identity: Option<String>) -> Self {
+        DnstapWriter {
+            output,
+            identity,
+            stream: None,
+            last_connect: None,
+        }
+    }
+
+    /// Opens the output and starts the Frame Stream
+    fn connect(&mut self) -> io::Result<()> {
+        self.last_connect = Some(Instant::now());
+
+        let mut stream: Box<dyn Write + Send> = match self.output {
+            DnstapOutput::File(ref path) => Box::new(BufWriter::new(File::create(path)?)),
+            DnstapOutput::Socket(ref path) => Box::new(BufWriter::new(connect_socket(path)?)),
+        };
+        stream.write_all(&control_frame(FSTRM_CONTROL_START, true))?;
+
+        self.stream = Some(stream);
+        Ok(())
+    }
+
+    fn write(&mut self, entry: &QueryLogEntry) {
+        if self.stream.is_none() {
+            let retry = self
+                .last_connect
+                .map_or(true, |last| last.elapsed() >= DNSTAP_RECONNECT_INTERVAL);
+            if !retry {
+                return;
+            }
+
+            if let Err(e) = self.connect() {
+                warn!("error connecting to dnstap output: {}", e);
+                return;
+            }
+        }
+
+        let identity = self.identity.as_ref().map(String::as_bytes);
+        let query = data_frame(&dnstap_message(identity, entry, false));
+        let response = data_frame(&dnstap_message(identity, entry, true));
+
+        let result = self
+            .stream
+            .as_mut()
+            .expect("dnstap stream is connected")
+            .write_all(&query)
+            .and_then(|_| self.stream.as_mut().unwrap().write_all(&response));
+        if let Err(e) = result {
+            warn!("error writing dnstap: {}", e);
+            self.stream = None;
+        }
+    }
+
+    fn flush(&mut self) {
+        let result = self.stream.as_mut().map_or(Ok(()), |stream| stream.flush());
+        if let Err(e) = result {
+            warn!("error writing dnstap: {}", e);
+            self.stream = None;
+        }
+    }
+
+    /// Stops the Frame Stream
+    fn finish(&mut self) {
+        if let Some(mut stream) = self.stream.take() {
+            stream
+                .write_all(&control_frame(FSTRM_CONTROL_STOP, false))
+                .and_then(|_| stream.flush())
+                .unwrap_or_else(|e| warn!("error writing dnstap: {}", e));
+        }
+    }
+}
+
+/// Connects to the collector and performs the bidirectional Frame Streams handshake, up to START
+#[cfg(unix)]
+fn connect_socket(path: &Path) -> io::Result<::std::os::unix::net::UnixStream> {
+    use std::os::unix::net::UnixStream;
+
+    let mut stream = UnixStream::connect(path)?;
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    stream.write_all(&control_frame(FSTRM_CONTROL_READY, true))?;
+
+    // the ACCEPT frame: escape, length of the control frame, then the control frame
+    let mut header = [0u8; 8];
+    stream.read_exact(&mut header)?;
+    let len = read_u32(&header[4..]) as usize;
+    if read_u32(&header[..4]) != 0 || len < 4 || len > 512 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "bad dnstap handshake",
+        ));
+    }
+    let mut control = vec![0u8; len];
+    stream.read_exact(&mut control)?;
+    if read_u32(&control) != FSTRM_CONTROL_ACCEPT {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "dnstap collector did not accept the stream",
+        ));
+    }
+
+    stream.set_read_timeout(None)?;
+    Ok(stream)
+}
+
+#[cfg(not(unix))]
+fn connect_socket(_path: &Path) -> io::Result<File> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "dnstap sockets are only supported on Unix",
+    ))
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    (u32::from(bytes[0]) << 24)
+        | (u32::from(bytes[1]) << 16)
+        | (u32::from(bytes[2]) << 8)
+        | u32::from(bytes[3])
+}
+
+// Frame Streams control frame types
+const FSTRM_CONTROL_ACCEPT: u32 = 0x01;
+const FSTRM_CONTROL_START: u32 = 0x02;
+const FSTRM_CONTROL_STOP: u32 = 0x03;
+const FSTRM_CONTROL_READY: u32 = 0x04;
+const FSTRM_CONTROL_FIELD_CONTENT_TYPE: u32 = 0x01;
+
+/// A Frame Streams data frame, the length of the payload followed by the payload
+fn data_frame(payload: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(payload.len() + 4);
+    frame.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    frame.extend_from_slice(payload);
+    frame
+}
+
+/// A Frame Streams control frame, escaped by a zero length
+fn control_frame(control_type: u32, content_type: bool) -> Vec<u8> {
+    let mut control = Vec::with_capacity(12 + DNSTAP_CONTENT_TYPE.len());
+    control.extend_from_slice(&control_type.to_be_bytes());
+    if content_type {
+        control.extend_from_slice(&FSTRM_CONTROL_FIELD_CONTENT_TYPE.to_be_bytes());
+        control.extend_from_slice(&(DNSTAP_CONTENT_TYPE.len() as u32).to_be_bytes());
+        control.extend_from_slice(DNSTAP_CONTENT_TYPE);
+    }
+
+    let mut frame = vec![0u8; 4];
+    frame.extend_from_slice(&(control.len() as u32).to_be_bytes());
+    frame.extend_from_slice(&control);
+    frame
+}
+
+// dnstap Message types
+const DNSTAP_AUTH_QUERY: u64 = 1;
+const DNSTAP_AUTH_RESPONSE: u64 = 2;
+
+/// Encodes the query or the response as a `dnstap.Dnstap` protobuf message
+fn dnstap_message(identity: Option<&[u8]>, entry: &QueryLogEntry, response: bool) -> Vec<u8> {
+    let mut message = Vec::with_capacity(128);
+
+    // Message.type
+    let message_type = if response {
+        DNSTAP_AUTH_RESPONSE
+    } else {
+        DNSTAP_AUTH_QUERY
+    };
+    put_varint_field(&mut message, 1, message_type);
+    // Message.socket_family, INET or INET6
+    let family = if entry.src.is_ipv4() { 1 } else { 2 };
+    put_varint_field(&mut message, 2, family);
+    // Message.socket_protocol, UDP, TCP, DOT or DOH
+    let protocol = match entry.protocol {
+        Protocol::Udp => 1,
+        Protocol::Tcp => 2,
+        Protocol::Tls => 3,
+        Protocol::Https => 4,
+    };
+    put_varint_field(&mut message, 3, protocol);
+    // Message.query_address and response_address
+    put_bytes_field(&mut message, 4, &ip_octets(entry.src.ip()));
+    put_bytes_field(&mut message, 5, &ip_octets(entry.dst.ip()));
+    // Message.query_port and response_port
+    put_varint_field(&mut message, 6, u64::from(entry.src.port()));
+    put_varint_field(&mut message, 7, u64::from(entry.dst.port()));
+    // Message.query_time_sec and query_time_nsec
+    let (secs, nanos) = unix_time(entry.query_time);
+    put_varint_field(&mut message, 8, secs);
+    put_fixed32_field(&mut message, 9, nanos);
+
+    if response {
+        // Message.response_time_sec, response_time_nsec and response_message
+        let (secs, nanos) = unix_time(entry.response_time);
+        put_varint_field(&mut message, 12, secs);
+        put_fixed32_field(&mut message, 13, nanos);
+        if let Some(ref bytes) = entry.response_message {
+            put_bytes_field(&mut message, 14, bytes);
+        }
+    } else if let Some(ref bytes) = entry.query_message {
+        // Message.query_message
+        put_bytes_field(&mut message, 10, bytes);
+    }
+
+    let mut dnstap = Vec::with_capacity(message.len() + 64);
+    // Dnstap.identity and version
+    if let Some(identity) = identity {
+        put_bytes_field(&mut dnstap, 1, identity);
+    }
+    put_bytes_field(
+        &mut dnstap,
+        2,
+        format!("trust-dns {}", ::trust_dns::version()).as_bytes(),
+    );
+    // Dnstap.message and type, MESSAGE
+    put_bytes_field(&mut dnstap, 14, &message);
+    put_varint_field(&mut dnstap, 15, 1);
+
+    dnstap
+}
+
+fn ip_octets(ip: IpAddr) -> Vec<u8> {
+    match ip {
+        IpAddr::V4(ip) => ip.octets().to_vec(),
+        IpAddr::V6(ip) => ip.octets().to_vec(),
+    }
+}
+
+fn unix_time(time: SystemTime) -> (u64, u32) {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    (since_epoch.as_secs(), since_epoch.subsec_nanos())
+}
+
+fn put_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn put_varint_field(buf: &mut Vec<u8>, field: u64, value: u64) {
+    put_varint(buf, field << 3);
+    put_varint(buf, value);
+}
+
+fn put_fixed32_field(buf: &mut Vec<u8>, field: u64, value: u32) {
+    put_varint(buf, field << 3 | 5);
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_bytes_field(buf: &mut Vec<u8>, field: u64, bytes: &[u8]) {
+    put_varint(buf, field << 3 | 2);
+    put_varint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+/// A request whose response is logged by the handler which encodes and sends the response
+pub struct QueryLogRequest {
+    logger: Arc<QueryLogger>,
+    src: SocketAddr,
+    dst: SocketAddr,
+    protocol: Protocol,
+    query_time: SystemTime,
+    received: Instant,
+    /// The wire format of the query, only kept for dnstap
+    query_message: Option<Vec<u8>>,
+}
+
+impl QueryLogRequest {
+    /// Starts the log entry of a request, as it is received
+    ///
+    /// # Arguments
+    ///
+    /// * `logger` - the log to which the request and response are written
+    /// * `src` - the address of the client
+    /// * `dst` - the address on which the request was received
+    /// * `protocol` - the protocol on which the request was received
+    /// * `query_message` - the request as received
+    pub fn new(
+        logger: Arc<QueryLogger>,
+        src: SocketAddr,
+        dst: SocketAddr,
+        protocol: Protocol,
+        query_message: &[u8],
+    ) -> Self {
+        let query_message = if logger.keep_messages {
+            Some(query_message.to_vec())
+        } else {
+            None
+        };
+
+        QueryLogRequest {
+            logger,
+            src,
+            dst,
+            protocol,
+            query_time: SystemTime::now(),
+            received: Instant::now(),
+            query_message,
+        }
+    }
+
+    /// Logs the response as it is sent to the client, in wire format
+    pub fn log_response(self, response: &[u8]) {
+        let mut decoder = BinDecoder::new(response);
+        let header = match Header::read(&mut decoder) {
+            Ok(header) => header,
+            Err(e) => {
+                debug!("response not logged, could not be decoded: {}", e);
+                return;
+            }
+        };
+        let query = if header.query_count() > 0 {
+            Query::read(&mut decoder).ok()
+        } else {
+            None
+        };
+
+        let entry = QueryLogEntry {
+            src: self.src,
+            dst: self.dst,
+            protocol: self.protocol,
+            id: header.id(),
+            op_code: header.op_code(),
+            query,
+            query_time: self.query_time,
+            response_time: SystemTime::now(),
+            latency: self.received.elapsed(),
+            response_header: header,
+            query_message: self.query_message,
+            response_message: if self.logger.keep_messages {
+                Some(response.to_vec())
+            } else {
+                None
+            },
+        };
+
+        self.logger.log(entry);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use proto::op::MessageType;
+    use proto::rr::{DNSClass, Name, RecordType};
+
+    use super::*;
+
+    fn entry() -> QueryLogEntry {
+        let mut header = Header::new();
+        header
+            .set_message_type(MessageType::Response)
+            .set_authoritative(true)
+            .set_recursion_desired(true)
+            .set_answer_count(1);
+
+        QueryLogEntry {
+            src: "192.0.2.1:53000".parse().unwrap(),
+            dst: "198.51.100.1:53".parse().unwrap(),
+            protocol: Protocol::Udp,
+            id: 1234,
+            op_code: OpCode::Query,
+            query: Some(Query::query(
+                Name::from_str("www.example.com.").unwrap(),
+                RecordType::A,
+            )),
+            query_time: UNIX_EPOCH + Duration::new(1_500_000_000, 250_000_000),
+            response_time: UNIX_EPOCH + Duration::new(1_500_000_000, 251_500_000),
+            latency: Duration::from_micros(1500),
+            response_header: header,
+            query_message: Some(vec![1, 2, 3]),
+            response_message: Some(vec![4, 5]),
+        }
+    }
+
+    #[test]
+    fn test_json_line() {
+        let line = json_line(&entry());
+
+        assert_eq!(
+            line,
+            "{\"time\":\"2017-07-14T02:40:00.250+00:00\",\"client\":\"192.0.2.1\",\"client_port\":53000,\
+             \"server\":\"198.51.100.1\",\"server_port\":53,\"protocol\":\"udp\",\"id\":1234,\
+             \"op_code\":\"Query\",\"name\":\"www.example.com.\",\"type\":\"A\",\"class\":\"IN\",\
+             \"rcode\":\"NoError\",\"flags\":\"qr aa rd\",\"answers\":1,\"authorities\":0,\
+             \"additionals\":0,\"latency_us\":1500}\n"
+        );
+        assert_eq!(DNSClass::IN.to_string(), "IN");
+    }
+
+    #[test]
+    fn test_json_escape() {
+        assert_eq!(json_escape("a\"b\\c\n"), "a\\\"b\\\\c\\u000a");
+    }
+
+    #[test]
+    fn test_frames() {
+        assert_eq!(data_frame(&[0xAA, 0xBB]), vec![0, 0, 0, 2, 0xAA, 0xBB]);
+        assert_eq!(
+            control_frame(FSTRM_CONTROL_STOP, false),
+            vec![0, 0, 0, 0, 0, 0, 0, 4, 0, 0, 0, 3]
+        );
+
+        let start = control_frame(FSTRM_CONTROL_START, true);
+        assert_eq!(&start[..4], &[0, 0, 0, 0]);
+        assert_eq!(read_u32(&start[4..]) as usize, start.len() - 8);
+        assert_eq!(read_u32(&start[8..]), FSTRM_CONTROL_START);
+        assert_eq!(read_u32(&start[12..]), FSTRM_CONTROL_FIELD_CONTENT_TYPE);
+        assert_eq!(&start[20..], DNSTAP_CONTENT_TYPE);
+    }
+
+    #[test]
+    fn test_varint() {
+        let mut buf = Vec::new();
+        put_varint(&mut buf, 1);
+        put_varint(&mut buf, 300);
+        assert_eq!(buf, vec![0x01, 0xAC, 0x02]);
+    }
+
+    #[test]
+    fn test_dnstap_message() {
+        let query = dnstap_message(Some(b"ns1"), &entry(), false);
+
+        // Dnstap.identity
+        assert_eq!(&query[..5], &[0x0A, 3, b'n', b's', b'1']);
+        // Dnstap.type is MESSAGE
+        assert_eq!(&query[query.len() - 2..], &[0x78, 1]);
+        // Message.query_message is the last field before Dnstap.type
+        assert_eq!(&query[query.len() - 7..query.len() - 2], &[0x52, 3, 1, 2, 3]);
+
+        let response = dnstap_message(None, &entry(), true);
+        // Message.response_message is the last field before Dnstap.type
+        assert_eq!(
+            &response[response.len() - 6..],
+            &[0x72, 2, 4, 5, 0x78, 1]
+        );
+    }
+}
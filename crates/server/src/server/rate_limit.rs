@@ -0,0 +1,625 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Response Rate Limiting (RRL) for UDP responses
+//!
+//! UDP responses can be sent to spoofed source addresses, which allows a server to be used to
+//!  reflect and amplify traffic towards a victim. Rate limiting the identical responses sent to a
+//!  single network makes that far less useful, while legitimate clients which are limited can
+//!  still retry over TCP when sent a truncated response (see `slip`).
+
+use std::io;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use lru_cache::LruCache;
+use proto::error::ProtoResult;
+use proto::op::ResponseCode;
+use proto::rr::RecordType;
+use trust_dns::rr::LowerName;
+
+use authority::{MessageResponse, NetworkPrefix};
+use server::ResponseHandler;
+
+static DEFAULT_SLIP: u32 = 2;
+static DEFAULT_IPV4_PREFIX_LENGTH: u8 = 24;
+static DEFAULT_IPV6_PREFIX_LENGTH: u8 = 56;
+static DEFAULT_MAX_TABLE_SIZE: usize = 100_000;
+
+/// Configuration for Response Rate Limiting
+///
+/// Rates are per client network and response, a rate of `0` disables limiting for that class of
+///  response.
+#[derive(Deserialize, PartialEq, Debug, Default)]
+pub struct RateLimitConfig {
+    /// Identical responses per second allowed to a client network
+    pub responses_per_second: Option<u32>,
+    /// NXDOMAIN responses per second for a zone allowed to a client network, defaults to `responses_per_second`
+    pub nxdomains_per_second: Option<u32>,
+    /// Error responses per second allowed to a client network, defaults to `responses_per_second`
+    pub errors_per_second: Option<u32>,
+    /// Every `slip` limited response is sent as a truncated response, `0` drops all, default is 2
+    pub slip: Option<u32>,
+    /// Prefix length which groups IPv4 clients into a network, default is 24
+    pub ipv4_prefix_length: Option<u8>,
+    /// Prefix length which groups IPv6 clients into a network, default is 56
+    pub ipv6_prefix_length: Option<u8>,
+    /// Only log responses which would be limited, but send them all
+    #[serde(default)]
+    pub log_only: bool,
+    /// Client networks which are never limited
+    #[serde(default)]
+    pub exempt_clients: Vec<String>,
+    /// Maximum number of tracked client network and response pairs, once it is reached the least
+    ///  recently used pair is forgotten if it is no longer limited, otherwise responses of new pairs
+    ///  share a single limit
+    pub max_table_size: Option<usize>,
+}
+
+impl RateLimitConfig {
+    /// Identical responses per second allowed to a client network
+    pub fn get_responses_per_second(&self) -> u32 {
+        self.responses_per_second.unwrap_or(0)
+    }
+
+    /// NXDOMAIN responses per second for a zone allowed to a client network
+    pub fn get_nxdomains_per_second(&self) -> u32 {
+        self.nxdomains_per_second
+            .unwrap_or_else(|| self.get_responses_per_second())
+    }
+
+    /// Error responses per second allowed to a client network
+    pub fn get_errors_per_second(&self) -> u32 {
+        self.errors_per_second
+            .unwrap_or_else(|| self.get_responses_per_second())
+    }
+
+    /// Every `slip` limited response is sent truncated, `0` for never
+    pub fn get_slip(&self) -> u32 {
+        self.slip.unwrap_or(DEFAULT_SLIP)
+    }
+
+    /// Prefix length which groups IPv4 clients into a network
+    pub fn get_ipv4_prefix_length(&self) -> u8 {
+        self.ipv4_prefix_length
+            .unwrap_or(DEFAULT_IPV4_PREFIX_LENGTH)
+    }
+
+    /// Prefix length which groups IPv6 clients into a network
+    pub fn get_ipv6_prefix_length(&self) -> u8 {
+        self.ipv6_prefix_length
+            .unwrap_or(DEFAULT_IPV6_PREFIX_LENGTH)
+    }
+
+    /// Only log responses which would be limited
+    pub fn is_log_only(&self) -> bool {
+        self.log_only
+    }
+
+    /// Client networks which are never limited
+    pub fn get_exempt_clients(&self) -> ProtoResult<Vec<NetworkPrefix>> {
+        self.exempt_clients.iter().map(|s| s.parse()).collect()
+    }
+
+    /// Maximum number of tracked client network and response pairs
+    pub fn get_max_table_size(&self) -> usize {
+        self.max_table_size.unwrap_or(DEFAULT_MAX_TABLE_SIZE)
+    }
+}
+
+/// What should be done with a response
+#[derive(Debug, PartialEq, Eq)]
+pub enum RateLimitAction {
+    /// Send the response as is
+    Send,
+    /// Do not send any response
+    Drop,
+    /// Send a truncated response instead, which asks the client to retry over TCP
+    Slip,
+}
+
+/// The identity of a response, identical responses to a network share a rate limit
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum ResponseKind {
+    /// Answers, or no data, for the name and type
+    Response(LowerName, RecordType),
+    /// Non-existent names in the zone
+    NXDomain(LowerName),
+    /// All errors of a response code
+    Error(ResponseCode),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct RateLimitKey {
+    client: NetworkPrefix,
+    kind: ResponseKind,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    rate: f64,
+    tokens: f64,
+    last_update: Instant,
+    limited: u32,
+}
+
+impl Bucket {
+    fn new(rate: f64, now: Instant) -> Self {
+        Bucket {
+            rate,
+            tokens: rate,
+            last_update: now,
+            limited: 0,
+        }
+    }
+
+    /// Returns the tokens of the bucket at `now`, at most `rate`
+    fn tokens_at(&self, now: Instant) -> f64 {
+        let elapsed = now.duration_since(self.last_update);
+        let elapsed = elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) / 1e9;
+        (self.tokens + elapsed * self.rate).min(self.rate)
+    }
+
+    /// True if the bucket has refilled, forgetting it would not change any limit
+    fn is_full(&self, now: Instant) -> bool {
+        self.tokens_at(now) >= self.rate
+    }
+}
+
+/// The tracked client network and response pairs
+struct Buckets {
+    table: LruCache<RateLimitKey, Bucket>,
+    /// shared by the pairs which are not tracked, while the table is full of limited pairs
+    overflow: Bucket,
+}
+
+impl Buckets {
+    /// Returns the bucket of the key, which is created with `rate` if it is not tracked yet
+    ///
+    /// When the table is full, the least recently used bucket is only forgotten if it has refilled.
+    ///  The bucket of a limited client is kept, otherwise a flood from many spoofed sources would
+    ///  reset it, and the response shares the overflow bucket instead.
+    fn get_mut(&mut self, key: &RateLimitKey, rate: f64, now: Instant) -> &mut Bucket {
+        if !self.table.contains_key(key) {
+            if self.table.len() >= self.table.capacity() {
+                let is_lru_full = self
+                    .table
+                    .iter()
+                    .next()
+                    .map_or(true, |(_, bucket)| bucket.is_full(now));
+                if !is_lru_full {
+                    self.overflow.rate = rate;
+                    return &mut self.overflow;
+                }
+
+                self.table.remove_lru();
+            }
+
+            self.table.insert(key.clone(), Bucket::new(rate, now));
+        }
+
+        self.table.get_mut(key).expect("bucket was inserted")
+    }
+}
+
+/// Token buckets for rate limiting responses, keyed by client network and response identity
+pub struct RateLimiter {
+    responses_per_second: u32,
+    nxdomains_per_second: u32,
+    errors_per_second: u32,
+    slip: u32,
+    ipv4_prefix_length: u8,
+    ipv6_prefix_length: u8,
+    log_only: bool,
+    exempt_clients: Vec<NetworkPrefix>,
+    buckets: Mutex<Buckets>,
+}
+
+impl RateLimiter {
+    /// Constructs a new RateLimiter from the configuration
+    pub fn try_from_config(config: &RateLimitConfig) -> ProtoResult<Self> {
+        Ok(RateLimiter {
+            responses_per_second: config.get_responses_per_second(),
+            nxdomains_per_second: config.get_nxdomains_per_second(),
+            errors_per_second: config.get_errors_per_second(),
+            slip: config.get_slip(),
+            ipv4_prefix_length: config.get_ipv4_prefix_length(),
+            ipv6_prefix_length: config.get_ipv6_prefix_length(),
+            log_only: config.is_log_only(),
+            exempt_clients: config.get_exempt_clients()?,
+            // at least the bucket being checked must fit
+            buckets: Mutex::new(Buckets {
+                table: LruCache::new(config.get_max_table_size().max(1)),
+                overflow: Bucket::new(0.0, Instant::now()),
+            }),
+        })
+    }
+
+    /// Decide what to do with the response to be sent to the client
+    pub fn check(&self, client: IpAddr, response: &MessageResponse) -> RateLimitAction {
+        self.check_at(client, response, Instant::now())
+    }
+
+    fn check_at(
+        &self,
+        client: IpAddr,
+        response: &MessageResponse,
+        now: Instant,
+    ) -> RateLimitAction {
+        if self.exempt_clients.iter().any(|p| p.contains(client)) {
+            return RateLimitAction::Send;
+        }
+
+        let kind = response_kind(response);
+
+        let rate = match kind {
+            ResponseKind::Response(..) => self.responses_per_second,
+            ResponseKind::NXDomain(..) => self.nxdomains_per_second,
+            ResponseKind::Error(..) => self.errors_per_second,
+        };
+        if rate == 0 {
+            return RateLimitAction::Send;
+        }
+
+        let prefix_length = match client {
+            IpAddr::V4(_) => self.ipv4_prefix_length,
+            IpAddr::V6(_) => self.ipv6_prefix_length,
+        };
+        let key = RateLimitKey {
+            client: NetworkPrefix::new(client, prefix_length),
+            kind,
+        };
+
+        let mut buckets = self.buckets.lock().expect("poisoned lock");
+        let bucket = buckets.get_mut(&key, f64::from(rate), now);
+
+        bucket.tokens = bucket.tokens_at(now);
+        bucket.last_update = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            if bucket.limited > 0 {
+                info!(
+                    "rate limit ended for {} {:?}, limited: {}",
+                    key.client, key.kind, bucket.limited
+                );
+                bucket.limited = 0;
+            }
+            return RateLimitAction::Send;
+        }
+
+        bucket.limited = bucket.limited.saturating_add(1);
+        if bucket.limited == 1 {
+            info!(
+                "rate limit started for {} {:?}{}",
+                key.client,
+                key.kind,
+                if self.log_only { " (log only)" } else { "" }
+            );
+        }
+
+        if self.log_only {
+            debug!("rate limit would limit response to: {}", client);
+            RateLimitAction::Send
+        } else if self.slip > 0 && bucket.limited % self.slip == 0 {
+            debug!("rate limit slipping response to: {}", client);
+            RateLimitAction::Slip
+        } else {
+            debug!("rate limit dropping response to: {}", client);
+            RateLimitAction::Drop
+        }
+    }
+}
+
+/// Returns the identity of the response
+fn response_kind(response: &MessageResponse) -> ResponseKind {
+    let query = response
+        .queries()
+        .and_then(|queries| queries.queries().first());
+
+    let response_code = ResponseCode::from(0, response.header().response_code());
+    match (response_code, query) {
+        (ResponseCode::NoError, Some(query)) => {
+            ResponseKind::Response(query.name().clone(), query.query_type())
+        }
+        (ResponseCode::NXDomain, Some(query)) => {
+            // all non-existent names in a zone share a limit, otherwise random names evade it
+            let zone = response
+                .soa_record()
+                .map_or_else(|| query.name().clone(), |soa| LowerName::new(soa.name()));
+            ResponseKind::NXDomain(zone)
+        }
+        (response_code, _) => ResponseKind::Error(response_code),
+    }
+}
+
+/// A `ResponseHandler` for UDP which applies the `RateLimiter` before the response is sent by the
+///  wrapped handler
+pub struct RateLimitedResponseHandle<R: ResponseHandler> {
+    inner: R,
+    client: IpAddr,
+    rate_limiter: Arc<RateLimiter>,
+}
+
+impl<R: ResponseHandler> RateLimitedResponseHandle<R> {
+    /// Returns a new `RateLimitedResponseHandle`, which sends the responses to `client` with `inner`
+    pub fn new(inner: R, client: IpAddr, rate_limiter: Arc<RateLimiter>) -> Self {
+        RateLimitedResponseHandle {
+            inner,
+            client,
+            rate_limiter,
+        }
+    }
+}
+
+impl<R: ResponseHandler> ResponseHandler for RateLimitedResponseHandle<R> {
+    /// Sends the response if the rate limit allows, or a truncated response, or nothing at all
+    fn send_response(self, response: MessageResponse) -> io::Result<()> {
+        match self.rate_limiter.check(self.client, &response) {
+            RateLimitAction::Send => self.inner.send_response(response),
+            RateLimitAction::Slip => self.inner.send_response(response.into_truncated()),
+            RateLimitAction::Drop => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::SocketAddr;
+    use std::time::Duration;
+
+    use futures::sync::mpsc::unbounded;
+    use futures::Stream;
+    use proto::op::{Header, Message, MessageType, Query};
+    use proto::rr::dnssec::SupportedAlgorithms;
+    use proto::rr::rdata::SOA;
+    use proto::rr::{Name, RData, Record, RecordSet};
+    use proto::serialize::binary::{BinDecoder, BinEncodable, BinEncoder};
+    use proto::BufStreamHandle;
+
+    use authority::{AuthLookup, LookupRecords, MessageResponseBuilder, Queries};
+    use server::ResponseHandle;
+
+    /// The query, and the response code and SOA record of a response to it
+    struct TestResponse {
+        query: Vec<u8>,
+        response_code: ResponseCode,
+        soa: AuthLookup,
+    }
+
+    impl TestResponse {
+        /// Passes the response to `f` as it is passed to a `ResponseHandler`
+        fn with_response<F, T>(&self, f: F) -> T
+        where
+            F: FnOnce(MessageResponse) -> T,
+        {
+            let queries = Queries::read(&mut BinDecoder::new(&self.query), 1).unwrap();
+            let mut header = Header::new();
+            header
+                .set_id(10)
+                .set_message_type(MessageType::Response)
+                .set_response_code(self.response_code);
+
+            let none = AuthLookup::default();
+            f(MessageResponseBuilder::new(Some(&queries)).build(
+                header,
+                none.iter(),
+                none.iter(),
+                self.soa.iter(),
+            ))
+        }
+    }
+
+    fn response(name: &str, response_code: ResponseCode) -> TestResponse {
+        let mut query = Vec::new();
+        Query::query(Name::from_ascii(name).unwrap(), RecordType::A)
+            .emit(&mut BinEncoder::new(&mut query))
+            .unwrap();
+
+        TestResponse {
+            query,
+            response_code,
+            soa: AuthLookup::default(),
+        }
+    }
+
+    fn nxdomain(name: &str, zone: &str) -> TestResponse {
+        let zone = Name::from_ascii(zone).unwrap();
+        let soa = Record::from_rdata(
+            zone.clone(),
+            3600,
+            RecordType::SOA,
+            RData::SOA(SOA::new(zone.clone(), zone, 1, 3600, 60, 86400, 60)),
+        );
+        let soa = LookupRecords::new(
+            false,
+            SupportedAlgorithms::new(),
+            Arc::new(RecordSet::from(soa)),
+        );
+
+        TestResponse {
+            soa: soa.into(),
+            ..response(name, ResponseCode::NXDomain)
+        }
+    }
+
+    fn check_at(
+        limiter: &RateLimiter,
+        client: IpAddr,
+        response: &TestResponse,
+        now: Instant,
+    ) -> RateLimitAction {
+        response.with_response(|response| limiter.check_at(client, &response, now))
+    }
+
+    fn rate_limiter(config: &str) -> RateLimiter {
+        let config: RateLimitConfig = ::toml::de::from_str(config).unwrap();
+        RateLimiter::try_from_config(&config).unwrap()
+    }
+
+    #[test]
+    fn test_limit_and_slip() {
+        let limiter = rate_limiter("responses_per_second = 2\nslip = 2");
+        let now = Instant::now();
+        let client: IpAddr = "192.0.2.1".parse().unwrap();
+        let www = response("www.example.com.", ResponseCode::NoError);
+
+        assert_eq!(check_at(&limiter, client, &www, now), RateLimitAction::Send);
+        assert_eq!(check_at(&limiter, client, &www, now), RateLimitAction::Send);
+        assert_eq!(check_at(&limiter, client, &www, now), RateLimitAction::Drop);
+        assert_eq!(check_at(&limiter, client, &www, now), RateLimitAction::Slip);
+
+        // same network, other client
+        let neighbor: IpAddr = "192.0.2.200".parse().unwrap();
+        assert_eq!(
+            check_at(&limiter, neighbor, &www, now),
+            RateLimitAction::Drop
+        );
+
+        // different response, and different network
+        let mail = response("mail.example.com.", ResponseCode::NoError);
+        assert_eq!(
+            check_at(&limiter, client, &mail, now),
+            RateLimitAction::Send
+        );
+        let other: IpAddr = "198.51.100.1".parse().unwrap();
+        assert_eq!(check_at(&limiter, other, &www, now), RateLimitAction::Send);
+
+        // refilled after a second
+        let later = now + Duration::from_secs(1);
+        assert_eq!(
+            check_at(&limiter, client, &www, later),
+            RateLimitAction::Send
+        );
+    }
+
+    #[test]
+    fn test_slipped_response() {
+        let limiter = Arc::new(rate_limiter("responses_per_second = 1\nslip = 1"));
+        let client: SocketAddr = "192.0.2.1:53".parse().unwrap();
+        let (sender, receiver) = unbounded();
+        let mut receiver = receiver.wait();
+        let www = response("www.example.com.", ResponseCode::NoError);
+
+        for _ in 0..2 {
+            let handle = RateLimitedResponseHandle::new(
+                ResponseHandle::new(client, BufStreamHandle::new(sender.clone())),
+                client.ip(),
+                limiter.clone(),
+            );
+            www.with_response(|response| handle.send_response(response))
+                .unwrap();
+        }
+
+        let sent = receiver.next().unwrap().unwrap();
+        assert!(!Message::from_vec(sent.bytes()).unwrap().truncated());
+
+        let slipped = receiver.next().unwrap().unwrap();
+        assert_eq!(slipped.addr(), client);
+        let message = Message::from_vec(slipped.bytes()).unwrap();
+        assert!(message.truncated());
+        assert_eq!(message.id(), 10);
+        assert_eq!(message.queries().len(), 1);
+        assert!(message.answers().is_empty());
+    }
+
+    #[test]
+    fn test_nxdomain_zone_limit() {
+        let limiter = rate_limiter("responses_per_second = 1\nslip = 0");
+        let now = Instant::now();
+        let client: IpAddr = "192.0.2.1".parse().unwrap();
+
+        // random names of a zone share a limit
+        let a = nxdomain("a.example.com.", "example.com.");
+        let b = nxdomain("b.example.com.", "example.com.");
+        assert_eq!(check_at(&limiter, client, &a, now), RateLimitAction::Send);
+        assert_eq!(check_at(&limiter, client, &b, now), RateLimitAction::Drop);
+
+        let other = nxdomain("a.example.org.", "example.org.");
+        assert_eq!(
+            check_at(&limiter, client, &other, now),
+            RateLimitAction::Send
+        );
+    }
+
+    #[test]
+    fn test_separate_error_limits() {
+        let limiter = rate_limiter("responses_per_second = 1\nerrors_per_second = 0\nslip = 0");
+        let now = Instant::now();
+        let client: IpAddr = "2001:db8::1".parse().unwrap();
+        let refused = response("www.example.com.", ResponseCode::Refused);
+        let www = response("www.example.com.", ResponseCode::NoError);
+
+        for _ in 0..10 {
+            assert_eq!(
+                check_at(&limiter, client, &refused, now),
+                RateLimitAction::Send
+            );
+        }
+
+        assert_eq!(check_at(&limiter, client, &www, now), RateLimitAction::Send);
+        assert_eq!(check_at(&limiter, client, &www, now), RateLimitAction::Drop);
+        assert_eq!(check_at(&limiter, client, &www, now), RateLimitAction::Drop);
+    }
+
+    #[test]
+    fn test_log_only_and_exempt() {
+        let limiter = rate_limiter("responses_per_second = 1\nlog_only = true");
+        let now = Instant::now();
+        let client: IpAddr = "192.0.2.1".parse().unwrap();
+        let www = response("www.example.com.", ResponseCode::NoError);
+
+        for _ in 0..10 {
+            assert_eq!(check_at(&limiter, client, &www, now), RateLimitAction::Send);
+        }
+
+        let limiter = rate_limiter("responses_per_second = 1\nexempt_clients = [\"192.0.2.0/24\"]");
+        for _ in 0..10 {
+            assert_eq!(check_at(&limiter, client, &www, now), RateLimitAction::Send);
+        }
+    }
+
+    #[test]
+    fn test_max_table_size() {
+        let limiter = rate_limiter("responses_per_second = 1\nslip = 0\nmax_table_size = 2");
+        let now = Instant::now();
+        let www = response("www.example.com.", ResponseCode::NoError);
+        let first: IpAddr = "192.0.2.1".parse().unwrap();
+
+        assert_eq!(check_at(&limiter, first, &www, now), RateLimitAction::Send);
+        assert_eq!(check_at(&limiter, first, &www, now), RateLimitAction::Drop);
+
+        // a flood of other networks within the same second doesn't grow the table
+        for i in 0..100 {
+            let client = IpAddr::from([198, 51, i, 1]);
+            check_at(&limiter, client, &www, now);
+            assert!(limiter.buckets.lock().unwrap().table.len() <= 2);
+        }
+
+        // nor does it reset the bucket of the limited network
+        assert_eq!(check_at(&limiter, first, &www, now), RateLimitAction::Drop);
+
+        // once the least recently used bucket has refilled, it makes room for a new network
+        let later = now + Duration::from_secs(1);
+        let client: IpAddr = "203.0.113.1".parse().unwrap();
+        assert_eq!(
+            check_at(&limiter, client, &www, later),
+            RateLimitAction::Send
+        );
+        assert_eq!(
+            check_at(&limiter, client, &www, later),
+            RateLimitAction::Drop
+        );
+        assert_eq!(
+            check_at(&limiter, first, &www, later),
+            RateLimitAction::Send
+        );
+    }
+}
@@ -19,6 +19,8 @@ pub struct Request<'r> {
     pub message: MessageRequest<'r>,
     /// Source address of the Client
     pub src: SocketAddr,
+    /// Local address on which the request was received
+    pub dst: SocketAddr,
 }
 
 /// Trait for handling incoming requests, and providing a message response.
@@ -13,6 +13,7 @@ use trust_dns::BufStreamHandle;
 use proto::xfer::SerialMessage;
 
 use authority::MessageResponse;
+use server::QueryLogRequest;
 
 /// A handler for send a response to a client
 pub trait ResponseHandler: Send {
@@ -30,12 +31,23 @@ pub trait ResponseHandler: Send {
 pub struct ResponseHandle {
     dst: SocketAddr,
     stream_handle: BufStreamHandle,
+    query_log: Option<QueryLogRequest>,
 }
 
 impl ResponseHandle {
     /// Returns a new `ResponseHandle` for sending a response message
     pub fn new(dst: SocketAddr, stream_handle: BufStreamHandle) -> Self {
-        ResponseHandle { dst, stream_handle }
+        ResponseHandle {
+            dst,
+            stream_handle,
+            query_log: None,
+        }
+    }
+
+    /// Logs the response to the query log, as it is sent
+    pub fn with_query_log(mut self, query_log: Option<QueryLogRequest>) -> Self {
+        self.query_log = query_log;
+        self
     }
 }
 
@@ -62,6 +74,10 @@ impl ResponseHandler for ResponseHandle {
             )
         })?;
 
+        if let Some(query_log) = self.query_log {
+            query_log.log_response(&buffer);
+        }
+
         self.stream_handle
             .unbounded_send(SerialMessage::new(buffer, self.dst))
             .map_err(|_| io::Error::new(io::ErrorKind::Other, "unknown"))
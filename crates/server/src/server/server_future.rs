@@ -8,7 +8,7 @@ use std;
 use std::io;
 use std::net::SocketAddr;
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use futures::{Future, Stream};
 
@@ -31,13 +31,19 @@ use proto::BufStreamHandle;
 use trust_dns_openssl::tls_server::*;
 
 use authority::MessageRequest;
-use server::{Request, RequestHandler, ResponseHandle, ResponseHandler, TimeoutStream};
+use server::{
+    record_type_label, Metrics, MetricsResponseHandle, Protocol, QueryLogRequest, QueryLogger,
+    RateLimitedResponseHandle, RateLimiter, Request, RequestHandler, ResponseHandle,
+    ResponseHandler, TimeoutStream,
+};
 
 // TODO, would be nice to have a Slab for buffers here...
 
 /// A Futures based implementation of a DNS server
 pub struct ServerFuture<T: RequestHandler> {
     handler: Arc<Mutex<T>>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    query_log: Option<Arc<QueryLogger>>,
 }
 
 impl<T: RequestHandler> ServerFuture<T> {
@@ -45,24 +51,85 @@ impl<T: RequestHandler> ServerFuture<T> {
     pub fn new(handler: T) -> ServerFuture<T> {
         ServerFuture {
             handler: Arc::new(Mutex::new(handler)),
+            rate_limiter: None,
+            query_log: None,
         }
     }
 
+    /// Returns the shared handler of all requests, e.g. to modify the `Catalog` while serving
+    pub fn request_handler(&self) -> Arc<Mutex<T>> {
+        self.handler.clone()
+    }
+
+    /// Apply Response Rate Limiting to all responses sent over UDP sockets registered after this.
+    ///
+    /// TCP, TLS and HTTPS connections can not be used for reflection, and are not limited.
+    pub fn set_rate_limiter(&mut self, rate_limiter: RateLimiter) {
+        self.rate_limiter = Some(Arc::new(rate_limiter));
+    }
+
+    /// Log all queries received on sockets and listeners registered after this, with their responses
+    pub fn set_query_logger(&mut self, query_log: QueryLogger) {
+        self.query_log = Some(Arc::new(query_log));
+    }
+
     /// Register a UDP socket. Should be bound before calling this function.
-    pub fn register_socket(&self, socket: tokio_udp::UdpSocket) {
+    ///
+    /// The local address of the socket is the destination of all requests received on it, for a
+    ///  socket bound to the unspecified address, e.g. `0.0.0.0`, no request matches the
+    ///  destination rules of a `View`.
+    pub fn register_socket(&self, socket: tokio_udp::UdpSocket) -> io::Result<()> {
         debug!("registered udp: {:?}", socket);
+        let dst_addr = socket.local_addr()?;
 
         // create the new UdpStream
         let (buf_stream, stream_handle) = UdpStream::with_bound(socket);
         //let request_stream = RequestStream::new(buf_stream, stream_handle);
         let handler = self.handler.clone();
+        let query_log = self.query_log.clone();
+        let rate_limiter = self.rate_limiter.clone();
 
         // this spawns a ForEach future which handles all the requests into a Handler.
         tokio_executor::spawn(
             buf_stream
                 .for_each(move |message| {
                     let src_addr = message.addr();
-                    self::handle_raw_request(message, handler.clone(), stream_handle.clone())
+                    let result = if let Some(ref rate_limiter) = rate_limiter {
+                        let query_log = query_log.as_ref().map(|query_log| {
+                            QueryLogRequest::new(
+                                query_log.clone(),
+                                src_addr,
+                                dst_addr,
+                                Protocol::Udp,
+                                message.bytes(),
+                            )
+                        });
+                        let response_handle = ResponseHandle::new(src_addr, stream_handle.clone())
+                            .with_query_log(query_log);
+                        let response_handle = RateLimitedResponseHandle::new(
+                            response_handle,
+                            src_addr.ip(),
+                            rate_limiter.clone(),
+                        );
+                        self::handle_raw_request_with(
+                            message,
+                            dst_addr,
+                            Protocol::Udp,
+                            handler.clone(),
+                            response_handle,
+                        )
+                    } else {
+                        self::handle_raw_request(
+                            message,
+                            dst_addr,
+                            Protocol::Udp,
+                            handler.clone(),
+                            query_log.clone(),
+                            stream_handle.clone(),
+                        )
+                    };
+
+                    result
                         .map_err(move |e| {
                             debug!("error parsing UDP request src: {:?} error: {}", src_addr, e)
                         }).ok();
@@ -71,13 +138,13 @@ impl<T: RequestHandler> ServerFuture<T> {
                     Ok(())
                 }).map_err(|e| panic!("error in UDP request_stream handler: {}", e)),
         );
+
+        Ok(())
     }
 
     /// Register a UDP socket. Should be bound before calling this function.
-    pub fn register_socket_std(&self, socket: std::net::UdpSocket) {
-        self.register_socket(
-            tokio_udp::UdpSocket::from_std(socket, &Handle::default()).expect("bad handle?"),
-        )
+    pub fn register_socket_std(&self, socket: std::net::UdpSocket) -> io::Result<()> {
+        self.register_socket(tokio_udp::UdpSocket::from_std(socket, &Handle::default())?)
     }
 
     /// Register a TcpListener to the Server. This should already be bound to either an IPv6 or an
@@ -98,6 +165,7 @@ impl<T: RequestHandler> ServerFuture<T> {
         timeout: Duration,
     ) -> io::Result<()> {
         let handler = self.handler.clone();
+        let query_log = self.query_log.clone();
         debug!("registered tcp: {:?}", listener);
 
         // for each incoming request...
@@ -106,12 +174,15 @@ impl<T: RequestHandler> ServerFuture<T> {
                 .incoming()
                 .for_each(move |tcp_stream| {
                     let src_addr = tcp_stream.peer_addr().unwrap();
+                    let dst_addr = tcp_stream.local_addr().unwrap();
                     debug!("accepted request from: {}", src_addr);
+                    Metrics::global().connections.inc(&[Protocol::Tcp.as_str()]);
                     // take the created stream...
                     let (buf_stream, stream_handle) = TcpStream::from_stream(tcp_stream, src_addr);
                     let timeout_stream = TimeoutStream::new(buf_stream, timeout);
                     //let request_stream = RequestStream::new(timeout_stream, stream_handle);
                     let handler = handler.clone();
+                    let query_log = query_log.clone();
 
                     // and spawn to the io_loop
                     tokio_executor::spawn(
@@ -119,7 +190,10 @@ impl<T: RequestHandler> ServerFuture<T> {
                             .for_each(move |message| {
                                 self::handle_raw_request(
                                     message,
+                                    dst_addr,
+                                    Protocol::Tcp,
                                     handler.clone(),
+                                    query_log.clone(),
                                     stream_handle.clone(),
                                 )
                             }).map_err(move |e| {
@@ -188,6 +262,7 @@ impl<T: RequestHandler> ServerFuture<T> {
 
         let ((cert, chain), key) = certificate_and_key;
         let handler = self.handler.clone();
+        let query_log = self.query_log.clone();
         debug!("registered tcp: {:?}", listener);
 
         let tls_acceptor = tls_server::new_acceptor(cert, chain, key)?;
@@ -198,23 +273,30 @@ impl<T: RequestHandler> ServerFuture<T> {
                 .incoming()
                 .for_each(move |tcp_stream| {
                     let src_addr = tcp_stream.peer_addr().unwrap();
+                    let dst_addr = tcp_stream.local_addr().unwrap();
                     debug!("accepted request from: {}", src_addr);
                     let handler = handler.clone();
+                    let query_log = query_log.clone();
 
                     // take the created stream...
                     tls_acceptor
                         .accept_async(tcp_stream)
                         .map_err(|e| {
+                            Metrics::global()
+                                .handshake_errors
+                                .inc(&[Protocol::Tls.as_str()]);
                             io::Error::new(
                                 io::ErrorKind::ConnectionRefused,
                                 format!("tls error: {}", e),
                             )
                         }).and_then(move |tls_stream| {
+                            Metrics::global().connections.inc(&[Protocol::Tls.as_str()]);
                             let (buf_stream, stream_handle) =
                                 TlsStream::from_stream(tls_stream, src_addr);
                             let timeout_stream = TimeoutStream::new(buf_stream, timeout);
                             //let request_stream = RequestStream::new(timeout_stream, stream_handle);
                             let handler = handler.clone();
+                            let query_log = query_log.clone();
 
                             // and spawn to the io_loop
                             tokio_executor::spawn(
@@ -222,7 +304,10 @@ impl<T: RequestHandler> ServerFuture<T> {
                                     .for_each(move |message| {
                                         self::handle_raw_request(
                                             message,
+                                            dst_addr,
+                                            Protocol::Tls,
                                             handler.clone(),
+                                            query_log.clone(),
                                             stream_handle.clone(),
                                         )
                                     }).map_err(move |e| {
@@ -300,6 +385,7 @@ impl<T: RequestHandler> ServerFuture<T> {
         use trust_dns_rustls::{tls_from_stream, tls_server};
 
         let handler = self.handler.clone();
+        let query_log = self.query_log.clone();
 
         debug!("registered tcp: {:?}", listener);
 
@@ -318,23 +404,30 @@ impl<T: RequestHandler> ServerFuture<T> {
                 .incoming()
                 .for_each(move |tcp_stream| {
                     let src_addr = tcp_stream.peer_addr().unwrap();
+                    let dst_addr = tcp_stream.local_addr().unwrap();
                     debug!("accepted request from: {}", src_addr);
                     let handler = handler.clone();
+                    let query_log = query_log.clone();
 
                     // TODO: need to consider timeout of total connect...
                     // take the created stream...
                     tls_acceptor
                         .accept(tcp_stream)
                         .map_err(|e| {
+                            Metrics::global()
+                                .handshake_errors
+                                .inc(&[Protocol::Tls.as_str()]);
                             io::Error::new(
                                 io::ErrorKind::ConnectionRefused,
                                 format!("tls error: {}", e),
                             )
                         }).and_then(move |tls_stream| {
+                            Metrics::global().connections.inc(&[Protocol::Tls.as_str()]);
                             let (buf_stream, stream_handle) = tls_from_stream(tls_stream, src_addr);
                             let timeout_stream = TimeoutStream::new(buf_stream, timeout);
                             //let request_stream = RequestStream::new(timeout_stream, stream_handle);
                             let handler = handler.clone();
+                            let query_log = query_log.clone();
 
                             // and spawn to the io_loop
                             tokio_executor::spawn(
@@ -342,7 +435,10 @@ impl<T: RequestHandler> ServerFuture<T> {
                                     .for_each(move |message| {
                                         self::handle_raw_request(
                                             message,
+                                            dst_addr,
+                                            Protocol::Tls,
                                             handler.clone(),
+                                            query_log.clone(),
                                             stream_handle.clone(),
                                         )
                                     }).map_err(move |e| {
@@ -421,6 +517,7 @@ impl<T: RequestHandler> ServerFuture<T> {
 
         let dns_hostname = Arc::new(dns_hostname);
         let handler = self.handler.clone();
+        let query_log = self.query_log.clone();
         debug!("registered tcp: {:?}", listener);
 
         let tls_acceptor = tls_server::new_acceptor(certificate_and_key.0, certificate_and_key.1)
@@ -441,8 +538,10 @@ impl<T: RequestHandler> ServerFuture<T> {
                 .incoming()
                 .for_each(move |tcp_stream| {
                     let src_addr = tcp_stream.peer_addr().unwrap();
+                    let dst_addr = tcp_stream.local_addr().unwrap();
                     debug!("accepted request from: {}", src_addr);
                     let handler = handler.clone();
+                    let query_log = query_log.clone();
                     let dns_hostname = dns_hostname.clone();
 
                     // TODO: need to consider timeout of total connect...
@@ -450,12 +549,23 @@ impl<T: RequestHandler> ServerFuture<T> {
                     tls_acceptor
                         .accept(tcp_stream)
                         .map_err(|e| {
+                            Metrics::global()
+                                .handshake_errors
+                                .inc(&[Protocol::Https.as_str()]);
                             io::Error::new(
                                 io::ErrorKind::ConnectionRefused,
                                 format!("tls error: {}", e),
                             )
                         }).and_then(move |tls_stream| {
-                            h2_handler(handler, tls_stream, src_addr, dns_hostname)
+                            Metrics::global().connections.inc(&[Protocol::Https.as_str()]);
+                            h2_handler(
+                                handler,
+                                query_log,
+                                tls_stream,
+                                src_addr,
+                                dst_addr,
+                                dns_hostname,
+                            )
                         })
                     // FIXME: need to map this error to Ok, otherwise this is a DOS potential
                     // .map_err(move |e| {
@@ -470,11 +580,40 @@ impl<T: RequestHandler> ServerFuture<T> {
 
 pub(crate) fn handle_raw_request<T: RequestHandler>(
     message: SerialMessage,
+    dst_addr: SocketAddr,
+    protocol: Protocol,
     request_handler: Arc<Mutex<T>>,
+    query_log: Option<Arc<QueryLogger>>,
     response_handler: BufStreamHandle,
+) -> io::Result<()> {
+    let query_log = query_log.map(|query_log| {
+        QueryLogRequest::new(
+            query_log,
+            message.addr(),
+            dst_addr,
+            protocol,
+            message.bytes(),
+        )
+    });
+    let response_handler =
+        ResponseHandle::new(message.addr(), response_handler).with_query_log(query_log);
+    self::handle_raw_request_with(
+        message,
+        dst_addr,
+        protocol,
+        request_handler,
+        response_handler,
+    )
+}
+
+pub(crate) fn handle_raw_request_with<T: RequestHandler, R: ResponseHandler + 'static>(
+    message: SerialMessage,
+    dst_addr: SocketAddr,
+    protocol: Protocol,
+    request_handler: Arc<Mutex<T>>,
+    response_handler: R,
 ) -> io::Result<()> {
     let src_addr = message.addr();
-    let response_handler = ResponseHandle::new(message.addr(), response_handler);
 
     // TODO: rather than decoding the message here, this RequestStream should instead
     //       forward the request to another sender such that we could pull serialization off
@@ -482,18 +621,29 @@ pub(crate) fn handle_raw_request<T: RequestHandler>(
     // decode any messages that are ready
     let mut decoder = BinDecoder::new(message.bytes());
     let message = MessageRequest::read(&mut decoder)?;
-    self::handle_request(message, src_addr, request_handler, response_handler)
+    self::handle_request(
+        message,
+        src_addr,
+        dst_addr,
+        protocol,
+        request_handler,
+        response_handler,
+    )
 }
 
 pub(crate) fn handle_request<'q, R: ResponseHandler + 'static, T: RequestHandler>(
     message: MessageRequest<'q>,
     src_addr: SocketAddr,
+    dst_addr: SocketAddr,
+    protocol: Protocol,
     request_handler: Arc<Mutex<T>>,
     response_handler: R,
 ) -> io::Result<()> {
+    let received = Instant::now();
     let request = Request {
         message,
         src: src_addr,
+        dst: dst_addr,
     };
 
     info!(
@@ -513,6 +663,18 @@ pub(crate) fn handle_request<'q, R: ResponseHandler + 'static, T: RequestHandler
             .unwrap_or_else(|| "empty_queries".to_string()),
     );
 
+    let metrics = Metrics::global();
+    metrics.requests.inc(&[
+        protocol.as_str(),
+        &format!("{:?}", request.message.op_code()),
+    ]);
+    for query in request.message.queries() {
+        metrics
+            .queries
+            .inc(&[protocol.as_str(), record_type_label(query.query_type())]);
+    }
+
+    let response_handler = MetricsResponseHandle::new(response_handler, protocol, received);
     request_handler
         .lock()
         .expect("poisoned lock")
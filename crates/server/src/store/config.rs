@@ -7,11 +7,17 @@
 
 //! Configuration for the stores
 
+use std::time::Duration;
+
+use trust_dns_resolver::config::ResolverOpts;
+
 use store::file::FileConfig;
+use store::forwarder::ForwardConfig;
+use store::hint::HintConfig;
 use store::sqlite::SqliteConfig;
 
 /// Enumeration over all Store configurations
-#[derive(Deserialize, PartialEq, Debug)]
+#[derive(Deserialize, PartialEq, Debug, Clone)]
 #[serde(tag = "type")]
 #[serde(rename_all = "lowercase")]
 pub enum StoreConfig {
@@ -19,4 +25,38 @@ pub enum StoreConfig {
     File(FileConfig),
     /// Sqlite based configuration file
     Sqlite(SqliteConfig),
+    /// Root hints for recursive resolution
+    Hint(HintConfig),
+    /// Upstream name servers for recursive resolution
+    Forward(ForwardConfig),
+}
+
+/// Default number of responses cached by the resolvers of Hint and Forward zones
+const DEFAULT_CACHE_SIZE: usize = 1024;
+
+/// The options of the resolver of a Hint or Forward zone
+///
+/// The hosts file of the server is never used, its names are not served to remote clients.
+///
+/// # Arguments
+///
+/// * `cache_size` - number of responses cached, defaults to 1024
+/// * `timeout` - seconds to wait for the response of a name server
+/// * `attempts` - number of times a query is sent to the name servers before it fails
+pub(crate) fn resolver_opts(
+    cache_size: Option<usize>,
+    timeout: Option<u64>,
+    attempts: Option<usize>,
+) -> ResolverOpts {
+    let mut options = ResolverOpts::default();
+    options.use_hosts_file = false;
+    options.cache_size = cache_size.unwrap_or(DEFAULT_CACHE_SIZE);
+    if let Some(timeout) = timeout {
+        options.timeout = Duration::from_secs(timeout);
+    }
+    if let Some(attempts) = attempts {
+        options.attempts = attempts;
+    }
+
+    options
 }
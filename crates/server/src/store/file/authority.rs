@@ -17,6 +17,8 @@ use trust_dns::op::ResponseCode;
 use trust_dns::rr::dnssec::{DnsSecResult, Signer, SupportedAlgorithms};
 use trust_dns::rr::{DNSClass, LowerName, Name, RData, Record, RecordSet, RecordType, RrKey};
 
+#[cfg(feature = "dnssec")]
+use authority::zone_digest;
 use authority::{
     AnyRecords, AuthLookup, Authority, LookupRecords, MessageRequest, UpdateResult, ZoneType,
 };
@@ -114,7 +116,68 @@ impl FileAuthority {
             records.len()
         );
 
-        Ok(FileAuthority::new(origin, records, zone_type, allow_axfr))
+        #[cfg_attr(
+            not(any(feature = "dnssec-openssl", feature = "dnssec-ring")),
+            allow(unused_mut)
+        )]
+        let mut authority = FileAuthority::new(origin, records, zone_type, allow_axfr);
+
+        #[cfg(any(feature = "dnssec-openssl", feature = "dnssec-ring"))]
+        {
+            let serial = authority.serial();
+            zone_digest::load_zonemd(&authority.origin, zone_type, &mut authority.records, serial)?;
+        }
+
+        Ok(authority)
+    }
+
+    /// Creates the Authority of a slave zone from the records of a zone transfer
+    ///
+    /// # Arguments
+    ///
+    /// * `origin` - The zone `Name` being transferred.
+    /// * `records` - The records of the AXFR response, starting with the SOA record of the zone.
+    /// * `allow_axfr` - if true, then the zone may be transferred further to other slaves.
+    ///
+    /// # Return value
+    ///
+    /// The new `Authority`, or an error if the records do not start with the SOA of the zone, or
+    ///  if the ZONEMD of the zone does not verify.
+    pub fn try_from_transfer(
+        origin: Name,
+        records: Vec<Record>,
+        allow_axfr: bool,
+    ) -> Result<Self, String> {
+        let serial = records
+            .first()
+            .filter(|record| *record.name() == origin)
+            .and_then(|record| match *record.rdata() {
+                RData::SOA(ref soa) => Some(soa.serial()),
+                _ => None,
+            })
+            .ok_or_else(|| format!("transfer of {} does not start with its SOA", origin))?;
+
+        let mut authority =
+            FileAuthority::new(origin, BTreeMap::new(), ZoneType::Slave, allow_axfr);
+        for record in records {
+            authority.upsert(record, serial);
+        }
+
+        info!(
+            "zone transferred: {} with {} records",
+            authority.origin,
+            authority.records.len()
+        );
+
+        #[cfg(any(feature = "dnssec-openssl", feature = "dnssec-ring"))]
+        zone_digest::load_zonemd(
+            &authority.origin,
+            ZoneType::Slave,
+            &mut authority.records,
+            serial,
+        )?;
+
+        Ok(authority)
     }
 
     /// Enables AXFRs of all the zones records
@@ -231,7 +294,13 @@ impl FileAuthority {
         self.increment_soa_serial();
 
         // TODO: should we auto sign here? or maybe up a level...
-        self.sign_zone()
+        self.sign_zone()?;
+
+        // the digest covers the RRSIGs, so the ZONEMD is updated last
+        #[cfg(any(feature = "dnssec-openssl", feature = "dnssec-ring"))]
+        self.update_zonemd()?;
+
+        Ok(())
     }
 
     /// (Re)generates the nsec records, increments the serial number nad signs the zone
@@ -317,8 +386,6 @@ impl FileAuthority {
     #[cfg(feature = "dnssec")]
     fn sign_zone(&mut self) -> DnsSecResult<()> {
         use chrono::Utc;
-        use trust_dns::rr::dnssec::tbs;
-        use trust_dns::rr::rdata::{DNSSECRData, DNSSECRecordType, SIG};
 
         debug!("signing zone: {}", self.origin);
         let inception = Utc::now();
@@ -334,84 +401,13 @@ impl FileAuthority {
             let mut rr_set = RecordSet::clone(&*rr_set_orig);
             // becuase the rrset is an Arc, it must be cloned before mutated
 
-            rr_set.clear_rrsigs();
-            let rrsig_temp = Record::with(
-                rr_set.name().clone(),
-                RecordType::DNSSEC(DNSSECRecordType::RRSIG),
+            zone_digest::sign_rrset(
+                &mut rr_set,
+                &self.secure_keys,
                 zone_ttl,
-            );
-
-            for signer in &self.secure_keys {
-                debug!(
-                    "signing rr_set: {}, {} with: {}",
-                    rr_set.name(),
-                    rr_set.record_type(),
-                    signer.algorithm(),
-                );
-
-                let expiration = inception + signer.sig_duration();
-
-                let tbs = tbs::rrset_tbs(
-                    rr_set.name(),
-                    self.class,
-                    rr_set.name().num_labels(),
-                    rr_set.record_type(),
-                    signer.algorithm(),
-                    rr_set.ttl(),
-                    expiration.timestamp() as u32,
-                    inception.timestamp() as u32,
-                    signer.calculate_key_tag()?,
-                    signer.signer_name(),
-                    // TODO: this is a nasty clone... the issue is that the vec
-                    //  from records is of Vec<&R>, but we really want &[R]
-                    &rr_set
-                        .records_without_rrsigs()
-                        .cloned()
-                        .collect::<Vec<Record>>(),
-                );
-
-                // TODO, maybe chain these with some ETL operations instead?
-                let tbs = match tbs {
-                    Ok(tbs) => tbs,
-                    Err(err) => {
-                        error!("could not serialize rrset to sign: {}", err);
-                        continue;
-                    }
-                };
-
-                let signature = signer.sign(&tbs);
-                let signature = match signature {
-                    Ok(signature) => signature,
-                    Err(err) => {
-                        error!("could not sign rrset: {}", err);
-                        continue;
-                    }
-                };
-
-                let mut rrsig = rrsig_temp.clone();
-                rrsig.set_rdata(RData::DNSSEC(DNSSECRData::SIG(SIG::new(
-                    // type_covered: RecordType,
-                    rr_set.record_type(),
-                    // algorithm: Algorithm,
-                    signer.algorithm(),
-                    // num_labels: u8,
-                    rr_set.name().num_labels(),
-                    // original_ttl: u32,
-                    rr_set.ttl(),
-                    // sig_expiration: u32,
-                    expiration.timestamp() as u32,
-                    // sig_inception: u32,
-                    inception.timestamp() as u32,
-                    // key_tag: u16,
-                    signer.calculate_key_tag()?,
-                    // signer_name: Name,
-                    signer.signer_name().clone(),
-                    // sig: Vec<u8>
-                    signature,
-                ))));
-
-                rr_set.insert_rrsig(rrsig);
-            }
+                self.class,
+                inception,
+            )?;
 
             *rr_set_orig = Arc::new(rr_set);
         }
@@ -419,6 +415,29 @@ impl FileAuthority {
         Ok(())
     }
 
+    /// Recomputes the digests of the ZONEMD records at the apex of the zone, see `zone_digest::update_zonemd`
+    ///
+    /// The digest covers the RRSIGs, so this must be called after the zone was signed.
+    #[cfg(any(feature = "dnssec-openssl", feature = "dnssec-ring"))]
+    pub fn update_zonemd(&mut self) -> DnsSecResult<()> {
+        let serial = self.serial();
+        let zone_ttl = self.minimum_ttl();
+        zone_digest::update_zonemd(
+            &self.origin,
+            &mut self.records,
+            serial,
+            &self.secure_keys,
+            zone_ttl,
+            self.class,
+        )
+    }
+
+    /// Verifies the zone with the ZONEMD records at its apex, RFC 8976 section 4
+    #[cfg(any(feature = "dnssec-openssl", feature = "dnssec-ring"))]
+    pub fn verify_zonemd(&self) -> DnsSecResult<()> {
+        zone_digest::verify_zonemd(&self.origin, &self.records)
+    }
+
     /// unwrap all the records
     pub(crate) fn unwrap_records(self) -> BTreeMap<RrKey, Arc<RecordSet>> {
         self.records
@@ -665,6 +684,12 @@ impl Authority for FileAuthority {
         AuthLookup::default()
     }
 
+    fn has_delegations(&self) -> bool {
+        self.records
+            .keys()
+            .any(|key| key.record_type == RecordType::NS && key.name != self.origin)
+    }
+
     /// By adding a secure key, this will implicitly enable dnssec for the zone.
     ///
     /// # Arguments
@@ -709,7 +734,13 @@ impl Authority for FileAuthority {
         self.increment_soa_serial();
 
         // TODO: should we auto sign here? or maybe up a level...
-        self.sign_zone()
+        self.sign_zone()?;
+
+        // the digest covers the RRSIGs, so the ZONEMD is updated last
+        #[cfg(any(feature = "dnssec-openssl", feature = "dnssec-ring"))]
+        self.update_zonemd()?;
+
+        Ok(())
     }
 
     /// (Re)generates the nsec records, increments the serial number nad signs the zone
@@ -758,4 +789,235 @@ mod tests {
             _ => panic!("wrong rdata type returned"),
         }
     }
+
+    #[test]
+    fn test_try_from_transfer() {
+        let config = FileConfig {
+            zone_file_path: "tests/named_test_configs/example.com.zone".to_string(),
+        };
+        let origin = Name::from_str("example.com.").unwrap();
+        let master =
+            FileAuthority::try_from_config(origin.clone(), ZoneType::Master, false, None, &config)
+                .expect("failed to load file");
+
+        // an AXFR starts and ends with the SOA record
+        let soa = master.soa().iter().next().cloned().unwrap();
+        let mut records = vec![soa.clone()];
+        records.extend(
+            master
+                .records()
+                .values()
+                .flat_map(|rrset| rrset.records_without_rrsigs().cloned()),
+        );
+        records.push(soa);
+
+        let authority = FileAuthority::try_from_transfer(origin.clone(), records.clone(), false)
+            .expect("failed to load transfer");
+        assert_eq!(authority.zone_type(), ZoneType::Slave);
+        assert_eq!(authority.serial(), master.serial());
+        assert_eq!(authority.records().len(), master.records().len());
+        assert!(authority
+            .lookup(
+                &LowerName::from_str("www.example.com.").unwrap(),
+                RecordType::A,
+                false,
+                SupportedAlgorithms::new(),
+            )
+            .iter()
+            .next()
+            .is_some());
+
+        records.retain(|record| record.rr_type() != RecordType::SOA);
+        assert!(FileAuthority::try_from_transfer(origin, records, false).is_err());
+    }
+
+    /// RFC 8976 appendix A.1, with a placeholder ZONEMD
+    #[cfg(any(feature = "dnssec-openssl", feature = "dnssec-ring"))]
+    fn zonemd_example(zone_type: ZoneType) -> FileAuthority {
+        use std::net::Ipv6Addr;
+
+        use trust_dns::rr::rdata::zonemd::{ZonemdHashAlgorithm, ZonemdScheme};
+        use trust_dns::rr::rdata::{SOA, ZONEMD};
+
+        let origin = Name::from_str("example.").unwrap();
+        let mut authority = FileAuthority::new(origin.clone(), BTreeMap::new(), zone_type, false);
+
+        let ns1 = Name::from_str("ns1.example.").unwrap();
+        let ns2 = Name::from_str("ns2.example.").unwrap();
+        let records = vec![
+            Record::from_rdata(
+                origin.clone(),
+                86400,
+                RecordType::SOA,
+                RData::SOA(SOA::new(
+                    ns1.clone(),
+                    Name::from_str("admin.example.").unwrap(),
+                    2018031900,
+                    1800,
+                    900,
+                    604800,
+                    86400,
+                )),
+            ),
+            Record::from_rdata(
+                origin.clone(),
+                86400,
+                RecordType::NS,
+                RData::NS(ns1.clone()),
+            ),
+            Record::from_rdata(
+                origin.clone(),
+                86400,
+                RecordType::NS,
+                RData::NS(ns2.clone()),
+            ),
+            Record::from_rdata(
+                origin.clone(),
+                86400,
+                RecordType::ZONEMD,
+                RData::ZONEMD(ZONEMD::new(
+                    0,
+                    ZonemdScheme::Simple,
+                    ZonemdHashAlgorithm::SHA384,
+                    vec![0; 48],
+                )),
+            ),
+            Record::from_rdata(
+                ns1,
+                3600,
+                RecordType::A,
+                RData::A(Ipv4Addr::new(203, 0, 113, 63)),
+            ),
+            Record::from_rdata(
+                ns2,
+                3600,
+                RecordType::AAAA,
+                RData::AAAA(Ipv6Addr::from_str("2001:db8::63").unwrap()),
+            ),
+        ];
+        for record in records {
+            authority.upsert(record, 2018031900);
+        }
+
+        authority
+    }
+
+    #[test]
+    #[cfg(any(feature = "dnssec-openssl", feature = "dnssec-ring"))]
+    fn test_update_zonemd() {
+        let origin = Name::from_str("example.").unwrap();
+        let mut authority = zonemd_example(ZoneType::Master);
+
+        assert!(authority.verify_zonemd().is_err());
+        authority.update_zonemd().expect("failed to update zonemd");
+        authority.verify_zonemd().expect("failed to verify zonemd");
+
+        let lookup = authority.lookup(
+            &LowerName::new(&origin),
+            RecordType::ZONEMD,
+            false,
+            SupportedAlgorithms::new(),
+        );
+        match lookup
+            .into_iter()
+            .next()
+            .expect("ZONEMD record not found in authority")
+            .rdata()
+        {
+            RData::ZONEMD(zonemd) => {
+                assert_eq!(zonemd.serial(), 2018031900);
+                assert_eq!(&zonemd.digest()[..4], &[0xc6, 0x80, 0x90, 0xd9]);
+            }
+            _ => panic!("wrong rdata type returned"),
+        }
+    }
+
+    #[test]
+    #[cfg(any(feature = "dnssec-openssl", feature = "dnssec-ring"))]
+    fn test_load_zonemd() {
+        // the placeholder of a primary is recomputed
+        let mut authority = zonemd_example(ZoneType::Master);
+        let serial = authority.serial();
+        zone_digest::load_zonemd(
+            &authority.origin,
+            ZoneType::Master,
+            &mut authority.records,
+            serial,
+        )
+        .expect("failed to load zonemd");
+        authority.verify_zonemd().expect("failed to verify zonemd");
+
+        // a secondary only accepts a zone with a valid digest
+        let mut records = authority.unwrap_records();
+        let origin = LowerName::from_str("example.").unwrap();
+        zone_digest::load_zonemd(&origin, ZoneType::Slave, &mut records, serial)
+            .expect("failed to verify zonemd");
+
+        let mut records = zonemd_example(ZoneType::Slave).unwrap_records();
+        assert!(zone_digest::load_zonemd(&origin, ZoneType::Slave, &mut records, serial).is_err());
+    }
+
+    #[test]
+    #[cfg(any(feature = "dnssec-openssl", feature = "dnssec-ring"))]
+    fn test_load_zonemd_not_recomputed() {
+        use trust_dns::rr::dnssec::Algorithm;
+        use trust_dns::rr::rdata::{DNSSECRData, DNSSECRecordType, SIG};
+
+        let origin = LowerName::from_str("example.").unwrap();
+        let zonemd_digest = |records: &BTreeMap<RrKey, Arc<RecordSet>>| {
+            let rr_set = &records[&RrKey::new(origin.clone(), RecordType::ZONEMD)];
+            let record = rr_set
+                .records_without_rrsigs()
+                .next()
+                .expect("ZONEMD record not found");
+
+            match *record.rdata() {
+                RData::ZONEMD(ref zonemd) => zonemd.digest().to_vec(),
+                _ => panic!("wrong rdata type returned"),
+            }
+        };
+
+        // a digest which is not a placeholder is served as is
+        let mut authority = zonemd_example(ZoneType::Master);
+        let serial = authority.serial();
+        authority.update_zonemd().expect("failed to update zonemd");
+        authority.upsert(
+            Record::from_rdata(
+                Name::from_str("www.example.").unwrap(),
+                3600,
+                RecordType::A,
+                RData::A(Ipv4Addr::new(192, 0, 2, 1)),
+            ),
+            serial,
+        );
+        let mut records = authority.unwrap_records();
+        let digest = zonemd_digest(&records);
+        zone_digest::load_zonemd(&origin, ZoneType::Master, &mut records, serial)
+            .expect("failed to load zonemd");
+        assert_eq!(zonemd_digest(&records), digest);
+
+        // the placeholder of a signed zone is served as is
+        let mut records = zonemd_example(ZoneType::Master).unwrap_records();
+        let rrsig = Record::from_rdata(
+            origin.clone().into(),
+            86400,
+            RecordType::DNSSEC(DNSSECRecordType::RRSIG),
+            RData::DNSSEC(DNSSECRData::SIG(SIG::new(
+                RecordType::SOA,
+                Algorithm::RSASHA256,
+                1,
+                86400,
+                0,
+                0,
+                0,
+                origin.clone().into(),
+                vec![0; 64],
+            ))),
+        );
+        let soa = RrKey::new(origin.clone(), RecordType::SOA);
+        Arc::make_mut(records.get_mut(&soa).unwrap()).insert_rrsig(rrsig);
+        zone_digest::load_zonemd(&origin, ZoneType::Master, &mut records, serial)
+            .expect("failed to load zonemd");
+        assert_eq!(zonemd_digest(&records), vec![0; 48]);
+    }
 }
@@ -6,7 +6,7 @@
 // copied, modified, or distributed except according to those terms.
 
 /// Configuration for master file based zones
-#[derive(Deserialize, PartialEq, Debug)]
+#[derive(Deserialize, PartialEq, Debug, Clone)]
 pub struct FileConfig {
     /// path to the master file
     pub zone_file_path: String,
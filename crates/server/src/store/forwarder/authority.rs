@@ -0,0 +1,260 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::sync::Mutex;
+use std::time::Instant;
+
+use futures::Future;
+use tokio_executor;
+
+use trust_dns::op::{Message, Query, ResponseCode};
+use trust_dns::rr::dnssec::SupportedAlgorithms;
+use trust_dns::rr::{LowerName, Name, RecordType};
+use trust_dns_resolver::config::{NameServerConfigGroup, ResolverConfig, ResolverOpts};
+use trust_dns_resolver::error::ResolveErrorKind;
+use trust_dns_resolver::recursor::RecursorFuture;
+use trust_dns_resolver::AsyncResolver;
+
+use authority::{AuthLookup, Authority, MessageRequest, UpdateResult, ZoneType};
+use server::count_resolved;
+use store::forwarder::ForwardConfig;
+
+/// ForwardAuthority answers recursive queries with the upstream name servers of its configuration.
+///
+/// No records are served directly by this authority, queries without the RD bit set are refused.
+///  With DNS64 configured, AAAA records are synthesized for names which only have A records.
+pub struct ForwardAuthority {
+    origin: LowerName,
+    resolver: AsyncResolver,
+    /// the background task of the resolver, spawned with the first query
+    background: Mutex<Option<Box<Future<Item = (), Error = ()> + Send>>>,
+}
+
+impl ForwardAuthority {
+    /// Creates a new Authority.
+    ///
+    /// # Arguments
+    ///
+    /// * `origin` - The zone `Name` of the queries which are forwarded, generally the root.
+    /// * `config` - The name servers, and the DNS64 prefixes if AAAA records should be synthesized.
+    /// * `options` - The options of the resolver, see `ForwardConfig::get_resolver_opts`.
+    pub fn new(origin: Name, config: ResolverConfig, mut options: ResolverOpts) -> Self {
+        // the names of the queries are always fully qualified
+        options.ndots = 0;
+
+        let (resolver, background) = AsyncResolver::new(config, options);
+
+        ForwardAuthority {
+            origin: LowerName::new(&origin),
+            resolver,
+            background: Mutex::new(Some(Box::new(background))),
+        }
+    }
+
+    /// Read the Authority for the origin from the specified configuration
+    pub fn try_from_config(origin: Name, config: &ForwardConfig) -> Result<Self, String> {
+        if config.name_servers.is_empty() {
+            return Err(format!("no name servers to forward {} to", origin));
+        }
+
+        let name_servers =
+            NameServerConfigGroup::from_ips_clear(&config.name_servers, config.get_port());
+        let mut resolver_config = ResolverConfig::from_parts(None, vec![], name_servers);
+        if let Some(dns64) = config.get_dns64()? {
+            info!("DNS64 for {} with prefixes: {:?}", origin, dns64.prefixes());
+            resolver_config.set_dns64(dns64);
+        }
+
+        info!("forwarding {} to: {:?}", origin, config.name_servers);
+        Ok(Self::new(
+            origin,
+            resolver_config,
+            config.get_resolver_opts(),
+        ))
+    }
+}
+
+impl Authority for ForwardAuthority {
+    /// What type is this zone
+    fn zone_type(&self) -> ZoneType {
+        ZoneType::Forward
+    }
+
+    /// Return true if AXFR is allowed
+    fn is_axfr_allowed(&self) -> bool {
+        false
+    }
+
+    /// Forward zones can not be updated
+    fn update(&mut self, _update: &MessageRequest) -> UpdateResult<bool> {
+        Err(ResponseCode::NotImp)
+    }
+
+    /// Get the origin of this zone, i.e. example.com is the origin for www.example.com
+    fn origin(&self) -> &LowerName {
+        &self.origin
+    }
+
+    /// There are no records in a forward zone, all non-recursive queries are refused
+    fn lookup(
+        &self,
+        _name: &LowerName,
+        _rtype: RecordType,
+        _is_secure: bool,
+        _supported_algorithms: SupportedAlgorithms,
+    ) -> AuthLookup {
+        AuthLookup::Refused
+    }
+
+    fn get_nsec_records(
+        &self,
+        _name: &LowerName,
+        _is_secure: bool,
+        _supported_algorithms: SupportedAlgorithms,
+    ) -> AuthLookup {
+        AuthLookup::default()
+    }
+
+    /// Looks up the query with the upstream name servers
+    ///
+    /// The answers start with the CNAME records which lead to the name of the records, all with their owner names
+    ///  as received. Responses without records keep the response code, NXDOMAIN or NOERROR, and the SOA record of
+    ///  the authority section, see RFC 2308.
+    fn resolve(&self, query: &Query) -> Option<RecursorFuture> {
+        if let Some(background) = self.background.lock().unwrap().take() {
+            tokio_executor::spawn(background);
+        }
+
+        let query = query.clone();
+        let lookup = self
+            .resolver
+            .lookup(query.name().clone(), query.query_type())
+            .then(move |result| {
+                let mut message = Message::new();
+                message.add_query(query.clone());
+
+                let lookup = match result {
+                    Ok(lookup) => lookup,
+                    Err(e) => match *e.kind() {
+                        ResolveErrorKind::NoRecordsFound { .. } => {
+                            message.set_response_code(
+                                e.response_code().unwrap_or(ResponseCode::NoError),
+                            );
+                            if let Some(soa) = e.soa() {
+                                message.add_name_server(soa.clone());
+                            }
+                            return Ok(message);
+                        }
+                        _ => return Err(e),
+                    },
+                };
+
+                let now = Instant::now();
+                let ttl = if lookup.valid_until() > now {
+                    (lookup.valid_until() - now)
+                        .as_secs()
+                        .min(u64::from(u32::max_value())) as u32
+                } else {
+                    0
+                };
+                let answers = lookup
+                    .records()
+                    .iter()
+                    .filter(|record| {
+                        query.query_type() == RecordType::ANY
+                            || record.rr_type() == query.query_type()
+                            || record.rr_type() == RecordType::CNAME
+                    })
+                    .map(|record| {
+                        let mut record = record.clone();
+                        record.set_ttl(ttl);
+                        record
+                    })
+                    .collect::<Vec<_>>();
+
+                message.insert_answers(answers);
+                Ok(message)
+            });
+
+        Some(count_resolved(&self.origin, Box::new(lookup)))
+    }
+
+    /// Removes all answers of the upstream name servers from the cache of the resolver
+    fn flush_cache(&self) -> bool {
+        if let Err(e) = self.resolver.cache().flush() {
+            warn!("failed to flush the cache of {}: {}", self.origin, e);
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn test_forward_config() {
+        let config = ForwardConfig {
+            name_servers: vec![IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1))],
+            port: None,
+            dns64_prefixes: Some(vec![
+                "64:ff9b::/96".to_string(),
+                "2001:db8:122::/48".to_string(),
+            ]),
+            dns64_exclusions: Some(vec!["2001:db8::/32".to_string()]),
+            cache_size: None,
+            timeout: None,
+            attempts: None,
+        };
+
+        assert_eq!(config.get_port(), 53);
+
+        let dns64 = config.get_dns64().unwrap().expect("dns64 is configured");
+        assert_eq!(dns64.prefixes().len(), 2);
+        assert_eq!(dns64.exclusions().len(), 2);
+
+        let authority = ForwardAuthority::try_from_config(Name::root(), &config)
+            .expect("failed to create authority");
+        assert_eq!(authority.zone_type(), ZoneType::Forward);
+        assert!(authority
+            .lookup(
+                &LowerName::from_str("www.example.com.").unwrap(),
+                RecordType::A,
+                false,
+                SupportedAlgorithms::new(),
+            )
+            .is_refused());
+    }
+
+    #[test]
+    fn test_bad_forward_config() {
+        let config = ForwardConfig {
+            name_servers: vec![IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1))],
+            port: None,
+            dns64_prefixes: Some(vec!["64:ff9b::/95".to_string()]),
+            dns64_exclusions: None,
+            cache_size: None,
+            timeout: None,
+            attempts: None,
+        };
+        assert!(ForwardAuthority::try_from_config(Name::root(), &config).is_err());
+
+        let config = ForwardConfig {
+            name_servers: vec![],
+            port: None,
+            dns64_prefixes: None,
+            dns64_exclusions: None,
+            cache_size: None,
+            timeout: None,
+            attempts: None,
+        };
+        assert!(ForwardAuthority::try_from_config(Name::root(), &config).is_err());
+    }
+}
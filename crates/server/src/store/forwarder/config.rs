@@ -0,0 +1,78 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use trust_dns_resolver::config::ResolverOpts;
+use trust_dns_resolver::dns64::{Dns64Config, Dns64Exclusion, Dns64Prefix};
+
+use store::resolver_opts;
+
+/// Configuration for forward zones, i.e. recursive resolution by upstream name servers
+#[derive(Deserialize, PartialEq, Debug, Clone)]
+pub struct ForwardConfig {
+    /// addresses of the upstream name servers, queried over UDP and TCP
+    pub name_servers: Vec<IpAddr>,
+    /// port on which all name servers are queried, defaults to 53
+    pub port: Option<u16>,
+    /// DNS64 prefixes, e.g. "64:ff9b::/96", AAAA records are synthesized for names without them when set
+    pub dns64_prefixes: Option<Vec<String>>,
+    /// networks of AAAA records which are ignored for DNS64, in addition to "::ffff:0:0/96"
+    pub dns64_exclusions: Option<Vec<String>>,
+    /// number of responses cached, defaults to 1024
+    pub cache_size: Option<usize>,
+    /// seconds to wait for the response of a name server
+    pub timeout: Option<u64>,
+    /// number of times a query is sent to the name servers before it fails
+    pub attempts: Option<usize>,
+}
+
+impl ForwardConfig {
+    /// port on which all name servers are queried
+    pub fn get_port(&self) -> u16 {
+        self.port.unwrap_or(53)
+    }
+
+    /// the options of the resolver, the hosts file of the server is never used
+    pub fn get_resolver_opts(&self) -> ResolverOpts {
+        resolver_opts(self.cache_size, self.timeout, self.attempts)
+    }
+
+    /// The DNS64 configuration, `None` if no prefixes are configured
+    pub fn get_dns64(&self) -> Result<Option<Dns64Config>, String> {
+        let prefixes = match self.dns64_prefixes {
+            Some(ref prefixes) => prefixes,
+            None => {
+                if self.dns64_exclusions.is_some() {
+                    warn!("ignoring dns64_exclusions without dns64_prefixes");
+                }
+                return Ok(None);
+            }
+        };
+
+        let prefixes = prefixes
+            .iter()
+            .map(|prefix| {
+                Dns64Prefix::from_str(prefix)
+                    .map_err(|e| format!("bad dns64 prefix {}: {}", prefix, e))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut dns64 = Dns64Config::new(prefixes);
+        for exclusion in self.dns64_exclusions.iter().flat_map(|e| e.iter()) {
+            let exclusion = Dns64Exclusion::from_str(exclusion)
+                .map_err(|e| format!("bad dns64 exclusion {}: {}", exclusion, e))?;
+            dns64.add_exclusion(exclusion);
+        }
+
+        dns64
+            .validate()
+            .map_err(|e| format!("bad dns64 configuration: {}", e))?;
+        Ok(Some(dns64))
+    }
+}
@@ -0,0 +1,229 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! All authority related types
+
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use trust_dns::op::ResponseCode;
+use trust_dns::rr::dnssec::SupportedAlgorithms;
+use trust_dns::rr::{LowerName, Name, RData, RecordSet, RecordType, RrKey};
+use trust_dns_resolver::config::ResolverOpts;
+use trust_dns_resolver::Recursor;
+
+use authority::{AuthLookup, Authority, LookupRecords, MessageRequest, UpdateResult, ZoneType};
+use store::hint::HintConfig;
+
+/// HintAuthority answers recursive queries by iterating from the root servers.
+///
+/// The root hints, i.e. the NS records of the origin and the addresses of those name servers, are
+///  the only records served directly by this authority. All queries with the RD bit set are
+///  resolved with the `Recursor`.
+pub struct HintAuthority {
+    origin: LowerName,
+    records: BTreeMap<RrKey, Arc<RecordSet>>,
+    recursor: Recursor,
+}
+
+impl HintAuthority {
+    /// Creates a new Authority.
+    ///
+    /// # Arguments
+    ///
+    /// * `origin` - The zone `Name` of the hints, generally the root.
+    /// * `records` - The root hints, NS records for the origin and addresses of the name servers.
+    /// * `port` - The port on which all name servers are queried.
+    /// * `options` - The timeouts, attempts and cache size of the recursor.
+    ///
+    /// # Return value
+    ///
+    /// The new `Authority`, or an error if there are no addresses for the name servers.
+    pub fn new(
+        origin: Name,
+        records: BTreeMap<RrKey, RecordSet>,
+        port: u16,
+        options: ResolverOpts,
+    ) -> Result<Self, String> {
+        let origin = LowerName::new(&origin);
+        let records: BTreeMap<RrKey, Arc<RecordSet>> = records
+            .into_iter()
+            .map(|(key, rrset)| (key, Arc::new(rrset)))
+            .collect();
+
+        let root_hints = Self::root_addresses(&origin, &records);
+        if root_hints.is_empty() {
+            return Err(format!("no addresses for the name servers of {}", origin));
+        }
+
+        info!("root hints for {}: {:?}", origin, root_hints);
+        let recursor = Recursor::new(&root_hints, port, options);
+
+        Ok(HintAuthority {
+            origin,
+            records,
+            recursor,
+        })
+    }
+
+    /// Read the Authority for the origin from the specified configuration
+    pub fn try_from_config(
+        origin: Name,
+        root_dir: Option<&Path>,
+        config: &HintConfig,
+    ) -> Result<Self, String> {
+        use std::fs::File;
+        use std::io::Read;
+        use trust_dns::serialize::txt::{Lexer, Parser};
+
+        let hints_path = root_dir
+            .map(PathBuf::from)
+            .unwrap_or_else(PathBuf::new)
+            .join(&config.root_hints_path);
+
+        info!("loading root hints: {:?}", hints_path);
+
+        let mut file = File::open(&hints_path)
+            .map_err(|e| format!("error opening {}: {:?}", hints_path.display(), e))?;
+
+        let mut buf = String::new();
+        file.read_to_string(&mut buf)
+            .map_err(|e| format!("failed to read {}: {:?}", hints_path.display(), e))?;
+        let lexer = Lexer::new(&buf);
+        let (origin, records) = Parser::new()
+            .parse(lexer, Some(origin))
+            .map_err(|e| format!("failed to parse {}: {:?}", hints_path.display(), e))?;
+
+        Self::new(
+            origin,
+            records,
+            config.get_port(),
+            config.get_resolver_opts(),
+        )
+    }
+
+    /// The addresses of the name servers listed in the NS records of the origin
+    fn root_addresses(origin: &LowerName, records: &BTreeMap<RrKey, Arc<RecordSet>>) -> Vec<IpAddr> {
+        let ns_names = records
+            .get(&RrKey::new(origin.clone(), RecordType::NS))
+            .into_iter()
+            .flat_map(|rrset| rrset.records_without_rrsigs())
+            .filter_map(|record| match *record.rdata() {
+                RData::NS(ref name) => Some(LowerName::new(name)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        let mut addresses = vec![];
+        for ns_name in ns_names {
+            for rtype in &[RecordType::A, RecordType::AAAA] {
+                let rrset = match records.get(&RrKey::new(ns_name.clone(), *rtype)) {
+                    Some(rrset) => rrset,
+                    None => continue,
+                };
+
+                addresses.extend(rrset.records_without_rrsigs().filter_map(|record| {
+                    match *record.rdata() {
+                        RData::A(ip) => Some(IpAddr::V4(ip)),
+                        RData::AAAA(ip) => Some(IpAddr::V6(ip)),
+                        _ => None,
+                    }
+                }));
+            }
+        }
+
+        addresses
+    }
+}
+
+impl Authority for HintAuthority {
+    /// What type is this zone
+    fn zone_type(&self) -> ZoneType {
+        ZoneType::Hint
+    }
+
+    /// Return true if AXFR is allowed
+    fn is_axfr_allowed(&self) -> bool {
+        false
+    }
+
+    /// Hints can not be updated
+    fn update(&mut self, _update: &MessageRequest) -> UpdateResult<bool> {
+        Err(ResponseCode::NotImp)
+    }
+
+    /// Get the origin of this zone, i.e. example.com is the origin for www.example.com
+    fn origin(&self) -> &LowerName {
+        &self.origin
+    }
+
+    /// Looks up the root hints, any other non-recursive query is refused
+    fn lookup(
+        &self,
+        name: &LowerName,
+        rtype: RecordType,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+    ) -> AuthLookup {
+        self.records
+            .get(&RrKey::new(name.clone(), rtype))
+            .map_or(AuthLookup::Refused, |rrset| {
+                LookupRecords::new(is_secure, supported_algorithms, rrset.clone()).into()
+            })
+    }
+
+    fn get_nsec_records(
+        &self,
+        _name: &LowerName,
+        _is_secure: bool,
+        _supported_algorithms: SupportedAlgorithms,
+    ) -> AuthLookup {
+        AuthLookup::default()
+    }
+
+    fn recursor(&self) -> Option<&Recursor> {
+        Some(&self.recursor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_load_root_hints() {
+        let config = HintConfig::new("tests/named_test_configs/root.hints".to_string());
+        let authority = HintAuthority::try_from_config(Name::root(), None, &config)
+            .expect("failed to load hints");
+
+        assert_eq!(
+            HintAuthority::root_addresses(authority.origin(), &authority.records),
+            vec![IpAddr::V4(Ipv4Addr::new(127, 0, 0, 2))]
+        );
+        assert!(authority.recursor().is_some());
+
+        let lookup = authority.lookup(
+            &LowerName::from_str("a.root-servers.test.").unwrap(),
+            RecordType::A,
+            false,
+            SupportedAlgorithms::new(),
+        );
+        assert_eq!(lookup.iter().count(), 1);
+
+        let lookup = authority.lookup(
+            &LowerName::from_str("www.example.com.").unwrap(),
+            RecordType::A,
+            false,
+            SupportedAlgorithms::new(),
+        );
+        assert!(lookup.is_refused());
+    }
+}
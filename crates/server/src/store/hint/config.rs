@@ -0,0 +1,48 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use trust_dns_resolver::config::ResolverOpts;
+
+use store::resolver_opts;
+
+/// Configuration for hint zones, i.e. recursive resolution from the root servers
+#[derive(Deserialize, PartialEq, Debug, Clone)]
+pub struct HintConfig {
+    /// path to the root hints file, a master file with the NS and addresses of the root servers
+    pub root_hints_path: String,
+    /// port on which all name servers are queried, defaults to 53
+    pub port: Option<u16>,
+    /// number of responses cached, defaults to 1024
+    pub cache_size: Option<usize>,
+    /// seconds to wait for the response of a name server
+    pub timeout: Option<u64>,
+    /// number of times a query is sent to the name servers before it fails
+    pub attempts: Option<usize>,
+}
+
+impl HintConfig {
+    /// Creates a configuration with the default options for the root hints file at `path`
+    pub fn new(root_hints_path: String) -> Self {
+        HintConfig {
+            root_hints_path,
+            port: None,
+            cache_size: None,
+            timeout: None,
+            attempts: None,
+        }
+    }
+
+    /// port on which all name servers are queried
+    pub fn get_port(&self) -> u16 {
+        self.port.unwrap_or(53)
+    }
+
+    /// the options of the recursor, the hosts file of the server is never used
+    pub fn get_resolver_opts(&self) -> ResolverOpts {
+        resolver_opts(self.cache_size, self.timeout, self.attempts)
+    }
+}
@@ -9,8 +9,11 @@
 
 mod config;
 pub mod file;
+pub mod forwarder;
+pub mod hint;
 pub mod sqlite;
 
 // TODO: add a dynamic library option?
 
+pub(crate) use self::config::resolver_opts;
 pub use self::config::StoreConfig;
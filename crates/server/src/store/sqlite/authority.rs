@@ -20,6 +20,8 @@ use trust_dns::op::ResponseCode;
 use trust_dns::rr::dnssec::{DnsSecResult, Signer, SupportedAlgorithms};
 use trust_dns::rr::{DNSClass, LowerName, Name, RData, Record, RecordSet, RecordType, RrKey};
 
+#[cfg(feature = "dnssec")]
+use authority::zone_digest;
 #[cfg(feature = "dnssec")]
 use authority::UpdateRequest;
 use authority::{
@@ -124,6 +126,17 @@ impl SqliteAuthority {
                 .recover_with_journal(&journal)
                 .map_err(|e| format!("error recovering from journal: {}", e))?;
 
+            #[cfg(any(feature = "dnssec-openssl", feature = "dnssec-ring"))]
+            {
+                let serial = authority.serial();
+                zone_digest::load_zonemd(
+                    &authority.origin,
+                    zone_type,
+                    &mut authority.records,
+                    serial,
+                )?;
+            }
+
             authority.set_journal(journal);
             info!("recovered zone: {}", zone_name);
 
@@ -876,6 +889,12 @@ impl SqliteAuthority {
                 // the secure_zone() function increments the SOA during it's operation, if we're not
                 //  dnssec, then we need to do it here...
                 self.increment_soa_serial();
+
+                #[cfg(any(feature = "dnssec-openssl", feature = "dnssec-ring"))]
+                self.update_zonemd().map_err(|e| {
+                    error!("failure updating zonemd: {}", e);
+                    ResponseCode::ServFail
+                })?;
             }
         }
 
@@ -990,8 +1009,6 @@ impl SqliteAuthority {
     #[cfg(feature = "dnssec")]
     fn sign_zone(&mut self) -> DnsSecResult<()> {
         use chrono::Utc;
-        use trust_dns::rr::dnssec::tbs;
-        use trust_dns::rr::rdata::{DNSSECRData, DNSSECRecordType, SIG};
 
         debug!("signing zone: {}", self.origin);
         let inception = Utc::now();
@@ -1007,90 +1024,42 @@ impl SqliteAuthority {
             let mut rr_set = RecordSet::clone(&*rr_set_orig);
             // becuase the rrset is an Arc, it must be cloned before mutated
 
-            rr_set.clear_rrsigs();
-            let rrsig_temp = Record::with(
-                rr_set.name().clone(),
-                RecordType::DNSSEC(DNSSECRecordType::RRSIG),
+            zone_digest::sign_rrset(
+                &mut rr_set,
+                &self.secure_keys,
                 zone_ttl,
-            );
-
-            for signer in &self.secure_keys {
-                debug!(
-                    "signing rr_set: {}, {} with: {}",
-                    rr_set.name(),
-                    rr_set.record_type(),
-                    signer.algorithm(),
-                );
-
-                let expiration = inception + signer.sig_duration();
-
-                let tbs = tbs::rrset_tbs(
-                    rr_set.name(),
-                    self.class,
-                    rr_set.name().num_labels(),
-                    rr_set.record_type(),
-                    signer.algorithm(),
-                    rr_set.ttl(),
-                    expiration.timestamp() as u32,
-                    inception.timestamp() as u32,
-                    signer.calculate_key_tag()?,
-                    signer.signer_name(),
-                    // TODO: this is a nasty clone... the issue is that the vec
-                    //  from records is of Vec<&R>, but we really want &[R]
-                    &rr_set
-                        .records_without_rrsigs()
-                        .cloned()
-                        .collect::<Vec<Record>>(),
-                );
-
-                // TODO, maybe chain these with some ETL operations instead?
-                let tbs = match tbs {
-                    Ok(tbs) => tbs,
-                    Err(err) => {
-                        error!("could not serialize rrset to sign: {}", err);
-                        continue;
-                    }
-                };
-
-                let signature = signer.sign(&tbs);
-                let signature = match signature {
-                    Ok(signature) => signature,
-                    Err(err) => {
-                        error!("could not sign rrset: {}", err);
-                        continue;
-                    }
-                };
-
-                let mut rrsig = rrsig_temp.clone();
-                rrsig.set_rdata(RData::DNSSEC(DNSSECRData::SIG(SIG::new(
-                    // type_covered: RecordType,
-                    rr_set.record_type(),
-                    // algorithm: Algorithm,
-                    signer.algorithm(),
-                    // num_labels: u8,
-                    rr_set.name().num_labels(),
-                    // original_ttl: u32,
-                    rr_set.ttl(),
-                    // sig_expiration: u32,
-                    expiration.timestamp() as u32,
-                    // sig_inception: u32,
-                    inception.timestamp() as u32,
-                    // key_tag: u16,
-                    signer.calculate_key_tag()?,
-                    // signer_name: Name,
-                    signer.signer_name().clone(),
-                    // sig: Vec<u8>
-                    signature,
-                ))));
-
-                rr_set.insert_rrsig(rrsig);
-            }
+                self.class,
+                inception,
+            )?;
 
             *rr_set_orig = Arc::new(rr_set);
         }
 
         Ok(())
     }
+
+    /// Recomputes the digests of the ZONEMD records at the apex of the zone, see `zone_digest::update_zonemd`
+    ///
+    /// The digest covers the RRSIGs, so this must be called after the zone was signed.
+    #[cfg(any(feature = "dnssec-openssl", feature = "dnssec-ring"))]
+    pub fn update_zonemd(&mut self) -> DnsSecResult<()> {
+        let serial = self.serial();
+        let zone_ttl = self.minimum_ttl();
+        zone_digest::update_zonemd(
+            &self.origin,
+            &mut self.records,
+            serial,
+            &self.secure_keys,
+            zone_ttl,
+            self.class,
+        )
+    }
+
+    /// Verifies the zone with the ZONEMD records at its apex, RFC 8976 section 4
+    #[cfg(any(feature = "dnssec-openssl", feature = "dnssec-ring"))]
+    pub fn verify_zonemd(&self) -> DnsSecResult<()> {
+        zone_digest::verify_zonemd(&self.origin, &self.records)
+    }
 }
 
 impl Authority for SqliteAuthority {
@@ -1345,6 +1314,12 @@ impl Authority for SqliteAuthority {
         AuthLookup::default()
     }
 
+    fn has_delegations(&self) -> bool {
+        self.records
+            .keys()
+            .any(|key| key.record_type == RecordType::NS && key.name != self.origin)
+    }
+
     #[cfg(feature = "dnssec")]
     fn add_update_auth_key(&mut self, name: Name, key: KEY) -> DnsSecResult<()> {
         let rdata = RData::DNSSEC(DNSSECRData::KEY(key));
@@ -1407,7 +1382,13 @@ impl Authority for SqliteAuthority {
         self.increment_soa_serial();
 
         // TODO: should we auto sign here? or maybe up a level...
-        self.sign_zone()
+        self.sign_zone()?;
+
+        // the digest covers the RRSIGs, so the ZONEMD is updated last
+        #[cfg(any(feature = "dnssec-openssl", feature = "dnssec-ring"))]
+        self.update_zonemd()?;
+
+        Ok(())
     }
 
     /// (Re)generates the nsec records, increments the serial number nad signs the zone
@@ -6,7 +6,7 @@
 // copied, modified, or distributed except according to those terms.
 
 /// Configuration for master file for sqlite based zones
-#[derive(Deserialize, PartialEq, Debug)]
+#[derive(Deserialize, PartialEq, Debug, Clone)]
 pub struct SqliteConfig {
     /// path to initial master zone file
     pub zone_file_path: String,
@@ -19,11 +19,11 @@ extern crate trust_dns_proto;
 extern crate trust_dns_server;
 
 use std::env;
-use std::net::{Ipv4Addr, Ipv6Addr};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::path::{Path, PathBuf};
 use std::time::Duration;
 
-use trust_dns_server::authority::ZoneType;
+use trust_dns_server::authority::{NetworkPrefix, TsigAlgorithm, ZoneType};
 use trust_dns_server::config::*;
 
 #[test]
@@ -155,6 +155,256 @@ fn test_parse_toml() {
     assert_eq!(config.get_directory(), Path::new("/dev/null"));
 }
 
+#[test]
+fn test_parse_views() {
+    let config: Config = "
+[[tsig_keys]]
+name = \"internal-key\"
+secret = \"c2VjcmV0\"
+
+[[views]]
+name = \"internal\"
+match_clients = [\"10.0.0.0/8\", \"::1\"]
+match_destinations = [\"192.0.2.53\"]
+match_keys = [\"internal-key\"]
+
+[[views.zones]]
+zone = \"example.com\"
+zone_type = \"Master\"
+file = \"example.com.zone\"
+
+[[views]]
+name = \"external\"
+
+[[views.zones]]
+zone = \"example.com\"
+zone_type = \"Master\"
+file = \"example.com.zone\"
+"
+    .parse()
+    .unwrap();
+
+    let views = config.get_views();
+    assert_eq!(views.len(), 2);
+    assert_eq!(views[0].get_name(), "internal");
+    assert_eq!(
+        views[0].get_match_clients().unwrap(),
+        vec![
+            "10.0.0.0/8".parse::<NetworkPrefix>().unwrap(),
+            "::1/128".parse::<NetworkPrefix>().unwrap(),
+        ]
+    );
+    assert_eq!(
+        views[0].get_match_destinations().unwrap(),
+        vec!["192.0.2.53/32".parse::<NetworkPrefix>().unwrap()]
+    );
+    let keys = views[0].get_match_keys(config.get_tsig_keys()).unwrap();
+    assert_eq!(keys.len(), 1);
+    assert_eq!(keys[0].name().to_string(), "internal-key.");
+    assert_eq!(keys[0].algorithm(), TsigAlgorithm::HmacSha256);
+    assert_eq!(views[0].get_zones()[0].get_file(), PathBuf::from("example.com.zone"));
+
+    assert_eq!(views[1].get_name(), "external");
+    assert!(views[1].get_match_clients().unwrap().is_empty());
+    assert_eq!(views[1].get_zones()[0].get_file(), PathBuf::from("example.com.zone"));
+
+    assert!("[[views]]\nname = \"bad\"\nmatch_clients = [\"10.0.0.0/40\"]"
+        .parse::<Config>()
+        .unwrap()
+        .get_views()[0]
+        .get_match_clients()
+        .is_err());
+
+    // keys must be configured
+    assert!(views[1]
+        .get_match_keys(config.get_tsig_keys())
+        .unwrap()
+        .is_empty());
+    assert!("[[views]]\nname = \"keys\"\nmatch_keys = [\"other-key\"]"
+        .parse::<Config>()
+        .unwrap()
+        .get_views()[0]
+        .get_match_keys(config.get_tsig_keys())
+        .is_err());
+}
+
+#[test]
+fn test_parse_rate_limit() {
+    let config: Config = "".parse().unwrap();
+    assert!(config.get_rate_limit().is_none());
+
+    let config: Config = "
+[rate_limit]
+responses_per_second = 5
+errors_per_second = 2
+slip = 3
+ipv6_prefix_length = 48
+log_only = true
+exempt_clients = [\"10.0.0.0/8\"]
+"
+    .parse()
+    .unwrap();
+
+    let rate_limit = config.get_rate_limit().unwrap();
+    assert_eq!(rate_limit.get_responses_per_second(), 5);
+    assert_eq!(rate_limit.get_nxdomains_per_second(), 5);
+    assert_eq!(rate_limit.get_errors_per_second(), 2);
+    assert_eq!(rate_limit.get_slip(), 3);
+    assert_eq!(rate_limit.get_ipv4_prefix_length(), 24);
+    assert_eq!(rate_limit.get_ipv6_prefix_length(), 48);
+    assert!(rate_limit.is_log_only());
+    assert_eq!(
+        rate_limit.get_exempt_clients().unwrap(),
+        vec!["10.0.0.0/8".parse::<NetworkPrefix>().unwrap()]
+    );
+}
+
+#[test]
+fn test_parse_control_socket() {
+    let config: Config = "".parse().unwrap();
+    assert!(config.get_control_socket().is_none());
+
+    let config: Config = "control_socket = \"/var/run/named.sock\"".parse().unwrap();
+    assert_eq!(
+        config.get_control_socket(),
+        Some(Path::new("/var/run/named.sock"))
+    );
+}
+
+#[test]
+fn test_parse_referrals() {
+    let config: Config = "referrals = true".parse().unwrap();
+    assert!(config.is_referrals_enabled());
+}
+
+#[test]
+fn test_parse_slave_zone() {
+    let config: Config = "
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Slave\"
+file = \"example.com.zone\"
+masters = [\"192.0.2.1:53\", \"[2001:db8::1]:5353\"]
+also_notify = [\"192.0.2.2:53\"]
+"
+    .parse()
+    .unwrap();
+
+    let zone = &config.get_zones()[0];
+    assert_eq!(zone.get_zone_type(), ZoneType::Slave);
+    assert_eq!(
+        zone.get_masters(),
+        [
+            "192.0.2.1:53".parse::<SocketAddr>().unwrap(),
+            "[2001:db8::1]:5353".parse::<SocketAddr>().unwrap(),
+        ]
+    );
+    assert_eq!(
+        zone.get_also_notify(),
+        ["192.0.2.2:53".parse::<SocketAddr>().unwrap()]
+    );
+}
+
+#[test]
+fn test_parse_hint_zone() {
+    use trust_dns_server::store::StoreConfig;
+
+    let config: Config = "
+allow_recursion = [\"127.0.0.0/8\", \"::1/128\"]
+
+[[zones]]
+zone = \".\"
+zone_type = \"Hint\"
+stores = { type = \"hint\", root_hints_path = \"root.hints\", port = 5353, cache_size = 4096 }
+"
+    .parse()
+    .unwrap();
+
+    let allow_recursion = config.get_allow_recursion().unwrap();
+    assert_eq!(allow_recursion.len(), 2);
+    assert!(allow_recursion[0].contains("127.0.0.2".parse().unwrap()));
+    assert!(!allow_recursion[0].contains("192.0.2.1".parse().unwrap()));
+    assert!(!config.is_referrals_enabled());
+
+    let zone = &config.get_zones()[0];
+    assert_eq!(zone.get_zone_type(), ZoneType::Hint);
+    let hint_config = match zone.stores {
+        Some(StoreConfig::Hint(ref hint_config)) => hint_config,
+        ref stores => panic!("expected hint store: {:?}", stores),
+    };
+    assert_eq!(hint_config.root_hints_path, "root.hints");
+    assert_eq!(hint_config.get_port(), 5353);
+
+    let options = hint_config.get_resolver_opts();
+    assert_eq!(options.cache_size, 4096);
+    assert!(!options.use_hosts_file);
+}
+
+#[test]
+fn test_parse_forward_zone() {
+    use trust_dns_server::store::forwarder::ForwardConfig;
+    use trust_dns_server::store::StoreConfig;
+
+    let config: Config = "
+[[zones]]
+zone = \".\"
+zone_type = \"Forward\"
+stores = { type = \"forward\", name_servers = [\"192.0.2.1\", \"2001:db8::1\"], dns64_prefixes = [\"64:ff9b::/96\"], cache_size = 4096, timeout = 2 }
+"
+    .parse()
+    .unwrap();
+
+    let zone = &config.get_zones()[0];
+    assert_eq!(zone.get_zone_type(), ZoneType::Forward);
+    assert_eq!(
+        zone.stores,
+        Some(StoreConfig::Forward(ForwardConfig {
+            name_servers: vec![
+                IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)),
+                IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)),
+            ],
+            port: None,
+            dns64_prefixes: Some(vec!["64:ff9b::/96".to_string()]),
+            dns64_exclusions: None,
+            cache_size: Some(4096),
+            timeout: Some(2),
+            attempts: None,
+        }))
+    );
+
+    let options = match zone.stores {
+        Some(StoreConfig::Forward(ref forward_config)) => forward_config.get_resolver_opts(),
+        ref stores => panic!("expected forward store: {:?}", stores),
+    };
+    assert_eq!(options.cache_size, 4096);
+    assert_eq!(options.timeout, Duration::from_secs(2));
+    assert!(!options.use_hosts_file);
+}
+
+#[test]
+fn test_parse_query_log() {
+    let config: Config = "".parse().unwrap();
+    assert!(config.get_query_log().is_none());
+
+    let config: Config = "
+[query_log]
+json_path = \"-\"
+dnstap_socket = \"/var/run/dnstap.sock\"
+dnstap_identity = \"ns1\"
+"
+    .parse()
+    .unwrap();
+    let query_log = config.get_query_log().unwrap();
+    assert_eq!(query_log.get_json_path(), Some(Path::new("-")));
+    assert_eq!(query_log.get_dnstap_path(), None);
+    assert_eq!(
+        query_log.get_dnstap_socket(),
+        Some(Path::new("/var/run/dnstap.sock"))
+    );
+    assert_eq!(query_log.get_dnstap_identity(), Some("ns1"));
+    assert_eq!(query_log.get_queue_size(), 10_000);
+}
+
 #[cfg(feature = "dnssec")]
 #[test]
 fn test_parse_zone_keys() {
@@ -274,6 +524,7 @@ macro_rules! define_test_config {
 }
 
 define_test_config!(all_supported_dnssec);
+define_test_config!(control);
 define_test_config!(dns_over_https);
 define_test_config!(dns_over_tls_rustls_and_openssl);
 define_test_config!(dns_over_tls);
@@ -282,6 +533,11 @@ define_test_config!(dnssec_with_update_deprecated);
 define_test_config!(example);
 define_test_config!(ipv4_and_ipv6);
 define_test_config!(ipv4_only);
+define_test_config!(metrics);
 define_test_config!(ipv6_only);
 define_test_config!(openssl_dnssec);
+define_test_config!(query_log);
+define_test_config!(recursion_hierarchy);
 define_test_config!(ring_dnssec);
+define_test_config!(views);
+
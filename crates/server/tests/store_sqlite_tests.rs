@@ -69,4 +69,72 @@ basic_battery!(sqlite);
 #[cfg(feature = "dnssec")]
 dnssec_battery!(sqlite);
 #[cfg(feature = "dnssec")]
-dynamic_update!(sqlite_update);
\ No newline at end of file
+dynamic_update!(sqlite_update);
+#[test]
+#[cfg(any(feature = "dnssec-openssl", feature = "dnssec-ring"))]
+fn test_update_recomputes_zonemd() {
+    use std::collections::BTreeMap;
+    use std::net::Ipv4Addr;
+
+    use trust_dns::rr::rdata::zonemd::{ZonemdHashAlgorithm, ZonemdScheme};
+    use trust_dns::rr::rdata::{SOA, ZONEMD};
+    use trust_dns::rr::{RData, Record, RecordType};
+
+    let origin = Name::from_str("example.").unwrap();
+    let ns = Name::from_str("ns.example.").unwrap();
+
+    // an unsigned zone, without DNSSEC
+    let mut authority = SqliteAuthority::new(
+        origin.clone(),
+        BTreeMap::new(),
+        ZoneType::Master,
+        true,
+        false,
+        false,
+    );
+    authority.upsert(
+        Record::from_rdata(
+            origin.clone(),
+            86400,
+            RecordType::SOA,
+            RData::SOA(SOA::new(
+                ns.clone(),
+                Name::from_str("admin.example.").unwrap(),
+                1,
+                1800,
+                900,
+                604800,
+                86400,
+            )),
+        ),
+        1,
+    );
+    authority.upsert(
+        Record::from_rdata(
+            origin.clone(),
+            86400,
+            RecordType::ZONEMD,
+            RData::ZONEMD(ZONEMD::new(
+                0,
+                ZonemdScheme::Simple,
+                ZonemdHashAlgorithm::SHA384,
+                vec![0; 48],
+            )),
+        ),
+        1,
+    );
+    authority.update_zonemd().expect("failed to update zonemd");
+
+    let www = Record::from_rdata(
+        Name::from_str("www.example.").unwrap(),
+        3600,
+        RecordType::A,
+        RData::A(Ipv4Addr::new(192, 0, 2, 1)),
+    );
+    let updated = authority.update_records(&[www], true).expect("update failed");
+    assert!(updated);
+
+    // the serial was incremented and the digest covers the new record
+    assert_eq!(authority.serial(), 2);
+    authority.verify_zonemd().expect("failed to verify zonemd");
+}
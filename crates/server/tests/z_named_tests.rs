@@ -26,7 +26,7 @@ use std::str::FromStr;
 use tokio::runtime::current_thread::Runtime;
 
 use trust_dns::client::*;
-use trust_dns::op::ResponseCode;
+use trust_dns::op::{Message, ResponseCode};
 use trust_dns::rr::*;
 use trust_dns::tcp::TcpClientStream;
 use trust_dns::udp::UdpClientStream;
@@ -35,7 +35,7 @@ use trust_dns::udp::UdpClientStream;
 // #[cfg(feature = "dns-over-openssl")]
 // use trust_dns_openssl::TlsClientStreamBuilder;
 
-use server_harness::{named_test_harness, query_a};
+use server_harness::{named_test_harness, query_a, query_message};
 
 #[test]
 fn test_example_toml_startup() {
@@ -87,6 +87,302 @@ fn test_ipv4_only_toml_startup() {
     })
 }
 
+#[test]
+fn test_views_toml_startup() {
+    named_test_harness("views.toml", |port, _, _| {
+        let mut io_loop = Runtime::new().unwrap();
+        let addr: SocketAddr = SocketAddr::new(Ipv4Addr::new(127, 0, 0, 1).into(), port);
+        let (stream, sender) = TcpClientStream::new(addr);
+        let (bg, mut client) = ClientFuture::new(Box::new(stream), sender, None);
+
+        io_loop.spawn(bg);
+
+        // localhost clients are served from the internal view
+        query_a(&mut io_loop, &mut client);
+    })
+}
+
+#[cfg(unix)]
+#[test]
+fn test_control_socket() {
+    use std::io::{Read, Write};
+    use std::net::Shutdown;
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::net::UnixStream;
+    use std::path::Path;
+
+    use trust_dns_server::server::{send_command, ControlCommand};
+
+    named_test_harness("control.toml", |_, _, _| {
+        let socket = Path::new("../../target/named_control_test.sock");
+        let send = |command: &str| {
+            send_command(socket, &command.parse::<ControlCommand>().unwrap())
+                .expect("could not send control command")
+        };
+
+        // only the user running named may connect
+        let mode = std::fs::metadata(socket).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        let status = send("status").expect("status failed");
+        assert!(status.contains("example.com. in view default: type: Master"));
+        assert!(!status.contains("frozen"));
+
+        send("freeze example.com.").expect("freeze failed");
+        assert!(send("status example.com.").unwrap().contains("frozen"));
+        send("thaw example.com.").expect("thaw failed");
+        assert!(!send("status example.com.").unwrap().contains("frozen"));
+
+        assert!(send("reload example.com.").unwrap().contains("is up to date"));
+        assert!(send("status example.org.").is_err());
+        assert!(send("sign example.org.").is_err());
+        assert!(send("stats").unwrap().contains("view default: zones: 1"));
+
+        // commands longer than the limit of 1024 bytes are rejected, not truncated
+        let mut stream = UnixStream::connect(socket).expect("could not connect");
+        write!(stream, "status {}", "a".repeat(1025 - "status ".len())).unwrap();
+        stream.shutdown(Shutdown::Write).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("error\ncommand is longer than"));
+    })
+}
+
+#[cfg(unix)]
+#[test]
+fn test_control_retransfer() {
+    use std::path::Path;
+
+    use trust_dns_server::server::{send_command, ControlCommand};
+
+    named_test_harness("axfr.toml", |master_port, _, _| {
+        let config = format!(
+            "control_socket = \"../../target/named_slave_test.sock\"
+
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Slave\"
+file = \"example.com.zone\"
+masters = [\"127.0.0.1:{port}\"]
+also_notify = [\"127.0.0.1:{port}\"]
+",
+            port = master_port
+        );
+        std::fs::write("../../target/named_slave_test.toml", config)
+            .expect("failed to write slave config");
+
+        // relative to the named_test_configs directory
+        named_test_harness("../../../../target/named_slave_test.toml", |port, _, _| {
+            let socket = Path::new("../../target/named_slave_test.sock");
+            let send = |command: &str| {
+                send_command(socket, &command.parse::<ControlCommand>().unwrap())
+                    .expect("could not send control command")
+            };
+
+            let transferred = send("retransfer example.com.").expect("retransfer failed");
+            assert!(transferred.contains(&format!(
+                "example.com. in view default: transferred serial 199609203 from 127.0.0.1:{}",
+                master_port
+            )));
+
+            let mut io_loop = Runtime::new().unwrap();
+            let addr: SocketAddr = SocketAddr::new(Ipv4Addr::new(127, 0, 0, 1).into(), port);
+            let (stream, sender) = TcpClientStream::new(addr);
+            let (bg, mut client) = ClientFuture::new(Box::new(stream), sender, None);
+
+            io_loop.spawn(bg);
+            query_a(&mut io_loop, &mut client);
+
+            // the name server of the zone is its primary master, only also_notify is notified,
+            //  named itself answers NOTIFY messages with NotImp
+            let notified = send("notify example.com.").expect("notify failed");
+            assert_eq!(
+                notified,
+                format!(
+                    "example.com. in view default: notified 127.0.0.1:{}: NotImp\n",
+                    master_port
+                )
+            );
+
+            assert!(send("retransfer example.org.").is_err());
+            assert!(send("flush example.com.").is_err());
+        })
+    })
+}
+
+#[test]
+fn test_metrics_endpoint() {
+    use std::io::Read;
+
+    named_test_harness("metrics.toml", |port, _, _| {
+        let mut io_loop = Runtime::new().unwrap();
+        let addr: SocketAddr = SocketAddr::new(Ipv4Addr::new(127, 0, 0, 1).into(), port);
+        let (stream, sender) = TcpClientStream::new(addr);
+        let (bg, mut client) = ClientFuture::new(Box::new(stream), sender, None);
+
+        io_loop.spawn(bg);
+        query_a(&mut io_loop, &mut client);
+
+        // zone transfers are not allowed
+        let name = Name::from_str("example.com").unwrap();
+        let response = query_message(&mut io_loop, &mut client, name, RecordType::AXFR);
+        assert_eq!(response.response_code(), ResponseCode::Refused);
+
+        let mut metrics = std::net::TcpStream::connect("127.0.0.1:24153").unwrap();
+        metrics
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        metrics.read_to_string(&mut response).unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("dns_queries_total{protocol=\"tcp\",type=\"A\"} 1\n"));
+        assert!(response.contains("dns_responses_total{protocol=\"tcp\",rcode=\"NoError\"} 1\n"));
+        assert!(response.contains("dns_zone_queries_total{zone=\"example.com.\"} 2\n"));
+        assert!(response
+            .contains("dns_zone_transfers_total{zone=\"example.com.\",result=\"refused\"} 1\n"));
+        assert!(response.contains("dns_connections_total{protocol=\"tcp\"} 1\n"));
+
+        // requests with too many headers are not answered
+        let mut metrics = std::net::TcpStream::connect("127.0.0.1:24153").unwrap();
+        let headers = "X-Test: header\r\n".repeat(200);
+        metrics
+            .write_all(format!("GET /metrics HTTP/1.1\r\n{}\r\n", headers).as_bytes())
+            .unwrap();
+        let mut response = String::new();
+        metrics.read_to_string(&mut response).ok();
+        assert!(response.is_empty());
+    })
+}
+
+#[test]
+fn test_query_log() {
+    named_test_harness("query_log.toml", |port, _, _| {
+        let mut io_loop = Runtime::new().unwrap();
+        let addr: SocketAddr = SocketAddr::new(Ipv4Addr::new(127, 0, 0, 1).into(), port);
+        let (stream, sender) = TcpClientStream::new(addr);
+        let (bg, mut client) = ClientFuture::new(Box::new(stream), sender, None);
+
+        io_loop.spawn(bg);
+        query_a(&mut io_loop, &mut client);
+
+        // the log is written on a separate thread
+        let path = "../../target/named_query_log_test.json";
+        let logged = (0..50).any(|_| {
+            let log = std::fs::read_to_string(path).unwrap_or_default();
+            if log.contains("\"name\":\"www.example.com.\",\"type\":\"A\"")
+                && log.contains("\"protocol\":\"tcp\"")
+            {
+                true
+            } else {
+                std::thread::sleep(std::time::Duration::from_millis(100));
+                false
+            }
+        });
+        assert!(logged, "query was not logged to {}", path);
+    })
+}
+
+#[test]
+fn test_recursion_from_root_hints() {
+    named_test_harness("recursion_hierarchy.toml", |hierarchy_port, _, _| {
+        // all the name servers of the hierarchy listen on the port chosen by the harness
+        let config = format!(
+            "allow_recursion = [\"127.0.0.0/8\"]
+
+[[zones]]
+zone = \".\"
+zone_type = \"Hint\"
+stores = {{ type = \"hint\", root_hints_path = \"root.hints\", port = {} }}
+",
+            hierarchy_port
+        );
+        std::fs::write("../../target/named_recursor_test.toml", config)
+            .expect("failed to write recursor config");
+
+        // relative to the named_test_configs directory
+        named_test_harness("../../../../target/named_recursor_test.toml", |port, _, _| {
+            let mut io_loop = Runtime::new().unwrap();
+            let addr: SocketAddr = SocketAddr::new(Ipv4Addr::new(127, 0, 0, 1).into(), port);
+            let (stream, sender) = TcpClientStream::new(addr);
+            let (bg, mut client) = ClientFuture::new(Box::new(stream), sender, None);
+
+            io_loop.spawn(bg);
+
+            // root -> com -> example.com
+            query_a(&mut io_loop, &mut client);
+        })
+    })
+}
+
+/// Asserts that the authority section of the negative response is the SOA record of example.com, RFC 2308
+fn assert_soa(response: &Message) {
+    assert_eq!(response.name_servers().len(), 1);
+    assert_eq!(response.name_servers()[0].rr_type(), RecordType::SOA);
+    assert_eq!(
+        *response.name_servers()[0].name(),
+        Name::from_str("example.com.").unwrap()
+    );
+}
+
+#[test]
+fn test_forward_response_codes() {
+    named_test_harness("recursion_hierarchy.toml", |upstream_port, _, _| {
+        // the example.com zone of the hierarchy is served on 127.0.0.4
+        let config = format!(
+            "allow_recursion = [\"127.0.0.0/8\"]
+control_socket = \"../../target/named_forward_test.sock\"
+
+[[zones]]
+zone = \".\"
+zone_type = \"Forward\"
+stores = {{ type = \"forward\", name_servers = [\"127.0.0.4\"], port = {} }}
+",
+            upstream_port
+        );
+        std::fs::write("../../target/named_forward_test.toml", config)
+            .expect("failed to write forwarder config");
+
+        // relative to the named_test_configs directory
+        let forward_config = "../../../../target/named_forward_test.toml";
+        named_test_harness(forward_config, |port, _, _| {
+            let mut io_loop = Runtime::new().unwrap();
+            let addr: SocketAddr = SocketAddr::new(Ipv4Addr::new(127, 0, 0, 1).into(), port);
+            let (stream, sender) = TcpClientStream::new(addr);
+            let (bg, mut client) = ClientFuture::new(Box::new(stream), sender, None);
+
+            io_loop.spawn(bg);
+
+            query_a(&mut io_loop, &mut client);
+
+            let name = Name::from_str("nonexistent.example.com.").unwrap();
+            let response = query_message(&mut io_loop, &mut client, name, RecordType::A);
+            assert_eq!(response.response_code(), ResponseCode::NXDomain);
+            assert!(response.answers().is_empty());
+            assert_soa(&response);
+
+            let www = Name::from_str("www.example.com.").unwrap();
+            let response = query_message(&mut io_loop, &mut client, www, RecordType::MX);
+            assert_eq!(response.response_code(), ResponseCode::NoError);
+            assert!(response.answers().is_empty());
+            assert_soa(&response);
+
+            #[cfg(unix)]
+            {
+                use std::path::Path;
+
+                use trust_dns_server::server::{send_command, ControlCommand};
+
+                let socket = Path::new("../../target/named_forward_test.sock");
+                let flushed = send_command(socket, &ControlCommand::Flush(None))
+                    .expect("could not send control command")
+                    .expect("flush failed");
+                assert_eq!(flushed, ". in view default: flushed\n");
+            }
+        })
+    })
+}
+
 // TODO: this is commented out b/c at least on macOS, ipv4 will route properly to ipv6 only
 //  listeners over the [::ffff:127.0.0.1] interface
 //
@@ -131,6 +131,7 @@ impl Stream for TestClientStream {
                 let request = Request {
                     message,
                     src: src_addr,
+                    dst: SocketAddr::from(([127, 0, 0, 1], 53)),
                 };
 
                 let response_handler = TestResponseHandler::new();
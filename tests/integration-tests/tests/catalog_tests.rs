@@ -1,16 +1,26 @@
+extern crate futures;
+extern crate tokio;
 extern crate trust_dns;
 extern crate trust_dns_integration;
+extern crate trust_dns_resolver;
 extern crate trust_dns_server;
 
 use std::collections::*;
 use std::net::*;
 
+use futures::future;
+use tokio::runtime::current_thread::Runtime;
+
 use trust_dns::op::*;
+use trust_dns::rr::dnssec::SupportedAlgorithms;
+use trust_dns::rr::rdata::opt::{ClientSubnet, EdnsOption};
 use trust_dns::rr::rdata::*;
 use trust_dns::rr::*;
 use trust_dns::serialize::binary::{BinDecodable, BinEncodable};
+use trust_dns_resolver::recursor::RecursorFuture;
 
 use trust_dns_server::authority::*;
+use trust_dns_server::server::{Request, RequestHandler};
 use trust_dns_server::store::sqlite::SqliteAuthority;
 
 use trust_dns_integration::authority::create_example;
@@ -410,3 +420,239 @@ fn test_axfr_refused() {
 
 #[test]
 fn test_truncated_returns_records() {}
+
+#[test]
+fn test_catalog_lookup_client_subnet() {
+    let example = create_example();
+    let origin = example.origin().clone();
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), Box::new(example));
+
+    let mut question: Message = Message::new();
+
+    let mut query: Query = Query::new();
+    query.set_name(origin.clone().into());
+    question.add_query(query);
+
+    let client_subnet = ClientSubnet::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)), 24, 0);
+    let mut edns = Edns::new();
+    edns.set_option(EdnsOption::Subnet(client_subnet));
+    question.set_edns(edns);
+
+    let question_bytes = question.to_bytes().unwrap();
+    let question_req = MessageRequest::from_bytes(&question_bytes).unwrap();
+    assert_eq!(question_req.client_subnet(), Some(&client_subnet));
+
+    let response_handler = TestResponseHandler::new();
+    catalog
+        .lookup(&question_req, Some(Edns::new()), response_handler.clone())
+        .unwrap();
+    let result = response_handler.into_message();
+
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+    assert!(!result.answers().is_empty());
+
+    // the answer of the zone is the same for all clients
+    assert_eq!(
+        result.edns().and_then(Edns::client_subnet),
+        Some(&client_subnet.with_scope_prefix(0))
+    );
+}
+
+#[test]
+fn test_catalog_referral() {
+    let mut example = create_example();
+    let origin = example.origin().clone();
+    assert!(!example.has_delegations());
+
+    let sub = Name::parse("sub.example.com.", None).unwrap();
+    let sub_ns = Name::parse("ns.sub.example.com.", None).unwrap();
+    example.upsert(
+        Record::from_rdata(
+            sub.clone(),
+            86400,
+            RecordType::NS,
+            RData::NS(sub_ns.clone()),
+        ),
+        0,
+    );
+    example.upsert(
+        Record::from_rdata(
+            sub_ns.clone(),
+            86400,
+            RecordType::A,
+            RData::A(Ipv4Addr::new(192, 0, 2, 53)),
+        ),
+        0,
+    );
+    assert!(example.has_delegations());
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), Box::new(example));
+
+    let lookup = |catalog: &Catalog, name: &str| {
+        let mut question: Message = Message::new();
+        question.add_query(Query::query(
+            Name::parse(name, None).unwrap(),
+            RecordType::A,
+        ));
+
+        let question_bytes = question.to_bytes().unwrap();
+        let question_req = MessageRequest::from_bytes(&question_bytes).unwrap();
+
+        let response_handler = TestResponseHandler::new();
+        catalog
+            .lookup(&question_req, None, response_handler.clone())
+            .unwrap();
+        response_handler.into_message()
+    };
+
+    // without referrals, the zone cut is ignored and the names are answered by the zone
+    let result = lookup(&catalog, "www.sub.example.com.");
+    assert_eq!(result.response_code(), ResponseCode::NXDomain);
+    assert!(result.authoritative());
+    let result = lookup(&catalog, "ns.sub.example.com.");
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+    assert!(result.authoritative());
+    assert_eq!(
+        result.answers()[0].rdata(),
+        &RData::A(Ipv4Addr::new(192, 0, 2, 53))
+    );
+
+    // names below the zone cut are referred to the name servers of the child zone
+    catalog.set_referrals(true);
+    for name in &["www.sub.example.com.", "ns.sub.example.com."] {
+        let result = lookup(&catalog, name);
+        assert_eq!(result.response_code(), ResponseCode::NoError);
+        assert!(!result.authoritative());
+        assert!(result.answers().is_empty());
+        assert_eq!(result.name_servers().len(), 1);
+        assert_eq!(result.name_servers()[0].name(), &sub);
+        assert_eq!(result.name_servers()[0].rdata(), &RData::NS(sub_ns.clone()));
+        assert_eq!(result.additionals().len(), 1);
+        assert_eq!(
+            result.additionals()[0].rdata(),
+            &RData::A(Ipv4Addr::new(192, 0, 2, 53))
+        );
+    }
+
+    // names above the zone cut are answered by the zone
+    let result = lookup(&catalog, "www.example.com.");
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+    assert!(result.authoritative());
+    assert!(!result.answers().is_empty());
+}
+
+/// A zone which resolves all recursive queries to 192.0.2.1, and refuses all others
+struct ResolvingAuthority {
+    origin: LowerName,
+}
+
+impl Authority for ResolvingAuthority {
+    fn zone_type(&self) -> ZoneType {
+        ZoneType::Forward
+    }
+
+    fn is_axfr_allowed(&self) -> bool {
+        false
+    }
+
+    fn update(&mut self, _update: &MessageRequest) -> UpdateResult<bool> {
+        Err(ResponseCode::NotImp)
+    }
+
+    fn origin(&self) -> &LowerName {
+        &self.origin
+    }
+
+    fn lookup(
+        &self,
+        _name: &LowerName,
+        _rtype: RecordType,
+        _is_secure: bool,
+        _supported_algorithms: SupportedAlgorithms,
+    ) -> AuthLookup {
+        AuthLookup::Refused
+    }
+
+    fn get_nsec_records(
+        &self,
+        _name: &LowerName,
+        _is_secure: bool,
+        _supported_algorithms: SupportedAlgorithms,
+    ) -> AuthLookup {
+        AuthLookup::default()
+    }
+
+    fn resolve(&self, query: &Query) -> Option<RecursorFuture> {
+        let mut message = Message::new();
+        message.add_query(query.clone());
+        message.add_answer(Record::from_rdata(
+            query.name().clone(),
+            300,
+            RecordType::A,
+            RData::A(Ipv4Addr::new(192, 0, 2, 1)),
+        ));
+
+        Some(Box::new(future::ok(message)))
+    }
+}
+
+fn resolve(catalog: &Catalog, src: IpAddr) -> Message {
+    let mut question: Message = Message::new();
+    question
+        .add_query(Query::query(
+            Name::parse("www.example.com.", None).unwrap(),
+            RecordType::A,
+        ))
+        .set_recursion_desired(true);
+
+    let question_bytes = question.to_bytes().unwrap();
+    let request = Request {
+        message: MessageRequest::from_bytes(&question_bytes).unwrap(),
+        src: SocketAddr::new(src, 5353),
+        dst: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 53),
+    };
+
+    // the resolution is spawned, run it to completion
+    let response_handler = TestResponseHandler::new();
+    let mut runtime = Runtime::new().unwrap();
+    runtime
+        .block_on(future::lazy(|| {
+            catalog.handle_request(&request, response_handler.clone())
+        }))
+        .unwrap();
+    runtime.run().unwrap();
+
+    response_handler.into_message()
+}
+
+#[test]
+fn test_catalog_recursion_allowed_clients() {
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(
+        LowerName::from(Name::root()),
+        Box::new(ResolvingAuthority {
+            origin: LowerName::from(Name::root()),
+        }),
+    );
+    catalog.set_allow_recursion(vec!["127.0.0.0/8".parse().unwrap()]);
+
+    let result = resolve(&catalog, IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)));
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+    assert!(result.recursion_available());
+    assert_eq!(
+        result.answers()[0].rdata(),
+        &RData::A(Ipv4Addr::new(192, 0, 2, 1))
+    );
+
+    let result = resolve(&catalog, IpAddr::V4(Ipv4Addr::new(192, 0, 2, 10)));
+    assert_eq!(result.response_code(), ResponseCode::Refused);
+    assert!(result.answers().is_empty());
+
+    // with an empty allow-list, recursion is disabled
+    catalog.set_allow_recursion(vec![]);
+    let result = resolve(&catalog, IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)));
+    assert_eq!(result.response_code(), ResponseCode::Refused);
+}
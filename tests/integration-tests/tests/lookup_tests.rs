@@ -49,6 +49,7 @@ fn test_lookup() {
         RecordType::A,
         Default::default(),
         CachingClient::new(0, client),
+        None,
     );
     let lookup = io_loop.block_on(lookup).unwrap();
 
@@ -196,6 +197,7 @@ fn test_mock_lookup() {
         RecordType::A,
         Default::default(),
         CachingClient::new(0, client),
+        None,
     );
 
     let mut io_loop = Runtime::new().unwrap();
@@ -226,6 +228,7 @@ fn test_cname_lookup() {
         RecordType::A,
         Default::default(),
         CachingClient::new(0, client),
+        None,
     );
 
     let mut io_loop = Runtime::new().unwrap();
@@ -261,6 +264,7 @@ fn test_chained_cname_lookup() {
         RecordType::A,
         Default::default(),
         CachingClient::new(0, client),
+        None,
     );
 
     let mut io_loop = Runtime::new().unwrap();
@@ -348,6 +352,7 @@ fn test_max_chained_lookup_depth() {
         RecordType::A,
         Default::default(),
         client.clone(),
+        None,
     );
 
     let mut io_loop = Runtime::new().unwrap();
@@ -362,6 +367,7 @@ fn test_max_chained_lookup_depth() {
         RecordType::A,
         Default::default(),
         client,
+        None,
     );
 
     println!("performing followup resolve, should work");
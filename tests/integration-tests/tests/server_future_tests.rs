@@ -282,7 +282,9 @@ fn server_thread_udp(udp_socket: UdpSocket, server_continue: Arc<AtomicBool>) {
     let server = ServerFuture::new(catalog);
     io_loop
         .block_on::<Box<Future<Item = (), Error = ()> + Send>>(Box::new(future::lazy(|| {
-            server.register_socket(udp_socket);
+            server
+                .register_socket(udp_socket)
+                .expect("could not register UDP socket");
             future::ok(())
         }))).unwrap();
 
@@ -0,0 +1,199 @@
+extern crate trust_dns;
+extern crate trust_dns_integration;
+extern crate trust_dns_server;
+
+use std::net::*;
+
+use trust_dns::op::*;
+use trust_dns::rr::*;
+use trust_dns::serialize::binary::{BinDecodable, BinEncodable};
+
+use trust_dns_server::authority::*;
+use trust_dns_server::server::{Request, RequestHandler};
+
+use trust_dns_integration::authority::create_example;
+use trust_dns_integration::*;
+
+fn create_views() -> ViewCatalog {
+    let example = create_example();
+    let origin = example.origin().clone();
+
+    let mut internal_catalog = Catalog::new();
+    internal_catalog.upsert(origin, Box::new(example));
+
+    let mut internal = View::new("internal".to_string(), internal_catalog);
+    internal.set_match_clients(vec!["10.0.0.0/8".parse().unwrap()]);
+
+    let mut external = View::new("external".to_string(), Catalog::new());
+    external.set_match_destinations(vec!["192.0.2.53".parse().unwrap()]);
+
+    let mut views = ViewCatalog::new();
+    views.push(internal);
+    views.push(external);
+    views
+}
+
+fn question() -> Message {
+    let mut question: Message = Message::new();
+
+    let mut query: Query = Query::new();
+    query.set_name(Name::from_ascii("www.example.com.").unwrap());
+    question.add_query(query);
+    question
+}
+
+fn query_from(views: &ViewCatalog, src: IpAddr, dst: IpAddr) -> Message {
+    request_from(views, &question().to_bytes().unwrap(), src, dst)
+}
+
+fn request_from(views: &ViewCatalog, question_bytes: &[u8], src: IpAddr, dst: IpAddr) -> Message {
+    let request = Request {
+        message: MessageRequest::from_bytes(question_bytes).unwrap(),
+        src: SocketAddr::new(src, 5353),
+        dst: SocketAddr::new(dst, 53),
+    };
+
+    let response_handler = TestResponseHandler::new();
+    views
+        .handle_request(&request, response_handler.clone())
+        .unwrap();
+    response_handler.into_message()
+}
+
+/// Signs the message with a TSIG record of the key, RFC 8945
+#[cfg(feature = "dnssec")]
+fn sign(mut message: Message, key_name: &Name, secret: &[u8]) -> Vec<u8> {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    use trust_dns::rr::rdata::NULL;
+    use trust_dns::serialize::binary::BinEncoder;
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let algorithm = Name::from_ascii("hmac-sha256.").unwrap();
+
+    let mut variables = Vec::new();
+    {
+        let mut encoder = BinEncoder::new(&mut variables);
+        key_name.emit_as_canonical(&mut encoder, true).unwrap();
+        encoder.emit_u16(DNSClass::ANY.into()).unwrap();
+        encoder.emit_u32(0).unwrap();
+        algorithm.emit_as_canonical(&mut encoder, true).unwrap();
+        encoder.emit_u16((now >> 32) as u16).unwrap();
+        encoder.emit_u32(now as u32).unwrap();
+        encoder.emit_u16(300).unwrap();
+        encoder.emit_u16(0).unwrap();
+        encoder.emit_u16(0).unwrap();
+    }
+    let mac = TsigAlgorithm::HmacSha256
+        .mac(secret, &[&message.to_bytes().unwrap(), &variables])
+        .unwrap();
+
+    let mut rdata = Vec::new();
+    {
+        let mut encoder = BinEncoder::new(&mut rdata);
+        algorithm.emit_as_canonical(&mut encoder, true).unwrap();
+        encoder.emit_u16((now >> 32) as u16).unwrap();
+        encoder.emit_u32(now as u32).unwrap();
+        encoder.emit_u16(300).unwrap();
+        encoder.emit_u16(mac.len() as u16).unwrap();
+        encoder.emit_vec(&mac).unwrap();
+        encoder.emit_u16(message.id()).unwrap();
+        encoder.emit_u16(0).unwrap();
+        encoder.emit_u16(0).unwrap();
+    }
+
+    let mut tsig = Record::with(key_name.clone(), RecordType::Unknown(250), 0);
+    tsig.set_dns_class(DNSClass::ANY).set_rdata(RData::Unknown {
+        code: 250,
+        rdata: NULL::with(rdata),
+    });
+    message.add_additional(tsig);
+    message.to_bytes().unwrap()
+}
+
+#[test]
+fn test_view_by_client() {
+    let views = create_views();
+
+    let result = query_from(
+        &views,
+        Ipv4Addr::new(10, 1, 1, 1).into(),
+        Ipv4Addr::new(192, 0, 2, 53).into(),
+    );
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+    assert_eq!(
+        result.answers().first().unwrap().rdata(),
+        &RData::A(Ipv4Addr::new(93, 184, 216, 34))
+    );
+}
+
+#[test]
+fn test_view_by_destination() {
+    let views = create_views();
+
+    // the external view has no zones
+    let result = query_from(
+        &views,
+        Ipv4Addr::new(203, 0, 113, 1).into(),
+        Ipv4Addr::new(192, 0, 2, 53).into(),
+    );
+    assert_eq!(result.response_code(), ResponseCode::NXDomain);
+    assert!(result.answers().is_empty());
+}
+
+#[test]
+fn test_view_no_match() {
+    let views = create_views();
+
+    let result = query_from(
+        &views,
+        Ipv4Addr::new(203, 0, 113, 1).into(),
+        Ipv4Addr::new(198, 51, 100, 53).into(),
+    );
+    assert_eq!(result.response_code(), ResponseCode::Refused);
+
+    // received on a socket bound to the unspecified address
+    let result = query_from(
+        &views,
+        Ipv4Addr::new(203, 0, 113, 1).into(),
+        Ipv4Addr::UNSPECIFIED.into(),
+    );
+    assert_eq!(result.response_code(), ResponseCode::Refused);
+}
+
+#[cfg(feature = "dnssec")]
+#[test]
+fn test_view_by_key() {
+    let key_name = Name::from_ascii("internal-key.").unwrap();
+    let secret = b"0123456789abcdef0123456789abcdef";
+
+    let mut views = create_views();
+    views.views_mut()[0]
+        .set_match_clients(vec![])
+        .set_match_keys(vec![TsigKey::new(
+            key_name.clone(),
+            TsigAlgorithm::HmacSha256,
+            secret.to_vec(),
+        )]);
+
+    let src = Ipv4Addr::new(203, 0, 113, 1).into();
+    let dst = Ipv4Addr::new(192, 0, 2, 53).into();
+
+    let signed = sign(question(), &key_name, secret);
+    let result = request_from(&views, &signed, src, dst);
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+    assert_eq!(
+        result.answers().first().unwrap().rdata(),
+        &RData::A(Ipv4Addr::new(93, 184, 216, 34))
+    );
+
+    // the external view has no zones
+    let result = query_from(&views, src, dst);
+    assert_eq!(result.response_code(), ResponseCode::NXDomain);
+
+    let forged = sign(question(), &key_name, b"another secret");
+    let result = request_from(&views, &forged, src, dst);
+    assert_eq!(result.response_code(), ResponseCode::NXDomain);
+}